@@ -0,0 +1,40 @@
+use std::net::SocketAddr;
+use std::path::Path;
+use dolls_network::prelude::{hex_dump, replay_capture, Capture, ClientboundPacketType, FrameDirection, PacketType};
+
+/// `capture print`, one line per packet followed by its payload.
+pub(crate) fn print_capture(path: &Path, limit: usize) -> anyhow::Result<()> {
+    let capture = Capture::open(path)?;
+    println!("Connection {} from {}, started at {} ms, {} packets", capture.connection_id, capture.peer_addr, capture.started_at, capture.packets.len());
+    for captured in &capture.packets {
+        let (state, packet_id) = (captured.state, captured.packet.packet_id);
+        let name = match captured.direction {
+            FrameDirection::Inbound => PacketType::from_parts(state, packet_id).map(PacketType::name),
+            FrameDirection::Outbound => ClientboundPacketType::from_parts(state, packet_id).map(ClientboundPacketType::name),
+        };
+        println!("{:>10.3} ms {:<3} {:?} {} (0x{:02X}) len={}", captured.elapsed.as_secs_f64() * 1000.0, captured.direction, state,
+            name.unwrap_or("Unknown"), packet_id, captured.packet.payload.len());
+        if !captured.packet.payload.is_empty() && limit > 0 {
+            println!("{}", hex_dump(&captured.packet.payload, limit));
+        }
+    }
+    Ok(())
+}
+
+/// `capture replay`, fails if the server answered differently.
+pub(crate) async fn replay(path: &Path, address: SocketAddr) -> anyhow::Result<()> {
+    let capture = Capture::open(path)?;
+    let report = replay_capture(&capture, address).await?;
+    println!("Sent {} packets, received {}", report.sent, report.received);
+    for mismatch in &report.mismatches {
+        let name = |packet_id| ClientboundPacketType::from_parts(mismatch.state, packet_id).map_or("Unknown", ClientboundPacketType::name);
+        match mismatch.received {
+            Some(received) => println!("Packet {}: expected {} in {:?}, received {}", mismatch.index, name(mismatch.expected), mismatch.state, name(received)),
+            None => println!("Packet {}: expected {} in {:?}, received nothing", mismatch.index, name(mismatch.expected), mismatch.state),
+        }
+    }
+    if !report.mismatches.is_empty() {
+        anyhow::bail!("{} packets differ from the capture", report.mismatches.len());
+    }
+    Ok(())
+}
@@ -0,0 +1,147 @@
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use anyhow::{anyhow, Context};
+use clap::{Parser, Subcommand, ValueEnum};
+use dolls_config::{ServerConfig, DEFAULT_CONFIG_PATH};
+use dolls_network::prelude::PACKET_DUMP_LIMIT;
+
+/// Command line options, anything given here overrides the config file and `DOLLS_*` variables.
+///
+/// Every config key can also be set as `DOLLS_<SECTION>_<KEY>`, e.g. `DOLLS_SERVER_MAX_PLAYERS=50`.
+/// Later sources win: defaults, the config file, environment variables, `--set`, then the dedicated flags.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub(crate) struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Path of the server configuration file.
+    #[arg(long, default_value = DEFAULT_CONFIG_PATH)]
+    pub config: PathBuf,
+
+    /// Address to listen on.
+    #[arg(long)]
+    pub bind: Option<IpAddr>,
+
+    /// Port to listen on.
+    #[arg(long)]
+    pub port: Option<u16>,
+
+    /// Whether players are authenticated against Mojang.
+    #[arg(long)]
+    pub online_mode: Option<bool>,
+
+    /// Overrides any config key, e.g. `--set world.level-name=lobby`. May be repeated.
+    #[arg(long = "set", value_name = "SECTION.KEY=VALUE")]
+    pub overrides: Vec<String>,
+
+    /// Minimum level of log messages to print.
+    #[arg(long, value_enum, default_value_t = LogLevel::All)]
+    pub log_level: LogLevel,
+}
+
+#[derive(Debug, Subcommand)]
+pub(crate) enum Command {
+    /// Inspects the configuration instead of starting the server.
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    /// Inspects or replays packet captures written to `network.capture-directory`.
+    #[command(subcommand)]
+    Capture(CaptureCommand),
+    /// Sits between a client and a server in offline mode instead of starting the server, logging the
+    /// packets of both sides.
+    Proxy {
+        /// `host:port` of the server to forward to.
+        upstream: String,
+        #[arg(long, default_value = "0.0.0.0:25566")]
+        listen: SocketAddr,
+        /// Logs a hex dump of every payload.
+        #[arg(long)]
+        dump: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub(crate) enum ConfigCommand {
+    /// Prints the effective configuration after applying every override.
+    Print,
+}
+
+#[derive(Debug, Subcommand)]
+pub(crate) enum CaptureCommand {
+    /// Lists the packets of a capture with their payload.
+    Print {
+        file: PathBuf,
+        /// Payload bytes shown per packet.
+        #[arg(long, default_value_t = PACKET_DUMP_LIMIT)]
+        limit: usize,
+    },
+    /// Sends the client's packets of a capture to a server and compares the answers.
+    Replay {
+        file: PathBuf,
+        #[arg(long, default_value = "127.0.0.1:25565")]
+        address: SocketAddr,
+    },
+}
+
+#[derive(Debug, Copy, Clone, ValueEnum)]
+pub(crate) enum LogLevel {
+    All,
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Critical,
+    Off,
+}
+
+impl Cli {
+    pub fn apply(&self, config: &mut ServerConfig) -> anyhow::Result<()> {
+        for assignment in &self.overrides {
+            let (key, value) = assignment.split_once('=').ok_or_else(|| anyhow!("Expected KEY=VALUE, got {}", assignment))?;
+            config.set(key.trim(), value).with_context(|| format!("Failed to apply --set {}", assignment))?;
+        }
+        if let Some(bind) = self.bind {
+            config.network.bind_address = bind;
+        }
+        if let Some(port) = self.port {
+            config.network.port = port;
+        }
+        if let Some(online_mode) = self.online_mode {
+            config.server.online_mode = online_mode;
+        }
+        Ok(())
+    }
+}
+
+impl From<LogLevel> for spdlog::LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        use spdlog::{Level, LevelFilter};
+
+        match level {
+            LogLevel::All => LevelFilter::All,
+            LogLevel::Trace => LevelFilter::MoreSevereEqual(Level::Trace),
+            LogLevel::Debug => LevelFilter::MoreSevereEqual(Level::Debug),
+            LogLevel::Info => LevelFilter::MoreSevereEqual(Level::Info),
+            LogLevel::Warn => LevelFilter::MoreSevereEqual(Level::Warn),
+            LogLevel::Error => LevelFilter::MoreSevereEqual(Level::Error),
+            LogLevel::Critical => LevelFilter::MoreSevereEqual(Level::Critical),
+            LogLevel::Off => LevelFilter::Off,
+        }
+    }
+}
+
+/// `log` has no critical level, critical messages of the libraries are logged as errors.
+impl From<LogLevel> for log::LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::All | LogLevel::Trace => log::LevelFilter::Trace,
+            LogLevel::Debug => log::LevelFilter::Debug,
+            LogLevel::Info => log::LevelFilter::Info,
+            LogLevel::Warn => log::LevelFilter::Warn,
+            LogLevel::Error | LogLevel::Critical => log::LevelFilter::Error,
+            LogLevel::Off => log::LevelFilter::Off,
+        }
+    }
+}
@@ -0,0 +1,21 @@
+use std::sync::Arc;
+use async_std::io::{stdin, BufReader};
+use async_std::prelude::StreamExt;
+use async_std::io::prelude::BufReadExt;
+use spdlog::warn;
+use dolls_commands::prelude::{execute_command, CommandSource};
+use dolls_network::prelude::DollNetworkServer;
+
+/// Reads operator commands from stdin until it is closed.
+pub(crate) async fn run_console(network_server: Arc<DollNetworkServer>) {
+    let source = CommandSource::console(network_server.connections().clone());
+    let mut lines = BufReader::new(stdin()).lines();
+    while let Some(Ok(line)) = lines.next().await {
+        let line = line.trim();
+        if !line.is_empty() {
+            if let Err(err) = execute_command(&source, line) {
+                warn!("{}", err);
+            }
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use log::{Level, Metadata, Record};
+use crate::cli::LogLevel;
+
+/// Forwards the records of the libraries, which log through the `log` facade, to the spdlog logger.
+/// Other crates only get through with warnings and errors.
+struct LibraryLogger;
+
+impl log::Log for LibraryLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.target().starts_with("dolls") || metadata.level() <= Level::Warn
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        match record.level() {
+            Level::Error => spdlog::error!("{}", record.args()),
+            Level::Warn => spdlog::warn!("{}", record.args()),
+            Level::Info => spdlog::info!("{}", record.args()),
+            Level::Debug => spdlog::debug!("{}", record.args()),
+            Level::Trace => spdlog::trace!("{}", record.args()),
+        }
+    }
+
+    fn flush(&self) {
+        spdlog::default_logger().flush();
+    }
+}
+
+pub(crate) fn init_logger(level: LogLevel) {
+    spdlog::default_logger().set_level_filter(level.into());
+    if log::set_logger(&LibraryLogger).is_ok() {
+        log::set_max_level(level.into());
+    }
+}
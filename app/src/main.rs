@@ -1,57 +1,352 @@
-#![feature(future_join)]
+mod capture;
+mod cli;
+mod console;
+mod logger;
+mod metrics;
+mod rcon;
+mod service;
+#[cfg(feature = "otel")]
+mod telemetry;
 
-use std::future::join;
-use std::net::{IpAddr, Ipv4Addr};
-use std::sync::Arc;
-use async_std::sync::Mutex;
-use async_std::task::{block_on};
-use spdlog::info;
-use dolls_network::prelude::DollNetworkServer;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use anyhow::Context;
+use async_std::task::block_on;
+use spdlog::{critical, error, info, warn};
+use clap::Parser;
+use dolls_commands::builtin::register_builtin_commands;
+use dolls_commands::prelude::enable_chat_commands;
+use dolls_config::ServerConfig;
+use dolls_core::advancement::{advancements, AdvancementRegistry};
+use dolls_core::item::{items, ItemRegistry};
+use dolls_core::recipe::{recipes, RecipeRegistry};
+use dolls_core::statistic::{statistics, StatisticRegistry};
+use dolls_network::prelude::{announce_advancements, banned_ips, banned_players, choose_world_spawn, favicon, load_favicon, run_proxy, run_query, ops, whitelist, save_all_entities, save_all_health, save_all_statistics, set_chat_formatter, start_ai_debug, start_entity_tracker, start_health, start_heartbeat, start_lan_broadcast, start_world_time, BanList, DollNetworkServer, OpsList, ProxyOptions, ResourcePack, TemplateChatFormatter, Whitelist, BANNED_IPS_FILE, BANNED_PLAYERS_FILE, OPS_FILE, SERVER_ICON_FILE, WHITELIST_FILE};
+use dolls_plugin::prelude::{PluginManager, PLUGINS_DIRECTORY};
+use dolls_tick::prelude::{scheduler, TickLoop, TICKS_PER_SECOND};
+use dolls_world::level::{level, LevelData};
+use dolls_world::prelude::{advancement_data, blocks, player_data, scoreboard, stats_data, AdvancementStorage, Scoreboard, StatsStorage, BlockRegistry, Dimension, PlayerDataStorage, RegionStorage, World};
+use crate::cli::{CaptureCommand, Cli, Command, ConfigCommand};
+
+/// Writes `level.dat`, the scoreboard and every chunk changed since the last save.
+fn save_level(level_name: &str) {
+    let path = Path::new(level_name).join("level.dat");
+    if let Err(err) = level().read().unwrap().save(&path) {
+        error!("Failed to save {}: {:#}", path.display(), err);
+    }
+    let path = Path::new(level_name).join("data").join("scoreboard.dat");
+    if let Err(err) = scoreboard().read().unwrap().save(&path) {
+        error!("Failed to save {}: {:#}", path.display(), err);
+    }
+    save_all_entities();
+    for dimension in Dimension::ALL {
+        match dimension.world().write().unwrap().flush() {
+            Ok(0) => {}
+            Ok(saved) => info!("Saved {} chunks of {}.", saved, dimension.name()),
+            Err(err) => error!("Failed to save chunks of {}: {:#}", dimension.name(), err),
+        }
+    }
+}
+
+/// Replaces the block and item registries with those of the data generator reports in `directory`.
+fn load_reports(directory: &Path) -> anyhow::Result<()> {
+    let read = |name: &str| {
+        let path = directory.join(name);
+        std::fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))
+    };
+    let mut block_registry = BlockRegistry::from_report(&read("blocks.json")?)?;
+    let registries = read("registries.json")?;
+    let mut item_registry = ItemRegistry::from_report(&registries)?;
+    let statistic_registry = StatisticRegistry::from_report(&registries)?;
+    if directory.join("items.json").exists() {
+        item_registry.load_components(&read("items.json")?)?;
+    }
+    // Block items share the name of the block they place.
+    for id in 1..item_registry.len() as i32 {
+        let state = item_registry.name(id).and_then(|name| block_registry.default_state(name));
+        if let Some(state) = state {
+            block_registry.register_item(id, state);
+        }
+    }
+    info!("Loaded {} block states and {} items from {}.", block_registry.state_count(), item_registry.len(), directory.display());
+    *blocks().write().unwrap() = block_registry;
+    *items().write().unwrap() = item_registry;
+    *statistics().write().unwrap() = statistic_registry;
+    Ok(())
+}
 
 #[derive(Debug)]
 pub(crate) struct App {
-    network_server: Arc<Mutex<DollNetworkServer>>,
+    network_server: Arc<DollNetworkServer>,
+    plugins: Mutex<PluginManager>,
 }
 
-// Tell compiler this is none of its business.
-unsafe impl Send for App {}
-unsafe impl Sync for App {}
-
 impl App {
-    pub fn new() -> Self {
+    pub fn new(config: ServerConfig) -> Self {
+        let network_server = Arc::new(DollNetworkServer::from_config(Arc::new(config)));
+        let mut plugins = PluginManager::new(network_server.clone());
+        plugins.add_directory(PLUGINS_DIRECTORY);
         Self {
-            network_server: Arc::new(Mutex::new(DollNetworkServer::new(
-                IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
-                25565
-            ))),
+            plugins: Mutex::new(plugins),
+            network_server,
         }
     }
 
-    pub async fn start(&mut self) {
+    /// Runs until the server is shut down, the only place the runtime is entered is `main`.
+    pub async fn run(&self) -> anyhow::Result<()> {
+        self.plugins.lock().unwrap().load_all();
+        self.network_server.bind().await?;
+        register_builtin_commands(&self.network_server);
+        enable_chat_commands();
+        announce_advancements(self.network_server.connections().clone());
+        if let Some(chat_format) = &self.network_server.config().server.chat_format {
+            set_chat_formatter(Arc::new(TemplateChatFormatter::new(chat_format.clone())));
+        }
+
         let network_handle = {
             let network_server = self.network_server.clone();
             async_std::task::Builder::new()
                 .name("Network TCP Listener".to_string())
                 .spawn( async move {
                     info!("Network service started.");
-                    network_server.lock().await.accept().await;
+                    if let Err(err) = network_server.accept().await {
+                        error!("Network service failed: {:#}", err);
+                    }
                 }).unwrap()
         };
 
-        block_on(async {
-            join!(network_handle).await;
-        });
+        let tick_loop = TickLoop::start();
+        let entity_tracker = start_entity_tracker(self.network_server.connections().clone());
+        let world_time = start_world_time(self.network_server.connections().clone());
+        let health = start_health(self.network_server.connections().clone());
+        let ai_debug = start_ai_debug(self.network_server.connections().clone());
+        let world_config = &self.network_server.config().world;
+        let autosave = match world_config.autosave_interval {
+            0 => None,
+            seconds => {
+                let level_name = world_config.level_name.clone();
+                let connections = self.network_server.connections().clone();
+                let period = seconds * TICKS_PER_SECOND as u64;
+                Some(scheduler().run_repeating("Autosave", period, period, move || {
+                    save_level(&level_name);
+                    save_all_statistics(&connections);
+                    save_all_health(&connections);
+                }).guard())
+            }
+        };
+
+        self.plugins.lock().unwrap().enable_all();
+
+        let console_handle = async_std::task::spawn(console::run_console(self.network_server.clone()));
+        let rcon_handle = async_std::task::spawn(rcon::run_rcon(self.network_server.clone()));
+        let query_handle = async_std::task::spawn(run_query(self.network_server.config().clone(), self.network_server.connections().clone()));
+        let metrics_handle = async_std::task::spawn(metrics::run_metrics(self.network_server.clone()));
+        let heartbeat = start_heartbeat(self.network_server.config().clone(), self.network_server.connections().clone());
+        let lan_broadcast = start_lan_broadcast(self.network_server.config().clone(), self.network_server.connections().clone());
+
+        let network_server = self.network_server.clone();
+        if let Err(err) = ctrlc::set_handler(move || {
+            info!("Received shutdown signal, stopping.");
+            network_server.shutdown();
+        }) {
+            warn!("Failed to install signal handler: {}", err);
+        }
+
+        network_handle.await;
+        self.plugins.lock().unwrap().disable_all();
+        console_handle.cancel().await;
+        rcon_handle.cancel().await;
+        query_handle.cancel().await;
+        metrics_handle.cancel().await;
+        drop(lan_broadcast);
+        drop(heartbeat);
+        drop(autosave);
+        drop(ai_debug);
+        drop(health);
+        drop(world_time);
+        drop(entity_tracker);
+        tick_loop.stop();
+        save_level(&self.network_server.config().world.level_name);
+        // The cancelled workers never saw their players leave, so nothing else saves what they did.
+        save_all_statistics(self.network_server.connections());
+        save_all_health(self.network_server.connections());
+        Ok(())
     }
 }
 
+/// The file, or the defaults without creating it when only printing, with every override applied.
+fn resolve_config(cli: &Cli, create: bool) -> anyhow::Result<ServerConfig> {
+    let mut config = match create || cli.config.exists() {
+        true => ServerConfig::load_or_create(&cli.config)?,
+        false => ServerConfig::default(),
+    };
+    config.apply_env(std::env::vars())?;
+    cli.apply(&mut config)?;
+    Ok(config)
+}
+
 fn main() {
-    init_logger();
+    let cli = Cli::parse();
+    logger::init_logger(cli.log_level);
+
+    if let Some(Command::Config(ConfigCommand::Print)) = cli.command {
+        match resolve_config(&cli, false).and_then(|config| config.to_toml()) {
+            Ok(config) => print!("{}", config),
+            Err(err) => {
+                critical!("Failed to load configuration: {:#}", err);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if let Some(Command::Proxy { upstream, listen, dump }) = &cli.command {
+        let options = ProxyOptions { listen: *listen, upstream: upstream.clone(), dump: *dump };
+        if let Err(err) = block_on(run_proxy(options)) {
+            critical!("{:#}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(Command::Capture(command)) = &cli.command {
+        let result = match command {
+            CaptureCommand::Print { file, limit } => capture::print_capture(file, *limit),
+            CaptureCommand::Replay { file, address } => block_on(capture::replay(file, *address)),
+        };
+        if let Err(err) = result {
+            critical!("{:#}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     info!("Running {} version {}.", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+    let config = match resolve_config(&cli, true) {
+        Ok(config) => config,
+        Err(err) => {
+            critical!("Failed to load configuration: {:#}", err);
+            std::process::exit(1);
+        }
+    };
+    if config.server.online_mode {
+        warn!("online-mode is not supported yet, players are not authenticated.");
+    }
+    if let Err(err) = ResourcePack::from_config(&config.resource_pack) {
+        critical!("Invalid resource pack configuration: {:#}", err);
+        std::process::exit(1);
+    }
+    #[cfg(feature = "otel")]
+    let _telemetry = match telemetry::init_telemetry(&config.telemetry) {
+        Ok(telemetry) => telemetry,
+        Err(err) => {
+            critical!("Failed to start exporting traces: {:#}", err);
+            std::process::exit(1);
+        }
+    };
+    #[cfg(not(feature = "otel"))]
+    if !config.telemetry.otlp_endpoint.is_empty() {
+        warn!("telemetry.otlp-endpoint is set but the server was built without the otel feature, no traces are exported.");
+    }
 
-    let mut app = App::new();
-    block_on(app.start());
-}
+    let world_config = &config.world;
+    if let Some(directory) = &world_config.reports_directory {
+        if let Err(err) = load_reports(directory) {
+            critical!("Failed to load reports: {:#}", err);
+            std::process::exit(1);
+        }
+    }
+    if let Some(directory) = &world_config.data_directory {
+        match RecipeRegistry::load(directory, &items().read().unwrap()) {
+            Ok(registry) => {
+                info!("Loaded {} recipes from {}.", registry.len(), directory.display());
+                *recipes().write().unwrap() = registry;
+            }
+            Err(err) => {
+                critical!("Failed to load recipes: {:#}", err);
+                std::process::exit(1);
+            }
+        }
+        match AdvancementRegistry::load(directory, &items().read().unwrap()) {
+            Ok(registry) => {
+                info!("Loaded {} advancements from {}.", registry.len(), directory.display());
+                *advancements().write().unwrap() = registry;
+            }
+            Err(err) => {
+                critical!("Failed to load advancements: {:#}", err);
+                std::process::exit(1);
+            }
+        }
+    }
+    match LevelData::load_or_create(&world_config.level_name, &world_config.level_name, &world_config.level_seed) {
+        Ok(level_data) => {
+            info!("Loaded level \"{}\" with seed {}.", world_config.level_name, level_data.seed);
+            *level().write().unwrap() = level_data;
+        }
+        Err(err) => {
+            critical!("Failed to load level: {:#}", err);
+            std::process::exit(1);
+        }
+    }
+    match OpsList::load(OPS_FILE) {
+        Ok(loaded) => *ops().write().unwrap() = loaded,
+        Err(err) => {
+            critical!("Failed to load the operators: {:#}", err);
+            std::process::exit(1);
+        }
+    }
+    match Whitelist::load(WHITELIST_FILE, config.server.whitelist) {
+        Ok(loaded) => *whitelist().write().unwrap() = loaded,
+        Err(err) => {
+            critical!("Failed to load the whitelist: {:#}", err);
+            std::process::exit(1);
+        }
+    }
+    match BanList::load(BANNED_PLAYERS_FILE) {
+        Ok(loaded) => *banned_players().write().unwrap() = loaded,
+        Err(err) => {
+            critical!("Failed to load the banned players: {:#}", err);
+            std::process::exit(1);
+        }
+    }
+    match BanList::load(BANNED_IPS_FILE) {
+        Ok(loaded) => *banned_ips().write().unwrap() = loaded,
+        Err(err) => {
+            critical!("Failed to load the banned IP addresses: {:#}", err);
+            std::process::exit(1);
+        }
+    }
+    match load_favicon(SERVER_ICON_FILE) {
+        Ok(loaded) => *favicon().write().unwrap() = loaded,
+        // The server list shows the default icon instead.
+        Err(err) => warn!("Failed to load the server icon: {:#}", err),
+    }
+    match Scoreboard::load(Path::new(&world_config.level_name).join("data").join("scoreboard.dat")) {
+        Ok(loaded) => *scoreboard().write().unwrap() = loaded,
+        Err(err) => {
+            critical!("Failed to load the scoreboard: {:#}", err);
+            std::process::exit(1);
+        }
+    }
 
-fn init_logger() {
-    spdlog::default_logger().set_level_filter(spdlog::LevelFilter::All);
+    for dimension in Dimension::ALL {
+        let directory = Path::new(&world_config.level_name).join(dimension.directory());
+        let storage = |folder| RegionStorage::new(directory.join(folder)).with_compression(world_config.region_file_compression.into());
+        *dimension.world().write().unwrap() = World::with_storage(dimension.min_y(), dimension.height(), storage("region"))
+            .with_entity_storage(storage("entities"));
+    }
+    if let Err(err) = choose_world_spawn(world_config.spawn) {
+        critical!("Failed to choose the world spawn: {:#}", err);
+        std::process::exit(1);
+    }
+    *player_data().write().unwrap() = PlayerDataStorage::new(Path::new(&world_config.level_name).join("playerdata"));
+    *advancement_data().write().unwrap() = AdvancementStorage::new(Path::new(&world_config.level_name).join("advancements"));
+    *stats_data().write().unwrap() = StatsStorage::new(Path::new(&world_config.level_name).join("stats"));
+
+    let app = App::new(config);
+    if let Err(err) = block_on(app.run()) {
+        critical!("{:#}", err);
+        spdlog::default_logger().flush();
+        std::process::exit(1);
+    }
+    info!("Bye.");
+    spdlog::default_logger().flush();
 }
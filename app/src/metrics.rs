@@ -0,0 +1,94 @@
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use async_std::io::{ReadExt, WriteExt};
+use async_std::net::{TcpListener, TcpStream};
+use spdlog::{error, info};
+use dolls_network::prelude::{handler_metrics_prometheus, traffic_metrics_prometheus, ConnectionRegistry, DollNetworkServer};
+use dolls_tick::prelude::{tick_stats, TICK_BUCKETS};
+use dolls_world::prelude::Dimension;
+use crate::service::serve_clients;
+
+/// Longest request head read, scrapers send a few short headers.
+const MAX_REQUEST_SIZE: usize = 8192;
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Serves Prometheus metrics until the task is cancelled, if they are enabled.
+pub(crate) async fn run_metrics(network_server: Arc<DollNetworkServer>) {
+    let config = &network_server.config().metrics;
+    if !config.enabled {
+        return;
+    }
+    let address = SocketAddr::new(network_server.config().network.bind_address, config.port);
+    let listener = match TcpListener::bind(address).await {
+        Ok(listener) => listener,
+        Err(err) => {
+            error!("Failed to bind the metrics endpoint to {}: {}", address, err);
+            return;
+        }
+    };
+    info!("Metrics served on http://{}/metrics", address);
+    serve_clients(listener, "Metrics", |stream| {
+        let connections = network_server.connections().clone();
+        async move { serve_client(stream, &connections).await }
+    }).await;
+}
+
+async fn serve_client(mut stream: TcpStream, connections: &ConnectionRegistry) -> anyhow::Result<()> {
+    let mut request = Vec::new();
+    let mut buffer = [0; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        let read = stream.read(&mut buffer).await?;
+        if read == 0 || request.len() + read > MAX_REQUEST_SIZE {
+            return Ok(());
+        }
+        request.extend_from_slice(&buffer[..read]);
+    }
+    let request_line = String::from_utf8_lossy(&request).lines().next().unwrap_or_default().to_string();
+    let mut parts = request_line.split_whitespace();
+    let response = match (parts.next(), parts.next().map(|target| target.split('?').next().unwrap_or_default())) {
+        (Some("GET"), Some("/metrics")) => response("200 OK", CONTENT_TYPE, &metrics(connections)),
+        (Some("GET"), _) => response("404 Not Found", "text/plain", "Not found, metrics are at /metrics\n"),
+        _ => response("405 Method Not Allowed", "text/plain", "Only GET is supported\n"),
+    };
+    stream.write_all(response.as_bytes()).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+fn response(status: &str, content_type: &str, body: &str) -> String {
+    format!("HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status, content_type, body.len(), body)
+}
+
+/// Every metric of the server in the Prometheus text format.
+fn metrics(connections: &ConnectionRegistry) -> String {
+    let mut output = String::new();
+    output.push_str("# HELP dolls_connections Open connections, in any state.\n");
+    output.push_str("# TYPE dolls_connections gauge\n");
+    let _ = writeln!(output, "dolls_connections {}", connections.len());
+    output.push_str("# HELP dolls_players Players in Play.\n");
+    output.push_str("# TYPE dolls_players gauge\n");
+    let _ = writeln!(output, "dolls_players {}", connections.players().len());
+
+    let stats = tick_stats();
+    output.push_str("# HELP dolls_tick_duration_seconds Time ticks took to run.\n");
+    output.push_str("# TYPE dolls_tick_duration_seconds histogram\n");
+    let mut cumulative = 0;
+    for (bound, count) in TICK_BUCKETS.iter().zip(&stats.buckets) {
+        cumulative += count;
+        let _ = writeln!(output, "dolls_tick_duration_seconds_bucket{{le=\"{}\"}} {}", bound.as_secs_f64(), cumulative);
+    }
+    let _ = writeln!(output, "dolls_tick_duration_seconds_bucket{{le=\"+Inf\"}} {}", stats.ticks);
+    let _ = writeln!(output, "dolls_tick_duration_seconds_sum {}", stats.total.as_secs_f64());
+    let _ = writeln!(output, "dolls_tick_duration_seconds_count {}", stats.ticks);
+
+    output.push_str("# HELP dolls_loaded_chunks Chunks held in memory.\n");
+    output.push_str("# TYPE dolls_loaded_chunks gauge\n");
+    for dimension in Dimension::ALL {
+        let _ = writeln!(output, "dolls_loaded_chunks{{dimension=\"{}\"}} {}", dimension.name(), dimension.world().read().unwrap().chunks().count());
+    }
+
+    output.push_str(&traffic_metrics_prometheus());
+    output.push_str(&handler_metrics_prometheus());
+    output
+}
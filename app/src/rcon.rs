@@ -0,0 +1,142 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use async_std::io::{ReadExt, WriteExt};
+use async_std::net::{TcpListener, TcpStream};
+use anyhow::{anyhow, bail};
+use spdlog::{error, info, warn};
+use dolls_commands::prelude::{execute_command, CommandSource};
+use dolls_network::prelude::{ConnectionRegistry, DollNetworkServer};
+use crate::service::serve_clients;
+
+/// Packet types, the answer to a login shares its type with commands.
+const RESPONSE_VALUE: i32 = 0;
+const EXEC_COMMAND: i32 = 2;
+const AUTH_RESPONSE: i32 = 2;
+const AUTH: i32 = 3;
+/// Request id answering a failed login.
+const AUTH_FAILED_ID: i32 = -1;
+/// Largest request vanilla accepts, counting the length field.
+const MAX_REQUEST_SIZE: usize = 1460;
+/// Request id, type and the two terminating zero bytes.
+const MIN_PACKET_LENGTH: usize = 10;
+/// Longest body of a response packet, longer output is split over several packets like in vanilla.
+const MAX_RESPONSE_BODY: usize = 4096;
+/// How long a client may take to send its next request before it is closed.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(300);
+/// Wrong passwords a client may send before it is closed.
+const MAX_AUTH_FAILURES: u32 = 3;
+
+/// Accepts RCON clients until the task is cancelled, if RCON is enabled and has a password.
+pub(crate) async fn run_rcon(network_server: Arc<DollNetworkServer>) {
+    let config = &network_server.config().rcon;
+    if !config.enabled {
+        return;
+    }
+    if config.password.is_empty() {
+        warn!("RCON is enabled without a password, it stays off.");
+        return;
+    }
+    let address = SocketAddr::new(network_server.config().network.bind_address, config.port);
+    let listener = match TcpListener::bind(address).await {
+        Ok(listener) => listener,
+        Err(err) => {
+            error!("Failed to bind RCON to {}: {}", address, err);
+            return;
+        }
+    };
+    info!("RCON running on {}", address);
+    serve_clients(listener, "RCON", |stream| {
+        let password = config.password.clone();
+        let connections = network_server.connections().clone();
+        async move { serve_client(stream, &password, connections).await }
+    }).await;
+}
+
+async fn serve_client(mut stream: TcpStream, password: &str, connections: Arc<ConnectionRegistry>) -> anyhow::Result<()> {
+    let peer_addr = stream.peer_addr()?;
+    let source = CommandSource::remote(connections);
+    let mut authenticated = false;
+    let mut auth_failures = 0;
+    loop {
+        let request = async_std::future::timeout(REQUEST_TIMEOUT, read_packet(&mut stream)).await
+            .map_err(|_| anyhow!("No request within {:?}", REQUEST_TIMEOUT))??;
+        let Some((id, kind, body)) = request else { break };
+        match kind {
+            AUTH => {
+                authenticated = body == password;
+                match authenticated {
+                    true => info!("RCON client {} logged in", peer_addr),
+                    false => warn!("RCON client {} used a wrong password", peer_addr),
+                }
+                let id = if authenticated { id } else { AUTH_FAILED_ID };
+                write_packet(&mut stream, id, AUTH_RESPONSE, "").await?;
+                if !authenticated {
+                    auth_failures += 1;
+                    if auth_failures >= MAX_AUTH_FAILURES {
+                        bail!("{} wrong passwords", auth_failures);
+                    }
+                }
+            }
+            _ if !authenticated => write_packet(&mut stream, AUTH_FAILED_ID, AUTH_RESPONSE, "").await?,
+            EXEC_COMMAND => {
+                info!("RCON client {} issued server command: {}", peer_addr, body);
+                if let Err(err) = execute_command(&source, body.trim().trim_start_matches('/')) {
+                    source.send_error(err.to_string());
+                }
+                let output = source.take_output().join("\n");
+                write_response(&mut stream, id, &output).await?;
+            }
+            kind => write_response(&mut stream, id, &format!("Unknown request {:x}", kind)).await?,
+        }
+    }
+    Ok(())
+}
+
+/// Reads a request: id, type and body. `None` once the client closed the connection.
+async fn read_packet(stream: &mut TcpStream) -> anyhow::Result<Option<(i32, i32, String)>> {
+    let mut length = [0; 4];
+    match stream.read_exact(&mut length).await {
+        Ok(()) => {}
+        Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err.into()),
+    }
+    let length = i32::from_le_bytes(length);
+    if length < MIN_PACKET_LENGTH as i32 || length as usize + 4 > MAX_REQUEST_SIZE {
+        bail!("Invalid RCON packet length {}", length);
+    }
+    let mut packet = vec![0; length as usize];
+    stream.read_exact(&mut packet).await?;
+    let id = i32::from_le_bytes(packet[0..4].try_into()?);
+    let kind = i32::from_le_bytes(packet[4..8].try_into()?);
+    let body = &packet[8..];
+    let end = body.iter().position(|&byte| byte == 0).unwrap_or(body.len());
+    Ok(Some((id, kind, String::from_utf8_lossy(&body[..end]).into_owned())))
+}
+
+async fn write_packet(stream: &mut TcpStream, id: i32, kind: i32, body: &str) -> anyhow::Result<()> {
+    let mut packet = Vec::with_capacity(body.len() + MIN_PACKET_LENGTH + 4);
+    packet.extend_from_slice(&((body.len() + MIN_PACKET_LENGTH) as i32).to_le_bytes());
+    packet.extend_from_slice(&id.to_le_bytes());
+    packet.extend_from_slice(&kind.to_le_bytes());
+    packet.extend_from_slice(body.as_bytes());
+    packet.extend_from_slice(&[0, 0]);
+    stream.write_all(&packet).await?;
+    Ok(())
+}
+
+/// Answers a command, with as many packets as the output needs.
+async fn write_response(stream: &mut TcpStream, id: i32, output: &str) -> anyhow::Result<()> {
+    let mut rest = output;
+    loop {
+        let mut end = rest.len().min(MAX_RESPONSE_BODY);
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        write_packet(stream, id, RESPONSE_VALUE, &rest[..end]).await?;
+        rest = &rest[end..];
+        if rest.is_empty() {
+            return Ok(());
+        }
+    }
+}
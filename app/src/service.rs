@@ -0,0 +1,36 @@
+use std::future::Future;
+use async_std::net::{TcpListener, TcpStream};
+use spdlog::{debug, error, warn};
+use dolls_network::prelude::{is_listener_gone, AcceptBackoff};
+
+/// Accepts clients of a side service such as RCON until the task is cancelled, serving each on a task of its
+/// own. Accept errors are retried after a backoff, unless the listener itself is gone.
+pub(crate) async fn serve_clients<F, Fut>(listener: TcpListener, service: &'static str, serve: F)
+where
+    F: Fn(TcpStream) -> Fut,
+    Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+{
+    let mut backoff = AcceptBackoff::new();
+    loop {
+        backoff.wait().await;
+        match listener.accept().await {
+            Ok((stream, peer_addr)) => {
+                backoff.succeeded();
+                let client = serve(stream);
+                async_std::task::spawn(async move {
+                    if let Err(err) = client.await {
+                        debug!("{} client {} failed: {:#}", service, peer_addr, err);
+                    }
+                });
+            }
+            Err(err) if is_listener_gone(&err) => {
+                error!("Stopped accepting {} clients: {}", service, err);
+                return;
+            }
+            Err(err) => {
+                backoff.failed();
+                warn!("Failed to accept a {} client, retrying in {:?}: {}", service, backoff.delay(), err);
+            }
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use anyhow::Context;
+use opentelemetry::trace::TracerProvider;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::trace::{Sampler, SdkTracerProvider};
+use opentelemetry_sdk::Resource;
+use spdlog::{error, info};
+use tracing_subscriber::filter::{LevelFilter, Targets};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::Layer;
+use tracing_subscriber::util::SubscriberInitExt;
+use dolls_config::TelemetryConfig;
+
+/// Exports the spans of the network workers while it lives, dropping it sends what is left.
+pub(crate) struct Telemetry {
+    provider: SdkTracerProvider,
+}
+
+/// Starts exporting spans, `None` if no endpoint is configured.
+pub(crate) fn init_telemetry(config: &TelemetryConfig) -> anyhow::Result<Option<Telemetry>> {
+    if config.otlp_endpoint.is_empty() {
+        return Ok(None);
+    }
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(&config.otlp_endpoint)
+        .build()
+        .context("Failed to create the OTLP exporter")?;
+    let resource = Resource::builder()
+        .with_service_name(config.service_name.clone())
+        .with_attribute(KeyValue::new("service.version", env!("CARGO_PKG_VERSION")))
+        .build();
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        // Packet spans follow the decision taken for their connection.
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(config.sample_ratio.clamp(0.0, 1.0)))))
+        .with_resource(resource)
+        .build();
+    // Only the server's own spans, those of the HTTP client exporting them would feed back into it.
+    let layer = tracing_opentelemetry::layer().with_tracer(provider.tracer("dolls"))
+        .with_filter(Targets::new().with_target("dolls", LevelFilter::TRACE));
+    tracing_subscriber::registry().with(layer).try_init().context("Failed to install the tracing subscriber")?;
+    info!("Exporting traces to {}.", config.otlp_endpoint);
+    Ok(Some(Telemetry { provider }))
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        if let Err(err) = self.provider.shutdown() {
+            error!("Failed to export the last traces: {}", err);
+        }
+    }
+}
@@ -0,0 +1,272 @@
+use std::net::SocketAddr;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use anyhow::{anyhow, bail, Context};
+use async_std::net::{TcpStream, ToSocketAddrs};
+use log::debug;
+use uuid::Uuid;
+use dolls_core::datatype::{decode_from_slice, Decode, Encode, Identifier, VarInt};
+use dolls_core::text::{JsonTextComponent, TextComponent};
+use dolls_network::prelude::{offline_uuid, ClientboundPacketType, ConnectionState, KnownPack, PacketHandler, PacketType, PingPong,
+    PlayerPosition, RawPacket, StatusResponse, PROTOCOL_VERSION};
+
+/// How long [`Client::receive`] waits by default.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Login packet of servers in online mode, which would need a Mojang session.
+const ENCRYPTION_REQUEST: u32 = 0x01;
+/// Answers to Add Resource Pack, the client pretends to load every pack.
+const RESOURCE_PACK_ACCEPTED: i32 = 3;
+const RESOURCE_PACK_LOADED: i32 = 0;
+
+/// A packet the server sent, with its type if this crate knows the id.
+#[derive(Debug, Clone)]
+pub struct ServerPacket {
+    pub packet_type: Option<ClientboundPacketType>,
+    pub packet: RawPacket,
+}
+
+impl ServerPacket {
+    pub fn is(&self, packet_type: ClientboundPacketType) -> bool {
+        self.packet_type == Some(packet_type)
+    }
+
+    pub fn decode<T: Decode>(&self) -> std::io::Result<T> {
+        decode_from_slice(&self.packet.payload)
+    }
+}
+
+/// What a status ping returned.
+#[derive(Debug, Clone)]
+pub struct ServerStatus {
+    pub json: serde_json::Value,
+    /// Time between the ping and the pong.
+    pub latency: Duration,
+}
+
+/// A client in offline mode speaking the protocol of this server, for tests, bots and checking other
+/// servers. Each method expects the connection to be in the state the protocol sends it in, e.g.
+/// [`Client::configure`] right after [`Client::login`].
+#[derive(Debug)]
+pub struct Client {
+    connection: PacketHandler,
+    peer_addr: SocketAddr,
+    state: ConnectionState,
+    timeout: Duration,
+    pub(crate) username: Option<String>,
+    pub(crate) uuid: Option<Uuid>,
+    pub(crate) entity_id: Option<i32>,
+    pub(crate) position: Option<PlayerPosition>,
+}
+
+impl Client {
+    pub async fn connect(address: impl ToSocketAddrs) -> anyhow::Result<Self> {
+        let stream = TcpStream::connect(address).await.context("Failed to connect")?;
+        stream.set_nodelay(true)?;
+        let peer_addr = stream.peer_addr()?;
+        Ok(Self {
+            connection: PacketHandler::new(stream),
+            peer_addr,
+            state: ConnectionState::Handshaking,
+            timeout: DEFAULT_TIMEOUT,
+            username: None,
+            uuid: None,
+            entity_id: None,
+            position: None,
+        })
+    }
+
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    pub fn state(&self) -> ConnectionState {
+        self.state
+    }
+
+    pub fn peer_addr(&self) -> SocketAddr {
+        self.peer_addr
+    }
+
+    pub fn username(&self) -> Option<&str> {
+        self.username.as_deref()
+    }
+
+    /// UUID the server assigned, known after [`Client::login`].
+    pub fn uuid(&self) -> Option<Uuid> {
+        self.uuid
+    }
+
+    /// Entity id of the player, known once Play started.
+    pub fn entity_id(&self) -> Option<i32> {
+        self.entity_id
+    }
+
+    /// Where the server last put the player, or where it last moved to.
+    pub fn position(&self) -> Option<PlayerPosition> {
+        self.position
+    }
+
+    /// Sends a packet of the current state.
+    pub async fn send(&mut self, packet_type: PacketType, payload: Vec<u8>) -> anyhow::Result<()> {
+        if packet_type.state() != self.state {
+            bail!("{} is not sent in {:?}", packet_type.name(), self.state);
+        }
+        self.connection.write_packet(&RawPacket::new(packet_type.id(), payload)).await
+    }
+
+    pub async fn send_encoded(&mut self, packet_type: PacketType, packet: &impl Encode) -> anyhow::Result<()> {
+        let mut payload = Vec::new();
+        packet.encode(&mut payload)?;
+        self.send(packet_type, payload).await
+    }
+
+    /// The next packet, failing after the timeout. Nothing is answered, see [`Client::next_packet`] in Play.
+    pub async fn receive(&mut self) -> anyhow::Result<ServerPacket> {
+        let timeout = self.timeout;
+        let packet = async_std::future::timeout(timeout, self.connection.next_packet()).await
+            .map_err(|_| anyhow!("No packet within {:?} in {:?}", timeout, self.state))??;
+        Ok(ServerPacket { packet_type: ClientboundPacketType::from_parts(self.state, packet.packet_id), packet })
+    }
+
+    /// Receives packets until one of `packet_type`, dropping the others.
+    pub async fn receive_until(&mut self, packet_type: ClientboundPacketType) -> anyhow::Result<RawPacket> {
+        loop {
+            let packet = self.receive().await?;
+            if packet.is(packet_type) {
+                return Ok(packet.packet);
+            }
+        }
+    }
+
+    pub async fn handshake(&mut self, next_state: ConnectionState) -> anyhow::Result<()> {
+        let next = match next_state {
+            ConnectionState::Status => 1,
+            ConnectionState::Login => 2,
+            state => bail!("Cannot switch to {:?} from a handshake", state),
+        };
+        let mut payload = Vec::new();
+        VarInt(PROTOCOL_VERSION).encode(&mut payload)?;
+        self.peer_addr.ip().to_string().encode(&mut payload)?;
+        self.peer_addr.port().encode(&mut payload)?;
+        VarInt(next).encode(&mut payload)?;
+        self.send(PacketType::Handshake, payload).await?;
+        self.state = next_state;
+        Ok(())
+    }
+
+    /// Handshakes for Status, asks for the status and measures a ping.
+    pub async fn status(&mut self) -> anyhow::Result<ServerStatus> {
+        self.handshake(ConnectionState::Status).await?;
+        self.send(PacketType::StatusRequest, Vec::new()).await?;
+        let response: StatusResponse = decode_from_slice(&self.receive_until(ClientboundPacketType::StatusResponse).await?.payload)?;
+        // Vanilla pings with the current time, any value is echoed.
+        let payload = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as i64;
+        let sent = Instant::now();
+        self.send_encoded(PacketType::PingRequest, &PingPong { payload }).await?;
+        let pong: PingPong = decode_from_slice(&self.receive_until(ClientboundPacketType::PongResponse).await?.payload)?;
+        if pong.payload != payload {
+            bail!("The server answered the ping with {} instead of {}", pong.payload, payload);
+        }
+        Ok(ServerStatus { json: serde_json::from_str(&response.json)?, latency: sent.elapsed() })
+    }
+
+    /// Handshakes for Login and logs in as an offline player, up to Configuration.
+    pub async fn login(&mut self, username: &str) -> anyhow::Result<Uuid> {
+        self.handshake(ConnectionState::Login).await?;
+        let mut payload = Vec::new();
+        username.encode(&mut payload)?;
+        offline_uuid(username).encode(&mut payload)?;
+        self.send(PacketType::LoginStart, payload).await?;
+        loop {
+            let packet = self.receive().await?;
+            let mut payload = packet.packet.payload.as_slice();
+            match packet.packet_type {
+                Some(ClientboundPacketType::SetCompression) => {
+                    self.connection.set_compression(usize::try_from(VarInt::decode(&mut payload)?.0).ok());
+                }
+                Some(ClientboundPacketType::LoginSuccess) => {
+                    let uuid = Uuid::decode(&mut payload)?;
+                    self.send(PacketType::LoginAcknowledged, Vec::new()).await?;
+                    self.state = ConnectionState::Configuration;
+                    self.username = Some(username.to_string());
+                    self.uuid = Some(uuid);
+                    return Ok(uuid);
+                }
+                Some(ClientboundPacketType::LoginDisconnect) => {
+                    let JsonTextComponent(reason) = JsonTextComponent::decode(&mut payload)?;
+                    bail!("Disconnected during login: {}", reason.to_plain_text());
+                }
+                Some(ClientboundPacketType::LoginPluginRequest) => {
+                    // Not understood, as vanilla does for channels it does not know.
+                    let message_id = VarInt::decode(&mut payload)?;
+                    let mut response = Vec::new();
+                    message_id.encode(&mut response)?;
+                    false.encode(&mut response)?;
+                    self.send(PacketType::LoginPluginResponse, response).await?;
+                }
+                Some(ClientboundPacketType::LoginCookieRequest) => {
+                    let key = Identifier::decode(&mut payload)?;
+                    self.send(PacketType::LoginCookieResponse, cookie_response(&key)?).await?;
+                }
+                None if packet.packet.packet_id == ENCRYPTION_REQUEST => bail!("The server is in online mode, only offline mode is supported"),
+                _ => debug!("Ignoring packet 0x{:02X} during login", packet.packet.packet_id),
+            }
+        }
+    }
+
+    /// Agrees on the known packs, answers requests and finishes configuration, up to Play.
+    pub async fn configure(&mut self) -> anyhow::Result<()> {
+        loop {
+            let packet = self.receive().await?;
+            let mut payload = packet.packet.payload.as_slice();
+            match packet.packet_type {
+                Some(ClientboundPacketType::ClientboundKnownPacks) => {
+                    // Claims to know every pack the server does, so that it sends only their ids.
+                    let packs: Vec<KnownPack> = Vec::decode(&mut payload)?;
+                    self.send_encoded(PacketType::ServerboundKnownPacks, &packs).await?;
+                }
+                Some(ClientboundPacketType::FinishConfiguration) => {
+                    self.send(PacketType::AcknowledgeFinishConfiguration, Vec::new()).await?;
+                    self.state = ConnectionState::Play;
+                    return Ok(());
+                }
+                Some(ClientboundPacketType::ConfigurationDisconnect) => {
+                    bail!("Disconnected during configuration: {}", TextComponent::decode(&mut payload)?.to_plain_text());
+                }
+                Some(ClientboundPacketType::ConfigurationCookieRequest) => {
+                    let key = Identifier::decode(&mut payload)?;
+                    self.send(PacketType::ConfigurationCookieResponse, cookie_response(&key)?).await?;
+                }
+                Some(ClientboundPacketType::ConfigurationAddResourcePack) => {
+                    let id = Uuid::decode(&mut payload)?;
+                    for result in [RESOURCE_PACK_ACCEPTED, RESOURCE_PACK_LOADED] {
+                        let mut response = Vec::new();
+                        id.encode(&mut response)?;
+                        VarInt(result).encode(&mut response)?;
+                        self.send(PacketType::ConfigurationResourcePackResponse, response).await?;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Logs in, configures and waits for the Play Login packet.
+    pub async fn join(&mut self, username: &str) -> anyhow::Result<Uuid> {
+        let uuid = self.login(username).await?;
+        self.configure().await?;
+        self.wait_for(ClientboundPacketType::Login).await?;
+        Ok(uuid)
+    }
+
+    pub async fn disconnect(self) {
+        let _ = self.connection.get_ref().shutdown(std::net::Shutdown::Both);
+    }
+}
+
+/// Answer to a cookie request, the client never stores any.
+fn cookie_response(key: &Identifier) -> anyhow::Result<Vec<u8>> {
+    let mut response = Vec::new();
+    key.encode(&mut response)?;
+    false.encode(&mut response)?;
+    Ok(response)
+}
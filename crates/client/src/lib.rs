@@ -0,0 +1,6 @@
+pub mod client;
+pub mod play;
+
+pub mod prelude {
+    pub use crate::client::*;
+}
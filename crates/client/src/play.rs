@@ -0,0 +1,110 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+use anyhow::bail;
+use dolls_core::datatype::{Decode, Encode, VarInt};
+use dolls_core::text::TextComponent;
+use dolls_network::prelude::{ClientboundPacketType, KeepAlive, PacketType, PlayerPosition, RawPacket};
+use crate::prelude::{Client, ServerPacket};
+
+/// Flags of Synchronize Player Position marking the fields relative to the current position.
+const RELATIVE_X: u8 = 0x01;
+const RELATIVE_Y: u8 = 0x02;
+const RELATIVE_Z: u8 = 0x04;
+const RELATIVE_YAW: u8 = 0x08;
+const RELATIVE_PITCH: u8 = 0x10;
+/// Client Status action asking to respawn.
+const PERFORM_RESPAWN: i32 = 0;
+
+impl ServerPacket {
+    /// Text of a System Chat Message shown in the chat, `None` for other packets and action bar text.
+    pub fn system_chat(&self) -> Option<TextComponent> {
+        if !self.is(ClientboundPacketType::SystemChatMessage) {
+            return None;
+        }
+        let mut payload = self.packet.payload.as_slice();
+        let content = TextComponent::decode(&mut payload).ok()?;
+        (!bool::decode(&mut payload).ok()?).then_some(content)
+    }
+}
+
+impl Client {
+    /// The next Play packet, after answering keep alives and teleports as the vanilla client does.
+    /// Fails if the server disconnects the player.
+    pub async fn next_packet(&mut self) -> anyhow::Result<ServerPacket> {
+        let packet = self.receive().await?;
+        let mut payload = packet.packet.payload.as_slice();
+        match packet.packet_type {
+            Some(ClientboundPacketType::KeepAlive) => {
+                let keep_alive = KeepAlive::decode(&mut payload)?;
+                self.send_encoded(PacketType::KeepAlive, &keep_alive).await?;
+            }
+            Some(ClientboundPacketType::SynchronizePlayerPosition) => {
+                let (x, y, z) = (f64::decode(&mut payload)?, f64::decode(&mut payload)?, f64::decode(&mut payload)?);
+                let (yaw, pitch) = (f32::decode(&mut payload)?, f32::decode(&mut payload)?);
+                let flags = u8::decode(&mut payload)?;
+                let teleport_id = VarInt::decode(&mut payload)?;
+                let current = self.position.unwrap_or_default();
+                let relative = |flag: u8, current: f64| if flags & flag != 0 { current } else { 0.0 };
+                self.position = Some(PlayerPosition {
+                    x: relative(RELATIVE_X, current.x) + x,
+                    y: relative(RELATIVE_Y, current.y) + y,
+                    z: relative(RELATIVE_Z, current.z) + z,
+                    yaw: relative(RELATIVE_YAW, current.yaw as f64) as f32 + yaw,
+                    pitch: relative(RELATIVE_PITCH, current.pitch as f64) as f32 + pitch,
+                    on_ground: current.on_ground,
+                });
+                self.send_encoded(PacketType::ConfirmTeleportation, &teleport_id).await?;
+            }
+            Some(ClientboundPacketType::Login) => self.entity_id = Some(i32::decode(&mut payload)?),
+            Some(ClientboundPacketType::PlayDisconnect) => {
+                bail!("Disconnected: {}", TextComponent::decode(&mut payload)?.to_plain_text());
+            }
+            _ => {}
+        }
+        Ok(packet)
+    }
+
+    /// Handles Play packets until one of `packet_type`.
+    pub async fn wait_for(&mut self, packet_type: ClientboundPacketType) -> anyhow::Result<RawPacket> {
+        loop {
+            let packet = self.next_packet().await?;
+            if packet.is(packet_type) {
+                return Ok(packet.packet);
+            }
+        }
+    }
+
+    /// Sends an unsigned chat message, which servers enforcing secure chat reject.
+    pub async fn chat(&mut self, message: &str) -> anyhow::Result<()> {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as i64;
+        let mut payload = Vec::new();
+        message.encode(&mut payload)?;
+        timestamp.encode(&mut payload)?;
+        // Salt, no signature, then an empty last seen update.
+        0i64.encode(&mut payload)?;
+        false.encode(&mut payload)?;
+        VarInt(0).encode(&mut payload)?;
+        [0u8; 3].encode(&mut payload)?;
+        self.send(PacketType::ChatMessage, payload).await
+    }
+
+    /// Runs a command, given without its leading `/`.
+    pub async fn command(&mut self, command: &str) -> anyhow::Result<()> {
+        self.send_encoded(PacketType::ChatCommand, &command).await
+    }
+
+    pub async fn move_to(&mut self, x: f64, y: f64, z: f64, on_ground: bool) -> anyhow::Result<()> {
+        let mut payload = Vec::new();
+        for coordinate in [x, y, z] {
+            coordinate.encode(&mut payload)?;
+        }
+        on_ground.encode(&mut payload)?;
+        self.send(PacketType::SetPlayerPosition, payload).await?;
+        let current = self.position.unwrap_or_default();
+        self.position = Some(PlayerPosition { x, y, z, on_ground, ..current });
+        Ok(())
+    }
+
+    pub async fn respawn(&mut self) -> anyhow::Result<()> {
+        self.send_encoded(PacketType::ClientStatus, &VarInt(PERFORM_RESPAWN)).await
+    }
+}
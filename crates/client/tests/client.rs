@@ -0,0 +1,36 @@
+mod common;
+
+use dolls_network::prelude::{offline_uuid, ConnectionState, PROTOCOL_VERSION};
+use common::{run, Connect, TestServer};
+
+#[test]
+fn client_pings_joins_and_runs_commands() {
+    run(async {
+        let server = TestServer::start().await;
+
+        let mut client = server.connect().await;
+        let status = client.status().await.unwrap();
+        assert_eq!(status.json["version"]["protocol"], PROTOCOL_VERSION);
+        client.disconnect().await;
+
+        let mut client = server.connect().await;
+        assert_eq!(client.join("Bob").await.unwrap(), offline_uuid("Bob"));
+        assert_eq!(client.state(), ConnectionState::Play);
+        assert!(client.entity_id().is_some());
+
+        // No command handler is installed outside of the app.
+        client.command("help").await.unwrap();
+        let message = loop {
+            if let Some(message) = client.next_packet().await.unwrap().system_chat() {
+                break message;
+            }
+        };
+        assert_eq!(message.to_plain_text(), "Commands are not available");
+
+        client.move_to(1.5, 80.0, -2.5, false).await.unwrap();
+        assert_eq!(client.position().map(|position| (position.x, position.y, position.z)), Some((1.5, 80.0, -2.5)));
+
+        client.disconnect().await;
+        server.stop().await;
+    });
+}
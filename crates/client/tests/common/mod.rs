@@ -0,0 +1,33 @@
+//! End-to-end harness: the shared [`TestServer`], driven by [`Client`] over real sockets.
+
+// Every test binary compiles the helpers, most use only some of them.
+#![allow(dead_code)]
+
+use dolls_client::prelude::Client;
+
+pub use dolls_network::testing::TestServer;
+
+/// Connects [`Client`]s to a [`TestServer`].
+pub trait Connect {
+    async fn connect(&self) -> Client;
+
+    /// A player who finished joining, in Play.
+    async fn join(&self, username: &str) -> Client;
+}
+
+impl Connect for TestServer {
+    async fn connect(&self) -> Client {
+        Client::connect(self.address).await.expect("Failed to connect to the test server")
+    }
+
+    async fn join(&self, username: &str) -> Client {
+        let mut client = self.connect().await;
+        client.join(username).await.unwrap_or_else(|err| panic!("{} failed to join: {:#}", username, err));
+        client
+    }
+}
+
+/// Runs an end-to-end test on the async runtime.
+pub fn run<F: std::future::Future<Output = ()>>(test: F) {
+    async_std::task::block_on(test)
+}
@@ -0,0 +1,90 @@
+mod common;
+
+use std::time::Duration;
+use dolls_config::ServerConfig;
+use dolls_network::prelude::{offline_uuid, ClientboundPacketType, ConnectionState};
+use common::{run, Connect, TestServer};
+
+#[test]
+fn status_reports_config_and_players() {
+    run(async {
+        let mut config = ServerConfig::default();
+        config.server.max_players = 7;
+        config.server.motd = "End to end".to_string();
+        let server = TestServer::start_with(config).await;
+
+        let status = server.connect().await.status().await.unwrap();
+        assert_eq!(status.json["players"]["max"], 7);
+        assert_eq!(status.json["players"]["online"], 0);
+        assert!(status.json["description"].to_string().contains("End to end"));
+
+        let player = server.join("Carol").await;
+        let status = server.connect().await.status().await.unwrap();
+        assert_eq!(status.json["players"]["online"], 1);
+        assert_eq!(status.json["players"]["sample"][0]["name"], "Carol");
+        assert_eq!(status.json["players"]["sample"][0]["id"], offline_uuid("Carol").hyphenated().to_string());
+
+        player.disconnect().await;
+        server.stop().await;
+    });
+}
+
+#[test]
+fn status_hides_players_when_configured() {
+    run(async {
+        let mut config = ServerConfig::default();
+        config.server.hide_online_players = true;
+        let server = TestServer::start_with(config).await;
+
+        let player = server.join("Dave").await;
+        let status = server.connect().await.status().await.unwrap();
+        assert_eq!(status.json["players"]["online"], 1);
+        assert_eq!(status.json["players"]["sample"].as_array().map(Vec::len), Some(0));
+
+        player.disconnect().await;
+        server.stop().await;
+    });
+}
+
+#[test]
+fn login_without_compression() {
+    run(async {
+        let mut config = ServerConfig::default();
+        config.network.compression_threshold = -1;
+        let server = TestServer::start_with(config).await;
+
+        let mut client = server.connect().await;
+        assert_eq!(client.login("Erin").await.unwrap(), offline_uuid("Erin"));
+        client.configure().await.unwrap();
+        client.wait_for(ClientboundPacketType::Login).await.unwrap();
+        assert_eq!(client.state(), ConnectionState::Play);
+
+        client.disconnect().await;
+        server.stop().await;
+    });
+}
+
+#[test]
+fn players_see_each_other_and_rejoin() {
+    run(async {
+        let server = TestServer::start().await;
+
+        let mut first = server.join("Frank").await;
+        let second = server.join("Grace").await;
+        assert_ne!(first.entity_id(), second.entity_id());
+        // The first player learns about the second from the player list.
+        first.wait_for(ClientboundPacketType::PlayerInfoUpdate).await.unwrap();
+        first.wait_for(ClientboundPacketType::PlayerInfoUpdate).await.unwrap();
+
+        second.disconnect().await;
+        first.wait_for(ClientboundPacketType::PlayerInfoRemove).await.unwrap();
+        let second = server.join("Grace").await;
+        assert_eq!(second.uuid(), Some(offline_uuid("Grace")));
+
+        first.disconnect().await;
+        second.disconnect().await;
+        // Give the server a moment to save the players before the storage is dropped.
+        async_std::task::sleep(Duration::from_millis(50)).await;
+        server.stop().await;
+    });
+}
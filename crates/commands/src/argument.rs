@@ -0,0 +1,278 @@
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use anyhow::bail;
+use dolls_core::datatype::{BlockPos, Identifier};
+use dolls_entities::prelude::Vec3;
+use dolls_network::prelude::ConnectionHandle;
+use dolls_world::scoreboard::scoreboard;
+use crate::prelude::{CommandSender, CommandSource, StringReader};
+
+/// How much of the input a string argument consumes.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum StringKind {
+    /// A single unquoted word.
+    Word,
+    /// A word, or a quoted string which may contain spaces.
+    Phrase,
+    /// Everything up to the end of the input.
+    Greedy,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgumentType {
+    Bool,
+    Integer { min: Option<i32>, max: Option<i32> },
+    Double { min: Option<f64>, max: Option<f64> },
+    String(StringKind),
+    /// A player name or a selector matching exactly one player.
+    Player,
+    /// A player name or a selector matching any number of players.
+    Players,
+    BlockPos,
+    /// A position with decimals, whole absolute x and z are centered on their block like in vanilla.
+    Vec3,
+    /// A resource location such as `minecraft:block.note_block.bell`, the namespace defaults to `minecraft`.
+    Identifier,
+    /// Names with scores: a player name or selector, `*` for every name with a score, or any other name.
+    ScoreHolders,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgumentValue {
+    Bool(bool),
+    Integer(i32),
+    Double(f64),
+    String(String),
+    Players(EntitySelector),
+    BlockPos(Coordinates),
+    Vec3(Coordinates),
+    Identifier(Identifier),
+    ScoreHolders(ScoreHolder),
+}
+
+impl ArgumentType {
+    pub fn parse(&self, reader: &mut StringReader) -> anyhow::Result<ArgumentValue> {
+        Ok(match self {
+            ArgumentType::Bool => ArgumentValue::Bool(reader.read_bool()?),
+            ArgumentType::Integer { min, max } => {
+                let value = reader.read_int()?;
+                check_range(value, *min, *max)?;
+                ArgumentValue::Integer(value)
+            }
+            ArgumentType::Double { min, max } => {
+                let value = reader.read_double()?;
+                check_range(value, *min, *max)?;
+                ArgumentValue::Double(value)
+            }
+            ArgumentType::String(StringKind::Word) => ArgumentValue::String(reader.read_unquoted().to_string()),
+            ArgumentType::String(StringKind::Phrase) => ArgumentValue::String(reader.read_string()?),
+            ArgumentType::String(StringKind::Greedy) => {
+                let value = reader.remaining().to_string();
+                reader.set_cursor(reader.input().len());
+                ArgumentValue::String(value)
+            }
+            ArgumentType::Player => {
+                let selector = EntitySelector::parse(reader)?;
+                if selector == EntitySelector::AllPlayers {
+                    bail!("Only one player is allowed, but the provided selector allows more than one");
+                }
+                ArgumentValue::Players(selector)
+            }
+            ArgumentType::Players => ArgumentValue::Players(EntitySelector::parse(reader)?),
+            ArgumentType::BlockPos => ArgumentValue::BlockPos(Coordinates::parse(reader)?),
+            ArgumentType::Vec3 => ArgumentValue::Vec3(Coordinates::parse_vec3(reader)?),
+            ArgumentType::Identifier => match reader.read_until_space() {
+                "" => bail!("Expected a resource location"),
+                identifier => ArgumentValue::Identifier(identifier.parse()?),
+            },
+            ArgumentType::ScoreHolders => ArgumentValue::ScoreHolders(ScoreHolder::parse(reader)?),
+        })
+    }
+}
+
+fn check_range<T: PartialOrd + std::fmt::Display>(value: T, min: Option<T>, max: Option<T>) -> anyhow::Result<()> {
+    if let Some(min) = min.filter(|min| value < *min) {
+        bail!("Value must not be less than {}, found {}", min, value);
+    }
+    if let Some(max) = max.filter(|max| value > *max) {
+        bail!("Value must not be more than {}, found {}", max, value);
+    }
+    Ok(())
+}
+
+/// Player name or target selector, resolved against the online players when the command runs.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum EntitySelector {
+    Name(String),
+    /// `@a`
+    AllPlayers,
+    /// `@p`, the executing player until positions are tracked.
+    NearestPlayer,
+    /// `@r`
+    RandomPlayer,
+    /// `@s`
+    Executor,
+}
+
+impl EntitySelector {
+    pub fn parse(reader: &mut StringReader) -> anyhow::Result<Self> {
+        if reader.peek() != Some('@') {
+            let name = reader.read_until_space();
+            if name.is_empty() || name.len() > 16 {
+                bail!("Invalid player name '{}'", name);
+            }
+            return Ok(EntitySelector::Name(name.to_string()));
+        }
+        reader.skip();
+        let selector = match reader.peek() {
+            Some('a') => EntitySelector::AllPlayers,
+            Some('p') => EntitySelector::NearestPlayer,
+            Some('r') => EntitySelector::RandomPlayer,
+            Some('s') => EntitySelector::Executor,
+            Some(other) => bail!("Unknown selector type '@{}'", other),
+            None => bail!("Missing selector type"),
+        };
+        reader.skip();
+        if reader.peek() == Some('[') {
+            bail!("Selector options are not supported");
+        }
+        Ok(selector)
+    }
+
+    pub fn resolve(&self, source: &CommandSource) -> anyhow::Result<Vec<ConnectionHandle>> {
+        let connections = &source.connections;
+        let executor = match &source.sender {
+            CommandSender::Player(connection) => Some(connection.clone()),
+            CommandSender::Console | CommandSender::Remote(_) => None,
+        };
+        Ok(match self {
+            EntitySelector::Name(name) => connections.find_player(name).into_iter().collect(),
+            EntitySelector::AllPlayers => connections.players(),
+            EntitySelector::NearestPlayer => executor.or_else(|| connections.players().into_iter().next()).into_iter().collect(),
+            EntitySelector::RandomPlayer => {
+                let players = connections.players();
+                if players.is_empty() {
+                    Vec::new()
+                } else {
+                    let index = RandomState::new().hash_one(players.len()) as usize % players.len();
+                    vec![players[index].clone()]
+                }
+            }
+            EntitySelector::Executor => match executor {
+                Some(executor) => vec![executor],
+                None => bail!("A player is required to run this command here"),
+            },
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScoreHolder {
+    Selector(EntitySelector),
+    /// `*`
+    All,
+    /// Any name, players do not have to be online and other holders are not players at all.
+    Name(String),
+}
+
+impl ScoreHolder {
+    pub fn parse(reader: &mut StringReader) -> anyhow::Result<Self> {
+        match reader.peek() {
+            Some('@') => Ok(ScoreHolder::Selector(EntitySelector::parse(reader)?)),
+            Some('*') => {
+                reader.skip();
+                Ok(ScoreHolder::All)
+            }
+            _ => match reader.read_until_space() {
+                "" => bail!("Expected a score holder"),
+                name => Ok(ScoreHolder::Name(name.to_string())),
+            },
+        }
+    }
+
+    pub fn resolve(&self, source: &CommandSource) -> anyhow::Result<Vec<String>> {
+        Ok(match self {
+            ScoreHolder::Selector(selector) => selector.resolve(source)?.iter().filter_map(ConnectionHandle::username).collect(),
+            ScoreHolder::All => scoreboard().read().unwrap().holders().map(str::to_string).collect(),
+            ScoreHolder::Name(name) => vec![name.clone()],
+        })
+    }
+}
+
+/// Single axis of a position argument, `~` makes it relative to the source.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Coordinate {
+    pub value: f64,
+    pub relative: bool,
+}
+
+impl Coordinate {
+    fn parse(reader: &mut StringReader) -> anyhow::Result<Self> {
+        Self::parse_with(reader, |reader| Ok(reader.read_int()? as f64))
+    }
+
+    /// An axis of a [`ArgumentType::Vec3`], `center` adds half a block to whole absolute values.
+    fn parse_decimal(reader: &mut StringReader, center: bool) -> anyhow::Result<Self> {
+        Self::parse_with(reader, |reader| {
+            let start = reader.cursor();
+            let value = reader.read_double()?;
+            let whole = !reader.input()[start..reader.cursor()].contains('.');
+            Ok(if center && whole { value + 0.5 } else { value })
+        })
+    }
+
+    fn parse_with(reader: &mut StringReader, absolute: impl FnOnce(&mut StringReader) -> anyhow::Result<f64>) -> anyhow::Result<Self> {
+        if reader.peek() == Some('~') {
+            reader.skip();
+            let value = if reader.peek().is_some_and(|c| c != ' ') { reader.read_double()? } else { 0.0 };
+            return Ok(Coordinate { value, relative: true });
+        }
+        if reader.peek() == Some('^') {
+            bail!("Local coordinates are not supported");
+        }
+        Ok(Coordinate { value: absolute(reader)?, relative: false })
+    }
+
+    pub fn resolve(&self, origin: f64) -> f64 {
+        if self.relative { origin + self.value } else { self.value }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Coordinates {
+    pub x: Coordinate,
+    pub y: Coordinate,
+    pub z: Coordinate,
+}
+
+impl Coordinates {
+    pub fn parse(reader: &mut StringReader) -> anyhow::Result<Self> {
+        let x = Coordinate::parse(reader)?;
+        reader.expect(' ').map_err(|_| anyhow::anyhow!("Incomplete position, expected 3 coordinates"))?;
+        let y = Coordinate::parse(reader)?;
+        reader.expect(' ').map_err(|_| anyhow::anyhow!("Incomplete position, expected 3 coordinates"))?;
+        let z = Coordinate::parse(reader)?;
+        Ok(Coordinates { x, y, z })
+    }
+
+    pub fn parse_vec3(reader: &mut StringReader) -> anyhow::Result<Self> {
+        let x = Coordinate::parse_decimal(reader, true)?;
+        reader.expect(' ').map_err(|_| anyhow::anyhow!("Incomplete position, expected 3 coordinates"))?;
+        let y = Coordinate::parse_decimal(reader, false)?;
+        reader.expect(' ').map_err(|_| anyhow::anyhow!("Incomplete position, expected 3 coordinates"))?;
+        let z = Coordinate::parse_decimal(reader, true)?;
+        Ok(Coordinates { x, y, z })
+    }
+
+    pub fn resolve(&self, origin: Vec3) -> Vec3 {
+        Vec3::new(self.x.resolve(origin.x), self.y.resolve(origin.y), self.z.resolve(origin.z))
+    }
+
+    pub fn resolve_block_pos(&self, origin: BlockPos) -> BlockPos {
+        BlockPos {
+            x: self.x.resolve(origin.x as f64).floor() as i32,
+            y: self.y.resolve(origin.y as f64).floor() as i32,
+            z: self.z.resolve(origin.z as f64).floor() as i32,
+        }
+    }
+}
@@ -0,0 +1,1299 @@
+use dolls_core::advancement::advancements;
+use dolls_core::datatype::{Identifier, Uuid};
+use dolls_core::recipe::recipes;
+use dolls_core::text::{ClickEvent, HoverEvent, Style, TextComponent};
+use std::net::IpAddr;
+use std::path::Path;
+use std::time::Duration;
+use dolls_world::level::{level, LevelData};
+use dolls_world::weather::Weather;
+use dolls_world::game_rules::{GameRuleValue, GAME_RULES};
+use dolls_world::world::Dimension;
+use dolls_world::border::DEFAULT_BORDER_DIAMETER;
+use dolls_entities::prelude::entities;
+use dolls_world::scoreboard::{color_from_name, scoreboard, CollisionRule, DisplaySlot, Objective, RenderType, Team, Visibility, COLOR_NAMES, CRITERIA};
+use dolls_network::prelude::{add_objective, add_score, add_team, broadcast_chat, join_team, leave_team, modify_objective, modify_team, player_position, damage, set_day_time, set_game_mode, set_spawn_point, set_game_rule, set_weather, remove_objective, remove_team, reset_score, set_display_slot, set_score, grant_advancements, grant_criterion, revoke_advancements, revoke_criterion, handler_metrics, lock_recipes, set_ai_debug, packet_dump_enabled, set_packet_dump, transfer, reset_handler_metrics, resize_border, save_all_entities, save_all_health, save_all_statistics, set_border_center, set_border_warning_delay,
+    set_border_warning_distance, unlock_recipes, set_world_spawn, world_border, offline_uuid, ops, whitelist, banned_ips, banned_players, BanEntry, BannedPlayer, ChatLine, ConnectionHandle, DamageSource, DollNetworkServer, GameMode, Operator, SpawnPoint, WhitelistEntry, Sound, SystemChatMessage, DAY_LENGTH, SoundCategory, TitleTimes, PARTICLE_RANGE};
+use crate::prelude::{argument, literal, refresh_permissions, register_command, ArgumentType, ArgumentValue, CommandContext, CommandNode, CommandSender, Coordinates, StringKind};
+
+/// Registers the vanilla commands the server can already back: `stop`, `list`, `say`, `seed`, `save-all`,
+/// `op`, `deop`, `whitelist`, `kick`, `ban`, `ban-ip`, `pardon`, `pardon-ip`, `worldborder`, `recipe`, `advancement`, `scoreboard`, `team`, `title`, `playsound`, `gamemode`, `kill`, `spawnpoint`, `setworldspawn`, `time`, `weather` and `gamerule`, plus `debug handlers` to inspect packet handler timings, `debug packets` to dump packets to the log and `debug ai` to show what
+/// the AI of nearby entities reported.
+pub fn register_builtin_commands(server: &DollNetworkServer) {
+    let shutdown = server.shutdown_handle();
+    register_command(literal("stop").requires(4).executes(move |context| {
+        context.source.send_message(TextComponent::text("Stopping the server"));
+        shutdown.shutdown();
+        Ok(())
+    }));
+
+    let max_players = server.config().server.max_players;
+    register_command(literal("list").executes(move |context| {
+        let names = context.source.connections.players().iter()
+            .filter_map(|player| player.username())
+            .collect::<Vec<_>>();
+        context.source.send_message(TextComponent::text(format!(
+            "There are {} of a max of {} players online: {}", names.len(), max_players, names.join(", "))));
+        Ok(())
+    }));
+
+    register_command(literal("say").requires(2).then(
+        argument("message", ArgumentType::String(StringKind::Greedy)).executes(|context| {
+            broadcast_chat(&context.source.connections, &ChatLine {
+                chat_type: Identifier::minecraft("say_command"),
+                sender_name: TextComponent::text(context.source.name()),
+                content: TextComponent::text(context.get_string("message")?),
+            })?;
+            Ok(())
+        })
+    ));
+
+    register_command(literal("seed").requires(2).executes(|context| {
+        let seed = level().read().unwrap().seed.to_string();
+        let seed_text = TextComponent::text(format!("[{}]", seed)).with_style(Style {
+            color: Some("green".to_string()),
+            insertion: Some(seed.clone()),
+            click_event: Some(ClickEvent { action: "copy_to_clipboard".to_string(), value: seed }),
+            hover_event: Some(HoverEvent {
+                action: "show_text".to_string(),
+                contents: Box::new(TextComponent::translatable("chat.copy.click", vec![])),
+            }),
+            ..Default::default()
+        });
+        context.source.send_message(TextComponent::translatable("commands.seed.success", vec![seed_text]).fallback("Seed: %s"));
+        Ok(())
+    }));
+
+    let level_path = Path::new(&server.config().world.level_name).join("level.dat");
+    let scoreboard_path = Path::new(&server.config().world.level_name).join("data").join("scoreboard.dat");
+    register_command(literal("save-all").requires(4).executes(move |context| {
+        context.source.send_message(TextComponent::translatable("commands.save.saving", vec![]).fallback("Saving the game (this may take a moment!)"));
+        level().read().unwrap().save(&level_path)?;
+        scoreboard().read().unwrap().save(&scoreboard_path)?;
+        save_all_entities();
+        for dimension in Dimension::ALL {
+            dimension.world().write().unwrap().flush()?;
+        }
+        save_all_statistics(&context.source.connections);
+        save_all_health(&context.source.connections);
+        context.source.send_message(TextComponent::translatable("commands.save.success", vec![]).fallback("Saved the game"));
+        Ok(())
+    }));
+
+    let op_level = server.config().server.op_permission_level;
+    register_command(literal("op").requires(3).then(
+        argument("targets", ArgumentType::Players).executes(move |context| change_operators(context, Some(op_level)))
+    ));
+    register_command(literal("deop").requires(3).then(
+        argument("targets", ArgumentType::Players).executes(|context| change_operators(context, None))
+    ));
+
+    register_command(literal("whitelist").requires(3)
+        .then(literal("on").executes(|context| toggle_whitelist(context, true)))
+        .then(literal("off").executes(|context| toggle_whitelist(context, false)))
+        .then(literal("list").executes(|context| {
+            let names = whitelist().read().unwrap().iter().map(|entry| entry.name.clone()).collect::<Vec<_>>();
+            context.source.send_message(match names.is_empty() {
+                true => TextComponent::translatable("commands.whitelist.none", vec![]).fallback("There are no whitelisted players"),
+                false => TextComponent::translatable("commands.whitelist.list", vec![TextComponent::text(names.len().to_string()), TextComponent::text(names.join(", "))])
+                    .fallback("There are %s whitelisted player(s): %s"),
+            });
+            Ok(())
+        }))
+        .then(literal("reload").executes(|context| {
+            whitelist().write().unwrap().reload()?;
+            context.source.send_message(TextComponent::translatable("commands.whitelist.reloaded", vec![]).fallback("Reloaded the whitelist"));
+            Ok(())
+        }))
+        .then(literal("add").then(argument("player", ArgumentType::String(StringKind::Word)).executes(|context| {
+            let (uuid, name) = player_profile(context, context.get_string("player")?);
+            let mut whitelist = whitelist().write().unwrap();
+            if !whitelist.add(WhitelistEntry { uuid, name: name.clone() }) {
+                anyhow::bail!("Player is already whitelisted");
+            }
+            whitelist.save()?;
+            context.source.send_message(TextComponent::translatable("commands.whitelist.add.success", vec![TextComponent::text(name)]).fallback("Added %s to the whitelist"));
+            Ok(())
+        })))
+        .then(literal("remove").then(argument("player", ArgumentType::String(StringKind::Word)).executes(|context| {
+            let (uuid, name) = player_profile(context, context.get_string("player")?);
+            let mut whitelist = whitelist().write().unwrap();
+            if whitelist.remove(uuid).is_none() {
+                anyhow::bail!("Player is not whitelisted");
+            }
+            whitelist.save()?;
+            context.source.send_message(TextComponent::translatable("commands.whitelist.remove.success", vec![TextComponent::text(name)]).fallback("Removed %s from the whitelist"));
+            Ok(())
+        })))
+    );
+
+    register_command(literal("kick").requires(3).then(argument("targets", ArgumentType::Players)
+        .executes(|context| kick_players(context, TextComponent::translatable("multiplayer.disconnect.kicked", vec![]).fallback("Kicked by an operator")))
+        .then(argument("reason", ArgumentType::String(StringKind::Greedy)).executes(|context| kick_players(context, TextComponent::text(context.get_string("reason")?))))
+    ));
+    register_command(literal("transfer").requires(3).then(argument("hostname", ArgumentType::String(StringKind::Word))
+        .executes(|context| transfer_players(context, false, false))
+        .then(argument("port", ArgumentType::Integer { min: Some(1), max: Some(65535) })
+            .executes(|context| transfer_players(context, true, false))
+            .then(argument("players", ArgumentType::Players).executes(|context| transfer_players(context, true, true))))
+    ));
+    register_command(literal("ban").requires(3).then(argument("targets", ArgumentType::String(StringKind::Word))
+        .executes(|context| ban_player(context, None))
+        .then(argument("reason", ArgumentType::String(StringKind::Greedy)).executes(|context| ban_player(context, Some(context.get_string("reason")?.to_string()))))
+    ));
+    register_command(literal("ban-ip").requires(3).then(argument("target", ArgumentType::String(StringKind::Word))
+        .executes(|context| ban_ip(context, None))
+        .then(argument("reason", ArgumentType::String(StringKind::Greedy)).executes(|context| ban_ip(context, Some(context.get_string("reason")?.to_string()))))
+    ));
+    register_command(literal("pardon").requires(3).then(argument("targets", ArgumentType::String(StringKind::Word)).executes(|context| {
+        let (uuid, name) = player_profile(context, context.get_string("targets")?);
+        let mut banned_players = banned_players().write().unwrap();
+        if banned_players.remove(uuid).is_none() {
+            anyhow::bail!("Nothing changed. The player isn't banned");
+        }
+        banned_players.save()?;
+        context.source.send_message(TextComponent::translatable("commands.pardon.success", vec![TextComponent::text(name)]).fallback("Unbanned %s"));
+        Ok(())
+    })));
+    register_command(literal("pardon-ip").requires(3).then(argument("target", ArgumentType::String(StringKind::Word)).executes(|context| {
+        let Ok(ip) = context.get_string("target")?.parse::<IpAddr>() else { anyhow::bail!("Invalid IP address") };
+        let mut banned_ips = banned_ips().write().unwrap();
+        if banned_ips.remove(ip).is_none() {
+            anyhow::bail!("Nothing changed. That IP isn't banned");
+        }
+        banned_ips.save()?;
+        context.source.send_message(TextComponent::translatable("commands.pardonip.success", vec![TextComponent::text(ip.to_string())]).fallback("Unbanned IP %s"));
+        Ok(())
+    })));
+
+    register_command(literal("debug").requires(3)
+        .then(literal("handlers")
+            .executes(|context| {
+                let metrics = handler_metrics();
+                if metrics.is_empty() {
+                    context.source.send_message(TextComponent::text("No packet was handled yet"));
+                }
+                for (packet_type, stats) in metrics {
+                    context.source.send_message(TextComponent::text(format!(
+                        "{:?} {}: {} calls, {} errors, mean {:?}, p99 {:?}, max {:?}",
+                        packet_type.state(), packet_type.name(), stats.invocations, stats.errors,
+                        stats.mean(), stats.quantile(0.99), stats.max)));
+                }
+                Ok(())
+            })
+            .then(literal("reset").executes(|context| {
+                reset_handler_metrics();
+                context.source.send_message(TextComponent::text("Reset packet handler metrics"));
+                Ok(())
+            })))
+        .then(literal("packets")
+            .executes(|context| {
+                let state = if packet_dump_enabled() { "on" } else { "off" };
+                context.source.send_message(TextComponent::text(format!("Packet dump is {}", state)));
+                Ok(())
+            })
+            .then(literal("on").executes(|context| toggle_packet_dump(context, true)))
+            .then(literal("off").executes(|context| toggle_packet_dump(context, false))))
+        .then(literal("ai")
+            .executes(describe_ai)
+            .then(literal("on").executes(|context| toggle_ai_debug(context, true)))
+            .then(literal("off").executes(|context| toggle_ai_debug(context, false)))));
+
+    register_command(literal("worldborder").requires(2)
+        .then(literal("get").executes(|context| {
+            let diameter = TextComponent::text(format!("{:.0}", world_border().diameter()));
+            context.source.send_message(TextComponent::translatable("commands.worldborder.get", vec![diameter])
+                .fallback("The world border is currently %s block(s) wide"));
+            Ok(())
+        }))
+        .then(literal("set").then(
+            argument("distance", ArgumentType::Double { min: Some(1.0), max: Some(DEFAULT_BORDER_DIAMETER) })
+                .executes(|context| set_border_size(context, 0))
+                .then(argument("time", ArgumentType::Integer { min: Some(0), max: None })
+                    .executes(|context| set_border_size(context, context.get_integer("time")?)))
+        ))
+        .then(literal("center").then(argument("x", ArgumentType::Double { min: None, max: None }).then(
+            argument("z", ArgumentType::Double { min: None, max: None }).executes(|context| {
+                let (x, z) = (context.get_double("x")?, context.get_double("z")?);
+                set_border_center(&context.source.connections, x, z)?;
+                let center = vec![TextComponent::text(format!("{:.2}", x)), TextComponent::text(format!("{:.2}", z))];
+                context.source.send_message(TextComponent::translatable("commands.worldborder.center.success", center)
+                    .fallback("Set the center of the world border to %s, %s"));
+                Ok(())
+            })
+        )))
+        .then(literal("warning")
+            .then(literal("distance").then(argument("distance", ArgumentType::Integer { min: Some(0), max: None }).executes(|context| {
+                let distance = context.get_integer("distance")?;
+                set_border_warning_distance(&context.source.connections, distance)?;
+                context.source.send_message(TextComponent::translatable("commands.worldborder.warning.distance.success", vec![TextComponent::text(distance.to_string())])
+                    .fallback("Set the world border warning distance to %s block(s)"));
+                Ok(())
+            })))
+            .then(literal("time").then(argument("time", ArgumentType::Integer { min: Some(0), max: None }).executes(|context| {
+                let time = context.get_integer("time")?;
+                set_border_warning_delay(&context.source.connections, time)?;
+                context.source.send_message(TextComponent::translatable("commands.worldborder.warning.time.success", vec![TextComponent::text(time.to_string())])
+                    .fallback("Set the world border warning time to %s second(s)"));
+                Ok(())
+            })))
+        )
+    );
+
+    register_command(literal("recipe").requires(2)
+        .then(literal("give").then(argument("targets", ArgumentType::Players).then(
+            argument("recipe", ArgumentType::String(StringKind::Greedy)).executes(|context| change_recipes(context, true))
+        )))
+        .then(literal("take").then(argument("targets", ArgumentType::Players).then(
+            argument("recipe", ArgumentType::String(StringKind::Greedy)).executes(|context| change_recipes(context, false))
+        )))
+    );
+
+    register_command(literal("advancement").requires(2)
+        .then(literal("grant").then(argument("targets", ArgumentType::Players)
+            .then(literal("everything").executes(|context| change_advancements(context, true, true)))
+            .then(literal("only").then(argument("advancement", ArgumentType::String(StringKind::Greedy))
+                .executes(|context| change_advancements(context, true, false))))
+        ))
+        .then(literal("revoke").then(argument("targets", ArgumentType::Players)
+            .then(literal("everything").executes(|context| change_advancements(context, false, true)))
+            .then(literal("only").then(argument("advancement", ArgumentType::String(StringKind::Greedy))
+                .executes(|context| change_advancements(context, false, false))))
+        ))
+    );
+
+    register_command(literal("scoreboard").requires(2)
+        .then(literal("objectives")
+            .then(literal("list").executes(list_objectives))
+            .then(literal("add").then(argument("objective", ArgumentType::String(StringKind::Word)).then(
+                argument("criteria", ArgumentType::String(StringKind::Word)).suggests(|_, partial| matching(CRITERIA.iter().copied(), partial))
+                    .executes(|context| add_scoreboard_objective(context, None))
+                    .then(argument("displayName", ArgumentType::String(StringKind::Greedy))
+                        .executes(|context| add_scoreboard_objective(context, Some(parse_text(context.get_string("displayName")?)))))
+            )))
+            .then(literal("remove").then(objective_argument().executes(|context| {
+                let name = context.get_string("objective")?;
+                if !remove_objective(&context.source.connections, name)? {
+                    anyhow::bail!("Unknown scoreboard objective '{}'", name);
+                }
+                context.source.send_message(TextComponent::translatable("commands.scoreboard.objectives.remove.success", vec![TextComponent::text(name)])
+                    .fallback("Removed objective %s"));
+                Ok(())
+            })))
+            .then(literal("setdisplay").then(
+                argument("slot", ArgumentType::String(StringKind::Word))
+                    .suggests(|_, partial| matching(DisplaySlot::all().map(DisplaySlot::name), partial))
+                    .executes(|context| set_scoreboard_display(context, false))
+                    .then(objective_argument().executes(|context| set_scoreboard_display(context, true)))
+            ))
+            .then(literal("modify").then(objective_argument()
+                .then(literal("displayname").then(argument("displayName", ArgumentType::String(StringKind::Greedy)).executes(|context| {
+                    let (name, display_name) = (context.get_string("objective")?, parse_text(context.get_string("displayName")?));
+                    modify_objective(&context.source.connections, name, |objective| objective.display_name = display_name.clone())?;
+                    context.source.send_message(TextComponent::translatable("commands.scoreboard.objectives.modify.displayname", vec![TextComponent::text(name), display_name])
+                        .fallback("Changed the display name of %s to %s"));
+                    Ok(())
+                })))
+                .then(literal("rendertype")
+                    .then(literal("integer").executes(|context| set_render_type(context, RenderType::Integer)))
+                    .then(literal("hearts").executes(|context| set_render_type(context, RenderType::Hearts))))
+            ))
+        )
+        .then(literal("players")
+            .then(literal("list")
+                .executes(|context| {
+                    let holders = scoreboard().read().unwrap().holders().map(str::to_string).collect::<Vec<_>>();
+                    context.source.send_message(match holders.is_empty() {
+                        true => TextComponent::translatable("commands.scoreboard.players.list.empty", vec![]).fallback("There are no tracked entities"),
+                        false => TextComponent::translatable("commands.scoreboard.players.list.success",
+                            vec![TextComponent::text(holders.len().to_string()), TextComponent::text(holders.join(", "))])
+                            .fallback("There are %s tracked entity/entities: %s"),
+                    });
+                    Ok(())
+                })
+                .then(argument("target", ArgumentType::ScoreHolders).executes(list_scores)))
+            .then(literal("get").then(argument("target", ArgumentType::ScoreHolders).then(objective_argument().executes(|context| {
+                let (holder, name) = (single_holder(context)?, context.get_string("objective")?);
+                let scoreboard = scoreboard().read().unwrap();
+                let objective = scoreboard.objective(name).ok_or_else(|| anyhow::anyhow!("Unknown scoreboard objective '{}'", name))?;
+                let Some(score) = scoreboard.score(&holder, name) else {
+                    anyhow::bail!("Can't get value of {} for {}; none is set", name, holder);
+                };
+                let arguments = vec![TextComponent::text(holder.as_str()), TextComponent::text(score.value.to_string()), objective.display_name.clone()];
+                context.source.send_message(TextComponent::translatable("commands.scoreboard.players.get.success", arguments).fallback("%s has %s %s"));
+                Ok(())
+            }))))
+            .then(literal("set").then(score_change_arguments(None, ScoreChange::Set)))
+            .then(literal("add").then(score_change_arguments(Some(0), ScoreChange::Add)))
+            .then(literal("remove").then(score_change_arguments(Some(0), ScoreChange::Remove)))
+            .then(literal("reset").then(argument("targets", ArgumentType::ScoreHolders)
+                .executes(|context| reset_scores(context, false))
+                .then(objective_argument().executes(|context| reset_scores(context, true)))
+            ))
+        )
+    );
+
+    register_command(literal("team").requires(2)
+        .then(literal("list")
+            .executes(|context| {
+                let names = scoreboard().read().unwrap().teams().map(|team| team.name.clone()).collect::<Vec<_>>();
+                context.source.send_message(match names.is_empty() {
+                    true => TextComponent::translatable("commands.team.list.teams.empty", vec![]).fallback("There are no teams"),
+                    false => TextComponent::translatable("commands.team.list.teams.success",
+                        vec![TextComponent::text(names.len().to_string()), TextComponent::text(names.join(", "))])
+                        .fallback("There are %s team(s): %s"),
+                });
+                Ok(())
+            })
+            .then(team_argument().executes(|context| {
+                let name = context.get_string("team")?;
+                let team = scoreboard().read().unwrap().team(name).cloned().ok_or_else(|| anyhow::anyhow!("Unknown team '{}'", name))?;
+                let members = team.members().collect::<Vec<_>>();
+                context.source.send_message(match members.is_empty() {
+                    true => TextComponent::translatable("commands.team.list.members.empty", vec![team.display_name.clone()])
+                        .fallback("There are no members on team %s"),
+                    false => TextComponent::translatable("commands.team.list.members.success",
+                        vec![team.display_name.clone(), TextComponent::text(members.len().to_string()), TextComponent::text(members.join(", "))])
+                        .fallback("Team %s has %s member(s): %s"),
+                });
+                Ok(())
+            })))
+        .then(literal("add").then(argument("team", ArgumentType::String(StringKind::Word))
+            .executes(|context| add_scoreboard_team(context, None))
+            .then(argument("displayName", ArgumentType::String(StringKind::Greedy))
+                .executes(|context| add_scoreboard_team(context, Some(parse_text(context.get_string("displayName")?)))))
+        ))
+        .then(literal("remove").then(team_argument().executes(|context| {
+            let name = context.get_string("team")?;
+            if !remove_team(&context.source.connections, name)? {
+                anyhow::bail!("Unknown team '{}'", name);
+            }
+            context.source.send_message(TextComponent::translatable("commands.team.remove.success", vec![TextComponent::text(name)])
+                .fallback("Removed team %s"));
+            Ok(())
+        })))
+        .then(literal("empty").then(team_argument().executes(|context| {
+            let name = context.get_string("team")?;
+            let members = scoreboard().read().unwrap().team(name).ok_or_else(|| anyhow::anyhow!("Unknown team '{}'", name))?
+                .members().map(str::to_string).collect::<Vec<_>>();
+            if members.is_empty() {
+                anyhow::bail!("Nothing changed. That team is already empty");
+            }
+            leave_team(&context.source.connections, &members)?;
+            context.source.send_message(TextComponent::translatable("commands.team.empty.success",
+                vec![TextComponent::text(members.len().to_string()), TextComponent::text(name)])
+                .fallback("Removed %s member(s) from team %s"));
+            Ok(())
+        })))
+        .then(literal("join").then(team_argument()
+            .executes(|context| join_scoreboard_team(context, vec![context.source.name()]))
+            .then(argument("members", ArgumentType::ScoreHolders).executes(|context| join_scoreboard_team(context, context.get_score_holders("members")?)))
+        ))
+        .then(literal("leave").then(argument("members", ArgumentType::ScoreHolders).executes(|context| {
+            let members = context.get_score_holders("members")?;
+            leave_team(&context.source.connections, &members)?;
+            context.source.send_message(match members.as_slice() {
+                [member] => TextComponent::translatable("commands.team.leave.success.single", vec![TextComponent::text(member.as_str())])
+                    .fallback("Removed %s from any team"),
+                members => TextComponent::translatable("commands.team.leave.success.multiple", vec![TextComponent::text(members.len().to_string())])
+                    .fallback("Removed %s members from any team"),
+            });
+            Ok(())
+        })))
+        .then(literal("modify").then(team_argument()
+            .then(literal("displayName").then(argument("displayName", ArgumentType::String(StringKind::Greedy)).executes(|context| {
+                let display_name = parse_text(context.get_string("displayName")?);
+                modify_scoreboard_team(context, |team| team.display_name = display_name.clone(), |team| {
+                    TextComponent::translatable("commands.team.option.name.success", vec![team]).fallback("Updated the name of team %s")
+                })
+            })))
+            .then(literal("color").then(argument("value", ArgumentType::String(StringKind::Word))
+                .suggests(|_, partial| matching(COLOR_NAMES.iter().copied().chain(["reset"]), partial))
+                .executes(|context| {
+                    let name = context.get_string("value")?;
+                    let color = color_from_name(name).ok_or_else(|| anyhow::anyhow!("Unknown color '{}'", name))?;
+                    modify_scoreboard_team(context, |team| team.color = color, |team| {
+                        TextComponent::translatable("commands.team.option.color.success", vec![team, TextComponent::text(name)])
+                            .fallback("Updated the color for team %s to %s")
+                    })
+                })))
+            .then(literal("prefix").then(argument("prefix", ArgumentType::String(StringKind::Greedy)).executes(|context| {
+                let prefix = parse_text(context.get_string("prefix")?);
+                modify_scoreboard_team(context, |team| team.prefix = prefix.clone(), |_| {
+                    TextComponent::translatable("commands.team.option.prefix.success", vec![prefix.clone()]).fallback("Team prefix set to %s")
+                })
+            })))
+            .then(literal("suffix").then(argument("suffix", ArgumentType::String(StringKind::Greedy)).executes(|context| {
+                let suffix = parse_text(context.get_string("suffix")?);
+                modify_scoreboard_team(context, |team| team.suffix = suffix.clone(), |_| {
+                    TextComponent::translatable("commands.team.option.suffix.success", vec![suffix.clone()]).fallback("Team suffix set to %s")
+                })
+            })))
+            .then(literal("friendlyFire").then(argument("allowed", ArgumentType::Bool).executes(|context| {
+                let allowed = context.get_bool("allowed")?;
+                modify_scoreboard_team(context, |team| team.friendly_fire = allowed, |team| match allowed {
+                    true => TextComponent::translatable("commands.team.option.friendlyfire.enabled", vec![team]).fallback("Enabled friendly fire for team %s"),
+                    false => TextComponent::translatable("commands.team.option.friendlyfire.disabled", vec![team]).fallback("Disabled friendly fire for team %s"),
+                })
+            })))
+            .then(literal("seeFriendlyInvisibles").then(argument("allowed", ArgumentType::Bool).executes(|context| {
+                let allowed = context.get_bool("allowed")?;
+                modify_scoreboard_team(context, |team| team.see_friendly_invisibles = allowed, |team| match allowed {
+                    true => TextComponent::translatable("commands.team.option.seeFriendlyInvisibles.enabled", vec![team])
+                        .fallback("Team %s can now see invisible teammates"),
+                    false => TextComponent::translatable("commands.team.option.seeFriendlyInvisibles.disabled", vec![team])
+                        .fallback("Team %s can no longer see invisible teammates"),
+                })
+            })))
+            .then(literal("nametagVisibility").then(visibility_argument().executes(|context| {
+                let visibility = visibility_value(context)?;
+                modify_scoreboard_team(context, |team| team.name_tag_visibility = visibility, |team| {
+                    TextComponent::translatable("commands.team.option.nametagVisibility.success", vec![team, TextComponent::text(visibility.name())])
+                        .fallback("Nametag visibility for team %s is now \"%s\"")
+                })
+            })))
+            .then(literal("deathMessageVisibility").then(visibility_argument().executes(|context| {
+                let visibility = visibility_value(context)?;
+                modify_scoreboard_team(context, |team| team.death_message_visibility = visibility, |team| {
+                    TextComponent::translatable("commands.team.option.deathMessageVisibility.success", vec![team, TextComponent::text(visibility.name())])
+                        .fallback("Death message visibility for team %s is now \"%s\"")
+                })
+            })))
+            .then(literal("collisionRule").then(argument("value", ArgumentType::String(StringKind::Word))
+                .suggests(|_, partial| matching(CollisionRule::ALL.into_iter().map(CollisionRule::name), partial))
+                .executes(|context| {
+                    let name = context.get_string("value")?;
+                    let rule = CollisionRule::from_name(name).ok_or_else(|| anyhow::anyhow!("Unknown collision rule '{}'", name))?;
+                    modify_scoreboard_team(context, |team| team.collision_rule = rule, |team| {
+                        TextComponent::translatable("commands.team.option.collisionRule.success", vec![team, TextComponent::text(rule.name())])
+                            .fallback("Collision rule for team %s is now \"%s\"")
+                    })
+                })))
+        ))
+    );
+
+    register_command(literal("title").requires(2).then(argument("targets", ArgumentType::Players)
+        .then(literal("clear").executes(|context| show_titles(context, "commands.title.cleared", "Cleared titles for", ConnectionHandle::clear_title)))
+        .then(literal("reset").executes(|context| show_titles(context, "commands.title.reset", "Reset title options for", ConnectionHandle::reset_title)))
+        .then(literal("title").then(argument("title", ArgumentType::String(StringKind::Greedy)).executes(|context| {
+            let title = parse_text(context.get_string("title")?);
+            show_titles(context, "commands.title.show.title", "Showing new title for", |player| player.send_title(title.clone()))
+        })))
+        .then(literal("subtitle").then(argument("title", ArgumentType::String(StringKind::Greedy)).executes(|context| {
+            let subtitle = parse_text(context.get_string("title")?);
+            show_titles(context, "commands.title.show.subtitle", "Showing new subtitle for", |player| player.send_subtitle(subtitle.clone()))
+        })))
+        .then(literal("actionbar").then(argument("title", ArgumentType::String(StringKind::Greedy)).executes(|context| {
+            let text = parse_text(context.get_string("title")?);
+            show_titles(context, "commands.title.show.actionbar", "Showing new actionbar title for", |player| player.send_action_bar(text.clone()))
+        })))
+        .then(literal("times").then(argument("fadeIn", ArgumentType::Integer { min: Some(0), max: None }).then(
+            argument("stay", ArgumentType::Integer { min: Some(0), max: None }).then(
+                argument("fadeOut", ArgumentType::Integer { min: Some(0), max: None }).executes(|context| {
+                    let times = TitleTimes { fade_in: context.get_integer("fadeIn")?, stay: context.get_integer("stay")?, fade_out: context.get_integer("fadeOut")? };
+                    show_titles(context, "commands.title.times", "Changed title display times for", |player| player.set_title_times(times))
+                })
+            )
+        )))
+    ));
+
+    register_command(literal("playsound").requires(2).then(argument("sound", ArgumentType::Identifier).then(
+        argument("source", ArgumentType::String(StringKind::Word))
+            .suggests(|_, partial| matching(SoundCategory::ALL.into_iter().map(SoundCategory::name), partial))
+            .then(argument("targets", ArgumentType::Players)
+                .executes(|context| play_sound_command(context, SoundArguments::Targets))
+                .then(argument("pos", ArgumentType::Vec3)
+                    .executes(|context| play_sound_command(context, SoundArguments::Position))
+                    .then(argument("volume", ArgumentType::Double { min: Some(0.0), max: None })
+                        .executes(|context| play_sound_command(context, SoundArguments::Volume))
+                        .then(argument("pitch", ArgumentType::Double { min: Some(0.0), max: Some(2.0) })
+                            .executes(|context| play_sound_command(context, SoundArguments::Pitch)))
+                    )
+                )
+            )
+    )));
+
+    register_command(literal("gamemode").requires(2).then(argument("gamemode", ArgumentType::String(StringKind::Word))
+        .suggests(|_, partial| matching(GameMode::ALL.into_iter().map(GameMode::name), partial))
+        .executes(|context| change_game_mode(context, vec![own_player(context)?]))
+        .then(argument("target", ArgumentType::Players).executes(|context| change_game_mode(context, context.get_players("target")?)))
+    ));
+
+    register_command(literal("kill").requires(2)
+        .executes(|context| kill_players(context, vec![own_player(context)?]))
+        .then(argument("targets", ArgumentType::Players).executes(|context| kill_players(context, context.get_players("targets")?)))
+    );
+
+    register_command(literal("spawnpoint").requires(2)
+        .executes(|context| set_spawn_points(context, vec![own_player(context)?], None, 0.0))
+        .then(argument("targets", ArgumentType::Players)
+            .executes(|context| set_spawn_points(context, context.get_players("targets")?, None, 0.0))
+            .then(argument("pos", ArgumentType::BlockPos)
+                .executes(|context| set_spawn_points(context, context.get_players("targets")?, Some(context.get_coordinates("pos")?), 0.0))
+                .then(argument("angle", ArgumentType::Double { min: Some(-180.0), max: Some(180.0) }).executes(|context| {
+                    let angle = context.get_double("angle")? as f32;
+                    set_spawn_points(context, context.get_players("targets")?, Some(context.get_coordinates("pos")?), angle)
+                }))
+            )
+        )
+    );
+
+    register_command(literal("setworldspawn").requires(2)
+        .executes(|context| move_world_spawn(context, None, 0.0))
+        .then(argument("pos", ArgumentType::BlockPos)
+            .executes(|context| move_world_spawn(context, Some(context.get_coordinates("pos")?), 0.0))
+            .then(argument("angle", ArgumentType::Double { min: Some(-180.0), max: Some(180.0) }).executes(|context| {
+                let angle = context.get_double("angle")? as f32;
+                move_world_spawn(context, Some(context.get_coordinates("pos")?), angle)
+            }))
+        )
+    );
+
+    register_command(literal("time").requires(2)
+        .then(literal("set")
+            .then(literal("day").executes(|context| set_time(context, 1000)))
+            .then(literal("noon").executes(|context| set_time(context, 6000)))
+            .then(literal("night").executes(|context| set_time(context, 13000)))
+            .then(literal("midnight").executes(|context| set_time(context, 18000)))
+            .then(argument("time", ArgumentType::Integer { min: Some(0), max: None }).executes(|context| set_time(context, context.get_integer("time")? as i64))))
+        .then(literal("add").then(argument("time", ArgumentType::Integer { min: Some(0), max: None }).executes(|context| {
+            let day_time = level().read().unwrap().day_time + context.get_integer("time")? as i64;
+            set_time(context, day_time)
+        })))
+        .then(literal("query")
+            .then(literal("daytime").executes(|context| query_time(context, |level| level.day_time % DAY_LENGTH)))
+            .then(literal("gametime").executes(|context| query_time(context, |level| level.time)))
+            .then(literal("day").executes(|context| query_time(context, |level| level.day_time / DAY_LENGTH))))
+    );
+
+    register_command(literal("weather").requires(2)
+        .then(literal("clear")
+            .executes(|context| change_weather(context, "clear", None))
+            .then(weather_duration_argument().executes(|context| change_weather(context, "clear", Some(context.get_integer("duration")?)))))
+        .then(literal("rain")
+            .executes(|context| change_weather(context, "rain", None))
+            .then(weather_duration_argument().executes(|context| change_weather(context, "rain", Some(context.get_integer("duration")?)))))
+        .then(literal("thunder")
+            .executes(|context| change_weather(context, "thunder", None))
+            .then(weather_duration_argument().executes(|context| change_weather(context, "thunder", Some(context.get_integer("duration")?)))))
+    );
+
+    let mut gamerule = literal("gamerule").requires(2);
+    for &(name, default) in GAME_RULES {
+        let value_type = match default {
+            GameRuleValue::Bool(_) => ArgumentType::Bool,
+            GameRuleValue::Int(_) => ArgumentType::Integer { min: None, max: None },
+        };
+        gamerule = gamerule.then(literal(name)
+            .executes(move |context| {
+                let value = level().read().unwrap().game_rules.get(name).expect("Known rule");
+                context.source.send_message(TextComponent::translatable("commands.gamerule.query", vec![TextComponent::text(name), TextComponent::text(value.to_string())])
+                    .fallback("Gamerule %s is currently set to: %s"));
+                Ok(())
+            })
+            .then(argument("value", value_type).executes(move |context| {
+                let value = match context.argument("value")? {
+                    ArgumentValue::Bool(value) => value.to_string(),
+                    ArgumentValue::Integer(value) => value.to_string(),
+                    other => anyhow::bail!("Invalid game rule value {:?}", other),
+                };
+                let value = set_game_rule(&context.source.connections, name, &value)?;
+                context.source.send_message(TextComponent::translatable("commands.gamerule.set", vec![TextComponent::text(name), TextComponent::text(value.to_string())])
+                    .fallback("Gamerule %s is now set to: %s"));
+                Ok(())
+            })));
+    }
+    register_command(gamerule);
+}
+
+/// Names starting with what was typed so far.
+fn matching(names: impl Iterator<Item = impl Into<String>>, partial: &str) -> Vec<String> {
+    names.map(Into::into).filter(|name| name.starts_with(partial)).collect()
+}
+
+fn objective_argument() -> CommandNode {
+    argument("objective", ArgumentType::String(StringKind::Word))
+        .suggests(|_, partial| matching(scoreboard().read().unwrap().objectives().map(|objective| objective.name.clone()), partial))
+}
+
+fn team_argument() -> CommandNode {
+    argument("team", ArgumentType::String(StringKind::Word))
+        .suggests(|_, partial| matching(scoreboard().read().unwrap().teams().map(|team| team.name.clone()), partial))
+}
+
+fn visibility_argument() -> CommandNode {
+    argument("value", ArgumentType::String(StringKind::Word))
+        .suggests(|_, partial| matching(Visibility::ALL.into_iter().map(Visibility::name), partial))
+}
+
+fn visibility_value(context: &CommandContext) -> anyhow::Result<Visibility> {
+    let name = context.get_string("value")?;
+    Visibility::from_name(name).ok_or_else(|| anyhow::anyhow!("Unknown visibility '{}'", name))
+}
+
+/// Text components are given as JSON, anything else is taken as plain text.
+fn parse_text(text: &str) -> TextComponent {
+    TextComponent::from_json(text).unwrap_or_else(|_| TextComponent::text(text))
+}
+
+fn list_objectives(context: &CommandContext) -> anyhow::Result<()> {
+    let names = scoreboard().read().unwrap().objectives().map(|objective| objective.name.clone()).collect::<Vec<_>>();
+    context.source.send_message(match names.is_empty() {
+        true => TextComponent::translatable("commands.scoreboard.objectives.list.empty", vec![]).fallback("There are no objectives"),
+        false => TextComponent::translatable("commands.scoreboard.objectives.list.success",
+            vec![TextComponent::text(names.len().to_string()), TextComponent::text(names.join(", "))])
+            .fallback("There are %s objective(s): %s"),
+    });
+    Ok(())
+}
+
+fn add_scoreboard_objective(context: &CommandContext, display_name: Option<TextComponent>) -> anyhow::Result<()> {
+    let name = context.get_string("objective")?;
+    let mut objective = Objective::new(name);
+    objective.criterion = context.get_string("criteria")?.to_string();
+    if let Some(display_name) = display_name {
+        objective.display_name = display_name;
+    }
+    add_objective(&context.source.connections, objective)?;
+    context.source.send_message(TextComponent::translatable("commands.scoreboard.objectives.add.success", vec![TextComponent::text(name)])
+        .fallback("Created new objective %s"));
+    Ok(())
+}
+
+fn add_scoreboard_team(context: &CommandContext, display_name: Option<TextComponent>) -> anyhow::Result<()> {
+    let name = context.get_string("team")?;
+    let mut team = Team::new(name);
+    if let Some(display_name) = display_name {
+        team.display_name = display_name;
+    }
+    add_team(&context.source.connections, team)?;
+    context.source.send_message(TextComponent::translatable("commands.team.add.success", vec![TextComponent::text(name)])
+        .fallback("Created team %s"));
+    Ok(())
+}
+
+fn join_scoreboard_team(context: &CommandContext, members: Vec<String>) -> anyhow::Result<()> {
+    let name = context.get_string("team")?;
+    join_team(&context.source.connections, name, &members)?;
+    let team = TextComponent::text(name);
+    context.source.send_message(match members.as_slice() {
+        [member] => TextComponent::translatable("commands.team.join.success.single", vec![TextComponent::text(member.as_str()), team])
+            .fallback("Added %s to team %s"),
+        members => TextComponent::translatable("commands.team.join.success.multiple", vec![TextComponent::text(members.len().to_string()), team])
+            .fallback("Added %s members to team %s"),
+    });
+    Ok(())
+}
+
+/// `team modify <team> <option> <value>`, with the feedback for the option given the team's name.
+fn modify_scoreboard_team(context: &CommandContext, modify: impl FnOnce(&mut Team), feedback: impl FnOnce(TextComponent) -> TextComponent) -> anyhow::Result<()> {
+    let name = context.get_string("team")?;
+    modify_team(&context.source.connections, name, modify)?;
+    context.source.send_message(feedback(TextComponent::text(name)));
+    Ok(())
+}
+
+/// Runs a `title` subcommand for every target, with the vanilla feedback `key` read as "`feedback` <targets>".
+fn show_titles(context: &CommandContext, key: &str, feedback: &str, show: impl Fn(&ConnectionHandle) -> anyhow::Result<()>) -> anyhow::Result<()> {
+    let players = context.get_players("targets")?;
+    for player in &players {
+        show(player)?;
+    }
+    context.source.send_message(match players.as_slice() {
+        [player] => TextComponent::translatable(format!("{}.single", key), vec![TextComponent::text(player.username().unwrap_or_default())])
+            .fallback(format!("{} %s", feedback)),
+        players => TextComponent::translatable(format!("{}.multiple", key), vec![TextComponent::text(players.len().to_string())])
+            .fallback(format!("{} %s players", feedback)),
+    });
+    Ok(())
+}
+
+/// How many of the optional `playsound` arguments were given.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+enum SoundArguments {
+    Targets,
+    Position,
+    Volume,
+    Pitch,
+}
+
+/// `playsound <sound> <source> <targets> [<pos>] [<volume>] [<pitch>]`, heard by the targets in range of the position.
+fn play_sound_command(context: &CommandContext, arguments: SoundArguments) -> anyhow::Result<()> {
+    let name = context.get_identifier("sound")?.clone();
+    let source = context.get_string("source")?;
+    let category = SoundCategory::from_name(source).ok_or_else(|| anyhow::anyhow!("Unknown sound source '{}'", source))?;
+    let position = match arguments >= SoundArguments::Position {
+        true => Some(context.get_coordinates("pos")?.resolve(context.source.position())),
+        false => None,
+    };
+    let mut sound = Sound::named(name.clone()).category(category);
+    if arguments >= SoundArguments::Volume {
+        sound = sound.volume(context.get_double("volume")? as f32);
+    }
+    if arguments >= SoundArguments::Pitch {
+        sound = sound.pitch(context.get_double("pitch")? as f32);
+    }
+    let range = sound.range();
+    let mut listeners = Vec::new();
+    for player in context.get_players("targets")? {
+        let Some(at) = player_position(&player).map(|position| position.vec3()) else { continue };
+        let position = position.unwrap_or(at);
+        if at.distance_squared(position) <= range * range {
+            player.play_sound(&sound, position)?;
+            listeners.push(player);
+        }
+    }
+    let name = TextComponent::text(name.to_string());
+    context.source.send_message(match listeners.as_slice() {
+        [] => anyhow::bail!("The sound is too far away to be heard"),
+        [player] => TextComponent::translatable("commands.playsound.success.single", vec![name, TextComponent::text(player.username().unwrap_or_default())])
+            .fallback("Played sound %s to %s"),
+        players => TextComponent::translatable("commands.playsound.success.multiple", vec![name, TextComponent::text(players.len().to_string())])
+            .fallback("Played sound %s to %s players"),
+    });
+    Ok(())
+}
+
+/// The player running a command which targets its runner when no target is given.
+fn own_player(context: &CommandContext) -> anyhow::Result<ConnectionHandle> {
+    match &context.source.sender {
+        CommandSender::Player(player) => Ok(player.clone()),
+        CommandSender::Console | CommandSender::Remote(_) => anyhow::bail!("A player is required to run this command here"),
+    }
+}
+
+/// `op <targets>` with the level to give, or `deop <targets>`. Targets are told their new level and
+/// sent the commands they may use now.
+fn change_operators(context: &CommandContext, level: Option<u8>) -> anyhow::Result<()> {
+    let mut changed = 0;
+    for target in context.get_players("targets")? {
+        let (Some(uuid), Some(name)) = (target.uuid(), target.username()) else { continue };
+        let changes = {
+            let mut ops = ops().write().unwrap();
+            let changes = match level {
+                Some(level) if ops.get(uuid).is_some_and(|operator| operator.level == level) => false,
+                Some(level) => {
+                    ops.add(Operator { uuid, name: name.clone(), level, bypasses_player_limit: false });
+                    true
+                }
+                None => ops.remove(uuid).is_some(),
+            };
+            if changes {
+                ops.save()?;
+            }
+            changes
+        };
+        if !changes {
+            continue;
+        }
+        refresh_permissions(&target, context.source.connections.clone())?;
+        changed += 1;
+        let name = TextComponent::text(name);
+        context.source.send_message(match level {
+            Some(_) => TextComponent::translatable("commands.op.success", vec![name]).fallback("Made %s a server operator"),
+            None => TextComponent::translatable("commands.deop.success", vec![name]).fallback("Made %s no longer a server operator"),
+        });
+    }
+    if changed == 0 {
+        match level {
+            Some(_) => anyhow::bail!("Nothing changed. The player already is an operator"),
+            None => anyhow::bail!("Nothing changed. The player is not an operator"),
+        }
+    }
+    Ok(())
+}
+
+/// UUID and name of a player given by name, online or not. Players not online get the UUID they
+/// would log in with, as players are not authenticated.
+fn player_profile(context: &CommandContext, name: &str) -> (Uuid, String) {
+    match context.source.connections.find_player(name).and_then(|player| Some((player.uuid()?, player.username()?))) {
+        Some(profile) => profile,
+        None => (offline_uuid(name), name.to_string()),
+    }
+}
+
+/// `kick <targets> [<reason>]`.
+fn kick_players(context: &CommandContext, reason: TextComponent) -> anyhow::Result<()> {
+    for target in context.get_players("targets")? {
+        target.disconnect(reason.clone())?;
+        context.source.send_message(TextComponent::translatable("commands.kick.success", vec![TextComponent::text(target.username().unwrap_or_default()), reason.clone()])
+            .fallback("Kicked %s: %s"));
+    }
+    Ok(())
+}
+
+/// `transfer <hostname> [<port>] [<players>]`, sending players to another server, the runner by default.
+fn transfer_players(context: &CommandContext, with_port: bool, with_targets: bool) -> anyhow::Result<()> {
+    let hostname = context.get_string("hostname")?;
+    let port = if with_port { context.get_integer("port")? as u16 } else { 25565 };
+    let targets = if with_targets { context.get_players("players")? } else { vec![own_player(context)?] };
+    for target in &targets {
+        transfer(target, hostname, port)?;
+    }
+    let destination = [TextComponent::text(hostname), TextComponent::text(port.to_string())];
+    context.source.send_message(match targets.as_slice() {
+        [] => anyhow::bail!("No player was found"),
+        [player] => TextComponent::translatable("commands.transfer.success.single",
+            [vec![TextComponent::text(player.username().unwrap_or_default())], destination.to_vec()].concat())
+            .fallback("Transferring %s to %s:%s"),
+        players => TextComponent::translatable("commands.transfer.success.multiple",
+            [vec![TextComponent::text(players.len().to_string())], destination.to_vec()].concat())
+            .fallback("Transferring %s players to %s:%s"),
+    });
+    Ok(())
+}
+
+/// `ban <player> [<reason>]`, banning players whether they are online or not.
+fn ban_player(context: &CommandContext, reason: Option<String>) -> anyhow::Result<()> {
+    let (uuid, name) = player_profile(context, context.get_string("targets")?);
+    let ban = BanEntry::new(BannedPlayer { uuid, name: name.clone() }, context.source.name(), reason);
+    let reason = ban.reason.clone();
+    let mut banned_players = banned_players().write().unwrap();
+    if banned_players.get(uuid).is_some() {
+        anyhow::bail!("Nothing changed. The player is already banned");
+    }
+    banned_players.add(ban);
+    banned_players.save()?;
+    drop(banned_players);
+    context.source.send_message(TextComponent::translatable("commands.ban.success", vec![TextComponent::text(name.clone()), TextComponent::text(reason)])
+        .fallback("Banned %s: %s"));
+    if let Some(player) = context.source.connections.find_player(&name) {
+        player.disconnect(TextComponent::translatable("multiplayer.disconnect.banned", vec![]).fallback("You are banned from this server."))?;
+    }
+    Ok(())
+}
+
+/// `ban-ip <target> [<reason>]`, the target being an IP address or the name of an online player.
+fn ban_ip(context: &CommandContext, reason: Option<String>) -> anyhow::Result<()> {
+    let target = context.get_string("target")?;
+    let ip = match target.parse::<IpAddr>() {
+        Ok(ip) => ip,
+        Err(_) => match context.source.connections.find_player(target) {
+            Some(player) => player.peer_addr().ip(),
+            None => anyhow::bail!("Invalid IP address or unknown player"),
+        },
+    };
+    let ban = BanEntry::new(ip, context.source.name(), reason);
+    let reason = ban.reason.clone();
+    {
+        let mut banned_ips = banned_ips().write().unwrap();
+        if banned_ips.get(ip).is_some() {
+            anyhow::bail!("Nothing changed. That IP is already banned");
+        }
+        banned_ips.add(ban);
+        banned_ips.save()?;
+    }
+    context.source.send_message(TextComponent::translatable("commands.banip.success", vec![TextComponent::text(ip.to_string()), TextComponent::text(reason)])
+        .fallback("Banned IP %s: %s"));
+    let affected = context.source.connections.players().into_iter()
+        .filter(|player| player.peer_addr().ip() == ip)
+        .collect::<Vec<_>>();
+    if !affected.is_empty() {
+        let names = affected.iter().filter_map(ConnectionHandle::username).collect::<Vec<_>>();
+        context.source.send_message(TextComponent::translatable("commands.banip.info", vec![TextComponent::text(names.len().to_string()), TextComponent::text(names.join(", "))])
+            .fallback("This ban affects %s player(s): %s"));
+    }
+    for player in affected {
+        player.disconnect(TextComponent::translatable("multiplayer.disconnect.ip_banned", vec![]).fallback("You have been IP banned from this server."))?;
+    }
+    Ok(())
+}
+
+fn toggle_whitelist(context: &CommandContext, enabled: bool) -> anyhow::Result<()> {
+    let changed = std::mem::replace(&mut whitelist().write().unwrap().enabled, enabled) != enabled;
+    match (enabled, changed) {
+        (true, true) => context.source.send_message(TextComponent::translatable("commands.whitelist.enabled", vec![]).fallback("Whitelist is now turned on")),
+        (false, true) => context.source.send_message(TextComponent::translatable("commands.whitelist.disabled", vec![]).fallback("Whitelist is now turned off")),
+        (true, false) => anyhow::bail!("Whitelist is already turned on"),
+        (false, false) => anyhow::bail!("Whitelist is already turned off"),
+    }
+    Ok(())
+}
+
+fn toggle_packet_dump(context: &CommandContext, enabled: bool) -> anyhow::Result<()> {
+    set_packet_dump(enabled);
+    let state = if enabled { "on" } else { "off" };
+    context.source.send_message(TextComponent::text(format!("Packet dump is now {}, packets are logged at info level", state)));
+    Ok(())
+}
+
+/// `debug ai`, the goals and path every entity near the runner reported.
+fn describe_ai(context: &CommandContext) -> anyhow::Result<()> {
+    let entities = entities().read().unwrap();
+    let mut described = 0;
+    for entity in entities.within(context.source.position(), PARTICLE_RANGE) {
+        let Some(info) = entities.ai_debug_info(entity.id()) else { continue };
+        let goals = info.goals.iter()
+            .map(|goal| format!("{} {}{}", goal.priority, goal.name, if goal.running { " (running)" } else { "" }))
+            .collect::<Vec<_>>();
+        let path = match &info.path {
+            Some(path) => match path.target() {
+                Some(target) => format!("node {}/{} to {} {} {}", path.next, path.nodes.len(), target.x, target.y, target.z),
+                None => "empty".to_string(),
+            },
+            None => "none".to_string(),
+        };
+        let position = entity.position();
+        context.source.send_message(TextComponent::text(format!("#{} {} at {:.1} {:.1} {:.1}: goals [{}], path {}",
+            entity.id(), entity.entity_type().name(), position.x, position.y, position.z, goals.join(", "), path)));
+        described += 1;
+    }
+    if described == 0 {
+        context.source.send_message(TextComponent::text("No entity nearby reported its AI"));
+    }
+    Ok(())
+}
+
+/// `debug ai on|off`, drawing the paths of entities around the runner with particles.
+fn toggle_ai_debug(context: &CommandContext, enabled: bool) -> anyhow::Result<()> {
+    let player = own_player(context)?;
+    set_ai_debug(&player, enabled);
+    let state = if enabled { "on" } else { "off" };
+    context.source.send_message(TextComponent::text(format!("AI debug is now {}, paths are drawn with particles", state)));
+    Ok(())
+}
+
+/// `kill [<targets>]`, killing players regardless of their game mode.
+fn kill_players(context: &CommandContext, targets: Vec<ConnectionHandle>) -> anyhow::Result<()> {
+    let source = DamageSource::of("generic_kill");
+    for target in &targets {
+        damage(&context.source.connections, target, &source, f32::MAX)?;
+    }
+    context.source.send_message(match targets.as_slice() {
+        [target] => TextComponent::translatable("commands.kill.success.single", vec![TextComponent::text(target.username().unwrap_or_default())])
+            .fallback("Killed %s"),
+        targets => TextComponent::translatable("commands.kill.success.multiple", vec![TextComponent::text(targets.len().to_string())])
+            .fallback("Killed %s entities"),
+    });
+    Ok(())
+}
+
+/// `spawnpoint [<targets>] [<pos>] [<angle>]`, forced spawn points at the runner's position by default.
+fn set_spawn_points(context: &CommandContext, targets: Vec<ConnectionHandle>, position: Option<Coordinates>, angle: f32) -> anyhow::Result<()> {
+    let origin = context.source.position().block_pos();
+    let position = position.map_or(origin, |position| position.resolve_block_pos(origin));
+    let spawn_point = SpawnPoint { angle, forced: true, ..SpawnPoint::new(position) };
+    for target in &targets {
+        set_spawn_point(target, Some(spawn_point.clone()));
+    }
+    let mut arguments = [position.x, position.y, position.z].map(|value| TextComponent::text(value.to_string())).to_vec();
+    arguments.push(TextComponent::text(angle.to_string()));
+    arguments.push(TextComponent::text(spawn_point.dimension.to_string()));
+    context.source.send_message(match targets.as_slice() {
+        [target] => {
+            arguments.push(TextComponent::text(target.username().unwrap_or_default()));
+            TextComponent::translatable("commands.spawnpoint.success.single", arguments).fallback("Set spawn point to %s, %s, %s [%s] in %s for %s")
+        }
+        targets => {
+            arguments.push(TextComponent::text(targets.len().to_string()));
+            TextComponent::translatable("commands.spawnpoint.success.multiple", arguments).fallback("Set spawn point to %s, %s, %s [%s] in %s for %s players")
+        }
+    });
+    Ok(())
+}
+
+fn move_world_spawn(context: &CommandContext, position: Option<Coordinates>, angle: f32) -> anyhow::Result<()> {
+    let origin = context.source.position().block_pos();
+    let position = position.map_or(origin, |position| position.resolve_block_pos(origin));
+    set_world_spawn(&context.source.connections, position, angle)?;
+    let mut arguments = [position.x, position.y, position.z].map(|value| TextComponent::text(value.to_string())).to_vec();
+    arguments.push(TextComponent::text(angle.to_string()));
+    context.source.send_message(TextComponent::translatable("commands.setworldspawn.success", arguments).fallback("Set the world spawn point to %s, %s, %s [%s]"));
+    Ok(())
+}
+
+/// `gamemode <gamemode> [<target>]`, players already in the game mode are left out of the feedback.
+fn change_game_mode(context: &CommandContext, targets: Vec<ConnectionHandle>) -> anyhow::Result<()> {
+    let name = context.get_string("gamemode")?;
+    let game_mode = GameMode::from_name(name).ok_or_else(|| anyhow::anyhow!("Unknown game mode '{}'", name))?;
+    let mode_text = || {
+        let mut label = name.to_string();
+        label[..1].make_ascii_uppercase();
+        TextComponent::translatable(format!("gameMode.{}", name), vec![]).fallback(format!("{} Mode", label))
+    };
+    let own = match &context.source.sender {
+        CommandSender::Player(player) => Some(player.id()),
+        CommandSender::Console | CommandSender::Remote(_) => None,
+    };
+    for target in targets {
+        if !set_game_mode(&context.source.connections, &target, game_mode)? {
+            continue;
+        }
+        if own == Some(target.id()) {
+            context.source.send_message(TextComponent::translatable("commands.gamemode.success.self", vec![mode_text()]).fallback("Set own game mode to %s"));
+            continue;
+        }
+        let _ = target.send(&SystemChatMessage {
+            content: TextComponent::translatable("gameMode.changed", vec![mode_text()]).fallback("Your game mode has been updated to %s"),
+            overlay: false,
+        });
+        let player = TextComponent::text(target.username().unwrap_or_default());
+        context.source.send_message(TextComponent::translatable("commands.gamemode.success.other", vec![player, mode_text()]).fallback("Set %s's game mode to %s"));
+    }
+    Ok(())
+}
+
+fn set_time(context: &CommandContext, day_time: i64) -> anyhow::Result<()> {
+    set_day_time(&context.source.connections, day_time)?;
+    context.source.send_message(TextComponent::translatable("commands.time.set", vec![TextComponent::text(day_time.to_string())]).fallback("Set the time to %s"));
+    Ok(())
+}
+
+fn query_time(context: &CommandContext, query: impl FnOnce(&LevelData) -> i64) -> anyhow::Result<()> {
+    let time = query(&level().read().unwrap());
+    context.source.send_message(TextComponent::translatable("commands.time.query", vec![TextComponent::text(time.to_string())]).fallback("The time is %s"));
+    Ok(())
+}
+
+/// Ticks the weather lasts, random like vanilla without one.
+fn weather_duration_argument() -> CommandNode {
+    argument("duration", ArgumentType::Integer { min: Some(1), max: Some(1_000_000) })
+}
+
+fn change_weather(context: &CommandContext, weather: &str, duration: Option<i32>) -> anyhow::Result<()> {
+    let (change, fallback): (fn(&mut Weather, Option<i32>), _) = match weather {
+        "clear" => (Weather::set_clear, "Set the weather to clear"),
+        "rain" => (Weather::set_rain, "Set the weather to rain"),
+        _ => (Weather::set_thunder, "Set the weather to rain & thunder"),
+    };
+    set_weather(|current| change(current, duration));
+    context.source.send_message(TextComponent::translatable(format!("commands.weather.set.{}", weather), vec![]).fallback(fallback));
+    Ok(())
+}
+
+/// `scoreboard objectives setdisplay <slot> [<objective>]`, clearing the slot without an objective.
+fn set_scoreboard_display(context: &CommandContext, with_objective: bool) -> anyhow::Result<()> {
+    let slot_name = context.get_string("slot")?;
+    let slot = DisplaySlot::from_name(slot_name).ok_or_else(|| anyhow::anyhow!("Unknown display slot '{}'", slot_name))?;
+    let objective = match with_objective {
+        true => Some(context.get_string("objective")?),
+        false => None,
+    };
+    set_display_slot(&context.source.connections, slot, objective)?;
+    context.source.send_message(match objective {
+        Some(objective) => TextComponent::translatable("commands.scoreboard.objectives.display.set",
+            vec![TextComponent::text(slot_name), TextComponent::text(objective)])
+            .fallback("Set display slot %s to show objective %s"),
+        None => TextComponent::translatable("commands.scoreboard.objectives.display.cleared", vec![TextComponent::text(slot_name)])
+            .fallback("Cleared any objectives in display slot %s"),
+    });
+    Ok(())
+}
+
+fn set_render_type(context: &CommandContext, render_type: RenderType) -> anyhow::Result<()> {
+    let name = context.get_string("objective")?;
+    modify_objective(&context.source.connections, name, |objective| objective.render_type = render_type)?;
+    context.source.send_message(TextComponent::translatable("commands.scoreboard.objectives.modify.rendertype", vec![TextComponent::text(name)])
+        .fallback("Changed the render type of objective %s"));
+    Ok(())
+}
+
+/// The one holder of a `target` argument, `*` and selectors may only match a single name.
+fn single_holder(context: &CommandContext) -> anyhow::Result<String> {
+    let mut holders = context.get_score_holders("target")?;
+    if holders.len() > 1 {
+        anyhow::bail!("Only one entity is allowed, but the provided selector allows more than one");
+    }
+    Ok(holders.remove(0))
+}
+
+fn list_scores(context: &CommandContext) -> anyhow::Result<()> {
+    let holder = single_holder(context)?;
+    let scoreboard = scoreboard().read().unwrap();
+    let scores = scoreboard.scores(&holder).collect::<Vec<_>>();
+    if scores.is_empty() {
+        context.source.send_message(TextComponent::translatable("commands.scoreboard.players.list.entity.empty", vec![TextComponent::text(holder.as_str())])
+            .fallback("%s has no scores to show"));
+        return Ok(());
+    }
+    context.source.send_message(TextComponent::translatable("commands.scoreboard.players.list.entity.success",
+        vec![TextComponent::text(holder.as_str()), TextComponent::text(scores.len().to_string())])
+        .fallback("%s has %s score(s):"));
+    for (name, score) in scores {
+        let display_name = scoreboard.objective(name).map_or_else(|| TextComponent::text(name), |objective| objective.display_name.clone());
+        context.source.send_message(TextComponent::translatable("commands.scoreboard.players.list.entity.entry",
+            vec![display_name, TextComponent::text(score.value.to_string())])
+            .fallback("%s: %s"));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Copy, Clone)]
+enum ScoreChange {
+    Set,
+    Add,
+    Remove,
+}
+
+/// `<targets> <objective> <score>` of `scoreboard players set`, `add` and `remove`.
+fn score_change_arguments(min: Option<i32>, change: ScoreChange) -> CommandNode {
+    argument("targets", ArgumentType::ScoreHolders).then(objective_argument().then(
+        argument("score", ArgumentType::Integer { min, max: None }).executes(move |context| change_scores(context, change))
+    ))
+}
+
+fn change_scores(context: &CommandContext, change: ScoreChange) -> anyhow::Result<()> {
+    let holders = context.get_score_holders("targets")?;
+    let (name, amount) = (context.get_string("objective")?, context.get_integer("score")?);
+    match scoreboard().read().unwrap().objective(name) {
+        None => anyhow::bail!("Unknown scoreboard objective '{}'", name),
+        Some(objective) if objective.is_read_only() => anyhow::bail!("Objective '{}' is read-only", objective.name),
+        Some(_) => {}
+    }
+    let connections = &context.source.connections;
+    let mut value = 0;
+    for holder in &holders {
+        value = match change {
+            ScoreChange::Set => {
+                set_score(connections, holder, name, amount)?;
+                amount
+            }
+            ScoreChange::Add => add_score(connections, holder, name, amount)?,
+            ScoreChange::Remove => add_score(connections, holder, name, amount.wrapping_neg())?,
+        };
+    }
+    let (targets, single) = match holders.as_slice() {
+        [holder] => (TextComponent::text(holder.as_str()), true),
+        holders => (TextComponent::text(holders.len().to_string()), false),
+    };
+    let (amount, name, value) = (TextComponent::text(amount.to_string()), TextComponent::text(name), TextComponent::text(value.to_string()));
+    context.source.send_message(match (change, single) {
+        (ScoreChange::Set, true) => TextComponent::translatable("commands.scoreboard.players.set.success.single", vec![name, targets, amount])
+            .fallback("Set %s for %s to %s"),
+        (ScoreChange::Set, false) => TextComponent::translatable("commands.scoreboard.players.set.success.multiple", vec![name, targets, amount])
+            .fallback("Set %s for %s entities to %s"),
+        (ScoreChange::Add, true) => TextComponent::translatable("commands.scoreboard.players.add.success.single", vec![amount, name, targets, value])
+            .fallback("Added %s to %s for %s (now %s)"),
+        (ScoreChange::Add, false) => TextComponent::translatable("commands.scoreboard.players.add.success.multiple", vec![amount, name, targets])
+            .fallback("Added %s to %s for %s entities"),
+        (ScoreChange::Remove, true) => TextComponent::translatable("commands.scoreboard.players.remove.success.single", vec![amount, name, targets, value])
+            .fallback("Removed %s from %s for %s (now %s)"),
+        (ScoreChange::Remove, false) => TextComponent::translatable("commands.scoreboard.players.remove.success.multiple", vec![amount, name, targets])
+            .fallback("Removed %s from %s for %s entities"),
+    });
+    Ok(())
+}
+
+/// `scoreboard players reset <targets> [<objective>]`, all scores of the holders without an objective.
+fn reset_scores(context: &CommandContext, with_objective: bool) -> anyhow::Result<()> {
+    let holders = context.get_score_holders("targets")?;
+    let objective = match with_objective {
+        true => Some(context.get_string("objective")?),
+        false => None,
+    };
+    if let Some(name) = objective.filter(|name| scoreboard().read().unwrap().objective(name).is_none()) {
+        anyhow::bail!("Unknown scoreboard objective '{}'", name);
+    }
+    for holder in &holders {
+        reset_score(&context.source.connections, holder, objective)?;
+    }
+    let targets = match holders.as_slice() {
+        [holder] => TextComponent::text(holder.as_str()),
+        holders => TextComponent::text(holders.len().to_string()),
+    };
+    let single = holders.len() == 1;
+    context.source.send_message(match (objective, single) {
+        (None, true) => TextComponent::translatable("commands.scoreboard.players.reset.all.single", vec![targets]).fallback("Reset all scores for %s"),
+        (None, false) => TextComponent::translatable("commands.scoreboard.players.reset.all.multiple", vec![targets])
+            .fallback("Reset all scores for %s entities"),
+        (Some(name), true) => TextComponent::translatable("commands.scoreboard.players.reset.specific.single", vec![TextComponent::text(name), targets])
+            .fallback("Reset %s for %s"),
+        (Some(name), false) => TextComponent::translatable("commands.scoreboard.players.reset.specific.multiple", vec![TextComponent::text(name), targets])
+            .fallback("Reset %s for %s entities"),
+    });
+    Ok(())
+}
+
+/// `advancement grant` or `revoke` for every advancement, or for one given as `<advancement> [<criterion>]`.
+fn change_advancements(context: &CommandContext, grant: bool, everything: bool) -> anyhow::Result<()> {
+    let targets = context.get_players("targets")?;
+    let (ids, criterion) = match everything {
+        true => (advancements().read().unwrap().iter().map(|(id, _)| id.clone()).collect::<Vec<_>>(), None),
+        false => {
+            let argument = context.get_string("advancement")?;
+            let (id, criterion) = argument.split_once(' ').map_or((argument, None), |(id, criterion)| (id, Some(criterion.trim())));
+            let id = id.parse::<Identifier>().map_err(|_| anyhow::anyhow!("Invalid advancement id {}", id))?;
+            let registry = advancements().read().unwrap();
+            let Some(advancement) = registry.get(&id) else { anyhow::bail!("Unknown advancement: {}", id) };
+            if let Some(criterion) = criterion.filter(|criterion| !advancement.criteria.contains_key(*criterion)) {
+                anyhow::bail!("Advancement {} does not contain the criterion {}", id, criterion);
+            }
+            (vec![id], criterion)
+        }
+    };
+    let mut changed = 0;
+    for target in &targets {
+        changed += match (criterion, grant) {
+            (Some(criterion), true) => grant_criterion(target, &ids[0], criterion)? as usize,
+            (Some(criterion), false) => revoke_criterion(target, &ids[0], criterion)? as usize,
+            (None, true) => grant_advancements(target, &ids)?,
+            (None, false) => revoke_advancements(target, &ids)?,
+        };
+    }
+    if changed == 0 {
+        anyhow::bail!(match grant {
+            true => "Couldn't grant advancements, the players already have them",
+            false => "Couldn't revoke advancements, the players do not have them",
+        });
+    }
+    let (key, fallback) = match grant {
+        true => ("commands.advancement.grant.many.to.many.success", "Granted %s advancements to %s players"),
+        false => ("commands.advancement.revoke.many.to.many.success", "Revoked %s advancements from %s players"),
+    };
+    let arguments = vec![TextComponent::text(ids.len().to_string()), TextComponent::text(targets.len().to_string())];
+    context.source.send_message(TextComponent::translatable(key, arguments).fallback(fallback));
+    Ok(())
+}
+
+/// `recipe give` and `recipe take`, `*` stands for every recipe.
+fn change_recipes(context: &CommandContext, give: bool) -> anyhow::Result<()> {
+    let targets = context.get_players("targets")?;
+    let recipe = context.get_string("recipe")?;
+    let ids = match recipe {
+        "*" => recipes().read().unwrap().iter().map(|(id, _)| id.clone()).collect::<Vec<_>>(),
+        recipe => {
+            let id = recipe.parse::<Identifier>().map_err(|_| anyhow::anyhow!("Invalid recipe id {}", recipe))?;
+            if recipes().read().unwrap().get(&id).is_none() {
+                anyhow::bail!("Unknown recipe: {}", id);
+            }
+            vec![id]
+        }
+    };
+    let mut changed = 0;
+    for target in &targets {
+        changed += match give {
+            true => unlock_recipes(target, &ids)?,
+            false => lock_recipes(target, &ids)?,
+        };
+    }
+    if changed == 0 {
+        anyhow::bail!(match give {
+            true => "Unable to give recipes",
+            false => "Unable to take recipes",
+        });
+    }
+    let (key, fallback) = match give {
+        true => ("commands.recipe.give.success.multiple", "Unlocked %s recipe(s) for %s players"),
+        false => ("commands.recipe.take.success.multiple", "Took %s recipe(s) from %s players"),
+    };
+    let arguments = vec![TextComponent::text(ids.len().to_string()), TextComponent::text(targets.len().to_string())];
+    context.source.send_message(TextComponent::translatable(key, arguments).fallback(fallback));
+    Ok(())
+}
+
+/// `worldborder set`, resizing over `seconds`.
+fn set_border_size(context: &CommandContext, seconds: i32) -> anyhow::Result<()> {
+    let distance = context.get_double("distance")?;
+    let shrinking = distance < world_border().diameter();
+    resize_border(&context.source.connections, distance, Duration::from_secs(seconds as u64))?;
+    let size = TextComponent::text(format!("{:.1}", distance));
+    let message = match seconds {
+        0 => TextComponent::translatable("commands.worldborder.set.immediate", vec![size]).fallback("Set the world border to %s block(s) wide"),
+        _ if shrinking => TextComponent::translatable("commands.worldborder.set.shrink", vec![size, TextComponent::text(seconds.to_string())])
+            .fallback("Shrinking the world border to %s block(s) wide over %s second(s)"),
+        _ => TextComponent::translatable("commands.worldborder.set.grow", vec![size, TextComponent::text(seconds.to_string())])
+            .fallback("Growing the world border to %s block(s) wide over %s second(s)"),
+    };
+    context.source.send_message(message);
+    Ok(())
+}
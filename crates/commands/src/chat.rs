@@ -0,0 +1,35 @@
+use std::sync::Arc;
+use dolls_network::prelude::{register_join_listener, set_chat_command_handler, ChatCommandHandler, JoinListener, PacketContext, Suggestions};
+use crate::prelude::{commands_packet, dispatcher, execute_command, suggest, CommandSource};
+
+/// Routes commands typed in chat to the global dispatcher.
+struct DispatcherChatCommands;
+
+impl ChatCommandHandler for DispatcherChatCommands {
+    fn execute(&self, context: &mut PacketContext, command: &str) -> anyhow::Result<()> {
+        let source = CommandSource::player(context.connection.clone(), context.connections.clone());
+        if let Err(err) = execute_command(&source, command) {
+            source.send_error(err.to_string());
+        }
+        Ok(())
+    }
+
+    fn suggest(&self, context: &mut PacketContext, command: &str) -> Suggestions {
+        let source = CommandSource::player(context.connection.clone(), context.connections.clone());
+        suggest(dispatcher().read().unwrap().root(), &source, command)
+    }
+}
+
+impl JoinListener for DispatcherChatCommands {
+    fn on_join(&self, context: &mut PacketContext) -> anyhow::Result<()> {
+        let source = CommandSource::player(context.connection.clone(), context.connections.clone());
+        let packet = commands_packet(dispatcher().read().unwrap().root(), &source);
+        context.send(&packet)
+    }
+}
+
+/// Lets players run commands from chat and declares the command tree to them when they join.
+pub fn enable_chat_commands() {
+    set_chat_command_handler(Arc::new(DispatcherChatCommands));
+    register_join_listener(Arc::new(DispatcherChatCommands));
+}
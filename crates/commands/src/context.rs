@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+use anyhow::{anyhow, bail};
+use dolls_core::datatype::Identifier;
+use dolls_network::prelude::ConnectionHandle;
+use crate::prelude::{ArgumentValue, CommandSource, Coordinates};
+
+/// Parsed arguments of a command together with its source, handed to executors.
+#[derive(Debug)]
+pub struct CommandContext<'a> {
+    pub source: &'a CommandSource,
+    pub input: &'a str,
+    arguments: HashMap<String, ArgumentValue>,
+}
+
+impl<'a> CommandContext<'a> {
+    pub fn new(source: &'a CommandSource, input: &'a str, arguments: HashMap<String, ArgumentValue>) -> Self {
+        Self { source, input, arguments }
+    }
+
+    pub fn argument(&self, name: &str) -> anyhow::Result<&ArgumentValue> {
+        self.arguments.get(name).ok_or_else(|| anyhow!("No such argument '{}'", name))
+    }
+
+    pub fn get_bool(&self, name: &str) -> anyhow::Result<bool> {
+        match self.argument(name)? {
+            ArgumentValue::Bool(value) => Ok(*value),
+            other => bail!("Argument '{}' is not a bool: {:?}", name, other),
+        }
+    }
+
+    pub fn get_integer(&self, name: &str) -> anyhow::Result<i32> {
+        match self.argument(name)? {
+            ArgumentValue::Integer(value) => Ok(*value),
+            other => bail!("Argument '{}' is not an integer: {:?}", name, other),
+        }
+    }
+
+    pub fn get_double(&self, name: &str) -> anyhow::Result<f64> {
+        match self.argument(name)? {
+            ArgumentValue::Double(value) => Ok(*value),
+            other => bail!("Argument '{}' is not a double: {:?}", name, other),
+        }
+    }
+
+    pub fn get_string(&self, name: &str) -> anyhow::Result<&str> {
+        match self.argument(name)? {
+            ArgumentValue::String(value) => Ok(value),
+            other => bail!("Argument '{}' is not a string: {:?}", name, other),
+        }
+    }
+
+    pub fn get_coordinates(&self, name: &str) -> anyhow::Result<Coordinates> {
+        match self.argument(name)? {
+            ArgumentValue::BlockPos(value) | ArgumentValue::Vec3(value) => Ok(*value),
+            other => bail!("Argument '{}' is not a position: {:?}", name, other),
+        }
+    }
+
+    pub fn get_identifier(&self, name: &str) -> anyhow::Result<&Identifier> {
+        match self.argument(name)? {
+            ArgumentValue::Identifier(value) => Ok(value),
+            other => bail!("Argument '{}' is not a resource location: {:?}", name, other),
+        }
+    }
+
+    /// Online players matched by a player argument, fails when there are none.
+    pub fn get_players(&self, name: &str) -> anyhow::Result<Vec<ConnectionHandle>> {
+        let ArgumentValue::Players(selector) = self.argument(name)? else { bail!("Argument '{}' is not a player", name) };
+        let players = selector.resolve(self.source)?;
+        if players.is_empty() {
+            bail!("No player was found");
+        }
+        Ok(players)
+    }
+
+    pub fn get_player(&self, name: &str) -> anyhow::Result<ConnectionHandle> {
+        let mut players = self.get_players(name)?;
+        if players.len() > 1 {
+            bail!("Only one player is allowed, but the provided selector allows more than one");
+        }
+        Ok(players.remove(0))
+    }
+
+    /// Names matched by a score holder argument, fails when there are none.
+    pub fn get_score_holders(&self, name: &str) -> anyhow::Result<Vec<String>> {
+        let ArgumentValue::ScoreHolders(holder) = self.argument(name)? else { bail!("Argument '{}' is not a score holder", name) };
+        let holders = holder.resolve(self.source)?;
+        if holders.is_empty() {
+            bail!("No score holder was found");
+        }
+        Ok(holders)
+    }
+}
@@ -0,0 +1,95 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+use dolls_core::datatype::{encode_to_vec, Identifier, VarInt};
+use dolls_network::prelude::{Commands, ConnectionHandle, ConnectionRegistry, DeclaredNode, DeclaredNodeKind};
+use crate::prelude::{dispatcher, ArgumentType, CommandNode, CommandSource, NodeKind, StringKind};
+
+impl ArgumentType {
+    /// Network id in the `minecraft:command_argument_type` registry.
+    pub fn parser_id(&self) -> i32 {
+        match self {
+            ArgumentType::Bool => 0,
+            ArgumentType::Double { .. } => 2,
+            ArgumentType::Integer { .. } => 3,
+            ArgumentType::String(_) => 5,
+            ArgumentType::Player | ArgumentType::Players => 6,
+            ArgumentType::BlockPos => 8,
+            ArgumentType::Vec3 => 10,
+            ArgumentType::Identifier => 35,
+            ArgumentType::ScoreHolders => 30,
+        }
+    }
+
+    pub fn encode_properties(&self) -> Vec<u8> {
+        fn range<T: dolls_core::datatype::Encode>(min: &Option<T>, max: &Option<T>) -> Vec<u8> {
+            let flags = min.is_some() as u8 | (max.is_some() as u8) << 1;
+            let mut properties = vec![flags];
+            for bound in [min, max].into_iter().flatten() {
+                properties.extend(encode_to_vec(bound).expect("Writing to a Vec cannot fail"));
+            }
+            properties
+        }
+
+        match self {
+            ArgumentType::Bool | ArgumentType::BlockPos | ArgumentType::Vec3 | ArgumentType::Identifier => Vec::new(),
+            ArgumentType::Integer { min, max } => range(min, max),
+            ArgumentType::Double { min, max } => range(min, max),
+            ArgumentType::String(kind) => vec![match kind {
+                StringKind::Word => 0,
+                StringKind::Phrase => 1,
+                StringKind::Greedy => 2,
+            }],
+            // Single entity, players only.
+            ArgumentType::Player => vec![0x01 | 0x02],
+            ArgumentType::Players => vec![0x02],
+            // Multiple holders allowed.
+            ArgumentType::ScoreHolders => vec![0x01],
+        }
+    }
+}
+
+/// Flattens the part of the tree `source` may use into a Commands packet.
+pub fn commands_packet(root: &CommandNode, source: &CommandSource) -> Commands {
+    let mut nodes = Vec::new();
+    let mut queue = VecDeque::from([root]);
+    // Children are numbered in the order they are queued, which is the order they are emitted.
+    let mut next_index = 1;
+    while let Some(node) = queue.pop_front() {
+        let children = node.children.iter()
+            .filter(|child| source.can_use(child))
+            .collect::<Vec<_>>();
+        let child_indices = (next_index..next_index + children.len() as i32).map(VarInt).collect();
+        next_index += children.len() as i32;
+        queue.extend(children);
+
+        let kind = match &node.kind {
+            NodeKind::Root => DeclaredNodeKind::Root,
+            NodeKind::Literal(name) => DeclaredNodeKind::Literal(name.clone()),
+            NodeKind::Argument { name, argument_type } => DeclaredNodeKind::Argument {
+                name: name.clone(),
+                parser: VarInt(argument_type.parser_id()),
+                properties: argument_type.encode_properties(),
+                suggestions: asks_server(node, argument_type).then(|| Identifier::minecraft("ask_server")),
+            },
+        };
+        nodes.push(DeclaredNode {
+            kind,
+            executable: node.executor.is_some(),
+            children: child_indices,
+            redirect: None,
+        });
+    }
+    Commands { nodes, root_index: VarInt(0) }
+}
+
+/// Player names come from the server until the client learns them from the tab list.
+fn asks_server(node: &CommandNode, argument_type: &ArgumentType) -> bool {
+    node.suggestions.is_some() || matches!(argument_type, ArgumentType::Player | ArgumentType::Players | ArgumentType::ScoreHolders)
+}
+
+/// Sends the commands a player may use, again whenever their permissions change.
+pub fn send_commands(connection: &ConnectionHandle, connections: Arc<ConnectionRegistry>) -> anyhow::Result<()> {
+    let source = CommandSource::player(connection.clone(), connections);
+    let packet = commands_packet(dispatcher().read().unwrap().root(), &source);
+    connection.send(&packet)
+}
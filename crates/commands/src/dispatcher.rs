@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+use anyhow::anyhow;
+use once_cell::sync::Lazy;
+use crate::prelude::{ArgumentValue, CommandContext, CommandExecutor, CommandNode, CommandSource, NodeKind, StringReader};
+
+/// Root of the command tree.
+#[derive(Debug)]
+pub struct CommandDispatcher {
+    root: CommandNode,
+}
+
+/// A command matched against the tree, ready to run.
+pub struct ParsedCommand {
+    pub executor: CommandExecutor,
+    pub arguments: HashMap<String, ArgumentValue>,
+}
+
+/// Furthest point parsing got to, which makes for the most useful error.
+struct SyntaxError {
+    cursor: usize,
+    message: String,
+}
+
+impl Default for CommandDispatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CommandDispatcher {
+    pub fn new() -> Self {
+        Self { root: CommandNode::new(NodeKind::Root) }
+    }
+
+    pub fn root(&self) -> &CommandNode {
+        &self.root
+    }
+
+    /// Adds a top-level command, merging it with an existing one of the same name.
+    pub fn register(&mut self, mut command: CommandNode) {
+        if command.permission.is_none() && command.is_literal() {
+            command.permission = Some(format!("minecraft.command.{}", command.name()));
+        }
+        self.root.add_child(command);
+    }
+
+    /// Matches `input` (without the leading `/`) against the commands `source` may use.
+    pub fn parse(&self, source: &CommandSource, input: &str) -> anyhow::Result<ParsedCommand> {
+        let mut reader = StringReader::new(input);
+        let mut arguments = Vec::new();
+        let mut error = SyntaxError { cursor: 0, message: "Unknown or incomplete command".to_string() };
+        match parse_children(&self.root, source, &mut reader, &mut arguments, &mut error) {
+            Some(executor) => Ok(ParsedCommand { executor, arguments: arguments.into_iter().collect() }),
+            None => {
+                let cursor = error.cursor.min(input.len());
+                let mut start = cursor.saturating_sub(10);
+                while !input.is_char_boundary(start) {
+                    start -= 1;
+                }
+                Err(anyhow!("{}\n...{}<--[HERE]", error.message, &input[start..cursor]))
+            }
+        }
+    }
+
+    pub fn execute(&self, source: &CommandSource, input: &str) -> anyhow::Result<()> {
+        let parsed = self.parse(source, input)?;
+        (parsed.executor)(&CommandContext::new(source, input, parsed.arguments))
+    }
+}
+
+/// Tries the children of `node` at the reader's cursor, literals before arguments.
+fn parse_children(
+    node: &CommandNode,
+    source: &CommandSource,
+    reader: &mut StringReader,
+    arguments: &mut Vec<(String, ArgumentValue)>,
+    error: &mut SyntaxError,
+) -> Option<CommandExecutor> {
+    let start = reader.cursor();
+    let children = node.children.iter().filter(|child| child.is_literal())
+        .chain(node.children.iter().filter(|child| !child.is_literal()))
+        .filter(|child| source.can_use(child));
+
+    for child in children {
+        reader.set_cursor(start);
+        let argument_count = arguments.len();
+        let parsed = match &child.kind {
+            NodeKind::Root => continue,
+            NodeKind::Literal(name) => if reader.read_until_space() == name { Ok(()) } else { continue },
+            NodeKind::Argument { name, argument_type } => argument_type.parse(reader)
+                .map(|value| arguments.push((name.clone(), value))),
+        };
+        let result = parsed.and_then(|_| match reader.peek() {
+            None | Some(' ') => Ok(()),
+            Some(_) => Err(anyhow!("Expected whitespace to end one argument, but found trailing data")),
+        });
+        if let Err(err) = result {
+            record_error(error, reader.cursor(), err.to_string());
+            arguments.truncate(argument_count);
+            continue;
+        }
+
+        if !reader.can_read() {
+            if let Some(executor) = &child.executor {
+                return Some(executor.clone());
+            }
+            record_error(error, reader.cursor(), "Unknown or incomplete command".to_string());
+        } else {
+            reader.skip();
+            if let Some(executor) = parse_children(child, source, reader, arguments, error) {
+                return Some(executor);
+            }
+        }
+        arguments.truncate(argument_count);
+    }
+
+    let message = if node.children.is_empty() { "Incorrect argument for command" } else { "Unknown or incomplete command" };
+    record_error(error, start, message.to_string());
+    None
+}
+
+fn record_error(error: &mut SyntaxError, cursor: usize, message: String) {
+    if cursor > error.cursor {
+        *error = SyntaxError { cursor, message };
+    }
+}
+
+static DISPATCHER: Lazy<RwLock<CommandDispatcher>> = Lazy::new(|| RwLock::new(CommandDispatcher::new()));
+
+pub fn dispatcher() -> &'static RwLock<CommandDispatcher> {
+    &DISPATCHER
+}
+
+pub fn register_command(command: CommandNode) {
+    DISPATCHER.write().unwrap().register(command);
+}
+
+/// Parses and runs a command on the global dispatcher. The tree is not locked while the command runs.
+pub fn execute_command(source: &CommandSource, input: &str) -> anyhow::Result<()> {
+    let input = input.strip_prefix('/').unwrap_or(input);
+    let parsed = DISPATCHER.read().unwrap().parse(source, input)?;
+    (parsed.executor)(&CommandContext::new(source, input, parsed.arguments))
+}
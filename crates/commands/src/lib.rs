@@ -0,0 +1,22 @@
+pub mod reader;
+pub mod argument;
+pub mod node;
+pub mod context;
+pub mod source;
+pub mod dispatcher;
+pub mod builtin;
+pub mod chat;
+pub mod declare;
+pub mod suggest;
+
+pub mod prelude {
+    pub use crate::reader::*;
+    pub use crate::argument::*;
+    pub use crate::node::*;
+    pub use crate::context::*;
+    pub use crate::source::*;
+    pub use crate::dispatcher::*;
+    pub use crate::chat::*;
+    pub use crate::declare::*;
+    pub use crate::suggest::*;
+}
@@ -0,0 +1,122 @@
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+use crate::prelude::{ArgumentType, CommandContext, CommandSource};
+
+pub type CommandExecutor = Arc<dyn Fn(&CommandContext) -> anyhow::Result<()> + Send + Sync>;
+
+/// Completions for the partial text of an argument.
+pub type SuggestionProvider = Arc<dyn Fn(&CommandSource, &str) -> Vec<String> + Send + Sync>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NodeKind {
+    Root,
+    Literal(String),
+    Argument { name: String, argument_type: ArgumentType },
+}
+
+/// Node of the command tree, built with [`literal`] and [`argument`].
+#[derive(Clone)]
+pub struct CommandNode {
+    pub kind: NodeKind,
+    pub children: Vec<CommandNode>,
+    pub executor: Option<CommandExecutor>,
+    /// Permission level from 0 to 4 the source needs to use this node.
+    pub permission_level: u8,
+    /// Permission node which providers may grant or deny instead, top-level commands get
+    /// `minecraft.command.<name>` when registered without one.
+    pub permission: Option<String>,
+    /// Asked by the client while typing this argument.
+    pub suggestions: Option<SuggestionProvider>,
+}
+
+pub fn literal(name: impl Into<String>) -> CommandNode {
+    CommandNode::new(NodeKind::Literal(name.into()))
+}
+
+pub fn argument(name: impl Into<String>, argument_type: ArgumentType) -> CommandNode {
+    CommandNode::new(NodeKind::Argument { name: name.into(), argument_type })
+}
+
+impl CommandNode {
+    pub fn new(kind: NodeKind) -> Self {
+        Self {
+            kind,
+            children: Vec::new(),
+            executor: None,
+            permission_level: 0,
+            permission: None,
+            suggestions: None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match &self.kind {
+            NodeKind::Root => "",
+            NodeKind::Literal(name) => name,
+            NodeKind::Argument { name, .. } => name,
+        }
+    }
+
+    pub fn then(mut self, child: CommandNode) -> Self {
+        self.add_child(child);
+        self
+    }
+
+    pub fn executes(mut self, executor: impl Fn(&CommandContext) -> anyhow::Result<()> + Send + Sync + 'static) -> Self {
+        self.executor = Some(Arc::new(executor));
+        self
+    }
+
+    pub fn suggests(mut self, provider: impl Fn(&CommandSource, &str) -> Vec<String> + Send + Sync + 'static) -> Self {
+        self.suggestions = Some(Arc::new(provider));
+        self
+    }
+
+    pub fn requires(mut self, permission_level: u8) -> Self {
+        self.permission_level = permission_level;
+        self
+    }
+
+    pub fn permission(mut self, node: impl Into<String>) -> Self {
+        self.permission = Some(node.into());
+        self
+    }
+
+    /// Adds a child, merging it into an existing child of the same kind and name like Brigadier does.
+    pub fn add_child(&mut self, child: CommandNode) {
+        if let Some(existing) = self.children.iter_mut().find(|existing| existing.kind == child.kind) {
+            if child.executor.is_some() {
+                existing.executor = child.executor;
+            }
+            if child.suggestions.is_some() {
+                existing.suggestions = child.suggestions;
+            }
+            existing.permission_level = child.permission_level;
+            if child.permission.is_some() {
+                existing.permission = child.permission;
+            }
+            for grandchild in child.children {
+                existing.add_child(grandchild);
+            }
+        } else {
+            self.children.push(child);
+        }
+    }
+
+    pub fn is_literal(&self) -> bool {
+        matches!(self.kind, NodeKind::Literal(_))
+    }
+}
+
+impl Debug for CommandNode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CommandNode")
+            .field("kind", &self.kind)
+            .field("children", &self.children)
+            .field("executable", &self.executor.is_some())
+            .field("permission_level", &self.permission_level)
+            .field("permission", &self.permission)
+            .field("suggests", &self.suggestions.is_some())
+            .finish()
+    }
+}
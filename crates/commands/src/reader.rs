@@ -0,0 +1,138 @@
+use anyhow::bail;
+
+/// Cursor over command input, mirroring Brigadier's `StringReader`.
+#[derive(Debug, Clone)]
+pub struct StringReader<'a> {
+    input: &'a str,
+    cursor: usize,
+}
+
+impl<'a> StringReader<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self { input, cursor: 0 }
+    }
+
+    pub fn input(&self) -> &'a str {
+        self.input
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn set_cursor(&mut self, cursor: usize) {
+        self.cursor = cursor;
+    }
+
+    pub fn remaining(&self) -> &'a str {
+        &self.input[self.cursor..]
+    }
+
+    pub fn can_read(&self) -> bool {
+        self.cursor < self.input.len()
+    }
+
+    pub fn peek(&self) -> Option<char> {
+        self.remaining().chars().next()
+    }
+
+    pub fn skip(&mut self) {
+        if let Some(c) = self.peek() {
+            self.cursor += c.len_utf8();
+        }
+    }
+
+    pub fn expect(&mut self, expected: char) -> anyhow::Result<()> {
+        if self.peek() != Some(expected) {
+            bail!("Expected '{}'", expected);
+        }
+        self.skip();
+        Ok(())
+    }
+
+    /// Reads up to the next space, which is how literals and player names are delimited.
+    pub fn read_until_space(&mut self) -> &'a str {
+        let start = self.cursor;
+        while self.peek().is_some_and(|c| c != ' ') {
+            self.skip();
+        }
+        &self.input[start..self.cursor]
+    }
+
+    pub fn read_unquoted(&mut self) -> &'a str {
+        let start = self.cursor;
+        while self.peek().is_some_and(is_allowed_in_unquoted_string) {
+            self.skip();
+        }
+        &self.input[start..self.cursor]
+    }
+
+    /// Reads a string in single or double quotes, `\` escapes the quote and itself.
+    pub fn read_quoted(&mut self) -> anyhow::Result<String> {
+        let Some(quote) = self.peek().filter(|c| *c == '"' || *c == '\'') else { bail!("Expected quote to start a string") };
+        self.skip();
+        let mut result = String::new();
+        let mut escaped = false;
+        while let Some(c) = self.peek() {
+            self.skip();
+            if escaped {
+                if c != quote && c != '\\' {
+                    bail!("Invalid escape sequence '\\{}' in quoted string", c);
+                }
+                result.push(c);
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == quote {
+                return Ok(result);
+            } else {
+                result.push(c);
+            }
+        }
+        bail!("Unclosed quoted string")
+    }
+
+    pub fn read_string(&mut self) -> anyhow::Result<String> {
+        match self.peek() {
+            Some('"' | '\'') => self.read_quoted(),
+            _ => Ok(self.read_unquoted().to_string()),
+        }
+    }
+
+    pub fn read_int(&mut self) -> anyhow::Result<i32> {
+        let number = self.read_number();
+        if number.is_empty() {
+            bail!("Expected integer");
+        }
+        number.parse().map_err(|_| anyhow::anyhow!("Invalid integer '{}'", number))
+    }
+
+    pub fn read_double(&mut self) -> anyhow::Result<f64> {
+        let number = self.read_number();
+        if number.is_empty() {
+            bail!("Expected double");
+        }
+        number.parse().map_err(|_| anyhow::anyhow!("Invalid double '{}'", number))
+    }
+
+    pub fn read_bool(&mut self) -> anyhow::Result<bool> {
+        match self.read_unquoted() {
+            "true" => Ok(true),
+            "false" => Ok(false),
+            "" => bail!("Expected bool"),
+            other => bail!("Invalid bool, expected true or false but found '{}'", other),
+        }
+    }
+
+    fn read_number(&mut self) -> &'a str {
+        let start = self.cursor;
+        while self.peek().is_some_and(|c| c.is_ascii_digit() || c == '.' || c == '-') {
+            self.skip();
+        }
+        &self.input[start..self.cursor]
+    }
+}
+
+pub fn is_allowed_in_unquoted_string(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '+')
+}
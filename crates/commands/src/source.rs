@@ -0,0 +1,120 @@
+use std::sync::{Arc, Mutex};
+use log::{info, warn};
+use dolls_core::text::TextComponent;
+use dolls_entities::prelude::Vec3;
+use dolls_network::prelude::{has_permission, permission_level, player_position, send_permission_level, ConnectionHandle, ConnectionRegistry, ConnectionState,
+    SystemChatMessage, MAX_PERMISSION_LEVEL};
+use dolls_world::level::level;
+use dolls_world::game_rules::SEND_COMMAND_FEEDBACK;
+use crate::prelude::{send_commands, CommandNode};
+
+/// Tells a player its permission level and re-sends the commands it may use, after its permissions
+/// changed, e.g. by `op` or a plugin's permission provider.
+pub fn refresh_permissions(connection: &ConnectionHandle, connections: Arc<ConnectionRegistry>) -> anyhow::Result<()> {
+    if connection.state() != ConnectionState::Play {
+        return Ok(());
+    }
+    send_permission_level(connection)?;
+    send_commands(connection, connections)
+}
+
+#[derive(Debug, Clone)]
+pub enum CommandSender {
+    Console,
+    Player(ConnectionHandle),
+    /// A console connected over the network such as RCON, which answers with the feedback
+    /// collected here.
+    Remote(Arc<Mutex<Vec<String>>>),
+}
+
+/// Who runs a command, and the server it runs on.
+#[derive(Debug, Clone)]
+pub struct CommandSource {
+    pub sender: CommandSender,
+    pub connections: Arc<ConnectionRegistry>,
+}
+
+impl CommandSource {
+    pub fn console(connections: Arc<ConnectionRegistry>) -> Self {
+        Self { sender: CommandSender::Console, connections }
+    }
+
+    pub fn player(connection: ConnectionHandle, connections: Arc<ConnectionRegistry>) -> Self {
+        Self { sender: CommandSender::Player(connection), connections }
+    }
+
+    /// A remote console with the permissions of the console, see [`CommandSource::take_output`].
+    pub fn remote(connections: Arc<ConnectionRegistry>) -> Self {
+        Self { sender: CommandSender::Remote(Arc::default()), connections }
+    }
+
+    /// Feedback lines given to a remote console since the last call, none for other senders.
+    pub fn take_output(&self) -> Vec<String> {
+        match &self.sender {
+            CommandSender::Remote(output) => std::mem::take(&mut output.lock().unwrap()),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match &self.sender {
+            CommandSender::Console => "Server".to_string(),
+            CommandSender::Player(connection) => connection.username().unwrap_or_default(),
+            CommandSender::Remote(_) => "Rcon".to_string(),
+        }
+    }
+
+    /// Where relative coordinates start from: the player's position, or the world spawn for the console.
+    pub fn position(&self) -> Vec3 {
+        let player = match &self.sender {
+            CommandSender::Console | CommandSender::Remote(_) => None,
+            CommandSender::Player(connection) => player_position(connection),
+        };
+        player.map(|position| position.vec3()).unwrap_or_else(|| {
+            let spawn = level().read().unwrap().spawn();
+            Vec3::new(spawn.x as f64 + 0.5, spawn.y as f64, spawn.z as f64 + 0.5)
+        })
+    }
+
+    pub fn permission_level(&self) -> u8 {
+        match &self.sender {
+            CommandSender::Console | CommandSender::Remote(_) => MAX_PERMISSION_LEVEL,
+            CommandSender::Player(connection) => permission_level(connection),
+        }
+    }
+
+    pub fn has_permission(&self, level: u8) -> bool {
+        self.permission_level() >= level
+    }
+
+    /// Whether the source may use `node`, consoles may use everything.
+    pub fn can_use(&self, node: &CommandNode) -> bool {
+        match (&self.sender, &node.permission) {
+            (CommandSender::Console | CommandSender::Remote(_), _) => true,
+            (CommandSender::Player(connection), Some(permission)) => has_permission(connection, permission, node.permission_level),
+            (CommandSender::Player(_), None) => self.has_permission(node.permission_level),
+        }
+    }
+
+    /// Command feedback, printed for the console, collected for remote consoles and sent as a system
+    /// message to players unless the `sendCommandFeedback` game rule is off.
+    pub fn send_message(&self, message: TextComponent) {
+        match &self.sender {
+            CommandSender::Console => info!("{}", message.to_plain_text()),
+            CommandSender::Remote(output) => output.lock().unwrap().push(message.to_plain_text()),
+            CommandSender::Player(_) if !level().read().unwrap().game_rules.get_bool(SEND_COMMAND_FEEDBACK) => {}
+            CommandSender::Player(connection) => {
+                // A closed connection has nobody left to read the feedback.
+                let _ = connection.send(&SystemChatMessage { content: message, overlay: false });
+            }
+        }
+    }
+
+    pub fn send_error(&self, message: impl Into<String>) {
+        match &self.sender {
+            CommandSender::Console => warn!("{}", message.into()),
+            CommandSender::Remote(output) => output.lock().unwrap().push(message.into()),
+            CommandSender::Player(_) => self.send_message(TextComponent::text(message).color("red")),
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use dolls_network::prelude::{SuggestionMatch, Suggestions};
+use crate::prelude::{ArgumentType, CommandNode, CommandSource, NodeKind, StringKind, StringReader};
+
+const SELECTORS: [&str; 4] = ["@a", "@p", "@r", "@s"];
+
+/// Completions for the last, partially typed token of `input`.
+pub fn suggest(root: &CommandNode, source: &CommandSource, input: &str) -> Suggestions {
+    let mut candidates = Vec::new();
+    collect(root, source, &mut StringReader::new(input), &mut candidates);
+
+    // Completions of different tokens cannot be shown together, the furthest ones win.
+    let start = candidates.iter().map(|(start, _)| *start).max().unwrap_or(input.len());
+    let mut matches = candidates.into_iter()
+        .filter(|(candidate_start, _)| *candidate_start == start)
+        .map(|(_, text)| text)
+        .collect::<Vec<_>>();
+    matches.sort();
+    matches.dedup();
+    Suggestions {
+        start,
+        length: input.len() - start,
+        matches: matches.into_iter().map(|text| SuggestionMatch { text, tooltip: None }).collect(),
+    }
+}
+
+fn collect(node: &CommandNode, source: &CommandSource, reader: &mut StringReader, candidates: &mut Vec<(usize, String)>) {
+    let start = reader.cursor();
+    let partial = reader.remaining();
+    let is_last_token = !partial.contains(' ');
+
+    for child in node.children.iter().filter(|child| source.can_use(child)) {
+        reader.set_cursor(start);
+        let parsed = match &child.kind {
+            NodeKind::Root => false,
+            NodeKind::Literal(name) => reader.read_until_space() == name,
+            NodeKind::Argument { argument_type, .. } => argument_type.parse(reader).is_ok(),
+        };
+        if parsed && reader.peek() == Some(' ') {
+            reader.skip();
+            collect(child, source, reader, candidates);
+            continue;
+        }
+
+        let greedy = matches!(&child.kind, NodeKind::Argument { argument_type: ArgumentType::String(StringKind::Greedy), .. });
+        if is_last_token || greedy {
+            candidates.extend(child_suggestions(child, source, partial).into_iter().map(|text| (start, text)));
+        }
+    }
+}
+
+fn child_suggestions(child: &CommandNode, source: &CommandSource, partial: &str) -> Vec<String> {
+    if let Some(provider) = &child.suggestions {
+        return provider(source, partial);
+    }
+    match &child.kind {
+        NodeKind::Literal(name) if name.starts_with(partial) => vec![name.clone()],
+        NodeKind::Argument { argument_type: ArgumentType::Player | ArgumentType::Players | ArgumentType::ScoreHolders, .. } => {
+            let lowercase = partial.to_lowercase();
+            let names = source.connections.players().into_iter()
+                .filter_map(|player| player.username())
+                .filter(|name| name.to_lowercase().starts_with(&lowercase));
+            let selectors = SELECTORS.iter()
+                .filter(|selector| selector.starts_with(partial))
+                .map(|selector| selector.to_string());
+            names.chain(selectors).collect()
+        }
+        NodeKind::Argument { argument_type: ArgumentType::Bool, .. } => ["true", "false"].iter()
+            .filter(|value| value.starts_with(partial))
+            .map(|value| value.to_string())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
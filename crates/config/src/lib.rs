@@ -0,0 +1,460 @@
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use anyhow::{anyhow, bail, Context};
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_CONFIG_PATH: &str = "server.toml";
+/// Environment variables starting with this override config keys, see [`ServerConfig::apply_env`].
+pub const ENV_PREFIX: &str = "DOLLS_";
+
+/// Root of `server.toml`.
+///
+/// Later sources win: the defaults, the file, `DOLLS_*` environment variables, then command line flags.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct ServerConfig {
+    pub network: NetworkConfig,
+    pub server: GameServerConfig,
+    pub world: WorldConfig,
+    pub heartbeat: HeartbeatConfig,
+    pub rcon: RconConfig,
+    pub query: QueryConfig,
+    pub metrics: MetricsConfig,
+    pub telemetry: TelemetryConfig,
+    pub resource_pack: ResourcePackConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct NetworkConfig {
+    pub bind_address: IpAddr,
+    pub port: u16,
+    /// Further addresses to accept players on, e.g. `"[::]:25565"` next to an IPv4 bind address.
+    pub additional_listeners: Vec<SocketAddr>,
+    /// Packets of at least this many bytes are compressed, a negative value disables compression.
+    pub compression_threshold: i32,
+    /// Seconds a connection may take from handshake to finishing login.
+    pub login_timeout: u64,
+    /// Seconds on top of `login-timeout` a connection may take to finish Configuration, where the player
+    /// may have to answer the resource pack prompt and download the pack.
+    pub configuration_timeout: u64,
+    /// Seconds a player in Play may send nothing before it is closed, 0 keeps silent connections.
+    /// Players answer a keep-alive every 15 seconds.
+    pub idle_timeout: u64,
+    /// How much clients are told when they are disconnected for a protocol error during login or configuration.
+    pub disconnect_verbosity: DisconnectVerbosity,
+    /// Milliseconds a packet handler may take before a warning is logged, 0 disables the warning.
+    pub handler_time_budget: u64,
+    /// Announces the server to clients on the local network, which list it in the LAN tab.
+    pub lan_broadcast: bool,
+    /// Status pings a single IP address may make per minute, further ones are closed unanswered.
+    /// 0 disables the limit.
+    pub status_rate_limit: u32,
+    /// Lets clients join which another server sent here with a Transfer packet.
+    pub accepts_transfers: bool,
+    /// Debug aid: appends sequence number, length and CRC32 of every frame to this file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frame_trace: Option<PathBuf>,
+    /// Debug aid: logs every packet with its state, name and a hex dump of its payload. `debug packets`
+    /// toggles it at runtime.
+    pub packet_dump: bool,
+    /// Debug aid: records the packets of every connection to a file of its own in this directory, to
+    /// inspect or replay them with `capture print` and `capture replay`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub capture_directory: Option<PathBuf>,
+    /// How sockets are driven, `io-uring` needs a build with the `io-uring` feature and Linux 5.6 or newer,
+    /// otherwise the default is used.
+    pub io_backend: IoBackend,
+    /// Accept loops per listener, each on a socket of its own that the kernel spreads connections over with
+    /// `SO_REUSEPORT`, 0 starts one per CPU core. Only Unix supports more than one.
+    pub accept_loops: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct GameServerConfig {
+    pub max_players: u32,
+    /// Server list description: text with `&` or `§` formatting codes and `{placeholders}`, or a JSON
+    /// text component.
+    pub motd: String,
+    /// Leaves the player list out of the status, only the count is shown.
+    pub hide_online_players: bool,
+    /// Lines shown when hovering the player count instead of the online players, with `{placeholders}`.
+    pub status_sample: Vec<String>,
+    /// Server name shown in the debug screen, sent as the `minecraft:brand` plugin message.
+    pub brand: String,
+    pub online_mode: bool,
+    /// Rejects chat from players without a signed chat session.
+    pub enforce_secure_profile: bool,
+    /// Radius in chunks sent to players.
+    pub view_distance: u32,
+    /// Radius in chunks around players in which the world ticks.
+    pub simulation_distance: u32,
+    /// Chat template with `{placeholders}` and `{message}`, vanilla formatting when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chat_format: Option<String>,
+    /// Game mode of players when they join.
+    pub game_mode: GameMode,
+    /// Permission level `op` gives, from 1 to 4.
+    pub op_permission_level: u8,
+    /// Only lets operators and players in `whitelist.json` join, `whitelist on` and `off` change it
+    /// until the server restarts.
+    pub whitelist: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct WorldConfig {
+    /// Directory of the world, relative to the working directory.
+    pub level_name: String,
+    /// Seed for new worlds, a number or any text. Empty picks a random one, existing worlds keep theirs.
+    pub level_seed: String,
+    /// How chunks are compressed when saved, any of them is read back.
+    pub region_file_compression: RegionCompression,
+    /// Seconds between saves of the level and changed chunks, 0 only saves on shutdown.
+    pub autosave_interval: u64,
+    /// World spawn as `[x, y, z]`, replacing the one in `level.dat`. Unset keeps that one, new worlds
+    /// pick the surface at the origin.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spawn: Option<[i32; 3]>,
+    /// `reports` directory of the vanilla data generator, its `blocks.json`, `registries.json` and
+    /// `items.json` define the known blocks and items.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reports_directory: Option<PathBuf>,
+    /// `data` directory of data packs such as the vanilla one extracted from the server jar, its recipes
+    /// and advancements are loaded once the items are known from the reports.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_directory: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct HeartbeatConfig {
+    /// `http://` URLs of server lists or monitors to POST the server's status to, none disables heartbeats.
+    pub endpoints: Vec<String>,
+    /// Seconds between heartbeats.
+    pub interval: u64,
+    /// Further attempts after a failed heartbeat, waiting twice as long before each.
+    pub max_retries: u32,
+    /// Seconds a single request may take.
+    pub timeout: u64,
+}
+
+/// Remote console speaking the RCON protocol, on the bind address of the network section.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct RconConfig {
+    pub enabled: bool,
+    pub port: u16,
+    /// Required to run commands, RCON stays off without one.
+    pub password: String,
+}
+
+/// GameSpy4 query protocol over UDP, which server list sites and panels read, on the bind address of
+/// the network section.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct QueryConfig {
+    pub enabled: bool,
+    pub port: u16,
+}
+
+/// Prometheus metrics served over HTTP at `/metrics`, on the bind address of the network section.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct MetricsConfig {
+    pub enabled: bool,
+    pub port: u16,
+}
+
+/// Traces of connections and packet handlers, exported over OTLP by servers built with the `otel` feature.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct TelemetryConfig {
+    /// OTLP/HTTP traces endpoint of a collector, e.g. `http://localhost:4318/v1/traces`. Empty exports nothing.
+    pub otlp_endpoint: String,
+    pub service_name: String,
+    /// Share of connections traced, from 0 to 1.
+    pub sample_ratio: f64,
+}
+
+/// Resource pack offered to players while they join, like vanilla's `resource-pack` properties.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct ResourcePackConfig {
+    /// Download URL of the zip, empty offers no pack.
+    pub url: String,
+    /// SHA-1 of the zip in hex, lets clients reuse a copy they downloaded before. Empty skips the check.
+    pub sha1: String,
+    /// UUID of the pack, derived from the URL when empty.
+    pub id: String,
+    /// Shown on the download prompt, with `&` or `§` formatting codes.
+    pub prompt: String,
+    /// Disconnects players declining the pack.
+    pub required: bool,
+}
+
+/// Socket backend of the listeners and the connections they accept.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IoBackend {
+    /// Readiness polling by the async-std reactor.
+    #[default]
+    AsyncStd,
+    /// Completion based IO through a shared Linux io_uring, fewer syscalls with many connections.
+    IoUring,
+}
+
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DisconnectVerbosity {
+    /// Only what kind of error it was.
+    Minimal,
+    /// The reason and a diagnostic code to look up.
+    #[default]
+    Code,
+    /// Also the full error, which may reveal server internals.
+    Detailed,
+}
+
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GameMode {
+    #[default]
+    Survival,
+    Creative,
+    Adventure,
+    Spectator,
+}
+
+/// Chunk compression in region files, `zstd` worlds can only be opened by Dolls.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RegionCompression {
+    #[default]
+    Deflate,
+    Lz4,
+    Zstd,
+    None,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            bind_address: IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)),
+            port: 25565,
+            additional_listeners: Vec::new(),
+            compression_threshold: 256,
+            login_timeout: 30,
+            configuration_timeout: 300,
+            idle_timeout: 30,
+            disconnect_verbosity: DisconnectVerbosity::Code,
+            handler_time_budget: 50,
+            status_rate_limit: 60,
+            lan_broadcast: false,
+            accepts_transfers: false,
+            frame_trace: None,
+            packet_dump: false,
+            capture_directory: None,
+            io_backend: IoBackend::AsyncStd,
+            accept_loops: 1,
+        }
+    }
+}
+
+impl Default for GameServerConfig {
+    fn default() -> Self {
+        Self {
+            max_players: 20,
+            motd: "A Dolls Server".to_string(),
+            hide_online_players: false,
+            status_sample: Vec::new(),
+            brand: "Dolls".to_string(),
+            online_mode: false,
+            enforce_secure_profile: false,
+            view_distance: 10,
+            simulation_distance: 10,
+            chat_format: None,
+            game_mode: GameMode::Survival,
+            op_permission_level: 4,
+            whitelist: false,
+        }
+    }
+}
+
+impl Default for WorldConfig {
+    fn default() -> Self {
+        Self {
+            level_name: "world".to_string(),
+            level_seed: String::new(),
+            region_file_compression: RegionCompression::Deflate,
+            autosave_interval: 300,
+            spawn: None,
+            reports_directory: None,
+            data_directory: None,
+        }
+    }
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        Self {
+            endpoints: Vec::new(),
+            interval: 60,
+            max_retries: 3,
+            timeout: 10,
+        }
+    }
+}
+
+impl Default for RconConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 25575,
+            password: String::new(),
+        }
+    }
+}
+
+impl Default for QueryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 25565,
+        }
+    }
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 9225,
+        }
+    }
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            otlp_endpoint: String::new(),
+            service_name: "dolls".to_string(),
+            sample_ratio: 1.0,
+        }
+    }
+}
+
+impl NetworkConfig {
+    /// Every address to listen on, the bind address first.
+    pub fn listeners(&self) -> Vec<SocketAddr> {
+        let mut listeners = vec![SocketAddr::new(self.bind_address, self.port)];
+        listeners.extend_from_slice(&self.additional_listeners);
+        listeners
+    }
+
+    /// Accept loops per listener, with 0 resolved to the number of CPU cores.
+    pub fn accept_loops(&self) -> usize {
+        match self.accept_loops {
+            0 => std::thread::available_parallelism().map_or(1, |cores| cores.get()),
+            loops => loops as usize,
+        }
+    }
+
+    /// `None` when silent connections are kept.
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        (self.idle_timeout > 0).then(|| Duration::from_secs(self.idle_timeout))
+    }
+
+    /// `None` when slow handlers are not reported.
+    pub fn handler_time_budget(&self) -> Option<Duration> {
+        (self.handler_time_budget > 0).then(|| Duration::from_millis(self.handler_time_budget))
+    }
+
+    pub fn compression_threshold(&self) -> Option<usize> {
+        usize::try_from(self.compression_threshold).ok()
+    }
+}
+
+impl ServerConfig {
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        toml::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        fs::write(path, self.to_toml()?).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Loads the config, writing the defaults first if the file does not exist yet.
+    pub fn load_or_create(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        if path.exists() {
+            Self::load(path)
+        } else {
+            let config = Self::default();
+            config.save(path)?;
+            Ok(config)
+        }
+    }
+
+    /// Sets `section.key` as written in the file, e.g. `network.port`. The value is read as a TOML value
+    /// such as `25566`, `true` or `["[::]:25565"]`, anything that does not parse as one is a string.
+    pub fn set(&mut self, key: &str, value: &str) -> anyhow::Result<()> {
+        let (section, name) = key.split_once('.').ok_or_else(|| anyhow!("Expected section.key, got {}", key))?;
+        let mut root = toml::Value::try_from(&*self)?;
+        let table = root.get_mut(section).and_then(toml::Value::as_table_mut)
+            .ok_or_else(|| anyhow!("Unknown configuration section {}", section))?;
+        let is_string = matches!(table.get(name), Some(toml::Value::String(_)));
+        let string = toml::Value::String(value.to_string());
+        let parsed = match is_string {
+            true => string.clone(),
+            false => parse_value(value).unwrap_or_else(|| string.clone()),
+        };
+
+        table.insert(name.to_string(), parsed.clone());
+        let config = match root.clone().try_into::<ServerConfig>() {
+            Ok(config) => config,
+            // Strings which look like numbers, e.g. a chat format of `42`.
+            Err(err) if parsed != string => {
+                root[section].as_table_mut().expect("Section is a table").insert(name.to_string(), string);
+                root.try_into::<ServerConfig>().map_err(|_| err)
+                    .with_context(|| format!("Invalid value for {}", key))?
+            }
+            Err(err) => return Err(err).with_context(|| format!("Invalid value for {}", key)),
+        };
+        // Unknown keys are silently dropped when deserializing.
+        if toml::Value::try_from(&config)?.get(section).and_then(|table| table.get(name)).is_none() {
+            bail!("Unknown configuration key {}", key);
+        }
+        *self = config;
+        Ok(())
+    }
+
+    /// Applies `DOLLS_<SECTION>_<KEY>` variables, the key upper-cased with dashes as underscores,
+    /// e.g. `DOLLS_NETWORK_COMPRESSION_THRESHOLD=512`. Other variables are ignored.
+    pub fn apply_env(&mut self, vars: impl IntoIterator<Item = (String, String)>) -> anyhow::Result<()> {
+        for (var, value) in vars {
+            let Some(name) = var.strip_prefix(ENV_PREFIX) else { continue };
+            let name = name.to_ascii_lowercase();
+            let (section, key) = name.split_once('_').ok_or_else(|| anyhow!("{} names no configuration key", var))?;
+            self.set(&format!("{}.{}", section, key.replace('_', "-")), &value)
+                .with_context(|| format!("Failed to apply {}", var))?;
+        }
+        Ok(())
+    }
+
+    /// The configuration as it would be written to the file.
+    pub fn to_toml(&self) -> anyhow::Result<String> {
+        Ok(toml::to_string_pretty(self)?)
+    }
+}
+
+fn parse_value(value: &str) -> Option<toml::Value> {
+    let mut table = toml::from_str::<toml::Table>(&format!("value = {}", value)).ok()?;
+    table.remove("value")
+}
@@ -0,0 +1,78 @@
+use std::hint::black_box;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use dolls_core::datatype::{decode_from_slice, Decode, Encode, VarInt};
+
+/// Values taking one to five bytes as VarInts.
+const VAR_INTS: [i32; 5] = [1, 300, 70_000, 10_000_000, -1];
+
+fn encoded(value: &impl Encode) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    value.encode(&mut bytes).unwrap();
+    bytes
+}
+
+fn var_int(c: &mut Criterion) {
+    let mut group = c.benchmark_group("var_int");
+    for value in VAR_INTS {
+        let size = VarInt(value).written_size();
+        // Followed by more data, as in a buffer of received bytes.
+        let mut bytes = encoded(&VarInt(value));
+        bytes.extend_from_slice(&[0; 8]);
+        group.bench_with_input(BenchmarkId::new("decode", size), &bytes, |b, bytes| {
+            b.iter(|| decode_from_slice::<VarInt>(black_box(bytes)).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("decode_prefix", size), &bytes, |b, bytes| {
+            b.iter(|| VarInt::decode_prefix(black_box(bytes)).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("encode", size), &value, |b, &value| {
+            let mut buffer = Vec::with_capacity(5);
+            b.iter(|| {
+                buffer.clear();
+                VarInt(black_box(value)).encode(&mut buffer).unwrap();
+            })
+        });
+    }
+    // Mixed sizes back to back, as the fields of a packet are.
+    let mut stream = Vec::new();
+    for index in 0..4096 {
+        VarInt(VAR_INTS[index % VAR_INTS.len()]).encode(&mut stream).unwrap();
+    }
+    group.throughput(Throughput::Elements(4096));
+    group.bench_with_input("decode_stream", &stream, |b, stream| {
+        b.iter(|| {
+            let mut reader = black_box(stream.as_slice());
+            while !reader.is_empty() {
+                black_box(VarInt::decode(&mut reader).unwrap());
+            }
+        })
+    });
+    group.bench_with_input("decode_prefix_stream", &stream, |b, stream| {
+        b.iter(|| {
+            let mut bytes = black_box(stream.as_slice());
+            while let Some((value, size)) = VarInt::decode_prefix(bytes).unwrap() {
+                black_box(value);
+                bytes = &bytes[size..];
+            }
+        })
+    });
+    group.finish();
+}
+
+fn string(c: &mut Criterion) {
+    let mut group = c.benchmark_group("string_decode");
+    let cases = [
+        ("username", "Notch".to_string()),
+        ("chat", "a".repeat(256)),
+        ("unicode", "\u{4e2d}\u{6587}".repeat(128)),
+        ("json", format!("{{\"text\":\"{}\"}}", "x".repeat(4096))),
+    ];
+    for (name, value) in cases {
+        let bytes = encoded(&value);
+        group.throughput(Throughput::Bytes(bytes.len() as u64));
+        group.bench_with_input(name, &bytes, |b, bytes| b.iter(|| decode_from_slice::<String>(black_box(bytes)).unwrap()));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, var_int, string);
+criterion_main!(benches);
@@ -0,0 +1,272 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use std::sync::RwLock;
+use anyhow::{anyhow, bail, Context};
+use once_cell::sync::Lazy;
+use serde_json::Value;
+use crate::datatype::Identifier;
+use crate::item::{ItemRegistry, ItemStack};
+use crate::recipe::json_files;
+use crate::text::TextComponent;
+
+/// Shape of an advancement's frame, by protocol id.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub enum AdvancementFrame {
+    #[default]
+    Task,
+    Challenge,
+    Goal,
+}
+
+impl AdvancementFrame {
+    /// Color and translation key of the chat message announcing completion.
+    pub fn announcement(self) -> (&'static str, &'static str) {
+        match self {
+            AdvancementFrame::Task => ("green", "chat.type.advancement.task"),
+            AdvancementFrame::Challenge => ("dark_purple", "chat.type.advancement.challenge"),
+            AdvancementFrame::Goal => ("green", "chat.type.advancement.goal"),
+        }
+    }
+}
+
+/// How an advancement appears in the advancements screen.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdvancementDisplay {
+    pub title: TextComponent,
+    pub description: TextComponent,
+    pub icon: ItemStack,
+    pub frame: AdvancementFrame,
+    /// Texture behind the tab, only used by roots.
+    pub background: Option<Identifier>,
+    pub show_toast: bool,
+    pub announce_to_chat: bool,
+    /// Hidden advancements only show up once done.
+    pub hidden: bool,
+    /// Position in the tab, laid out when the registry is loaded.
+    pub x: f32,
+    pub y: f32,
+}
+
+/// A condition of an advancement, met when its trigger fires with matching `conditions`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Criterion {
+    pub trigger: Identifier,
+    pub conditions: Value,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AdvancementRewards {
+    pub recipes: Vec<Identifier>,
+    pub experience: i32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Advancement {
+    pub parent: Option<Identifier>,
+    pub display: Option<AdvancementDisplay>,
+    pub criteria: BTreeMap<String, Criterion>,
+    /// Every group needs one of its criteria met, by default each criterion is its own group.
+    pub requirements: Vec<Vec<String>>,
+    pub rewards: AdvancementRewards,
+    pub sends_telemetry_event: bool,
+}
+
+impl Advancement {
+    /// Whether the criteria for which `met` holds complete the advancement.
+    pub fn is_done(&self, met: impl Fn(&str) -> bool) -> bool {
+        !self.requirements.is_empty() && self.requirements.iter().all(|group| group.iter().any(|criterion| met(criterion)))
+    }
+}
+
+/// Advancements by id, as found in the `advancement` directories of data packs.
+#[derive(Debug, Clone, Default)]
+pub struct AdvancementRegistry {
+    advancements: Vec<(Identifier, Advancement)>,
+    index: HashMap<Identifier, usize>,
+}
+
+impl AdvancementRegistry {
+    /// Reads every namespace of a `data` directory. Advancements with unknown icons are skipped,
+    /// and so are their children.
+    pub fn load(data_directory: &Path, items: &ItemRegistry) -> anyhow::Result<Self> {
+        let namespaces = fs::read_dir(data_directory).with_context(|| format!("Failed to read {}", data_directory.display()))?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut loaded = Vec::new();
+        for namespace in namespaces.iter().filter(|path| path.is_dir()) {
+            let name = namespace.file_name().and_then(|name| name.to_str()).unwrap_or_default().to_string();
+            for (path, json) in json_files(&namespace.join("advancement"))? {
+                let id = Identifier::new(name.clone(), path)?;
+                if let Some(advancement) = parse_advancement(&json, items).with_context(|| format!("Invalid advancement {}", id))? {
+                    loaded.push((id, advancement));
+                }
+            }
+        }
+        loaded.sort_by_key(|(id, _)| id.to_string());
+
+        // Children are only kept once their parent is, so a parent always comes first.
+        let mut registry = Self::default();
+        loop {
+            let before = loaded.len();
+            loaded.retain(|(id, advancement)| {
+                let ready = advancement.parent.as_ref().is_none_or(|parent| registry.get(parent).is_some());
+                if ready {
+                    registry.register(id.clone(), advancement.clone());
+                }
+                !ready
+            });
+            if loaded.len() == before {
+                break;
+            }
+        }
+        registry.lay_out();
+        Ok(registry)
+    }
+
+    /// Adds or replaces an advancement, its parent has to be registered first.
+    pub fn register(&mut self, id: Identifier, advancement: Advancement) {
+        match self.index.get(&id) {
+            Some(&position) => self.advancements[position].1 = advancement,
+            None => {
+                self.index.insert(id.clone(), self.advancements.len());
+                self.advancements.push((id, advancement));
+            }
+        }
+    }
+
+    pub fn get(&self, id: &Identifier) -> Option<&Advancement> {
+        self.index.get(id).map(|&position| &self.advancements[position].1)
+    }
+
+    pub fn len(&self) -> usize {
+        self.advancements.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.advancements.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Identifier, &Advancement)> {
+        self.advancements.iter().map(|(id, advancement)| (id, advancement))
+    }
+
+    /// Advancements with a criterion of the given trigger, with the criterion's name.
+    pub fn with_trigger<'a>(&'a self, trigger: &'a Identifier) -> impl Iterator<Item = (&'a Identifier, &'a str, &'a Criterion)> {
+        self.iter().flat_map(move |(id, advancement)| advancement.criteria.iter()
+            .filter(move |(_, criterion)| criterion.trigger == *trigger)
+            .map(move |(name, criterion)| (id, name.as_str(), criterion)))
+    }
+
+    /// Places displayed advancements of each tree in columns by depth, siblings below each other.
+    fn lay_out(&mut self) {
+        let mut children: HashMap<Identifier, Vec<usize>> = HashMap::new();
+        let mut roots = Vec::new();
+        for (position, (_, advancement)) in self.advancements.iter().enumerate() {
+            match &advancement.parent {
+                Some(parent) => children.entry(parent.clone()).or_default().push(position),
+                None => roots.push(position),
+            }
+        }
+        for root in roots {
+            // Depth first, a parent shares the row of its first child and each leaf ends a row.
+            let mut row = 0;
+            let mut pending = vec![(root, 0)];
+            while let Some((position, depth)) = pending.pop() {
+                let (id, advancement) = &mut self.advancements[position];
+                if let Some(display) = &mut advancement.display {
+                    display.x = depth as f32;
+                    display.y = row as f32;
+                }
+                match children.get(id) {
+                    Some(below) => pending.extend(below.iter().rev().map(|child| (*child, depth + 1))),
+                    None => row += 1,
+                }
+            }
+        }
+    }
+}
+
+/// An advancement, `None` if its icon is an unknown item.
+fn parse_advancement(json: &Value, items: &ItemRegistry) -> anyhow::Result<Option<Advancement>> {
+    let parent = json.get("parent").and_then(Value::as_str)
+        .map(|parent| parent.parse::<Identifier>().map_err(|_| anyhow!("Invalid parent {}", parent)))
+        .transpose()?;
+    let display = match json.get("display") {
+        Some(display) => match parse_display(display, items)? {
+            Some(display) => Some(display),
+            None => return Ok(None),
+        },
+        None => None,
+    };
+
+    let mut criteria = BTreeMap::new();
+    for (name, criterion) in json.get("criteria").and_then(Value::as_object).ok_or_else(|| anyhow!("Advancement has no criteria"))? {
+        let trigger = criterion.get("trigger").and_then(Value::as_str).ok_or_else(|| anyhow!("Criterion {} has no trigger", name))?;
+        let trigger = trigger.parse().map_err(|_| anyhow!("Invalid trigger {}", trigger))?;
+        let conditions = criterion.get("conditions").cloned().unwrap_or(Value::Null);
+        criteria.insert(name.clone(), Criterion { trigger, conditions });
+    }
+    let requirements = match json.get("requirements").and_then(Value::as_array) {
+        Some(groups) => groups.iter()
+            .map(|group| group.as_array().ok_or_else(|| anyhow!("Requirements are lists of criteria"))?.iter()
+                .map(|name| match name.as_str() {
+                    Some(name) if criteria.contains_key(name) => Ok(name.to_string()),
+                    _ => Err(anyhow!("Requirements name unknown criterion {}", name)),
+                })
+                .collect::<anyhow::Result<Vec<_>>>())
+            .collect::<anyhow::Result<Vec<_>>>()?,
+        None => criteria.keys().map(|name| vec![name.clone()]).collect(),
+    };
+
+    let rewards = json.get("rewards").map(|rewards| AdvancementRewards {
+        recipes: rewards.get("recipes").and_then(Value::as_array).into_iter().flatten()
+            .filter_map(Value::as_str)
+            .filter_map(|recipe| recipe.parse().ok())
+            .collect(),
+        experience: rewards.get("experience").and_then(Value::as_i64).unwrap_or(0) as i32,
+    }).unwrap_or_default();
+    let sends_telemetry_event = json.get("sends_telemetry_event").and_then(Value::as_bool).unwrap_or(false);
+    Ok(Some(Advancement { parent, display, criteria, requirements, rewards, sends_telemetry_event }))
+}
+
+fn parse_display(json: &Value, items: &ItemRegistry) -> anyhow::Result<Option<AdvancementDisplay>> {
+    let text = |key: &str| -> anyhow::Result<TextComponent> {
+        let value = json.get(key).ok_or_else(|| anyhow!("Display has no {}", key))?;
+        serde_json::from_value(value.clone()).with_context(|| format!("Invalid {}", key))
+    };
+    let icon = json.get("icon").ok_or_else(|| anyhow!("Display has no icon"))?;
+    let item = icon.get("id").or_else(|| icon.get("item")).and_then(Value::as_str).ok_or_else(|| anyhow!("Icon names no item"))?;
+    let item = item.parse::<Identifier>().map_err(|_| anyhow!("Invalid icon {}", item))?;
+    let Some(item_id) = items.id(&item) else { return Ok(None) };
+    let count = icon.get("count").and_then(Value::as_i64).unwrap_or(1) as i32;
+    let frame = match json.get("frame").and_then(Value::as_str).unwrap_or("task") {
+        "task" => AdvancementFrame::Task,
+        "challenge" => AdvancementFrame::Challenge,
+        "goal" => AdvancementFrame::Goal,
+        frame => bail!("Unknown frame {}", frame),
+    };
+    let background = json.get("background").and_then(Value::as_str)
+        .map(|background| background.parse::<Identifier>().map_err(|_| anyhow!("Invalid background {}", background)))
+        .transpose()?;
+    let flag = |key: &str, default: bool| json.get(key).and_then(Value::as_bool).unwrap_or(default);
+    Ok(Some(AdvancementDisplay {
+        title: text("title")?,
+        description: text("description")?,
+        icon: ItemStack::new(item_id, count),
+        frame,
+        background,
+        show_toast: flag("show_toast", true),
+        announce_to_chat: flag("announce_to_chat", true),
+        hidden: flag("hidden", false),
+        x: 0.0,
+        y: 0.0,
+    }))
+}
+
+static ADVANCEMENTS: Lazy<RwLock<AdvancementRegistry>> = Lazy::new(|| RwLock::new(AdvancementRegistry::default()));
+
+/// Advancements of the server, none until loaded from a data directory.
+pub fn advancements() -> &'static RwLock<AdvancementRegistry> {
+    &ADVANCEMENTS
+}
@@ -1 +1,20 @@
+mod codec;
+mod varint;
+mod string;
+mod uuid;
+mod identifier;
+mod position;
+mod id_or;
+mod bytes;
+mod bitset;
 
+pub use codec::*;
+pub use varint::*;
+pub use string::*;
+pub use identifier::*;
+pub use position::*;
+pub use id_or::*;
+pub use bytes::*;
+pub use bitset::*;
+pub use ::uuid::Uuid;
+pub use dolls_macros::{Decode, Encode};
@@ -0,0 +1,60 @@
+use std::io::{self, Read, Write};
+use crate::datatype::{Decode, Encode};
+
+/// A growable set of bits, sent as a VarInt-prefixed array of longs like Java's `BitSet`.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct BitSet(pub Vec<i64>);
+
+impl BitSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, index: usize) -> bool {
+        self.0.get(index / 64).is_some_and(|word| (*word as u64 >> (index % 64)) & 1 != 0)
+    }
+
+    pub fn set(&mut self, index: usize, value: bool) {
+        if self.0.len() <= index / 64 {
+            if !value {
+                return;
+            }
+            self.0.resize(index / 64 + 1, 0);
+        }
+        let bit = 1i64 << (index % 64);
+        match value {
+            true => self.0[index / 64] |= bit,
+            false => self.0[index / 64] &= !bit,
+        }
+        // Java trims trailing empty words, so equal sets encode the same.
+        while self.0.last() == Some(&0) {
+            self.0.pop();
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|word| *word == 0)
+    }
+}
+
+impl FromIterator<usize> for BitSet {
+    fn from_iter<I: IntoIterator<Item = usize>>(indices: I) -> Self {
+        let mut set = BitSet::new();
+        for index in indices {
+            set.set(index, true);
+        }
+        set
+    }
+}
+
+impl Decode for BitSet {
+    fn decode(reader: &mut impl Read) -> io::Result<Self> {
+        Ok(BitSet(Vec::decode(reader)?))
+    }
+}
+
+impl Encode for BitSet {
+    fn encode(&self, writer: &mut impl Write) -> io::Result<()> {
+        self.0.encode(writer)
+    }
+}
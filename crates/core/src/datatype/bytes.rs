@@ -0,0 +1,31 @@
+use std::io::{self, Read, Write};
+use crate::datatype::{Decode, Encode, VarInt};
+
+/// Reads a VarInt prefixed byte array of at most `max_length` bytes.
+pub fn read_bounded_bytes(reader: &mut impl Read, max_length: usize) -> io::Result<Vec<u8>> {
+    let length = VarInt::decode(reader)?.0;
+    if length < 0 || length as usize > max_length {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Byte array length {} out of bounds", length)));
+    }
+    let mut buffer = vec![0u8; length as usize];
+    reader.read_exact(&mut buffer)?;
+    Ok(buffer)
+}
+
+/// Unprefixed bytes which take up the rest of the packet, must be the last field.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Hash)]
+pub struct RemainingBytes(pub Vec<u8>);
+
+impl Decode for RemainingBytes {
+    fn decode(reader: &mut impl Read) -> io::Result<Self> {
+        let mut buffer = Vec::new();
+        reader.read_to_end(&mut buffer)?;
+        Ok(RemainingBytes(buffer))
+    }
+}
+
+impl Encode for RemainingBytes {
+    fn encode(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(&self.0)
+    }
+}
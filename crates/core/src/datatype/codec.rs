@@ -0,0 +1,126 @@
+use std::io::{self, Read, Write};
+use crate::datatype::VarInt;
+
+/// Types which can be read from a packet payload.
+pub trait Decode: Sized {
+    fn decode(reader: &mut impl Read) -> io::Result<Self>;
+}
+
+/// Types which can be written into a packet payload.
+pub trait Encode {
+    fn encode(&self, writer: &mut impl Write) -> io::Result<()>;
+}
+
+/// Decodes a value from the whole buffer.
+pub fn decode_from_slice<T: Decode>(mut buffer: &[u8]) -> io::Result<T> {
+    T::decode(&mut buffer)
+}
+
+/// Encodes a value into a fresh buffer.
+pub fn encode_to_vec(value: &impl Encode) -> io::Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    value.encode(&mut buffer)?;
+    Ok(buffer)
+}
+
+macro_rules! impl_primitive {
+    ($($ty:ty),*) => {
+        $(
+            impl Decode for $ty {
+                fn decode(reader: &mut impl Read) -> io::Result<Self> {
+                    let mut buffer = [0u8; size_of::<$ty>()];
+                    reader.read_exact(&mut buffer)?;
+                    Ok(<$ty>::from_be_bytes(buffer))
+                }
+            }
+
+            impl Encode for $ty {
+                fn encode(&self, writer: &mut impl Write) -> io::Result<()> {
+                    writer.write_all(&self.to_be_bytes())
+                }
+            }
+        )*
+    };
+}
+
+impl_primitive!(u8, i8, u16, i16, u32, i32, u64, i64, u128, f32, f64);
+
+impl Decode for bool {
+    fn decode(reader: &mut impl Read) -> io::Result<Self> {
+        match u8::decode(reader)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            value => Err(io::Error::new(io::ErrorKind::InvalidData, format!("Invalid boolean value {}", value))),
+        }
+    }
+}
+
+impl Encode for bool {
+    fn encode(&self, writer: &mut impl Write) -> io::Result<()> {
+        (*self as u8).encode(writer)
+    }
+}
+
+/// Optional values are prefixed with a boolean.
+impl<T: Decode> Decode for Option<T> {
+    fn decode(reader: &mut impl Read) -> io::Result<Self> {
+        if bool::decode(reader)? {
+            Ok(Some(T::decode(reader)?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl<T: Encode> Encode for Option<T> {
+    fn encode(&self, writer: &mut impl Write) -> io::Result<()> {
+        match self {
+            Some(value) => {
+                true.encode(writer)?;
+                value.encode(writer)
+            }
+            None => false.encode(writer),
+        }
+    }
+}
+
+/// Arrays are prefixed with their length as a VarInt.
+impl<T: Decode> Decode for Vec<T> {
+    fn decode(reader: &mut impl Read) -> io::Result<Self> {
+        let length = VarInt::decode(reader)?.0;
+        if length < 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Negative array length"));
+        }
+        // Do not trust the length for preallocation, it comes from the client.
+        let mut values = Vec::with_capacity((length as usize).min(1024));
+        for _ in 0..length {
+            values.push(T::decode(reader)?);
+        }
+        Ok(values)
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode(&self, writer: &mut impl Write) -> io::Result<()> {
+        VarInt(self.len() as i32).encode(writer)?;
+        for value in self {
+            value.encode(writer)?;
+        }
+        Ok(())
+    }
+}
+
+/// Fixed-size byte arrays are written as is, without a length prefix.
+impl<const N: usize> Decode for [u8; N] {
+    fn decode(reader: &mut impl Read) -> io::Result<Self> {
+        let mut bytes = [0; N];
+        reader.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+impl<const N: usize> Encode for [u8; N] {
+    fn encode(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(self)
+    }
+}
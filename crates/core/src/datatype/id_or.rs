@@ -0,0 +1,32 @@
+use std::io::{self, Read, Write};
+use crate::datatype::{Decode, Encode, VarInt};
+
+/// Either a registry id, or an inline definition of the entry.
+///
+/// On the wire this is a VarInt holding `id + 1`, or `0` followed by the inline value.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum IdOr<T> {
+    Id(i32),
+    Inline(T),
+}
+
+impl<T: Decode> Decode for IdOr<T> {
+    fn decode(reader: &mut impl Read) -> io::Result<Self> {
+        match VarInt::decode(reader)?.0 {
+            0 => Ok(IdOr::Inline(T::decode(reader)?)),
+            id => Ok(IdOr::Id(id - 1)),
+        }
+    }
+}
+
+impl<T: Encode> Encode for IdOr<T> {
+    fn encode(&self, writer: &mut impl Write) -> io::Result<()> {
+        match self {
+            IdOr::Id(id) => VarInt(id + 1).encode(writer),
+            IdOr::Inline(value) => {
+                VarInt(0).encode(writer)?;
+                value.encode(writer)
+            }
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use std::fmt::{Display, Formatter};
+use std::io::{self, Read, Write};
+use std::str::FromStr;
+use crate::datatype::{Decode, Encode};
+
+pub const DEFAULT_NAMESPACE: &str = "minecraft";
+
+/// Namespaced resource location such as `minecraft:overworld`.
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct Identifier {
+    namespace: String,
+    path: String,
+}
+
+impl Identifier {
+    pub fn new(namespace: impl Into<String>, path: impl Into<String>) -> io::Result<Self> {
+        let namespace = namespace.into();
+        let path = path.into();
+
+        if !namespace.chars().all(|c| matches!(c, 'a'..='z' | '0'..='9' | '.' | '-' | '_')) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Invalid identifier namespace {:?}", namespace)));
+        }
+        if !path.chars().all(|c| matches!(c, 'a'..='z' | '0'..='9' | '.' | '-' | '_' | '/')) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Invalid identifier path {:?}", path)));
+        }
+
+        Ok(Self { namespace, path })
+    }
+
+    /// Identifier in the `minecraft` namespace, panics on invalid paths.
+    pub fn minecraft(path: &str) -> Self {
+        Self::new(DEFAULT_NAMESPACE, path).expect("Invalid vanilla identifier")
+    }
+
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+impl FromStr for Identifier {
+    type Err = io::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.split_once(':') {
+            Some((namespace, path)) => Self::new(namespace, path),
+            None => Self::new(DEFAULT_NAMESPACE, value),
+        }
+    }
+}
+
+impl Display for Identifier {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.namespace, self.path)
+    }
+}
+
+impl Decode for Identifier {
+    fn decode(reader: &mut impl Read) -> io::Result<Self> {
+        String::decode(reader)?.parse()
+    }
+}
+
+impl Encode for Identifier {
+    fn encode(&self, writer: &mut impl Write) -> io::Result<()> {
+        self.to_string().encode(writer)
+    }
+}
@@ -0,0 +1,69 @@
+use std::io::{self, Read, Write};
+use crate::datatype::{Decode, Encode, Identifier};
+
+/// Block coordinates packed into a single long: x (26 bits), z (26 bits), y (12 bits).
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct BlockPos {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl BlockPos {
+    pub const fn new(x: i32, y: i32, z: i32) -> Self {
+        Self { x, y, z }
+    }
+
+    pub const fn pack(self) -> i64 {
+        ((self.x as i64 & 0x3FF_FFFF) << 38) | ((self.z as i64 & 0x3FF_FFFF) << 12) | (self.y as i64 & 0xFFF)
+    }
+
+    pub const fn unpack(value: i64) -> Self {
+        Self {
+            x: (value >> 38) as i32,
+            y: (value << 52 >> 52) as i32,
+            z: (value << 26 >> 38) as i32,
+        }
+    }
+}
+
+impl Decode for BlockPos {
+    fn decode(reader: &mut impl Read) -> io::Result<Self> {
+        Ok(Self::unpack(i64::decode(reader)?))
+    }
+}
+
+impl Encode for BlockPos {
+    fn encode(&self, writer: &mut impl Write) -> io::Result<()> {
+        self.pack().encode(writer)
+    }
+}
+
+/// A block position inside a specific dimension, e.g. a death location or lodestone target.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct GlobalPos {
+    pub dimension: Identifier,
+    pub position: BlockPos,
+}
+
+impl GlobalPos {
+    pub fn new(dimension: Identifier, position: BlockPos) -> Self {
+        Self { dimension, position }
+    }
+}
+
+impl Decode for GlobalPos {
+    fn decode(reader: &mut impl Read) -> io::Result<Self> {
+        Ok(Self {
+            dimension: Identifier::decode(reader)?,
+            position: BlockPos::decode(reader)?,
+        })
+    }
+}
+
+impl Encode for GlobalPos {
+    fn encode(&self, writer: &mut impl Write) -> io::Result<()> {
+        self.dimension.encode(writer)?;
+        self.position.encode(writer)
+    }
+}
@@ -0,0 +1,48 @@
+use std::io::{self, Read, Write};
+use crate::datatype::{Decode, Encode, VarInt};
+
+/// Maximum length of a protocol string, in UTF-16 code units.
+pub const MAX_STRING_LENGTH: usize = 32767;
+
+/// Reads a string with a custom upper bound on its length.
+pub fn read_bounded_string(reader: &mut impl Read, max_length: usize) -> io::Result<String> {
+    let length = VarInt::decode(reader)?.0;
+    if length < 0 || length as usize > max_length * 3 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("String length {} out of bounds", length)));
+    }
+
+    let mut buffer = vec![0u8; length as usize];
+    reader.read_exact(&mut buffer)?;
+    let value = String::from_utf8(buffer).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+    if value.encode_utf16().count() > max_length {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "String is too long"));
+    }
+
+    Ok(value)
+}
+
+impl Decode for String {
+    fn decode(reader: &mut impl Read) -> io::Result<Self> {
+        read_bounded_string(reader, MAX_STRING_LENGTH)
+    }
+}
+
+impl Encode for String {
+    fn encode(&self, writer: &mut impl Write) -> io::Result<()> {
+        self.as_str().encode(writer)
+    }
+}
+
+impl Encode for str {
+    fn encode(&self, writer: &mut impl Write) -> io::Result<()> {
+        VarInt(self.len() as i32).encode(writer)?;
+        writer.write_all(self.as_bytes())
+    }
+}
+
+impl<T: Encode + ?Sized> Encode for &T {
+    fn encode(&self, writer: &mut impl Write) -> io::Result<()> {
+        (**self).encode(writer)
+    }
+}
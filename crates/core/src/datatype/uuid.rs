@@ -0,0 +1,16 @@
+use std::io::{self, Read, Write};
+use uuid::Uuid;
+use crate::datatype::{Decode, Encode};
+
+/// UUIDs are sent as two big-endian longs, which matches a big-endian `u128`.
+impl Decode for Uuid {
+    fn decode(reader: &mut impl Read) -> io::Result<Self> {
+        Ok(Uuid::from_u128(u128::decode(reader)?))
+    }
+}
+
+impl Encode for Uuid {
+    fn encode(&self, writer: &mut impl Write) -> io::Result<()> {
+        self.as_u128().encode(writer)
+    }
+}
@@ -0,0 +1,131 @@
+use std::io::{self, Read, Write};
+use crate::datatype::{Decode, Encode};
+
+pub const SEGMENT_BITS: u8 = 0x7F;
+pub const CONTINUE_BIT: u8 = 0x80;
+
+/// Variable-length encoded `i32`, at most 5 bytes on the wire.
+#[derive(Debug, Default, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct VarInt(pub i32);
+
+/// Variable-length encoded `i64`, at most 10 bytes on the wire.
+#[derive(Debug, Default, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct VarLong(pub i64);
+
+impl VarInt {
+    /// Number of bytes this value takes on the wire.
+    pub fn written_size(&self) -> usize {
+        let value = self.0 as u32;
+        match value {
+            0..=0x7F => 1,
+            0x80..=0x3FFF => 2,
+            0x4000..=0x1F_FFFF => 3,
+            0x20_0000..=0xFFF_FFFF => 4,
+            _ => 5,
+        }
+    }
+
+    /// Decodes a VarInt from the start of `bytes` without going through a reader, returning it with the
+    /// number of bytes it took, or `None` if `bytes` ends before it does. The loop has a fixed bound so
+    /// that it is unrolled.
+    #[inline]
+    pub fn decode_prefix(bytes: &[u8]) -> io::Result<Option<(Self, usize)>> {
+        let mut value: u32 = 0;
+        for index in 0..5 {
+            let Some(&byte) = bytes.get(index) else {
+                return Ok(None);
+            };
+            value |= ((byte & SEGMENT_BITS) as u32) << (7 * index);
+            if byte & CONTINUE_BIT == 0 {
+                return Ok(Some((VarInt(value as i32), index + 1)));
+            }
+        }
+        Err(io::Error::new(io::ErrorKind::InvalidData, "VarInt is too big"))
+    }
+}
+
+impl Decode for VarInt {
+    fn decode(reader: &mut impl Read) -> io::Result<Self> {
+        let mut value: u32 = 0;
+        let mut position: u32 = 0;
+
+        loop {
+            let current_byte = u8::decode(reader)?;
+            value |= ((current_byte & SEGMENT_BITS) as u32) << position;
+
+            if (current_byte & CONTINUE_BIT) == 0 {
+                break;
+            }
+
+            position += 7;
+
+            if position >= 32 {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "VarInt is too big"));
+            }
+        }
+
+        Ok(VarInt(value as i32))
+    }
+}
+
+impl Encode for VarInt {
+    fn encode(&self, writer: &mut impl Write) -> io::Result<()> {
+        let mut value = self.0 as u32;
+        loop {
+            if value & !(SEGMENT_BITS as u32) == 0 {
+                return writer.write_all(&[value as u8]);
+            }
+            writer.write_all(&[(value as u8 & SEGMENT_BITS) | CONTINUE_BIT])?;
+            value >>= 7;
+        }
+    }
+}
+
+impl Decode for VarLong {
+    fn decode(reader: &mut impl Read) -> io::Result<Self> {
+        let mut value: u64 = 0;
+        let mut position: u32 = 0;
+
+        loop {
+            let current_byte = u8::decode(reader)?;
+            value |= ((current_byte & SEGMENT_BITS) as u64) << position;
+
+            if (current_byte & CONTINUE_BIT) == 0 {
+                break;
+            }
+
+            position += 7;
+
+            if position >= 64 {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "VarLong is too big"));
+            }
+        }
+
+        Ok(VarLong(value as i64))
+    }
+}
+
+impl Encode for VarLong {
+    fn encode(&self, writer: &mut impl Write) -> io::Result<()> {
+        let mut value = self.0 as u64;
+        loop {
+            if value & !(SEGMENT_BITS as u64) == 0 {
+                return writer.write_all(&[value as u8]);
+            }
+            writer.write_all(&[(value as u8 & SEGMENT_BITS) | CONTINUE_BIT])?;
+            value >>= 7;
+        }
+    }
+}
+
+impl From<i32> for VarInt {
+    fn from(value: i32) -> Self {
+        VarInt(value)
+    }
+}
+
+impl From<i64> for VarLong {
+    fn from(value: i64) -> Self {
+        VarLong(value)
+    }
+}
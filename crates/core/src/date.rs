@@ -0,0 +1,57 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Milliseconds since the epoch, now.
+pub fn now_millis() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_millis() as i64)
+}
+
+/// Milliseconds since the epoch as `yyyy-MM-dd HH:mm:ss +0000`, the format vanilla stores dates in
+/// its JSON files, e.g. advancement criteria and bans.
+pub fn format_date(millis: i64) -> String {
+    let seconds = millis.div_euclid(1000);
+    let (days, time) = (seconds.div_euclid(86400), seconds.rem_euclid(86400));
+    let (year, month, day) = civil_from_days(days);
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02} +0000", year, month, day, time / 3600, time / 60 % 60, time % 60)
+}
+
+/// Milliseconds since the epoch of a date in the format of [`format_date`], with any zone offset.
+pub fn parse_date(date: &str) -> Option<i64> {
+    let (date, rest) = date.split_once(' ')?;
+    let (time, zone) = rest.split_once(' ').unwrap_or((rest, "+0000"));
+    let [year, month, day] = parse_fields(date, '-')?;
+    let [hour, minute, second] = parse_fields(time, ':')?;
+    let sign = if zone.starts_with('-') { -1 } else { 1 };
+    let zone = zone.trim_start_matches(['+', '-']).parse::<i64>().ok()?;
+    let offset = sign * (zone / 100 * 3600 + zone % 100 * 60);
+    let seconds = days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second - offset;
+    Some(seconds * 1000)
+}
+
+fn parse_fields(value: &str, separator: char) -> Option<[i64; 3]> {
+    let mut fields = value.split(separator).map(|field| field.parse::<i64>().ok());
+    let parsed = [fields.next()??, fields.next()??, fields.next()??];
+    fields.next().is_none().then_some(parsed)
+}
+
+/// Year, month and day of a day since the epoch in the proleptic Gregorian calendar.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    (year_of_era + era * 400 + (month <= 2) as i64, month, day)
+}
+
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let shifted_month = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * shifted_month + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::sync::RwLock;
+use anyhow::{anyhow, Context};
+use once_cell::sync::Lazy;
+use serde_json::Value;
+use crate::datatype::{Decode, Encode, Identifier, VarInt};
+
+/// Stack size of items unless the registry knows better.
+pub const MAX_STACK_SIZE: i32 = 64;
+/// Largest count a slot can be sent with.
+pub const MAX_SLOT_COUNT: i32 = 99;
+
+/// Stack of items as carried by slots, identified by the `minecraft:item` registry id.
+///
+/// Data components are not modelled yet, stacks are sent without any.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub struct ItemStack {
+    pub item_id: i32,
+    pub count: i32,
+}
+
+impl ItemStack {
+    pub const EMPTY: ItemStack = ItemStack { item_id: 0, count: 0 };
+
+    pub const fn new(item_id: i32, count: i32) -> Self {
+        Self { item_id, count }
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.count <= 0
+    }
+}
+
+impl Encode for ItemStack {
+    fn encode(&self, writer: &mut impl Write) -> io::Result<()> {
+        if self.is_empty() {
+            return VarInt(0).encode(writer);
+        }
+        VarInt(self.count).encode(writer)?;
+        VarInt(self.item_id).encode(writer)?;
+        // Components to add, components to remove.
+        VarInt(0).encode(writer)?;
+        VarInt(0).encode(writer)
+    }
+}
+
+impl Decode for ItemStack {
+    fn decode(reader: &mut impl Read) -> io::Result<Self> {
+        let count = VarInt::decode(reader)?.0;
+        if count <= 0 {
+            return Ok(ItemStack::EMPTY);
+        }
+        let item_id = VarInt::decode(reader)?.0;
+        let added = VarInt::decode(reader)?.0;
+        let removed = VarInt::decode(reader)?.0;
+        if added != 0 || removed != 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Item data components are not supported"));
+        }
+        Ok(ItemStack { item_id, count })
+    }
+}
+
+/// Item requirement of a trade: an item id, a count and component predicates (always empty).
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct ItemCost {
+    pub item_id: i32,
+    pub count: i32,
+}
+
+impl ItemCost {
+    pub const fn new(item_id: i32, count: i32) -> Self {
+        Self { item_id, count }
+    }
+
+    pub fn matches(&self, stack: &ItemStack) -> bool {
+        stack.item_id == self.item_id && stack.count >= self.count
+    }
+}
+
+impl Encode for ItemCost {
+    fn encode(&self, writer: &mut impl Write) -> io::Result<()> {
+        VarInt(self.item_id).encode(writer)?;
+        VarInt(self.count).encode(writer)?;
+        VarInt(0).encode(writer)
+    }
+}
+
+/// Item names and stack sizes by `minecraft:item` id.
+///
+/// Empty out of the box, in which case every positive id counts as an item that stacks to
+/// [`MAX_STACK_SIZE`]. The ids of a Minecraft version come from its data generator reports, see
+/// [`ItemRegistry::from_report`].
+#[derive(Debug, Clone, Default)]
+pub struct ItemRegistry {
+    names: Vec<Identifier>,
+    ids: HashMap<Identifier, i32>,
+    max_stack_sizes: HashMap<i32, i32>,
+}
+
+impl ItemRegistry {
+    /// Reads the `minecraft:item` registry from the `registries.json` report.
+    pub fn from_report(json: &str) -> anyhow::Result<Self> {
+        let report: Value = serde_json::from_str(json).context("Malformed registries report")?;
+        let entries = report.get("minecraft:item").and_then(|registry| registry.get("entries")).and_then(Value::as_object)
+            .ok_or_else(|| anyhow!("Registries report has no minecraft:item entries"))?;
+        let mut names = vec![None; entries.len()];
+        for (name, entry) in entries {
+            let id: Identifier = name.parse().map_err(|_| anyhow!("Invalid item name {}", name))?;
+            let protocol_id = entry.get("protocol_id").and_then(Value::as_u64)
+                .ok_or_else(|| anyhow!("Item {} has no protocol id", name))? as usize;
+            *names.get_mut(protocol_id).ok_or_else(|| anyhow!("Protocol id {} of {} out of range", protocol_id, name))? = Some(id);
+        }
+        let names = names.into_iter().enumerate()
+            .map(|(protocol_id, name)| name.ok_or_else(|| anyhow!("No item has protocol id {}", protocol_id)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let ids = names.iter().enumerate().map(|(protocol_id, name)| (name.clone(), protocol_id as i32)).collect();
+        Ok(Self { names, ids, max_stack_sizes: HashMap::new() })
+    }
+
+    /// Reads stack sizes from the `items.json` report, items it does not list keep the default.
+    pub fn load_components(&mut self, json: &str) -> anyhow::Result<()> {
+        let report: Value = serde_json::from_str(json).context("Malformed item report")?;
+        let report = report.as_object().ok_or_else(|| anyhow!("Item report is not an object"))?;
+        for (name, item) in report {
+            let Some(id) = name.parse().ok().and_then(|name: Identifier| self.id(&name)) else { continue };
+            if let Some(max_stack_size) = item.pointer("/components/minecraft:max_stack_size").and_then(Value::as_i64) {
+                self.max_stack_sizes.insert(id, max_stack_size.clamp(1, MAX_SLOT_COUNT as i64) as i32);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    pub fn id(&self, name: &Identifier) -> Option<i32> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn name(&self, id: i32) -> Option<&Identifier> {
+        usize::try_from(id).ok().and_then(|id| self.names.get(id))
+    }
+
+    /// Whether `id` names an item other than air.
+    pub fn contains(&self, id: i32) -> bool {
+        match self.is_empty() {
+            true => id > 0,
+            false => id > 0 && (id as usize) < self.names.len(),
+        }
+    }
+
+    pub fn max_stack_size(&self, id: i32) -> i32 {
+        self.max_stack_sizes.get(&id).copied().unwrap_or(MAX_STACK_SIZE)
+    }
+
+    /// Whether a slot may hold `stack`: empty, or a known item in a count it stacks to.
+    pub fn is_valid(&self, stack: &ItemStack) -> bool {
+        stack.is_empty() || (self.contains(stack.item_id) && stack.count <= self.max_stack_size(stack.item_id))
+    }
+}
+
+static ITEMS: Lazy<RwLock<ItemRegistry>> = Lazy::new(|| RwLock::new(ItemRegistry::default()));
+
+/// Items of the server, replace it with one read from the reports before players join.
+pub fn items() -> &'static RwLock<ItemRegistry> {
+    &ITEMS
+}
@@ -1 +1,15 @@
+// Lets the derive macros refer to `::dolls_core` from inside this crate too.
+extern crate self as dolls_core;
+
 pub mod datatype;
+pub mod nbt;
+pub mod text;
+pub mod registry;
+pub mod item;
+pub mod recipe;
+pub mod advancement;
+pub mod statistic;
+pub mod particle;
+pub mod date;
+#[cfg(feature = "testing")]
+pub mod testing;
@@ -0,0 +1,4 @@
+mod tag;
+mod mutf8;
+
+pub use tag::*;
@@ -0,0 +1,52 @@
+use std::io;
+
+/// Encodes a string as Java's modified UTF-8, used by NBT strings.
+pub(crate) fn encode(value: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(value.len());
+    for unit in value.encode_utf16() {
+        match unit {
+            0x0001..=0x007F => bytes.push(unit as u8),
+            0x0000 | 0x0080..=0x07FF => {
+                bytes.push(0xC0 | (unit >> 6) as u8);
+                bytes.push(0x80 | (unit & 0x3F) as u8);
+            }
+            _ => {
+                bytes.push(0xE0 | (unit >> 12) as u8);
+                bytes.push(0x80 | ((unit >> 6) & 0x3F) as u8);
+                bytes.push(0x80 | (unit & 0x3F) as u8);
+            }
+        }
+    }
+    bytes
+}
+
+pub(crate) fn decode(bytes: &[u8]) -> io::Result<String> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "Invalid modified UTF-8 string");
+    let mut units = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+
+    while index < bytes.len() {
+        let first = bytes[index] as u16;
+        let unit = match first {
+            0x00..=0x7F => {
+                index += 1;
+                first
+            }
+            0xC0..=0xDF => {
+                let second = *bytes.get(index + 1).ok_or_else(invalid)? as u16;
+                index += 2;
+                ((first & 0x1F) << 6) | (second & 0x3F)
+            }
+            0xE0..=0xEF => {
+                let second = *bytes.get(index + 1).ok_or_else(invalid)? as u16;
+                let third = *bytes.get(index + 2).ok_or_else(invalid)? as u16;
+                index += 3;
+                ((first & 0x0F) << 12) | ((second & 0x3F) << 6) | (third & 0x3F)
+            }
+            _ => return Err(invalid()),
+        };
+        units.push(unit);
+    }
+
+    String::from_utf16(&units).map_err(|_| invalid())
+}
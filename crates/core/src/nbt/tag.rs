@@ -0,0 +1,348 @@
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+use crate::datatype::{Decode, Encode};
+use crate::nbt::mutf8;
+
+/// Nesting limit when reading, deeper data is rejected like vanilla does.
+pub const MAX_NBT_DEPTH: usize = 512;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NbtTag {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<i8>),
+    String(String),
+    List(Vec<NbtTag>),
+    Compound(NbtCompound),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NbtCompound(BTreeMap<String, NbtTag>);
+
+impl NbtTag {
+    pub const fn type_id(&self) -> u8 {
+        match self {
+            NbtTag::Byte(_) => 1,
+            NbtTag::Short(_) => 2,
+            NbtTag::Int(_) => 3,
+            NbtTag::Long(_) => 4,
+            NbtTag::Float(_) => 5,
+            NbtTag::Double(_) => 6,
+            NbtTag::ByteArray(_) => 7,
+            NbtTag::String(_) => 8,
+            NbtTag::List(_) => 9,
+            NbtTag::Compound(_) => 10,
+            NbtTag::IntArray(_) => 11,
+            NbtTag::LongArray(_) => 12,
+        }
+    }
+
+    pub fn as_compound(&self) -> Option<&NbtCompound> {
+        match self {
+            NbtTag::Compound(compound) => Some(compound),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[NbtTag]> {
+        match self {
+            NbtTag::List(list) => Some(list),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            NbtTag::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Numeric tags widened to `i64`, booleans are stored as bytes.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            NbtTag::Byte(value) => Some(*value as i64),
+            NbtTag::Short(value) => Some(*value as i64),
+            NbtTag::Int(value) => Some(*value as i64),
+            NbtTag::Long(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            NbtTag::Float(value) => Some(*value as f64),
+            NbtTag::Double(value) => Some(*value),
+            _ => self.as_i64().map(|value| value as f64),
+        }
+    }
+
+    fn write_payload(&self, writer: &mut impl Write) -> io::Result<()> {
+        match self {
+            NbtTag::Byte(value) => value.encode(writer),
+            NbtTag::Short(value) => value.encode(writer),
+            NbtTag::Int(value) => value.encode(writer),
+            NbtTag::Long(value) => value.encode(writer),
+            NbtTag::Float(value) => value.encode(writer),
+            NbtTag::Double(value) => value.encode(writer),
+            NbtTag::ByteArray(values) => {
+                (values.len() as i32).encode(writer)?;
+                values.iter().try_for_each(|value| value.encode(writer))
+            }
+            NbtTag::String(value) => write_string(writer, value),
+            NbtTag::List(values) => {
+                let element_type = values.first().map_or(0, NbtTag::type_id);
+                if values.iter().any(|value| value.type_id() != element_type) {
+                    return Err(io::Error::new(io::ErrorKind::InvalidInput, "NBT list elements must share a type"));
+                }
+                element_type.encode(writer)?;
+                (values.len() as i32).encode(writer)?;
+                values.iter().try_for_each(|value| value.write_payload(writer))
+            }
+            NbtTag::Compound(compound) => compound.write_payload(writer),
+            NbtTag::IntArray(values) => {
+                (values.len() as i32).encode(writer)?;
+                values.iter().try_for_each(|value| value.encode(writer))
+            }
+            NbtTag::LongArray(values) => {
+                (values.len() as i32).encode(writer)?;
+                values.iter().try_for_each(|value| value.encode(writer))
+            }
+        }
+    }
+
+    fn read_payload(reader: &mut impl Read, type_id: u8, depth: usize) -> io::Result<Self> {
+        if depth > MAX_NBT_DEPTH {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "NBT is nested too deeply"));
+        }
+
+        Ok(match type_id {
+            1 => NbtTag::Byte(i8::decode(reader)?),
+            2 => NbtTag::Short(i16::decode(reader)?),
+            3 => NbtTag::Int(i32::decode(reader)?),
+            4 => NbtTag::Long(i64::decode(reader)?),
+            5 => NbtTag::Float(f32::decode(reader)?),
+            6 => NbtTag::Double(f64::decode(reader)?),
+            7 => NbtTag::ByteArray(read_array(reader, i8::decode)?),
+            8 => NbtTag::String(read_string(reader)?),
+            9 => {
+                let element_type = u8::decode(reader)?;
+                let length = read_length(reader)?;
+                if element_type == 0 && length > 0 {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, "Non-empty NBT list of end tags"));
+                }
+                let mut values = Vec::with_capacity(length.min(1024));
+                for _ in 0..length {
+                    values.push(NbtTag::read_payload(reader, element_type, depth + 1)?);
+                }
+                NbtTag::List(values)
+            }
+            10 => NbtTag::Compound(NbtCompound::read_payload(reader, depth + 1)?),
+            11 => NbtTag::IntArray(read_array(reader, i32::decode)?),
+            12 => NbtTag::LongArray(read_array(reader, i64::decode)?),
+            type_id => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Unknown NBT tag type {}", type_id))),
+        })
+    }
+}
+
+impl NbtCompound {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, key: impl Into<String>, value: impl Into<NbtTag>) -> &mut Self {
+        self.0.insert(key.into(), value.into());
+        self
+    }
+
+    /// Builder-style [`NbtCompound::insert`].
+    pub fn with(mut self, key: impl Into<String>, value: impl Into<NbtTag>) -> Self {
+        self.insert(key, value);
+        self
+    }
+
+    pub fn get(&self, key: &str) -> Option<&NbtTag> {
+        self.0.get(key)
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<NbtTag> {
+        self.0.remove(key)
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.0.contains_key(key)
+    }
+
+    pub fn get_compound(&self, key: &str) -> Option<&NbtCompound> {
+        self.get(key).and_then(NbtTag::as_compound)
+    }
+
+    pub fn get_list(&self, key: &str) -> Option<&[NbtTag]> {
+        self.get(key).and_then(NbtTag::as_list)
+    }
+
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        self.get(key).and_then(NbtTag::as_str)
+    }
+
+    pub fn get_i64(&self, key: &str) -> Option<i64> {
+        self.get(key).and_then(NbtTag::as_i64)
+    }
+
+    pub fn get_f64(&self, key: &str) -> Option<f64> {
+        self.get(key).and_then(NbtTag::as_f64)
+    }
+
+    pub fn get_bool(&self, key: &str) -> Option<bool> {
+        self.get_i64(key).map(|value| value != 0)
+    }
+
+    pub fn get_long_array(&self, key: &str) -> Option<&[i64]> {
+        match self.get(key) {
+            Some(NbtTag::LongArray(values)) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &NbtTag)> {
+        self.0.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Writes the compound as a named root tag, the format used on disk.
+    pub fn write_named(&self, writer: &mut impl Write, name: &str) -> io::Result<()> {
+        10u8.encode(writer)?;
+        write_string(writer, name)?;
+        self.write_payload(writer)
+    }
+
+    /// Reads a named root compound, returning its name alongside.
+    pub fn read_named(reader: &mut impl Read) -> io::Result<(String, Self)> {
+        let type_id = u8::decode(reader)?;
+        if type_id != 10 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Root NBT tag is not a compound ({})", type_id)));
+        }
+        let name = read_string(reader)?;
+        Ok((name, Self::read_payload(reader, 0)?))
+    }
+
+    fn write_payload(&self, writer: &mut impl Write) -> io::Result<()> {
+        for (key, value) in &self.0 {
+            value.type_id().encode(writer)?;
+            write_string(writer, key)?;
+            value.write_payload(writer)?;
+        }
+        0u8.encode(writer)
+    }
+
+    fn read_payload(reader: &mut impl Read, depth: usize) -> io::Result<Self> {
+        let mut compound = Self::new();
+        loop {
+            let type_id = u8::decode(reader)?;
+            if type_id == 0 {
+                return Ok(compound);
+            }
+            let key = read_string(reader)?;
+            compound.0.insert(key, NbtTag::read_payload(reader, type_id, depth)?);
+        }
+    }
+}
+
+/// Network NBT: the root tag's type followed by its payload, without a name.
+impl Encode for NbtTag {
+    fn encode(&self, writer: &mut impl Write) -> io::Result<()> {
+        self.type_id().encode(writer)?;
+        self.write_payload(writer)
+    }
+}
+
+impl Decode for NbtTag {
+    fn decode(reader: &mut impl Read) -> io::Result<Self> {
+        let type_id = u8::decode(reader)?;
+        NbtTag::read_payload(reader, type_id, 0)
+    }
+}
+
+impl Encode for NbtCompound {
+    fn encode(&self, writer: &mut impl Write) -> io::Result<()> {
+        10u8.encode(writer)?;
+        self.write_payload(writer)
+    }
+}
+
+impl Decode for NbtCompound {
+    fn decode(reader: &mut impl Read) -> io::Result<Self> {
+        match NbtTag::decode(reader)? {
+            NbtTag::Compound(compound) => Ok(compound),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, "Expected an NBT compound")),
+        }
+    }
+}
+
+fn write_string(writer: &mut impl Write, value: &str) -> io::Result<()> {
+    let bytes = mutf8::encode(value);
+    let length = u16::try_from(bytes.len()).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "NBT string is too long"))?;
+    length.encode(writer)?;
+    writer.write_all(&bytes)
+}
+
+fn read_string(reader: &mut impl Read) -> io::Result<String> {
+    let length = u16::decode(reader)? as usize;
+    let mut bytes = vec![0u8; length];
+    reader.read_exact(&mut bytes)?;
+    mutf8::decode(&bytes)
+}
+
+fn read_length(reader: &mut impl Read) -> io::Result<usize> {
+    usize::try_from(i32::decode(reader)?).map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Negative NBT length"))
+}
+
+fn read_array<R: Read, T>(reader: &mut R, read: fn(&mut R) -> io::Result<T>) -> io::Result<Vec<T>> {
+    let length = read_length(reader)?;
+    let mut values = Vec::with_capacity(length.min(4096));
+    for _ in 0..length {
+        values.push(read(reader)?);
+    }
+    Ok(values)
+}
+
+macro_rules! impl_from {
+    ($($ty:ty => $variant:ident),*) => {
+        $(
+            impl From<$ty> for NbtTag {
+                fn from(value: $ty) -> Self {
+                    NbtTag::$variant(value)
+                }
+            }
+        )*
+    };
+}
+
+impl_from!(i8 => Byte, i16 => Short, i32 => Int, i64 => Long, f32 => Float, f64 => Double,
+    Vec<i8> => ByteArray, String => String, Vec<NbtTag> => List, NbtCompound => Compound,
+    Vec<i32> => IntArray, Vec<i64> => LongArray);
+
+impl From<bool> for NbtTag {
+    fn from(value: bool) -> Self {
+        NbtTag::Byte(value as i8)
+    }
+}
+
+impl From<&str> for NbtTag {
+    fn from(value: &str) -> Self {
+        NbtTag::String(value.to_string())
+    }
+}
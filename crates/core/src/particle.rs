@@ -0,0 +1,131 @@
+use std::io::{self, Write};
+use crate::datatype::{BlockPos, Encode, Identifier, VarInt};
+use crate::item::ItemStack;
+
+/// Names of the `minecraft:particle_type` registry of 1.21.1, in id order.
+pub const PARTICLE_TYPES: &[&str] = &["angry_villager", "block", "block_marker", "bubble", "cloud", "crit", "damage_indicator",
+    "dragon_breath", "dripping_lava", "falling_lava", "landing_lava", "dripping_water", "falling_water", "dust", "dust_color_transition",
+    "effect", "elder_guardian", "enchanted_hit", "enchant", "end_rod", "entity_effect", "explosion_emitter", "explosion", "gust",
+    "small_gust", "gust_emitter_large", "gust_emitter_small", "sonic_boom", "falling_dust", "firework", "fishing", "flame", "infested",
+    "cherry_leaves", "sculk_soul", "sculk_charge", "sculk_charge_pop", "soul_fire_flame", "soul", "flash", "happy_villager", "composter",
+    "heart", "instant_effect", "item", "vibration", "item_slime", "item_cobweb", "item_snowball", "large_smoke", "lava", "mycelium",
+    "note", "poof", "portal", "rain", "smoke", "white_smoke", "sneeze", "spit", "squid_ink", "sweep_attack", "totem_of_undying",
+    "underwater", "splash", "witch", "bubble_pop", "current_down", "bubble_column_up", "nautilus", "dolphin", "campfire_cosy_smoke",
+    "campfire_signal_smoke", "dripping_honey", "falling_honey", "landing_honey", "falling_nectar", "falling_spore_blossom", "ash",
+    "crimson_spore", "warped_spore", "spore_blossom_air", "dripping_obsidian_tear", "falling_obsidian_tear", "landing_obsidian_tear",
+    "reverse_portal", "white_ash", "small_flame", "snowflake", "dripping_dripstone_lava", "falling_dripstone_lava",
+    "dripping_dripstone_water", "falling_dripstone_water", "glow_squid_ink", "glow", "wax_on", "wax_off", "electric_spark", "scrape",
+    "shriek", "egg_crack", "dust_plume", "trial_spawner_detection", "trial_spawner_detection_ominous", "vault_connection", "dust_pillar",
+    "ominous_spawning", "raid_omen", "trial_omen"];
+
+/// Particle types whose options have to be given, they cannot be a [`Particle::Simple`].
+const TYPES_WITH_DATA: &[&str] = &["block", "block_marker", "falling_dust", "dust_pillar", "dust", "dust_color_transition", "entity_effect",
+    "item", "vibration", "sculk_charge", "shriek"];
+
+/// Network id of a particle type, by its path in the `minecraft` namespace.
+pub fn particle_type_id(name: &str) -> Option<i32> {
+    PARTICLE_TYPES.iter().position(|particle| *particle == name).map(|id| id as i32)
+}
+
+/// What a vibration particle travels to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VibrationTarget {
+    Block(BlockPos),
+    Entity { entity_id: i32, eye_height: f32 },
+}
+
+/// A particle type with its options, encoded as its id followed by the options.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Particle {
+    /// A particle type without options, such as `flame`.
+    Simple(i32),
+    /// Breaking block fragments, by block state id.
+    Block(i32),
+    BlockMarker(i32),
+    FallingDust(i32),
+    DustPillar(i32),
+    /// Redstone dust, with color components from 0 to 1.
+    Dust { color: [f32; 3], scale: f32 },
+    DustColorTransition { from: [f32; 3], to: [f32; 3], scale: f32 },
+    /// Potion swirls, colored by an ARGB color.
+    EntityEffect(i32),
+    Item(ItemStack),
+    Vibration { target: VibrationTarget, ticks: i32 },
+    SculkCharge { roll: f32 },
+    Shriek { delay: i32 },
+}
+
+impl Particle {
+    /// A particle type without options by name, `None` for unknown types and those that need options.
+    pub fn simple(name: &Identifier) -> Option<Self> {
+        if name.namespace() != "minecraft" || TYPES_WITH_DATA.contains(&name.path()) {
+            return None;
+        }
+        particle_type_id(name.path()).map(Particle::Simple)
+    }
+
+    /// Dust of an RGB color such as `0xFF0000`.
+    pub fn dust(rgb: u32, scale: f32) -> Self {
+        Particle::Dust { color: rgb_components(rgb), scale: scale.clamp(0.01, 4.0) }
+    }
+
+    pub fn type_id(&self) -> i32 {
+        let name = match self {
+            Particle::Simple(id) => return *id,
+            Particle::Block(_) => "block",
+            Particle::BlockMarker(_) => "block_marker",
+            Particle::FallingDust(_) => "falling_dust",
+            Particle::DustPillar(_) => "dust_pillar",
+            Particle::Dust { .. } => "dust",
+            Particle::DustColorTransition { .. } => "dust_color_transition",
+            Particle::EntityEffect(_) => "entity_effect",
+            Particle::Item(_) => "item",
+            Particle::Vibration { .. } => "vibration",
+            Particle::SculkCharge { .. } => "sculk_charge",
+            Particle::Shriek { .. } => "shriek",
+        };
+        particle_type_id(name).expect("Particle types with options are registered")
+    }
+}
+
+fn rgb_components(rgb: u32) -> [f32; 3] {
+    [16, 8, 0].map(|shift| ((rgb >> shift) & 0xFF) as f32 / 255.0)
+}
+
+impl Encode for Particle {
+    fn encode(&self, writer: &mut impl Write) -> io::Result<()> {
+        VarInt(self.type_id()).encode(writer)?;
+        match self {
+            Particle::Simple(_) => Ok(()),
+            Particle::Block(state) | Particle::BlockMarker(state) | Particle::FallingDust(state) | Particle::DustPillar(state) => {
+                VarInt(*state).encode(writer)
+            }
+            Particle::Dust { color, scale } => {
+                color.iter().try_for_each(|component| component.encode(writer))?;
+                scale.encode(writer)
+            }
+            Particle::DustColorTransition { from, to, scale } => {
+                from.iter().chain(to).try_for_each(|component| component.encode(writer))?;
+                scale.encode(writer)
+            }
+            Particle::EntityEffect(color) => color.encode(writer),
+            Particle::Item(stack) => stack.encode(writer),
+            Particle::Vibration { target, ticks } => {
+                match target {
+                    VibrationTarget::Block(position) => {
+                        VarInt(0).encode(writer)?;
+                        position.encode(writer)?;
+                    }
+                    VibrationTarget::Entity { entity_id, eye_height } => {
+                        VarInt(1).encode(writer)?;
+                        VarInt(*entity_id).encode(writer)?;
+                        eye_height.encode(writer)?;
+                    }
+                }
+                VarInt(*ticks).encode(writer)
+            }
+            Particle::SculkCharge { roll } => roll.encode(writer),
+            Particle::Shriek { delay } => VarInt(*delay).encode(writer),
+        }
+    }
+}
@@ -0,0 +1,439 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::sync::RwLock;
+use anyhow::{anyhow, bail, Context};
+use once_cell::sync::Lazy;
+use serde_json::Value;
+use crate::datatype::Identifier;
+use crate::item::{ItemRegistry, ItemStack};
+
+/// Items a recipe accepts in one place.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Ingredient(pub Vec<i32>);
+
+impl Ingredient {
+    pub fn matches(&self, stack: &ItemStack) -> bool {
+        !stack.is_empty() && self.0.contains(&stack.item_id)
+    }
+}
+
+/// Recipe book tab of crafting recipes, by protocol id.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub enum CraftingCategory {
+    Building,
+    Redstone,
+    Equipment,
+    #[default]
+    Misc,
+}
+
+/// Recipe book tab of cooking recipes, by protocol id.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub enum CookingCategory {
+    Food,
+    Blocks,
+    #[default]
+    Misc,
+}
+
+/// A recipe whose ingredients have to be laid out in a pattern, which may be mirrored.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShapedRecipe {
+    /// Recipes of the same group share one recipe book entry.
+    pub group: String,
+    pub category: CraftingCategory,
+    pub width: usize,
+    pub height: usize,
+    /// Row by row, `None` for places which have to stay empty.
+    pub pattern: Vec<Option<Ingredient>>,
+    pub result: ItemStack,
+    /// Whether unlocking the recipe shows a toast.
+    pub show_notification: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShapelessRecipe {
+    pub group: String,
+    pub category: CraftingCategory,
+    pub ingredients: Vec<Ingredient>,
+    pub result: ItemStack,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum CookingKind {
+    Smelting,
+    Blasting,
+    Smoking,
+    CampfireCooking,
+}
+
+/// A recipe of furnaces, blast furnaces, smokers and campfires.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CookingRecipe {
+    pub kind: CookingKind,
+    pub group: String,
+    pub category: CookingCategory,
+    pub ingredient: Ingredient,
+    pub result: ItemStack,
+    pub experience: f32,
+    /// Ticks to cook one item.
+    pub cooking_time: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Recipe {
+    Shaped(ShapedRecipe),
+    Shapeless(ShapelessRecipe),
+    Cooking(CookingRecipe),
+}
+
+impl Recipe {
+    pub fn result(&self) -> ItemStack {
+        match self {
+            Recipe::Shaped(recipe) => recipe.result,
+            Recipe::Shapeless(recipe) => recipe.result,
+            Recipe::Cooking(recipe) => recipe.result,
+        }
+    }
+}
+
+impl ShapedRecipe {
+    /// Whether the pattern, possibly mirrored, fills `grid` (of `width` columns) exactly once the
+    /// empty rows and columns around the items are cut off.
+    pub fn matches(&self, grid: &[ItemStack], width: usize) -> bool {
+        let Some((left, top, right, bottom)) = bounds(grid, width) else { return false };
+        if right - left != self.width || bottom - top != self.height {
+            return false;
+        }
+        [false, true].into_iter().any(|mirrored| {
+            (0..self.height).all(|y| (0..self.width).all(|x| {
+                let column = match mirrored {
+                    true => self.width - 1 - x,
+                    false => x,
+                };
+                let stack = &grid[(top + y) * width + left + x];
+                match &self.pattern[y * self.width + column] {
+                    Some(ingredient) => ingredient.matches(stack),
+                    None => stack.is_empty(),
+                }
+            }))
+        })
+    }
+}
+
+impl ShapelessRecipe {
+    /// Whether the items of `grid` are the ingredients in any order, nothing else.
+    pub fn matches(&self, grid: &[ItemStack]) -> bool {
+        let stacks = grid.iter().filter(|stack| !stack.is_empty()).collect::<Vec<_>>();
+        if stacks.len() != self.ingredients.len() {
+            return false;
+        }
+        let mut used = vec![false; stacks.len()];
+        assign(&self.ingredients, &stacks, &mut used)
+    }
+}
+
+/// Matches every ingredient to a distinct stack, backtracking since ingredients may overlap.
+fn assign(ingredients: &[Ingredient], stacks: &[&ItemStack], used: &mut [bool]) -> bool {
+    let Some((ingredient, rest)) = ingredients.split_first() else { return true };
+    for index in 0..stacks.len() {
+        if !used[index] && ingredient.matches(stacks[index]) {
+            used[index] = true;
+            if assign(rest, stacks, used) {
+                return true;
+            }
+            used[index] = false;
+        }
+    }
+    false
+}
+
+/// Left, top, right and bottom (exclusive) edges of the items in a grid.
+fn bounds(grid: &[ItemStack], width: usize) -> Option<(usize, usize, usize, usize)> {
+    let occupied = grid.iter().enumerate().filter(|(_, stack)| !stack.is_empty()).map(|(index, _)| (index % width, index / width));
+    occupied.fold(None, |bounds, (x, y)| match bounds {
+        None => Some((x, y, x + 1, y + 1)),
+        Some((left, top, right, bottom)) => Some((left.min(x), top.min(y), right.max(x + 1), bottom.max(y + 1))),
+    })
+}
+
+/// Recipes by id, as found in the `recipe` directories of data packs.
+#[derive(Debug, Clone, Default)]
+pub struct RecipeRegistry {
+    recipes: Vec<(Identifier, Recipe)>,
+    index: HashMap<Identifier, usize>,
+}
+
+impl RecipeRegistry {
+    /// Reads every namespace of a `data` directory, e.g. the vanilla one extracted from the server jar.
+    /// Item tags come from `tags/item`. Recipes of other types or naming unknown items are skipped.
+    pub fn load(data_directory: &Path, items: &ItemRegistry) -> anyhow::Result<Self> {
+        if items.is_empty() {
+            bail!("Recipes can only be loaded once the items are known");
+        }
+        let namespaces = fs::read_dir(data_directory).with_context(|| format!("Failed to read {}", data_directory.display()))?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut tags = HashMap::new();
+        let mut files = Vec::new();
+        for namespace in namespaces.iter().filter(|path| path.is_dir()) {
+            let name = namespace.file_name().and_then(|name| name.to_str()).unwrap_or_default().to_string();
+            for (path, json) in json_files(&namespace.join("tags").join("item"))? {
+                tags.insert(Identifier::new(name.clone(), path)?, json);
+            }
+            for (path, json) in json_files(&namespace.join("recipe"))? {
+                files.push((Identifier::new(name.clone(), path)?, json));
+            }
+        }
+
+        let resolver = TagResolver { items, tags };
+        let mut registry = Self::default();
+        for (id, json) in files {
+            match parse_recipe(&json, &resolver).with_context(|| format!("Invalid recipe {}", id))? {
+                Some(recipe) => registry.register(id, recipe),
+                None => continue,
+            }
+        }
+        Ok(registry)
+    }
+
+    /// Adds or replaces a recipe.
+    pub fn register(&mut self, id: Identifier, recipe: Recipe) {
+        match self.index.get(&id) {
+            Some(&position) => self.recipes[position].1 = recipe,
+            None => {
+                self.index.insert(id.clone(), self.recipes.len());
+                self.recipes.push((id, recipe));
+            }
+        }
+    }
+
+    pub fn get(&self, id: &Identifier) -> Option<&Recipe> {
+        self.index.get(id).map(|&position| &self.recipes[position].1)
+    }
+
+    pub fn len(&self) -> usize {
+        self.recipes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.recipes.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Identifier, &Recipe)> {
+        self.recipes.iter().map(|(id, recipe)| (id, recipe))
+    }
+
+    /// The crafting recipe matching a square grid of `width` columns, like the 2x2 inventory grid
+    /// or the 3x3 crafting table.
+    pub fn craft(&self, grid: &[ItemStack], width: usize) -> Option<(&Identifier, &Recipe)> {
+        if grid.iter().all(ItemStack::is_empty) {
+            return None;
+        }
+        self.iter().find(|(_, recipe)| match recipe {
+            Recipe::Shaped(recipe) => recipe.matches(grid, width),
+            Recipe::Shapeless(recipe) => recipe.matches(grid),
+            Recipe::Cooking(_) => false,
+        })
+    }
+
+    /// The recipe cooking `stack` in a block of the given kind.
+    pub fn cook(&self, kind: CookingKind, stack: &ItemStack) -> Option<&CookingRecipe> {
+        self.recipes.iter().find_map(|(_, recipe)| match recipe {
+            Recipe::Cooking(recipe) if recipe.kind == kind && recipe.ingredient.matches(stack) => Some(recipe),
+            _ => None,
+        })
+    }
+}
+
+/// JSON files below `directory` by path without the extension, `/` separated. Missing directories have none.
+pub(crate) fn json_files(directory: &Path) -> anyhow::Result<Vec<(String, Value)>> {
+    let mut files = Vec::new();
+    if !directory.is_dir() {
+        return Ok(files);
+    }
+    let mut pending = vec![directory.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        for entry in fs::read_dir(&current).with_context(|| format!("Failed to read {}", current.display()))? {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+                continue;
+            }
+            if path.extension().is_none_or(|extension| extension != "json") {
+                continue;
+            }
+            let relative = path.strip_prefix(directory)?.with_extension("");
+            let name = relative.components().map(|component| component.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/");
+            let content = fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+            let json = serde_json::from_str(&content).with_context(|| format!("Malformed {}", path.display()))?;
+            files.push((name, json));
+        }
+    }
+    Ok(files)
+}
+
+struct TagResolver<'a> {
+    items: &'a ItemRegistry,
+    tags: HashMap<Identifier, Value>,
+}
+
+impl TagResolver<'_> {
+    fn item(&self, name: &str) -> anyhow::Result<Option<i32>> {
+        let name: Identifier = name.parse().map_err(|_| anyhow!("Invalid item name {}", name))?;
+        Ok(self.items.id(&name))
+    }
+
+    /// Items of a tag, following nested tags. Optional entries which do not exist are left out.
+    fn tag(&self, name: &str, visited: &mut HashSet<Identifier>) -> anyhow::Result<Vec<i32>> {
+        let name: Identifier = name.parse().map_err(|_| anyhow!("Invalid tag name {}", name))?;
+        if !visited.insert(name.clone()) {
+            bail!("Tag {} includes itself", name);
+        }
+        let tag = self.tags.get(&name).ok_or_else(|| anyhow!("Unknown item tag {}", name))?;
+        let values = tag.get("values").and_then(Value::as_array).ok_or_else(|| anyhow!("Tag {} has no values", name))?;
+        let mut items = Vec::new();
+        for value in values {
+            let (entry, required) = match value {
+                Value::String(entry) => (entry.as_str(), true),
+                value => (value.get("id").and_then(Value::as_str).ok_or_else(|| anyhow!("Invalid entry in tag {}", name))?,
+                    value.get("required").and_then(Value::as_bool).unwrap_or(true)),
+            };
+            let resolved = match entry.strip_prefix('#') {
+                Some(nested) => self.tag(nested, visited).map(Some),
+                None => self.item(entry).map(|item| item.map(|item| vec![item])),
+            };
+            match resolved {
+                Ok(Some(resolved)) => items.extend(resolved),
+                Ok(None) | Err(_) if !required => {}
+                Ok(None) => bail!("Tag {} names unknown item {}", name, entry),
+                Err(err) => return Err(err),
+            }
+        }
+        visited.remove(&name);
+        Ok(items)
+    }
+
+    /// An ingredient as `{"item": ...}`, `{"tag": ...}`, a string with `#` for tags, or a list of those.
+    /// `None` when it names an item the registry does not know.
+    fn ingredient(&self, value: &Value) -> anyhow::Result<Option<Ingredient>> {
+        let mut items = Vec::new();
+        let alternatives = match value {
+            Value::Array(alternatives) => alternatives.iter().collect(),
+            value => vec![value],
+        };
+        for alternative in alternatives {
+            let (name, is_tag) = match alternative {
+                Value::String(name) => match name.strip_prefix('#') {
+                    Some(tag) => (tag, true),
+                    None => (name.as_str(), false),
+                },
+                value => match (value.get("item").and_then(Value::as_str), value.get("tag").and_then(Value::as_str)) {
+                    (Some(item), _) => (item, false),
+                    (None, Some(tag)) => (tag, true),
+                    (None, None) => bail!("Ingredient names neither an item nor a tag"),
+                },
+            };
+            match is_tag {
+                true => items.extend(self.tag(name, &mut HashSet::new())?),
+                false => match self.item(name)? {
+                    Some(item) => items.push(item),
+                    None => return Ok(None),
+                },
+            }
+        }
+        Ok(Some(Ingredient(items)))
+    }
+
+    /// A result as `{"id": ..., "count": ...}`, `None` for unknown items.
+    fn result(&self, value: &Value) -> anyhow::Result<Option<ItemStack>> {
+        let name = match value {
+            Value::String(name) => Some(name.as_str()),
+            value => value.get("id").or_else(|| value.get("item")).and_then(Value::as_str),
+        };
+        let name = name.ok_or_else(|| anyhow!("Result names no item"))?;
+        let count = value.get("count").and_then(Value::as_i64).unwrap_or(1) as i32;
+        Ok(self.item(name)?.map(|item| ItemStack::new(item, count)))
+    }
+}
+
+/// A recipe of a supported type, `None` for other types and recipes with unknown items.
+fn parse_recipe(json: &Value, resolver: &TagResolver) -> anyhow::Result<Option<Recipe>> {
+    let kind = json.get("type").and_then(Value::as_str).ok_or_else(|| anyhow!("Recipe has no type"))?;
+    let kind = kind.strip_prefix("minecraft:").unwrap_or(kind);
+    let Some(result) = json.get("result").map(|result| resolver.result(result)).transpose()? else { return Ok(None) };
+    let Some(result) = result else { return Ok(None) };
+    let group = json.get("group").and_then(Value::as_str).unwrap_or_default().to_string();
+    let category = json.get("category").and_then(Value::as_str).unwrap_or("misc");
+    let crafting_category = match category {
+        "building" => CraftingCategory::Building,
+        "redstone" => CraftingCategory::Redstone,
+        "equipment" => CraftingCategory::Equipment,
+        _ => CraftingCategory::Misc,
+    };
+    let recipe = match kind {
+        "crafting_shaped" => {
+            let rows = json.get("pattern").and_then(Value::as_array).ok_or_else(|| anyhow!("Shaped recipe has no pattern"))?
+                .iter()
+                .map(|row| row.as_str().map(|row| row.chars().collect::<Vec<_>>()).ok_or_else(|| anyhow!("Pattern rows are strings")))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            let width = rows.first().map_or(0, Vec::len);
+            if rows.is_empty() || width == 0 || rows.len() > 3 || width > 3 || rows.iter().any(|row| row.len() != width) {
+                bail!("Patterns are 1 to 3 rows of equal length up to 3");
+            }
+            let key = json.get("key").and_then(Value::as_object).ok_or_else(|| anyhow!("Shaped recipe has no key"))?;
+            let mut pattern = Vec::with_capacity(width * rows.len());
+            for symbol in rows.iter().flatten() {
+                if *symbol == ' ' {
+                    pattern.push(None);
+                    continue;
+                }
+                let value = key.get(&symbol.to_string()).ok_or_else(|| anyhow!("Key has no {:?}", symbol))?;
+                let Some(ingredient) = resolver.ingredient(value)? else { return Ok(None) };
+                pattern.push(Some(ingredient));
+            }
+            let show_notification = json.get("show_notification").and_then(Value::as_bool).unwrap_or(true);
+            Recipe::Shaped(ShapedRecipe { group, category: crafting_category, width, height: rows.len(), pattern, result, show_notification })
+        }
+        "crafting_shapeless" => {
+            let values = json.get("ingredients").and_then(Value::as_array).ok_or_else(|| anyhow!("Shapeless recipe has no ingredients"))?;
+            if values.is_empty() || values.len() > 9 {
+                bail!("Shapeless recipes have 1 to 9 ingredients");
+            }
+            let mut ingredients = Vec::with_capacity(values.len());
+            for value in values {
+                let Some(ingredient) = resolver.ingredient(value)? else { return Ok(None) };
+                ingredients.push(ingredient);
+            }
+            Recipe::Shapeless(ShapelessRecipe { group, category: crafting_category, ingredients, result })
+        }
+        "smelting" | "blasting" | "smoking" | "campfire_cooking" => {
+            let (kind, default_time) = match kind {
+                "smelting" => (CookingKind::Smelting, 200),
+                "blasting" => (CookingKind::Blasting, 100),
+                "smoking" => (CookingKind::Smoking, 100),
+                _ => (CookingKind::CampfireCooking, 100),
+            };
+            let value = json.get("ingredient").ok_or_else(|| anyhow!("Cooking recipe has no ingredient"))?;
+            let Some(ingredient) = resolver.ingredient(value)? else { return Ok(None) };
+            let experience = json.get("experience").and_then(Value::as_f64).unwrap_or(0.0) as f32;
+            let cooking_time = json.get("cookingtime").and_then(Value::as_u64).unwrap_or(default_time) as u32;
+            let category = match category {
+                "food" => CookingCategory::Food,
+                "blocks" => CookingCategory::Blocks,
+                _ => CookingCategory::Misc,
+            };
+            Recipe::Cooking(CookingRecipe { kind, group, category, ingredient, result, experience, cooking_time })
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(recipe))
+}
+
+static RECIPES: Lazy<RwLock<RecipeRegistry>> = Lazy::new(|| RwLock::new(RecipeRegistry::default()));
+
+/// Recipes of the server, none until loaded from a data directory.
+pub fn recipes() -> &'static RwLock<RecipeRegistry> {
+    &RECIPES
+}
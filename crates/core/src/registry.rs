@@ -0,0 +1,115 @@
+mod vanilla;
+mod chat_type;
+
+pub use vanilla::*;
+pub use chat_type::*;
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+use once_cell::sync::Lazy;
+use crate::datatype::Identifier;
+use crate::nbt::NbtTag;
+
+/// Values of a registry which is synchronized to clients during Configuration.
+pub trait RegistryValue {
+    fn to_nbt(&self) -> NbtTag;
+}
+
+#[derive(Debug, Clone)]
+pub struct RegistryEntry<T> {
+    pub id: Identifier,
+    pub value: T,
+    /// Whether the entry ships with the vanilla core data pack, clients knowing the
+    /// pack get its id only.
+    pub known: bool,
+}
+
+/// Ordered registry, the network id of an entry is its position.
+#[derive(Debug, Clone)]
+pub struct Registry<T> {
+    id: Identifier,
+    entries: Vec<RegistryEntry<T>>,
+    index: HashMap<Identifier, usize>,
+}
+
+impl<T> Registry<T> {
+    pub fn new(id: Identifier) -> Self {
+        Self {
+            id,
+            entries: Vec::new(),
+            index: HashMap::new(),
+        }
+    }
+
+    pub fn id(&self) -> &Identifier {
+        &self.id
+    }
+
+    /// Adds or replaces an entry, returning its network id.
+    pub fn register(&mut self, id: Identifier, value: T, known: bool) -> i32 {
+        if let Some(&position) = self.index.get(&id) {
+            self.entries[position] = RegistryEntry { id, value, known };
+            return position as i32;
+        }
+        self.index.insert(id.clone(), self.entries.len());
+        self.entries.push(RegistryEntry { id, value, known });
+        self.entries.len() as i32 - 1
+    }
+
+    pub fn get(&self, id: &Identifier) -> Option<&T> {
+        self.index.get(id).map(|&position| &self.entries[position].value)
+    }
+
+    pub fn get_by_network_id(&self, network_id: i32) -> Option<&RegistryEntry<T>> {
+        usize::try_from(network_id).ok().and_then(|position| self.entries.get(position))
+    }
+
+    pub fn network_id(&self, id: &Identifier) -> Option<i32> {
+        self.index.get(id).map(|&position| position as i32)
+    }
+
+    pub fn entries(&self) -> &[RegistryEntry<T>] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Entries as sent in Registry Data: known vanilla entries carry no data.
+pub fn registry_sync_entries<T: RegistryValue>(registry: &Registry<T>, client_knows_core: bool) -> Vec<(Identifier, Option<NbtTag>)> {
+    registry.entries().iter()
+        .map(|entry| {
+            let data = (!(entry.known && client_knows_core)).then(|| entry.value.to_nbt());
+            (entry.id.clone(), data)
+        })
+        .collect()
+}
+
+/// Registries synchronized to clients during Configuration.
+#[derive(Debug, Clone)]
+pub struct Registries {
+    pub chat_types: Registry<ChatType>,
+    /// Vanilla registries whose contents Dolls does not model, only their entry ids are known.
+    pub opaque: Vec<Registry<OpaqueEntry>>,
+}
+
+impl Default for Registries {
+    fn default() -> Self {
+        Self {
+            chat_types: vanilla_chat_types(),
+            opaque: vanilla_opaque_registries(),
+        }
+    }
+}
+
+static REGISTRIES: Lazy<RwLock<Registries>> = Lazy::new(|| RwLock::new(Registries::default()));
+
+pub fn registries() -> &'static RwLock<Registries> {
+    &REGISTRIES
+}
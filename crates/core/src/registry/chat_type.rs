@@ -0,0 +1,187 @@
+use std::io::{self, Write};
+use crate::datatype::{Encode, IdOr, Identifier, VarInt};
+use crate::nbt::{NbtCompound, NbtTag};
+use crate::registry::{registries, Registry, RegistryValue};
+use crate::text::{Style, TextComponent};
+
+/// Argument substituted into a chat decoration's translation.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ChatDecorationParameter {
+    Sender,
+    Target,
+    Content,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatDecoration {
+    pub translation_key: String,
+    pub parameters: Vec<ChatDecorationParameter>,
+    pub style: Style,
+}
+
+/// Entry of the `minecraft:chat_type` registry.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatType {
+    pub chat: ChatDecoration,
+    pub narration: ChatDecoration,
+}
+
+/// Chat type with the names for its decoration, as carried by chat packets.
+#[derive(Debug, Clone, PartialEq, Encode)]
+pub struct ChatTypeBound {
+    pub chat_type: IdOr<ChatType>,
+    pub sender_name: TextComponent,
+    pub target_name: Option<TextComponent>,
+}
+
+impl ChatDecorationParameter {
+    pub const fn name(self) -> &'static str {
+        match self {
+            ChatDecorationParameter::Sender => "sender",
+            ChatDecorationParameter::Target => "target",
+            ChatDecorationParameter::Content => "content",
+        }
+    }
+
+    pub const fn network_id(self) -> i32 {
+        match self {
+            ChatDecorationParameter::Sender => 0,
+            ChatDecorationParameter::Target => 1,
+            ChatDecorationParameter::Content => 2,
+        }
+    }
+}
+
+impl ChatDecoration {
+    pub fn new(translation_key: &str, parameters: &[ChatDecorationParameter]) -> Self {
+        Self {
+            translation_key: translation_key.to_string(),
+            parameters: parameters.to_vec(),
+            style: Style::default(),
+        }
+    }
+
+    pub fn with_style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Builds the component the client would show, e.g. `<sender> content`.
+    pub fn decorate(&self, sender: &TextComponent, target: Option<&TextComponent>, content: &TextComponent) -> TextComponent {
+        let arguments = self.parameters.iter()
+            .map(|parameter| match parameter {
+                ChatDecorationParameter::Sender => sender.clone(),
+                ChatDecorationParameter::Target => target.cloned().unwrap_or_default(),
+                ChatDecorationParameter::Content => content.clone(),
+            })
+            .collect();
+        TextComponent::translatable(self.translation_key.clone(), arguments).with_style(self.style.clone())
+    }
+
+    fn to_nbt(&self) -> NbtCompound {
+        let parameters = self.parameters.iter().map(|parameter| NbtTag::from(parameter.name())).collect::<Vec<_>>();
+        let mut compound = NbtCompound::new()
+            .with("translation_key", self.translation_key.as_str())
+            .with("parameters", parameters);
+        let style = self.style.to_nbt();
+        if !style.is_empty() {
+            compound.insert("style", style);
+        }
+        compound
+    }
+}
+
+impl ChatType {
+    pub fn new(chat: ChatDecoration, narration: ChatDecoration) -> Self {
+        Self { chat, narration }
+    }
+
+    pub fn decorate(&self, sender: &TextComponent, target: Option<&TextComponent>, content: &TextComponent) -> TextComponent {
+        self.chat.decorate(sender, target, content)
+    }
+}
+
+impl RegistryValue for ChatType {
+    fn to_nbt(&self) -> NbtTag {
+        NbtTag::Compound(NbtCompound::new()
+            .with("chat", self.chat.to_nbt())
+            .with("narration", self.narration.to_nbt()))
+    }
+}
+
+/// Inline form used when a packet refers to an unregistered chat type.
+impl Encode for ChatDecoration {
+    fn encode(&self, writer: &mut impl Write) -> io::Result<()> {
+        self.translation_key.encode(writer)?;
+        VarInt(self.parameters.len() as i32).encode(writer)?;
+        for parameter in &self.parameters {
+            VarInt(parameter.network_id()).encode(writer)?;
+        }
+        self.style.to_nbt().encode(writer)
+    }
+}
+
+impl Encode for ChatType {
+    fn encode(&self, writer: &mut impl Write) -> io::Result<()> {
+        self.chat.encode(writer)?;
+        self.narration.encode(writer)
+    }
+}
+
+impl ChatTypeBound {
+    /// Refers to a registered chat type by id, `None` if it is not registered.
+    pub fn registered(id: &Identifier, sender_name: TextComponent, target_name: Option<TextComponent>) -> Option<Self> {
+        let (network_id, _) = chat_type(id)?;
+        Some(Self {
+            chat_type: IdOr::Id(network_id),
+            sender_name,
+            target_name,
+        })
+    }
+
+    /// The component a client renders for `content` under this chat type.
+    pub fn decorate(&self, content: &TextComponent) -> Option<TextComponent> {
+        let chat_type = match &self.chat_type {
+            IdOr::Id(network_id) => registries().read().unwrap().chat_types.get_by_network_id(*network_id)?.value.clone(),
+            IdOr::Inline(chat_type) => chat_type.clone(),
+        };
+        Some(chat_type.decorate(&self.sender_name, self.target_name.as_ref(), content))
+    }
+}
+
+pub(crate) fn vanilla_chat_types() -> Registry<ChatType> {
+    use ChatDecorationParameter::{Content, Sender, Target};
+
+    let narrate = ChatDecoration::new("chat.type.text.narrate", &[Sender, Content]);
+    let whisper = Style {
+        color: Some("gray".to_string()),
+        italic: Some(true),
+        ..Default::default()
+    };
+    let vanilla = [
+        ("chat", ChatDecoration::new("chat.type.text", &[Sender, Content]), narrate.clone()),
+        ("emote_command", ChatDecoration::new("chat.type.emote", &[Sender, Content]), ChatDecoration::new("chat.type.emote", &[Sender, Content])),
+        ("msg_command_incoming", ChatDecoration::new("commands.message.display.incoming", &[Sender, Content]).with_style(whisper.clone()), narrate.clone()),
+        ("msg_command_outgoing", ChatDecoration::new("commands.message.display.outgoing", &[Target, Content]).with_style(whisper), narrate.clone()),
+        ("say_command", ChatDecoration::new("chat.type.announcement", &[Sender, Content]), narrate.clone()),
+        ("team_msg_command_incoming", ChatDecoration::new("chat.type.team.text", &[Target, Sender, Content]), narrate.clone()),
+        ("team_msg_command_outgoing", ChatDecoration::new("chat.type.team.sent", &[Target, Sender, Content]), narrate),
+    ];
+
+    let mut registry = Registry::new(Identifier::minecraft("chat_type"));
+    for (name, chat, narration) in vanilla {
+        registry.register(Identifier::minecraft(name), ChatType::new(chat, narration), true);
+    }
+    registry
+}
+
+/// Registers a custom chat type, clients receive it on their next Configuration phase.
+pub fn register_chat_type(id: Identifier, chat_type: ChatType) -> i32 {
+    registries().write().unwrap().chat_types.register(id, chat_type, false)
+}
+
+pub fn chat_type(id: &Identifier) -> Option<(i32, ChatType)> {
+    let registries = registries().read().unwrap();
+    let network_id = registries.chat_types.network_id(id)?;
+    registries.chat_types.get(id).map(|chat_type| (network_id, chat_type.clone()))
+}
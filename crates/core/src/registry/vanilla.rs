@@ -0,0 +1,110 @@
+use crate::datatype::Identifier;
+use crate::nbt::{NbtCompound, NbtTag};
+use crate::registry::{Registry, RegistryValue};
+
+/// Minecraft version whose core data pack the vanilla entries come from.
+pub const CORE_PACK_VERSION: &str = "1.21.1";
+
+/// Entry whose data only exists in the client's built-in core pack.
+#[derive(Debug, Clone, Default)]
+pub struct OpaqueEntry(pub Option<NbtCompound>);
+
+impl RegistryValue for OpaqueEntry {
+    fn to_nbt(&self) -> NbtTag {
+        NbtTag::Compound(self.0.clone().unwrap_or_default())
+    }
+}
+
+const DIMENSION_TYPES: &[&str] = &["overworld", "overworld_caves", "the_end", "the_nether"];
+
+const BIOMES: &[&str] = &[
+    "badlands", "bamboo_jungle", "basalt_deltas", "beach", "birch_forest", "cherry_grove", "cold_ocean",
+    "crimson_forest", "dark_forest", "deep_cold_ocean", "deep_dark", "deep_frozen_ocean", "deep_lukewarm_ocean",
+    "deep_ocean", "desert", "dripstone_caves", "end_barrens", "end_highlands", "end_midlands", "eroded_badlands",
+    "flower_forest", "forest", "frozen_ocean", "frozen_peaks", "frozen_river", "grove", "ice_spikes",
+    "jagged_peaks", "jungle", "lukewarm_ocean", "lush_caves", "mangrove_swamp", "meadow", "mushroom_fields",
+    "nether_wastes", "ocean", "old_growth_birch_forest", "old_growth_pine_taiga", "old_growth_spruce_taiga",
+    "plains", "river", "savanna", "savanna_plateau", "small_end_islands", "snowy_beach", "snowy_plains",
+    "snowy_slopes", "snowy_taiga", "soul_sand_valley", "sparse_jungle", "stony_peaks", "stony_shore",
+    "sunflower_plains", "swamp", "taiga", "the_end", "the_void", "warm_ocean", "warped_forest",
+    "windswept_forest", "windswept_gravelly_hills", "windswept_hills", "windswept_savanna", "wooded_badlands",
+];
+
+const TRIM_PATTERNS: &[&str] = &[
+    "bolt", "coast", "dune", "eye", "flow", "host", "raiser", "rib", "sentry", "shaper", "silence", "snout",
+    "spire", "tide", "vex", "ward", "wayfinder", "wild",
+];
+
+const TRIM_MATERIALS: &[&str] = &[
+    "amethyst", "copper", "diamond", "emerald", "gold", "iron", "lapis", "netherite", "quartz", "redstone",
+];
+
+const WOLF_VARIANTS: &[&str] = &["ashen", "black", "chestnut", "pale", "rusty", "snowy", "spotted", "striped", "woods"];
+
+const PAINTING_VARIANTS: &[&str] = &[
+    "alban", "aztec", "aztec2", "backyard", "baroque", "bomb", "bouquet", "burning_skull", "bust", "cavebird",
+    "changing", "cotan", "courbet", "creebet", "donkey_kong", "earth", "endboss", "fern", "fighters", "finding",
+    "fire", "graham", "humble", "kebab", "lowmist", "match", "meditative", "orb", "owlemons", "passage",
+    "pigscene", "plant", "pointer", "pond", "pool", "prairie_ride", "sea", "skeleton", "skull_and_roses",
+    "stage", "sunflowers", "sunset", "tides", "unpacked", "void", "wanderer", "wasteland", "water", "wind",
+    "wither",
+];
+
+const DAMAGE_TYPES: &[&str] = &[
+    "arrow", "bad_respawn_point", "cactus", "campfire", "cramming", "dragon_breath", "drown", "dry_out",
+    "explosion", "fall", "falling_anvil", "falling_block", "falling_stalactite", "fireball", "fireworks",
+    "fly_into_wall", "freeze", "generic", "generic_kill", "hot_floor", "in_fire", "in_wall", "indirect_magic",
+    "lava", "lightning_bolt", "mace_smash", "magic", "mob_attack", "mob_attack_no_aggro", "mob_projectile",
+    "on_fire", "out_of_world", "outside_border", "player_attack", "player_explosion", "sonic_boom", "spit",
+    "stalagmite", "starve", "sting", "sweet_berry_bush", "thorns", "thrown", "trident",
+    "unattributed_fireball", "wind_charge", "wither", "wither_skull",
+];
+
+const BANNER_PATTERNS: &[&str] = &[
+    "base", "border", "bricks", "circle", "creeper", "cross", "curly_border", "diagonal_left", "diagonal_right",
+    "diagonal_up_left", "diagonal_up_right", "flow", "flower", "globe", "gradient", "gradient_up", "guster",
+    "half_horizontal", "half_horizontal_bottom", "half_vertical", "half_vertical_right", "mojang", "piglin",
+    "rhombus", "skull", "small_stripes", "square_bottom_left", "square_bottom_right", "square_top_left",
+    "square_top_right", "straight_cross", "stripe_bottom", "stripe_center", "stripe_downleft",
+    "stripe_downright", "stripe_left", "stripe_middle", "stripe_right", "stripe_top", "triangle_bottom",
+    "triangle_top", "triangles_bottom", "triangles_top",
+];
+
+const ENCHANTMENTS: &[&str] = &[
+    "aqua_affinity", "bane_of_arthropods", "binding_curse", "blast_protection", "breach", "channeling",
+    "density", "depth_strider", "efficiency", "feather_falling", "fire_aspect", "fire_protection", "flame",
+    "fortune", "frost_walker", "impaling", "infinity", "knockback", "looting", "loyalty", "luck_of_the_sea",
+    "lure", "mending", "multishot", "piercing", "power", "projectile_protection", "protection", "punch",
+    "quick_charge", "respiration", "riptide", "sharpness", "silk_touch", "smite", "soul_speed",
+    "sweeping_edge", "swift_sneak", "thorns", "unbreaking", "vanishing_curse", "wind_burst",
+];
+
+const JUKEBOX_SONGS: &[&str] = &[
+    "11", "13", "5", "blocks", "cat", "chirp", "creator", "creator_music_box", "far", "mall", "mellohi",
+    "otherside", "pigstep", "precipice", "relic", "stal", "strad", "wait", "ward",
+];
+
+pub(crate) fn vanilla_opaque_registries() -> Vec<Registry<OpaqueEntry>> {
+    let registries: [(&str, &[&str]); 10] = [
+        ("dimension_type", DIMENSION_TYPES),
+        ("worldgen/biome", BIOMES),
+        ("trim_pattern", TRIM_PATTERNS),
+        ("trim_material", TRIM_MATERIALS),
+        ("wolf_variant", WOLF_VARIANTS),
+        ("painting_variant", PAINTING_VARIANTS),
+        ("damage_type", DAMAGE_TYPES),
+        ("banner_pattern", BANNER_PATTERNS),
+        ("enchantment", ENCHANTMENTS),
+        ("jukebox_song", JUKEBOX_SONGS),
+    ];
+
+    registries.into_iter()
+        .map(|(name, entries)| {
+            let mut registry = Registry::new(Identifier::minecraft(name));
+            for entry in entries {
+                registry.register(Identifier::minecraft(entry), OpaqueEntry::default(), true);
+            }
+            registry
+        })
+        .collect()
+}
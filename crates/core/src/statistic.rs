@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+use anyhow::{anyhow, Context};
+use once_cell::sync::Lazy;
+use serde_json::Value;
+use crate::datatype::Identifier;
+
+/// Kinds of statistics, by `minecraft:stat_type` id. All but [`StatType::Custom`] count per block,
+/// item or entity type.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub enum StatType {
+    Mined,
+    Crafted,
+    Used,
+    Broken,
+    PickedUp,
+    Dropped,
+    Killed,
+    KilledBy,
+    Custom,
+}
+
+impl StatType {
+    pub const ALL: [StatType; 9] = [StatType::Mined, StatType::Crafted, StatType::Used, StatType::Broken, StatType::PickedUp,
+        StatType::Dropped, StatType::Killed, StatType::KilledBy, StatType::Custom];
+
+    pub const fn id(self) -> i32 {
+        self as i32
+    }
+
+    pub fn name(self) -> Identifier {
+        Identifier::minecraft(match self {
+            StatType::Mined => "mined",
+            StatType::Crafted => "crafted",
+            StatType::Used => "used",
+            StatType::Broken => "broken",
+            StatType::PickedUp => "picked_up",
+            StatType::Dropped => "dropped",
+            StatType::Killed => "killed",
+            StatType::KilledBy => "killed_by",
+            StatType::Custom => "custom",
+        })
+    }
+
+    pub fn from_name(name: &Identifier) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name() == *name)
+    }
+}
+
+/// Names of the `minecraft:custom_stat` registry of 1.21.1, in id order.
+pub const CUSTOM_STATS: &[&str] = &["leave_game", "play_time", "total_world_time", "time_since_death", "time_since_rest", "sneak_time",
+    "walk_one_cm", "crouch_one_cm", "sprint_one_cm", "walk_on_water_one_cm", "fall_one_cm", "climb_one_cm", "fly_one_cm",
+    "walk_under_water_one_cm", "minecart_one_cm", "boat_one_cm", "pig_one_cm", "horse_one_cm", "aviate_one_cm", "swim_one_cm",
+    "strider_one_cm", "jump", "drop", "damage_dealt", "damage_dealt_absorbed", "damage_dealt_resisted", "damage_taken",
+    "damage_blocked_by_shield", "damage_absorbed", "damage_resisted", "deaths", "mob_kills", "animals_bred", "player_kills",
+    "fish_caught", "talked_to_villager", "traded_with_villager", "eat_cake_slice", "fill_cauldron", "use_cauldron", "clean_armor",
+    "clean_banner", "clean_shulker_box", "interact_with_brewingstand", "interact_with_beacon", "inspect_dropper", "inspect_hopper",
+    "inspect_dispenser", "play_noteblock", "tune_noteblock", "pot_flower", "trigger_trapped_chest", "open_enderchest", "enchant_item",
+    "play_record", "interact_with_furnace", "interact_with_crafting_table", "open_chest", "sleep_in_bed", "open_shulker_box",
+    "open_barrel", "interact_with_blast_furnace", "interact_with_smoker", "interact_with_lectern", "interact_with_campfire",
+    "interact_with_cartography_table", "interact_with_loom", "interact_with_stonecutter", "bell_ring", "raid_trigger", "raid_win",
+    "interact_with_anvil", "interact_with_grindstone", "target_hit", "interact_with_smithing_table"];
+
+/// Network ids of what statistics count: custom statistics, blocks and entity types. Items use the
+/// [`ItemRegistry`](crate::item::ItemRegistry).
+///
+/// Knows the custom statistics of 1.21.1 out of the box, blocks and entity types come from the
+/// `registries.json` report, see [`StatisticRegistry::from_report`].
+#[derive(Debug, Clone)]
+pub struct StatisticRegistry {
+    custom: HashMap<Identifier, i32>,
+    blocks: HashMap<Identifier, i32>,
+    entity_types: HashMap<Identifier, i32>,
+}
+
+impl Default for StatisticRegistry {
+    fn default() -> Self {
+        let custom = CUSTOM_STATS.iter().enumerate().map(|(id, name)| (Identifier::minecraft(name), id as i32)).collect();
+        Self { custom, blocks: HashMap::new(), entity_types: HashMap::new() }
+    }
+}
+
+impl StatisticRegistry {
+    /// Reads the `minecraft:custom_stat`, `minecraft:block` and `minecraft:entity_type` registries
+    /// from the `registries.json` report.
+    pub fn from_report(json: &str) -> anyhow::Result<Self> {
+        let report: Value = serde_json::from_str(json).context("Malformed registries report")?;
+        let registry = |name: &str| -> anyhow::Result<HashMap<Identifier, i32>> {
+            let entries = report.get(name).and_then(|registry| registry.get("entries")).and_then(Value::as_object)
+                .ok_or_else(|| anyhow!("Registries report has no {} entries", name))?;
+            entries.iter()
+                .map(|(entry_name, entry)| {
+                    let id = entry_name.parse().map_err(|_| anyhow!("Invalid name {} in {}", entry_name, name))?;
+                    let protocol_id = entry.get("protocol_id").and_then(Value::as_i64)
+                        .ok_or_else(|| anyhow!("{} of {} has no protocol id", entry_name, name))?;
+                    Ok((id, protocol_id as i32))
+                })
+                .collect()
+        };
+        Ok(Self { custom: registry("minecraft:custom_stat")?, blocks: registry("minecraft:block")?, entity_types: registry("minecraft:entity_type")? })
+    }
+
+    /// Network id of what a statistic of `kind` counts, `None` for names of other kinds and items.
+    pub fn id(&self, kind: StatType, name: &Identifier) -> Option<i32> {
+        match kind {
+            StatType::Custom => self.custom.get(name).copied(),
+            StatType::Mined => self.blocks.get(name).copied(),
+            StatType::Killed | StatType::KilledBy => self.entity_types.get(name).copied(),
+            _ => None,
+        }
+    }
+}
+
+static STATISTICS: Lazy<RwLock<StatisticRegistry>> = Lazy::new(|| RwLock::new(StatisticRegistry::default()));
+
+/// Statistic ids of the server, replace it with one read from the reports before players join.
+pub fn statistics() -> &'static RwLock<StatisticRegistry> {
+    &STATISTICS
+}
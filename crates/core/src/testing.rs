@@ -0,0 +1,66 @@
+//! Proptest strategies for the protocol types and round-trip assertions, so that every codec gets
+//! encode and decode coverage with a few lines of test.
+
+use std::fmt::Debug;
+use proptest::prelude::*;
+use proptest::test_runner::TestCaseError;
+use crate::datatype::{decode_from_slice, BlockPos, Decode, Encode, Identifier, Uuid, VarInt, VarLong};
+
+/// Encodes `value`, decodes it back and checks that the two are equal and every byte was read.
+pub fn assert_round_trip<T: Encode + Decode + PartialEq + Debug>(value: &T) -> Result<(), TestCaseError> {
+    let mut encoded = Vec::new();
+    value.encode(&mut encoded).map_err(|err| TestCaseError::fail(format!("Failed to encode {:?}: {}", value, err)))?;
+    let decoded: T = decode_from_slice(&encoded).map_err(|err| TestCaseError::fail(format!("Failed to decode {:?}: {}", value, err)))?;
+    prop_assert_eq!(&decoded, value);
+    Ok(())
+}
+
+/// Strings of any characters up to `max_length` characters long.
+pub fn protocol_string(max_length: usize) -> impl Strategy<Value = String> {
+    prop::collection::vec(any::<char>(), 0..=max_length).prop_map(|chars| chars.into_iter().collect())
+}
+
+pub fn uuid() -> impl Strategy<Value = Uuid> {
+    any::<u128>().prop_map(Uuid::from_u128)
+}
+
+impl Arbitrary for VarInt {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        any::<i32>().prop_map(VarInt).boxed()
+    }
+}
+
+impl Arbitrary for VarLong {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        any::<i64>().prop_map(VarLong).boxed()
+    }
+}
+
+/// Positions within the bits a packed position has room for.
+impl Arbitrary for BlockPos {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        (-(1i32 << 25)..(1 << 25), -(1i32 << 11)..(1 << 11), -(1i32 << 25)..(1 << 25))
+            .prop_map(|(x, y, z)| BlockPos::new(x, y, z))
+            .boxed()
+    }
+}
+
+impl Arbitrary for Identifier {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        ("[a-z0-9._-]{1,16}", "[a-z0-9._/-]{1,32}")
+            .prop_map(|(namespace, path)| Identifier::new(namespace, path).expect("Strategy produced an invalid identifier"))
+            .boxed()
+    }
+}
@@ -0,0 +1,420 @@
+use std::io::{self, Read, Write};
+use serde::{Deserialize, Serialize};
+use crate::datatype::{Decode, Encode};
+use crate::nbt::{NbtCompound, NbtTag};
+
+/// Codes following `§` in legacy formatted text: 16 colors, then the formats and `r` for reset.
+const LEGACY_CODES: &str = "0123456789abcdefklmnor";
+/// Colors of the codes `0` to `f`.
+const LEGACY_COLORS: [&str; 16] = [
+    "black", "dark_blue", "dark_green", "dark_aqua", "dark_red", "dark_purple", "gold", "gray",
+    "dark_gray", "blue", "green", "aqua", "red", "light_purple", "yellow", "white",
+];
+
+/// Chat component, sent as NBT in packets and as JSON in the status response.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(from = "TextComponentRepr")]
+pub struct TextComponent {
+    #[serde(flatten)]
+    pub content: TextContent,
+    #[serde(flatten)]
+    pub style: Style,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra: Vec<TextComponent>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum TextContent {
+    Text {
+        text: String,
+    },
+    Translatable {
+        translate: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        fallback: Option<String>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        with: Vec<TextComponent>,
+    },
+    Keybind {
+        keybind: String,
+    },
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Style {
+    /// Named color such as `red`, or `#RRGGBB`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bold: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub italic: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub underlined: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strikethrough: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub obfuscated: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub font: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub insertion: Option<String>,
+    #[serde(default, rename = "clickEvent", skip_serializing_if = "Option::is_none")]
+    pub click_event: Option<ClickEvent>,
+    #[serde(default, rename = "hoverEvent", skip_serializing_if = "Option::is_none")]
+    pub hover_event: Option<HoverEvent>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClickEvent {
+    /// `open_url`, `run_command`, `suggest_command`, `change_page` or `copy_to_clipboard`.
+    pub action: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HoverEvent {
+    /// Only `show_text` is modelled, entity and item tooltips need registry data.
+    pub action: String,
+    pub contents: Box<TextComponent>,
+}
+
+/// JSON accepts plain strings and arrays as components too.
+#[allow(clippy::large_enum_variant)] // Only lives while deserializing.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TextComponentRepr {
+    Plain(String),
+    List(Vec<TextComponent>),
+    Object {
+        #[serde(flatten)]
+        content: TextContent,
+        #[serde(flatten)]
+        style: Style,
+        #[serde(default)]
+        extra: Vec<TextComponent>,
+    },
+}
+
+impl From<TextComponentRepr> for TextComponent {
+    fn from(repr: TextComponentRepr) -> Self {
+        match repr {
+            TextComponentRepr::Plain(text) => TextComponent::text(text),
+            TextComponentRepr::List(mut components) => {
+                if components.is_empty() {
+                    return TextComponent::default();
+                }
+                let mut first = components.remove(0);
+                first.extra.extend(components);
+                first
+            }
+            TextComponentRepr::Object { content, style, extra } => TextComponent { content, style, extra },
+        }
+    }
+}
+
+impl Default for TextContent {
+    fn default() -> Self {
+        TextContent::Text { text: String::new() }
+    }
+}
+
+impl TextComponent {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self {
+            content: TextContent::Text { text: text.into() },
+            ..Default::default()
+        }
+    }
+
+    pub fn translatable(key: impl Into<String>, with: Vec<TextComponent>) -> Self {
+        Self {
+            content: TextContent::Translatable {
+                translate: key.into(),
+                fallback: None,
+                with,
+            },
+            ..Default::default()
+        }
+    }
+
+    /// Text shown by clients lacking the translation, `%s` stands for the next argument.
+    pub fn fallback(mut self, fallback: impl Into<String>) -> Self {
+        if let TextContent::Translatable { fallback: current, .. } = &mut self.content {
+            *current = Some(fallback.into());
+        }
+        self
+    }
+
+    pub fn color(mut self, color: impl Into<String>) -> Self {
+        self.style.color = Some(color.into());
+        self
+    }
+
+    pub fn bold(mut self, bold: bool) -> Self {
+        self.style.bold = Some(bold);
+        self
+    }
+
+    pub fn italic(mut self, italic: bool) -> Self {
+        self.style.italic = Some(italic);
+        self
+    }
+
+    pub fn with_style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn append(mut self, component: TextComponent) -> Self {
+        self.extra.push(component);
+        self
+    }
+
+    /// Text content of this component and its children, ignoring styles and translations.
+    pub fn to_plain_text(&self) -> String {
+        let mut text = match &self.content {
+            TextContent::Text { text } => text.clone(),
+            TextContent::Translatable { translate, fallback, with } => {
+                let mut args = with.iter().map(TextComponent::to_plain_text);
+                match fallback {
+                    Some(fallback) => fallback.split("%s").enumerate()
+                        .map(|(index, part)| if index == 0 { part.to_string() } else { args.next().unwrap_or_default() + part })
+                        .collect(),
+                    None if with.is_empty() => translate.clone(),
+                    None => format!("{}[{}]", translate, args.collect::<Vec<_>>().join(", ")),
+                }
+            }
+            TextContent::Keybind { keybind } => keybind.clone(),
+        };
+        for child in &self.extra {
+            text.push_str(&child.to_plain_text());
+        }
+        text
+    }
+
+    /// Parses text with legacy formatting codes such as `§6gold §lbold`, `&` works in place of `§`
+    /// as usual in configuration files. A color resets the formatting, like in vanilla.
+    pub fn from_legacy(text: &str) -> Self {
+        let mut parts = Vec::new();
+        let mut style = Style::default();
+        let mut current = String::new();
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            let code = match c {
+                '§' | '&' => chars.peek().map(char::to_ascii_lowercase).filter(|code| LEGACY_CODES.contains(*code)),
+                _ => None,
+            };
+            let Some(code) = code else {
+                current.push(c);
+                continue;
+            };
+            chars.next();
+            if !current.is_empty() {
+                parts.push(TextComponent::text(std::mem::take(&mut current)).with_style(style.clone()));
+            }
+            match code {
+                'k' => style.obfuscated = Some(true),
+                'l' => style.bold = Some(true),
+                'm' => style.strikethrough = Some(true),
+                'n' => style.underlined = Some(true),
+                'o' => style.italic = Some(true),
+                'r' => style = Style::default(),
+                color => style = Style {
+                    color: color.to_digit(16).map(|index| LEGACY_COLORS[index as usize].to_string()),
+                    ..Default::default()
+                },
+            }
+        }
+        if !current.is_empty() || parts.is_empty() {
+            parts.push(TextComponent::text(current).with_style(style));
+        }
+        match parts.len() {
+            1 => parts.remove(0),
+            _ => TextComponent { extra: parts, ..TextComponent::text("") },
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("Text components always serialize")
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    pub fn to_nbt(&self) -> NbtTag {
+        let mut compound = NbtCompound::new();
+        match &self.content {
+            TextContent::Text { text } => {
+                compound.insert("text", text.as_str());
+            }
+            TextContent::Translatable { translate, fallback, with } => {
+                compound.insert("translate", translate.as_str());
+                if let Some(fallback) = fallback {
+                    compound.insert("fallback", fallback.as_str());
+                }
+                if !with.is_empty() {
+                    compound.insert("with", with.iter().map(TextComponent::to_nbt).collect::<Vec<_>>());
+                }
+            }
+            TextContent::Keybind { keybind } => {
+                compound.insert("keybind", keybind.as_str());
+            }
+        }
+        self.style.write_nbt(&mut compound);
+        if !self.extra.is_empty() {
+            compound.insert("extra", self.extra.iter().map(TextComponent::to_nbt).collect::<Vec<_>>());
+        }
+        NbtTag::Compound(compound)
+    }
+
+    pub fn from_nbt(tag: &NbtTag) -> io::Result<Self> {
+        let compound = match tag {
+            NbtTag::String(text) => return Ok(TextComponent::text(text.clone())),
+            NbtTag::List(components) => {
+                let components = components.iter().map(TextComponent::from_nbt).collect::<io::Result<Vec<_>>>()?;
+                return Ok(TextComponentRepr::List(components).into());
+            }
+            NbtTag::Compound(compound) => compound,
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid text component tag")),
+        };
+
+        let components = |key: &str| -> io::Result<Vec<TextComponent>> {
+            compound.get_list(key).unwrap_or_default().iter().map(TextComponent::from_nbt).collect()
+        };
+        let content = if let Some(text) = compound.get_str("text") {
+            TextContent::Text { text: text.to_string() }
+        } else if let Some(translate) = compound.get_str("translate") {
+            TextContent::Translatable {
+                translate: translate.to_string(),
+                fallback: compound.get_str("fallback").map(str::to_string),
+                with: components("with")?,
+            }
+        } else if let Some(keybind) = compound.get_str("keybind") {
+            TextContent::Keybind { keybind: keybind.to_string() }
+        } else {
+            TextContent::default()
+        };
+
+        Ok(Self {
+            content,
+            style: Style::from_nbt(compound)?,
+            extra: components("extra")?,
+        })
+    }
+}
+
+impl Style {
+    /// Style fields alone, as used by chat decorations.
+    pub fn to_nbt(&self) -> NbtCompound {
+        let mut compound = NbtCompound::new();
+        self.write_nbt(&mut compound);
+        compound
+    }
+
+    fn write_nbt(&self, compound: &mut NbtCompound) {
+        if let Some(color) = &self.color {
+            compound.insert("color", color.as_str());
+        }
+        let flags = [("bold", self.bold), ("italic", self.italic), ("underlined", self.underlined),
+            ("strikethrough", self.strikethrough), ("obfuscated", self.obfuscated)];
+        for (key, value) in flags {
+            if let Some(value) = value {
+                compound.insert(key, value);
+            }
+        }
+        if let Some(font) = &self.font {
+            compound.insert("font", font.as_str());
+        }
+        if let Some(insertion) = &self.insertion {
+            compound.insert("insertion", insertion.as_str());
+        }
+        if let Some(click_event) = &self.click_event {
+            compound.insert("clickEvent", NbtCompound::new()
+                .with("action", click_event.action.as_str())
+                .with("value", click_event.value.as_str()));
+        }
+        if let Some(hover_event) = &self.hover_event {
+            compound.insert("hoverEvent", NbtCompound::new()
+                .with("action", hover_event.action.as_str())
+                .with("contents", hover_event.contents.to_nbt()));
+        }
+    }
+
+    /// Reads the style fields of a compound, ignoring the rest.
+    pub fn from_nbt(compound: &NbtCompound) -> io::Result<Self> {
+        let click_event = compound.get_compound("clickEvent").map(|event| ClickEvent {
+            action: event.get_str("action").unwrap_or_default().to_string(),
+            value: event.get_str("value").unwrap_or_default().to_string(),
+        });
+        let hover_event = match compound.get_compound("hoverEvent") {
+            Some(event) => match event.get("contents") {
+                Some(contents) => Some(HoverEvent {
+                    action: event.get_str("action").unwrap_or_default().to_string(),
+                    contents: Box::new(TextComponent::from_nbt(contents)?),
+                }),
+                None => None,
+            },
+            None => None,
+        };
+
+        Ok(Self {
+            color: compound.get_str("color").map(str::to_string),
+            bold: compound.get_bool("bold"),
+            italic: compound.get_bool("italic"),
+            underlined: compound.get_bool("underlined"),
+            strikethrough: compound.get_bool("strikethrough"),
+            obfuscated: compound.get_bool("obfuscated"),
+            font: compound.get_str("font").map(str::to_string),
+            insertion: compound.get_str("insertion").map(str::to_string),
+            click_event,
+            hover_event,
+        })
+    }
+}
+
+impl From<&str> for TextComponent {
+    fn from(text: &str) -> Self {
+        TextComponent::text(text)
+    }
+}
+
+impl From<String> for TextComponent {
+    fn from(text: String) -> Self {
+        TextComponent::text(text)
+    }
+}
+
+/// Packets carry text components as network NBT since 1.20.3.
+impl Encode for TextComponent {
+    fn encode(&self, writer: &mut impl Write) -> io::Result<()> {
+        self.to_nbt().encode(writer)
+    }
+}
+
+impl Decode for TextComponent {
+    fn decode(reader: &mut impl Read) -> io::Result<Self> {
+        TextComponent::from_nbt(&NbtTag::decode(reader)?)
+    }
+}
+
+/// Text component sent as a JSON string, as in Login Disconnect.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonTextComponent(pub TextComponent);
+
+impl Encode for JsonTextComponent {
+    fn encode(&self, writer: &mut impl Write) -> io::Result<()> {
+        self.0.to_json().encode(writer)
+    }
+}
+
+impl Decode for JsonTextComponent {
+    fn decode(reader: &mut impl Read) -> io::Result<Self> {
+        let json = crate::datatype::read_bounded_string(reader, 262144)?;
+        TextComponent::from_json(&json)
+            .map(JsonTextComponent)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
@@ -0,0 +1,56 @@
+use proptest::prelude::*;
+use dolls_core::datatype::{decode_from_slice, encode_to_vec, BlockPos, Decode, Identifier, VarInt, VarLong};
+use dolls_core::testing::{assert_round_trip, protocol_string, uuid};
+
+proptest! {
+    #[test]
+    fn var_ints(value: VarInt) {
+        assert_round_trip(&value)?;
+    }
+
+    #[test]
+    fn var_longs(value: VarLong) {
+        assert_round_trip(&value)?;
+    }
+
+    #[test]
+    fn strings(value in protocol_string(256)) {
+        assert_round_trip(&value)?;
+    }
+
+    #[test]
+    fn uuids(value in uuid()) {
+        assert_round_trip(&value)?;
+    }
+
+    #[test]
+    fn block_positions(value: BlockPos) {
+        assert_round_trip(&value)?;
+    }
+
+    #[test]
+    fn identifiers(value: Identifier) {
+        assert_round_trip(&value)?;
+    }
+
+    #[test]
+    fn var_int_prefixes_match_the_reader(bytes in prop::collection::vec(any::<u8>(), 0..8)) {
+        let mut reader = bytes.as_slice();
+        match (VarInt::decode_prefix(&bytes), VarInt::decode(&mut reader)) {
+            (Ok(Some((prefix, size))), Ok(decoded)) => {
+                prop_assert_eq!(prefix, decoded);
+                prop_assert_eq!(size, bytes.len() - reader.len());
+            }
+            (Ok(None), Err(err)) => prop_assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof),
+            (Err(_), Err(err)) => prop_assert_eq!(err.kind(), std::io::ErrorKind::InvalidData),
+            (prefix, decoded) => prop_assert!(false, "Decoders disagree: {:?} and {:?}", prefix, decoded),
+        }
+    }
+
+    #[test]
+    fn var_int_prefixes(value: VarInt) {
+        let bytes = encode_to_vec(&value).unwrap();
+        prop_assert_eq!(VarInt::decode_prefix(&bytes).unwrap(), Some((value, value.written_size())));
+        prop_assert_eq!(decode_from_slice::<VarInt>(&bytes).unwrap(), value);
+    }
+}
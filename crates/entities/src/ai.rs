@@ -0,0 +1,87 @@
+use dolls_core::datatype::BlockPos;
+use crate::prelude::{EntityId, EntityManager};
+
+/// A goal of an entity's goal selector, as last reported by its AI.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GoalState {
+    /// Lower priorities win, as in vanilla.
+    pub priority: i32,
+    pub name: String,
+    pub running: bool,
+}
+
+impl GoalState {
+    pub fn new(priority: i32, name: impl Into<String>, running: bool) -> Self {
+        Self { priority, name: name.into(), running }
+    }
+}
+
+/// The path an entity follows, `next` is the index of the node it walks to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AiPath {
+    pub nodes: Vec<BlockPos>,
+    pub next: usize,
+}
+
+impl AiPath {
+    pub fn new(nodes: Vec<BlockPos>) -> Self {
+        Self { nodes, next: 0 }
+    }
+
+    pub fn target(&self) -> Option<BlockPos> {
+        self.nodes.last().copied()
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.next >= self.nodes.len()
+    }
+}
+
+/// What the AI of an entity reported about itself, kept as a component so that `debug ai` can show it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AiDebugInfo {
+    /// Sorted by priority.
+    pub goals: Vec<GoalState>,
+    pub path: Option<AiPath>,
+}
+
+impl AiDebugInfo {
+    pub fn running_goals(&self) -> impl Iterator<Item = &GoalState> {
+        self.goals.iter().filter(|goal| goal.running)
+    }
+}
+
+impl EntityManager {
+    fn ai_debug_info_mut(&mut self, id: EntityId) -> Option<&mut AiDebugInfo> {
+        if self.component::<AiDebugInfo>(id).is_none() {
+            self.insert_component(id, AiDebugInfo::default());
+        }
+        self.component_mut(id)
+    }
+
+    /// Replaces the goals reported for a live entity.
+    pub fn report_goals(&mut self, id: EntityId, mut goals: Vec<GoalState>) {
+        goals.sort_by_key(|goal| goal.priority);
+        if let Some(info) = self.ai_debug_info_mut(id) {
+            info.goals = goals;
+        }
+    }
+
+    /// Replaces the path reported for a live entity, `None` once it stopped navigating.
+    pub fn report_path(&mut self, id: EntityId, path: Option<AiPath>) {
+        if let Some(info) = self.ai_debug_info_mut(id) {
+            info.path = path;
+        }
+    }
+
+    /// Moves the reported path of an entity on to its node `next`.
+    pub fn advance_path(&mut self, id: EntityId, next: usize) {
+        if let Some(path) = self.component_mut::<AiDebugInfo>(id).and_then(|info| info.path.as_mut()) {
+            path.next = next.min(path.nodes.len());
+        }
+    }
+
+    pub fn ai_debug_info(&self, id: EntityId) -> Option<&AiDebugInfo> {
+        self.component(id)
+    }
+}
@@ -0,0 +1,129 @@
+use std::ops::{Add, Mul, Sub};
+use dolls_core::datatype::{BlockPos, Encode, Uuid};
+use dolls_world::prelude::{ChunkPos, Dimension};
+use crate::prelude::{EntityMetadata, EntityType};
+
+/// Ids are shared by every entity of the server, players included.
+pub type EntityId = i32;
+
+/// Encoded as three doubles, as in most packets with exact positions.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Encode)]
+pub struct Vec3 {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Vec3 {
+    pub const ZERO: Vec3 = Vec3 { x: 0.0, y: 0.0, z: 0.0 };
+
+    pub const fn new(x: f64, y: f64, z: f64) -> Self {
+        Self { x, y, z }
+    }
+
+    pub fn length_squared(self) -> f64 {
+        self.x * self.x + self.y * self.y + self.z * self.z
+    }
+
+    pub fn distance_squared(self, other: Vec3) -> f64 {
+        (self - other).length_squared()
+    }
+
+    pub fn block_pos(self) -> BlockPos {
+        BlockPos::new(self.x.floor() as i32, self.y.floor() as i32, self.z.floor() as i32)
+    }
+
+    pub fn chunk_pos(self) -> ChunkPos {
+        ChunkPos::of(self.block_pos())
+    }
+}
+
+impl Add for Vec3 {
+    type Output = Vec3;
+
+    fn add(self, other: Vec3) -> Vec3 {
+        Vec3::new(self.x + other.x, self.y + other.y, self.z + other.z)
+    }
+}
+
+impl Sub for Vec3 {
+    type Output = Vec3;
+
+    fn sub(self, other: Vec3) -> Vec3 {
+        Vec3::new(self.x - other.x, self.y - other.y, self.z - other.z)
+    }
+}
+
+impl Mul<f64> for Vec3 {
+    type Output = Vec3;
+
+    fn mul(self, factor: f64) -> Vec3 {
+        Vec3::new(self.x * factor, self.y * factor, self.z * factor)
+    }
+}
+
+/// Where an entity looks, in degrees.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Rotation {
+    pub yaw: f32,
+    pub pitch: f32,
+    /// Living entities turn their head apart from their body.
+    pub head_yaw: f32,
+}
+
+/// The data every entity has, anything else is kept in components of the [`EntityManager`](crate::prelude::EntityManager).
+#[derive(Debug, Clone)]
+pub struct Entity {
+    id: EntityId,
+    uuid: Uuid,
+    entity_type: EntityType,
+    /// Only the manager moves entities, it keeps them indexed by chunk.
+    position: Vec3,
+    pub rotation: Rotation,
+    /// Blocks per tick.
+    pub velocity: Vec3,
+    pub on_ground: bool,
+    pub metadata: EntityMetadata,
+    /// Only players in the same dimension see the entity, chunks are indexed regardless of it.
+    pub dimension: Dimension,
+}
+
+impl Entity {
+    pub(crate) fn new(id: EntityId, uuid: Uuid, entity_type: EntityType, position: Vec3) -> Self {
+        Self {
+            id,
+            uuid,
+            entity_type,
+            position,
+            rotation: Rotation::default(),
+            velocity: Vec3::ZERO,
+            on_ground: false,
+            metadata: EntityMetadata::new(),
+            dimension: Dimension::Overworld,
+        }
+    }
+
+    pub fn id(&self) -> EntityId {
+        self.id
+    }
+
+    pub fn uuid(&self) -> Uuid {
+        self.uuid
+    }
+
+    pub fn entity_type(&self) -> EntityType {
+        self.entity_type
+    }
+
+    pub fn position(&self) -> Vec3 {
+        self.position
+    }
+
+    pub(crate) fn set_position(&mut self, position: Vec3) {
+        self.position = position;
+    }
+
+    pub fn chunk_pos(&self) -> ChunkPos {
+        self.position.chunk_pos()
+    }
+}
@@ -0,0 +1,91 @@
+use std::fmt::{Display, Formatter};
+
+/// Names of the `minecraft:entity_type` registry of 1.21.1, indexed by protocol id.
+const ENTITY_TYPE_NAMES: [&str; 130] = [
+    "allay", "area_effect_cloud", "armadillo", "armor_stand", "arrow", "axolotl", "bat", "bee", "blaze", "block_display",
+    "boat", "bogged", "breeze", "breeze_wind_charge", "camel", "cat", "cave_spider", "chest_boat", "chest_minecart", "chicken",
+    "cod", "command_block_minecart", "cow", "creeper", "dolphin", "donkey", "dragon_fireball", "drowned", "egg", "elder_guardian",
+    "end_crystal", "ender_dragon", "ender_pearl", "enderman", "endermite", "evoker", "evoker_fangs", "experience_bottle", "experience_orb", "eye_of_ender",
+    "falling_block", "firework_rocket", "fox", "frog", "furnace_minecart", "ghast", "giant", "glow_item_frame", "glow_squid", "goat",
+    "guardian", "hoglin", "hopper_minecart", "horse", "husk", "illusioner", "interaction", "iron_golem", "item", "item_display",
+    "item_frame", "ominous_item_spawner", "fireball", "leash_knot", "lightning_bolt", "llama", "llama_spit", "magma_cube", "marker", "minecart",
+    "mooshroom", "mule", "ocelot", "painting", "panda", "parrot", "phantom", "pig", "piglin", "piglin_brute",
+    "pillager", "polar_bear", "potion", "pufferfish", "rabbit", "ravager", "salmon", "sheep", "shulker", "shulker_bullet",
+    "silverfish", "skeleton", "skeleton_horse", "slime", "small_fireball", "sniffer", "snow_golem", "snowball", "spawner_minecart", "spectral_arrow",
+    "spider", "squid", "stray", "strider", "tadpole", "text_display", "tnt", "tnt_minecart", "trader_llama", "trident",
+    "tropical_fish", "turtle", "vex", "villager", "vindicator", "wandering_trader", "warden", "wind_charge", "witch", "wither",
+    "wither_skeleton", "wither_skull", "wolf", "zoglin", "zombie", "zombie_horse", "zombie_villager", "zombified_piglin", "player", "fishing_bobber",
+];
+
+/// A kind of entity, by its protocol id.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct EntityType(i32);
+
+impl EntityType {
+    pub const ARMOR_STAND: Self = Self(3);
+    pub const ARROW: Self = Self(4);
+    pub const EXPERIENCE_ORB: Self = Self(38);
+    pub const FALLING_BLOCK: Self = Self(40);
+    pub const ITEM: Self = Self(58);
+    pub const PIG: Self = Self(77);
+    pub const ZOMBIE: Self = Self(124);
+    pub const PLAYER: Self = Self(128);
+
+    pub fn from_id(id: i32) -> Option<Self> {
+        usize::try_from(id).ok().filter(|id| *id < ENTITY_TYPE_NAMES.len()).map(|_| Self(id))
+    }
+
+    /// Looks a type up by its name, with or without the `minecraft:` namespace.
+    pub fn by_name(name: &str) -> Option<Self> {
+        let name = name.strip_prefix("minecraft:").unwrap_or(name);
+        ENTITY_TYPE_NAMES.iter().position(|candidate| *candidate == name).map(|id| Self(id as i32))
+    }
+
+    pub fn id(self) -> i32 {
+        self.0
+    }
+
+    /// Name without the namespace.
+    pub fn name(self) -> &'static str {
+        ENTITY_TYPE_NAMES[self.0 as usize]
+    }
+
+    /// Chunks from which players see entities of this type, capped by their view distance, as in vanilla.
+    pub fn tracking_range(self) -> u32 {
+        match self.name() {
+            "player" => 32,
+            "lightning_bolt" | "end_crystal" => 16,
+            "item" | "experience_orb" | "evoker_fangs" => 6,
+            "arrow" | "spectral_arrow" | "trident" | "snowball" | "egg" | "ender_pearl" | "potion" | "experience_bottle"
+            | "eye_of_ender" | "firework_rocket" | "fishing_bobber" | "fireball" | "small_fireball" | "dragon_fireball"
+            | "wither_skull" | "wind_charge" | "breeze_wind_charge" | "llama_spit" => 4,
+            "marker" => 0,
+            "minecart" | "chest_minecart" | "command_block_minecart" | "furnace_minecart" | "hopper_minecart"
+            | "spawner_minecart" | "tnt_minecart" | "shulker_bullet" => 8,
+            _ => 10,
+        }
+    }
+
+    /// Ticks between movement updates sent to players tracking entities of this type, `u32::MAX` for
+    /// entities which never move by themselves.
+    pub fn update_interval(self) -> u32 {
+        match self.name() {
+            "player" | "evoker_fangs" => 2,
+            "item" | "experience_orb" | "falling_block" | "arrow" | "spectral_arrow" | "trident" => 20,
+            "tnt" | "snowball" | "egg" | "ender_pearl" | "potion" | "experience_bottle" | "firework_rocket" | "fireball"
+            | "small_fireball" | "dragon_fireball" | "wither_skull" | "wind_charge" | "breeze_wind_charge" | "llama_spit" => 10,
+            "eye_of_ender" => 4,
+            "fishing_bobber" => 5,
+            "block_display" | "item_display" | "text_display" | "shulker_bullet" => 1,
+            "painting" | "item_frame" | "glow_item_frame" | "leash_knot" | "lightning_bolt" | "end_crystal"
+            | "area_effect_cloud" | "marker" => u32::MAX,
+            _ => 3,
+        }
+    }
+}
+
+impl Display for EntityType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "minecraft:{}", self.name())
+    }
+}
@@ -0,0 +1,14 @@
+pub mod entity_type;
+pub mod metadata;
+pub mod entity;
+pub mod manager;
+pub mod persistence;
+pub mod ai;
+
+pub mod prelude {
+    pub use crate::entity_type::*;
+    pub use crate::metadata::*;
+    pub use crate::entity::*;
+    pub use crate::manager::*;
+    pub use crate::ai::*;
+}
@@ -0,0 +1,216 @@
+use std::any::{Any, TypeId};
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, HashSet};
+use std::hash::BuildHasher;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::RwLock;
+use once_cell::sync::Lazy;
+use uuid::{Builder, Uuid};
+use dolls_world::prelude::ChunkPos;
+use crate::prelude::{Entity, EntityId, EntityType, Vec3};
+
+static NEXT_ENTITY_ID: AtomicI32 = AtomicI32::new(1);
+
+/// Allocates an id unique among all entities of the server, players included.
+pub fn next_entity_id() -> EntityId {
+    NEXT_ENTITY_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// A random version 4 UUID for entities which are not players.
+pub fn random_uuid() -> Uuid {
+    let state = RandomState::new();
+    let high = state.hash_one(std::time::SystemTime::now()) as u128;
+    let low = state.hash_one(next_entity_id()) as u128;
+    Builder::from_random_bytes(((high << 64) | low).to_be_bytes()).into_uuid()
+}
+
+static ENTITIES: Lazy<RwLock<EntityManager>> = Lazy::new(|| RwLock::new(EntityManager::default()));
+
+/// Every entity of the overworld.
+pub fn entities() -> &'static RwLock<EntityManager> {
+    &ENTITIES
+}
+
+/// Components of one type by entity, boxed so that removing an entity reaches every type.
+trait ComponentStorage: Send + Sync {
+    fn remove_entity(&mut self, id: EntityId);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: Any + Send + Sync> ComponentStorage for HashMap<EntityId, T> {
+    fn remove_entity(&mut self, id: EntityId) {
+        self.remove(&id);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Entities indexed by id, UUID and chunk. Data only some entities have is stored as components,
+/// one map per component type.
+#[derive(Default)]
+pub struct EntityManager {
+    entities: HashMap<EntityId, Entity>,
+    by_uuid: HashMap<Uuid, EntityId>,
+    by_chunk: HashMap<ChunkPos, HashSet<EntityId>>,
+    /// `HashMap<EntityId, T>` for every component type `T`.
+    components: HashMap<TypeId, Box<dyn ComponentStorage>>,
+}
+
+impl std::fmt::Debug for EntityManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EntityManager")
+            .field("entities", &self.entities.len())
+            .field("component_types", &self.components.len())
+            .finish()
+    }
+}
+
+impl EntityManager {
+    /// Adds an entity with a new id, `uuid` defaults to a random one.
+    pub fn spawn(&mut self, entity_type: EntityType, uuid: Option<Uuid>, position: Vec3) -> EntityId {
+        self.spawn_with_id(next_entity_id(), entity_type, uuid.unwrap_or_else(random_uuid), position)
+    }
+
+    /// Adds an entity under an id from [`next_entity_id`], replacing any entity with the same id or UUID.
+    pub fn spawn_with_id(&mut self, id: EntityId, entity_type: EntityType, uuid: Uuid, position: Vec3) -> EntityId {
+        self.remove(id);
+        if let Some(existing) = self.by_uuid.get(&uuid).copied() {
+            self.remove(existing);
+        }
+        self.entities.insert(id, Entity::new(id, uuid, entity_type, position));
+        self.by_uuid.insert(uuid, id);
+        self.by_chunk.entry(position.chunk_pos()).or_default().insert(id);
+        id
+    }
+
+    /// Removes an entity and its components.
+    pub fn remove(&mut self, id: EntityId) -> Option<Entity> {
+        let entity = self.entities.remove(&id)?;
+        self.by_uuid.remove(&entity.uuid());
+        self.unindex(id, entity.chunk_pos());
+        for storage in self.components.values_mut() {
+            storage.remove_entity(id);
+        }
+        Some(entity)
+    }
+
+    fn unindex(&mut self, id: EntityId, chunk: ChunkPos) {
+        if let Some(ids) = self.by_chunk.get_mut(&chunk) {
+            ids.remove(&id);
+            if ids.is_empty() {
+                self.by_chunk.remove(&chunk);
+            }
+        }
+    }
+
+    pub fn get(&self, id: EntityId) -> Option<&Entity> {
+        self.entities.get(&id)
+    }
+
+    /// The entity to change, moving it goes through [`EntityManager::move_entity`].
+    pub fn get_mut(&mut self, id: EntityId) -> Option<&mut Entity> {
+        self.entities.get_mut(&id)
+    }
+
+    pub fn by_uuid(&self, uuid: Uuid) -> Option<&Entity> {
+        self.by_uuid.get(&uuid).and_then(|id| self.entities.get(id))
+    }
+
+    pub fn contains(&self, id: EntityId) -> bool {
+        self.entities.contains_key(&id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Entity> {
+        self.entities.values()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Entity> {
+        self.entities.values_mut()
+    }
+
+    /// Moves an entity, re-indexing it when it changes chunks. Returns the chunk it left, if any.
+    pub fn move_entity(&mut self, id: EntityId, position: Vec3) -> Option<ChunkPos> {
+        let entity = self.entities.get_mut(&id)?;
+        let (from, to) = (entity.chunk_pos(), position.chunk_pos());
+        entity.set_position(position);
+        if from == to {
+            return None;
+        }
+        self.unindex(id, from);
+        self.by_chunk.entry(to).or_default().insert(id);
+        Some(from)
+    }
+
+    pub fn in_chunk(&self, chunk: ChunkPos) -> impl Iterator<Item = &Entity> {
+        self.by_chunk.get(&chunk).into_iter().flatten().filter_map(|id| self.entities.get(id))
+    }
+
+    /// Entities within `radius` blocks of `center`, looked up through the chunks the sphere touches.
+    pub fn within(&self, center: Vec3, radius: f64) -> impl Iterator<Item = &Entity> {
+        let chunk_of = |coordinate: f64| (coordinate.floor() as i32) >> 4;
+        let (min_x, max_x) = (chunk_of(center.x - radius), chunk_of(center.x + radius));
+        let (min_z, max_z) = (chunk_of(center.z - radius), chunk_of(center.z + radius));
+        (min_x..=max_x)
+            .flat_map(move |x| (min_z..=max_z).map(move |z| ChunkPos::new(x, z)))
+            .flat_map(|chunk| self.in_chunk(chunk))
+            .filter(move |entity| entity.position().distance_squared(center) <= radius * radius)
+    }
+
+    /// Chunks which hold at least one entity.
+    pub fn occupied_chunks(&self) -> impl Iterator<Item = ChunkPos> + '_ {
+        self.by_chunk.keys().copied()
+    }
+
+    fn storage<T: Any + Send + Sync>(&self) -> Option<&HashMap<EntityId, T>> {
+        self.components.get(&TypeId::of::<T>()).and_then(|storage| storage.as_any().downcast_ref())
+    }
+
+    fn storage_mut<T: Any + Send + Sync>(&mut self) -> &mut HashMap<EntityId, T> {
+        self.components.entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(HashMap::<EntityId, T>::new()))
+            .as_any_mut()
+            .downcast_mut()
+            .expect("Component storage of the wrong type")
+    }
+
+    /// Attaches a component to a live entity, returning the one it replaced.
+    pub fn insert_component<T: Any + Send + Sync>(&mut self, id: EntityId, component: T) -> Option<T> {
+        if !self.contains(id) {
+            return None;
+        }
+        self.storage_mut::<T>().insert(id, component)
+    }
+
+    pub fn component<T: Any + Send + Sync>(&self, id: EntityId) -> Option<&T> {
+        self.storage::<T>()?.get(&id)
+    }
+
+    pub fn component_mut<T: Any + Send + Sync>(&mut self, id: EntityId) -> Option<&mut T> {
+        self.components.get_mut(&TypeId::of::<T>())?.as_any_mut().downcast_mut::<HashMap<EntityId, T>>()?.get_mut(&id)
+    }
+
+    pub fn remove_component<T: Any + Send + Sync>(&mut self, id: EntityId) -> Option<T> {
+        self.components.get_mut(&TypeId::of::<T>())?.as_any_mut().downcast_mut::<HashMap<EntityId, T>>()?.remove(&id)
+    }
+
+    /// Every entity with a `T` component, along with it.
+    pub fn query<T: Any + Send + Sync>(&self) -> impl Iterator<Item = (&Entity, &T)> {
+        self.storage::<T>().into_iter().flatten()
+            .filter_map(|(id, component)| self.entities.get(id).map(|entity| (entity, component)))
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Write};
+use dolls_core::datatype::{BlockPos, Encode, Uuid, VarInt};
+use dolls_core::item::ItemStack;
+use dolls_core::text::TextComponent;
+
+/// Bits of the shared flags entry at index [`EntityMetadata::FLAGS`].
+pub const ON_FIRE_FLAG: u8 = 0x01;
+pub const CROUCHING_FLAG: u8 = 0x02;
+pub const SPRINTING_FLAG: u8 = 0x08;
+pub const SWIMMING_FLAG: u8 = 0x10;
+pub const INVISIBLE_FLAG: u8 = 0x20;
+pub const GLOWING_FLAG: u8 = 0x40;
+pub const FALL_FLYING_FLAG: u8 = 0x80;
+
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum Pose {
+    #[default]
+    Standing,
+    FallFlying,
+    Sleeping,
+    Swimming,
+    SpinAttack,
+    Crouching,
+    LongJumping,
+    Dying,
+}
+
+/// A value of an entity's metadata, only the kinds the server sets are modelled.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetadataValue {
+    Byte(u8),
+    VarInt(i32),
+    Float(f32),
+    String(String),
+    TextComponent(TextComponent),
+    OptionalTextComponent(Option<TextComponent>),
+    Slot(ItemStack),
+    Boolean(bool),
+    Position(BlockPos),
+    OptionalUuid(Option<Uuid>),
+    BlockState(i32),
+    Pose(Pose),
+}
+
+impl MetadataValue {
+    /// Id of the value's serializer in the protocol.
+    pub fn type_id(&self) -> i32 {
+        match self {
+            MetadataValue::Byte(_) => 0,
+            MetadataValue::VarInt(_) => 1,
+            MetadataValue::Float(_) => 3,
+            MetadataValue::String(_) => 4,
+            MetadataValue::TextComponent(_) => 5,
+            MetadataValue::OptionalTextComponent(_) => 6,
+            MetadataValue::Slot(_) => 7,
+            MetadataValue::Boolean(_) => 8,
+            MetadataValue::Position(_) => 10,
+            MetadataValue::OptionalUuid(_) => 13,
+            MetadataValue::BlockState(_) => 14,
+            MetadataValue::Pose(_) => 21,
+        }
+    }
+}
+
+impl Encode for MetadataValue {
+    fn encode(&self, writer: &mut impl Write) -> io::Result<()> {
+        VarInt(self.type_id()).encode(writer)?;
+        match self {
+            MetadataValue::Byte(value) => value.encode(writer),
+            MetadataValue::VarInt(value) | MetadataValue::BlockState(value) => VarInt(*value).encode(writer),
+            MetadataValue::Float(value) => value.encode(writer),
+            MetadataValue::String(value) => value.encode(writer),
+            MetadataValue::TextComponent(value) => value.encode(writer),
+            MetadataValue::OptionalTextComponent(value) => value.encode(writer),
+            MetadataValue::Slot(value) => value.encode(writer),
+            MetadataValue::Boolean(value) => value.encode(writer),
+            MetadataValue::Position(value) => value.encode(writer),
+            MetadataValue::OptionalUuid(value) => value.encode(writer),
+            MetadataValue::Pose(pose) => VarInt(*pose as i32).encode(writer),
+        }
+    }
+}
+
+/// Indexed values describing how an entity looks, entries changed since the last
+/// [`take_dirty`](EntityMetadata::take_dirty) are sent to players tracking it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EntityMetadata {
+    entries: BTreeMap<u8, MetadataValue>,
+    dirty: BTreeSet<u8>,
+}
+
+impl EntityMetadata {
+    /// Indices every entity shares.
+    pub const FLAGS: u8 = 0;
+    pub const AIR_TICKS: u8 = 1;
+    pub const CUSTOM_NAME: u8 = 2;
+    pub const CUSTOM_NAME_VISIBLE: u8 = 3;
+    pub const SILENT: u8 = 4;
+    pub const NO_GRAVITY: u8 = 5;
+    pub const POSE: u8 = 6;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, index: u8) -> Option<&MetadataValue> {
+        self.entries.get(&index)
+    }
+
+    /// Sets an entry, marking it dirty only if it changed.
+    pub fn set(&mut self, index: u8, value: MetadataValue) {
+        if self.entries.get(&index) != Some(&value) {
+            self.entries.insert(index, value);
+            self.dirty.insert(index);
+        }
+    }
+
+    /// Turns bits of the shared flags on or off.
+    pub fn set_flag(&mut self, flag: u8, enabled: bool) {
+        let flags = match self.get(Self::FLAGS) {
+            Some(MetadataValue::Byte(flags)) => *flags,
+            _ => 0,
+        };
+        let flags = match enabled {
+            true => flags | flag,
+            false => flags & !flag,
+        };
+        self.set(Self::FLAGS, MetadataValue::Byte(flags));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        !self.dirty.is_empty()
+    }
+
+    /// The entries changed since the last call, to send to players already tracking the entity.
+    pub fn take_dirty(&mut self) -> EntityMetadata {
+        let dirty = std::mem::take(&mut self.dirty);
+        let entries = dirty.into_iter()
+            .filter_map(|index| self.entries.get(&index).map(|value| (index, value.clone())))
+            .collect();
+        EntityMetadata { entries, dirty: BTreeSet::new() }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u8, &MetadataValue)> {
+        self.entries.iter().map(|(index, value)| (*index, value))
+    }
+}
+
+/// Entries as index, serializer and value, ended by `0xFF`.
+impl Encode for EntityMetadata {
+    fn encode(&self, writer: &mut impl Write) -> io::Result<()> {
+        for (index, value) in &self.entries {
+            index.encode(writer)?;
+            value.encode(writer)?;
+        }
+        0xFFu8.encode(writer)
+    }
+}
@@ -0,0 +1,63 @@
+use anyhow::anyhow;
+use dolls_core::datatype::Uuid;
+use dolls_core::nbt::{NbtCompound, NbtTag};
+use dolls_world::prelude::Dimension;
+use crate::prelude::{Entity, EntityId, EntityManager, EntityType, Rotation, Vec3};
+
+fn vec3_to_nbt(vector: Vec3) -> Vec<NbtTag> {
+    vec![NbtTag::Double(vector.x), NbtTag::Double(vector.y), NbtTag::Double(vector.z)]
+}
+
+fn vec3_from_nbt(nbt: &NbtCompound, key: &str) -> Option<Vec3> {
+    match nbt.get_list(key)?.iter().map(NbtTag::as_f64).collect::<Option<Vec<_>>>()?[..] {
+        [x, y, z] => Some(Vec3::new(x, y, z)),
+        _ => None,
+    }
+}
+
+/// UUIDs are saved as four ints, most significant first.
+fn uuid_to_nbt(uuid: Uuid) -> Vec<i32> {
+    let value = uuid.as_u128();
+    vec![(value >> 96) as i32, (value >> 64) as i32, (value >> 32) as i32, value as i32]
+}
+
+fn uuid_from_nbt(nbt: &NbtCompound) -> Option<Uuid> {
+    match nbt.get("UUID") {
+        Some(NbtTag::IntArray(ints)) if ints.len() == 4 => {
+            Some(Uuid::from_u128(ints.iter().fold(0, |value, int| (value << 32) | *int as u32 as u128)))
+        }
+        _ => None,
+    }
+}
+
+impl Entity {
+    /// The data every entity saves, as vanilla names it. Data of a type is added by the code giving it behaviour.
+    pub fn to_nbt(&self) -> NbtCompound {
+        NbtCompound::new()
+            .with("id", self.entity_type().to_string())
+            .with("UUID", uuid_to_nbt(self.uuid()))
+            .with("Pos", vec3_to_nbt(self.position()))
+            .with("Motion", vec3_to_nbt(self.velocity))
+            .with("Rotation", vec![NbtTag::Float(self.rotation.yaw), NbtTag::Float(self.rotation.pitch)])
+            .with("OnGround", self.on_ground)
+    }
+}
+
+impl EntityManager {
+    /// Spawns an entity saved by [`Entity::to_nbt`] in `dimension`, under a new id and its saved UUID.
+    pub fn spawn_from_nbt(&mut self, nbt: &NbtCompound, dimension: Dimension) -> anyhow::Result<EntityId> {
+        let name = nbt.get_str("id").ok_or_else(|| anyhow!("Entity without a type"))?;
+        let entity_type = EntityType::by_name(name).ok_or_else(|| anyhow!("Unknown entity type {}", name))?;
+        let position = vec3_from_nbt(nbt, "Pos").ok_or_else(|| anyhow!("{} without a position", name))?;
+        let id = self.spawn(entity_type, uuid_from_nbt(nbt), position);
+        let entity = self.get_mut(id).expect("Entity was just spawned");
+        entity.dimension = dimension;
+        entity.velocity = vec3_from_nbt(nbt, "Motion").unwrap_or(Vec3::ZERO);
+        entity.on_ground = nbt.get_bool("OnGround").unwrap_or_default();
+        let rotation = nbt.get_list("Rotation").and_then(|rotation| rotation.iter().map(NbtTag::as_f64).collect::<Option<Vec<_>>>());
+        if let Some(&[yaw, pitch]) = rotation.as_deref() {
+            entity.rotation = Rotation { yaw: yaw as f32, pitch: pitch as f32, head_yaw: yaw as f32 };
+        }
+        Ok(id)
+    }
+}
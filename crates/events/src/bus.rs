@@ -0,0 +1,146 @@
+use std::any::{type_name, Any, TypeId};
+use std::collections::HashMap;
+use std::future::Future;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use once_cell::sync::Lazy;
+use log::error;
+
+static EVENT_BUS: Lazy<EventBus> = Lazy::new(EventBus::default);
+
+/// The bus the server emits its events on.
+pub fn event_bus() -> &'static EventBus {
+    &EVENT_BUS
+}
+
+/// Something which happened in the server, given to the subscribers of its type.
+pub trait Event: Any + Clone + Send + Sync {
+    /// Whether a subscriber cancelled the event, the emitter then skips what the event announced.
+    fn is_cancelled(&self) -> bool {
+        false
+    }
+}
+
+/// Order synchronous subscribers run in, from [`Lowest`](EventPriority::Lowest) to
+/// [`Monitor`](EventPriority::Monitor). Later subscribers have the last word on the event, those with
+/// `Monitor` see its outcome and should not change it.
+#[derive(Debug, Default, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub enum EventPriority {
+    Lowest,
+    Low,
+    #[default]
+    Normal,
+    High,
+    Highest,
+    Monitor,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct SubscriptionId(u64);
+
+type BoxFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+type SyncHandler = Arc<dyn Fn(&mut dyn Any) + Send + Sync>;
+/// Starts the subscriber's task, `None` for events of another type.
+type AsyncHandler = Arc<dyn Fn(&dyn Any) -> Option<BoxFuture> + Send + Sync>;
+
+#[derive(Clone)]
+enum Handler {
+    Sync(SyncHandler),
+    Async(AsyncHandler),
+}
+
+struct Subscriber {
+    id: u64,
+    priority: EventPriority,
+    handler: Handler,
+}
+
+/// Dispatches events to the subscribers of their type.
+///
+/// Synchronous subscribers run on the emitting thread by priority, then in subscription order, and may
+/// change or cancel the event. Asynchronous subscribers get a copy of it afterwards, unless it
+/// was cancelled, and run as tasks of their own.
+#[derive(Default)]
+pub struct EventBus {
+    next_id: AtomicU64,
+    subscribers: RwLock<HashMap<TypeId, Vec<Subscriber>>>,
+}
+
+impl std::fmt::Debug for EventBus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let subscribers = self.subscribers.read().unwrap().values().map(Vec::len).sum::<usize>();
+        f.debug_struct("EventBus").field("subscribers", &subscribers).finish()
+    }
+}
+
+impl EventBus {
+    pub fn subscribe<E: Event>(&self, priority: EventPriority, handler: impl Fn(&mut E) + Send + Sync + 'static) -> SubscriptionId {
+        self.insert::<E>(priority, Handler::Sync(Arc::new(move |event: &mut dyn Any| {
+            if let Some(event) = event.downcast_mut::<E>() {
+                handler(event);
+            }
+        })))
+    }
+
+    /// Runs `handler` on a task of its own for every event of the type which was not cancelled, e.g. for
+    /// network requests or disk writes.
+    pub fn subscribe_async<E, F, Fut>(&self, handler: F) -> SubscriptionId
+    where
+        E: Event,
+        F: Fn(E) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.insert::<E>(EventPriority::Monitor, Handler::Async(Arc::new(move |event: &dyn Any| {
+            event.downcast_ref::<E>().map(|event| Box::pin(handler(event.clone())) as BoxFuture)
+        })))
+    }
+
+    /// Removes a subscriber, events being emitted may still reach it.
+    pub fn unsubscribe(&self, id: SubscriptionId) {
+        for subscribers in self.subscribers.write().unwrap().values_mut() {
+            subscribers.retain(|subscriber| subscriber.id != id.0);
+        }
+    }
+
+    /// Whether emitting events of the type reaches anyone, for events which are costly to build.
+    pub fn has_subscribers<E: Event>(&self) -> bool {
+        self.subscribers.read().unwrap().get(&TypeId::of::<E>()).is_some_and(|subscribers| !subscribers.is_empty())
+    }
+
+    /// Runs the subscribers of the event's type, the emitter checks [`Event::is_cancelled`] afterwards.
+    /// A panicking subscriber is logged and does not stop the others.
+    pub fn emit<E: Event>(&self, event: &mut E) {
+        let handlers = match self.subscribers.read().unwrap().get(&TypeId::of::<E>()) {
+            Some(subscribers) => subscribers.iter().map(|subscriber| subscriber.handler.clone()).collect::<Vec<_>>(),
+            None => return,
+        };
+        for handler in &handlers {
+            if let Handler::Sync(handler) = handler {
+                if catch_unwind(AssertUnwindSafe(|| handler(event))).is_err() {
+                    error!("A subscriber of {} panicked", type_name::<E>());
+                }
+            }
+        }
+        if event.is_cancelled() {
+            return;
+        }
+        for handler in &handlers {
+            if let Handler::Async(handler) = handler {
+                if let Some(task) = handler(event) {
+                    async_std::task::spawn(task);
+                }
+            }
+        }
+    }
+
+    fn insert<E: Event>(&self, priority: EventPriority, handler: Handler) -> SubscriptionId {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let mut subscribers = self.subscribers.write().unwrap();
+        let subscribers = subscribers.entry(TypeId::of::<E>()).or_default();
+        let index = subscribers.partition_point(|subscriber| subscriber.priority <= priority);
+        subscribers.insert(index, Subscriber { id, priority, handler });
+        SubscriptionId(id)
+    }
+}
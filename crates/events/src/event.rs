@@ -0,0 +1,86 @@
+use uuid::Uuid;
+use dolls_core::datatype::{BlockPos, Identifier};
+use crate::prelude::Event;
+
+/// A player entered Play, once the server sent it the world.
+#[derive(Debug, Clone)]
+pub struct PlayerJoinEvent {
+    pub connection_id: u64,
+    pub username: String,
+    pub uuid: Uuid,
+}
+
+impl Event for PlayerJoinEvent {}
+
+/// A player sent a chat message, before it is formatted and broadcast. Changing the message drops the
+/// signature of signed chat, cancelling the event drops the message.
+#[derive(Debug, Clone)]
+pub struct ChatEvent {
+    pub connection_id: u64,
+    pub username: String,
+    pub uuid: Uuid,
+    pub message: String,
+    pub cancelled: bool,
+}
+
+impl ChatEvent {
+    pub fn cancel(&mut self) {
+        self.cancelled = true;
+    }
+}
+
+impl Event for ChatEvent {
+    fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+}
+
+/// A player breaks a block, before it turns into air. Cancelling the event keeps the block, the client
+/// is sent it back.
+#[derive(Debug, Clone)]
+pub struct BlockBreakEvent {
+    pub connection_id: u64,
+    pub username: String,
+    pub position: BlockPos,
+    /// `None` for states the block registry does not know.
+    pub block: Option<Identifier>,
+    pub state_id: u16,
+    pub cancelled: bool,
+}
+
+impl BlockBreakEvent {
+    pub fn cancel(&mut self) {
+        self.cancelled = true;
+    }
+}
+
+impl Event for BlockBreakEvent {
+    fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+}
+
+/// A connection sent a packet the server handles, before it is processed. Subscribers may rewrite the
+/// payload, cancelling the event drops the packet. The network worker only builds the event while
+/// someone subscribed to it.
+#[derive(Debug, Clone)]
+pub struct PacketReceiveEvent {
+    pub connection_id: u64,
+    /// Name of the packet, e.g. `ChatMessage`.
+    pub packet: &'static str,
+    pub packet_id: u32,
+    pub payload: Vec<u8>,
+    pub cancelled: bool,
+}
+
+impl PacketReceiveEvent {
+    pub fn cancel(&mut self) {
+        self.cancelled = true;
+    }
+}
+
+impl Event for PacketReceiveEvent {
+    fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+}
@@ -0,0 +1,7 @@
+pub mod bus;
+pub mod event;
+
+pub mod prelude {
+    pub use crate::bus::*;
+    pub use crate::event::*;
+}
@@ -0,0 +1,68 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use async_std::channel::unbounded;
+use dolls_core::datatype::BlockPos;
+use dolls_events::prelude::*;
+
+fn block_break() -> BlockBreakEvent {
+    BlockBreakEvent {
+        connection_id: 1,
+        username: "Steve".to_string(),
+        position: BlockPos::new(0, 64, 0),
+        block: None,
+        state_id: 1,
+        cancelled: false,
+    }
+}
+
+#[test]
+fn subscribers_run_by_priority_then_subscription_order() {
+    let bus = EventBus::default();
+    let order = Arc::new(Mutex::new(Vec::new()));
+    for (priority, name) in [(EventPriority::Monitor, "monitor"), (EventPriority::High, "high"), (EventPriority::Lowest, "lowest"), (EventPriority::High, "high again")] {
+        let order = order.clone();
+        bus.subscribe(priority, move |_: &mut BlockBreakEvent| order.lock().unwrap().push(name));
+    }
+    bus.emit(&mut block_break());
+    assert_eq!(*order.lock().unwrap(), ["lowest", "high", "high again", "monitor"]);
+}
+
+#[test]
+fn later_subscribers_decide_on_cancellation() {
+    let bus = EventBus::default();
+    bus.subscribe(EventPriority::Low, BlockBreakEvent::cancel);
+    bus.subscribe(EventPriority::High, |event: &mut BlockBreakEvent| event.cancelled = false);
+    let mut event = block_break();
+    bus.emit(&mut event);
+    assert!(!event.is_cancelled());
+}
+
+#[test]
+fn cancelled_events_skip_async_subscribers() {
+    let bus = EventBus::default();
+    let (sender, receiver) = unbounded();
+    bus.subscribe_async(move |event: BlockBreakEvent| {
+        let sender = sender.clone();
+        async move { sender.send(event.position).await.unwrap() }
+    });
+    let cancel = bus.subscribe(EventPriority::Normal, BlockBreakEvent::cancel);
+    bus.emit(&mut block_break());
+    bus.unsubscribe(cancel);
+    bus.emit(&mut block_break());
+    async_std::task::block_on(async {
+        assert_eq!(receiver.recv().await.unwrap(), BlockPos::new(0, 64, 0));
+        assert!(async_std::future::timeout(Duration::from_millis(50), receiver.recv()).await.is_err());
+    });
+}
+
+#[test]
+fn panicking_subscribers_do_not_stop_the_others() {
+    let bus = EventBus::default();
+    bus.subscribe(EventPriority::Low, |_: &mut BlockBreakEvent| panic!("broken subscriber"));
+    bus.subscribe(EventPriority::Normal, BlockBreakEvent::cancel);
+    let mut event = block_break();
+    bus.emit(&mut event);
+    assert!(event.is_cancelled());
+    assert!(bus.has_subscribers::<BlockBreakEvent>());
+    assert!(!bus.has_subscribers::<ChatEvent>());
+}
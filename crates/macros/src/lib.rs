@@ -1,8 +1,8 @@
-
-
 use proc_macro::TokenStream;
-use quote::quote;
-use syn::{parse_macro_input, ItemFn, Expr};
+use quote::{format_ident, quote};
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Fields, ItemFn, Expr, Path, Token};
 
 #[proc_macro_attribute]
 pub fn packet_processor(attr: TokenStream, item: TokenStream) -> TokenStream {
@@ -12,13 +12,131 @@ pub fn packet_processor(attr: TokenStream, item: TokenStream) -> TokenStream {
     let func_name = &func.sig.ident;
 
     let expanded = quote! {
-        use crate::register_packet_processor;
-        use crate::prelude::PacketProcessorRegistration;
-        use crate::prelude::PacketProcessorFn;
-
         #func
 
-        register_packet_processor!(#packet_id, #func_name as PacketProcessorFn);
+        crate::register_packet_processor!(#packet_id, #func_name as crate::prelude::PacketProcessorFn);
+    };
+
+    TokenStream::from(expanded)
+}
+
+struct DispatchEntry {
+    packet_type: Path,
+    processor: Path,
+}
+
+impl Parse for DispatchEntry {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let packet_type = input.parse()?;
+        input.parse::<Token![=>]>()?;
+        let processor = input.parse()?;
+        Ok(Self { packet_type, processor })
+    }
+}
+
+/// Generates `dispatch_packet`, a single `match (state, id)` over the listed processors.
+///
+/// ```ignore
+/// dispatch_table! {
+///     PacketType::Handshake => crate::io::packet::handshake::handshake_packet,
+/// }
+/// ```
+#[proc_macro]
+pub fn dispatch_table(input: TokenStream) -> TokenStream {
+    let entries = parse_macro_input!(input with Punctuated::<DispatchEntry, Token![,]>::parse_terminated);
+
+    let keys = (0..entries.len()).map(|index| format_ident!("PACKET_KEY_{}", index)).collect::<Vec<_>>();
+    let packet_types = entries.iter().map(|entry| &entry.packet_type);
+    let processors = entries.iter().map(|entry| &entry.processor);
+
+    let expanded = quote! {
+        pub fn dispatch_packet(state: crate::prelude::ConnectionState, packet_id: u32) -> Option<crate::prelude::PacketProcessorFn> {
+            #(
+                const #keys: (crate::prelude::ConnectionState, u32) = (#packet_types.state(), #packet_types.id());
+            )*
+
+            match (state, packet_id) {
+                #(
+                    #keys => Some(#processors as crate::prelude::PacketProcessorFn),
+                )*
+                _ => None,
+            }
+        }
+    };
+
+    TokenStream::from(expanded)
+}
+
+fn derive_codec(input: DeriveInput, trait_name: &str) -> syn::Result<(DeriveInput, Fields)> {
+    let fields = match &input.data {
+        Data::Struct(data) => data.fields.clone(),
+        _ => return Err(syn::Error::new_spanned(&input.ident, format!("{} can only be derived for structs", trait_name))),
+    };
+    Ok((input, fields))
+}
+
+/// Encodes every field in declaration order.
+#[proc_macro_derive(Encode)]
+pub fn derive_encode(item: TokenStream) -> TokenStream {
+    let (mut input, fields) = match derive_codec(parse_macro_input!(item as DeriveInput), "Encode") {
+        Ok(parsed) => parsed,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    for param in input.generics.type_params_mut() {
+        param.bounds.push(parse_quote!(::dolls_core::datatype::Encode));
+    }
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let members = fields.members();
+
+    let expanded = quote! {
+        impl #impl_generics ::dolls_core::datatype::Encode for #name #ty_generics #where_clause {
+            fn encode(&self, writer: &mut impl ::std::io::Write) -> ::std::io::Result<()> {
+                let _ = &writer;
+                #(
+                    ::dolls_core::datatype::Encode::encode(&self.#members, writer)?;
+                )*
+                Ok(())
+            }
+        }
+    };
+
+    TokenStream::from(expanded)
+}
+
+/// Decodes every field in declaration order.
+#[proc_macro_derive(Decode)]
+pub fn derive_decode(item: TokenStream) -> TokenStream {
+    let (mut input, fields) = match derive_codec(parse_macro_input!(item as DeriveInput), "Decode") {
+        Ok(parsed) => parsed,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    for param in input.generics.type_params_mut() {
+        param.bounds.push(parse_quote!(::dolls_core::datatype::Decode));
+    }
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let members = fields.members();
+
+    let body = match fields {
+        Fields::Unit => quote! { { let _ = reader; Self } },
+        _ => quote! {
+            Self {
+                #(
+                    #members: ::dolls_core::datatype::Decode::decode(reader)?,
+                )*
+            }
+        },
+    };
+
+    let expanded = quote! {
+        impl #impl_generics ::dolls_core::datatype::Decode for #name #ty_generics #where_clause {
+            fn decode(reader: &mut impl ::std::io::Read) -> ::std::io::Result<Self> {
+                Ok(#body)
+            }
+        }
     };
 
     TokenStream::from(expanded)
@@ -0,0 +1,70 @@
+use std::hint::black_box;
+use futures_lite::future::block_on;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use dolls_network::prelude::{get_handler, init_packet_processors, read_varint, ConnectionState, PacketHandler, PacketType, RawPacket};
+
+/// Payload sizes around the default compression threshold of 256 bytes, up to a chunk sized packet.
+const PAYLOAD_SIZES: [usize; 4] = [16, 255, 4096, 65536];
+const THRESHOLDS: [(&str, Option<usize>); 2] = [("uncompressed", None), ("threshold_256", Some(256))];
+/// Frames read through one handler per iteration, as a connection reads them.
+const FRAMES_PER_READ: usize = 32;
+
+/// Repetitive like most game data, so that compression has something to do.
+fn payload(size: usize) -> Vec<u8> {
+    (0..size).map(|index| (index % 64) as u8).collect()
+}
+
+fn write_frame(packet: &RawPacket, threshold: Option<usize>) -> Vec<u8> {
+    let mut stream = Vec::new();
+    let mut handler = PacketHandler::new(&mut stream);
+    handler.set_compression(threshold);
+    block_on(handler.write_packet(packet)).unwrap();
+    stream
+}
+
+fn framing(c: &mut Criterion) {
+    for (name, threshold) in THRESHOLDS {
+        let mut group = c.benchmark_group(format!("framing/{}", name));
+        for size in PAYLOAD_SIZES {
+            let packet = RawPacket::new(0x27, payload(size));
+            let frames = write_frame(&packet, threshold).repeat(FRAMES_PER_READ);
+            group.throughput(Throughput::Bytes(size as u64));
+            group.bench_with_input(BenchmarkId::new("write", size), &packet, |b, packet| {
+                b.iter(|| write_frame(black_box(packet), threshold))
+            });
+            group.throughput(Throughput::Bytes((size * FRAMES_PER_READ) as u64));
+            group.bench_with_input(BenchmarkId::new("read", size), &frames, |b, frames| {
+                b.iter(|| {
+                    let mut handler = PacketHandler::new(black_box(frames.as_slice()));
+                    handler.set_compression(threshold);
+                    block_on(async {
+                        for _ in 0..FRAMES_PER_READ {
+                            black_box(handler.next_packet().await.unwrap());
+                        }
+                    })
+                })
+            });
+        }
+        group.finish();
+    }
+}
+
+fn frame_length(c: &mut Criterion) {
+    let bytes = [0xff, 0xff, 0x7f];
+    c.bench_function("read_varint/stream", |b| b.iter(|| block_on(read_varint(&mut black_box(&bytes[..]))).unwrap()));
+}
+
+fn dispatch(c: &mut Criterion) {
+    block_on(init_packet_processors()).unwrap();
+    let mut group = c.benchmark_group("dispatch");
+    for packet_type in [PacketType::Handshake, PacketType::KeepAlive, PacketType::SetPlayerPosition] {
+        group.bench_function(packet_type.name(), |b| {
+            b.iter(|| block_on(get_handler(black_box(packet_type.state()), black_box(packet_type.id()))).unwrap())
+        });
+    }
+    group.bench_function("unknown", |b| b.iter(|| block_on(get_handler(black_box(ConnectionState::Play), black_box(0xFF)))));
+    group.finish();
+}
+
+criterion_group!(benches, framing, frame_length, dispatch);
+criterion_main!(benches);
@@ -0,0 +1,128 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use async_std::io::{ReadExt, WriteExt};
+use async_std::net::TcpStream;
+use async_std::task;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use dolls_config::IoBackend;
+use dolls_network::prelude::{backend_support, bind_listener, Listener, PacketHandler, RawPacket, Stream};
+
+/// Concurrent connections, from a single player up to a crowded server.
+const CONNECTIONS: [usize; 3] = [1, 64, 1024];
+/// Frames each connection carries per iteration, about what a player moving around sends in a second.
+const FRAMES: usize = 32;
+const PAYLOAD_SIZE: usize = 64;
+const BACKENDS: [IoBackend; 2] = [IoBackend::AsyncStd, IoBackend::IoUring];
+
+/// The frames a connection carries per iteration, as they are on the wire.
+fn frames() -> Arc<Vec<u8>> {
+    let mut stream = Vec::new();
+    let mut handler = PacketHandler::new(&mut stream);
+    let packet = RawPacket::new(0x1A, vec![0x2A; PAYLOAD_SIZE]);
+    task::block_on(async {
+        for _ in 0..FRAMES {
+            handler.write_packet(&packet).await.unwrap();
+        }
+    });
+    Arc::new(stream)
+}
+
+/// Pairs of a client and the server side stream the backend accepted for it.
+async fn connect(backend: IoBackend, connections: usize) -> Vec<(TcpStream, Stream)> {
+    let mut listener = Listener::new(bind_listener("127.0.0.1:0".parse().unwrap()).await.unwrap(), backend);
+    let address = listener.local_addr().unwrap();
+    let mut pairs = Vec::with_capacity(connections);
+    for _ in 0..connections {
+        let client = TcpStream::connect(address).await.unwrap();
+        client.set_nodelay(true).unwrap();
+        let server = listener.accept().await.unwrap();
+        server.set_nodelay(true).unwrap();
+        pairs.push((client, server));
+    }
+    pairs
+}
+
+/// Every client sends its frames, which the server reads through a packet handler per connection.
+async fn inbound(backend: IoBackend, connections: usize, iterations: u64) -> Duration {
+    let pairs = connect(backend, connections).await;
+    let frames = frames();
+    let start = Instant::now();
+    let tasks: Vec<_> = pairs.into_iter().map(|(mut client, server)| {
+        let frames = frames.clone();
+        task::spawn(async move {
+            let writer = task::spawn(async move {
+                for _ in 0..iterations {
+                    client.write_all(&frames).await.unwrap();
+                }
+                client
+            });
+            let mut handler = PacketHandler::new(server);
+            for _ in 0..iterations as usize * FRAMES {
+                handler.next_packet().await.unwrap();
+            }
+            writer.await;
+        })
+    }).collect();
+    for task in tasks {
+        task.await;
+    }
+    start.elapsed()
+}
+
+/// The server writes frames through a packet handler per connection, which every client reads.
+async fn outbound(backend: IoBackend, connections: usize, iterations: u64) -> Duration {
+    let pairs = connect(backend, connections).await;
+    let packet = Arc::new(RawPacket::new(0x1A, vec![0x2A; PAYLOAD_SIZE]));
+    let length = frames().len() * iterations as usize;
+    let start = Instant::now();
+    let tasks: Vec<_> = pairs.into_iter().map(|(mut client, server)| {
+        let packet = packet.clone();
+        task::spawn(async move {
+            let writer = task::spawn(async move {
+                let mut handler = PacketHandler::new(server);
+                for _ in 0..iterations as usize * FRAMES {
+                    handler.write_packet(&packet).await.unwrap();
+                }
+                handler
+            });
+            let mut received = vec![0; length];
+            client.read_exact(&mut received).await.unwrap();
+            writer.await;
+        })
+    }).collect();
+    for task in tasks {
+        task.await;
+    }
+    start.elapsed()
+}
+
+fn transport(c: &mut Criterion) {
+    let backends: Vec<_> = BACKENDS.into_iter()
+        .filter(|backend| match backend_support(*backend) {
+            Ok(()) => true,
+            Err(reason) => {
+                eprintln!("Skipping the {:?} backend: {}", backend, reason);
+                false
+            }
+        })
+        .collect();
+    for name in ["inbound", "outbound"] {
+        let mut group = c.benchmark_group(format!("transport/{}", name));
+        group.sample_size(20);
+        for connections in CONNECTIONS {
+            group.throughput(Throughput::Elements((connections * FRAMES) as u64));
+            for backend in &backends {
+                group.bench_with_input(BenchmarkId::new(format!("{:?}", backend), connections), &connections, |b, &connections| {
+                    b.iter_custom(|iterations| match name {
+                        "inbound" => task::block_on(inbound(*backend, connections, iterations)),
+                        _ => task::block_on(outbound(*backend, connections, iterations)),
+                    })
+                });
+            }
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, transport);
+criterion_main!(benches);
@@ -0,0 +1,38 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Collects every `#[packet_processor(...)]` of the crate into the table `static-dispatch` builds its `match`
+/// from. Processors are named by the path the `io` module re-exports them under.
+fn main() {
+    let mut entries = Vec::new();
+    collect(Path::new("src"), &mut entries);
+    let table = entries.iter()
+        .map(|(packet_type, processor)| format!("    {} => crate::io::{},\n", packet_type, processor))
+        .collect::<String>();
+    let out = PathBuf::from(std::env::var("OUT_DIR").unwrap()).join("dispatch_table.rs");
+    fs::write(out, format!("dispatch_table! {{\n{}}}\n", table)).unwrap();
+    println!("cargo:rerun-if-changed=src");
+}
+
+fn collect(directory: &Path, entries: &mut Vec<(String, String)>) {
+    let mut paths = fs::read_dir(directory).unwrap().map(|entry| entry.unwrap().path()).collect::<Vec<_>>();
+    paths.sort();
+    for path in paths {
+        if path.is_dir() {
+            collect(&path, entries);
+            continue;
+        }
+        if path.extension().is_none_or(|extension| extension != "rs") {
+            continue;
+        }
+        let source = fs::read_to_string(&path).unwrap();
+        let mut lines = source.lines().map(str::trim);
+        while let Some(line) = lines.next() {
+            let Some(packet_type) = line.strip_prefix("#[packet_processor(").and_then(|rest| rest.strip_suffix(")]")) else { continue };
+            let processor = lines.find_map(|line| line.split_once("fn "))
+                .and_then(|(_, signature)| signature.split(['(', '<']).next())
+                .unwrap_or_else(|| panic!("No function follows #[packet_processor({})] in {}", packet_type, path.display()));
+            entries.push((packet_type.to_string(), processor.trim().to_string()));
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use std::fs;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use anyhow::Context;
+use once_cell::sync::Lazy;
+use serde_json::{Map, Value};
+use dolls_core::datatype::Uuid;
+use dolls_core::date::{format_date, now_millis, parse_date};
+use dolls_core::text::TextComponent;
+
+/// Banned players file in the working directory, where vanilla keeps it.
+pub const BANNED_PLAYERS_FILE: &str = "banned-players.json";
+/// Banned IP addresses file in the working directory, where vanilla keeps it.
+pub const BANNED_IPS_FILE: &str = "banned-ips.json";
+/// Who vanilla records as the source of bans it cannot tell the source of.
+const UNKNOWN_BAN_SOURCE: &str = "(Unknown)";
+/// Reason vanilla gives bans without one.
+const DEFAULT_BAN_REASON: &str = "Banned by an operator.";
+/// `expires` of bans which never do.
+const NEVER_EXPIRES: &str = "forever";
+
+/// What a ban list holds, a player or an IP address.
+pub trait BanTarget: Clone {
+    /// What bans of the same target have in common.
+    type Key: PartialEq + Copy;
+
+    fn key(&self) -> Self::Key;
+
+    fn read(entry: &Value) -> Option<Self>;
+
+    fn write(&self, entry: &mut Map<String, Value>);
+}
+
+/// A banned player, recognized by its UUID.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BannedPlayer {
+    pub uuid: Uuid,
+    pub name: String,
+}
+
+impl BanTarget for BannedPlayer {
+    type Key = Uuid;
+
+    fn key(&self) -> Uuid {
+        self.uuid
+    }
+
+    fn read(entry: &Value) -> Option<Self> {
+        Some(Self {
+            uuid: entry.get("uuid")?.as_str()?.parse().ok()?,
+            name: entry.get("name").and_then(Value::as_str).unwrap_or_default().to_string(),
+        })
+    }
+
+    fn write(&self, entry: &mut Map<String, Value>) {
+        entry.insert("uuid".to_string(), Value::from(self.uuid.hyphenated().to_string()));
+        entry.insert("name".to_string(), Value::from(self.name.clone()));
+    }
+}
+
+impl BanTarget for IpAddr {
+    type Key = IpAddr;
+
+    fn key(&self) -> IpAddr {
+        *self
+    }
+
+    fn read(entry: &Value) -> Option<Self> {
+        entry.get("ip")?.as_str()?.parse().ok()
+    }
+
+    fn write(&self, entry: &mut Map<String, Value>) {
+        entry.insert("ip".to_string(), Value::from(self.to_string()));
+    }
+}
+
+/// A ban with the times in milliseconds since the epoch.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BanEntry<T> {
+    pub target: T,
+    pub created: i64,
+    /// Who banned the target, a player name or `Server` for the console.
+    pub source: String,
+    /// When the ban is lifted, `None` for permanent bans.
+    pub expires: Option<i64>,
+    pub reason: String,
+}
+
+impl<T> BanEntry<T> {
+    /// A permanent ban starting now.
+    pub fn new(target: T, source: impl Into<String>, reason: Option<String>) -> Self {
+        Self {
+            target,
+            created: now_millis(),
+            source: source.into(),
+            expires: None,
+            reason: reason.unwrap_or_else(|| DEFAULT_BAN_REASON.to_string()),
+        }
+    }
+
+    pub fn has_expired(&self) -> bool {
+        self.expires.is_some_and(|expires| expires <= now_millis())
+    }
+}
+
+/// Bans kept in `banned-players.json` or `banned-ips.json` in vanilla's format. Expired bans are
+/// ignored and dropped when the list is saved again.
+#[derive(Debug, Clone)]
+pub struct BanList<T> {
+    path: Option<PathBuf>,
+    entries: Vec<BanEntry<T>>,
+}
+
+impl<T> Default for BanList<T> {
+    fn default() -> Self {
+        Self { path: None, entries: Vec::new() }
+    }
+}
+
+impl<T: BanTarget> BanList<T> {
+    /// Reads the list, an empty one if the file does not exist yet. Malformed entries are skipped.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let mut list = Self { path: Some(path.to_path_buf()), entries: Vec::new() };
+        if !path.exists() {
+            return Ok(list);
+        }
+        let content = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let json: Value = serde_json::from_str(&content).with_context(|| format!("Malformed {}", path.display()))?;
+        list.entries = json.as_array().into_iter().flatten()
+            .filter_map(|entry| Some(BanEntry {
+                target: T::read(entry)?,
+                created: entry.get("created").and_then(Value::as_str).and_then(parse_date).unwrap_or_else(now_millis),
+                source: entry.get("source").and_then(Value::as_str).unwrap_or(UNKNOWN_BAN_SOURCE).to_string(),
+                expires: match entry.get("expires").and_then(Value::as_str) {
+                    None | Some(NEVER_EXPIRES) => None,
+                    Some(date) => Some(parse_date(date)?),
+                },
+                reason: entry.get("reason").and_then(Value::as_str).unwrap_or(DEFAULT_BAN_REASON).to_string(),
+            }))
+            .collect();
+        Ok(list)
+    }
+
+    /// Writes the list back to the file it was loaded from, lists never loaded are not saved.
+    pub fn save(&self) -> anyhow::Result<()> {
+        let Some(path) = &self.path else { return Ok(()) };
+        let entries = self.iter()
+            .map(|ban| {
+                let mut entry = Map::new();
+                ban.target.write(&mut entry);
+                entry.insert("created".to_string(), Value::from(format_date(ban.created)));
+                entry.insert("source".to_string(), Value::from(ban.source.clone()));
+                entry.insert("expires".to_string(), Value::from(ban.expires.map_or_else(|| NEVER_EXPIRES.to_string(), format_date)));
+                entry.insert("reason".to_string(), Value::from(ban.reason.clone()));
+                Value::Object(entry)
+            })
+            .collect::<Vec<_>>();
+        fs::write(path, serde_json::to_string_pretty(&entries)?).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// The ban in force against a target.
+    pub fn get(&self, key: T::Key) -> Option<&BanEntry<T>> {
+        self.iter().find(|ban| ban.target.key() == key)
+    }
+
+    /// Bans in force.
+    pub fn iter(&self) -> impl Iterator<Item = &BanEntry<T>> {
+        self.entries.iter().filter(|ban| !ban.has_expired())
+    }
+
+    /// Adds or replaces a ban, returns whether the target was not banned before.
+    pub fn add(&mut self, ban: BanEntry<T>) -> bool {
+        let added = self.remove(ban.target.key()).is_none();
+        self.entries.push(ban);
+        added
+    }
+
+    /// Lifts the ban in force against a target.
+    pub fn remove(&mut self, key: T::Key) -> Option<BanEntry<T>> {
+        self.entries.retain(|ban| !ban.has_expired());
+        let index = self.entries.iter().position(|ban| ban.target.key() == key)?;
+        Some(self.entries.remove(index))
+    }
+}
+
+static BANNED_PLAYERS: Lazy<RwLock<BanList<BannedPlayer>>> = Lazy::new(|| RwLock::new(BanList::default()));
+static BANNED_IPS: Lazy<RwLock<BanList<IpAddr>>> = Lazy::new(|| RwLock::new(BanList::default()));
+
+/// Banned players of the running server, loaded from `banned-players.json` at startup.
+pub fn banned_players() -> &'static RwLock<BanList<BannedPlayer>> {
+    &BANNED_PLAYERS
+}
+
+/// Banned IP addresses of the running server, loaded from `banned-ips.json` at startup.
+pub fn banned_ips() -> &'static RwLock<BanList<IpAddr>> {
+    &BANNED_IPS
+}
+
+/// Disconnect reason of a banned player, with the end of temporary bans.
+pub fn banned_reason<T>(ban: &BanEntry<T>) -> TextComponent {
+    let reason = TextComponent::translatable("multiplayer.disconnect.banned.reason", vec![TextComponent::text(ban.reason.clone())])
+        .fallback("You are banned from this server.\nReason: %s");
+    append_expiration(reason, "multiplayer.disconnect.banned.expiration", ban)
+}
+
+/// Disconnect reason of a connection from a banned IP address, with the end of temporary bans.
+pub fn banned_ip_reason<T>(ban: &BanEntry<T>) -> TextComponent {
+    let reason = TextComponent::translatable("multiplayer.disconnect.banned_ip.reason", vec![TextComponent::text(ban.reason.clone())])
+        .fallback("Your IP address is banned from this server.\nReason: %s");
+    append_expiration(reason, "multiplayer.disconnect.banned_ip.expiration", ban)
+}
+
+fn append_expiration<T>(reason: TextComponent, key: &str, ban: &BanEntry<T>) -> TextComponent {
+    match ban.expires {
+        Some(expires) => reason.append(TextComponent::translatable(key, vec![TextComponent::text(format_date(expires))])
+            .fallback("\nYour ban will be removed on %s")),
+        None => reason,
+    }
+}
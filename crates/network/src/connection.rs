@@ -0,0 +1,213 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use async_std::channel::Sender;
+use dolls_core::datatype::Uuid;
+use dolls_core::text::TextComponent;
+use crate::prelude::{capture_packet, disconnect_packet, dump_packet, record_packet, ChatVisibility, ClientInformation, ClientboundPacket, ConnectionState, FrameDirection, Outbound, RawPacket};
+
+/// What other tasks may know about a connection, refreshed after every processed packet.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionInfo {
+    pub state: ConnectionState,
+    pub username: Option<String>,
+    pub uuid: Option<Uuid>,
+    /// Entity id of the player, assigned when entering Play.
+    pub entity_id: Option<i32>,
+    pub client_information: Option<ClientInformation>,
+}
+
+/// Typed per-connection state which subsystems attach to a connection, one value per type.
+#[derive(Default)]
+pub struct Extensions(HashMap<TypeId, Box<dyn Any + Send + Sync>>);
+
+impl Extensions {
+    pub fn insert<T: Any + Send + Sync>(&mut self, value: T) -> Option<T> {
+        self.0.insert(TypeId::of::<T>(), Box::new(value)).and_then(|old| old.downcast().ok().map(|old| *old))
+    }
+
+    pub fn get<T: Any + Send + Sync>(&self) -> Option<&T> {
+        self.0.get(&TypeId::of::<T>()).and_then(|value| value.downcast_ref())
+    }
+
+    pub fn get_mut<T: Any + Send + Sync>(&mut self) -> Option<&mut T> {
+        self.0.get_mut(&TypeId::of::<T>()).and_then(|value| value.downcast_mut())
+    }
+
+    pub fn get_or_default<T: Any + Send + Sync + Default>(&mut self) -> &mut T {
+        self.0.entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(T::default()))
+            .downcast_mut()
+            .expect("Extension stored under the wrong type")
+    }
+
+    pub fn remove<T: Any + Send + Sync>(&mut self) -> Option<T> {
+        self.0.remove(&TypeId::of::<T>()).and_then(|value| value.downcast().ok().map(|value| *value))
+    }
+}
+
+impl Debug for Extensions {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Extensions").field("len", &self.0.len()).finish()
+    }
+}
+
+/// Cheap, cloneable reference to a live connection which can send it packets from any task.
+#[derive(Debug, Clone)]
+pub struct ConnectionHandle {
+    id: u64,
+    peer_addr: SocketAddr,
+    sender: Sender<Outbound>,
+    info: Arc<RwLock<ConnectionInfo>>,
+    extensions: Arc<Mutex<Extensions>>,
+}
+
+impl ConnectionHandle {
+    pub fn new(id: u64, peer_addr: SocketAddr, sender: Sender<Outbound>) -> Self {
+        Self {
+            id,
+            peer_addr,
+            sender,
+            info: Arc::new(RwLock::new(ConnectionInfo::default())),
+            extensions: Arc::new(Mutex::new(Extensions::default())),
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn peer_addr(&self) -> SocketAddr {
+        self.peer_addr
+    }
+
+    pub fn info(&self) -> ConnectionInfo {
+        self.info.read().unwrap().clone()
+    }
+
+    pub fn state(&self) -> ConnectionState {
+        self.info.read().unwrap().state
+    }
+
+    pub fn username(&self) -> Option<String> {
+        self.info.read().unwrap().username.clone()
+    }
+
+    pub fn uuid(&self) -> Option<Uuid> {
+        self.info.read().unwrap().uuid
+    }
+
+    pub fn entity_id(&self) -> Option<i32> {
+        self.info.read().unwrap().entity_id
+    }
+
+    /// What the client wants to see in chat, everything until it told otherwise.
+    pub fn chat_visibility(&self) -> ChatVisibility {
+        self.info.read().unwrap().client_information.as_ref()
+            .map(ClientInformation::chat_visibility)
+            .unwrap_or_default()
+    }
+
+    /// Runs `f` with the connection's extensions locked, keep it short.
+    pub fn extensions<R>(&self, f: impl FnOnce(&mut Extensions) -> R) -> R {
+        f(&mut self.extensions.lock().unwrap())
+    }
+
+    pub(crate) fn set_info(&self, info: ConnectionInfo) {
+        *self.info.write().unwrap() = info;
+    }
+
+    pub fn send<T: ClientboundPacket>(&self, packet: &T) -> anyhow::Result<()> {
+        self.send_raw(RawPacket::from_packet(packet)?)
+    }
+
+    pub fn send_raw(&self, packet: RawPacket) -> anyhow::Result<()> {
+        self.send_outbound(Outbound::Packet(packet))
+    }
+
+    /// Fails once the connection is closed.
+    pub fn send_outbound(&self, outbound: Outbound) -> anyhow::Result<()> {
+        if let Outbound::Packet(packet) = &outbound {
+            let state = self.state();
+            record_packet(FrameDirection::Outbound, state, packet);
+            dump_packet(self.id, FrameDirection::Outbound, state, packet);
+            capture_packet(self, FrameDirection::Outbound, state, packet);
+        }
+        self.sender.try_send(outbound).map_err(|err| anyhow::anyhow!("Connection {} is closed: {}", self.id, err))
+    }
+
+    /// Tells the client why it is disconnected with the Disconnect packet of its state, then closes
+    /// the connection once what is already queued is written. Packet processors use
+    /// [`PacketContext::disconnect`](crate::prelude::PacketContext::disconnect) instead.
+    pub fn disconnect(&self, reason: TextComponent) -> anyhow::Result<()> {
+        if let Some(packet) = disconnect_packet(self.state(), reason)? {
+            self.send_raw(packet)?;
+        }
+        self.send_outbound(Outbound::Close)
+    }
+}
+
+/// All live connections of a server.
+#[derive(Debug, Default)]
+pub struct ConnectionRegistry {
+    next_id: AtomicU64,
+    connections: RwLock<HashMap<u64, ConnectionHandle>>,
+}
+
+impl ConnectionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn register(&self, peer_addr: SocketAddr, sender: Sender<Outbound>) -> ConnectionHandle {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let handle = ConnectionHandle::new(id, peer_addr, sender);
+        self.connections.write().unwrap().insert(id, handle.clone());
+        handle
+    }
+
+    pub(crate) fn unregister(&self, id: u64) {
+        self.connections.write().unwrap().remove(&id);
+    }
+
+    pub fn get(&self, id: u64) -> Option<ConnectionHandle> {
+        self.connections.read().unwrap().get(&id).cloned()
+    }
+
+    pub fn all(&self) -> Vec<ConnectionHandle> {
+        self.connections.read().unwrap().values().cloned().collect()
+    }
+
+    /// Connections which finished Configuration, i.e. players in the world.
+    pub fn players(&self) -> Vec<ConnectionHandle> {
+        self.connections.read().unwrap().values()
+            .filter(|handle| handle.state() == ConnectionState::Play)
+            .cloned()
+            .collect()
+    }
+
+    pub fn find_player(&self, username: &str) -> Option<ConnectionHandle> {
+        self.players().into_iter()
+            .find(|handle| handle.username().is_some_and(|name| name.eq_ignore_ascii_case(username)))
+    }
+
+    pub fn len(&self) -> usize {
+        self.connections.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.connections.read().unwrap().is_empty()
+    }
+
+    /// Sends a packet to every player, connections closing meanwhile are skipped.
+    pub fn broadcast<T: ClientboundPacket>(&self, packet: &T) -> anyhow::Result<()> {
+        let packet = RawPacket::from_packet(packet)?;
+        for player in self.players() {
+            let _ = player.send_raw(RawPacket::new(packet.packet_id, packet.payload.clone()));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,147 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use anyhow::{anyhow, bail, Context};
+use async_std::io::{BufReader, WriteExt};
+use async_std::net::TcpStream;
+use async_std::prelude::*;
+use serde_json::json;
+use log::{debug, warn};
+use dolls_config::ServerConfig;
+use crate::prelude::{schedule_repeating, server_status, ConnectionRegistry, RepeatingTask};
+
+/// Wait before the first retry of a failed heartbeat, doubled for each further one.
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// An `http://` URL heartbeats are posted to, TLS is not supported.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct HeartbeatEndpoint {
+    pub host: String,
+    pub port: u16,
+    pub path: String,
+}
+
+impl FromStr for HeartbeatEndpoint {
+    type Err = anyhow::Error;
+
+    fn from_str(url: &str) -> anyhow::Result<Self> {
+        let rest = match url.split_once("://") {
+            Some(("http", rest)) => rest,
+            Some((scheme, _)) => bail!("Unsupported scheme {}, only http is supported", scheme),
+            None => bail!("{} is not a URL", url),
+        };
+        let (authority, path) = match rest.find('/') {
+            Some(index) => rest.split_at(index),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            // A colon inside brackets belongs to an IPv6 address.
+            Some((host, port)) if !port.ends_with(']') => (host, port.parse().with_context(|| format!("Invalid port in {}", url))?),
+            _ => (authority, 80),
+        };
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if host.is_empty() {
+            bail!("{} has no host", url);
+        }
+        Ok(Self { host: host.to_string(), port, path: path.to_string() })
+    }
+}
+
+impl Display for HeartbeatEndpoint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.host.contains(':') {
+            true => write!(f, "http://[{}]:{}{}", self.host, self.port, self.path),
+            false => write!(f, "http://{}:{}{}", self.host, self.port, self.path),
+        }
+    }
+}
+
+/// What is reported to server lists: version, player count and MOTD.
+pub fn heartbeat_json(config: &ServerConfig, connections: &ConnectionRegistry) -> String {
+    let status = server_status(config, connections);
+    json!({
+        "version": { "name": status.version_name, "protocol": status.protocol },
+        "players": { "max": status.max_players, "online": status.online_players },
+        "motd": status.description.to_plain_text(),
+        "port": config.network.port,
+        "online_mode": config.server.online_mode,
+    }).to_string()
+}
+
+/// POSTs `body` as JSON and returns the status code of the response.
+pub async fn post_json(endpoint: &HeartbeatEndpoint, body: &str, timeout: Duration) -> anyhow::Result<u16> {
+    let request = async {
+        let mut stream = TcpStream::connect((endpoint.host.as_str(), endpoint.port)).await?;
+        let host = match endpoint.host.contains(':') {
+            true => format!("[{}]", endpoint.host),
+            false => endpoint.host.clone(),
+        };
+        let head = format!(
+            "POST {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: Dolls/{}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            endpoint.path, host, env!("CARGO_PKG_VERSION"), body.len(),
+        );
+        stream.write_all(head.as_bytes()).await?;
+        stream.write_all(body.as_bytes()).await?;
+        stream.flush().await?;
+        let mut status_line = String::new();
+        BufReader::new(stream).read_line(&mut status_line).await?;
+        Ok::<_, std::io::Error>(status_line)
+    };
+    let status_line = async_std::io::timeout(timeout, request).await?;
+    status_line.split_whitespace().nth(1)
+        .filter(|_| status_line.starts_with("HTTP/"))
+        .and_then(|status| status.parse().ok())
+        .ok_or_else(|| anyhow!("Malformed response {:?}", status_line.trim_end()))
+}
+
+/// Posts one heartbeat, retrying failures with a growing delay.
+async fn send_heartbeat(endpoint: &HeartbeatEndpoint, body: &str, config: &ServerConfig) -> anyhow::Result<()> {
+    let timeout = Duration::from_secs(config.heartbeat.timeout);
+    let mut delay = RETRY_DELAY;
+    let mut attempt = 0;
+    loop {
+        let result = match post_json(endpoint, body, timeout).await {
+            Ok(status) if (200..300).contains(&status) => return Ok(()),
+            Ok(status) => Err(anyhow!("Server answered with status {}", status)),
+            Err(err) => Err(err),
+        };
+        if attempt >= config.heartbeat.max_retries {
+            return result;
+        }
+        attempt += 1;
+        debug!("Heartbeat to {} failed, retrying in {:?}: {:#}", endpoint, delay, result.unwrap_err());
+        async_std::task::sleep(delay).await;
+        delay *= 2;
+    }
+}
+
+/// Posts the server's status to every configured endpoint each interval, `None` without valid endpoints.
+pub fn start_heartbeat(config: Arc<ServerConfig>, connections: Arc<ConnectionRegistry>) -> Option<RepeatingTask> {
+    let endpoints = config.heartbeat.endpoints.iter()
+        .filter_map(|url| match url.parse::<HeartbeatEndpoint>() {
+            Ok(endpoint) => Some(endpoint),
+            Err(err) => {
+                warn!("Ignoring heartbeat endpoint {}: {:#}", url, err);
+                None
+            }
+        })
+        .collect::<Arc<[_]>>();
+    if endpoints.is_empty() {
+        return None;
+    }
+
+    let interval = Duration::from_secs(config.heartbeat.interval.max(1));
+    Some(schedule_repeating("Heartbeat", interval, move || {
+        let (config, connections, endpoints) = (config.clone(), connections.clone(), endpoints.clone());
+        async move {
+            let body = heartbeat_json(&config, &connections);
+            for endpoint in endpoints.iter() {
+                match send_heartbeat(endpoint, &body, &config).await {
+                    Ok(()) => debug!("Sent heartbeat to {}", endpoint),
+                    Err(err) => warn!("Failed to send heartbeat to {}: {:#}", endpoint, err),
+                }
+            }
+        }
+    }))
+}
@@ -1,5 +1,13 @@
+mod capture;
+mod dump;
 mod packet;
 mod parser;
+mod replay;
+mod trace;
 
+pub use capture::*;
+pub use dump::*;
 pub use packet::*;
 pub use parser::*;
+pub use replay::*;
+pub use trace::*;
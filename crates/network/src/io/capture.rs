@@ -0,0 +1,158 @@
+use std::fs::{create_dir_all, File};
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use anyhow::{bail, Context};
+use log::error;
+use crate::prelude::{ConnectionHandle, ConnectionState, FrameDirection, RawPacket};
+
+const CAPTURE_MAGIC: &[u8; 4] = b"DCAP";
+const CAPTURE_VERSION: u8 = 1;
+/// Extension of the files written to `network.capture-directory`.
+pub const CAPTURE_EXTENSION: &str = "dcap";
+const STATES: [ConnectionState; 5] = [
+    ConnectionState::Handshaking,
+    ConnectionState::Status,
+    ConnectionState::Login,
+    ConnectionState::Configuration,
+    ConnectionState::Play,
+];
+
+/// Records the packets of one connection, attached to it as an extension.
+///
+/// A capture starts with `DCAP`, a version byte, the start time in milliseconds since the Unix epoch,
+/// the connection id and the peer address as a length prefixed string. Each packet follows as the
+/// microseconds since the start, the direction (0 in, 1 out), the state, the packet id, the payload
+/// length and the payload, uncompressed. Numbers are big-endian.
+#[derive(Debug)]
+pub struct PacketCapture {
+    path: PathBuf,
+    file: BufWriter<File>,
+    started: Instant,
+}
+
+impl PacketCapture {
+    /// Creates `<started at>-<connection id>.dcap` in `directory`.
+    pub fn create(directory: &Path, connection_id: u64, peer_addr: SocketAddr) -> anyhow::Result<Self> {
+        create_dir_all(directory).with_context(|| format!("Failed to create {}", directory.display()))?;
+        let started_at = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+        let path = directory.join(format!("{}-{}.{}", started_at, connection_id, CAPTURE_EXTENSION));
+        let file = File::create(&path).with_context(|| format!("Failed to create capture {}", path.display()))?;
+        let mut file = BufWriter::new(file);
+        let peer_addr = peer_addr.to_string();
+        file.write_all(CAPTURE_MAGIC)?;
+        file.write_all(&[CAPTURE_VERSION])?;
+        file.write_all(&started_at.to_be_bytes())?;
+        file.write_all(&connection_id.to_be_bytes())?;
+        file.write_all(&[peer_addr.len() as u8])?;
+        file.write_all(peer_addr.as_bytes())?;
+        Ok(Self { path, file, started: Instant::now() })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn record(&mut self, direction: FrameDirection, state: ConnectionState, packet: &RawPacket) -> std::io::Result<()> {
+        let elapsed = self.started.elapsed().as_micros() as u64;
+        let state = STATES.iter().position(|known| *known == state).unwrap_or_default() as u8;
+        self.file.write_all(&elapsed.to_be_bytes())?;
+        self.file.write_all(&[direction as u8, state])?;
+        self.file.write_all(&packet.packet_id.to_be_bytes())?;
+        self.file.write_all(&(packet.payload.len() as u32).to_be_bytes())?;
+        self.file.write_all(&packet.payload)
+    }
+
+    pub fn finish(mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Appends the packet to the capture of the connection, if it has one. A capture which fails to write
+/// is dropped.
+pub fn capture_packet(connection: &ConnectionHandle, direction: FrameDirection, state: ConnectionState, packet: &RawPacket) {
+    connection.extensions(|extensions| {
+        let Some(capture) = extensions.get_mut::<PacketCapture>() else {
+            return;
+        };
+        if let Err(err) = capture.record(direction, state, packet) {
+            error!("Failed to write capture {}, stopping it: {}", capture.path().display(), err);
+            extensions.remove::<PacketCapture>();
+        }
+    });
+}
+
+/// One packet of a capture.
+#[derive(Debug, Clone)]
+pub struct CapturedPacket {
+    /// Time since the capture started.
+    pub elapsed: Duration,
+    pub direction: FrameDirection,
+    pub state: ConnectionState,
+    pub packet: RawPacket,
+}
+
+/// A capture read back from its file.
+#[derive(Debug, Clone)]
+pub struct Capture {
+    /// Milliseconds since the Unix epoch.
+    pub started_at: u64,
+    pub connection_id: u64,
+    pub peer_addr: String,
+    pub packets: Vec<CapturedPacket>,
+}
+
+impl Capture {
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).with_context(|| format!("Failed to open capture {}", path.display()))?;
+        Self::read(BufReader::new(file)).with_context(|| format!("Failed to read capture {}", path.display()))
+    }
+
+    pub fn read(mut reader: impl Read) -> anyhow::Result<Self> {
+        let mut magic = [0; 5];
+        reader.read_exact(&mut magic)?;
+        if &magic[..4] != CAPTURE_MAGIC {
+            bail!("Not a packet capture");
+        }
+        if magic[4] != CAPTURE_VERSION {
+            bail!("Unsupported capture version {}", magic[4]);
+        }
+        let started_at = u64::from_be_bytes(read_array(&mut reader)?);
+        let connection_id = u64::from_be_bytes(read_array(&mut reader)?);
+        let [length] = read_array(&mut reader)?;
+        let mut peer_addr = vec![0; length as usize];
+        reader.read_exact(&mut peer_addr)?;
+        let mut packets = Vec::new();
+        loop {
+            match read_packet(&mut reader) {
+                Ok(packet) => packets.push(packet),
+                // A capture cut short by a crash keeps the packets written before.
+                Err(err) if err.kind() == ErrorKind::UnexpectedEof => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(Self { started_at, connection_id, peer_addr: String::from_utf8_lossy(&peer_addr).into_owned(), packets })
+    }
+}
+
+fn read_packet(reader: &mut impl Read) -> std::io::Result<CapturedPacket> {
+    let elapsed = u64::from_be_bytes(read_array(reader)?);
+    let [direction, state] = read_array(reader)?;
+    let packet_id = u32::from_be_bytes(read_array(reader)?);
+    let mut payload = vec![0; u32::from_be_bytes(read_array(reader)?) as usize];
+    reader.read_exact(&mut payload)?;
+    Ok(CapturedPacket {
+        elapsed: Duration::from_micros(elapsed),
+        direction: if direction == 0 { FrameDirection::Inbound } else { FrameDirection::Outbound },
+        state: *STATES.get(state as usize).ok_or_else(|| std::io::Error::new(ErrorKind::InvalidData, format!("Unknown state {}", state)))?,
+        packet: RawPacket::new(packet_id, payload),
+    })
+}
+
+fn read_array<const N: usize>(reader: &mut impl Read) -> std::io::Result<[u8; N]> {
+    let mut buffer = [0; N];
+    reader.read_exact(&mut buffer)?;
+    Ok(buffer)
+}
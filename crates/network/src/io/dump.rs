@@ -0,0 +1,59 @@
+use std::fmt::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use log::info;
+use crate::prelude::{ClientboundPacketType, ConnectionState, FrameDirection, PacketType, RawPacket};
+
+/// Payload bytes shown per dumped packet, the rest is only counted.
+pub const PACKET_DUMP_LIMIT: usize = 256;
+
+static PACKET_DUMP: AtomicBool = AtomicBool::new(false);
+
+/// Debug aid: logs every packet sent or received from now on with a hex dump of its payload.
+pub fn set_packet_dump(enabled: bool) {
+    PACKET_DUMP.store(enabled, Ordering::Relaxed);
+}
+
+pub fn packet_dump_enabled() -> bool {
+    PACKET_DUMP.load(Ordering::Relaxed)
+}
+
+/// Logs the packet if the dump is enabled, `state` is the one it is sent or received in.
+pub fn dump_packet(connection_id: u64, direction: FrameDirection, state: ConnectionState, packet: &RawPacket) {
+    if !packet_dump_enabled() {
+        return;
+    }
+    let name = match direction {
+        FrameDirection::Inbound => PacketType::from_parts(state, packet.packet_id).map(PacketType::name),
+        FrameDirection::Outbound => ClientboundPacketType::from_parts(state, packet.packet_id).map(ClientboundPacketType::name),
+    };
+    let mut message = format!("conn={} {} {:?} {} (0x{:02X}) len={}", connection_id, direction, state, name.unwrap_or("Unknown"),
+        packet.packet_id, packet.payload.len());
+    if !packet.payload.is_empty() {
+        message.push('\n');
+        message.push_str(&hex_dump(&packet.payload, PACKET_DUMP_LIMIT));
+    }
+    info!("{}", message);
+}
+
+/// Offset, hex and ASCII columns of the first `limit` bytes, 16 per line, followed by how many were left
+/// out. Lines are separated by newlines, without a trailing one.
+pub fn hex_dump(bytes: &[u8], limit: usize) -> String {
+    let mut lines = Vec::new();
+    for (line, chunk) in bytes[..bytes.len().min(limit)].chunks(16).enumerate() {
+        let mut output = format!("{:04x} ", line * 16);
+        for index in 0..16 {
+            match chunk.get(index) {
+                Some(byte) => { let _ = write!(output, " {:02x}", byte); }
+                None => output.push_str("   "),
+            }
+        }
+        output.push_str("  |");
+        output.extend(chunk.iter().map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' }));
+        output.push('|');
+        lines.push(output);
+    }
+    if bytes.len() > limit {
+        lines.push(format!("... {} more bytes", bytes.len() - limit));
+    }
+    lines.join("\n")
+}
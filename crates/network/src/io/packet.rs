@@ -1,38 +1,231 @@
 mod raw;
 mod processor;
+mod metrics;
+mod state;
+mod context;
 mod handshake;
+mod status;
+mod login;
+mod configuration;
+mod play;
+mod disconnect;
+mod cookie;
+mod plugin_message;
+mod resource_pack;
+mod transfer;
+#[cfg(feature = "static-dispatch")]
+mod dispatch;
 
 pub use raw::*;
 pub use processor::*;
+pub use metrics::*;
+pub use state::*;
+pub use context::*;
+pub use handshake::*;
+pub use status::*;
+pub use login::*;
+pub use configuration::*;
+pub use play::*;
+pub use disconnect::*;
+pub use cookie::*;
+pub use plugin_message::*;
+pub use resource_pack::*;
+pub use transfer::*;
+#[cfg(feature = "static-dispatch")]
+pub use dispatch::*;
 
-use std::pin::Pin;
+use std::io::{ErrorKind, Read, Write};
+use anyhow::bail;
+use async_std::io::{Read as AsyncRead, ReadExt, Write as AsyncWrite, WriteExt};
 use async_std::net::TcpStream;
-use crate::prelude::{read_varint_and_get_size, read_varint, read_exact_bytes};
+use flate2::Compression;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use dolls_core::datatype::{Encode, VarInt};
+use crate::prelude::{FrameDirection, FrameTrace};
 
-/// Packet processor to pack packets from tcp stream.
+/// Largest frame accepted, the most a 3 byte VarInt length can announce as in vanilla.
+pub const MAX_FRAME_LENGTH: u32 = (1 << 21) - 1;
+/// Largest packet a compressed frame may inflate to.
+pub const MAX_DECOMPRESSED_LENGTH: u32 = 1 << 23;
+/// Bytes asked from the stream per read, most frames arrive whole within one.
+const READ_CHUNK: usize = 8192;
+
+/// Packet processor to pack packets from tcp stream, or any other byte stream such as a slice.
+///
+/// Reads go through a buffer which may hold the start of the next frames, so a stream should be read
+/// through a single handler for as long as it is open.
 #[derive(Debug)]
-pub struct PacketHandler<'a> {
-    stream: Pin<&'a mut TcpStream>,
-    enable_compression: bool,
+pub struct PacketHandler<S = TcpStream> {
+    stream: S,
+    compression_threshold: Option<usize>,
+    trace: Option<FrameTrace>,
+    buffer: Vec<u8>,
+    /// Bytes of `buffer` read from the stream but not yet taken as frames.
+    start: usize,
+    end: usize,
 }
 
-impl<'a> PacketHandler<'a> {
-    pub fn new(stream: &'a mut TcpStream) -> Self {
+impl<S: Unpin> PacketHandler<S> {
+    pub fn new(stream: S) -> Self {
         Self {
-            stream: Pin::new(stream),
-            enable_compression: false,
+            stream,
+            compression_threshold: None,
+            trace: None,
+            buffer: Vec::new(),
+            start: 0,
+            end: 0,
         }
     }
 
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    /// Enables compressed framing for packets of at least `threshold` bytes, `None` disables it.
+    pub fn set_compression(&mut self, threshold: Option<usize>) {
+        self.compression_threshold = threshold;
+    }
+
+    /// Records every frame read or written from now on.
+    pub fn set_trace(&mut self, trace: FrameTrace) {
+        self.trace = Some(trace);
+    }
+}
+
+impl<S: AsyncRead + Unpin> PacketHandler<S> {
     pub async fn next_packet(&mut self) -> anyhow::Result<RawPacket> {
-        let length = read_varint(&mut *self.stream).await?;
-        let (packet_id, packet_id_size) = read_varint_and_get_size(&mut *self.stream).await?;
-        let data_length = length - packet_id_size;
-        let payload = read_exact_bytes(&mut *self.stream, data_length as usize).await?;
-        Ok(RawPacket {
-            size_in_bytes: length,
-            packet_id,
-            payload,
-        })
+        let frame = self.next_frame().await?;
+        self.parse_frame(&frame).await
+    }
+
+    /// Reads the next frame without its length prefix, for callers which learn the compression of a frame
+    /// only after reading it. [`PacketHandler::parse_frame`] turns it into a packet.
+    ///
+    /// A frame leaves the buffer only once it is complete, so a read cancelled by a timeout loses nothing.
+    pub async fn next_frame(&mut self) -> anyhow::Result<Vec<u8>> {
+        loop {
+            let buffered = &self.buffer[self.start..self.end];
+            let needed = match VarInt::decode_prefix(buffered)? {
+                Some((length, prefix)) => {
+                    let length = length.0 as u32;
+                    if length > MAX_FRAME_LENGTH {
+                        bail!("Frame of {} bytes is over the limit of {}", length, MAX_FRAME_LENGTH);
+                    }
+                    let total = prefix + length as usize;
+                    if buffered.len() >= total {
+                        let frame = buffered[prefix..total].to_vec();
+                        self.start += total;
+                        record_bytes(FrameDirection::Inbound, total);
+                        return Ok(frame);
+                    }
+                    total
+                }
+                None => buffered.len() + 1,
+            };
+            self.fill_buffer(needed).await?;
+        }
+    }
+
+    /// Reads at least once from the stream, with room for `needed` unread bytes in the buffer.
+    async fn fill_buffer(&mut self, needed: usize) -> std::io::Result<()> {
+        if self.start == self.end {
+            (self.start, self.end) = (0, 0);
+        } else if self.start > 0 && self.buffer.len() - self.start < needed.max(READ_CHUNK) {
+            self.buffer.copy_within(self.start..self.end, 0);
+            (self.start, self.end) = (0, self.end - self.start);
+        }
+        let size = self.buffer.len().max(self.start + needed.max(READ_CHUNK));
+        self.buffer.resize(size, 0);
+        let read = self.stream.read(&mut self.buffer[self.end..]).await?;
+        if read == 0 {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+        self.end += read;
+        Ok(())
+    }
+
+    pub async fn parse_frame(&mut self, frame: &[u8]) -> anyhow::Result<RawPacket> {
+        let packet = decode_frame(frame, self.compression_threshold);
+        if let Some(trace) = &mut self.trace {
+            trace.record(frame, packet.as_ref().ok().map(|packet| packet.packet_id));
+        }
+        packet
+    }
+}
+
+fn decode_frame(frame: &[u8], compression_threshold: Option<usize>) -> anyhow::Result<RawPacket> {
+    let mut data = frame;
+    let decompressed;
+    if let Some(threshold) = compression_threshold {
+        let (data_length, prefix) = read_prefix(data)?;
+        data = &data[prefix..];
+        if data_length != 0 {
+            if data_length > MAX_DECOMPRESSED_LENGTH {
+                bail!("Compressed packet of {} bytes is over the limit of {}", data_length, MAX_DECOMPRESSED_LENGTH);
+            }
+            if (data_length as usize) < threshold {
+                bail!("Badly compressed packet: {} bytes is below the compression threshold of {}", data_length, threshold);
+            }
+            // The claimed length is only trusted as far as zlib can plausibly inflate what was actually sent.
+            let mut buffer = Vec::with_capacity((data_length as usize).min(data.len().saturating_mul(4)));
+            ZlibDecoder::new(data).take(data_length as u64).read_to_end(&mut buffer)?;
+            if buffer.len() != data_length as usize {
+                bail!("Compressed packet inflated to {} bytes instead of {}", buffer.len(), data_length);
+            }
+            decompressed = buffer;
+            data = decompressed.as_slice();
+        }
+    }
+
+    let (packet_id, prefix) = read_prefix(data)?;
+    Ok(RawPacket {
+        size_in_bytes: frame.len() as u32,
+        packet_id,
+        payload: data[prefix..].to_vec(),
+    })
+}
+
+/// A VarInt at the start of a complete frame, which cannot end before it.
+fn read_prefix(data: &[u8]) -> std::io::Result<(u32, usize)> {
+    VarInt::decode_prefix(data)?
+        .map(|(value, size)| (value.0 as u32, size))
+        .ok_or_else(|| ErrorKind::UnexpectedEof.into())
+}
+
+impl<S: AsyncWrite + Unpin> PacketHandler<S> {
+    pub async fn write_packet(&mut self, packet: &RawPacket) -> anyhow::Result<()> {
+        let mut body = Vec::with_capacity(packet.size_in_bytes as usize);
+        VarInt(packet.packet_id as i32).encode(&mut body)?;
+        body.extend_from_slice(&packet.payload);
+
+        let content = match self.compression_threshold {
+            Some(threshold) if body.len() >= threshold => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(&body)?;
+                let compressed = encoder.finish()?;
+                let mut content = Vec::with_capacity(compressed.len() + 5);
+                VarInt(body.len() as i32).encode(&mut content)?;
+                content.extend_from_slice(&compressed);
+                content
+            }
+            Some(_) => {
+                let mut content = Vec::with_capacity(body.len() + 1);
+                VarInt(0).encode(&mut content)?;
+                content.extend_from_slice(&body);
+                content
+            }
+            None => body,
+        };
+        if let Some(trace) = &mut self.trace {
+            trace.record(&content, Some(packet.packet_id));
+        }
+
+        let mut frame = Vec::with_capacity(content.len() + 5);
+        VarInt(content.len() as i32).encode(&mut frame)?;
+        frame.extend_from_slice(&content);
+        self.stream.write_all(&frame).await?;
+        record_bytes(FrameDirection::Outbound, frame.len());
+        Ok(())
     }
 }
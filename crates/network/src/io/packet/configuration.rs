@@ -0,0 +1,165 @@
+use log::debug;
+use dolls_core::datatype::{decode_from_slice, read_bounded_string, Decode, Encode, Identifier, VarInt};
+use dolls_core::nbt::NbtTag;
+use dolls_core::registry::{registries, registry_sync_entries, CORE_PACK_VERSION};
+use dolls_core::text::TextComponent;
+use dolls_macros::packet_processor;
+use crate::prelude::{send_configured_resource_pack, start_play, DisconnectCode, ProtocolError, ChatVisibility, ClientboundPacket, ClientboundPacketType, ConnectionState, PacketContext, PacketType, RawPacket};
+
+#[derive(Debug, Clone, Eq, PartialEq, Encode, Decode)]
+pub struct KnownPack {
+    pub namespace: String,
+    pub id: String,
+    pub version: String,
+}
+
+impl KnownPack {
+    pub fn core() -> Self {
+        Self {
+            namespace: "minecraft".to_string(),
+            id: "core".to_string(),
+            version: CORE_PACK_VERSION.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Encode)]
+pub struct ClientboundKnownPacks {
+    pub packs: Vec<KnownPack>,
+}
+
+impl ClientboundPacket for ClientboundKnownPacks {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::ClientboundKnownPacks;
+}
+
+#[derive(Debug, Encode)]
+pub struct RegistryDataEntry {
+    pub id: Identifier,
+    pub data: Option<NbtTag>,
+}
+
+#[derive(Debug, Encode)]
+pub struct RegistryData {
+    pub registry_id: Identifier,
+    pub entries: Vec<RegistryDataEntry>,
+}
+
+impl ClientboundPacket for RegistryData {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::RegistryData;
+}
+
+#[derive(Debug, Encode)]
+pub struct FinishConfiguration;
+
+impl ClientboundPacket for FinishConfiguration {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::FinishConfiguration;
+}
+
+#[derive(Debug, Encode)]
+pub struct ConfigurationDisconnect {
+    pub reason: TextComponent,
+}
+
+impl ClientboundPacket for ConfigurationDisconnect {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::ConfigurationDisconnect;
+}
+
+/// Client settings, sent during Configuration and again in Play whenever they change.
+#[derive(Debug, Clone)]
+pub struct ClientInformation {
+    pub locale: String,
+    pub view_distance: i8,
+    pub chat_mode: VarInt,
+    pub chat_colors: bool,
+    pub displayed_skin_parts: u8,
+    pub main_hand: VarInt,
+    pub text_filtering: bool,
+    pub allow_server_listings: bool,
+}
+
+impl ClientInformation {
+    pub fn chat_visibility(&self) -> ChatVisibility {
+        ChatVisibility::from_id(self.chat_mode.0)
+    }
+}
+
+impl Decode for ClientInformation {
+    fn decode(reader: &mut impl std::io::Read) -> std::io::Result<Self> {
+        Ok(Self {
+            locale: read_bounded_string(reader, 16)?,
+            view_distance: i8::decode(reader)?,
+            chat_mode: VarInt::decode(reader)?,
+            chat_colors: bool::decode(reader)?,
+            displayed_skin_parts: u8::decode(reader)?,
+            main_hand: VarInt::decode(reader)?,
+            text_filtering: bool::decode(reader)?,
+            allow_server_listings: bool::decode(reader)?,
+        })
+    }
+}
+
+/// Starts Configuration by offering the vanilla core pack, registries are sent once the client answers.
+pub(crate) fn start_configuration(context: &mut PacketContext) -> anyhow::Result<()> {
+    context.state = ConnectionState::Configuration;
+    context.send(&ClientboundKnownPacks { packs: vec![KnownPack::core()] })
+}
+
+fn send_registries(context: &mut PacketContext, client_knows_core: bool) -> anyhow::Result<()> {
+    let packets = {
+        let registries = registries().read().unwrap();
+        let to_packet = |registry_id: &Identifier, entries: Vec<(Identifier, Option<NbtTag>)>| RegistryData {
+            registry_id: registry_id.clone(),
+            entries: entries.into_iter().map(|(id, data)| RegistryDataEntry { id, data }).collect(),
+        };
+
+        let mut packets = vec![to_packet(registries.chat_types.id(), registry_sync_entries(&registries.chat_types, client_knows_core))];
+        for registry in &registries.opaque {
+            packets.push(to_packet(registry.id(), registry_sync_entries(registry, client_knows_core)));
+        }
+        packets
+    };
+
+    for packet in &packets {
+        context.send(packet)?;
+    }
+    Ok(())
+}
+
+#[packet_processor(PacketType::ConfigurationClientInformation)]
+pub(crate) fn client_information_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    let information: ClientInformation = decode_from_slice(&packet.payload)?;
+    debug!("Client information from {}: {:?}", context.peer_addr, information);
+    context.client_information = Some(information);
+    Ok(())
+}
+
+/// Same as during Configuration, sent whenever the player changes their options.
+#[packet_processor(PacketType::ClientInformation)]
+pub(crate) fn play_client_information_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    client_information_packet(context, packet)
+}
+
+#[packet_processor(PacketType::ServerboundKnownPacks)]
+pub(crate) fn known_packs_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    let packs: Vec<KnownPack> = decode_from_slice(&packet.payload)?;
+    let client_knows_core = packs.contains(&KnownPack::core());
+
+    if !client_knows_core {
+        // Only the ids of vanilla registry entries are known, their data has to come from the client.
+        let message = format!("This server requires Minecraft {}.", CORE_PACK_VERSION);
+        return Err(anyhow::Error::new(ProtocolError::new(DisconnectCode::IncompatibleVersion, message))
+            .context(format!("Client {} does not know the {} core pack", context.peer_addr, CORE_PACK_VERSION)));
+    }
+
+    send_registries(context, client_knows_core)?;
+    // With a configured resource pack, Configuration finishes once the client answered its prompt.
+    if send_configured_resource_pack(context)? {
+        return Ok(());
+    }
+    context.send(&FinishConfiguration)
+}
+
+#[packet_processor(PacketType::AcknowledgeFinishConfiguration)]
+pub(crate) fn acknowledge_finish_configuration_packet(context: &mut PacketContext, _packet: RawPacket) -> anyhow::Result<()> {
+    start_play(context)
+}
@@ -0,0 +1,100 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use dolls_config::ServerConfig;
+use dolls_core::datatype::Uuid;
+use dolls_core::text::TextComponent;
+use crate::prelude::{disconnect_packet, ClientInformation, ClientboundPacket, ConnectionHandle, ConnectionInfo, ConnectionRegistry, ConnectionState, LoginSession, RawPacket};
+
+/// Actions the worker performs on the connection once a processor returns, in order.
+#[derive(Debug)]
+pub enum Outbound {
+    Packet(RawPacket),
+    /// Switches the framing, packets queued after this one are compressed.
+    SetCompression(Option<usize>),
+    /// Closes the connection once everything queued before is written, what is queued after is dropped.
+    Close,
+}
+
+/// Per-connection context handed to packet processors.
+#[derive(Debug)]
+pub struct PacketContext {
+    pub peer_addr: SocketAddr,
+    pub config: Arc<ServerConfig>,
+    pub connection: ConnectionHandle,
+    pub connections: Arc<ConnectionRegistry>,
+    pub state: ConnectionState,
+    pub username: Option<String>,
+    pub uuid: Option<Uuid>,
+    pub entity_id: Option<i32>,
+    pub login: LoginSession,
+    pub client_information: Option<ClientInformation>,
+    outbound: Vec<Outbound>,
+    disconnected: bool,
+}
+
+impl PacketContext {
+    pub fn new(config: Arc<ServerConfig>, connection: ConnectionHandle, connections: Arc<ConnectionRegistry>) -> Self {
+        Self {
+            peer_addr: connection.peer_addr(),
+            config,
+            connection,
+            connections,
+            state: ConnectionState::default(),
+            username: None,
+            uuid: None,
+            entity_id: None,
+            login: LoginSession::default(),
+            client_information: None,
+            outbound: Vec::new(),
+            disconnected: false,
+        }
+    }
+
+    /// Queues a packet, it is written once the current processor returns.
+    pub fn send<T: ClientboundPacket>(&mut self, packet: &T) -> anyhow::Result<()> {
+        self.outbound.push(Outbound::Packet(RawPacket::from_packet(packet)?));
+        Ok(())
+    }
+
+    pub fn send_raw(&mut self, packet: RawPacket) {
+        self.outbound.push(Outbound::Packet(packet));
+    }
+
+    pub fn set_compression(&mut self, threshold: Option<usize>) {
+        self.outbound.push(Outbound::SetCompression(threshold));
+    }
+
+    /// Tells the client why it is disconnected with the Disconnect packet of the current state. The
+    /// connection is closed once the current processor returns and its packets were sent.
+    pub fn disconnect(&mut self, reason: TextComponent) -> anyhow::Result<()> {
+        if let Some(packet) = disconnect_packet(self.state, reason)? {
+            self.send_raw(packet);
+        }
+        self.close();
+        Ok(())
+    }
+
+    /// Closes the connection once the current processor returns, without telling the client why.
+    pub fn close(&mut self) {
+        self.disconnected = true;
+    }
+
+    pub fn is_disconnected(&self) -> bool {
+        self.disconnected
+    }
+
+    pub fn take_outbound(&mut self) -> Vec<Outbound> {
+        std::mem::take(&mut self.outbound)
+    }
+
+    /// Publishes the state processors changed to the connection registry.
+    pub(crate) fn sync_connection_info(&self) {
+        self.connection.set_info(ConnectionInfo {
+            state: self.state,
+            username: self.username.clone(),
+            uuid: self.uuid,
+            entity_id: self.entity_id,
+            client_information: self.client_information.clone(),
+        });
+    }
+}
@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use anyhow::{anyhow, bail};
+use once_cell::sync::Lazy;
+use log::{debug, error};
+use dolls_core::datatype::{read_bounded_bytes, Decode, Encode, Identifier};
+use dolls_macros::packet_processor;
+use crate::prelude::{finish_login_when_answered, ClientboundPacket, ClientboundPacketType, ConnectionHandle, ConnectionState, PacketContext, PacketType, RawPacket};
+
+/// Largest cookie clients store and send back.
+pub const MAX_COOKIE_SIZE: usize = 5120;
+
+#[derive(Debug, Clone, Encode)]
+pub struct LoginCookieRequest {
+    pub key: Identifier,
+}
+
+impl ClientboundPacket for LoginCookieRequest {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::LoginCookieRequest;
+}
+
+#[derive(Debug, Clone, Encode)]
+pub struct ConfigurationCookieRequest {
+    pub key: Identifier,
+}
+
+impl ClientboundPacket for ConfigurationCookieRequest {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::ConfigurationCookieRequest;
+}
+
+#[derive(Debug, Clone, Encode)]
+pub struct CookieRequest {
+    pub key: Identifier,
+}
+
+impl ClientboundPacket for CookieRequest {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::CookieRequest;
+}
+
+#[derive(Debug, Clone, Encode)]
+pub struct ConfigurationStoreCookie {
+    pub key: Identifier,
+    pub payload: Vec<u8>,
+}
+
+impl ClientboundPacket for ConfigurationStoreCookie {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::ConfigurationStoreCookie;
+}
+
+#[derive(Debug, Clone, Encode)]
+pub struct StoreCookie {
+    pub key: Identifier,
+    pub payload: Vec<u8>,
+}
+
+impl ClientboundPacket for StoreCookie {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::StoreCookie;
+}
+
+/// Handles the cookies clients send back for a key, e.g. data a server stashed before a transfer.
+pub trait CookieHandler: Send + Sync {
+    /// Whether to request the cookie from the connection after Login Start, the login then waits for it.
+    fn request_on_login(&self, _context: &PacketContext) -> bool {
+        false
+    }
+
+    /// Handles the client's answer, `payload` is `None` when it has no cookie for the key. Returning an
+    /// error during Login aborts the login, later it is logged.
+    fn on_cookie(&self, context: &mut PacketContext, payload: Option<&[u8]>) -> anyhow::Result<()>;
+}
+
+static COOKIE_HANDLERS: Lazy<RwLock<HashMap<Identifier, Arc<dyn CookieHandler>>>> = Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// Registers or replaces the handler of a cookie key, answers for keys without one are dropped.
+pub fn register_cookie_handler(key: Identifier, handler: Arc<dyn CookieHandler>) {
+    COOKIE_HANDLERS.write().unwrap().insert(key, handler);
+}
+
+/// Keys requested from a connection and not answered yet.
+#[derive(Debug, Default)]
+struct PendingCookies(Vec<Identifier>);
+
+/// The Cookie Request packet of `state`, Login, Configuration and Play have one.
+pub fn cookie_request_packet(state: ConnectionState, key: Identifier) -> anyhow::Result<RawPacket> {
+    Ok(match state {
+        ConnectionState::Login => RawPacket::from_packet(&LoginCookieRequest { key })?,
+        ConnectionState::Configuration => RawPacket::from_packet(&ConfigurationCookieRequest { key })?,
+        ConnectionState::Play => RawPacket::from_packet(&CookieRequest { key })?,
+        state => bail!("No cookies in {:?}", state),
+    })
+}
+
+/// The Store Cookie packet of `state`, only Configuration and Play have one.
+pub fn store_cookie_packet(state: ConnectionState, key: Identifier, payload: Vec<u8>) -> anyhow::Result<RawPacket> {
+    if payload.len() > MAX_COOKIE_SIZE {
+        bail!("Cookie {} has {} bytes, at most {} are stored", key, payload.len(), MAX_COOKIE_SIZE);
+    }
+    Ok(match state {
+        ConnectionState::Configuration => RawPacket::from_packet(&ConfigurationStoreCookie { key, payload })?,
+        ConnectionState::Play => RawPacket::from_packet(&StoreCookie { key, payload })?,
+        state => bail!("No cookies can be stored in {:?}", state),
+    })
+}
+
+/// Asks the client for a cookie, its answer goes to the handler registered for the key.
+pub fn request_cookie(connection: &ConnectionHandle, key: Identifier) -> anyhow::Result<()> {
+    let packet = cookie_request_packet(connection.state(), key.clone())?;
+    connection.extensions(|extensions| extensions.get_or_default::<PendingCookies>().0.push(key));
+    connection.send_raw(packet)
+}
+
+/// Stores a cookie on the client, it keeps it across transfers until it quits the game.
+pub fn store_cookie(connection: &ConnectionHandle, key: Identifier, payload: Vec<u8>) -> anyhow::Result<()> {
+    connection.send_raw(store_cookie_packet(connection.state(), key, payload)?)
+}
+
+pub(crate) fn has_pending_cookies(connection: &ConnectionHandle) -> bool {
+    connection.extensions(|extensions| extensions.get::<PendingCookies>().is_some_and(|pending| !pending.0.is_empty()))
+}
+
+/// Requests the cookies handlers want during Login, returns how many were requested.
+pub(crate) fn request_login_cookies(context: &mut PacketContext) -> anyhow::Result<usize> {
+    let handlers = COOKIE_HANDLERS.read().unwrap().clone();
+    let mut requested = 0;
+    for (key, handler) in handlers {
+        if handler.request_on_login(context) {
+            context.connection.extensions(|extensions| extensions.get_or_default::<PendingCookies>().0.push(key.clone()));
+            context.send(&LoginCookieRequest { key })?;
+            requested += 1;
+        }
+    }
+    Ok(requested)
+}
+
+fn handle_cookie_response(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    let mut payload = packet.payload.as_slice();
+    let key = Identifier::decode(&mut payload)?;
+    let cookie = match bool::decode(&mut payload)? {
+        true => Some(read_bounded_bytes(&mut payload, MAX_COOKIE_SIZE)?),
+        false => None,
+    };
+
+    let requested = context.connection.extensions(|extensions| {
+        let pending = &mut extensions.get_or_default::<PendingCookies>().0;
+        let index = pending.iter().position(|pending| *pending == key)?;
+        Some(pending.remove(index))
+    });
+    requested.ok_or_else(|| anyhow!("Unexpected Cookie Response for {}", key))?;
+
+    let handler = COOKIE_HANDLERS.read().unwrap().get(&key).cloned();
+    match handler {
+        Some(handler) => match handler.on_cookie(context, cookie.as_deref()) {
+            Err(err) if context.state == ConnectionState::Login => return Err(err),
+            Err(err) => error!("Cookie handler for {} failed for {:?}: {}", key, context.username, err),
+            Ok(()) => {}
+        },
+        None => debug!("Cookie {} from {} has no handler", key, context.peer_addr),
+    }
+
+    if context.state == ConnectionState::Login {
+        finish_login_when_answered(context)?;
+    }
+    Ok(())
+}
+
+#[packet_processor(PacketType::LoginCookieResponse)]
+pub(crate) fn login_cookie_response_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    handle_cookie_response(context, packet)
+}
+
+#[packet_processor(PacketType::ConfigurationCookieResponse)]
+pub(crate) fn configuration_cookie_response_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    handle_cookie_response(context, packet)
+}
+
+#[packet_processor(PacketType::CookieResponse)]
+pub(crate) fn cookie_response_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    handle_cookie_response(context, packet)
+}
@@ -0,0 +1,143 @@
+use std::fmt::{Display, Formatter};
+use std::io;
+use log::error;
+use dolls_config::DisconnectVerbosity;
+use dolls_core::datatype::Encode;
+use dolls_core::text::{JsonTextComponent, TextComponent};
+use crate::prelude::{ClientboundPacket, ClientboundPacketType, ConfigurationDisconnect, ConnectionHandle, ConnectionState, LoginDisconnect, RawPacket};
+
+/// Kinds of protocol errors, shown to clients so that users can report them.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum DisconnectCode {
+    /// A packet the current state does not allow.
+    UnexpectedPacket,
+    /// A frame or packet which could not be decoded.
+    MalformedPacket,
+    /// A valid packet sent at the wrong time or with values the server rejects.
+    InvalidSequence,
+    /// Login took longer than `login-timeout`, or Configuration longer than `configuration-timeout` on top.
+    LoginTimeout,
+    /// The client does not speak the server's version.
+    IncompatibleVersion,
+    /// Nothing was received for `idle-timeout`.
+    IdleTimeout,
+}
+
+impl DisconnectCode {
+    /// Stable identifier of the code.
+    pub const fn code(self) -> &'static str {
+        match self {
+            DisconnectCode::UnexpectedPacket => "DOLLS-P001",
+            DisconnectCode::MalformedPacket => "DOLLS-P002",
+            DisconnectCode::InvalidSequence => "DOLLS-P003",
+            DisconnectCode::LoginTimeout => "DOLLS-P004",
+            DisconnectCode::IncompatibleVersion => "DOLLS-P005",
+            DisconnectCode::IdleTimeout => "DOLLS-P006",
+        }
+    }
+
+    pub const fn summary(self) -> &'static str {
+        match self {
+            DisconnectCode::UnexpectedPacket => "Unexpected packet",
+            DisconnectCode::MalformedPacket => "Malformed packet",
+            DisconnectCode::InvalidSequence => "Invalid login sequence",
+            DisconnectCode::LoginTimeout => "Took too long to log in",
+            DisconnectCode::IncompatibleVersion => "Incompatible game version",
+            DisconnectCode::IdleTimeout => "Timed out",
+        }
+    }
+
+    /// The code of an error returned by a packet processor.
+    pub fn of(err: &anyhow::Error) -> Self {
+        if let Some(err) = err.downcast_ref::<ProtocolError>() {
+            return err.code;
+        }
+        match err.chain().any(|cause| cause.is::<io::Error>()) {
+            true => DisconnectCode::MalformedPacket,
+            false => DisconnectCode::InvalidSequence,
+        }
+    }
+}
+
+impl Display for DisconnectCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+/// An error meant for the client, processors return it to pick the code and message of the disconnect.
+#[derive(Debug, Clone)]
+pub struct ProtocolError {
+    pub code: DisconnectCode,
+    pub message: String,
+}
+
+impl ProtocolError {
+    pub fn new(code: DisconnectCode, message: impl Into<String>) -> Self {
+        Self { code, message: message.into() }
+    }
+}
+
+impl Display for ProtocolError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} [{}]", self.message, self.code)
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+/// Reason shown to the client, `details` only with [`DisconnectVerbosity::Detailed`].
+pub fn disconnect_reason(code: DisconnectCode, message: &str, details: &str, verbosity: DisconnectVerbosity) -> TextComponent {
+    let reason = match verbosity {
+        DisconnectVerbosity::Minimal => return TextComponent::text(code.summary()),
+        _ => TextComponent::text(message).append(TextComponent::text(format!(" [{}]", code)).color("gray")),
+    };
+    match verbosity {
+        DisconnectVerbosity::Detailed if !details.is_empty() && details != message => {
+            reason.append(TextComponent::text(format!("\n\n{}", details)).color("gray"))
+        }
+        _ => reason,
+    }
+}
+
+#[derive(Debug, Encode)]
+pub struct PlayDisconnect {
+    pub reason: TextComponent,
+}
+
+impl ClientboundPacket for PlayDisconnect {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::PlayDisconnect;
+}
+
+/// The Disconnect packet of `state`, `None` for states without one.
+pub fn disconnect_packet(state: ConnectionState, reason: TextComponent) -> anyhow::Result<Option<RawPacket>> {
+    Ok(match state {
+        ConnectionState::Login => Some(RawPacket::from_packet(&LoginDisconnect { reason: JsonTextComponent(reason) })?),
+        ConnectionState::Configuration => Some(RawPacket::from_packet(&ConfigurationDisconnect { reason })?),
+        ConnectionState::Play => Some(RawPacket::from_packet(&PlayDisconnect { reason })?),
+        _ => None,
+    })
+}
+
+/// Tells a client in Login or Configuration why it is disconnected, the caller closes the connection.
+pub fn send_protocol_error(connection: &ConnectionHandle, state: ConnectionState, err: &anyhow::Error, verbosity: DisconnectVerbosity) {
+    let code = DisconnectCode::of(err);
+    let message = match err.downcast_ref::<ProtocolError>() {
+        Some(err) => err.message.clone(),
+        None => code.summary().to_string(),
+    };
+    // A bare protocol error has nothing to add to its message.
+    let details = match err.chain().count() == 1 && err.is::<ProtocolError>() {
+        true => String::new(),
+        false => format!("{:#}", err),
+    };
+    let reason = disconnect_reason(code, &message, &details, verbosity);
+    match disconnect_packet(state, reason) {
+        Ok(Some(packet)) => {
+            // The connection may already be closed, there is no one left to tell then.
+            let _ = connection.send_raw(packet);
+        }
+        Ok(None) => {}
+        Err(err) => error!("Failed to encode disconnect for {:?}: {}", connection.peer_addr(), err),
+    }
+}
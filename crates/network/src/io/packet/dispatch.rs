@@ -0,0 +1,5 @@
+use dolls_macros::dispatch_table;
+use crate::prelude::PacketType;
+
+// The build script lists every `#[packet_processor]` of the crate.
+include!(concat!(env!("OUT_DIR"), "/dispatch_table.rs"));
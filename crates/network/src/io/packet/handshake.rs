@@ -1,10 +1,48 @@
-
+use anyhow::bail;
+use log::{debug, info};
+use dolls_core::datatype::{decode_from_slice, Decode, Encode, VarInt};
 use dolls_macros::packet_processor;
-use crate::prelude::{PacketType, RawPacket};
+use crate::prelude::{allow_status_ping, banned_ip_reason, banned_ips, transfers_disabled_reason, ConnectionState, Transferred, PacketContext, PacketType, RawPacket};
+
+#[derive(Debug, Clone, PartialEq, Encode, Decode)]
+pub struct Handshake {
+    pub protocol_version: VarInt,
+    pub server_address: String,
+    pub server_port: u16,
+    pub next_state: VarInt,
+}
 
 #[packet_processor(PacketType::Handshake)]
-fn handshake_packet(packet: RawPacket) -> anyhow::Result<()> {
-    println!("Handshake: {:?}", packet);
+pub(crate) fn handshake_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    let handshake: Handshake = decode_from_slice(&packet.payload)?;
+
+    debug!("Handshake from {}: protocol={}, address={}:{}, next_state={}",
+        context.peer_addr, handshake.protocol_version.0, handshake.server_address, handshake.server_port, handshake.next_state.0);
+
+    context.state = match handshake.next_state.0 {
+        1 => ConnectionState::Status,
+        2 | 3 => ConnectionState::Login,
+        state => bail!("Invalid next state {} in handshake", state),
+    };
+    if context.state == ConnectionState::Status && !allow_status_ping(context.peer_addr.ip(), context.config.network.status_rate_limit) {
+        debug!("Status ping from {} was dropped: over the rate limit", context.peer_addr);
+        context.close();
+        return Ok(());
+    }
+    // Banned addresses may still ping the server, vanilla only keeps them from logging in.
+    if context.state == ConnectionState::Login {
+        if let Some(ban) = banned_ips().read().unwrap().get(context.peer_addr.ip()) {
+            info!("Connection from {} was refused: the IP address is banned", context.peer_addr);
+            return context.disconnect(banned_ip_reason(ban));
+        }
+    }
+    if handshake.next_state.0 == 3 {
+        if !context.config.network.accepts_transfers {
+            info!("Transfer from {} was refused: transfers are disabled", context.peer_addr);
+            return context.disconnect(transfers_disabled_reason());
+        }
+        context.connection.extensions(|extensions| extensions.insert(Transferred));
+    }
 
     Ok(())
 }
@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use anyhow::{anyhow, bail};
+use once_cell::sync::Lazy;
+use log::{debug, info, warn};
+use dolls_core::datatype::{Decode, Encode, RemainingBytes, Uuid, VarInt};
+use dolls_core::text::JsonTextComponent;
+use dolls_macros::packet_processor;
+use crate::prelude::{banned_ip_reason, has_pending_cookies, request_login_cookies, banned_ips, banned_players, banned_reason, not_whitelisted_reason, start_configuration, whitelist, ClientboundPacket, ClientboundPacketType, PacketContext, PacketType, RawPacket};
+
+/// Handles a custom query channel during the Login phase, e.g. proxy forwarding.
+pub trait LoginChannelHandler: Send + Sync {
+    /// Payload of the Login Plugin Request, `None` skips this channel for the connection.
+    fn request(&self, context: &PacketContext) -> Option<Vec<u8>>;
+
+    /// Handles the client's answer, `data` is `None` when the client does not understand the channel.
+    /// Returning an error aborts the login.
+    fn response(&self, context: &mut PacketContext, data: Option<&[u8]>) -> anyhow::Result<()>;
+}
+
+type LoginChannel = (String, Arc<dyn LoginChannelHandler>);
+
+static LOGIN_CHANNELS: Lazy<RwLock<Vec<LoginChannel>>> = Lazy::new(|| RwLock::new(Vec::new()));
+
+/// Registers a handler queried for every connection after Login Start, in registration order.
+pub fn register_login_channel(channel: impl Into<String>, handler: Arc<dyn LoginChannelHandler>) {
+    LOGIN_CHANNELS.write().unwrap().push((channel.into(), handler));
+}
+
+/// Login progress of a connection.
+#[derive(Debug, Default)]
+pub struct LoginSession {
+    next_message_id: i32,
+    pending_queries: HashMap<i32, String>,
+    finished: bool,
+}
+
+#[derive(Debug, Encode)]
+pub struct LoginDisconnect {
+    pub reason: JsonTextComponent,
+}
+
+impl ClientboundPacket for LoginDisconnect {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::LoginDisconnect;
+}
+
+#[derive(Debug, Encode)]
+pub struct LoginPluginRequest {
+    pub message_id: VarInt,
+    pub channel: String,
+    pub data: RemainingBytes,
+}
+
+impl ClientboundPacket for LoginPluginRequest {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::LoginPluginRequest;
+}
+
+#[derive(Debug, Encode)]
+pub struct SetCompression {
+    pub threshold: VarInt,
+}
+
+impl ClientboundPacket for SetCompression {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::SetCompression;
+}
+
+#[derive(Debug, Clone, Encode)]
+pub struct ProfileProperty {
+    pub name: String,
+    pub value: String,
+    pub signature: Option<String>,
+}
+
+#[derive(Debug, Encode)]
+pub struct LoginSuccess {
+    pub uuid: Uuid,
+    pub username: String,
+    pub properties: Vec<ProfileProperty>,
+    pub strict_error_handling: bool,
+}
+
+impl ClientboundPacket for LoginSuccess {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::LoginSuccess;
+}
+
+/// UUID the vanilla server assigns to a player in offline mode.
+pub fn offline_uuid(username: &str) -> Uuid {
+    let digest = md5::compute(format!("OfflinePlayer:{}", username));
+    uuid::Builder::from_md5_bytes(digest.0).into_uuid()
+}
+
+/// Finishes the login once the client answered every plugin query and cookie request sent after Login Start.
+pub(crate) fn finish_login_when_answered(context: &mut PacketContext) -> anyhow::Result<()> {
+    if context.login.finished || !context.login.pending_queries.is_empty() || has_pending_cookies(&context.connection) {
+        return Ok(());
+    }
+    context.login.finished = true;
+    finish_login(context)
+}
+
+fn finish_login(context: &mut PacketContext) -> anyhow::Result<()> {
+    let username = context.username.clone().ok_or_else(|| anyhow!("Login finished before Login Start"))?;
+    let uuid = *context.uuid.get_or_insert_with(|| offline_uuid(&username));
+
+    if let Some(ban) = banned_players().read().unwrap().get(uuid) {
+        info!("{} ({}) was refused: banned", username, uuid);
+        return context.disconnect(banned_reason(ban));
+    }
+    if let Some(ban) = banned_ips().read().unwrap().get(context.peer_addr.ip()) {
+        info!("{} ({}) was refused: the IP address {} is banned", username, uuid, context.peer_addr.ip());
+        return context.disconnect(banned_ip_reason(ban));
+    }
+    if !whitelist().read().unwrap().allows(uuid) {
+        info!("{} ({}) was refused: not white-listed", username, uuid);
+        return context.disconnect(not_whitelisted_reason());
+    }
+    info!("{} ({}) logged in from {}", username, uuid, context.peer_addr);
+    if let Some(threshold) = context.config.network.compression_threshold() {
+        context.send(&SetCompression { threshold: VarInt(threshold as i32) })?;
+        context.set_compression(Some(threshold));
+    }
+    context.send(&LoginSuccess {
+        uuid,
+        username,
+        properties: Vec::new(),
+        strict_error_handling: false,
+    })
+}
+
+#[packet_processor(PacketType::LoginStart)]
+pub(crate) fn login_start_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    let mut payload = packet.payload.as_slice();
+    let username = dolls_core::datatype::read_bounded_string(&mut payload, 16)?;
+    let uuid = Uuid::decode(&mut payload)?;
+
+    debug!("Login Start from {}: {} ({})", context.peer_addr, username, uuid);
+    if context.username.is_some() {
+        bail!("Duplicate Login Start");
+    }
+    context.username = Some(username);
+
+    let channels = LOGIN_CHANNELS.read().unwrap().clone();
+    for (channel, handler) in channels {
+        if let Some(data) = handler.request(context) {
+            let message_id = context.login.next_message_id;
+            context.login.next_message_id += 1;
+            context.login.pending_queries.insert(message_id, channel.clone());
+            context.send(&LoginPluginRequest {
+                message_id: VarInt(message_id),
+                channel,
+                data: RemainingBytes(data),
+            })?;
+        }
+    }
+    request_login_cookies(context)?;
+
+    finish_login_when_answered(context)
+}
+
+#[packet_processor(PacketType::LoginPluginResponse)]
+pub(crate) fn login_plugin_response_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    let mut payload = packet.payload.as_slice();
+    let message_id = VarInt::decode(&mut payload)?.0;
+    let successful = bool::decode(&mut payload)?;
+    let data = RemainingBytes::decode(&mut payload)?.0;
+
+    let channel = context.login.pending_queries.remove(&message_id)
+        .ok_or_else(|| anyhow!("Unexpected Login Plugin Response (message_id={})", message_id))?;
+    let handler = LOGIN_CHANNELS.read().unwrap().iter()
+        .find(|(name, _)| *name == channel)
+        .map(|(_, handler)| handler.clone());
+
+    match handler {
+        Some(handler) => handler.response(context, successful.then_some(data.as_slice()))?,
+        None => warn!("Login channel {} was unregistered while waiting for a response", channel),
+    }
+
+    finish_login_when_answered(context)
+}
+
+#[packet_processor(PacketType::LoginAcknowledged)]
+pub(crate) fn login_acknowledged_packet(context: &mut PacketContext, _packet: RawPacket) -> anyhow::Result<()> {
+    start_configuration(context)
+}
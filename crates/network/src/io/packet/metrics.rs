@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use once_cell::sync::Lazy;
+use dolls_core::datatype::VarInt;
+use crate::prelude::{ClientboundPacketType, ConnectionState, FrameDirection, PacketType, RawPacket};
+
+/// Upper bounds of the execution time buckets, the last bucket holds everything slower.
+pub const HANDLER_BUCKETS: [Duration; 8] = [
+    Duration::from_micros(50),
+    Duration::from_micros(100),
+    Duration::from_micros(250),
+    Duration::from_micros(500),
+    Duration::from_millis(1),
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(50),
+];
+
+/// Invocations and execution times of the processor of one packet.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HandlerStats {
+    pub invocations: u64,
+    /// Invocations which returned an error.
+    pub errors: u64,
+    pub total: Duration,
+    pub max: Duration,
+    /// Invocations per bucket of [`HANDLER_BUCKETS`], plus one for slower ones.
+    pub buckets: [u64; HANDLER_BUCKETS.len() + 1],
+}
+
+impl HandlerStats {
+    pub fn mean(&self) -> Duration {
+        match self.invocations {
+            0 => Duration::ZERO,
+            invocations => self.total / invocations as u32,
+        }
+    }
+
+    /// Upper bound of the bucket the `quantile` falls in, the maximum for the last bucket.
+    pub fn quantile(&self, quantile: f64) -> Duration {
+        let rank = (self.invocations as f64 * quantile).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (bucket, count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return HANDLER_BUCKETS.get(bucket).copied().unwrap_or(self.max).min(self.max);
+            }
+        }
+        self.max
+    }
+
+    fn record(&mut self, elapsed: Duration, failed: bool) {
+        self.invocations += 1;
+        self.errors += failed as u64;
+        self.total += elapsed;
+        self.max = self.max.max(elapsed);
+        let bucket = HANDLER_BUCKETS.iter().position(|bound| elapsed <= *bound).unwrap_or(HANDLER_BUCKETS.len());
+        self.buckets[bucket] += 1;
+    }
+}
+
+static METRICS: Lazy<Mutex<HashMap<(ConnectionState, u32), HandlerStats>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Counts one run of the processor of `packet_id` in `state`.
+pub fn record_handler(state: ConnectionState, packet_id: u32, elapsed: Duration, failed: bool) {
+    METRICS.lock().unwrap().entry((state, packet_id)).or_default().record(elapsed, failed);
+}
+
+/// Statistics of every processor which ran, by state and packet id.
+pub fn handler_metrics() -> Vec<(PacketType, HandlerStats)> {
+    let mut metrics = METRICS.lock().unwrap().iter()
+        .filter_map(|((state, packet_id), stats)| Some((PacketType::from_parts(*state, *packet_id)?, stats.clone())))
+        .collect::<Vec<_>>();
+    metrics.sort_by_key(|(packet_type, _)| *packet_type);
+    metrics
+}
+
+pub fn reset_handler_metrics() {
+    METRICS.lock().unwrap().clear();
+}
+
+type TrafficKey = (FrameDirection, ConnectionState, u32);
+
+static TRAFFIC: Lazy<Mutex<HashMap<TrafficKey, TrafficStats>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+static BYTES_READ: AtomicU64 = AtomicU64::new(0);
+static BYTES_WRITTEN: AtomicU64 = AtomicU64::new(0);
+
+/// Packets of one id sent or received in one state.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrafficStats {
+    pub packets: u64,
+    /// Size of the packets before compression, with their id.
+    pub bytes: u64,
+}
+
+/// Counts one packet sent or received in `state`.
+pub fn record_packet(direction: FrameDirection, state: ConnectionState, packet: &RawPacket) {
+    let bytes = VarInt(packet.packet_id as i32).written_size() + packet.payload.len();
+    let mut traffic = TRAFFIC.lock().unwrap();
+    let stats = traffic.entry((direction, state, packet.packet_id)).or_default();
+    stats.packets += 1;
+    stats.bytes += bytes as u64;
+}
+
+/// Counts bytes read from or written to sockets, as they went over the wire.
+pub fn record_bytes(direction: FrameDirection, bytes: usize) {
+    let counter = match direction {
+        FrameDirection::Inbound => &BYTES_READ,
+        FrameDirection::Outbound => &BYTES_WRITTEN,
+    };
+    counter.fetch_add(bytes as u64, Ordering::Relaxed);
+}
+
+/// Bytes read from and written to sockets since the server started.
+pub fn bytes_transferred() -> (u64, u64) {
+    (BYTES_READ.load(Ordering::Relaxed), BYTES_WRITTEN.load(Ordering::Relaxed))
+}
+
+/// Packets counted by direction, state and id.
+pub fn traffic_metrics() -> Vec<(FrameDirection, ConnectionState, u32, TrafficStats)> {
+    let mut metrics = TRAFFIC.lock().unwrap().iter()
+        .map(|((direction, state, packet_id), stats)| (*direction, *state, *packet_id, stats.clone()))
+        .collect::<Vec<_>>();
+    metrics.sort_by_key(|(direction, state, packet_id, _)| (*direction as u8, *state, *packet_id));
+    metrics
+}
+
+/// Packet and byte counters in the Prometheus text format, packets are named after their type when it
+/// is known.
+pub fn traffic_metrics_prometheus() -> String {
+    let mut output = String::new();
+    let (read, written) = bytes_transferred();
+    output.push_str("# HELP dolls_network_bytes_total Bytes read from and written to sockets.\n");
+    output.push_str("# TYPE dolls_network_bytes_total counter\n");
+    let _ = writeln!(output, "dolls_network_bytes_total{{direction=\"{}\"}} {}", FrameDirection::Inbound, read);
+    let _ = writeln!(output, "dolls_network_bytes_total{{direction=\"{}\"}} {}", FrameDirection::Outbound, written);
+    let metrics = traffic_metrics();
+    let labels = |direction: FrameDirection, state: ConnectionState, packet_id: u32| {
+        let name = match direction {
+            FrameDirection::Inbound => PacketType::from_parts(state, packet_id).map(PacketType::name),
+            FrameDirection::Outbound => ClientboundPacketType::from_parts(state, packet_id).map(ClientboundPacketType::name),
+        };
+        format!("direction=\"{}\",state=\"{:?}\",id=\"{:#04x}\",packet=\"{}\"", direction, state, packet_id, name.unwrap_or("Unknown"))
+    };
+    output.push_str("# HELP dolls_packets_total Packets sent and received.\n");
+    output.push_str("# TYPE dolls_packets_total counter\n");
+    for (direction, state, packet_id, stats) in &metrics {
+        let _ = writeln!(output, "dolls_packets_total{{{}}} {}", labels(*direction, *state, *packet_id), stats.packets);
+    }
+    output.push_str("# HELP dolls_packet_bytes_total Size of the packets sent and received before compression.\n");
+    output.push_str("# TYPE dolls_packet_bytes_total counter\n");
+    for (direction, state, packet_id, stats) in &metrics {
+        let _ = writeln!(output, "dolls_packet_bytes_total{{{}}} {}", labels(*direction, *state, *packet_id), stats.bytes);
+    }
+    output
+}
+
+/// The metrics in the Prometheus text format, for exporters and scrapers.
+pub fn handler_metrics_prometheus() -> String {
+    let mut output = String::new();
+    output.push_str("# HELP dolls_packet_handler_seconds Execution time of packet handlers.\n");
+    output.push_str("# TYPE dolls_packet_handler_seconds histogram\n");
+    for (packet_type, stats) in handler_metrics() {
+        let labels = format!("state=\"{:?}\",packet=\"{}\"", packet_type.state(), packet_type.name());
+        let mut cumulative = 0;
+        for (bound, count) in HANDLER_BUCKETS.iter().zip(&stats.buckets) {
+            cumulative += count;
+            let _ = writeln!(output, "dolls_packet_handler_seconds_bucket{{{},le=\"{}\"}} {}", labels, bound.as_secs_f64(), cumulative);
+        }
+        let _ = writeln!(output, "dolls_packet_handler_seconds_bucket{{{},le=\"+Inf\"}} {}", labels, stats.invocations);
+        let _ = writeln!(output, "dolls_packet_handler_seconds_sum{{{}}} {}", labels, stats.total.as_secs_f64());
+        let _ = writeln!(output, "dolls_packet_handler_seconds_count{{{}}} {}", labels, stats.invocations);
+    }
+    output.push_str("# HELP dolls_packet_handler_errors_total Packet handler runs which failed.\n");
+    output.push_str("# TYPE dolls_packet_handler_errors_total counter\n");
+    for (packet_type, stats) in handler_metrics() {
+        let _ = writeln!(output, "dolls_packet_handler_errors_total{{state=\"{:?}\",packet=\"{}\"}} {}",
+            packet_type.state(), packet_type.name(), stats.errors);
+    }
+    output
+}
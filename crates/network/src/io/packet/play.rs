@@ -0,0 +1,73 @@
+mod chat;
+mod window;
+mod merchant;
+mod command;
+mod horse;
+mod container;
+mod join;
+mod camera;
+mod player_info;
+mod secure_chat;
+mod keep_alive;
+mod chunk;
+mod movement;
+mod chunk_tracker;
+mod block_change;
+mod world_border;
+mod entity_tracker;
+mod entity_storage;
+mod inventory;
+mod game_mode;
+mod recipe_book;
+mod advancement;
+mod statistics;
+mod scoreboard;
+mod teams;
+mod title;
+mod sound;
+mod particle;
+mod ai_debug;
+mod time;
+mod game_rules;
+mod health;
+mod respawn;
+mod dimension;
+mod world_spawn;
+mod difficulty;
+
+pub use chat::*;
+pub use window::*;
+pub use merchant::*;
+pub use command::*;
+pub use horse::*;
+pub use container::*;
+pub use join::*;
+pub use camera::*;
+pub use player_info::*;
+pub use secure_chat::*;
+pub use keep_alive::*;
+pub use chunk::*;
+pub use movement::*;
+pub use chunk_tracker::*;
+pub use block_change::*;
+pub use world_border::*;
+pub use entity_tracker::*;
+pub use entity_storage::*;
+pub use inventory::*;
+pub use game_mode::*;
+pub use recipe_book::*;
+pub use advancement::*;
+pub use statistics::*;
+pub use scoreboard::*;
+pub use teams::*;
+pub use title::*;
+pub use sound::*;
+pub use particle::*;
+pub use ai_debug::*;
+pub use time::*;
+pub use game_rules::*;
+pub use health::*;
+pub use respawn::*;
+pub use dimension::*;
+pub use world_spawn::*;
+pub use difficulty::*;
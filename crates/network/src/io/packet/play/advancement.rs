@@ -0,0 +1,376 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Write};
+use std::sync::{Arc, RwLock};
+use once_cell::sync::Lazy;
+use serde_json::Value;
+use log::warn;
+use dolls_core::advancement::{advancements, Advancement, AdvancementDisplay, AdvancementRegistry};
+use dolls_core::datatype::{decode_from_slice, Encode, Identifier, Uuid, VarInt};
+use dolls_core::date::now_millis;
+use dolls_core::item::{items, ItemStack};
+use dolls_core::text::TextComponent;
+use dolls_macros::packet_processor;
+use dolls_world::prelude::{advancement_data, level, AdvancementProgress, ANNOUNCE_ADVANCEMENTS};
+use crate::prelude::{broadcast_system_message, inventory_slots, unlock_recipes, ClientboundPacket, ClientboundPacketType, ConnectionHandle, ConnectionRegistry,
+    PacketContext, PacketType, RawPacket};
+
+/// Adds, removes and updates the progress of advancements shown to the client. The client shows a
+/// toast for advancements the update completes.
+#[derive(Debug, Clone, Default)]
+pub struct UpdateAdvancements {
+    /// Clears everything the client knew first.
+    pub reset: bool,
+    pub added: Vec<(Identifier, Advancement)>,
+    pub removed: Vec<Identifier>,
+    /// Every criterion of an advancement with the time it was met, in milliseconds since the epoch.
+    pub progress: Vec<(Identifier, BTreeMap<String, Option<i64>>)>,
+}
+
+impl ClientboundPacket for UpdateAdvancements {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::UpdateAdvancements;
+}
+
+impl Encode for UpdateAdvancements {
+    fn encode(&self, writer: &mut impl Write) -> io::Result<()> {
+        self.reset.encode(writer)?;
+        VarInt(self.added.len() as i32).encode(writer)?;
+        for (id, advancement) in &self.added {
+            id.encode(writer)?;
+            advancement.parent.encode(writer)?;
+            advancement.display.is_some().encode(writer)?;
+            if let Some(display) = &advancement.display {
+                encode_display(display, writer)?;
+            }
+            VarInt(advancement.requirements.len() as i32).encode(writer)?;
+            for group in &advancement.requirements {
+                group.encode(writer)?;
+            }
+            advancement.sends_telemetry_event.encode(writer)?;
+        }
+        self.removed.encode(writer)?;
+        VarInt(self.progress.len() as i32).encode(writer)?;
+        for (id, criteria) in &self.progress {
+            id.encode(writer)?;
+            VarInt(criteria.len() as i32).encode(writer)?;
+            for (name, achieved) in criteria {
+                name.encode(writer)?;
+                achieved.encode(writer)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn encode_display(display: &AdvancementDisplay, writer: &mut impl Write) -> io::Result<()> {
+    display.title.encode(writer)?;
+    display.description.encode(writer)?;
+    display.icon.encode(writer)?;
+    VarInt(display.frame as i32).encode(writer)?;
+    let flags = display.background.is_some() as i32 | (display.show_toast as i32) << 1 | (display.hidden as i32) << 2;
+    flags.encode(writer)?;
+    if let Some(background) = &display.background {
+        background.encode(writer)?;
+    }
+    display.x.encode(writer)?;
+    display.y.encode(writer)
+}
+
+/// Switches the advancements screen to the tab of a root advancement, `None` closes it.
+#[derive(Debug, Clone, Encode)]
+pub struct SelectAdvancementsTab {
+    pub tab: Option<Identifier>,
+}
+
+impl ClientboundPacket for SelectAdvancementsTab {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::SelectAdvancementsTab;
+}
+
+/// Told about every advancement a player completes, e.g. to announce it.
+pub trait AdvancementListener: Send + Sync {
+    fn on_advancement_done(&self, player: &ConnectionHandle, id: &Identifier, advancement: &Advancement);
+}
+
+static ADVANCEMENT_LISTENERS: Lazy<RwLock<Vec<Arc<dyn AdvancementListener>>>> = Lazy::new(|| RwLock::new(Vec::new()));
+
+pub fn register_advancement_listener(listener: Arc<dyn AdvancementListener>) {
+    ADVANCEMENT_LISTENERS.write().unwrap().push(listener);
+}
+
+/// Announces completed advancements which ask for it in everyone's chat, like vanilla.
+struct ChatAnnouncer(Arc<ConnectionRegistry>);
+
+impl AdvancementListener for ChatAnnouncer {
+    fn on_advancement_done(&self, player: &ConnectionHandle, _id: &Identifier, advancement: &Advancement) {
+        let Some(display) = advancement.display.as_ref().filter(|display| display.announce_to_chat) else { return };
+        if !level().read().unwrap().game_rules.get_bool(ANNOUNCE_ADVANCEMENTS) {
+            return;
+        }
+        let (color, key) = display.frame.announcement();
+        let title = TextComponent::text("[").append(display.title.clone()).append(TextComponent::text("]")).color(color);
+        let name = TextComponent::text(player.username().unwrap_or_default());
+        let message = TextComponent::translatable(key, vec![name, title]).fallback("%s has made the advancement %s");
+        let _ = broadcast_system_message(&self.0, message, false);
+    }
+}
+
+pub fn announce_advancements(connections: Arc<ConnectionRegistry>) {
+    register_advancement_listener(Arc::new(ChatAnnouncer(connections)));
+}
+
+/// A player's progress and what its client was told, kept in its connection's extensions.
+#[derive(Debug, Default)]
+struct PlayerAdvancements {
+    progress: AdvancementProgress,
+    /// Advancements the client knows about.
+    visible: BTreeSet<Identifier>,
+    /// Root of the tab the player has open in the advancements screen.
+    tab: Option<Identifier>,
+}
+
+impl PlayerAdvancements {
+    fn is_done(&self, id: &Identifier, advancement: &Advancement) -> bool {
+        let met = self.progress.get(id);
+        advancement.is_done(|criterion| met.is_some_and(|met| met.contains_key(criterion)))
+    }
+
+    /// Advancements with a display whose parents are shown, hidden ones only once done.
+    fn compute_visible(&self, registry: &AdvancementRegistry) -> BTreeSet<Identifier> {
+        let mut visible = BTreeSet::new();
+        for (id, advancement) in registry.iter() {
+            let Some(display) = &advancement.display else { continue };
+            let parent_visible = advancement.parent.as_ref().is_none_or(|parent| visible.contains(parent));
+            if parent_visible && (!display.hidden || self.is_done(id, advancement)) {
+                visible.insert(id.clone());
+            }
+        }
+        visible
+    }
+
+    fn criteria(&self, id: &Identifier, advancement: &Advancement) -> BTreeMap<String, Option<i64>> {
+        let met = self.progress.get(id);
+        advancement.criteria.keys()
+            .map(|name| (name.clone(), met.and_then(|met| met.get(name).copied())))
+            .collect()
+    }
+
+    /// The packet telling the client what changed about `changed` advancements, and which ones
+    /// were shown or hidden since the last update.
+    fn update(&mut self, registry: &AdvancementRegistry, changed: &[Identifier], reset: bool) -> UpdateAdvancements {
+        let visible = self.compute_visible(registry);
+        let added = visible.iter()
+            .filter(|id| reset || !self.visible.contains(*id))
+            .filter_map(|id| registry.get(id).map(|advancement| (id.clone(), advancement.clone())))
+            .collect::<Vec<_>>();
+        let removed = match reset {
+            true => Vec::new(),
+            false => self.visible.difference(&visible).cloned().collect(),
+        };
+        let progress = visible.iter()
+            .filter(|id| reset || changed.contains(id) || !self.visible.contains(*id))
+            .filter_map(|id| registry.get(id).map(|advancement| (id.clone(), self.criteria(id, advancement))))
+            .collect();
+        self.visible = visible;
+        UpdateAdvancements { reset, added, removed, progress }
+    }
+}
+
+fn save_progress(uuid: Option<Uuid>, progress: &AdvancementProgress) {
+    let Some(uuid) = uuid else { return };
+    let registry = advancements().read().unwrap();
+    let done = |id: &Identifier| registry.get(id).is_some_and(|advancement| {
+        let met = progress.get(id);
+        advancement.is_done(|criterion| met.is_some_and(|met| met.contains_key(criterion)))
+    });
+    if let Err(err) = advancement_data().read().unwrap().save(uuid, progress, done) {
+        warn!("Failed to save the advancements of {}: {}", uuid.hyphenated(), err);
+    }
+}
+
+/// Sends every advancement the joining player can see with its saved progress.
+pub(crate) fn send_advancements(context: &mut PacketContext) -> anyhow::Result<()> {
+    let progress = context.uuid.map(|uuid| advancement_data().read().unwrap().load(uuid).unwrap_or_else(|err| {
+        warn!("Failed to load the advancements of {}: {}", uuid.hyphenated(), err);
+        AdvancementProgress::new()
+    })).unwrap_or_default();
+    let mut player = PlayerAdvancements { progress, ..Default::default() };
+    let update = player.update(&advancements().read().unwrap(), &[], true);
+    context.send(&update)?;
+    context.connection.extensions(|extensions| extensions.insert(player));
+    Ok(())
+}
+
+/// Marks or clears criteria of advancements, then tells the client and hands out the rewards of
+/// advancements this completed. Returns how many criteria changed.
+fn change_criteria(connection: &ConnectionHandle, criteria: &[(Identifier, String)], grant: bool) -> anyhow::Result<usize> {
+    let registry = advancements().read().unwrap().clone();
+    let now = now_millis();
+    let changes = connection.extensions(|extensions| {
+        let player = extensions.get_or_default::<PlayerAdvancements>();
+        let mut changed = Vec::new();
+        let mut completed = Vec::new();
+        let mut count = 0;
+        for (id, criterion) in criteria {
+            let Some(advancement) = registry.get(id).filter(|advancement| advancement.criteria.contains_key(criterion)) else { continue };
+            let was_done = player.is_done(id, advancement);
+            let met = player.progress.entry(id.clone()).or_default();
+            let modified = match grant {
+                true => met.insert(criterion.clone(), now).is_none(),
+                false => met.remove(criterion).is_some(),
+            };
+            if met.is_empty() {
+                player.progress.remove(id);
+            }
+            if !modified {
+                continue;
+            }
+            count += 1;
+            if !changed.contains(id) {
+                changed.push(id.clone());
+            }
+            if !was_done && player.is_done(id, advancement) {
+                completed.push((id.clone(), advancement.clone()));
+            }
+        }
+        if count == 0 {
+            return None;
+        }
+        let update = player.update(&registry, &changed, false);
+        Some((count, update, completed, player.progress.clone()))
+    });
+    let Some((count, update, completed, progress)) = changes else { return Ok(0) };
+    save_progress(connection.uuid(), &progress);
+    connection.send(&update)?;
+
+    let listeners = ADVANCEMENT_LISTENERS.read().unwrap().clone();
+    for (id, advancement) in completed {
+        // Experience rewards wait for players to have experience.
+        if !advancement.rewards.recipes.is_empty() {
+            unlock_recipes(connection, &advancement.rewards.recipes)?;
+        }
+        for listener in &listeners {
+            listener.on_advancement_done(connection, &id, &advancement);
+        }
+    }
+    Ok(count)
+}
+
+/// Meets a criterion of an advancement, returning whether it was not met before.
+pub fn grant_criterion(connection: &ConnectionHandle, advancement: &Identifier, criterion: &str) -> anyhow::Result<bool> {
+    Ok(change_criteria(connection, &[(advancement.clone(), criterion.to_string())], true)? > 0)
+}
+
+pub fn revoke_criterion(connection: &ConnectionHandle, advancement: &Identifier, criterion: &str) -> anyhow::Result<bool> {
+    Ok(change_criteria(connection, &[(advancement.clone(), criterion.to_string())], false)? > 0)
+}
+
+fn all_criteria(ids: &[Identifier]) -> Vec<(Identifier, String)> {
+    let registry = advancements().read().unwrap();
+    ids.iter()
+        .filter_map(|id| registry.get(id).map(|advancement| (id, advancement)))
+        .flat_map(|(id, advancement)| advancement.criteria.keys().map(|name| (id.clone(), name.clone())))
+        .collect()
+}
+
+/// Meets every criterion of the advancements, returning how many criteria were not met before.
+pub fn grant_advancements(connection: &ConnectionHandle, ids: &[Identifier]) -> anyhow::Result<usize> {
+    change_criteria(connection, &all_criteria(ids), true)
+}
+
+pub fn revoke_advancements(connection: &ConnectionHandle, ids: &[Identifier]) -> anyhow::Result<usize> {
+    change_criteria(connection, &all_criteria(ids), false)
+}
+
+pub fn is_advancement_done(connection: &ConnectionHandle, id: &Identifier) -> bool {
+    let registry = advancements().read().unwrap();
+    let Some(advancement) = registry.get(id) else { return false };
+    connection.extensions(|extensions| extensions.get::<PlayerAdvancements>().is_some_and(|player| player.is_done(id, advancement)))
+}
+
+/// Fires a criteria trigger such as `minecraft:inventory_changed`, meeting the criteria of that
+/// trigger whose conditions `matches` accepts.
+pub fn trigger_criteria(connection: &ConnectionHandle, trigger: &Identifier, matches: impl Fn(&Value) -> bool) -> anyhow::Result<usize> {
+    let pending = {
+        let registry = advancements().read().unwrap();
+        let met = connection.extensions(|extensions| extensions.get::<PlayerAdvancements>().map(|player| player.progress.clone())).unwrap_or_default();
+        registry.with_trigger(trigger)
+            .filter(|(id, name, _)| !met.get(*id).is_some_and(|met| met.contains_key(*name)))
+            .filter(|(_, _, criterion)| matches(&criterion.conditions))
+            .map(|(id, name, _)| (id.clone(), name.to_string()))
+            .collect::<Vec<_>>()
+    };
+    if pending.is_empty() {
+        return Ok(0);
+    }
+    change_criteria(connection, &pending, true)
+}
+
+/// `minecraft:recipe_unlocked`, for criteria naming one of `recipes`.
+pub(crate) fn trigger_recipes_unlocked(connection: &ConnectionHandle, recipes: &[Identifier]) -> anyhow::Result<()> {
+    trigger_criteria(connection, &Identifier::minecraft("recipe_unlocked"), |conditions| {
+        conditions.get("recipe").and_then(Value::as_str)
+            .and_then(|recipe| recipe.parse::<Identifier>().ok())
+            .is_some_and(|recipe| recipes.contains(&recipe))
+    })?;
+    Ok(())
+}
+
+/// `minecraft:inventory_changed`, for criteria whose item predicates the inventory satisfies.
+/// Predicates naming item tags or components never match.
+pub(crate) fn trigger_inventory_changed(connection: &ConnectionHandle) -> anyhow::Result<()> {
+    let trigger = Identifier::minecraft("inventory_changed");
+    if advancements().read().unwrap().with_trigger(&trigger).next().is_none() {
+        return Ok(());
+    }
+    let slots = inventory_slots(connection);
+    trigger_criteria(connection, &trigger, |conditions| {
+        let predicates = conditions.get("items").and_then(Value::as_array).map_or(&[][..], Vec::as_slice);
+        predicates.iter().all(|predicate| matches_item_predicate(predicate, &slots))
+    })?;
+    Ok(())
+}
+
+fn matches_item_predicate(predicate: &Value, slots: &[ItemStack]) -> bool {
+    let names = match predicate.get("items") {
+        Some(Value::String(name)) => vec![name.as_str()],
+        Some(Value::Array(names)) => names.iter().filter_map(Value::as_str).collect(),
+        _ => return false,
+    };
+    let registry = items().read().unwrap();
+    let Some(item_ids) = names.iter()
+        .map(|name| name.parse::<Identifier>().ok().and_then(|name| registry.id(&name)))
+        .collect::<Option<Vec<_>>>() else { return false };
+    let min = match predicate.get("count") {
+        Some(Value::Number(count)) => count.as_i64().unwrap_or(1),
+        Some(count) => count.get("min").and_then(Value::as_i64).unwrap_or(1),
+        None => 1,
+    };
+    slots.iter().any(|stack| item_ids.contains(&stack.item_id) && stack.count as i64 >= min)
+}
+
+#[packet_processor(PacketType::SeenAdvancements)]
+pub(crate) fn seen_advancements_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    let mut payload = packet.payload.as_slice();
+    let action = decode_from_slice::<VarInt>(payload)?;
+    payload = &payload[action.written_size()..];
+    let tab = match action.0 {
+        0 => Some(decode_from_slice::<Identifier>(payload)?),
+        1 => None,
+        action => anyhow::bail!("Unknown advancements screen action {}", action),
+    };
+    let Some(tab) = tab else {
+        context.connection.extensions(|extensions| extensions.get_or_default::<PlayerAdvancements>().tab = None);
+        return Ok(());
+    };
+    let selected = context.connection.extensions(|extensions| {
+        let player = extensions.get_or_default::<PlayerAdvancements>();
+        let known = player.visible.contains(&tab);
+        if known {
+            player.tab = Some(tab.clone());
+        }
+        known
+    });
+    if selected {
+        context.send(&SelectAdvancementsTab { tab: Some(tab) })?;
+    }
+    Ok(())
+}
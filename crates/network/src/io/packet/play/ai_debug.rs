@@ -0,0 +1,82 @@
+use std::sync::Arc;
+use dolls_core::datatype::BlockPos;
+use dolls_core::particle::Particle;
+use dolls_entities::prelude::{entities, AiPath, Vec3};
+use dolls_tick::prelude::{scheduler, TaskGuard};
+use crate::prelude::{player_dimension, player_position, ConnectionHandle, ConnectionRegistry, ParticleEffect, PARTICLE_RANGE};
+
+/// Paths are drawn this often, the dust particles last about as long.
+const AI_DEBUG_INTERVAL: u64 = 10;
+/// Nodes the entity already walked past.
+const WALKED_COLOR: u32 = 0x808080;
+/// The node the entity walks to.
+const NEXT_COLOR: u32 = 0x00FF00;
+const PLANNED_COLOR: u32 = 0x3070FF;
+/// The end of the path.
+const TARGET_COLOR: u32 = 0xFF2020;
+
+/// Marks a player who turned on `debug ai`.
+struct AiDebugViewer;
+
+/// Shows a player the paths of the entities around it, or stops doing so.
+pub fn set_ai_debug(connection: &ConnectionHandle, enabled: bool) {
+    connection.extensions(|extensions| match enabled {
+        true => { extensions.insert(AiDebugViewer); }
+        false => { extensions.remove::<AiDebugViewer>(); }
+    });
+}
+
+pub fn ai_debug_enabled(connection: &ConnectionHandle) -> bool {
+    connection.extensions(|extensions| extensions.get::<AiDebugViewer>().is_some())
+}
+
+/// Dust at the center of every node of `path`, colored by whether the entity walked past it yet.
+pub fn path_particles(path: &AiPath) -> Vec<(ParticleEffect, Vec3)> {
+    let last = path.nodes.len().saturating_sub(1);
+    path.nodes.iter().enumerate()
+        .map(|(index, node)| {
+            let color = match index {
+                index if index == last => TARGET_COLOR,
+                index if index < path.next => WALKED_COLOR,
+                index if index == path.next => NEXT_COLOR,
+                _ => PLANNED_COLOR,
+            };
+            (ParticleEffect::new(Particle::dust(color, 1.0)), node_center(*node))
+        })
+        .collect()
+}
+
+fn node_center(node: BlockPos) -> Vec3 {
+    Vec3::new(node.x as f64 + 0.5, node.y as f64 + 0.5, node.z as f64 + 0.5)
+}
+
+/// Draws the reported paths of entities within particle range for every player with `debug ai` on,
+/// returning how many particle packets were sent.
+pub fn tick_ai_debug(connections: &ConnectionRegistry) -> usize {
+    let viewers = connections.players().into_iter()
+        .filter(ai_debug_enabled)
+        .filter_map(|player| player_position(&player).map(|position| (position.vec3(), player)))
+        .collect::<Vec<_>>();
+    if viewers.is_empty() {
+        return 0;
+    }
+    let entities = entities().read().unwrap();
+    let mut sent = 0;
+    for (position, viewer) in viewers {
+        let dimension = player_dimension(&viewer);
+        let paths = entities.within(position, PARTICLE_RANGE)
+            .filter(|entity| entity.dimension == dimension)
+            .filter_map(|entity| entities.ai_debug_info(entity.id())?.path.as_ref());
+        for (effect, node) in paths.flat_map(path_particles) {
+            if viewer.spawn_particles(&effect, node).is_ok() {
+                sent += 1;
+            }
+        }
+    }
+    sent
+}
+
+/// Draws AI paths for `debug ai` viewers, dropping the guard stops it.
+pub fn start_ai_debug(connections: Arc<ConnectionRegistry>) -> TaskGuard {
+    scheduler().run_repeating("AI debug", 0, AI_DEBUG_INTERVAL, move || { tick_ai_debug(&connections); }).guard()
+}
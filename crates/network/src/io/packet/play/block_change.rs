@@ -0,0 +1,235 @@
+use anyhow::bail;
+use log::debug;
+use dolls_core::datatype::{decode_from_slice, BlockPos, Decode, Encode, VarInt};
+use dolls_core::item::ItemStack;
+use dolls_core::statistic::StatType;
+use dolls_core::text::TextComponent;
+use dolls_events::prelude::{event_bus, BlockBreakEvent, Event};
+use dolls_macros::packet_processor;
+use dolls_world::prelude::{blocks, BlockState, ChunkPos};
+use crate::prelude::{abilities, add_exhaustion, set_spawn_point, spawn_point, SpawnPoint, SystemChatMessage, broadcast_light_changes, chunk_viewers, drop_held_item, game_mode, held_item, increment_stat, player_dimension, player_position, swap_hands, ClientboundPacket, ClientboundPacketType, ConnectionHandle, ConnectionRegistry, BLOCK_BREAK_EXHAUSTION,
+    PacketContext, PacketType, PlayerPosition, RawPacket};
+
+/// How far players reach blocks in survival, as in vanilla.
+pub const BLOCK_REACH: f64 = 4.5;
+/// Slack on top of the reach for positions which lag behind the client.
+const REACH_TOLERANCE: f64 = 1.0;
+const EYE_HEIGHT: f64 = 1.62;
+const PLAYER_WIDTH: f64 = 0.6;
+const PLAYER_HEIGHT: f64 = 1.8;
+
+#[derive(Debug, Clone, Encode)]
+pub struct BlockUpdate {
+    pub location: BlockPos,
+    pub block_id: VarInt,
+}
+
+impl ClientboundPacket for BlockUpdate {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::BlockUpdate;
+}
+
+/// Ends the client's predictions up to `sequence`, blocks it changed revert to what the server sent.
+#[derive(Debug, Clone, Encode)]
+pub struct AcknowledgeBlockChange {
+    pub sequence: VarInt,
+}
+
+impl ClientboundPacket for AcknowledgeBlockChange {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::AcknowledgeBlockChange;
+}
+
+#[derive(Debug, Clone, Decode)]
+struct PlayerAction {
+    status: VarInt,
+    location: BlockPos,
+    _face: u8,
+    sequence: VarInt,
+}
+
+#[derive(Debug, Clone, Decode)]
+struct UseItemOn {
+    hand: VarInt,
+    location: BlockPos,
+    face: VarInt,
+    _cursor_x: f32,
+    _cursor_y: f32,
+    _cursor_z: f32,
+    _inside_block: bool,
+    sequence: VarInt,
+}
+
+const START_DIGGING: i32 = 0;
+const CANCEL_DIGGING: i32 = 1;
+const FINISH_DIGGING: i32 = 2;
+const DROP_ITEM_STACK: i32 = 3;
+const DROP_ITEM: i32 = 4;
+const SWAP_ITEM_IN_HAND: i32 = 6;
+const MAIN_HAND: i32 = 0;
+
+/// The block next to `position` on `face`, in the order of the protocol's faces: down, up, north, south, west, east.
+pub fn relative(position: BlockPos, face: i32) -> Option<BlockPos> {
+    let (x, y, z) = match face {
+        0 => (0, -1, 0),
+        1 => (0, 1, 0),
+        2 => (0, 0, -1),
+        3 => (0, 0, 1),
+        4 => (-1, 0, 0),
+        5 => (1, 0, 0),
+        _ => return None,
+    };
+    Some(BlockPos::new(position.x + x, position.y + y, position.z + z))
+}
+
+/// Whether a player at `position` can reach any part of the block at `block`.
+pub fn within_reach(position: &PlayerPosition, block: BlockPos) -> bool {
+    let eye = [position.x, position.y + EYE_HEIGHT, position.z];
+    let distance = eye.iter().zip([block.x, block.y, block.z])
+        .map(|(eye, min)| (eye - eye.clamp(min as f64, min as f64 + 1.0)).powi(2))
+        .sum::<f64>();
+    distance < (BLOCK_REACH + REACH_TOLERANCE).powi(2)
+}
+
+/// Whether the block at `block` overlaps the player's body.
+fn intersects_player(position: &PlayerPosition, block: BlockPos) -> bool {
+    let half = PLAYER_WIDTH / 2.0;
+    let overlaps = |from: f64, to: f64, min: i32| from < min as f64 + 1.0 && to > min as f64;
+    overlaps(position.x - half, position.x + half, block.x)
+        && overlaps(position.y, position.y + PLAYER_HEIGHT, block.y)
+        && overlaps(position.z - half, position.z + half, block.z)
+}
+
+/// Changes a block in the actor's dimension and tells every player who sees it, except `actor` which
+/// is told by [`resync`].
+fn change_block(connections: &ConnectionRegistry, actor: &ConnectionHandle, position: BlockPos, state: BlockState) -> anyhow::Result<()> {
+    let dimension = player_dimension(actor);
+    dimension.world().write().unwrap().set_block(position, state)?;
+    let update = BlockUpdate { location: position, block_id: VarInt(state.id() as i32) };
+    for viewer in chunk_viewers(connections, dimension, ChunkPos::of(position)) {
+        if viewer.id() != actor.id() {
+            let _ = viewer.send(&update);
+        }
+    }
+    broadcast_light_changes(connections);
+    Ok(())
+}
+
+/// Sends the actor what the server has at `positions`, then acknowledges its prediction so that
+/// rejected changes are reverted on the client.
+fn resync(context: &mut PacketContext, positions: &[BlockPos], sequence: VarInt) -> anyhow::Result<()> {
+    let world = player_dimension(&context.connection).world();
+    for position in positions {
+        if let Some(state) = world.read().unwrap().get_block(*position) {
+            context.send(&BlockUpdate { location: *position, block_id: VarInt(state.id() as i32) })?;
+        }
+    }
+    context.send(&AcknowledgeBlockChange { sequence })
+}
+
+/// Breaks the block at `location`, blocks without collision break as soon as digging starts since
+/// their hardness is not known, and so does any block for players with instant build.
+fn dig(context: &mut PacketContext, position: &PlayerPosition, status: i32, location: BlockPos) -> anyhow::Result<()> {
+    if !within_reach(position, location) {
+        debug!("{} tried to dig {:?} out of reach", context.connection.id(), location);
+        return Ok(());
+    }
+    let Some(state) = player_dimension(&context.connection).world().read().unwrap().get_block(location) else { return Ok(()) };
+    let info = blocks().read().unwrap().state(state).map(|info| (info.is_air, info.blocks_motion, info.block.clone()));
+    let instant = abilities(&context.connection).instant_build;
+    let breaks = match (status, &info) {
+        (_, Some((true, _, _))) => false,
+        (START_DIGGING, _) if instant => true,
+        (START_DIGGING, Some((_, blocks_motion, _))) => !*blocks_motion,
+        (START_DIGGING, None) => false,
+        (FINISH_DIGGING, _) => true,
+        _ => false,
+    };
+    if breaks {
+        let mut event = BlockBreakEvent {
+            connection_id: context.connection.id(),
+            username: context.username.clone().unwrap_or_default(),
+            position: location,
+            block: info.as_ref().map(|(_, _, block)| block.clone()),
+            state_id: state.0,
+            cancelled: false,
+        };
+        event_bus().emit(&mut event);
+        if event.is_cancelled() {
+            return Ok(());
+        }
+        change_block(&context.connections, &context.connection, location, BlockState::AIR)?;
+        add_exhaustion(&context.connection, BLOCK_BREAK_EXHAUSTION);
+        if let Some((_, _, block)) = info.filter(|_| !instant) {
+            increment_stat(&context.connection, StatType::Mined, &block, 1);
+        }
+    }
+    Ok(())
+}
+
+/// Clicking a bed or a charged respawn anchor makes it the player's spawn point. Returns whether the
+/// block was one of them.
+fn use_spawn_block(context: &mut PacketContext, location: BlockPos, yaw: f32) -> anyhow::Result<bool> {
+    let Some(state) = player_dimension(&context.connection).world().read().unwrap().get_block(location) else { return Ok(false) };
+    let spawn_block = blocks().read().unwrap().state(state).is_some_and(|info| match info.block.path() {
+        "respawn_anchor" => info.properties.iter().any(|(name, value)| name == "charges" && value != "0"),
+        path => path.ends_with("_bed"),
+    });
+    if !spawn_block {
+        return Ok(false);
+    }
+    if spawn_point(&context.connection).is_none_or(|current| current.position != location) {
+        set_spawn_point(&context.connection, Some(SpawnPoint { angle: yaw, ..SpawnPoint::new(location) }));
+        context.send(&SystemChatMessage { content: TextComponent::translatable("block.minecraft.set_spawn", vec![]).fallback("Respawn point set"), overlay: false })?;
+    }
+    Ok(true)
+}
+
+#[packet_processor(PacketType::PlayerAction)]
+pub(crate) fn player_action_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    let PlayerAction { status, location, sequence, .. } = decode_from_slice(&packet.payload)?;
+    match status.0 {
+        START_DIGGING | CANCEL_DIGGING | FINISH_DIGGING => {}
+        DROP_ITEM_STACK | DROP_ITEM => return drop_held_item(&context.connection, status.0 == DROP_ITEM_STACK),
+        SWAP_ITEM_IN_HAND => return swap_hands(&context.connection),
+        // Eating, drawing bows and the like are not handled.
+        _ => return Ok(()),
+    }
+    let may_build = game_mode(&context.connection).may_build();
+    if let Some(position) = player_position(&context.connection).filter(|_| may_build) {
+        dig(context, &position, status.0, location)?;
+    }
+    resync(context, &[location], sequence)
+}
+
+#[packet_processor(PacketType::UseItemOn)]
+pub(crate) fn use_item_on_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    let UseItemOn { hand, location, face, sequence, .. } = decode_from_slice(&packet.payload)?;
+    let Some(target) = relative(location, face.0) else { bail!("Invalid block face {}", face.0) };
+    let Some(position) = player_position(&context.connection) else { return resync(context, &[location, target], sequence) };
+    if hand.0 == MAIN_HAND && within_reach(&position, location) && use_spawn_block(context, location, position.yaw)? {
+        return resync(context, &[location, target], sequence);
+    }
+    let stack = match hand.0 {
+        MAIN_HAND => held_item(&context.connection),
+        _ => ItemStack::EMPTY,
+    };
+    let placed = match stack.is_empty() {
+        true => None,
+        false => blocks().read().unwrap().item_block(stack.item_id),
+    };
+    let may_build = game_mode(&context.connection).may_build();
+    if let Some(state) = placed.filter(|_| may_build && within_reach(&position, location)) {
+        // Clicking into a replaceable block like air puts the new one there instead of next to it.
+        let world = player_dimension(&context.connection).world();
+        let clicked = world.read().unwrap().get_block(location);
+        let target = match clicked.is_some_and(BlockState::is_air) {
+            true => location,
+            false => target,
+        };
+        let free = world.read().unwrap().get_block(target).is_some_and(BlockState::is_air);
+        let blocks_motion = blocks().read().unwrap().state(state).is_some_and(|info| info.blocks_motion);
+        if free && !(blocks_motion && intersects_player(&position, target)) {
+            change_block(&context.connections, &context.connection, target, state)?;
+        }
+    }
+    resync(context, &[location, target], sequence)
+}
@@ -0,0 +1,72 @@
+use anyhow::anyhow;
+use dolls_core::datatype::{Decode, Encode, VarInt};
+use dolls_macros::packet_processor;
+use crate::prelude::{ClientboundPacket, ClientboundPacketType, ConnectionHandle, ConnectionRegistry, PacketContext, PacketType, RawPacket};
+
+/// Makes the client view the world from another entity, or from its own player again.
+#[derive(Debug, Clone, Encode)]
+pub struct SetCamera {
+    pub camera_id: VarInt,
+}
+
+impl ClientboundPacket for SetCamera {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::SetCamera;
+}
+
+/// Entity a player is viewing through, only present while it is not their own.
+#[derive(Debug, Copy, Clone)]
+struct Camera {
+    target: i32,
+}
+
+impl ConnectionHandle {
+    /// Views the world through `entity_id`, for cutscenes or spectating. The player's own id restores the camera.
+    pub fn set_camera(&self, entity_id: i32) -> anyhow::Result<()> {
+        let own_id = self.entity_id().ok_or_else(|| anyhow!("Connection {} has no player entity", self.id()))?;
+        self.extensions(|extensions| {
+            if entity_id == own_id {
+                extensions.remove::<Camera>();
+            } else {
+                extensions.insert(Camera { target: entity_id });
+            }
+        });
+        self.send(&SetCamera { camera_id: VarInt(entity_id) })
+    }
+
+    /// Moves the camera back to the player, if it is elsewhere.
+    pub fn reset_camera(&self) -> anyhow::Result<()> {
+        match self.extensions(|extensions| extensions.remove::<Camera>()) {
+            Some(_) => self.set_camera(self.entity_id().unwrap_or_default()),
+            None => Ok(()),
+        }
+    }
+
+    /// Entity the player currently views through, `None` for their own.
+    pub fn camera(&self) -> Option<i32> {
+        self.extensions(|extensions| extensions.get::<Camera>().map(|camera| camera.target))
+    }
+}
+
+/// Restores the camera of players viewing through a player that left.
+pub(crate) fn release_spectators(left: &ConnectionHandle, connections: &ConnectionRegistry) {
+    let Some(entity_id) = left.entity_id() else { return };
+    for player in connections.players() {
+        if player.camera() == Some(entity_id) {
+            // The spectator may be disconnecting as well.
+            let _ = player.reset_camera();
+        }
+    }
+}
+
+/// Sneaking leaves the spectated entity, like in vanilla spectator mode.
+#[packet_processor(PacketType::PlayerInput)]
+pub(crate) fn player_input_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    let mut payload = packet.payload.as_slice();
+    let _sideways = f32::decode(&mut payload)?;
+    let _forward = f32::decode(&mut payload)?;
+    let flags = u8::decode(&mut payload)?;
+    if flags & 0x02 != 0 {
+        context.connection.reset_camera()?;
+    }
+    Ok(())
+}
@@ -0,0 +1,218 @@
+use std::sync::{Arc, RwLock};
+use anyhow::bail;
+use once_cell::sync::Lazy;
+use log::{info, warn};
+use dolls_core::datatype::{read_bounded_string, Decode, Encode, Identifier};
+use dolls_core::registry::{register_chat_type, ChatDecoration, ChatDecorationParameter, ChatType, ChatTypeBound};
+use dolls_core::text::TextComponent;
+use dolls_events::prelude::{event_bus, ChatEvent, Event};
+use dolls_macros::packet_processor;
+use crate::prelude::{apply_last_seen_update, broadcast_signed_chat, placeholders, team_formatted_name, unpack_chat_message, ClientboundPacket, ClientboundPacketType, ConnectionRegistry, LastSeenUpdate, MessageBody, MessageSignature, PacketContext, PacketType, PlaceholderContext, RawPacket};
+
+/// Unsigned chat line, or action bar text when `overlay` is set.
+#[derive(Debug, Clone, Encode)]
+pub struct SystemChatMessage {
+    pub content: TextComponent,
+    pub overlay: bool,
+}
+
+impl ClientboundPacket for SystemChatMessage {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::SystemChatMessage;
+}
+
+/// Unsigned player chat, decorated by the client according to its chat type.
+#[derive(Debug, Clone, Encode)]
+pub struct DisguisedChatMessage {
+    pub message: TextComponent,
+    pub chat_type: ChatTypeBound,
+}
+
+impl ClientboundPacket for DisguisedChatMessage {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::DisguisedChatMessage;
+}
+
+/// Chat mode from the client options.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum ChatVisibility {
+    #[default]
+    Full,
+    /// Only command feedback and other system messages.
+    System,
+    Hidden,
+}
+
+impl ChatVisibility {
+    pub fn from_id(id: i32) -> Self {
+        match id {
+            1 => ChatVisibility::System,
+            2 => ChatVisibility::Hidden,
+            _ => ChatVisibility::Full,
+        }
+    }
+}
+
+/// A chat message ready to be broadcast.
+#[derive(Debug, Clone)]
+pub struct ChatLine {
+    pub chat_type: Identifier,
+    pub sender_name: TextComponent,
+    pub content: TextComponent,
+}
+
+/// Turns what a player typed into the line everyone sees.
+pub trait ChatFormatter: Send + Sync {
+    /// Returning `None` drops the message.
+    fn format(&self, context: &PacketContext, message: &str) -> Option<ChatLine>;
+}
+
+/// Vanilla `<name> message` formatting.
+#[derive(Debug, Default)]
+pub struct DefaultChatFormatter;
+
+impl ChatFormatter for DefaultChatFormatter {
+    fn format(&self, context: &PacketContext, message: &str) -> Option<ChatLine> {
+        Some(ChatLine {
+            chat_type: Identifier::minecraft("chat"),
+            sender_name: team_formatted_name(context.username.as_deref()?),
+            content: TextComponent::text(message),
+        })
+    }
+}
+
+/// Formats chat from a template such as `[{online}] {player}: {message}`.
+///
+/// Placeholders are expanded in the template only, never in what the player typed.
+#[derive(Debug, Clone)]
+pub struct TemplateChatFormatter {
+    template: String,
+}
+
+impl TemplateChatFormatter {
+    /// Chat type which shows the formatted line as is.
+    pub const CHAT_TYPE: &'static str = "dolls:raw";
+
+    pub fn new(template: impl Into<String>) -> Self {
+        let chat_type: Identifier = Self::CHAT_TYPE.parse().expect("Valid identifier");
+        if dolls_core::registry::chat_type(&chat_type).is_none() {
+            let decoration = ChatDecoration::new("%s", &[ChatDecorationParameter::Content]);
+            register_chat_type(chat_type, ChatType::new(decoration.clone(), decoration));
+        }
+        Self { template: template.into() }
+    }
+}
+
+impl ChatFormatter for TemplateChatFormatter {
+    fn format(&self, context: &PacketContext, message: &str) -> Option<ChatLine> {
+        let placeholder_context = PlaceholderContext {
+            player: Some(&context.connection),
+            connections: &context.connections,
+            config: &context.config,
+        };
+        let (before, after) = self.template.split_once("{message}").unwrap_or((&self.template, ""));
+        let line = format!("{}{}{}",
+            placeholders().apply(before, &placeholder_context),
+            message,
+            placeholders().apply(after, &placeholder_context));
+        Some(ChatLine {
+            chat_type: Self::CHAT_TYPE.parse().expect("Valid identifier"),
+            sender_name: team_formatted_name(context.username.as_deref()?),
+            content: TextComponent::text(line),
+        })
+    }
+}
+
+static CHAT_FORMATTER: Lazy<RwLock<Arc<dyn ChatFormatter>>> = Lazy::new(|| RwLock::new(Arc::new(DefaultChatFormatter)));
+
+pub fn set_chat_formatter(formatter: Arc<dyn ChatFormatter>) {
+    *CHAT_FORMATTER.write().unwrap() = formatter;
+}
+
+/// Sends a chat line to every player who has chat enabled.
+pub fn broadcast_chat(connections: &ConnectionRegistry, line: &ChatLine) -> anyhow::Result<()> {
+    let Some(chat_type) = ChatTypeBound::registered(&line.chat_type, line.sender_name.clone(), None) else {
+        bail!("Chat type {} is not registered", line.chat_type);
+    };
+    info!("<{}> {}", line.sender_name.to_plain_text(), line.content.to_plain_text());
+    let packet = RawPacket::from_packet(&DisguisedChatMessage { message: line.content.clone(), chat_type })?;
+    for player in connections.players() {
+        if player.chat_visibility() == ChatVisibility::Full {
+            let _ = player.send_raw(RawPacket::new(packet.packet_id, packet.payload.clone()));
+        }
+    }
+    Ok(())
+}
+
+/// Sends a system message to every player, only action bar text reaches players who hid their chat.
+pub fn broadcast_system_message(connections: &ConnectionRegistry, content: TextComponent, overlay: bool) -> anyhow::Result<()> {
+    let packet = RawPacket::from_packet(&SystemChatMessage { content, overlay })?;
+    for player in connections.players() {
+        if overlay || player.chat_visibility() != ChatVisibility::Hidden {
+            let _ = player.send_raw(RawPacket::new(packet.packet_id, packet.payload.clone()));
+        }
+    }
+    Ok(())
+}
+
+fn is_allowed_chat_character(c: char) -> bool {
+    c != '\u{a7}' && c >= ' ' && c != '\u{7f}'
+}
+
+/// Signed chat is verified and relayed as such, other chat is only accepted while secure profiles are not enforced.
+#[packet_processor(PacketType::ChatMessage)]
+pub(crate) fn chat_message_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    let mut payload = packet.payload.as_slice();
+    let message = read_bounded_string(&mut payload, 256)?;
+    let timestamp = i64::decode(&mut payload)?;
+    let salt = i64::decode(&mut payload)?;
+    let signature = Option::<MessageSignature>::decode(&mut payload)?;
+    let last_seen = LastSeenUpdate::decode(&mut payload)?;
+    if !message.chars().all(is_allowed_chat_character) {
+        bail!("Illegal characters in chat from {:?}", context.username);
+    }
+    if context.client_information.as_ref().is_some_and(|information| information.chat_visibility() == ChatVisibility::Hidden) {
+        return context.send(&SystemChatMessage {
+            content: TextComponent::translatable("chat.disabled.options", vec![]).color("red"),
+            overlay: false,
+        });
+    }
+
+    let last_seen = apply_last_seen_update(&context.connection, &last_seen)?;
+    let body = MessageBody { content: message, timestamp, salt, last_seen };
+    let signed = match unpack_chat_message(context, body.clone(), signature) {
+        Ok(signed) => signed,
+        Err(rejection) => {
+            if rejection.chain_broken {
+                warn!("Refusing signed chat from {:?} until its next chat session: {}", context.username, rejection.reason);
+            }
+            return context.send(&SystemChatMessage {
+                content: TextComponent::translatable(rejection.reason, vec![]).color("red"),
+                overlay: false,
+            });
+        }
+    };
+
+    let mut event = ChatEvent {
+        connection_id: context.connection.id(),
+        username: context.username.clone().unwrap_or_default(),
+        uuid: context.uuid.unwrap_or_default(),
+        message: body.content.clone(),
+        cancelled: false,
+    };
+    event_bus().emit(&mut event);
+    if event.is_cancelled() {
+        return Ok(());
+    }
+    // The signature only covers what the player wrote.
+    let signed = if event.message == body.content { signed } else { None };
+
+    let formatter = CHAT_FORMATTER.read().unwrap().clone();
+    let Some(line) = formatter.format(context, &event.message) else { return Ok(()) };
+    let result = match &signed {
+        Some(signed) => broadcast_signed_chat(&context.connections, signed, &line),
+        None => broadcast_chat(&context.connections, &line),
+    };
+    if let Err(err) = result {
+        warn!("Failed to broadcast chat from {:?}: {}", context.username, err);
+    }
+    Ok(())
+}
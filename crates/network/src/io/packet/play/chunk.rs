@@ -0,0 +1,136 @@
+use std::io;
+use dolls_core::datatype::{BitSet, Encode, Identifier, VarInt};
+use dolls_core::nbt::{NbtCompound, NbtTag};
+use dolls_core::registry::registries;
+use dolls_world::prelude::{encode_paletted, pack_indices, Chunk, HeightmapKind, LightKind, PaletteStrategy};
+use crate::prelude::{ClientboundPacket, ClientboundPacketType};
+
+/// Bytes of the light of one section, a nibble per block.
+pub const LIGHT_ARRAY_SIZE: usize = 2048;
+
+/// Light of a chunk's sections plus one section below and one above the world.
+/// Bit `i` of a mask is the `i`-th of those sections, counted from the bottom.
+#[derive(Debug, Clone, Default, PartialEq, Encode)]
+pub struct LightData {
+    pub sky_light_mask: BitSet,
+    pub block_light_mask: BitSet,
+    /// Sections known to have no light, as opposed to sections whose light is not sent.
+    pub empty_sky_light_mask: BitSet,
+    pub empty_block_light_mask: BitSet,
+    /// One array of [`LIGHT_ARRAY_SIZE`] bytes per bit set in `sky_light_mask`.
+    pub sky_light: Vec<Vec<u8>>,
+    pub block_light: Vec<Vec<u8>>,
+}
+
+impl LightData {
+    /// Full sky light everywhere and no block light, for chunks without computed light.
+    pub fn full_sky(sections: usize) -> Self {
+        let light_sections = sections + 2;
+        Self {
+            sky_light_mask: (0..light_sections).collect(),
+            block_light_mask: BitSet::new(),
+            empty_sky_light_mask: BitSet::new(),
+            empty_block_light_mask: (0..light_sections).collect(),
+            sky_light: vec![vec![0xFF; LIGHT_ARRAY_SIZE]; light_sections],
+            block_light: Vec::new(),
+        }
+    }
+
+    /// The light the world's light engine computed for `chunk`.
+    pub fn of(chunk: &Chunk) -> Self {
+        let mut light = Self::default();
+        for kind in LightKind::ALL {
+            let (mask, empty_mask, arrays) = match kind {
+                LightKind::Sky => (&mut light.sky_light_mask, &mut light.empty_sky_light_mask, &mut light.sky_light),
+                LightKind::Block => (&mut light.block_light_mask, &mut light.empty_block_light_mask, &mut light.block_light),
+            };
+            for (index, section) in chunk.light().sections(kind).iter().enumerate() {
+                match section.is_dark() {
+                    true => empty_mask.set(index, true),
+                    false => {
+                        mask.set(index, true);
+                        arrays.push(section.to_bytes());
+                    }
+                }
+            }
+        }
+        light
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Encode)]
+pub struct ChunkBlockEntity {
+    /// Section-relative x in the upper four bits, z in the lower four.
+    pub packed_xz: u8,
+    pub y: i16,
+    pub block_entity_type: VarInt,
+    pub data: NbtTag,
+}
+
+#[derive(Debug, Clone, PartialEq, Encode)]
+pub struct ChunkDataAndUpdateLight {
+    pub chunk_x: i32,
+    pub chunk_z: i32,
+    pub heightmaps: NbtCompound,
+    /// Every section from the bottom up, see [`encode_chunk_sections`].
+    pub data: Vec<u8>,
+    pub block_entities: Vec<ChunkBlockEntity>,
+    pub light: LightData,
+}
+
+impl ClientboundPacket for ChunkDataAndUpdateLight {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::ChunkDataAndUpdateLight;
+}
+
+impl ChunkDataAndUpdateLight {
+    pub fn new(chunk: &Chunk, light: LightData) -> io::Result<Self> {
+        Ok(Self {
+            chunk_x: chunk.position.x,
+            chunk_z: chunk.position.z,
+            heightmaps: heightmaps_nbt(chunk),
+            data: encode_chunk_sections(chunk)?,
+            block_entities: Vec::new(),
+            light,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Encode)]
+pub struct UpdateLight {
+    pub chunk_x: VarInt,
+    pub chunk_z: VarInt,
+    pub light: LightData,
+}
+
+impl ClientboundPacket for UpdateLight {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::UpdateLight;
+}
+
+impl UpdateLight {
+    pub fn of(chunk: &Chunk) -> Self {
+        Self { chunk_x: VarInt(chunk.position.x), chunk_z: VarInt(chunk.position.z), light: LightData::of(chunk) }
+    }
+}
+
+/// The heightmaps clients use, packed like in chunk NBT.
+pub fn heightmaps_nbt(chunk: &Chunk) -> NbtCompound {
+    let bits = u32::BITS - chunk.height().leading_zeros();
+    HeightmapKind::ALL.into_iter()
+        .fold(NbtCompound::new(), |heightmaps, kind| heightmaps.with(kind.name(), pack_indices(chunk.heightmap(kind).heights(), bits)))
+}
+
+/// Sections as in Chunk Data: non-air block count, block states and biomes, each a paletted container.
+pub fn encode_chunk_sections(chunk: &Chunk) -> io::Result<Vec<u8>> {
+    let biome_count = registries().read().unwrap().opaque.iter()
+        .find(|registry| *registry.id() == Identifier::minecraft("worldgen/biome"))
+        .map_or(1, |biomes| biomes.len());
+    let biome_strategy = PaletteStrategy::biomes(biome_count);
+
+    let mut data = Vec::new();
+    for section in chunk.sections() {
+        (section.block_count() as i16).encode(&mut data)?;
+        encode_paletted(&mut data, section.blocks(), &PaletteStrategy::BLOCKS, |state| state.id() as u32)?;
+        encode_paletted(&mut data, section.biomes(), &biome_strategy, |biome| biome as u32)?;
+    }
+    Ok(data)
+}
@@ -0,0 +1,330 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use once_cell::sync::Lazy;
+use log::error;
+use dolls_core::datatype::{decode_from_slice, Encode, VarInt};
+use dolls_macros::packet_processor;
+use dolls_tick::prelude::{scheduler, TaskGuard};
+use dolls_world::prelude::{ChunkPos, Dimension};
+use crate::prelude::{awaiting_teleport, player_dimension, player_position, spawn_loaded_entities, unload_entities, ChunkDataAndUpdateLight, ClientboundPacket, ClientboundPacketType,
+    ConnectionHandle, ConnectionRegistry, LightData, PacketContext, PacketType, RawPacket, UpdateLight};
+
+/// Smallest view distance, clients asking for less still get this many chunks.
+pub const MIN_VIEW_DISTANCE: u32 = 2;
+/// Chunks per tick until the client reported what it can handle, as in vanilla.
+const INITIAL_CHUNKS_PER_TICK: f32 = 9.0;
+const MAX_CHUNKS_PER_TICK: f32 = 64.0;
+const MIN_CHUNKS_PER_TICK: f32 = 0.01;
+/// Batches in flight before the client acknowledged any, and afterwards.
+const INITIAL_UNACKNOWLEDGED_BATCHES: u32 = 1;
+const MAX_UNACKNOWLEDGED_BATCHES: u32 = 10;
+
+#[derive(Debug, Clone, Encode)]
+pub struct SetCenterChunk {
+    pub chunk_x: VarInt,
+    pub chunk_z: VarInt,
+}
+
+impl ClientboundPacket for SetCenterChunk {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::SetCenterChunk;
+}
+
+/// Z comes first on the wire.
+#[derive(Debug, Clone, Encode)]
+pub struct UnloadChunk {
+    pub chunk_z: i32,
+    pub chunk_x: i32,
+}
+
+impl ClientboundPacket for UnloadChunk {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::UnloadChunk;
+}
+
+#[derive(Debug, Clone, Encode)]
+pub struct ChunkBatchStart;
+
+impl ClientboundPacket for ChunkBatchStart {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::ChunkBatchStart;
+}
+
+#[derive(Debug, Clone, Encode)]
+pub struct ChunkBatchFinished {
+    pub batch_size: VarInt,
+}
+
+impl ClientboundPacket for ChunkBatchFinished {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::ChunkBatchFinished;
+}
+
+/// Flow control of chunk batches, mirroring vanilla's `PlayerChunkSender`.
+#[derive(Debug)]
+struct BatchState {
+    /// What the client last reported it can take.
+    desired_per_tick: f32,
+    /// Chunks which may be sent, refilled every tick.
+    quota: f32,
+    unacknowledged: u32,
+    max_unacknowledged: u32,
+}
+
+impl Default for BatchState {
+    fn default() -> Self {
+        Self {
+            desired_per_tick: INITIAL_CHUNKS_PER_TICK,
+            quota: 0.0,
+            unacknowledged: 0,
+            max_unacknowledged: INITIAL_UNACKNOWLEDGED_BATCHES,
+        }
+    }
+}
+
+/// Chunks a player was sent and still lacks, kept in its connection's extensions.
+#[derive(Debug)]
+struct ChunkView {
+    /// Dimension the sent chunks belong to.
+    dimension: Dimension,
+    /// `None` until the first update.
+    center: Option<ChunkPos>,
+    distance: u32,
+    /// The configured view distance, the client may ask for less.
+    server_distance: u32,
+    sent: HashSet<ChunkPos>,
+    /// Chunks in view which were not sent yet, nearest first.
+    pending: Vec<ChunkPos>,
+    batch: BatchState,
+    _task: TaskGuard,
+}
+
+/// How many players were sent each chunk, chunks nobody sees are unloaded from their dimension.
+static VIEWERS: Lazy<Mutex<HashMap<(Dimension, ChunkPos), usize>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Whether `position` is within `distance` of `center`, the round area vanilla uses plus a ring of
+/// chunks around it which clients need to render the edge.
+pub fn is_in_view(center: ChunkPos, distance: u32, position: ChunkPos) -> bool {
+    let dx = ((position.x - center.x).unsigned_abs() as u64).saturating_sub(2);
+    let dz = ((position.z - center.z).unsigned_abs() as u64).saturating_sub(2);
+    dx * dx + dz * dz < distance as u64 * distance as u64
+}
+
+/// Every chunk in view of `center`, nearest first.
+pub fn chunks_in_view(center: ChunkPos, distance: u32) -> Vec<ChunkPos> {
+    let radius = distance as i32 + 2;
+    let mut chunks = (-radius..=radius)
+        .flat_map(|dz| (-radius..=radius).map(move |dx| ChunkPos::new(center.x + dx, center.z + dz)))
+        .filter(|position| is_in_view(center, distance, *position))
+        .collect::<Vec<_>>();
+    chunks.sort_by_key(|position| {
+        let (dx, dz) = ((position.x - center.x) as i64, (position.z - center.z) as i64);
+        dx * dx + dz * dz
+    });
+    chunks
+}
+
+/// The server's view distance, lowered to what the client asked for.
+fn view_distance(connection: &ConnectionHandle, server_distance: u32) -> u32 {
+    let client = connection.info().client_information
+        .map_or(server_distance, |information| information.view_distance.max(0) as u32);
+    server_distance.min(client).max(MIN_VIEW_DISTANCE)
+}
+
+/// The chunk as sent to clients, loaded or created in its dimension if needed.
+fn chunk_packet(dimension: Dimension, position: ChunkPos) -> anyhow::Result<ChunkDataAndUpdateLight> {
+    let mut world = dimension.world().write().unwrap();
+    let chunk = world.load_or_create_chunk(position)?;
+    Ok(ChunkDataAndUpdateLight::new(chunk, LightData::of(chunk))?)
+}
+
+/// Starts streaming chunks around the player every tick, once its client confirmed where it is.
+pub fn start_chunk_view(context: &mut PacketContext) {
+    let (connection, connections) = (context.connection.clone(), context.connections.clone());
+    let task = scheduler().run_repeating(format!("Chunks of {}", connection.id()), 0, 1, move || {
+        tick_chunk_view(&connection);
+        broadcast_light_changes(&connections);
+    });
+    let view = ChunkView {
+        dimension: player_dimension(&context.connection),
+        center: None,
+        distance: 0,
+        server_distance: context.config.server.view_distance,
+        sent: HashSet::new(),
+        pending: Vec::new(),
+        batch: BatchState::default(),
+        _task: task.guard(),
+    };
+    context.connection.extensions(|extensions| extensions.insert(view));
+}
+
+/// Follows the player's movement and sends the next batch of chunks the quota allows.
+fn tick_chunk_view(connection: &ConnectionHandle) {
+    let Some(position) = player_position(connection) else { return };
+    let awaiting_teleport = awaiting_teleport(connection);
+    let info_distance = |server_distance| view_distance(connection, server_distance);
+    // Everything is sent with the view locked so that packets of one player's chunks stay in order.
+    let released = connection.extensions(|extensions| {
+        let view = extensions.get_mut::<ChunkView>()?;
+        if view.center.is_none() && awaiting_teleport {
+            // Chunks before Login or the first position would be dropped by the client.
+            return None;
+        }
+        let released = update_view(connection, view, position.chunk_pos(), info_distance(view.server_distance));
+        send_batch(connection, view);
+        Some((view.dimension, released))
+    });
+    if let Some((dimension, released)) = released {
+        spawn_loaded_entities(dimension);
+        release_chunks(dimension, &released);
+    }
+}
+
+/// Moves the view, unloading chunks out of it and queueing those which entered. Returns the unloaded ones.
+fn update_view(connection: &ConnectionHandle, view: &mut ChunkView, center: ChunkPos, distance: u32) -> Vec<ChunkPos> {
+    if view.center == Some(center) && view.distance == distance {
+        return Vec::new();
+    }
+    if view.center != Some(center) {
+        let _ = connection.send(&SetCenterChunk { chunk_x: VarInt(center.x), chunk_z: VarInt(center.z) });
+    }
+    view.center = Some(center);
+    view.distance = distance;
+
+    let left = view.sent.iter().copied().filter(|chunk| !is_in_view(center, distance, *chunk)).collect::<Vec<_>>();
+    for chunk in &left {
+        view.sent.remove(chunk);
+        let _ = connection.send(&UnloadChunk { chunk_z: chunk.z, chunk_x: chunk.x });
+    }
+    view.pending = chunks_in_view(center, distance).into_iter().filter(|chunk| !view.sent.contains(chunk)).collect();
+    left
+}
+
+fn send_batch(connection: &ConnectionHandle, view: &mut ChunkView) {
+    let batch = &mut view.batch;
+    batch.quota = (batch.quota + batch.desired_per_tick).min(batch.desired_per_tick.max(1.0));
+    if view.pending.is_empty() || batch.quota < 1.0 || batch.unacknowledged >= batch.max_unacknowledged {
+        return;
+    }
+    let count = (batch.quota as usize).min(view.pending.len());
+    batch.quota -= count as f32;
+    batch.unacknowledged += 1;
+
+    let _ = connection.send(&ChunkBatchStart);
+    let mut viewers = VIEWERS.lock().unwrap();
+    let mut sent = 0;
+    for chunk in view.pending.drain(..count) {
+        match chunk_packet(view.dimension, chunk) {
+            Ok(packet) => {
+                if connection.send(&packet).is_err() {
+                    break;
+                }
+            }
+            Err(err) => {
+                error!("Failed to load chunk {} {}: {:#}", chunk.x, chunk.z, err);
+                continue;
+            }
+        }
+        *viewers.entry((view.dimension, chunk)).or_default() += 1;
+        view.sent.insert(chunk);
+        sent += 1;
+    }
+    let _ = connection.send(&ChunkBatchFinished { batch_size: VarInt(sent) });
+}
+
+/// Drops a player's claim on chunks of a dimension, unloading those nobody sees anymore.
+fn release_chunks(dimension: Dimension, chunks: &[ChunkPos]) {
+    if chunks.is_empty() {
+        return;
+    }
+    let unused = {
+        let mut viewers = VIEWERS.lock().unwrap();
+        chunks.iter().copied()
+            .filter(|chunk| match viewers.get_mut(&(dimension, *chunk)) {
+                Some(count) if *count > 1 => {
+                    *count -= 1;
+                    false
+                }
+                _ => viewers.remove(&(dimension, *chunk)).is_some(),
+            })
+            .collect::<Vec<_>>()
+    };
+    if unused.is_empty() {
+        return;
+    }
+    unload_entities(dimension, &unused);
+    let mut world = dimension.world().write().unwrap();
+    for chunk in unused {
+        if let Err(err) = world.unload_chunk(chunk) {
+            error!("Failed to unload chunk {} {}: {:#}", chunk.x, chunk.z, err);
+        }
+    }
+}
+
+/// Whether the player was sent `chunk` of the dimension it is in.
+pub fn sees_chunk(connection: &ConnectionHandle, chunk: ChunkPos) -> bool {
+    connection.extensions(|extensions| extensions.get::<ChunkView>().is_some_and(|view| view.sent.contains(&chunk)))
+}
+
+/// Players who were sent `chunk` of `dimension` and should hear of changes to it.
+pub fn chunk_viewers(connections: &ConnectionRegistry, dimension: Dimension, chunk: ChunkPos) -> Vec<ConnectionHandle> {
+    connections.players().into_iter()
+        .filter(|player| player.extensions(|extensions| extensions.get::<ChunkView>()
+            .is_some_and(|view| view.dimension == dimension && view.sent.contains(&chunk))))
+        .collect()
+}
+
+/// Sends the new light of chunks whose light changed to the players who were sent them.
+pub fn broadcast_light_changes(connections: &ConnectionRegistry) {
+    for dimension in Dimension::ALL {
+        let changed = dimension.world().write().unwrap().take_light_changes();
+        // Viewers are looked up without the world locked, chunks are sent while holding both the other way around.
+        let viewed = changed.into_iter()
+            .map(|chunk| (chunk, chunk_viewers(connections, dimension, chunk)))
+            .filter(|(_, viewers)| !viewers.is_empty())
+            .collect::<Vec<_>>();
+        for (chunk, viewers) in viewed {
+            let Some(update) = dimension.world().read().unwrap().chunk(chunk).map(UpdateLight::of) else { continue };
+            for viewer in viewers {
+                let _ = viewer.send(&update);
+            }
+        }
+    }
+}
+
+/// Stops streaming to a leaving player and forgets what it was sent.
+pub(crate) fn stop_chunk_view(connection: &ConnectionHandle) {
+    if let Some(view) = connection.extensions(|extensions| extensions.remove::<ChunkView>()) {
+        release_chunks(view.dimension, &view.sent.into_iter().collect::<Vec<_>>());
+    }
+}
+
+/// Starts the view over in another dimension, the client dropped the chunks it had on Respawn.
+pub(crate) fn reset_chunk_view(connection: &ConnectionHandle, dimension: Dimension) {
+    let released = connection.extensions(|extensions| {
+        let view = extensions.get_mut::<ChunkView>()?;
+        let released = view.sent.drain().collect::<Vec<_>>();
+        let previous = std::mem::replace(&mut view.dimension, dimension);
+        view.center = None;
+        view.pending.clear();
+        Some((previous, released))
+    });
+    if let Some((previous, released)) = released {
+        release_chunks(previous, &released);
+    }
+}
+
+#[packet_processor(PacketType::ChunkBatchReceived)]
+pub(crate) fn chunk_batch_received_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    let chunks_per_tick: f32 = decode_from_slice(&packet.payload)?;
+    context.connection.extensions(|extensions| {
+        let Some(view) = extensions.get_mut::<ChunkView>() else { return };
+        let batch = &mut view.batch;
+        batch.unacknowledged = batch.unacknowledged.saturating_sub(1);
+        batch.desired_per_tick = match chunks_per_tick.is_nan() {
+            true => MIN_CHUNKS_PER_TICK,
+            false => chunks_per_tick.clamp(MIN_CHUNKS_PER_TICK, MAX_CHUNKS_PER_TICK),
+        };
+        if batch.unacknowledged == 0 {
+            batch.quota = 1.0;
+        }
+        batch.max_unacknowledged = MAX_UNACKNOWLEDGED_BATCHES;
+    });
+    Ok(())
+}
@@ -0,0 +1,182 @@
+use std::io::{self, Write};
+use std::sync::{Arc, RwLock};
+use anyhow::bail;
+use once_cell::sync::Lazy;
+use dolls_core::datatype::{read_bounded_string, Decode, Encode, Identifier, VarInt};
+use dolls_core::text::TextComponent;
+use dolls_macros::packet_processor;
+use crate::prelude::{apply_last_seen_update, ArgumentSignature, ClientboundPacket, ClientboundPacketType, PacketContext, LastSeenUpdate, PacketType, RawPacket, SystemChatMessage};
+
+/// Runs commands players type in chat, installed by the command system.
+pub trait ChatCommandHandler: Send + Sync {
+    /// `command` comes without the leading `/`.
+    fn execute(&self, context: &mut PacketContext, command: &str) -> anyhow::Result<()>;
+
+    /// Completions for the partial `command`, without the leading `/` as well.
+    fn suggest(&self, _context: &mut PacketContext, _command: &str) -> Suggestions {
+        Suggestions::default()
+    }
+}
+
+/// Completions replacing `length` bytes of the input from `start` on.
+#[derive(Debug, Clone, Default)]
+pub struct Suggestions {
+    pub start: usize,
+    pub length: usize,
+    pub matches: Vec<SuggestionMatch>,
+}
+
+#[derive(Debug, Clone, Encode)]
+pub struct SuggestionMatch {
+    pub text: String,
+    pub tooltip: Option<TextComponent>,
+}
+
+#[derive(Debug, Clone, Encode)]
+pub struct CommandSuggestionsResponse {
+    pub transaction_id: VarInt,
+    pub start: VarInt,
+    pub length: VarInt,
+    pub matches: Vec<SuggestionMatch>,
+}
+
+impl ClientboundPacket for CommandSuggestionsResponse {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::CommandSuggestionsResponse;
+}
+
+static CHAT_COMMAND_HANDLER: Lazy<RwLock<Option<Arc<dyn ChatCommandHandler>>>> = Lazy::new(|| RwLock::new(None));
+
+pub fn set_chat_command_handler(handler: Arc<dyn ChatCommandHandler>) {
+    *CHAT_COMMAND_HANDLER.write().unwrap() = Some(handler);
+}
+
+fn handle_chat_command(context: &mut PacketContext, command: &str) -> anyhow::Result<()> {
+    let handler = CHAT_COMMAND_HANDLER.read().unwrap().clone();
+    match handler {
+        Some(handler) => handler.execute(context, command),
+        None => context.send(&SystemChatMessage {
+            content: TextComponent::text("Commands are not available").color("red"),
+            overlay: false,
+        }),
+    }
+}
+
+#[packet_processor(PacketType::ChatCommand)]
+pub(crate) fn chat_command_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    let command = read_bounded_string(&mut packet.payload.as_slice(), 256)?;
+    handle_chat_command(context, &command)
+}
+
+/// Message arguments a signed command may carry.
+const MAX_ARGUMENT_SIGNATURES: usize = 8;
+
+/// No command declares message arguments, so argument signatures are only checked for their shape.
+#[packet_processor(PacketType::SignedChatCommand)]
+pub(crate) fn signed_chat_command_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    let mut payload = packet.payload.as_slice();
+    let command = read_bounded_string(&mut payload, 256)?;
+    let _timestamp = i64::decode(&mut payload)?;
+    let _salt = i64::decode(&mut payload)?;
+    let argument_signatures = Vec::<ArgumentSignature>::decode(&mut payload)?;
+    if argument_signatures.len() > MAX_ARGUMENT_SIGNATURES {
+        bail!("Too many argument signatures from {:?}", context.username);
+    }
+    let last_seen = LastSeenUpdate::decode(&mut payload)?;
+    apply_last_seen_update(&context.connection, &last_seen)?;
+    handle_chat_command(context, &command)
+}
+
+#[packet_processor(PacketType::CommandSuggestionsRequest)]
+pub(crate) fn command_suggestions_request_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    let mut payload = packet.payload.as_slice();
+    let transaction_id = VarInt::decode(&mut payload)?;
+    let text = read_bounded_string(&mut payload, 32500)?;
+    let Some(handler) = CHAT_COMMAND_HANDLER.read().unwrap().clone() else { return Ok(()) };
+
+    // Chat sends the slash along, command blocks do not.
+    let (offset, command) = match text.strip_prefix('/') {
+        Some(command) => (1, command),
+        None => (0, text.as_str()),
+    };
+    let suggestions = handler.suggest(context, command);
+    // Offsets are in UTF-16 code units on the client.
+    let utf16_len = |end: usize| command[..end].encode_utf16().count();
+    context.send(&CommandSuggestionsResponse {
+        transaction_id,
+        start: VarInt((offset + utf16_len(suggestions.start)) as i32),
+        length: VarInt((utf16_len(suggestions.start + suggestions.length) - utf16_len(suggestions.start)) as i32),
+        matches: suggestions.matches,
+    })
+}
+
+/// Node of the flattened command tree in the Commands packet.
+#[derive(Debug, Clone)]
+pub struct DeclaredNode {
+    pub kind: DeclaredNodeKind,
+    pub executable: bool,
+    /// Indices into the packet's node list.
+    pub children: Vec<VarInt>,
+    pub redirect: Option<VarInt>,
+}
+
+#[derive(Debug, Clone)]
+pub enum DeclaredNodeKind {
+    Root,
+    Literal(String),
+    Argument {
+        name: String,
+        /// Network id in the `minecraft:command_argument_type` registry.
+        parser: VarInt,
+        /// Parser specific properties, already encoded.
+        properties: Vec<u8>,
+        suggestions: Option<Identifier>,
+    },
+}
+
+impl Encode for DeclaredNode {
+    fn encode(&self, writer: &mut impl Write) -> io::Result<()> {
+        let mut flags: u8 = match self.kind {
+            DeclaredNodeKind::Root => 0,
+            DeclaredNodeKind::Literal(_) => 1,
+            DeclaredNodeKind::Argument { .. } => 2,
+        };
+        if self.executable {
+            flags |= 0x04;
+        }
+        if self.redirect.is_some() {
+            flags |= 0x08;
+        }
+        if matches!(self.kind, DeclaredNodeKind::Argument { suggestions: Some(_), .. }) {
+            flags |= 0x10;
+        }
+        flags.encode(writer)?;
+        self.children.encode(writer)?;
+        if let Some(redirect) = self.redirect {
+            redirect.encode(writer)?;
+        }
+        match &self.kind {
+            DeclaredNodeKind::Root => {}
+            DeclaredNodeKind::Literal(name) => name.encode(writer)?,
+            DeclaredNodeKind::Argument { name, parser, properties, suggestions } => {
+                name.encode(writer)?;
+                parser.encode(writer)?;
+                writer.write_all(properties)?;
+                if let Some(suggestions) = suggestions {
+                    suggestions.encode(writer)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The command tree a player may use, for client side parsing and completion.
+#[derive(Debug, Clone, Encode)]
+pub struct Commands {
+    pub nodes: Vec<DeclaredNode>,
+    pub root_index: VarInt,
+}
+
+impl ClientboundPacket for Commands {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::Commands;
+}
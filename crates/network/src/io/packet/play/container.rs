@@ -0,0 +1,114 @@
+use std::sync::{Arc, RwLock};
+use anyhow::bail;
+use once_cell::sync::Lazy;
+use dolls_core::datatype::{read_bounded_string, Decode, VarInt};
+use dolls_macros::packet_processor;
+use crate::prelude::{open_window_of, ConnectionHandle, OpenWindow, PacketContext, PacketType, RawPacket, WindowKind, WindowType};
+
+/// Network ids of the `minecraft:mob_effect` registry a beacon can grant.
+pub mod beacon_effects {
+    pub const SPEED: i32 = 0;
+    pub const HASTE: i32 = 2;
+    pub const STRENGTH: i32 = 4;
+    pub const JUMP_BOOST: i32 = 7;
+    pub const REGENERATION: i32 = 9;
+    pub const RESISTANCE: i32 = 10;
+
+    pub const PRIMARY: [i32; 5] = [SPEED, HASTE, RESISTANCE, JUMP_BOOST, STRENGTH];
+}
+
+/// Longest item name an anvil accepts.
+pub const MAX_ITEM_NAME_LENGTH: usize = 50;
+
+/// A validated interaction with a block menu.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ContainerAction {
+    SetBeaconEffect { primary: Option<i32>, secondary: Option<i32> },
+    RenameItem(String),
+    /// Enchantment option, stonecutter recipe, loom pattern or lectern page button.
+    ClickButton(i32),
+}
+
+/// Reacts to block menu interactions, e.g. to apply a beacon effect or an enchantment.
+pub trait ContainerListener: Send + Sync {
+    fn on_action(&self, connection: &ConnectionHandle, window: &OpenWindow, action: &ContainerAction) -> anyhow::Result<()>;
+}
+
+static CONTAINER_LISTENERS: Lazy<RwLock<Vec<Arc<dyn ContainerListener>>>> = Lazy::new(|| RwLock::new(Vec::new()));
+
+pub fn register_container_listener(listener: Arc<dyn ContainerListener>) {
+    CONTAINER_LISTENERS.write().unwrap().push(listener);
+}
+
+fn dispatch_action(context: &PacketContext, window: &OpenWindow, action: ContainerAction) -> anyhow::Result<()> {
+    let listeners = CONTAINER_LISTENERS.read().unwrap().clone();
+    for listener in listeners {
+        listener.on_action(&context.connection, window, &action)?;
+    }
+    Ok(())
+}
+
+/// The open window, provided it is a menu of `window_type`.
+fn expect_menu(context: &PacketContext, window_type: WindowType) -> anyhow::Result<OpenWindow> {
+    match open_window_of(&context.connection) {
+        Some(window) if window.kind == WindowKind::Menu(window_type) => Ok(window),
+        Some(window) => bail!("Expected a {:?} menu, but window {} is {:?}", window_type, window.id, window.kind),
+        None => bail!("Expected a {:?} menu, but no window is open", window_type),
+    }
+}
+
+/// Drops formatting codes and control characters like vanilla does for player supplied names.
+fn filter_text(text: &str) -> String {
+    text.chars().filter(|c| *c != '\u{a7}' && *c >= ' ' && *c != '\u{7f}').collect()
+}
+
+#[packet_processor(PacketType::SetBeaconEffect)]
+pub(crate) fn set_beacon_effect_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    let mut payload = packet.payload.as_slice();
+    let primary = Option::<VarInt>::decode(&mut payload)?.map(|effect| effect.0);
+    let secondary = Option::<VarInt>::decode(&mut payload)?.map(|effect| effect.0);
+    let window = expect_menu(context, WindowType::Beacon)?;
+
+    if primary.is_some_and(|effect| !beacon_effects::PRIMARY.contains(&effect)) {
+        bail!("Effect {:?} is not a beacon effect", primary);
+    }
+    // The secondary power is either regeneration or the primary effect amplified.
+    if let Some(effect) = secondary {
+        if effect != beacon_effects::REGENERATION && Some(effect) != primary {
+            bail!("Effect {} cannot be the secondary beacon effect with primary {:?}", effect, primary);
+        }
+    }
+    dispatch_action(context, &window, ContainerAction::SetBeaconEffect { primary, secondary })
+}
+
+#[packet_processor(PacketType::RenameItem)]
+pub(crate) fn rename_item_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    let name = read_bounded_string(&mut packet.payload.as_slice(), i16::MAX as usize)?;
+    let window = expect_menu(context, WindowType::Anvil)?;
+    let name = filter_text(&name);
+    if name.chars().count() > MAX_ITEM_NAME_LENGTH {
+        bail!("Item name is longer than {} characters", MAX_ITEM_NAME_LENGTH);
+    }
+    dispatch_action(context, &window, ContainerAction::RenameItem(name))
+}
+
+#[packet_processor(PacketType::ClickContainerButton)]
+pub(crate) fn click_container_button_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    let mut payload = packet.payload.as_slice();
+    let window_id = u8::decode(&mut payload)? as i32;
+    let button = u8::decode(&mut payload)? as i32;
+    let Some(window) = open_window_of(&context.connection).filter(|window| window.id == window_id) else {
+        bail!("Button clicked in window {} which is not open", window_id);
+    };
+
+    let valid = match window.kind {
+        WindowKind::Menu(WindowType::Enchantment) => button <= 2,
+        WindowKind::Menu(WindowType::Lectern) => (1..=3).contains(&button) || button >= 100,
+        WindowKind::Menu(WindowType::Stonecutter | WindowType::Loom) => true,
+        _ => false,
+    };
+    if !valid {
+        bail!("Invalid button {} for {:?}", button, window.kind);
+    }
+    dispatch_action(context, &window, ContainerAction::ClickButton(button))
+}
@@ -0,0 +1,77 @@
+use log::debug;
+use dolls_core::datatype::{decode_from_slice, Decode, Encode};
+use dolls_macros::packet_processor;
+use dolls_world::prelude::{level, Difficulty};
+use crate::prelude::{has_permission, ClientboundPacket, ClientboundPacketType, ConnectionRegistry, PacketContext, PacketType, RawPacket};
+
+/// Permission node for changing and locking the difficulty from the options screen.
+pub const DIFFICULTY_PERMISSION: &str = "minecraft.difficulty";
+
+/// Shows the difficulty on the options screen, locked difficulties cannot be changed there.
+#[derive(Debug, Clone, Encode)]
+pub struct ChangeDifficulty {
+    pub difficulty: u8,
+    pub locked: bool,
+}
+
+impl ClientboundPacket for ChangeDifficulty {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::ChangeDifficulty;
+}
+
+impl ChangeDifficulty {
+    fn current() -> Self {
+        let level = level().read().unwrap();
+        Self { difficulty: level.difficulty.id(), locked: level.difficulty_locked }
+    }
+}
+
+#[derive(Debug, Clone, Decode)]
+struct RequestDifficulty {
+    difficulty: u8,
+}
+
+#[derive(Debug, Clone, Decode)]
+struct LockDifficulty {
+    locked: bool,
+}
+
+/// Sends a joining player the difficulty.
+pub(crate) fn send_difficulty(context: &mut PacketContext) -> anyhow::Result<()> {
+    context.send(&ChangeDifficulty::current())
+}
+
+/// Changes the difficulty unless it is locked, returns whether it changed.
+pub fn set_difficulty(connections: &ConnectionRegistry, difficulty: Difficulty) -> anyhow::Result<bool> {
+    {
+        let mut level = level().write().unwrap();
+        if level.difficulty_locked || level.difficulty == difficulty {
+            return Ok(false);
+        }
+        level.difficulty = difficulty;
+    }
+    connections.broadcast(&ChangeDifficulty::current())?;
+    Ok(true)
+}
+
+#[packet_processor(PacketType::ChangeDifficulty)]
+pub(crate) fn change_difficulty_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    let RequestDifficulty { difficulty } = decode_from_slice(&packet.payload)?;
+    let Some(difficulty) = Difficulty::from_id(difficulty) else { return Ok(()) };
+    if !has_permission(&context.connection, DIFFICULTY_PERMISSION, 2) {
+        debug!("{} may not change the difficulty", context.connection.id());
+        return Ok(());
+    }
+    set_difficulty(&context.connections, difficulty)?;
+    Ok(())
+}
+
+#[packet_processor(PacketType::LockDifficulty)]
+pub(crate) fn lock_difficulty_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    let LockDifficulty { locked } = decode_from_slice(&packet.payload)?;
+    if !locked || !has_permission(&context.connection, DIFFICULTY_PERMISSION, 2) {
+        debug!("{} may not lock the difficulty", context.connection.id());
+        return Ok(());
+    }
+    level().write().unwrap().difficulty_locked = true;
+    context.connections.broadcast(&ChangeDifficulty::current())
+}
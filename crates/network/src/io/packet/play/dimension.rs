@@ -0,0 +1,64 @@
+use dolls_core::datatype::VarInt;
+use dolls_entities::prelude::entities;
+use dolls_world::prelude::{level, Dimension};
+use crate::prelude::{abilities, death_location, dimension_type_id, forget_tracked, game_mode, previous_game_mode, reset_chunk_view, resend_vitals, send_inventory,
+    send_time_and_weather, send_world_border, send_world_spawn, teleport, ConnectionHandle, GameEvent, PacketContext, PlayerPosition, Respawn,
+    KEEP_ATTRIBUTES, KEEP_METADATA};
+
+/// The dimension a player is in, kept in its connection's extensions. Players join in the overworld.
+#[derive(Debug, Clone, Copy)]
+struct PlayerDimension(Dimension);
+
+pub fn player_dimension(connection: &ConnectionHandle) -> Dimension {
+    connection.extensions(|extensions| extensions.get::<PlayerDimension>().map(|dimension| dimension.0)).unwrap_or_default()
+}
+
+/// Sends Respawn, which makes the client recreate its player in `dimension`. `data_kept` is a mix of
+/// [`KEEP_ATTRIBUTES`] and [`KEEP_METADATA`].
+pub(crate) fn send_respawn(context: &mut PacketContext, dimension: Dimension, data_kept: u8) -> anyhow::Result<()> {
+    let name = dimension.name();
+    context.send(&Respawn {
+        dimension_type: VarInt(dimension_type_id(&name)),
+        dimension_name: name,
+        hashed_seed: level().read().unwrap().hashed_seed(),
+        game_mode: game_mode(&context.connection).id(),
+        previous_game_mode: previous_game_mode(&context.connection).map_or(-1, |previous| previous.id() as i8),
+        debug: false,
+        flat: false,
+        death_location: death_location(&context.connection),
+        portal_cooldown: VarInt(0),
+        data_kept,
+    })
+}
+
+/// Moves the player into `dimension`. Its client drops the chunks and entities of the old one, so
+/// the chunk view starts over and the entity tracker spawns the new dimension's entities again.
+pub(crate) fn enter_dimension(context: &mut PacketContext, dimension: Dimension) {
+    let connection = context.connection.clone();
+    connection.extensions(|extensions| extensions.insert(PlayerDimension(dimension)));
+    reset_chunk_view(&connection, dimension);
+    forget_tracked(&connection);
+    if let Some(entity_id) = connection.entity_id() {
+        if let Some(entity) = entities().write().unwrap().get_mut(entity_id) {
+            entity.dimension = dimension;
+        }
+    }
+}
+
+/// Sends the player to `position` in another dimension, keeping its health, inventory and effects.
+/// Does a plain teleport if it already is in that dimension.
+pub fn change_dimension(context: &mut PacketContext, dimension: Dimension, position: PlayerPosition) -> anyhow::Result<()> {
+    if player_dimension(&context.connection) == dimension {
+        return teleport(context, position);
+    }
+    enter_dimension(context, dimension);
+    send_respawn(context, dimension, KEEP_ATTRIBUTES | KEEP_METADATA)?;
+    context.send(&abilities(&context.connection).packet())?;
+    send_world_border(context)?;
+    send_time_and_weather(context)?;
+    send_world_spawn(context)?;
+    context.send(&GameEvent { event: GameEvent::START_WAITING_FOR_CHUNKS, value: 0.0 })?;
+    teleport(context, position)?;
+    send_inventory(&context.connection)?;
+    resend_vitals(&context.connection)
+}
@@ -0,0 +1,105 @@
+use std::collections::HashSet;
+use log::{error, warn};
+use dolls_core::nbt::NbtCompound;
+use dolls_entities::prelude::{entities, Entity, EntityManager, EntityType};
+use dolls_world::prelude::{ChunkPos, Dimension};
+use crate::prelude::{read_item_nbt, write_item_nbt};
+
+/// What an entity saves in its chunk, `None` for players, which are saved with their player data.
+fn entity_to_nbt(manager: &EntityManager, entity: &Entity) -> Option<NbtCompound> {
+    let mut nbt = entity.to_nbt();
+    match entity.entity_type() {
+        EntityType::PLAYER => return None,
+        EntityType::ITEM if !write_item_nbt(manager, entity, &mut nbt) => return None,
+        _ => {}
+    }
+    Some(nbt)
+}
+
+fn spawn_saved_entity(manager: &mut EntityManager, nbt: &NbtCompound, dimension: Dimension) -> anyhow::Result<()> {
+    let entity_id = manager.spawn_from_nbt(nbt, dimension)?;
+    if manager.get(entity_id).map(Entity::entity_type) == Some(EntityType::ITEM) {
+        if let Err(err) = read_item_nbt(manager, entity_id, nbt) {
+            manager.remove(entity_id);
+            return Err(err);
+        }
+    }
+    Ok(())
+}
+
+fn spawn_entities(dimension: Dimension, loaded: Vec<(ChunkPos, Vec<NbtCompound>)>) {
+    if loaded.is_empty() {
+        return;
+    }
+    let mut manager = entities().write().unwrap();
+    for (chunk, saved) in loaded {
+        for nbt in &saved {
+            if let Err(err) = spawn_saved_entity(&mut manager, nbt, dimension) {
+                warn!("Skipped an entity of chunk {} {} in {}: {:#}", chunk.x, chunk.z, dimension.name(), err);
+            }
+        }
+    }
+}
+
+/// Spawns the entities saved in the chunks of `dimension` which loaded since the last call.
+pub fn spawn_loaded_entities(dimension: Dimension) {
+    let loaded = dimension.world().write().unwrap().take_loaded_entities();
+    spawn_entities(dimension, loaded);
+}
+
+/// Saves the entities in `chunks` of `dimension`, removing them from the world when their chunks unload.
+fn save_entities(dimension: Dimension, chunks: &[ChunkPos], unload: bool) {
+    spawn_loaded_entities(dimension);
+    if dimension.world().read().unwrap().entity_storage().is_none() {
+        return;
+    }
+    let saved = {
+        let mut manager = entities().write().unwrap();
+        chunks.iter()
+            .map(|chunk| {
+                let ids = manager.in_chunk(*chunk)
+                    .filter(|entity| entity.dimension == dimension && entity.entity_type() != EntityType::PLAYER)
+                    .map(Entity::id)
+                    .collect::<Vec<_>>();
+                let nbt = ids.iter()
+                    .filter_map(|id| manager.get(*id).and_then(|entity| entity_to_nbt(&manager, entity)))
+                    .collect::<Vec<_>>();
+                if unload {
+                    for id in ids {
+                        manager.remove(id);
+                    }
+                }
+                (*chunk, nbt)
+            })
+            .collect::<Vec<_>>()
+    };
+    let mut world = dimension.world().write().unwrap();
+    // Chunks loaded since the entities were spawned have theirs only on disk, which must not be overwritten.
+    let loaded = world.take_loaded_entities();
+    for (chunk, nbt) in saved {
+        if loaded.iter().any(|(position, _)| *position == chunk) {
+            continue;
+        }
+        if let Err(err) = world.save_entities(chunk, nbt) {
+            error!("Failed to save the entities of chunk {} {} in {}: {:#}", chunk.x, chunk.z, dimension.name(), err);
+        }
+    }
+    drop(world);
+    spawn_entities(dimension, loaded);
+}
+
+/// Saves and removes the entities of chunks about to unload, they are spawned again when their chunk loads.
+pub(crate) fn unload_entities(dimension: Dimension, chunks: &[ChunkPos]) {
+    save_entities(dimension, chunks, true);
+}
+
+/// Saves the entities of every dimension, e.g. when the world is saved.
+pub fn save_all_entities() {
+    for dimension in Dimension::ALL {
+        let mut chunks = dimension.world().read().unwrap().chunks().map(|chunk| chunk.position).collect::<HashSet<_>>();
+        chunks.extend(entities().read().unwrap().iter()
+            .filter(|entity| entity.dimension == dimension)
+            .map(Entity::chunk_pos));
+        save_entities(dimension, &chunks.into_iter().collect::<Vec<_>>(), false);
+    }
+}
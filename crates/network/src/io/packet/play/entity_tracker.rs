@@ -0,0 +1,350 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use once_cell::sync::Lazy;
+use dolls_core::datatype::{Encode, Uuid, VarInt};
+use dolls_entities::prelude::{entities, Entity, EntityId, EntityMetadata, Vec3};
+use dolls_tick::prelude::{scheduler, TaskGuard};
+use crate::prelude::{player_dimension, player_position, sees_chunk, ClientboundPacket, ClientboundPacketType, ConnectionHandle, ConnectionRegistry, RawPacket};
+
+/// Ticks after which moving entities are teleported to their exact position, relative moves drift.
+const FORCED_TELEPORT_INTERVAL: u64 = 400;
+/// Positions are sent in 1/4096 of a block, relative moves carry an `i16` of them.
+const POSITION_SCALE: f64 = 4096.0;
+/// Velocities are sent in 1/8000 of a block per tick, clamped to this many blocks.
+const MAX_VELOCITY: f64 = 3.9;
+
+/// An angle in 1/256 of a turn.
+pub fn angle(degrees: f32) -> u8 {
+    (degrees * 256.0 / 360.0).floor() as i32 as u8
+}
+
+fn velocity(velocity: Vec3) -> [i16; 3] {
+    [velocity.x, velocity.y, velocity.z].map(|value| (value.clamp(-MAX_VELOCITY, MAX_VELOCITY) * 8000.0) as i16)
+}
+
+#[derive(Debug, Clone, Encode)]
+pub struct SpawnEntity {
+    pub entity_id: VarInt,
+    pub uuid: Uuid,
+    pub entity_type: VarInt,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub pitch: u8,
+    pub yaw: u8,
+    pub head_yaw: u8,
+    /// Meaning depends on the type, like the block state of falling blocks.
+    pub data: VarInt,
+    pub velocity_x: i16,
+    pub velocity_y: i16,
+    pub velocity_z: i16,
+}
+
+impl ClientboundPacket for SpawnEntity {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::SpawnEntity;
+}
+
+impl SpawnEntity {
+    pub fn of(entity: &Entity) -> Self {
+        let position = entity.position();
+        let [velocity_x, velocity_y, velocity_z] = velocity(entity.velocity);
+        Self {
+            entity_id: VarInt(entity.id()),
+            uuid: entity.uuid(),
+            entity_type: VarInt(entity.entity_type().id()),
+            x: position.x,
+            y: position.y,
+            z: position.z,
+            pitch: angle(entity.rotation.pitch),
+            yaw: angle(entity.rotation.yaw),
+            head_yaw: angle(entity.rotation.head_yaw),
+            data: VarInt(0),
+            velocity_x,
+            velocity_y,
+            velocity_z,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Encode)]
+pub struct RemoveEntities {
+    pub entity_ids: Vec<VarInt>,
+}
+
+impl ClientboundPacket for RemoveEntities {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::RemoveEntities;
+}
+
+/// Moves an entity by up to 8 blocks on each axis, in 1/4096 of a block.
+#[derive(Debug, Clone, Encode)]
+pub struct UpdateEntityPosition {
+    pub entity_id: VarInt,
+    pub delta_x: i16,
+    pub delta_y: i16,
+    pub delta_z: i16,
+    pub on_ground: bool,
+}
+
+impl ClientboundPacket for UpdateEntityPosition {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::UpdateEntityPosition;
+}
+
+#[derive(Debug, Clone, Encode)]
+pub struct UpdateEntityPositionAndRotation {
+    pub entity_id: VarInt,
+    pub delta_x: i16,
+    pub delta_y: i16,
+    pub delta_z: i16,
+    pub yaw: u8,
+    pub pitch: u8,
+    pub on_ground: bool,
+}
+
+impl ClientboundPacket for UpdateEntityPositionAndRotation {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::UpdateEntityPositionAndRotation;
+}
+
+#[derive(Debug, Clone, Encode)]
+pub struct UpdateEntityRotation {
+    pub entity_id: VarInt,
+    pub yaw: u8,
+    pub pitch: u8,
+    pub on_ground: bool,
+}
+
+impl ClientboundPacket for UpdateEntityRotation {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::UpdateEntityRotation;
+}
+
+#[derive(Debug, Clone, Encode)]
+pub struct TeleportEntity {
+    pub entity_id: VarInt,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub yaw: u8,
+    pub pitch: u8,
+    pub on_ground: bool,
+}
+
+impl ClientboundPacket for TeleportEntity {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::TeleportEntity;
+}
+
+#[derive(Debug, Clone, Encode)]
+pub struct SetHeadRotation {
+    pub entity_id: VarInt,
+    pub head_yaw: u8,
+}
+
+impl ClientboundPacket for SetHeadRotation {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::SetHeadRotation;
+}
+
+#[derive(Debug, Clone, Encode)]
+pub struct SetEntityMetadata {
+    pub entity_id: VarInt,
+    pub metadata: EntityMetadata,
+}
+
+impl ClientboundPacket for SetEntityMetadata {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::SetEntityMetadata;
+}
+
+#[derive(Debug, Clone, Encode)]
+pub struct SetEntityVelocity {
+    pub entity_id: VarInt,
+    pub velocity_x: i16,
+    pub velocity_y: i16,
+    pub velocity_z: i16,
+}
+
+impl ClientboundPacket for SetEntityVelocity {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::SetEntityVelocity;
+}
+
+/// What the players tracking an entity were last told about it.
+#[derive(Debug)]
+struct TrackedEntity {
+    /// In 1/4096 of a block.
+    position: [i64; 3],
+    yaw: u8,
+    pitch: u8,
+    head_yaw: u8,
+    velocity: [i16; 3],
+    last_teleport: u64,
+    /// Connection ids of the players the entity was spawned for.
+    viewers: HashSet<u64>,
+}
+
+impl TrackedEntity {
+    fn new(entity: &Entity, tick: u64) -> Self {
+        Self {
+            position: encode_position(entity.position()),
+            yaw: angle(entity.rotation.yaw),
+            pitch: angle(entity.rotation.pitch),
+            head_yaw: angle(entity.rotation.head_yaw),
+            velocity: velocity(entity.velocity),
+            last_teleport: tick,
+            viewers: HashSet::new(),
+        }
+    }
+
+    /// Packets telling the viewers how the entity changed since they were last told, on the ticks
+    /// of its type's update interval.
+    fn updates(&mut self, entity: &Entity, dirty: &EntityMetadata, tick: u64) -> anyhow::Result<Vec<RawPacket>> {
+        let mut updates = Vec::new();
+        let entity_id = VarInt(entity.id());
+        let interval = entity.entity_type().update_interval();
+        if interval != u32::MAX && tick.is_multiple_of(interval as u64) {
+            let position = encode_position(entity.position());
+            let (yaw, pitch) = (angle(entity.rotation.yaw), angle(entity.rotation.pitch));
+            let delta = [0, 1, 2].map(|axis| position[axis] - self.position[axis]);
+            let moved = delta != [0; 3];
+            let rotated = (yaw, pitch) != (self.yaw, self.pitch);
+            let relative = delta.iter().all(|delta| i16::try_from(*delta).is_ok());
+            let [delta_x, delta_y, delta_z] = delta.map(|delta| delta as i16);
+            let on_ground = entity.on_ground;
+            if moved && (!relative || tick - self.last_teleport >= FORCED_TELEPORT_INTERVAL) {
+                let Vec3 { x, y, z } = entity.position();
+                updates.push(RawPacket::from_packet(&TeleportEntity { entity_id, x, y, z, yaw, pitch, on_ground })?);
+                self.last_teleport = tick;
+            } else if moved && rotated {
+                updates.push(RawPacket::from_packet(&UpdateEntityPositionAndRotation { entity_id, delta_x, delta_y, delta_z, yaw, pitch, on_ground })?);
+            } else if moved {
+                updates.push(RawPacket::from_packet(&UpdateEntityPosition { entity_id, delta_x, delta_y, delta_z, on_ground })?);
+            } else if rotated {
+                updates.push(RawPacket::from_packet(&UpdateEntityRotation { entity_id, yaw, pitch, on_ground })?);
+            }
+            (self.position, self.yaw, self.pitch) = (position, yaw, pitch);
+
+            let head_yaw = angle(entity.rotation.head_yaw);
+            if head_yaw != self.head_yaw {
+                updates.push(RawPacket::from_packet(&SetHeadRotation { entity_id, head_yaw })?);
+                self.head_yaw = head_yaw;
+            }
+            let [velocity_x, velocity_y, velocity_z] = velocity(entity.velocity);
+            if [velocity_x, velocity_y, velocity_z] != self.velocity {
+                updates.push(RawPacket::from_packet(&SetEntityVelocity { entity_id, velocity_x, velocity_y, velocity_z })?);
+                self.velocity = [velocity_x, velocity_y, velocity_z];
+            }
+        }
+        if !dirty.is_empty() {
+            updates.push(RawPacket::from_packet(&SetEntityMetadata { entity_id, metadata: dirty.clone() })?);
+        }
+        Ok(updates)
+    }
+}
+
+fn encode_position(position: Vec3) -> [i64; 3] {
+    [position.x, position.y, position.z].map(|value| (value * POSITION_SCALE).round() as i64)
+}
+
+static TRACKED: Lazy<Mutex<HashMap<EntityId, TrackedEntity>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Whether `viewer` should see an entity: in its dimension, close enough for the entity's type and in
+/// a chunk it was sent.
+fn can_see(viewer: &ConnectionHandle, position: Option<Vec3>, entity: &Entity) -> bool {
+    let Some(position) = position else { return false };
+    let range = entity.entity_type().tracking_range() as f64 * 16.0;
+    let (dx, dz) = (entity.position().x - position.x, entity.position().z - position.z);
+    entity.dimension == player_dimension(viewer)
+        && dx * dx + dz * dz <= range * range
+        && sees_chunk(viewer, entity.chunk_pos())
+}
+
+/// Spawns entities for players who came in range, removes them for those who left it and sends
+/// movement and metadata to the rest. Runs every tick.
+pub fn tick_entity_tracker(connections: &ConnectionRegistry) {
+    let tick = scheduler().current_tick();
+    let players = connections.players().into_iter()
+        .map(|player| {
+            let position = player_position(&player).map(|position| position.vec3());
+            (player.entity_id(), position, player)
+        })
+        .collect::<Vec<_>>();
+    let snapshots = entities().write().unwrap().iter_mut()
+        .map(|entity| {
+            let dirty = entity.metadata.take_dirty();
+            (entity.clone(), dirty)
+        })
+        .collect::<Vec<_>>();
+
+    let mut outgoing: HashMap<u64, Vec<RawPacket>> = HashMap::new();
+    let mut removed: HashMap<u64, Vec<VarInt>> = HashMap::new();
+    let mut tracked = TRACKED.lock().unwrap();
+    let alive = snapshots.iter().map(|(entity, _)| entity.id()).collect::<HashSet<_>>();
+    let online = players.iter().map(|(_, _, player)| player.id()).collect::<HashSet<_>>();
+    tracked.retain(|entity_id, entity| {
+        entity.viewers.retain(|viewer| online.contains(viewer));
+        if alive.contains(entity_id) {
+            return true;
+        }
+        for viewer in &entity.viewers {
+            removed.entry(*viewer).or_default().push(VarInt(*entity_id));
+        }
+        false
+    });
+
+    for (entity, dirty) in &snapshots {
+        let state = tracked.entry(entity.id()).or_insert_with(|| TrackedEntity::new(entity, tick));
+        let updates = state.updates(entity, dirty, tick).unwrap_or_default();
+        for (player_entity, position, player) in &players {
+            let visible = *player_entity != Some(entity.id()) && can_see(player, *position, entity);
+            let viewing = state.viewers.contains(&player.id());
+            match (visible, viewing) {
+                (true, true) => outgoing.entry(player.id()).or_default().extend(updates.iter().cloned()),
+                (true, false) => {
+                    let packets = outgoing.entry(player.id()).or_default();
+                    packets.extend(RawPacket::from_packet(&SpawnEntity::of(entity)));
+                    if !entity.metadata.is_empty() {
+                        packets.extend(RawPacket::from_packet(&SetEntityMetadata { entity_id: VarInt(entity.id()), metadata: entity.metadata.clone() }));
+                    }
+                    state.viewers.insert(player.id());
+                }
+                (false, true) => {
+                    removed.entry(player.id()).or_default().push(VarInt(entity.id()));
+                    state.viewers.remove(&player.id());
+                }
+                (false, false) => {}
+            }
+        }
+    }
+    drop(tracked);
+
+    for (_, _, player) in players {
+        if let Some(entity_ids) = removed.remove(&player.id()) {
+            let _ = player.send(&RemoveEntities { entity_ids });
+        }
+        for packet in outgoing.remove(&player.id()).unwrap_or_default() {
+            let _ = player.send_raw(packet);
+        }
+    }
+}
+
+/// Players an entity is currently spawned for.
+pub fn entity_viewers(connections: &ConnectionRegistry, entity_id: EntityId) -> Vec<ConnectionHandle> {
+    let viewers = TRACKED.lock().unwrap().get(&entity_id).map(|entity| entity.viewers.clone()).unwrap_or_default();
+    connections.players().into_iter().filter(|player| viewers.contains(&player.id())).collect()
+}
+
+/// Makes the entity tracker spawn an entity again for everyone seeing it, e.g. a player who
+/// respawned after its body was removed by the clients.
+pub fn forget_viewers(entity_id: EntityId) {
+    if let Some(entity) = TRACKED.lock().unwrap().get_mut(&entity_id) {
+        entity.viewers.clear();
+    }
+}
+
+/// Makes the entity tracker forget every entity it spawned for `viewer`, whose client dropped them
+/// when changing dimensions.
+pub(crate) fn forget_tracked(viewer: &ConnectionHandle) {
+    for entity in TRACKED.lock().unwrap().values_mut() {
+        entity.viewers.remove(&viewer.id());
+    }
+}
+
+/// Runs the entity tracker on every tick, dropping the guard stops it.
+pub fn start_entity_tracker(connections: Arc<ConnectionRegistry>) -> TaskGuard {
+    scheduler().run_repeating("Entity tracker", 0, 1, move || tick_entity_tracker(&connections)).guard()
+}
@@ -0,0 +1,228 @@
+use log::{debug, warn};
+use dolls_config::GameMode as ConfiguredGameMode;
+use dolls_core::datatype::{Decode, Encode};
+use dolls_macros::packet_processor;
+use dolls_world::player_data::player_data;
+use crate::prelude::{set_listed_game_mode, ClientboundPacket, ClientboundPacketType, ConnectionHandle, ConnectionRegistry, GameEvent, PacketContext, PacketType, RawPacket};
+
+/// A player's game mode, kept in its connection's extensions.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub enum GameMode {
+    #[default]
+    Survival,
+    Creative,
+    Adventure,
+    Spectator,
+}
+
+impl GameMode {
+    pub const ALL: [GameMode; 4] = [GameMode::Survival, GameMode::Creative, GameMode::Adventure, GameMode::Spectator];
+
+    /// Id in the protocol, e.g. in Login (play).
+    pub const fn id(self) -> u8 {
+        self as u8
+    }
+
+    pub fn from_id(id: i32) -> Option<Self> {
+        match id {
+            0 => Some(GameMode::Survival),
+            1 => Some(GameMode::Creative),
+            2 => Some(GameMode::Adventure),
+            3 => Some(GameMode::Spectator),
+            _ => None,
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            GameMode::Survival => "survival",
+            GameMode::Creative => "creative",
+            GameMode::Adventure => "adventure",
+            GameMode::Spectator => "spectator",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|game_mode| game_mode.name() == name)
+    }
+
+    /// Whether blocks can be placed and broken, adventure and spectator players only look.
+    pub const fn may_build(self) -> bool {
+        matches!(self, GameMode::Survival | GameMode::Creative)
+    }
+}
+
+impl From<ConfiguredGameMode> for GameMode {
+    fn from(game_mode: ConfiguredGameMode) -> Self {
+        match game_mode {
+            ConfiguredGameMode::Survival => GameMode::Survival,
+            ConfiguredGameMode::Creative => GameMode::Creative,
+            ConfiguredGameMode::Adventure => GameMode::Adventure,
+            ConfiguredGameMode::Spectator => GameMode::Spectator,
+        }
+    }
+}
+
+/// Game mode a player had before the current one, shown by the client's game mode switcher.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct PreviousGameMode(GameMode);
+
+/// Player Abilities (clientbound), what the client lets its player do.
+#[derive(Debug, Clone, Encode)]
+pub struct PlayerAbilities {
+    pub flags: i8,
+    pub flying_speed: f32,
+    pub field_of_view_modifier: f32,
+}
+
+impl ClientboundPacket for PlayerAbilities {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::PlayerAbilities;
+}
+
+/// What a player's game mode allows, kept in its connection's extensions.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Abilities {
+    pub invulnerable: bool,
+    pub flying: bool,
+    pub may_fly: bool,
+    /// Blocks break as soon as digging starts.
+    pub instant_build: bool,
+    pub flying_speed: f32,
+    pub walking_speed: f32,
+}
+
+impl Abilities {
+    pub const INVULNERABLE: i8 = 0x01;
+    pub const FLYING: i8 = 0x02;
+    pub const MAY_FLY: i8 = 0x04;
+    pub const INSTANT_BUILD: i8 = 0x08;
+
+    pub fn of(game_mode: GameMode) -> Self {
+        let creative = game_mode == GameMode::Creative;
+        let spectator = game_mode == GameMode::Spectator;
+        Self {
+            invulnerable: creative || spectator,
+            flying: spectator,
+            may_fly: creative || spectator,
+            instant_build: creative,
+            flying_speed: 0.05,
+            walking_speed: 0.1,
+        }
+    }
+
+    pub fn flags(&self) -> i8 {
+        [(self.invulnerable, Self::INVULNERABLE), (self.flying, Self::FLYING), (self.may_fly, Self::MAY_FLY), (self.instant_build, Self::INSTANT_BUILD)]
+            .into_iter()
+            .filter(|(set, _)| *set)
+            .fold(0, |flags, (_, flag)| flags | flag)
+    }
+
+    pub fn packet(&self) -> PlayerAbilities {
+        PlayerAbilities { flags: self.flags(), flying_speed: self.flying_speed, field_of_view_modifier: self.walking_speed }
+    }
+}
+
+impl Default for Abilities {
+    fn default() -> Self {
+        Self::of(GameMode::Survival)
+    }
+}
+
+/// Player data keys of the current and previous game mode, as vanilla writes them.
+const GAME_MODE_KEY: &str = "playerGameType";
+const PREVIOUS_GAME_MODE_KEY: &str = "previousPlayerGameType";
+
+pub fn game_mode(connection: &ConnectionHandle) -> GameMode {
+    connection.extensions(|extensions| extensions.get::<GameMode>().copied()).unwrap_or_default()
+}
+
+/// Game mode before the last switch, `None` if the player never switched.
+pub fn previous_game_mode(connection: &ConnectionHandle) -> Option<GameMode> {
+    connection.extensions(|extensions| extensions.get::<PreviousGameMode>().map(|previous| previous.0))
+}
+
+pub fn is_creative(connection: &ConnectionHandle) -> bool {
+    game_mode(connection) == GameMode::Creative
+}
+
+pub fn abilities(connection: &ConnectionHandle) -> Abilities {
+    connection.extensions(|extensions| extensions.get::<Abilities>().copied()).unwrap_or_default()
+}
+
+/// Whether the player takes no damage, like creative and spectator players.
+pub fn is_invulnerable(connection: &ConnectionHandle) -> bool {
+    abilities(connection).invulnerable
+}
+
+/// Game mode of a player joining, the one saved for it unless it never played here.
+pub(crate) fn load_game_mode(context: &mut PacketContext) {
+    let data = context.uuid.and_then(|uuid| player_data().read().unwrap().load(uuid).unwrap_or_else(|err| {
+        warn!("Failed to load the player data of {}: {}", uuid.hyphenated(), err);
+        None
+    }));
+    let saved = |key| data.as_ref().and_then(|data| data.get_i64(key)).and_then(|id| GameMode::from_id(id as i32));
+    let game_mode = saved(GAME_MODE_KEY).unwrap_or_else(|| GameMode::from(context.config.server.game_mode));
+    let previous = saved(PREVIOUS_GAME_MODE_KEY);
+    context.connection.extensions(|extensions| {
+        extensions.insert(game_mode);
+        extensions.insert(Abilities::of(game_mode));
+        if let Some(previous) = previous {
+            extensions.insert(PreviousGameMode(previous));
+        }
+    });
+}
+
+fn save_game_mode(connection: &ConnectionHandle, game_mode: GameMode, previous: GameMode) {
+    let Some(uuid) = connection.uuid() else { return };
+    if let Err(err) = player_data().read().unwrap().update(uuid, |data| {
+        data.insert(GAME_MODE_KEY, game_mode.id() as i32);
+        data.insert(PREVIOUS_GAME_MODE_KEY, previous.id() as i32);
+    }) {
+        warn!("Failed to save the game mode of {}: {}", uuid.hyphenated(), err);
+    }
+}
+
+/// Switches a player's game mode, updating its abilities and everyone's tab list. Returns
+/// `false` if it already was in that mode.
+pub fn set_game_mode(connections: &ConnectionRegistry, player: &ConnectionHandle, game_mode: GameMode) -> anyhow::Result<bool> {
+    let switched = player.extensions(|extensions| {
+        let previous = extensions.get::<GameMode>().copied().unwrap_or_default();
+        if previous == game_mode {
+            return None;
+        }
+        let flying = extensions.get::<Abilities>().is_some_and(|abilities| abilities.flying);
+        let mut abilities = Abilities::of(game_mode);
+        // Creative players keep flying, spectators always fly.
+        abilities.flying |= flying && abilities.may_fly;
+        extensions.insert(game_mode);
+        extensions.insert(PreviousGameMode(previous));
+        extensions.insert(abilities);
+        Some((previous, abilities))
+    });
+    let Some((previous, abilities)) = switched else { return Ok(false) };
+    save_game_mode(player, game_mode, previous);
+    if previous == GameMode::Spectator {
+        player.reset_camera()?;
+    }
+    player.send(&GameEvent { event: GameEvent::CHANGE_GAME_MODE, value: game_mode.id() as f32 })?;
+    player.send(&abilities.packet())?;
+    set_listed_game_mode(connections, player, game_mode.id() as i32)?;
+    Ok(true)
+}
+
+/// The client starting or stopping to fly, refused if its game mode does not allow flight.
+#[packet_processor(PacketType::PlayerAbilities)]
+pub(crate) fn player_abilities_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    let flags = i8::decode(&mut packet.payload.as_slice())?;
+    let flying = flags & Abilities::FLYING != 0;
+    let abilities = context.connection.extensions(|extensions| {
+        let abilities = extensions.get_or_default::<Abilities>();
+        abilities.flying = flying && abilities.may_fly;
+        *abilities
+    });
+    if abilities.flying != flying {
+        debug!("{} tried to fly without being allowed to", context.connection.id());
+        context.send(&abilities.packet())?;
+    }
+    Ok(())
+}
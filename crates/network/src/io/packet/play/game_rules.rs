@@ -0,0 +1,42 @@
+use dolls_core::datatype::Encode;
+use dolls_world::prelude::{level, GameRuleValue, DO_IMMEDIATE_RESPAWN, DO_LIMITED_CRAFTING, REDUCED_DEBUG_INFO};
+use crate::prelude::{ClientboundPacket, ClientboundPacketType, ConnectionRegistry, GameEvent};
+
+/// Entity statuses which toggle the reduced debug screen of the player they are sent to.
+const ENABLE_REDUCED_DEBUG_INFO: i8 = 22;
+const DISABLE_REDUCED_DEBUG_INFO: i8 = 23;
+
+/// Triggers a status effect of an entity on the client, e.g. an animation or a client setting.
+#[derive(Debug, Clone, Encode)]
+pub struct EntityEvent {
+    pub entity_id: i32,
+    pub status: i8,
+}
+
+impl ClientboundPacket for EntityEvent {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::EntityEvent;
+}
+
+/// Changes a game rule from a value given as text and tells clients about rules they act on.
+pub fn set_game_rule(connections: &ConnectionRegistry, name: &str, value: &str) -> anyhow::Result<GameRuleValue> {
+    let value = level().write().unwrap().game_rules.set(name, value)?;
+    let enabled = value == GameRuleValue::Bool(true);
+    match name {
+        _ if name == DO_IMMEDIATE_RESPAWN.0 => {
+            connections.broadcast(&GameEvent { event: GameEvent::ENABLE_RESPAWN_SCREEN, value: if enabled { 1.0 } else { 0.0 } })?;
+        }
+        _ if name == DO_LIMITED_CRAFTING.0 => {
+            connections.broadcast(&GameEvent { event: GameEvent::LIMITED_CRAFTING, value: if enabled { 1.0 } else { 0.0 } })?;
+        }
+        _ if name == REDUCED_DEBUG_INFO.0 => {
+            let status = if enabled { ENABLE_REDUCED_DEBUG_INFO } else { DISABLE_REDUCED_DEBUG_INFO };
+            for player in connections.players() {
+                if let Some(entity_id) = player.entity_id() {
+                    let _ = player.send(&EntityEvent { entity_id, status });
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(value)
+}
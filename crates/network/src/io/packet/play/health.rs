@@ -0,0 +1,435 @@
+use std::sync::Arc;
+use log::warn;
+use dolls_core::datatype::{Encode, Identifier, VarInt};
+use dolls_core::nbt::NbtCompound;
+use dolls_core::registry::registries;
+use dolls_entities::prelude::Vec3;
+use dolls_tick::prelude::{scheduler, TaskGuard};
+use dolls_world::player_data::player_data;
+use dolls_world::prelude::{level, NATURAL_REGENERATION};
+use crate::prelude::{abilities, die, entity_viewers, ClientboundPacket, ClientboundPacketType, ConnectionHandle, ConnectionRegistry, PacketContext, PlayerPosition};
+
+pub const MAX_HEALTH: f32 = 20.0;
+pub const MAX_FOOD: i32 = 20;
+/// Exhaustion which costs a point of saturation, or of food once saturation ran out.
+const EXHAUSTION_PER_FOOD: f32 = 4.0;
+/// Ticks after being hurt in which only harder hits deal their difference, like vanilla.
+const HURT_COOLDOWN: u32 = 10;
+const JUMP_EXHAUSTION: f32 = 0.05;
+pub const BLOCK_BREAK_EXHAUSTION: f32 = 0.005;
+
+#[derive(Debug, Clone, Encode)]
+pub struct SetHealth {
+    pub health: f32,
+    pub food: VarInt,
+    pub food_saturation: f32,
+}
+
+impl ClientboundPacket for SetHealth {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::SetHealth;
+}
+
+/// Plays the hurt animation and sound of an entity, with what hurt it.
+#[derive(Debug, Clone, Encode)]
+pub struct DamageEvent {
+    pub entity_id: VarInt,
+    /// Network id in the `minecraft:damage_type` registry.
+    pub source_type_id: VarInt,
+    /// Entity id of the attacker plus one, zero for none.
+    pub source_cause_id: VarInt,
+    /// Entity id of what hit, e.g. an arrow, plus one, zero for none.
+    pub source_direct_id: VarInt,
+    pub source_position: Option<Vec3>,
+}
+
+impl ClientboundPacket for DamageEvent {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::DamageEvent;
+}
+
+/// What hurts a player: a `minecraft:damage_type` and optionally who or where from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DamageSource {
+    pub damage_type: Identifier,
+    pub attacker: Option<i32>,
+    pub direct: Option<i32>,
+    pub position: Option<Vec3>,
+}
+
+impl DamageSource {
+    pub fn new(damage_type: Identifier) -> Self {
+        Self { damage_type, attacker: None, direct: None, position: None }
+    }
+
+    /// A vanilla damage type, e.g. `starve` or `out_of_world`.
+    pub fn of(damage_type: &str) -> Self {
+        Self::new(Identifier::minecraft(damage_type))
+    }
+
+    /// Caused by an entity hitting directly.
+    pub fn attacker(mut self, entity_id: i32) -> Self {
+        self.attacker = Some(entity_id);
+        self.direct.get_or_insert(entity_id);
+        self
+    }
+
+    /// Caused by `entity_id`, e.g. a projectile, on behalf of the attacker.
+    pub fn direct(mut self, entity_id: i32) -> Self {
+        self.direct = Some(entity_id);
+        self
+    }
+
+    pub fn position(mut self, position: Vec3) -> Self {
+        self.position = Some(position);
+        self
+    }
+
+    fn is(&self, name: &str) -> bool {
+        self.damage_type.namespace() == "minecraft" && self.damage_type.path() == name
+    }
+
+    /// Whether it is fall damage, which has its own death messages.
+    pub fn is_fall(&self) -> bool {
+        self.is("fall")
+    }
+
+    /// Whether it also hurts creative and spectator players, like falling out of the world.
+    pub fn bypasses_invulnerability(&self) -> bool {
+        self.is("out_of_world") || self.is("generic_kill")
+    }
+
+    /// Whether the hurt cooldown is ignored, for damage which is applied over time anyway.
+    pub fn bypasses_cooldown(&self) -> bool {
+        self.is("starve") || self.bypasses_invulnerability()
+    }
+
+    /// Exhaustion the damage causes, as in the vanilla damage type definitions.
+    pub fn exhaustion(&self) -> f32 {
+        const NONE: &[&str] = &["starve", "drown", "in_wall", "cramming", "freeze", "out_of_world", "generic_kill", "outside_border", "dry_out", "bad_respawn_point"];
+        match NONE.iter().any(|name| self.is(name)) {
+            true => 0.0,
+            false => 0.1,
+        }
+    }
+
+    /// Part of the death message's translation key, like the `message_id` of vanilla damage types.
+    pub fn message_id(&self) -> String {
+        match self.damage_type.path() {
+            "mob_attack" | "mob_attack_no_aggro" | "mob_projectile" => "mob".to_string(),
+            "player_attack" => "player".to_string(),
+            "campfire" => "inFire".to_string(),
+            "dry_out" => "dryout".to_string(),
+            "falling_anvil" => "anvil".to_string(),
+            "sonic_boom" => "sonic_boom".to_string(),
+            path => {
+                let mut words = path.split('_');
+                let first = words.next().unwrap_or_default().to_string();
+                words.fold(first, |id, word| {
+                    let mut chars = word.chars();
+                    id + &chars.next().map(|first| first.to_ascii_uppercase().to_string() + chars.as_str()).unwrap_or_default()
+                })
+            }
+        }
+    }
+
+    /// Network id of the damage type, damage types the client does not know are sent as `generic`.
+    pub fn type_id(&self) -> i32 {
+        let registries = registries().read().unwrap();
+        let registry = registries.opaque.iter().find(|registry| *registry.id() == Identifier::minecraft("damage_type"));
+        registry.and_then(|registry| registry.network_id(&self.damage_type).or_else(|| registry.network_id(&Identifier::minecraft("generic"))))
+            .unwrap_or_default()
+    }
+}
+
+/// Health, food and what drains them, kept in a player's extensions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Vitals {
+    pub health: f32,
+    pub food: i32,
+    pub saturation: f32,
+    pub exhaustion: f32,
+    /// Ticks towards the next regeneration or starvation step.
+    food_timer: u32,
+    /// Ticks left of the hurt cooldown and the damage that started it.
+    hurt_cooldown: u32,
+    last_damage: f32,
+    /// What the client was last told, to only send changes.
+    sent: Option<(f32, i32, bool)>,
+}
+
+impl Vitals {
+    const HEALTH_KEY: &'static str = "Health";
+    const FOOD_KEY: &'static str = "foodLevel";
+    const SATURATION_KEY: &'static str = "foodSaturationLevel";
+    const EXHAUSTION_KEY: &'static str = "foodExhaustionLevel";
+    const FOOD_TIMER_KEY: &'static str = "foodTickTimer";
+
+    pub fn is_dead(&self) -> bool {
+        self.health <= 0.0
+    }
+
+    /// Saved vitals in player data, using the same keys as vanilla.
+    pub fn from_nbt(nbt: &NbtCompound) -> Self {
+        let default = Self::default();
+        let float = |key, default: f32| nbt.get_f64(key).map_or(default, |value| value as f32);
+        Self {
+            health: float(Self::HEALTH_KEY, default.health).clamp(0.0, MAX_HEALTH),
+            food: nbt.get_i64(Self::FOOD_KEY).map_or(default.food, |food| (food as i32).clamp(0, MAX_FOOD)),
+            saturation: float(Self::SATURATION_KEY, default.saturation),
+            exhaustion: float(Self::EXHAUSTION_KEY, default.exhaustion),
+            food_timer: nbt.get_i64(Self::FOOD_TIMER_KEY).map_or(0, |timer| timer.max(0) as u32),
+            ..default
+        }
+    }
+
+    pub fn write_nbt(&self, nbt: &mut NbtCompound) {
+        nbt.insert(Self::HEALTH_KEY, self.health);
+        nbt.insert(Self::FOOD_KEY, self.food);
+        nbt.insert(Self::SATURATION_KEY, self.saturation);
+        nbt.insert(Self::EXHAUSTION_KEY, self.exhaustion);
+        nbt.insert(Self::FOOD_TIMER_KEY, self.food_timer as i32);
+    }
+
+    pub fn packet(&self) -> SetHealth {
+        SetHealth { health: self.health, food: VarInt(self.food), food_saturation: self.saturation }
+    }
+
+    fn heal(&mut self, amount: f32) {
+        if !self.is_dead() {
+            self.health = (self.health + amount).min(MAX_HEALTH);
+        }
+    }
+
+    fn exhaust(&mut self, amount: f32) {
+        self.exhaustion = (self.exhaustion + amount).min(40.0);
+    }
+
+    /// Burns exhaustion and runs regeneration and starvation for a tick, returning the starvation
+    /// damage to deal. Starving stops at half the health, as on easy difficulty.
+    fn tick(&mut self, regeneration: bool) -> Option<f32> {
+        self.hurt_cooldown = self.hurt_cooldown.saturating_sub(1);
+        if self.is_dead() {
+            return None;
+        }
+        if self.exhaustion > EXHAUSTION_PER_FOOD {
+            self.exhaustion -= EXHAUSTION_PER_FOOD;
+            match self.saturation > 0.0 {
+                true => self.saturation = (self.saturation - 1.0).max(0.0),
+                false => self.food = (self.food - 1).max(0),
+            }
+        }
+        let hurt = self.health < MAX_HEALTH;
+        if regeneration && self.saturation > 0.0 && hurt && self.food >= MAX_FOOD {
+            self.food_timer += 1;
+            if self.food_timer >= 10 {
+                let amount = self.saturation.min(6.0);
+                self.heal(amount / 6.0);
+                self.exhaust(amount);
+                self.food_timer = 0;
+            }
+        } else if regeneration && self.food >= 18 && hurt {
+            self.food_timer += 1;
+            if self.food_timer >= 80 {
+                self.heal(1.0);
+                self.exhaust(6.0);
+                self.food_timer = 0;
+            }
+        } else if self.food <= 0 {
+            self.food_timer += 1;
+            if self.food_timer >= 80 {
+                self.food_timer = 0;
+                if self.health > MAX_HEALTH / 2.0 {
+                    return Some(1.0);
+                }
+            }
+        } else {
+            self.food_timer = 0;
+        }
+        None
+    }
+
+    /// The damage left after the hurt cooldown, `None` if the hit is absorbed by it.
+    fn hurt(&mut self, source: &DamageSource, amount: f32) -> Option<f32> {
+        if self.is_dead() || amount <= 0.0 {
+            return None;
+        }
+        let amount = match self.hurt_cooldown > 0 && !source.bypasses_cooldown() {
+            true if amount <= self.last_damage => return None,
+            true => amount - std::mem::replace(&mut self.last_damage, amount),
+            false => {
+                self.last_damage = amount;
+                self.hurt_cooldown = HURT_COOLDOWN;
+                amount
+            }
+        };
+        self.health = (self.health - amount).max(0.0);
+        self.exhaust(source.exhaustion());
+        Some(amount)
+    }
+
+    /// The packet to send if the client's view is outdated, like vanilla it is resent when health or
+    /// food changed or saturation ran out.
+    fn changes(&mut self) -> Option<SetHealth> {
+        let state = (self.health, self.food, self.saturation == 0.0);
+        match self.sent == Some(state) {
+            true => None,
+            false => {
+                self.sent = Some(state);
+                Some(self.packet())
+            }
+        }
+    }
+}
+
+impl Default for Vitals {
+    fn default() -> Self {
+        Self {
+            health: MAX_HEALTH,
+            food: MAX_FOOD,
+            saturation: 5.0,
+            exhaustion: 0.0,
+            food_timer: 0,
+            hurt_cooldown: 0,
+            last_damage: 0.0,
+            sent: None,
+        }
+    }
+}
+
+pub fn vitals(connection: &ConnectionHandle) -> Vitals {
+    connection.extensions(|extensions| extensions.get::<Vitals>().cloned()).unwrap_or_default()
+}
+
+pub fn health(connection: &ConnectionHandle) -> f32 {
+    vitals(connection).health
+}
+
+/// Changes a player's vitals and sends what the client needs to know about it.
+fn update_vitals<R>(connection: &ConnectionHandle, update: impl FnOnce(&mut Vitals) -> R) -> anyhow::Result<R> {
+    let (result, packet) = connection.extensions(|extensions| {
+        let vitals = extensions.get_or_default::<Vitals>();
+        let result = update(vitals);
+        (result, vitals.changes())
+    });
+    if let Some(packet) = packet {
+        connection.send(&packet)?;
+    }
+    Ok(result)
+}
+
+/// Heals a player by `amount` half hearts, dead players stay dead.
+pub fn heal(connection: &ConnectionHandle, amount: f32) -> anyhow::Result<()> {
+    update_vitals(connection, |vitals| vitals.heal(amount))
+}
+
+pub fn set_health(connection: &ConnectionHandle, health: f32) -> anyhow::Result<()> {
+    update_vitals(connection, |vitals| vitals.health = health.clamp(0.0, MAX_HEALTH))
+}
+
+pub fn set_food(connection: &ConnectionHandle, food: i32, saturation: f32) -> anyhow::Result<()> {
+    update_vitals(connection, |vitals| {
+        vitals.food = food.clamp(0, MAX_FOOD);
+        vitals.saturation = saturation.clamp(0.0, vitals.food as f32);
+    })
+}
+
+/// Adds exhaustion from an action, e.g. [`BLOCK_BREAK_EXHAUSTION`]. Invulnerable players do not get hungry.
+pub fn add_exhaustion(connection: &ConnectionHandle, amount: f32) {
+    if !abilities(connection).invulnerable {
+        connection.extensions(|extensions| extensions.get_or_default::<Vitals>().exhaust(amount));
+    }
+}
+
+/// Hurts a player by `amount` half hearts, returning the damage dealt. Creative and spectator
+/// players only take damage which bypasses invulnerability, and hits during the hurt cooldown only
+/// deal what exceeds the hit that started it.
+pub fn damage(connections: &ConnectionRegistry, player: &ConnectionHandle, source: &DamageSource, amount: f32) -> anyhow::Result<f32> {
+    if abilities(player).invulnerable && !source.bypasses_invulnerability() {
+        return Ok(0.0);
+    }
+    let Some((dealt, dead)) = update_vitals(player, |vitals| vitals.hurt(source, amount).map(|dealt| (dealt, vitals.is_dead())))? else { return Ok(0.0) };
+    if let Some(entity_id) = player.entity_id() {
+        let event = DamageEvent {
+            entity_id: VarInt(entity_id),
+            source_type_id: VarInt(source.type_id()),
+            source_cause_id: VarInt(source.attacker.map_or(0, |id| id + 1)),
+            source_direct_id: VarInt(source.direct.map_or(0, |id| id + 1)),
+            source_position: source.position,
+        };
+        player.send(&event)?;
+        for viewer in entity_viewers(connections, entity_id) {
+            let _ = viewer.send(&event);
+        }
+    }
+    if dead {
+        die(connections, player, source)?;
+    }
+    Ok(dealt)
+}
+
+/// Exhaustion from a move, only jumping costs food since sprinting is not tracked.
+pub(crate) fn exhaust_move(connection: &ConnectionHandle, from: &PlayerPosition, to: &PlayerPosition) {
+    if from.on_ground && !to.on_ground && to.y > from.y {
+        add_exhaustion(connection, JUMP_EXHAUSTION);
+    }
+}
+
+/// Runs regeneration and starvation of every player for a tick.
+pub fn tick_health(connections: &ConnectionRegistry) {
+    let regeneration = level().read().unwrap().game_rules.get_bool(NATURAL_REGENERATION);
+    for player in connections.players() {
+        match update_vitals(&player, |vitals| vitals.tick(regeneration)) {
+            Ok(Some(starvation)) => {
+                let _ = damage(connections, &player, &DamageSource::of("starve"), starvation);
+            }
+            Ok(None) => {}
+            // The player is disconnecting, it is removed from the registry next.
+            Err(_) => {}
+        }
+    }
+}
+
+/// Ticks the health of every player, dropping the guard stops it.
+pub fn start_health(connections: Arc<ConnectionRegistry>) -> TaskGuard {
+    scheduler().run_repeating("Player health", 0, 1, move || tick_health(&connections)).guard()
+}
+
+/// Reads the joining player's saved health and food and sends them.
+pub(crate) fn send_health(context: &mut PacketContext) -> anyhow::Result<()> {
+    let data = context.uuid.and_then(|uuid| player_data().read().unwrap().load(uuid).unwrap_or_else(|err| {
+        warn!("Failed to load the player data of {}: {}", uuid.hyphenated(), err);
+        None
+    }));
+    let mut vitals = data.as_ref().map(Vitals::from_nbt).unwrap_or_default();
+    let packet = vitals.changes();
+    context.connection.extensions(|extensions| extensions.insert(vitals));
+    match packet {
+        Some(packet) => context.send(&packet),
+        None => Ok(()),
+    }
+}
+
+/// Writes a player's health and food into its player data.
+pub fn save_health(connection: &ConnectionHandle) {
+    let Some(uuid) = connection.uuid() else { return };
+    let Some(vitals) = connection.extensions(|extensions| extensions.get::<Vitals>().cloned()) else { return };
+    if let Err(err) = player_data().read().unwrap().update(uuid, |data| vitals.write_nbt(data)) {
+        warn!("Failed to save the health of {}: {}", uuid.hyphenated(), err);
+    }
+}
+
+/// Writes the health of every player, e.g. when the world is saved.
+pub fn save_all_health(connections: &ConnectionRegistry) {
+    for player in connections.players() {
+        save_health(&player);
+    }
+}
+
+/// Sends the player's health and food again, e.g. after its client recreated the player.
+pub(crate) fn resend_vitals(connection: &ConnectionHandle) -> anyhow::Result<()> {
+    update_vitals(connection, |vitals| vitals.sent = None)
+}
+
+/// Gives a respawning player full health and food again.
+pub(crate) fn reset_vitals(connection: &ConnectionHandle) -> anyhow::Result<()> {
+    update_vitals(connection, |vitals| *vitals = Vitals::default())
+}
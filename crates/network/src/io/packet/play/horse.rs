@@ -0,0 +1,73 @@
+use std::sync::{Arc, Mutex};
+use anyhow::bail;
+use dolls_core::datatype::{Encode, VarInt};
+use dolls_core::item::ItemStack;
+use crate::prelude::{attach_container, send_inventory, ClientboundPacket, ClientboundPacketType, ConnectionHandle, WindowKind};
+
+/// Opens the inventory of a rideable entity, the client ignores it unless it knows the entity.
+#[derive(Debug, Clone, Encode)]
+pub struct OpenHorseScreen {
+    pub window_id: u8,
+    pub inventory_columns: VarInt,
+    pub entity_id: i32,
+}
+
+impl ClientboundPacket for OpenHorseScreen {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::OpenHorseScreen;
+}
+
+/// Contents of a horse, donkey, llama or camel inventory.
+///
+/// Slot 0 is the saddle, slot 1 the body armor (or llama carpet), then the chest in rows of three columns.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct HorseInventory {
+    pub saddle: ItemStack,
+    pub body_armor: ItemStack,
+    pub chest: Vec<ItemStack>,
+}
+
+impl HorseInventory {
+    /// Llamas carry up to 5 columns, donkeys and mules always 5.
+    pub const MAX_COLUMNS: usize = 5;
+
+    /// Inventory of an entity without a chest.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_chest(columns: usize) -> Self {
+        Self {
+            chest: vec![ItemStack::EMPTY; columns * 3],
+            ..Self::default()
+        }
+    }
+
+    pub fn columns(&self) -> usize {
+        self.chest.len() / 3
+    }
+
+    pub fn slots(&self) -> Vec<ItemStack> {
+        let mut slots = Vec::with_capacity(2 + self.chest.len());
+        slots.push(self.saddle);
+        slots.push(self.body_armor);
+        slots.extend_from_slice(&self.chest);
+        slots
+    }
+}
+
+/// Shows the inventory of the entity `entity_id` and returns the window id.
+pub fn open_horse_inventory(connection: &ConnectionHandle, entity_id: i32, inventory: &HorseInventory) -> anyhow::Result<i32> {
+    if !inventory.chest.len().is_multiple_of(3) || inventory.columns() > HorseInventory::MAX_COLUMNS {
+        bail!("Horse chests have 3 rows and up to {} columns, got {} slots", HorseInventory::MAX_COLUMNS, inventory.chest.len());
+    }
+    let columns = inventory.columns() as i32;
+    let slots = Arc::new(Mutex::new(inventory.slots()));
+    let window_id = attach_container(connection, WindowKind::Horse { entity_id, columns }, slots);
+    connection.send(&OpenHorseScreen {
+        window_id: window_id as u8,
+        inventory_columns: VarInt(columns),
+        entity_id,
+    })?;
+    send_inventory(connection)?;
+    Ok(window_id)
+}
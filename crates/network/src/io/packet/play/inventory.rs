@@ -0,0 +1,1085 @@
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::io::Read;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use anyhow::{anyhow, bail};
+use log::debug;
+use dolls_core::datatype::{decode_from_slice, Decode, Encode, Identifier, VarInt};
+use dolls_core::item::{items, ItemStack};
+use dolls_core::nbt::NbtCompound;
+use dolls_core::recipe::{recipes, Ingredient, Recipe};
+use dolls_core::statistic::StatType;
+use dolls_core::text::TextComponent;
+use dolls_entities::prelude::{entities, Entity, EntityId, EntityManager, EntityType, MetadataValue, Vec3};
+use dolls_macros::packet_processor;
+use dolls_tick::prelude::scheduler;
+use crate::prelude::{allocate_window, is_creative, increment_custom_stat, increment_stat, player_dimension, player_position, trigger_inventory_changed, unlock_recipes, ClientboundPacket, ClientboundPacketType, ConnectionHandle, ConnectionRegistry,
+    OpenScreen, PacketContext, PacketType, RawPacket, SetContainerContent, WindowKind, WindowType};
+
+/// Slots of the player inventory window: crafting result and grid, armor, main, hotbar and offhand.
+pub const PLAYER_INVENTORY_SIZE: usize = 46;
+pub const HOTBAR_SLOTS: usize = 9;
+/// Most slots a client may report as changed by one click, as in vanilla.
+const MAX_CHANGED_SLOTS: usize = 128;
+/// Ticks before dropped items disappear, as in vanilla.
+const ITEM_LIFETIME: u64 = 6000;
+/// Index of the item in the metadata of item entities.
+const ITEM_METADATA_INDEX: u8 = 8;
+/// Slot the client reports for clicks outside of the window.
+const OUTSIDE: i16 = -999;
+
+/// Ranges of the player inventory window.
+pub mod player_slots {
+    use std::ops::Range;
+
+    pub const CRAFTING_RESULT: usize = 0;
+    pub const CRAFTING_GRID: Range<usize> = 1..5;
+    pub const ARMOR: Range<usize> = 5..9;
+    pub const MAIN: Range<usize> = 9..36;
+    pub const HOTBAR: Range<usize> = 36..45;
+    pub const OFFHAND: usize = 45;
+}
+
+/// Slots shared by every player who has them open, like those of a chest.
+pub type SharedSlots = Arc<Mutex<Vec<ItemStack>>>;
+
+impl WindowType {
+    /// Slots of the menu itself, the player's inventory follows them unless it is a lectern.
+    pub const fn slot_count(self) -> usize {
+        match self {
+            WindowType::Generic9x1 => 9,
+            WindowType::Generic9x2 => 18,
+            WindowType::Generic9x3 | WindowType::ShulkerBox => 27,
+            WindowType::Generic9x4 => 36,
+            WindowType::Generic9x5 => 45,
+            WindowType::Generic9x6 => 54,
+            WindowType::Generic3x3 => 9,
+            WindowType::Crafter3x3 | WindowType::Crafting => 10,
+            WindowType::Anvil | WindowType::BlastFurnace | WindowType::Furnace | WindowType::Grindstone | WindowType::Merchant
+            | WindowType::Smoker | WindowType::CartographyTable => 3,
+            WindowType::Beacon | WindowType::Lectern => 1,
+            WindowType::BrewingStand | WindowType::Hopper => 5,
+            WindowType::Enchantment | WindowType::Stonecutter => 2,
+            WindowType::Loom | WindowType::Smithing => 4,
+        }
+    }
+
+    /// The slot holding the menu's output, which items cannot be put into.
+    pub const fn result_slot(self) -> Option<usize> {
+        match self {
+            WindowType::Crafting => Some(0),
+            WindowType::Crafter3x3 => Some(9),
+            WindowType::Anvil | WindowType::Grindstone | WindowType::Merchant | WindowType::CartographyTable
+            | WindowType::BlastFurnace | WindowType::Furnace | WindowType::Smoker => Some(2),
+            WindowType::Stonecutter => Some(1),
+            WindowType::Loom | WindowType::Smithing => Some(3),
+            _ => None,
+        }
+    }
+
+    /// Whether items left in the menu go back to the player when it is closed, true for menus
+    /// which are not backed by a block entity.
+    pub const fn returns_items(self) -> bool {
+        matches!(self, WindowType::Crafting | WindowType::Anvil | WindowType::Grindstone | WindowType::Merchant | WindowType::CartographyTable
+            | WindowType::Stonecutter | WindowType::Loom | WindowType::Smithing | WindowType::Enchantment)
+    }
+}
+
+/// Where a window crafts: its result slot and a square grid of `width` columns from slot `first` on.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct CraftingGrid {
+    result: usize,
+    first: usize,
+    width: usize,
+}
+
+impl CraftingGrid {
+    const INVENTORY: Self = Self { result: player_slots::CRAFTING_RESULT, first: player_slots::CRAFTING_GRID.start, width: 2 };
+    const TABLE: Self = Self { result: 0, first: 1, width: 3 };
+
+    fn slots(self) -> Range<usize> {
+        self.first..self.first + self.width * self.width
+    }
+}
+
+/// Most items crafted by one shift click, vanilla stops once the result no longer fits.
+const MAX_CRAFTS_PER_CLICK: usize = 64;
+
+/// Slots of the open window besides the player's inventory.
+#[derive(Debug)]
+struct OpenContainer {
+    window_id: i32,
+    slots: SharedSlots,
+    result_slot: Option<usize>,
+    returns_items: bool,
+    player_slots: bool,
+    crafting: Option<CraftingGrid>,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum DragKind {
+    /// Splits the carried stack evenly.
+    Even,
+    /// Puts one item into each slot.
+    One,
+    /// Fills each slot with a full stack, creative only.
+    Clone,
+}
+
+/// A player's inventory and the item on its cursor, kept in its connection's extensions.
+#[derive(Debug)]
+struct Inventory {
+    slots: [ItemStack; PLAYER_INVENTORY_SIZE],
+    selected: usize,
+    carried: ItemStack,
+    /// Revision of the open window's contents, echoed by the client with every click.
+    state_id: i32,
+    container: Option<OpenContainer>,
+    drag: Option<(DragKind, Vec<usize>)>,
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        Self {
+            slots: [ItemStack::EMPTY; PLAYER_INVENTORY_SIZE],
+            selected: 0,
+            carried: ItemStack::EMPTY,
+            state_id: 0,
+            container: None,
+            drag: None,
+        }
+    }
+}
+
+fn same_item(a: ItemStack, b: ItemStack) -> bool {
+    !a.is_empty() && !b.is_empty() && a.item_id == b.item_id
+}
+
+fn max_stack_size(item_id: i32) -> i32 {
+    items().read().unwrap().max_stack_size(item_id)
+}
+
+/// Moves as many items of `from` into `to` as fit, `to` being empty or holding the same item.
+fn merge(from: &mut ItemStack, to: &mut ItemStack) {
+    let limit = max_stack_size(from.item_id);
+    if to.is_empty() {
+        *to = ItemStack::new(from.item_id, 0);
+    }
+    let moved = from.count.min(limit - to.count).max(0);
+    to.count += moved;
+    from.count -= moved;
+    if from.count <= 0 {
+        *from = ItemStack::EMPTY;
+    }
+    if to.count <= 0 {
+        *to = ItemStack::EMPTY;
+    }
+}
+
+/// Adds `stack` to the hotbar and main inventory slots of `slots`, returning what did not fit.
+fn give_to(slots: &mut [ItemStack; PLAYER_INVENTORY_SIZE], mut stack: ItemStack) -> ItemStack {
+    let order = player_slots::HOTBAR.chain(player_slots::MAIN).collect::<Vec<_>>();
+    for fill_empty in [false, true] {
+        for slot in &order {
+            let target = &mut slots[*slot];
+            if (fill_empty && target.is_empty()) || same_item(*target, stack) {
+                merge(&mut stack, target);
+            }
+            if stack.is_empty() {
+                return stack;
+            }
+        }
+    }
+    stack
+}
+
+/// The open window as one list of slots: the container's, then the player's main inventory and hotbar.
+/// Without a container it is the player inventory window itself.
+struct WindowView<'a> {
+    inventory: &'a mut [ItemStack; PLAYER_INVENTORY_SIZE],
+    container: Option<(&'a mut Vec<ItemStack>, &'a OpenContainer)>,
+}
+
+impl WindowView<'_> {
+    fn container_len(&self) -> usize {
+        self.container.as_ref().map_or(0, |(slots, _)| slots.len())
+    }
+
+    fn len(&self) -> usize {
+        match &self.container {
+            None => PLAYER_INVENTORY_SIZE,
+            Some((slots, container)) if container.player_slots => slots.len() + 36,
+            Some((slots, _)) => slots.len(),
+        }
+    }
+
+    /// Player inventory index of a window slot past the container's.
+    fn inventory_index(&self, slot: usize) -> usize {
+        match self.container {
+            None => slot,
+            Some(_) => (player_slots::MAIN.start + slot).saturating_sub(self.container_len()),
+        }
+    }
+
+    fn slot_mut(&mut self, slot: usize) -> &mut ItemStack {
+        let index = self.inventory_index(slot);
+        match &mut self.container {
+            Some((slots, _)) if slot < slots.len() => &mut slots[slot],
+            _ => &mut self.inventory[index],
+        }
+    }
+
+    fn get(&mut self, slot: usize) -> ItemStack {
+        *self.slot_mut(slot)
+    }
+
+    fn is_result(&self, slot: usize) -> bool {
+        match &self.container {
+            None => slot == player_slots::CRAFTING_RESULT,
+            Some((_, container)) => container.result_slot == Some(slot),
+        }
+    }
+
+    /// Window slot of a hotbar slot, or of the offhand for 40.
+    fn hotbar_slot(&self, button: i8) -> Option<usize> {
+        match (button, &self.container) {
+            (0..=8, None) => Some(player_slots::HOTBAR.start + button as usize),
+            (40, None) => Some(player_slots::OFFHAND),
+            (0..=8, Some((_, container))) if container.player_slots => Some(self.container_len() + 27 + button as usize),
+            _ => None,
+        }
+    }
+
+    /// Where shift clicking `slot` moves its items, tried in order.
+    fn quick_move_targets(&self, slot: usize) -> Vec<usize> {
+        let len = self.len();
+        match &self.container {
+            None => match slot {
+                slot if player_slots::HOTBAR.contains(&slot) => player_slots::MAIN.collect(),
+                slot if player_slots::MAIN.contains(&slot) => player_slots::HOTBAR.collect(),
+                player_slots::CRAFTING_RESULT => (player_slots::MAIN.start..player_slots::HOTBAR.end).rev().collect(),
+                _ => player_slots::MAIN.chain(player_slots::HOTBAR).collect(),
+            },
+            Some((slots, _)) if slot < slots.len() => (slots.len()..len).rev().collect(),
+            Some((slots, container)) => (0..slots.len()).filter(|slot| container.result_slot != Some(*slot)).collect(),
+        }
+    }
+
+    /// Moves `slot`'s items into its quick move targets, filling matching stacks before empty slots.
+    fn quick_move(&mut self, slot: usize) {
+        let mut moving = self.get(slot);
+        if moving.is_empty() {
+            return;
+        }
+        let targets = self.quick_move_targets(slot);
+        for fill_empty in [false, true] {
+            for target in &targets {
+                let stack = self.slot_mut(*target);
+                if (fill_empty && stack.is_empty()) || same_item(*stack, moving) {
+                    merge(&mut moving, stack);
+                }
+                if moving.is_empty() {
+                    break;
+                }
+            }
+        }
+        *self.slot_mut(slot) = moving;
+    }
+
+    /// Whether quick moving `slot` would move all of its items.
+    fn quick_move_fits(&mut self, slot: usize) -> bool {
+        let moving = self.get(slot);
+        let limit = max_stack_size(moving.item_id);
+        let space = self.quick_move_targets(slot).into_iter()
+            .map(|target| self.get(target))
+            .map(|stack| match stack.is_empty() {
+                true => limit,
+                false if same_item(stack, moving) => (limit - stack.count).max(0),
+                false => 0,
+            })
+            .sum::<i32>();
+        space >= moving.count
+    }
+
+    fn crafting_grid(&self) -> Option<CraftingGrid> {
+        match &self.container {
+            None => Some(CraftingGrid::INVENTORY),
+            Some((_, container)) => container.crafting,
+        }
+    }
+
+    /// Recomputes the crafting result from the grid.
+    fn update_crafting_result(&mut self) {
+        let Some(grid) = self.crafting_grid() else { return };
+        let stacks = self.crafting_stacks(grid);
+        let result = recipes().read().unwrap().craft(&stacks, grid.width).map_or(ItemStack::EMPTY, |(_, recipe)| recipe.result());
+        *self.slot_mut(grid.result) = result;
+    }
+
+    fn crafting_stacks(&mut self, grid: CraftingGrid) -> Vec<ItemStack> {
+        grid.slots().map(|slot| self.get(slot)).collect()
+    }
+
+    /// Takes the crafted item the way `mode` does, using up one item of every grid slot per craft.
+    /// Returns the recipe used, if anything was crafted.
+    fn take_crafted(&mut self, grid: CraftingGrid, carried: &mut ItemStack, dropped: &mut Vec<ItemStack>, mode: i32, button: i8) -> Option<Identifier> {
+        // Shift clicking and throwing with control craft as often as the ingredients and free space allow.
+        let crafts = match (mode, button) {
+            (1, _) | (4, 1) => MAX_CRAFTS_PER_CLICK,
+            _ => 1,
+        };
+        let mut crafted = None;
+        for _ in 0..crafts {
+            let result = self.get(grid.result);
+            if result.is_empty() {
+                break;
+            }
+            let stacks = self.crafting_stacks(grid);
+            let recipe = recipes().read().unwrap().craft(&stacks, grid.width).map(|(id, _)| id.clone());
+            match mode {
+                0 if carried.is_empty() => *carried = result,
+                0 if same_item(*carried, result) && carried.count + result.count <= max_stack_size(result.item_id) => carried.count += result.count,
+                1 if self.quick_move_fits(grid.result) => self.quick_move(grid.result),
+                2 => match self.hotbar_slot(button) {
+                    Some(target) if self.get(target).is_empty() => *self.slot_mut(target) = result,
+                    _ => break,
+                },
+                4 => dropped.push(result),
+                _ => break,
+            }
+            for slot in grid.slots() {
+                let stack = self.slot_mut(slot);
+                stack.count -= 1;
+                if stack.count <= 0 {
+                    *stack = ItemStack::EMPTY;
+                }
+            }
+            self.update_crafting_result();
+            crafted = crafted.or(recipe);
+        }
+        crafted
+    }
+}
+
+impl Inventory {
+    fn view<'a>(&'a mut self, container: Option<&'a mut Vec<ItemStack>>) -> WindowView<'a> {
+        WindowView { inventory: &mut self.slots, container: container.zip(self.container.as_ref()) }
+    }
+
+    /// Adds `stack` to the hotbar and main inventory, returning what did not fit.
+    fn give(&mut self, stack: ItemStack) -> ItemStack {
+        give_to(&mut self.slots, stack)
+    }
+
+    fn content_packet(&mut self) -> SetContainerContent {
+        self.state_id = self.state_id.wrapping_add(1) & 0x7FFF;
+        let (window_id, slots) = match &self.container {
+            Some(container) => {
+                let mut slots = container.slots.lock().unwrap().clone();
+                if container.player_slots {
+                    slots.extend_from_slice(&self.slots[player_slots::MAIN.start..player_slots::HOTBAR.end]);
+                }
+                (container.window_id, slots)
+            }
+            None => (0, self.slots.to_vec()),
+        };
+        SetContainerContent { window_id: window_id as u8, state_id: VarInt(self.state_id), slots, carried_item: self.carried }
+    }
+}
+
+/// Sets one slot of a window, `state_id` is that of the window's contents afterwards.
+#[derive(Debug, Clone, Encode)]
+pub struct SetContainerSlot {
+    pub window_id: i8,
+    pub state_id: VarInt,
+    pub slot: i16,
+    pub slot_data: ItemStack,
+}
+
+impl ClientboundPacket for SetContainerSlot {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::SetContainerSlot;
+}
+
+#[derive(Debug, Clone)]
+struct ClickContainer {
+    window_id: u8,
+    state_id: VarInt,
+    slot: i16,
+    button: i8,
+    mode: VarInt,
+    /// Slots as the client predicts them after the click.
+    changed_slots: Vec<(i16, ItemStack)>,
+    carried_item: ItemStack,
+}
+
+impl Decode for ClickContainer {
+    fn decode(reader: &mut impl Read) -> std::io::Result<Self> {
+        let window_id = u8::decode(reader)?;
+        let state_id = VarInt::decode(reader)?;
+        let slot = i16::decode(reader)?;
+        let button = i8::decode(reader)?;
+        let mode = VarInt::decode(reader)?;
+        let count = VarInt::decode(reader)?.0;
+        if !(0..=MAX_CHANGED_SLOTS as i32).contains(&count) {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{} changed slots", count)));
+        }
+        let changed_slots = (0..count)
+            .map(|_| Ok((i16::decode(reader)?, ItemStack::decode(reader)?)))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        let carried_item = ItemStack::decode(reader)?;
+        Ok(Self { window_id, state_id, slot, button, mode, changed_slots, carried_item })
+    }
+}
+
+/// Contents of the open window under a new state id, the player inventory when no other is open.
+pub fn inventory_content(connection: &ConnectionHandle) -> SetContainerContent {
+    connection.extensions(|extensions| extensions.get_or_default::<Inventory>().content_packet())
+}
+
+/// Sends the whole open window, see [`inventory_content`].
+pub fn send_inventory(connection: &ConnectionHandle) -> anyhow::Result<()> {
+    connection.send(&inventory_content(connection))
+}
+
+/// A slot of the player inventory window, see [`player_slots`].
+pub fn inventory_slot(connection: &ConnectionHandle, slot: usize) -> ItemStack {
+    connection.extensions(|extensions| extensions.get::<Inventory>().and_then(|inventory| inventory.slots.get(slot).copied()))
+        .unwrap_or(ItemStack::EMPTY)
+}
+
+/// Every slot of the player inventory window, see [`player_slots`].
+pub fn inventory_slots(connection: &ConnectionHandle) -> [ItemStack; PLAYER_INVENTORY_SIZE] {
+    connection.extensions(|extensions| extensions.get::<Inventory>().map(|inventory| inventory.slots))
+        .unwrap_or([ItemStack::EMPTY; PLAYER_INVENTORY_SIZE])
+}
+
+/// Replaces a slot of the player inventory window and tells the client.
+pub fn set_inventory_slot(connection: &ConnectionHandle, slot: usize, stack: ItemStack) -> anyhow::Result<()> {
+    if slot >= PLAYER_INVENTORY_SIZE {
+        bail!("Inventory slot {} out of range", slot);
+    }
+    let state_id = connection.extensions(|extensions| {
+        let inventory = extensions.get_or_default::<Inventory>();
+        inventory.slots[slot] = stack;
+        inventory.state_id
+    });
+    connection.send(&SetContainerSlot { window_id: 0, state_id: VarInt(state_id), slot: slot as i16, slot_data: stack })?;
+    trigger_inventory_changed(connection)
+}
+
+/// Puts `stack` in a hotbar slot, it is what the player holds while the slot is selected.
+pub fn set_hotbar_item(connection: &ConnectionHandle, slot: usize, stack: ItemStack) -> anyhow::Result<()> {
+    if slot >= HOTBAR_SLOTS {
+        bail!("Hotbar slot {} out of range", slot);
+    }
+    set_inventory_slot(connection, player_slots::HOTBAR.start + slot, stack)
+}
+
+/// The stack in the selected hotbar slot.
+pub fn held_item(connection: &ConnectionHandle) -> ItemStack {
+    connection.extensions(|extensions| extensions.get::<Inventory>().map(|inventory| inventory.slots[player_slots::HOTBAR.start + inventory.selected]))
+        .unwrap_or(ItemStack::EMPTY)
+}
+
+/// Adds `stack` to the player's inventory and drops what does not fit.
+pub fn give_item(connection: &ConnectionHandle, stack: ItemStack) -> anyhow::Result<()> {
+    let leftover = connection.extensions(|extensions| extensions.get_or_default::<Inventory>().give(stack));
+    if !leftover.is_empty() {
+        drop_item(connection, leftover);
+    }
+    send_inventory(connection)?;
+    trigger_inventory_changed(connection)
+}
+
+/// Throws `stack` from the player's eyes in the direction it looks, as an item entity which
+/// disappears after [`ITEM_LIFETIME`] ticks.
+pub fn drop_item(connection: &ConnectionHandle, stack: ItemStack) {
+    let Some(position) = player_position(connection) else { return };
+    let (yaw, pitch) = (position.yaw.to_radians() as f64, position.pitch.to_radians() as f64);
+    let velocity = Vec3::new(-yaw.sin() * pitch.cos(), -pitch.sin() + 0.1, yaw.cos() * pitch.cos()) * 0.3;
+    spawn_item(connection, Vec3::new(position.x, position.y + 1.32, position.z), velocity, stack);
+}
+
+/// Empties the player's inventory and cursor, spilling every stack around it like a dying player.
+pub fn drop_inventory(connection: &ConnectionHandle) -> anyhow::Result<()> {
+    let stacks = connection.extensions(|extensions| {
+        let inventory = extensions.get_or_default::<Inventory>();
+        let slots = std::mem::replace(&mut inventory.slots, [ItemStack::EMPTY; PLAYER_INVENTORY_SIZE]);
+        // The crafting result is only a preview of the grid, which is spilled instead.
+        let mut stacks = slots[player_slots::CRAFTING_GRID.start..].to_vec();
+        stacks.push(std::mem::take(&mut inventory.carried));
+        stacks
+    });
+    if let Some(position) = player_position(connection) {
+        let seed = RandomState::new().hash_one(SystemTime::now());
+        for (index, stack) in stacks.into_iter().filter(|stack| !stack.is_empty()).enumerate() {
+            // Spread the stacks evenly around the player, each with its own angle and speed.
+            let angle = (seed % 360) as f64 + index as f64 * 137.5;
+            let speed = 0.1 + (index % 5) as f64 * 0.05;
+            let velocity = Vec3::new(-angle.to_radians().sin() * speed, 0.2, angle.to_radians().cos() * speed);
+            spawn_item(connection, Vec3::new(position.x, position.y + 1.32, position.z), velocity, stack);
+        }
+    }
+    send_inventory(connection)?;
+    trigger_inventory_changed(connection)
+}
+
+/// Spawns `stack` as an item entity in the dimension of the player dropping it, which disappears
+/// after [`ITEM_LIFETIME`] ticks.
+fn spawn_item(connection: &ConnectionHandle, position: Vec3, velocity: Vec3, stack: ItemStack) {
+    let dimension = player_dimension(connection);
+    let mut entities = entities().write().unwrap();
+    let entity_id = entities.spawn(EntityType::ITEM, None, position);
+    if let Some(entity) = entities.get_mut(entity_id) {
+        entity.velocity = velocity;
+        entity.dimension = dimension;
+        entity.metadata.set(ITEM_METADATA_INDEX, MetadataValue::Slot(stack));
+    }
+    schedule_item_despawn(&mut entities, entity_id, ITEM_LIFETIME);
+}
+
+/// Scheduler tick at which an item entity disappears, from which its `Age` is saved.
+#[derive(Debug, Copy, Clone)]
+struct ItemDespawn(u64);
+
+fn schedule_item_despawn(manager: &mut EntityManager, entity_id: EntityId, lifetime: u64) {
+    manager.insert_component(entity_id, ItemDespawn(scheduler().current_tick() + lifetime));
+    scheduler().run_later(format!("Despawn item {}", entity_id), lifetime, move || {
+        entities().write().unwrap().remove(entity_id);
+    });
+}
+
+/// Adds the `Item` and `Age` vanilla saves for item entities, `false` if the item has no stack to save.
+pub(crate) fn write_item_nbt(manager: &EntityManager, entity: &Entity, nbt: &mut NbtCompound) -> bool {
+    let Some(MetadataValue::Slot(stack)) = entity.metadata.get(ITEM_METADATA_INDEX) else { return false };
+    let Some(name) = items().read().unwrap().name(stack.item_id).map(ToString::to_string) else { return false };
+    let remaining = manager.component::<ItemDespawn>(entity.id())
+        .map_or(ITEM_LIFETIME, |despawn| despawn.0.saturating_sub(scheduler().current_tick()));
+    nbt.insert("Item", NbtCompound::new().with("id", name).with("count", stack.count))
+        .insert("Age", ITEM_LIFETIME.saturating_sub(remaining) as i16);
+    true
+}
+
+/// Gives an item entity spawned from `nbt` its stack back, it disappears once its saved `Age` reaches [`ITEM_LIFETIME`].
+pub(crate) fn read_item_nbt(manager: &mut EntityManager, entity_id: EntityId, nbt: &NbtCompound) -> anyhow::Result<()> {
+    let item = nbt.get_compound("Item").ok_or_else(|| anyhow!("Item entity without an item"))?;
+    let name = item.get_str("id").ok_or_else(|| anyhow!("Item without an id"))?;
+    let item_id = items().read().unwrap().id(&name.parse::<Identifier>()?).ok_or_else(|| anyhow!("Unknown item {}", name))?;
+    let stack = ItemStack::new(item_id, item.get_i64("count").unwrap_or(1) as i32);
+    if let Some(entity) = manager.get_mut(entity_id) {
+        entity.metadata.set(ITEM_METADATA_INDEX, MetadataValue::Slot(stack));
+    }
+    let age = nbt.get_i64("Age").unwrap_or_default().max(0) as u64;
+    schedule_item_despawn(manager, entity_id, ITEM_LIFETIME.saturating_sub(age).max(1));
+    Ok(())
+}
+
+/// Opens a container window showing `slots` above the player's inventory and returns its id.
+/// Players who open the same slots see each other's changes.
+pub fn open_container(connection: &ConnectionHandle, window_type: WindowType, title: TextComponent, slots: SharedSlots) -> anyhow::Result<i32> {
+    let window_id = attach_container(connection, WindowKind::Menu(window_type), slots);
+    connection.send(&OpenScreen { window_id: VarInt(window_id), window_type: VarInt(window_type.network_id()), title })?;
+    send_inventory(connection)?;
+    Ok(window_id)
+}
+
+/// Allocates a window for `kind` and makes `slots` the slots it shows above the player's inventory.
+pub(crate) fn attach_container(connection: &ConnectionHandle, kind: WindowKind, slots: SharedSlots) -> i32 {
+    let window_id = allocate_window(connection, kind);
+    let (result_slot, returns_items, player_slots) = match kind {
+        WindowKind::Menu(window_type) => (window_type.result_slot(), window_type.returns_items(), window_type != WindowType::Lectern),
+        WindowKind::Horse { .. } => (None, false, true),
+    };
+    let crafting = (kind == WindowKind::Menu(WindowType::Crafting)).then_some(CraftingGrid::TABLE);
+    let container = OpenContainer { window_id, slots, result_slot, returns_items, player_slots, crafting };
+    connection.extensions(|extensions| extensions.get_or_default::<Inventory>().container = Some(container));
+    window_id
+}
+
+/// Detaches the container of a closed window. Items on the cursor, in the player's crafting grid
+/// and in menus which do not keep them go back to the inventory, or are dropped if it is full.
+pub(crate) fn close_container(connection: &ConnectionHandle, window_id: i32) {
+    let returned = connection.extensions(|extensions| {
+        let inventory = extensions.get_or_default::<Inventory>();
+        let mut returned = vec![std::mem::take(&mut inventory.carried)];
+        inventory.drag = None;
+        match inventory.container.take_if(|container| container.window_id == window_id) {
+            Some(container) if container.returns_items => {
+                let mut slots = container.slots.lock().unwrap();
+                for (slot, stack) in slots.iter_mut().enumerate() {
+                    if container.result_slot != Some(slot) {
+                        returned.push(std::mem::take(stack));
+                    }
+                }
+            }
+            Some(_) => {}
+            None if window_id == 0 => {
+                for slot in player_slots::CRAFTING_GRID {
+                    returned.push(std::mem::take(&mut inventory.slots[slot]));
+                }
+                inventory.slots[player_slots::CRAFTING_RESULT] = ItemStack::EMPTY;
+            }
+            None => {}
+        }
+        returned.into_iter()
+            .filter(|stack| !stack.is_empty())
+            .map(|stack| inventory.give(stack))
+            .filter(|leftover| !leftover.is_empty())
+            .collect::<Vec<_>>()
+    });
+    for leftover in returned {
+        drop_item(connection, leftover);
+    }
+}
+
+/// What a click did besides changing slots.
+#[derive(Debug, Default)]
+struct ClickOutcome {
+    /// Stacks thrown out of the window.
+    dropped: Vec<ItemStack>,
+    /// Recipe of the items taken from a crafting result.
+    crafted: Option<Identifier>,
+}
+
+/// Performs a click the way the client does.
+fn click(inventory: &mut Inventory, container: Option<&mut Vec<ItemStack>>, slot: i16, button: i8, mode: i32, creative: bool) -> anyhow::Result<ClickOutcome> {
+    let mut carried = inventory.carried;
+    let mut drag = inventory.drag.take();
+    let mut view = inventory.view(container);
+    let mut dropped = Vec::new();
+    let mut crafted = None;
+    let index = match slot {
+        OUTSIDE => None,
+        slot if (0..view.len() as i16).contains(&slot) => Some(slot as usize),
+        // Clicking the border of the window.
+        -1 => None,
+        slot => bail!("Slot {} out of range", slot),
+    };
+    let crafting = view.crafting_grid().filter(|grid| index == Some(grid.result));
+    match (mode, index) {
+        (0 | 1 | 2 | 4, Some(_)) if crafting.is_some() => {
+            let grid = crafting.expect("Checked by the guard");
+            crafted = view.take_crafted(grid, &mut carried, &mut dropped, mode, button);
+        }
+        // Pickup: the left button takes or puts the whole stack, the right one half of it or one item.
+        (0, None) if slot == OUTSIDE => match button {
+            0 => dropped.push(std::mem::take(&mut carried)),
+            1 if !carried.is_empty() => {
+                dropped.push(ItemStack::new(carried.item_id, 1));
+                carried.count -= 1;
+            }
+            _ => {}
+        },
+        (0, Some(index)) => {
+            let result = view.is_result(index);
+            let stack = view.slot_mut(index);
+            match (button, carried.is_empty()) {
+                (0, true) => carried = std::mem::take(stack),
+                (1, true) => {
+                    let taken = match result {
+                        true => stack.count,
+                        false => (stack.count + 1) / 2,
+                    };
+                    carried = ItemStack::new(stack.item_id, taken);
+                    stack.count -= taken;
+                }
+                (_, false) if result => {
+                    if same_item(*stack, carried) && carried.count + stack.count <= max_stack_size(carried.item_id) {
+                        carried.count += std::mem::take(stack).count;
+                    }
+                }
+                (0, false) if stack.is_empty() || same_item(*stack, carried) => merge(&mut carried, stack),
+                (1, false) if stack.is_empty() || same_item(*stack, carried) => {
+                    let mut one = ItemStack::new(carried.item_id, 1);
+                    merge(&mut one, stack);
+                    carried.count -= 1 - one.count.max(0);
+                }
+                (0 | 1, false) => std::mem::swap(stack, &mut carried),
+                _ => bail!("Invalid button {} for pickup", button),
+            }
+            if stack.count <= 0 {
+                *stack = ItemStack::EMPTY;
+            }
+        }
+        (1, Some(index)) => view.quick_move(index),
+        (2, Some(index)) => {
+            let Some(hotbar) = view.hotbar_slot(button) else { bail!("Invalid hotbar button {}", button) };
+            let (from, to) = (view.get(index), view.get(hotbar));
+            if !view.is_result(index) || to.is_empty() {
+                *view.slot_mut(index) = to;
+                *view.slot_mut(hotbar) = from;
+            }
+        }
+        (3, Some(index)) => {
+            let stack = view.get(index);
+            if creative && carried.is_empty() && !stack.is_empty() {
+                carried = ItemStack::new(stack.item_id, max_stack_size(stack.item_id));
+            }
+        }
+        (4, Some(index)) => {
+            let stack = view.slot_mut(index);
+            if !stack.is_empty() {
+                let count = match button {
+                    0 => 1,
+                    _ => stack.count,
+                };
+                dropped.push(ItemStack::new(stack.item_id, count));
+                stack.count -= count;
+                if stack.count <= 0 {
+                    *stack = ItemStack::EMPTY;
+                }
+            }
+        }
+        (5, index) => match (button, index) {
+            (0 | 4 | 8, None) => {
+                let kind = match button {
+                    0 => DragKind::Even,
+                    4 => DragKind::One,
+                    _ => DragKind::Clone,
+                };
+                if !carried.is_empty() && (kind != DragKind::Clone || creative) {
+                    drag = Some((kind, Vec::new()));
+                }
+            }
+            (1 | 5 | 9, Some(index)) => {
+                let stack = view.get(index);
+                if let Some((_, slots)) = &mut drag {
+                    if !view.is_result(index) && (stack.is_empty() || same_item(stack, carried)) && !slots.contains(&index) {
+                        slots.push(index);
+                    }
+                }
+            }
+            (2 | 6 | 10, None) => {
+                if let Some((kind, slots)) = drag.take().filter(|(_, slots)| !slots.is_empty()) {
+                    let per_slot = match kind {
+                        DragKind::Even => (carried.count / slots.len() as i32).max(1),
+                        DragKind::One => 1,
+                        DragKind::Clone => max_stack_size(carried.item_id),
+                    };
+                    for index in slots {
+                        let stack = view.slot_mut(index);
+                        let mut portion = match kind {
+                            DragKind::Clone => ItemStack::new(carried.item_id, per_slot),
+                            _ => ItemStack::new(carried.item_id, per_slot.min(carried.count)),
+                        };
+                        let offered = portion.count;
+                        merge(&mut portion, stack);
+                        if kind != DragKind::Clone {
+                            carried.count -= offered - portion.count.max(0);
+                        }
+                    }
+                    if carried.count <= 0 {
+                        carried = ItemStack::EMPTY;
+                    }
+                }
+            }
+            // Out of order drag events end the drag, like in vanilla.
+            _ => drag = None,
+        },
+        // Double click collects items of the carried kind, full stacks last.
+        (6, Some(_)) => {
+            let limit = max_stack_size(carried.item_id);
+            for full in [false, true] {
+                for index in 0..view.len() {
+                    if carried.count >= limit || view.is_result(index) {
+                        continue;
+                    }
+                    let stack = view.slot_mut(index);
+                    if same_item(*stack, carried) && (stack.count >= limit) == full {
+                        let taken = stack.count.min(limit - carried.count);
+                        stack.count -= taken;
+                        carried.count += taken;
+                        if stack.is_empty() {
+                            *stack = ItemStack::EMPTY;
+                        }
+                    }
+                }
+            }
+        }
+        (0..=6, _) => {}
+        _ => bail!("Invalid click mode {}", mode),
+    }
+    view.update_crafting_result();
+    inventory.carried = carried;
+    inventory.drag = drag;
+    dropped.retain(|stack| !stack.is_empty());
+    Ok(ClickOutcome { dropped, crafted })
+}
+
+/// Grid slots of a crafting grid and what each has to hold to craft `recipe`, the pattern in the
+/// top left corner. `None` when the recipe does not fit the grid or is not a crafting recipe.
+fn recipe_layout(recipe: &Recipe, grid: CraftingGrid) -> Option<Vec<(usize, Ingredient)>> {
+    match recipe {
+        Recipe::Shaped(recipe) if recipe.width <= grid.width && recipe.height <= grid.width => Some(
+            recipe.pattern.iter().enumerate()
+                .filter_map(|(index, ingredient)| {
+                    let slot = grid.first + index / recipe.width * grid.width + index % recipe.width;
+                    ingredient.clone().map(|ingredient| (slot, ingredient))
+                })
+                .collect()
+        ),
+        Recipe::Shapeless(recipe) if recipe.ingredients.len() <= grid.width * grid.width => Some(
+            recipe.ingredients.iter().cloned().enumerate().map(|(index, ingredient)| (grid.first + index, ingredient)).collect()
+        ),
+        _ => None,
+    }
+}
+
+/// Fills the crafting grid of window `window_id` with ingredients for `recipe` from the player's
+/// inventory, as many sets as stack when `make_all` is set. What was in the grid goes back first.
+/// Returns false, changing nothing, if the player lacks the ingredients or their items do not fit.
+pub(crate) fn place_recipe(connection: &ConnectionHandle, window_id: i32, recipe: &Recipe, make_all: bool) -> anyhow::Result<bool> {
+    let placed = connection.extensions(|extensions| {
+        let inventory = extensions.get_or_default::<Inventory>();
+        let shared = match &inventory.container {
+            None if window_id == 0 => None,
+            Some(container) if container.window_id == window_id => Some(container.slots.clone()),
+            _ => return false,
+        };
+        let mut container = shared.as_ref().map(|slots| slots.lock().unwrap());
+        let mut slots = inventory.slots;
+        let mut view = WindowView { inventory: &mut slots, container: container.as_deref_mut().zip(inventory.container.as_ref()) };
+        let Some(grid) = view.crafting_grid() else { return false };
+        let Some(layout) = recipe_layout(recipe, grid) else { return false };
+
+        // Work on a copy of the player's slots so that nothing changes unless a set is placed.
+        let mut grid_stacks = vec![ItemStack::EMPTY; grid.width * grid.width];
+        let mut pool = *view.inventory;
+        for slot in grid.slots() {
+            let stack = std::mem::take(view.slot_mut(slot));
+            if !give_to(&mut pool, stack).is_empty() {
+                return false;
+            }
+        }
+        let sets = match make_all {
+            true => MAX_CRAFTS_PER_CLICK,
+            false => 1,
+        };
+        let mut placed = 0;
+        for _ in 0..sets {
+            let (mut trial_pool, mut trial_grid) = (pool, grid_stacks.clone());
+            let complete = layout.iter().all(|(slot, ingredient)| {
+                let target = &mut trial_grid[slot - grid.first];
+                // Empty places take the item the player has most of, so that further sets can stack on it.
+                let total = |item_id: i32| trial_pool.iter().filter(|stack| stack.item_id == item_id).map(|stack| stack.count).sum::<i32>();
+                let source = player_slots::MAIN.chain(player_slots::HOTBAR)
+                    .filter(|index| {
+                        let stack = trial_pool[*index];
+                        ingredient.matches(&stack) && (target.is_empty() || (same_item(*target, stack) && target.count < max_stack_size(stack.item_id)))
+                    })
+                    .max_by_key(|index| (total(trial_pool[*index].item_id), std::cmp::Reverse(*index)));
+                let Some(source) = source else { return false };
+                let mut one = ItemStack::new(trial_pool[source].item_id, 1);
+                trial_pool[source].count -= 1;
+                if trial_pool[source].is_empty() {
+                    trial_pool[source] = ItemStack::EMPTY;
+                }
+                merge(&mut one, target);
+                true
+            });
+            if !complete {
+                break;
+            }
+            (pool, grid_stacks) = (trial_pool, trial_grid);
+            placed += 1;
+        }
+        if placed == 0 {
+            // Put the grid back, nothing was taken out of it for good.
+            return false;
+        }
+        *view.inventory = pool;
+        for (slot, stack) in grid.slots().zip(grid_stacks) {
+            *view.slot_mut(slot) = stack;
+        }
+        view.update_crafting_result();
+        drop(container);
+        inventory.slots = slots;
+        true
+    });
+    if placed {
+        send_inventory(connection)?;
+        trigger_inventory_changed(connection)?;
+    }
+    Ok(placed)
+}
+
+/// Tells the other players who have `slots` open about the slots which changed.
+fn broadcast_container(connections: &ConnectionRegistry, actor: &ConnectionHandle, slots: &SharedSlots, before: &[ItemStack]) {
+    let after = slots.lock().unwrap().clone();
+    let changed = after.iter().enumerate()
+        .filter(|(slot, stack)| before.get(*slot) != Some(stack))
+        .map(|(slot, stack)| (slot as i16, *stack))
+        .collect::<Vec<_>>();
+    if changed.is_empty() {
+        return;
+    }
+    for player in connections.players() {
+        if player.id() == actor.id() {
+            continue;
+        }
+        let viewing = player.extensions(|extensions| {
+            let inventory = extensions.get_mut::<Inventory>()?;
+            let container = inventory.container.as_ref().filter(|container| Arc::ptr_eq(&container.slots, slots))?;
+            inventory.state_id = inventory.state_id.wrapping_add(1) & 0x7FFF;
+            Some((container.window_id, inventory.state_id))
+        });
+        if let Some((window_id, state_id)) = viewing {
+            for (slot, stack) in &changed {
+                let _ = player.send(&SetContainerSlot { window_id: window_id as i8, state_id: VarInt(state_id), slot: *slot, slot_data: *stack });
+            }
+        }
+    }
+}
+
+#[packet_processor(PacketType::ClickContainer)]
+pub(crate) fn click_container_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    let click_packet: ClickContainer = decode_from_slice(&packet.payload)?;
+    let window_id = click_packet.window_id as i32;
+    let connection = context.connection.clone();
+    let creative = is_creative(&connection);
+    let outcome = connection.extensions(|extensions| {
+        let inventory = extensions.get_or_default::<Inventory>();
+        // The player inventory is window 0 and can only be clicked while no container is open.
+        let shared = match &inventory.container {
+            None if window_id == 0 => None,
+            Some(container) if container.window_id == window_id => Some(container.slots.clone()),
+            _ => return None,
+        };
+        let mut container = shared.as_ref().map(|slots| slots.lock().unwrap());
+        let before = container.as_ref().map(|slots| slots.to_vec());
+        let crafting_result = |inventory: &mut Inventory, container: Option<&mut Vec<ItemStack>>| {
+            let mut view = inventory.view(container);
+            view.crafting_grid().map(|grid| (grid.result, view.get(grid.result)))
+        };
+        let result_before = crafting_result(inventory, container.as_deref_mut());
+        let (clicked, mut in_sync) = match click(inventory, container.as_deref_mut(), click_packet.slot, click_packet.button, click_packet.mode.0, creative) {
+            Ok(clicked) => (clicked, true),
+            Err(err) => {
+                debug!("{} sent an invalid click: {}", connection.id(), err);
+                (ClickOutcome::default(), false)
+            }
+        };
+        // The client's prediction only matches when it knew the latest contents, otherwise all slots are resent.
+        in_sync &= click_packet.state_id.0 == inventory.state_id && click_packet.carried_item == inventory.carried;
+        // Clients do not predict crafting results, the server sends them whenever they change.
+        let result_after = crafting_result(inventory, container.as_deref_mut());
+        let is_result = |slot: i16| result_after.is_some_and(|(result, _)| result as i16 == slot);
+        if in_sync {
+            let mut view = inventory.view(container.as_deref_mut());
+            in_sync = click_packet.changed_slots.iter()
+                .filter(|(slot, _)| !is_result(*slot))
+                .all(|(slot, stack)| (0..view.len() as i16).contains(slot) && view.get(*slot as usize) == *stack);
+        }
+        let predicted_result = click_packet.changed_slots.iter().find(|(slot, _)| is_result(*slot)).map(|(_, stack)| *stack);
+        let result_update = result_after
+            .filter(|(_, stack)| result_before != result_after || predicted_result.is_some_and(|predicted| predicted != *stack))
+            .map(|(slot, stack)| SetContainerSlot { window_id: window_id as i8, state_id: VarInt(inventory.state_id), slot: slot as i16, slot_data: stack });
+        drop(container);
+        let content = (!in_sync).then(|| inventory.content_packet());
+        Some((content, result_update, clicked, shared.zip(before)))
+    });
+    let Some((content, result_update, clicked, shared)) = outcome else {
+        debug!("{} clicked in window {} which is not open", connection.id(), window_id);
+        return Ok(());
+    };
+    // A full resync already carries the crafting result.
+    match (content, result_update) {
+        (Some(content), _) => context.send(&content)?,
+        (None, Some(result_update)) => context.send(&result_update)?,
+        (None, None) => {}
+    }
+    for stack in clicked.dropped {
+        drop_item(&connection, stack);
+    }
+    if let Some(recipe) = clicked.crafted {
+        unlock_recipes(&connection, &[recipe])?;
+    }
+    if let Some((slots, before)) = shared {
+        broadcast_container(&context.connections, &connection, &slots, &before);
+    }
+    trigger_inventory_changed(&connection)
+}
+
+/// Drops one item of the held stack, or all of it.
+pub(crate) fn drop_held_item(connection: &ConnectionHandle, whole_stack: bool) -> anyhow::Result<()> {
+    let (slot, dropped, remaining) = connection.extensions(|extensions| {
+        let inventory = extensions.get_or_default::<Inventory>();
+        let slot = player_slots::HOTBAR.start + inventory.selected;
+        let stack = &mut inventory.slots[slot];
+        let count = match whole_stack {
+            true => stack.count,
+            false => stack.count.min(1),
+        };
+        let dropped = ItemStack::new(stack.item_id, count);
+        stack.count -= count;
+        if stack.is_empty() {
+            *stack = ItemStack::EMPTY;
+        }
+        (slot, dropped, *stack)
+    });
+    if dropped.is_empty() {
+        return Ok(());
+    }
+    if let Some(name) = items().read().unwrap().name(dropped.item_id) {
+        increment_stat(connection, StatType::Dropped, name, dropped.count);
+    }
+    increment_custom_stat(connection, "drop", 1);
+    drop_item(connection, dropped);
+    set_inventory_slot(connection, slot, remaining)
+}
+
+/// Swaps the held stack with the one in the offhand.
+pub(crate) fn swap_hands(connection: &ConnectionHandle) -> anyhow::Result<()> {
+    let (slot, main_hand, offhand) = connection.extensions(|extensions| {
+        let inventory = extensions.get_or_default::<Inventory>();
+        let slot = player_slots::HOTBAR.start + inventory.selected;
+        inventory.slots.swap(slot, player_slots::OFFHAND);
+        (slot, inventory.slots[slot], inventory.slots[player_slots::OFFHAND])
+    });
+    set_inventory_slot(connection, slot, main_hand)?;
+    set_inventory_slot(connection, player_slots::OFFHAND, offhand)
+}
+
+/// Puts any item into a slot of the player inventory window, or throws it for slot -1.
+#[derive(Debug, Clone, Decode)]
+struct SetCreativeModeSlot {
+    slot: i16,
+    clicked_item: ItemStack,
+}
+
+#[packet_processor(PacketType::SetCreativeModeSlot)]
+pub(crate) fn set_creative_mode_slot_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    let SetCreativeModeSlot { slot, clicked_item } = decode_from_slice(&packet.payload)?;
+    let in_range = (1..PLAYER_INVENTORY_SIZE as i16).contains(&slot);
+    if slot != -1 && !in_range {
+        bail!("Creative slot {} out of range", slot);
+    }
+    let allowed = is_creative(&context.connection) && items().read().unwrap().is_valid(&clicked_item);
+    match (slot, allowed) {
+        (-1, true) if !clicked_item.is_empty() => drop_item(&context.connection, clicked_item),
+        (-1, _) => {}
+        (slot, true) => {
+            context.connection.extensions(|extensions| extensions.get_or_default::<Inventory>().slots[slot as usize] = clicked_item);
+            trigger_inventory_changed(&context.connection)?;
+        }
+        (slot, false) => {
+            debug!("{} may not put {:?} into slot {}", context.connection.id(), clicked_item, slot);
+            // Reverts the client, which already shows the item.
+            let stack = inventory_slot(&context.connection, slot as usize);
+            set_inventory_slot(&context.connection, slot as usize, stack)?;
+        }
+    }
+    Ok(())
+}
+
+#[packet_processor(PacketType::SetHeldItem)]
+pub(crate) fn set_held_item_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    let slot: i16 = decode_from_slice(&packet.payload)?;
+    if !(0..HOTBAR_SLOTS as i16).contains(&slot) {
+        bail!("Hotbar slot {} out of range", slot);
+    }
+    context.connection.extensions(|extensions| extensions.get_or_default::<Inventory>().selected = slot as usize);
+    Ok(())
+}
@@ -0,0 +1,176 @@
+use std::sync::{Arc, RwLock};
+use once_cell::sync::Lazy;
+use log::{error, info};
+use dolls_core::datatype::{Encode, GlobalPos, Identifier, VarInt};
+use dolls_entities::prelude::{entities, EntityType, Vec3};
+use dolls_events::prelude::{event_bus, PlayerJoinEvent};
+use dolls_world::level::level;
+use dolls_world::game_rules::{DO_IMMEDIATE_RESPAWN, DO_LIMITED_CRAFTING, REDUCED_DEBUG_INFO};
+use crate::prelude::{abilities, announce_player, permission_level, permission_level_event, send_difficulty, death_location, dimension_type_id, load_spawn_point, game_mode, load_game_mode, previous_game_mode, release_spectators, remove_player, inventory_content, save_health, send_health, load_statistics, send_advancements, send_brand, statistics_left, send_recipe_book, send_scoreboard, send_teams, send_time_and_weather, send_world_border, send_world_spawn, start_chunk_view, start_keep_alive, stop_chunk_view, stop_keep_alive, teleport, world_spawn_position, ClientboundPacket, ClientboundPacketType, ConnectionHandle, ConnectionRegistry, ConnectionState, PacketContext};
+
+/// Join Game, the first packet in Play.
+#[derive(Debug, Clone, Encode)]
+pub struct Login {
+    pub entity_id: i32,
+    pub hardcore: bool,
+    pub dimension_names: Vec<Identifier>,
+    pub max_players: VarInt,
+    pub view_distance: VarInt,
+    pub simulation_distance: VarInt,
+    pub reduced_debug_info: bool,
+    pub enable_respawn_screen: bool,
+    pub limited_crafting: bool,
+    /// Network id in the `minecraft:dimension_type` registry.
+    pub dimension_type: VarInt,
+    pub dimension_name: Identifier,
+    pub hashed_seed: i64,
+    pub game_mode: u8,
+    /// -1 when there is none.
+    pub previous_game_mode: i8,
+    pub debug: bool,
+    pub flat: bool,
+    pub death_location: Option<GlobalPos>,
+    pub portal_cooldown: VarInt,
+    pub enforces_secure_chat: bool,
+}
+
+impl ClientboundPacket for Login {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::Login;
+}
+
+#[derive(Debug, Clone, Encode)]
+pub struct GameEvent {
+    pub event: u8,
+    pub value: f32,
+}
+
+impl GameEvent {
+    /// The bed or respawn anchor of a respawning player is missing or obstructed.
+    pub const NO_RESPAWN_BLOCK_AVAILABLE: u8 = 0;
+    pub const END_RAINING: u8 = 1;
+    pub const BEGIN_RAINING: u8 = 2;
+    /// The value is the id of the new game mode.
+    pub const CHANGE_GAME_MODE: u8 = 3;
+    /// Rain strength from 0 to 1.
+    pub const RAIN_LEVEL_CHANGE: u8 = 7;
+    /// Thunder strength from 0 to 1, only visible while it rains.
+    pub const THUNDER_LEVEL_CHANGE: u8 = 8;
+    /// 0 shows the death screen, 1 respawns right away.
+    pub const ENABLE_RESPAWN_SCREEN: u8 = 11;
+    /// 1 only lets players craft unlocked recipes.
+    pub const LIMITED_CRAFTING: u8 = 12;
+    /// Makes the client wait on the loading screen until the chunk it is in arrived.
+    pub const START_WAITING_FOR_CHUNKS: u8 = 13;
+}
+
+impl ClientboundPacket for GameEvent {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::GameEvent;
+}
+
+/// Sets up a player entering Play, e.g. by sending the command tree.
+pub trait JoinListener: Send + Sync {
+    /// Returning an error is logged, it does not stop the other listeners.
+    fn on_join(&self, context: &mut PacketContext) -> anyhow::Result<()>;
+
+    /// Called once a player in Play disconnected, after it was removed from the registry.
+    fn on_leave(&self, _connection: &ConnectionHandle) {}
+}
+
+static JOIN_LISTENERS: Lazy<RwLock<Vec<Arc<dyn JoinListener>>>> = Lazy::new(|| RwLock::new(Vec::new()));
+
+/// Registers a listener run for every player entering Play, in registration order.
+pub fn register_join_listener(listener: Arc<dyn JoinListener>) {
+    JOIN_LISTENERS.write().unwrap().push(listener);
+}
+
+pub(crate) fn start_play(context: &mut PacketContext) -> anyhow::Result<()> {
+    context.state = ConnectionState::Play;
+    let spawn = world_spawn_position();
+    let entity_id = entities().write().unwrap().spawn(EntityType::PLAYER, context.uuid, Vec3::new(spawn.x, spawn.y, spawn.z));
+    context.entity_id = Some(entity_id);
+    load_game_mode(context);
+    load_spawn_point(context);
+    send_login(context, entity_id)?;
+    context.send(&abilities(&context.connection).packet())?;
+    announce_player(context)?;
+    send_difficulty(context)?;
+    context.send(&permission_level_event(entity_id, permission_level(&context.connection)))?;
+    send_brand(context)?;
+    send_world_border(context)?;
+    send_time_and_weather(context)?;
+    send_world_spawn(context)?;
+    context.send(&GameEvent { event: GameEvent::START_WAITING_FOR_CHUNKS, value: 0.0 })?;
+    teleport(context, spawn)?;
+    let inventory = inventory_content(&context.connection);
+    context.send(&inventory)?;
+    send_health(context)?;
+    send_recipe_book(context)?;
+    send_advancements(context)?;
+    load_statistics(context);
+    send_scoreboard(context)?;
+    send_teams(context)?;
+    start_chunk_view(context);
+    start_keep_alive(context.connection.clone());
+    info!("{} entered Play from {}", context.username.as_deref().unwrap_or("?"), context.peer_addr);
+
+    event_bus().emit(&mut PlayerJoinEvent {
+        connection_id: context.connection.id(),
+        username: context.username.clone().unwrap_or_default(),
+        uuid: context.uuid.unwrap_or_default(),
+    });
+    let listeners = JOIN_LISTENERS.read().unwrap().clone();
+    for listener in listeners {
+        if let Err(err) = listener.on_join(context) {
+            error!("Join listener failed for {:?}: {}", context.username, err);
+        }
+    }
+    Ok(())
+}
+
+fn send_login(context: &mut PacketContext, entity_id: i32) -> anyhow::Result<()> {
+    let overworld = Identifier::minecraft("overworld");
+    let dimension_type = dimension_type_id(&overworld);
+    let server = &context.config.server;
+    let rules = level().read().unwrap().game_rules.clone();
+    let login = Login {
+        entity_id,
+        hardcore: false,
+        dimension_names: vec![overworld.clone(), Identifier::minecraft("the_nether"), Identifier::minecraft("the_end")],
+        max_players: VarInt(server.max_players as i32),
+        view_distance: VarInt(server.view_distance as i32),
+        simulation_distance: VarInt(server.simulation_distance as i32),
+        reduced_debug_info: rules.get_bool(REDUCED_DEBUG_INFO),
+        enable_respawn_screen: !rules.get_bool(DO_IMMEDIATE_RESPAWN),
+        limited_crafting: rules.get_bool(DO_LIMITED_CRAFTING),
+        dimension_type: VarInt(dimension_type),
+        dimension_name: overworld,
+        hashed_seed: level().read().unwrap().hashed_seed(),
+        game_mode: game_mode(&context.connection).id(),
+        previous_game_mode: previous_game_mode(&context.connection).map_or(-1, |previous| previous.id() as i8),
+        debug: false,
+        flat: false,
+        death_location: death_location(&context.connection),
+        portal_cooldown: VarInt(0),
+        enforces_secure_chat: server.enforce_secure_profile,
+    };
+    context.send(&login)
+}
+
+pub(crate) fn player_left(connection: &ConnectionHandle, connections: &ConnectionRegistry) {
+    if connection.state() != ConnectionState::Play {
+        return;
+    }
+    stop_keep_alive(connection);
+    stop_chunk_view(connection);
+    statistics_left(connection);
+    save_health(connection);
+    release_spectators(connection, connections);
+    remove_player(connection, connections);
+    if let Some(entity_id) = connection.entity_id() {
+        entities().write().unwrap().remove(entity_id);
+    }
+    let listeners = JOIN_LISTENERS.read().unwrap().clone();
+    for listener in listeners {
+        listener.on_leave(connection);
+    }
+}
@@ -0,0 +1,72 @@
+use std::time::{Duration, Instant};
+use log::debug;
+use dolls_core::datatype::{decode_from_slice, Decode, Encode};
+use dolls_macros::packet_processor;
+use dolls_tick::prelude::{scheduler, TaskGuard, TICK_DURATION};
+use crate::prelude::{update_latency, ClientboundPacket, ClientboundPacketType, ConnectionHandle, PacketContext, PacketType, RawPacket};
+
+/// How often players in Play are pinged, as in vanilla.
+pub const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Clone, PartialEq, Encode, Decode)]
+pub struct KeepAlive {
+    pub id: i64,
+}
+
+impl ClientboundPacket for KeepAlive {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::KeepAlive;
+}
+
+/// Keep-alive in flight, dropping it stops the task pinging the connection.
+struct KeepAliveState {
+    pending: Option<(i64, Instant)>,
+    _task: TaskGuard,
+}
+
+/// Pings the player every [`KEEP_ALIVE_INTERVAL`] until [`stop_keep_alive`] is called.
+pub(crate) fn start_keep_alive(connection: ConnectionHandle) {
+    let epoch = Instant::now();
+    let period = (KEEP_ALIVE_INTERVAL.as_millis() / TICK_DURATION.as_millis()) as u64;
+    let pinged = connection.clone();
+    let task = scheduler().run_repeating(format!("Keep-alive of {}", connection.id()), period, period, move || {
+        let id = epoch.elapsed().as_millis() as i64;
+        let started = pinged.extensions(|extensions| match extensions.get_mut::<KeepAliveState>() {
+            Some(state) => {
+                state.pending = Some((id, Instant::now()));
+                true
+            }
+            None => false,
+        });
+        if started {
+            let _ = pinged.send(&KeepAlive { id });
+        }
+    });
+    connection.extensions(|extensions| extensions.insert(KeepAliveState { pending: None, _task: task.guard() }));
+}
+
+/// Ends the pings, the task holds a handle of the connection until then.
+pub(crate) fn stop_keep_alive(connection: &ConnectionHandle) {
+    connection.extensions(|extensions| extensions.remove::<KeepAliveState>());
+}
+
+#[packet_processor(PacketType::KeepAlive)]
+pub(crate) fn keep_alive_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    let KeepAlive { id } = decode_from_slice(&packet.payload)?;
+    let round_trip = context.connection.extensions(|extensions| {
+        let state = extensions.get_mut::<KeepAliveState>()?;
+        match state.pending {
+            Some((pending, sent)) if pending == id => {
+                state.pending = None;
+                Some(sent.elapsed())
+            }
+            _ => None,
+        }
+    });
+    match round_trip {
+        Some(round_trip) => update_latency(&context.connections, &context.connection, round_trip),
+        None => {
+            debug!("Unexpected keep-alive {} from {:?}", id, context.username);
+            Ok(())
+        }
+    }
+}
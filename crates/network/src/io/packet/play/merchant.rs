@@ -0,0 +1,195 @@
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+use anyhow::bail;
+use dolls_core::datatype::{decode_from_slice, Encode, VarInt};
+use dolls_core::item::{ItemCost, ItemStack};
+use dolls_core::text::TextComponent;
+use dolls_macros::packet_processor;
+use crate::prelude::{open_window, open_window_of, ClientboundPacket, ClientboundPacketType, ConnectionHandle, PacketContext, PacketType, RawPacket, WindowType};
+
+/// A single trade shown in a merchant window.
+#[derive(Debug, Clone, Encode)]
+pub struct TradeOffer {
+    pub input: ItemCost,
+    pub output: ItemStack,
+    pub second_input: Option<ItemCost>,
+    pub disabled: bool,
+    pub uses: i32,
+    pub max_uses: i32,
+    pub experience: i32,
+    pub special_price: i32,
+    pub price_multiplier: f32,
+    pub demand: i32,
+}
+
+impl TradeOffer {
+    pub fn new(input: ItemCost, output: ItemStack) -> Self {
+        Self {
+            input,
+            output,
+            second_input: None,
+            disabled: false,
+            uses: 0,
+            max_uses: i32::MAX,
+            experience: 0,
+            special_price: 0,
+            price_multiplier: 0.0,
+            demand: 0,
+        }
+    }
+
+    pub fn with_second_input(mut self, second_input: ItemCost) -> Self {
+        self.second_input = Some(second_input);
+        self
+    }
+
+    pub fn with_max_uses(mut self, max_uses: i32) -> Self {
+        self.max_uses = max_uses;
+        self
+    }
+
+    pub fn with_experience(mut self, experience: i32) -> Self {
+        self.experience = experience;
+        self
+    }
+
+    pub fn is_available(&self) -> bool {
+        !self.disabled && self.uses < self.max_uses
+    }
+}
+
+#[derive(Debug, Clone, Encode)]
+pub struct MerchantOffers {
+    pub window_id: VarInt,
+    pub offers: Vec<TradeOffer>,
+    pub level: VarInt,
+    pub experience: VarInt,
+    pub regular_villager: bool,
+    pub can_restock: bool,
+}
+
+impl ClientboundPacket for MerchantOffers {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::MerchantOffers;
+}
+
+/// Called when the player picks a trade, with the index of the offer.
+pub type TradeSelectCallback = Arc<dyn Fn(&ConnectionHandle, usize, &TradeOffer) + Send + Sync>;
+
+/// Server-defined trading menu, does not need a villager behind it.
+#[derive(Clone)]
+pub struct MerchantMenu {
+    pub title: TextComponent,
+    pub offers: Vec<TradeOffer>,
+    /// Villager level from 1 to 5, only shown for regular villagers.
+    pub level: i32,
+    pub experience: i32,
+    pub regular_villager: bool,
+    pub can_restock: bool,
+    on_select: Option<TradeSelectCallback>,
+}
+
+impl MerchantMenu {
+    pub fn new(title: TextComponent) -> Self {
+        Self {
+            title,
+            offers: Vec::new(),
+            level: 1,
+            experience: 0,
+            regular_villager: false,
+            can_restock: false,
+            on_select: None,
+        }
+    }
+
+    pub fn with_offer(mut self, offer: TradeOffer) -> Self {
+        self.offers.push(offer);
+        self
+    }
+
+    pub fn on_select(mut self, callback: impl Fn(&ConnectionHandle, usize, &TradeOffer) + Send + Sync + 'static) -> Self {
+        self.on_select = Some(Arc::new(callback));
+        self
+    }
+
+    fn offers_packet(&self, window_id: i32) -> MerchantOffers {
+        MerchantOffers {
+            window_id: VarInt(window_id),
+            offers: self.offers.clone(),
+            level: VarInt(self.level),
+            experience: VarInt(self.experience),
+            regular_villager: self.regular_villager,
+            can_restock: self.can_restock,
+        }
+    }
+}
+
+impl Debug for MerchantMenu {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MerchantMenu")
+            .field("title", &self.title)
+            .field("offers", &self.offers)
+            .field("level", &self.level)
+            .field("experience", &self.experience)
+            .field("regular_villager", &self.regular_villager)
+            .field("can_restock", &self.can_restock)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Merchant menu a player has open, stale once its window is no longer the open one.
+#[derive(Debug)]
+struct MerchantSession {
+    window_id: i32,
+    menu: MerchantMenu,
+    selected: Option<usize>,
+}
+
+/// Opens a trading window with the menu's offers and returns the window id.
+pub fn open_merchant(connection: &ConnectionHandle, menu: MerchantMenu) -> anyhow::Result<i32> {
+    let window_id = open_window(connection, WindowType::Merchant, menu.title.clone())?;
+    connection.send(&menu.offers_packet(window_id))?;
+    connection.extensions(|extensions| extensions.insert(MerchantSession { window_id, menu, selected: None }));
+    Ok(window_id)
+}
+
+/// Replaces the offers of the player's open merchant window, e.g. after a trade was used up.
+pub fn update_merchant_offers(connection: &ConnectionHandle, offers: Vec<TradeOffer>) -> anyhow::Result<()> {
+    let Some(window) = open_window_of(connection) else { bail!("No window is open") };
+    let packet = connection.extensions(|extensions| {
+        let session = extensions.get_mut::<MerchantSession>().filter(|session| session.window_id == window.id)?;
+        session.menu.offers = offers;
+        Some(session.menu.offers_packet(window.id))
+    });
+    let Some(packet) = packet else { bail!("Open window {} is not a merchant", window.id) };
+    connection.send(&packet)
+}
+
+/// Index of the trade the player last selected in their open merchant window.
+pub fn selected_trade(connection: &ConnectionHandle) -> Option<usize> {
+    let window = open_window_of(connection)?;
+    connection.extensions(|extensions| {
+        extensions.get::<MerchantSession>()
+            .filter(|session| session.window_id == window.id)
+            .and_then(|session| session.selected)
+    })
+}
+
+#[packet_processor(PacketType::SelectTrade)]
+pub(crate) fn select_trade_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    let slot = decode_from_slice::<VarInt>(&packet.payload)?.0;
+    let Some(window) = open_window_of(&context.connection) else { bail!("Trade selected without an open window") };
+
+    let selected = context.connection.extensions(|extensions| {
+        let session = extensions.get_mut::<MerchantSession>().filter(|session| session.window_id == window.id)?;
+        let index = usize::try_from(slot).ok().filter(|index| *index < session.menu.offers.len())?;
+        session.selected = Some(index);
+        Some((index, session.menu.offers[index].clone(), session.menu.on_select.clone()))
+    });
+    let Some((index, offer, callback)) = selected else { bail!("Invalid trade {} selected in window {}", slot, window.id) };
+
+    // Run outside of the extensions lock, the callback may well open another menu.
+    if let Some(callback) = callback {
+        callback(&context.connection, index, &offer);
+    }
+    Ok(())
+}
@@ -0,0 +1,252 @@
+use anyhow::bail;
+use log::warn;
+use dolls_core::datatype::{decode_from_slice, BlockPos, Decode, Encode, VarInt};
+use dolls_entities::prelude::{entities, Vec3};
+use dolls_macros::packet_processor;
+use dolls_world::prelude::ChunkPos;
+use crate::prelude::{exhaust_move, record_move, world_border, ClientboundPacket, ClientboundPacketType, ConnectionHandle, PacketContext, PacketType, RawPacket};
+
+/// Horizontal coordinates beyond this are clamped, as in vanilla.
+const MAX_HORIZONTAL_COORDINATE: f64 = 3.0e7;
+const MAX_VERTICAL_COORDINATE: f64 = 2.0e7;
+/// Squared distance a player may cover in one move before it is put back, as vanilla's "moved too quickly".
+const MAX_MOVE_DISTANCE_SQUARED: f64 = 100.0;
+
+/// Where a player is and looks, as last reported by its client.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct PlayerPosition {
+    pub x: f64,
+    /// Height of the player's feet.
+    pub y: f64,
+    pub z: f64,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub on_ground: bool,
+}
+
+impl PlayerPosition {
+    /// Standing on top of the block at `position`, centered on it.
+    pub fn on_block(position: BlockPos) -> Self {
+        Self {
+            x: position.x as f64 + 0.5,
+            y: position.y as f64,
+            z: position.z as f64 + 0.5,
+            ..Self::default()
+        }
+    }
+
+    pub fn block_pos(&self) -> BlockPos {
+        BlockPos::new(self.x.floor() as i32, self.y.floor() as i32, self.z.floor() as i32)
+    }
+
+    pub fn chunk_pos(&self) -> ChunkPos {
+        ChunkPos::of(self.block_pos())
+    }
+
+    pub fn vec3(&self) -> Vec3 {
+        Vec3::new(self.x, self.y, self.z)
+    }
+
+    /// Whether every coordinate and angle is a finite number.
+    fn is_finite(&self) -> bool {
+        [self.x, self.y, self.z, self.yaw as f64, self.pitch as f64].iter().all(|value| value.is_finite())
+    }
+
+    /// Clamps coordinates to the world, wraps the yaw to ±180° and clamps the pitch to ±90°.
+    fn normalized(self) -> Self {
+        Self {
+            x: self.x.clamp(-MAX_HORIZONTAL_COORDINATE, MAX_HORIZONTAL_COORDINATE),
+            y: self.y.clamp(-MAX_VERTICAL_COORDINATE, MAX_VERTICAL_COORDINATE),
+            z: self.z.clamp(-MAX_HORIZONTAL_COORDINATE, MAX_HORIZONTAL_COORDINATE),
+            yaw: (self.yaw + 180.0).rem_euclid(360.0) - 180.0,
+            pitch: self.pitch.clamp(-90.0, 90.0),
+            on_ground: self.on_ground,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Encode)]
+pub struct SynchronizePlayerPosition {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub yaw: f32,
+    pub pitch: f32,
+    /// Bits of the fields which are relative to the current position, none here.
+    pub flags: u8,
+    pub teleport_id: VarInt,
+}
+
+impl ClientboundPacket for SynchronizePlayerPosition {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::SynchronizePlayerPosition;
+}
+
+#[derive(Debug, Clone, Decode)]
+struct SetPlayerPosition {
+    x: f64,
+    y: f64,
+    z: f64,
+    on_ground: bool,
+}
+
+#[derive(Debug, Clone, Decode)]
+struct SetPlayerPositionAndRotation {
+    x: f64,
+    y: f64,
+    z: f64,
+    yaw: f32,
+    pitch: f32,
+    on_ground: bool,
+}
+
+#[derive(Debug, Clone, Decode)]
+struct SetPlayerRotation {
+    yaw: f32,
+    pitch: f32,
+    on_ground: bool,
+}
+
+/// Movement of a player, kept in its connection's extensions.
+#[derive(Debug, Default)]
+struct Movement {
+    position: PlayerPosition,
+    /// Moves are ignored until the client confirmed the last teleport.
+    pending_teleport: Option<i32>,
+    next_teleport_id: i32,
+}
+
+/// Last known position of a player in Play.
+pub fn player_position(connection: &ConnectionHandle) -> Option<PlayerPosition> {
+    connection.extensions(|extensions| extensions.get::<Movement>().map(|movement| movement.position))
+}
+
+/// Moves the player, its client does not move it until it confirmed the teleport.
+pub fn teleport(context: &mut PacketContext, position: PlayerPosition) -> anyhow::Result<()> {
+    let teleport_id = context.connection.extensions(|extensions| {
+        let movement = extensions.get_or_default::<Movement>();
+        let teleport_id = movement.next_teleport_id;
+        movement.next_teleport_id = movement.next_teleport_id.wrapping_add(1);
+        movement.pending_teleport = Some(teleport_id);
+        movement.position = position;
+        teleport_id
+    });
+    sync_entity(&context.connection, &position);
+    context.send(&SynchronizePlayerPosition {
+        x: position.x,
+        y: position.y,
+        z: position.z,
+        yaw: position.yaw,
+        pitch: position.pitch,
+        flags: 0,
+        teleport_id: VarInt(teleport_id),
+    })
+}
+
+/// Whether the client has yet to confirm the last teleport, its moves are ignored until then.
+pub fn awaiting_teleport(connection: &ConnectionHandle) -> bool {
+    connection.extensions(|extensions| extensions.get::<Movement>().is_some_and(|movement| movement.pending_teleport.is_some()))
+}
+
+/// Copies a player's position into its entity, which the entity tracker follows.
+fn sync_entity(connection: &ConnectionHandle, position: &PlayerPosition) {
+    let Some(entity_id) = connection.entity_id() else { return };
+    let mut entities = entities().write().unwrap();
+    entities.move_entity(entity_id, position.vec3());
+    if let Some(entity) = entities.get_mut(entity_id) {
+        entity.rotation.yaw = position.yaw;
+        entity.rotation.head_yaw = position.yaw;
+        entity.rotation.pitch = position.pitch;
+        entity.on_ground = position.on_ground;
+    }
+}
+
+/// What became of a move reported by the client.
+enum MoveOutcome {
+    /// Sent before the client confirmed the last teleport.
+    Ignored,
+    /// Accepted, from where the player was.
+    Accepted(PlayerPosition, PlayerPosition),
+    /// Rejected, the player is put back where it was.
+    TooFast(PlayerPosition),
+    /// Beyond the world border, the player is put back inside it.
+    OutsideBorder(PlayerPosition),
+    Invalid(PlayerPosition),
+}
+
+/// Applies a move reported by the client, chunks follow on the next tick. Moves which are too far
+/// or beyond the world border put the player back.
+fn move_player(context: &mut PacketContext, update: impl FnOnce(&mut PlayerPosition)) -> anyhow::Result<()> {
+    let border = world_border();
+    let outcome = context.connection.extensions(|extensions| {
+        let movement = extensions.get_or_default::<Movement>();
+        if movement.pending_teleport.is_some() {
+            return MoveOutcome::Ignored;
+        }
+        let mut next = movement.position;
+        update(&mut next);
+        if !next.is_finite() {
+            return MoveOutcome::Invalid(next);
+        }
+        let next = next.normalized();
+        if next.vec3().distance_squared(movement.position.vec3()) > MAX_MOVE_DISTANCE_SQUARED {
+            return MoveOutcome::TooFast(movement.position);
+        }
+        if !border.contains(next.x, next.z) {
+            let (x, z) = border.clamp(movement.position.x, movement.position.z);
+            return MoveOutcome::OutsideBorder(PlayerPosition { x, z, ..movement.position });
+        }
+        let previous = std::mem::replace(&mut movement.position, next);
+        MoveOutcome::Accepted(previous, next)
+    });
+    match outcome {
+        MoveOutcome::Ignored => Ok(()),
+        MoveOutcome::Accepted(previous, position) => {
+            sync_entity(&context.connection, &position);
+            record_move(&context.connection, &previous, &position);
+            exhaust_move(&context.connection, &previous, &position);
+            Ok(())
+        }
+        MoveOutcome::TooFast(position) => {
+            warn!("{} moved too quickly!", context.username.as_deref().unwrap_or("?"));
+            teleport(context, position)
+        }
+        MoveOutcome::OutsideBorder(position) => teleport(context, position),
+        MoveOutcome::Invalid(position) => bail!("Invalid move to {:?}", position),
+    }
+}
+
+#[packet_processor(PacketType::ConfirmTeleportation)]
+pub(crate) fn confirm_teleportation_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    let VarInt(teleport_id) = decode_from_slice(&packet.payload)?;
+    context.connection.extensions(|extensions| {
+        let movement = extensions.get_or_default::<Movement>();
+        if movement.pending_teleport == Some(teleport_id) {
+            movement.pending_teleport = None;
+        }
+    });
+    Ok(())
+}
+
+#[packet_processor(PacketType::SetPlayerPosition)]
+pub(crate) fn set_player_position_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    let SetPlayerPosition { x, y, z, on_ground } = decode_from_slice(&packet.payload)?;
+    move_player(context, |position| *position = PlayerPosition { x, y, z, on_ground, ..*position })
+}
+
+#[packet_processor(PacketType::SetPlayerPositionAndRotation)]
+pub(crate) fn set_player_position_and_rotation_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    let SetPlayerPositionAndRotation { x, y, z, yaw, pitch, on_ground } = decode_from_slice(&packet.payload)?;
+    move_player(context, |position| *position = PlayerPosition { x, y, z, yaw, pitch, on_ground })
+}
+
+#[packet_processor(PacketType::SetPlayerRotation)]
+pub(crate) fn set_player_rotation_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    let SetPlayerRotation { yaw, pitch, on_ground } = decode_from_slice(&packet.payload)?;
+    move_player(context, |position| *position = PlayerPosition { yaw, pitch, on_ground, ..*position })
+}
+
+#[packet_processor(PacketType::SetPlayerOnGround)]
+pub(crate) fn set_player_on_ground_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    let on_ground: bool = decode_from_slice(&packet.payload)?;
+    move_player(context, |position| position.on_ground = on_ground)
+}
@@ -0,0 +1,110 @@
+use dolls_core::datatype::Encode;
+use dolls_core::particle::Particle;
+use dolls_entities::prelude::Vec3;
+use crate::prelude::{player_position, ClientboundPacket, ClientboundPacketType, ConnectionHandle, ConnectionRegistry};
+
+/// How far away players see particles, as in vanilla.
+pub const PARTICLE_RANGE: f64 = 32.0;
+/// Range of long distance particles, which also ignore the client's particle setting.
+pub const LONG_DISTANCE_PARTICLE_RANGE: f64 = 512.0;
+
+/// Spawns `count` particles around a position.
+#[derive(Debug, Clone, Encode)]
+pub struct ParticlePacket {
+    pub long_distance: bool,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub offset_x: f32,
+    pub offset_y: f32,
+    pub offset_z: f32,
+    pub max_speed: f32,
+    pub count: i32,
+    pub particle: Particle,
+}
+
+impl ClientboundPacket for ParticlePacket {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::Particle;
+}
+
+/// Particles spread around a position: up to `delta` away on each axis, moving at up to `speed`.
+/// A count of 0 spawns a single particle moving in the direction of `delta` instead.
+#[derive(Debug, Clone)]
+pub struct ParticleEffect {
+    pub particle: Particle,
+    pub delta: [f32; 3],
+    pub speed: f32,
+    pub count: i32,
+    pub long_distance: bool,
+}
+
+impl ParticleEffect {
+    /// One particle without spread or speed.
+    pub fn new(particle: Particle) -> Self {
+        Self { particle, delta: [0.0; 3], speed: 0.0, count: 1, long_distance: false }
+    }
+
+    pub fn delta(mut self, delta: [f32; 3]) -> Self {
+        self.delta = delta;
+        self
+    }
+
+    pub fn speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    pub fn count(mut self, count: i32) -> Self {
+        self.count = count.max(0);
+        self
+    }
+
+    /// Shown from further away and even to players who turned particles down.
+    pub fn long_distance(mut self, long_distance: bool) -> Self {
+        self.long_distance = long_distance;
+        self
+    }
+
+    pub fn range(&self) -> f64 {
+        if self.long_distance { LONG_DISTANCE_PARTICLE_RANGE } else { PARTICLE_RANGE }
+    }
+
+    fn at(&self, position: Vec3) -> ParticlePacket {
+        let [offset_x, offset_y, offset_z] = self.delta;
+        ParticlePacket {
+            long_distance: self.long_distance,
+            x: position.x,
+            y: position.y,
+            z: position.z,
+            offset_x,
+            offset_y,
+            offset_z,
+            max_speed: self.speed,
+            count: self.count,
+            particle: self.particle.clone(),
+        }
+    }
+}
+
+impl ConnectionHandle {
+    /// Shows particles to this player only, wherever they are.
+    pub fn spawn_particles(&self, effect: &ParticleEffect, position: Vec3) -> anyhow::Result<()> {
+        self.send(&effect.at(position))
+    }
+}
+
+/// Players close enough to see an effect at `position`.
+pub fn particle_viewers(connections: &ConnectionRegistry, effect: &ParticleEffect, position: Vec3) -> Vec<ConnectionHandle> {
+    let range = effect.range();
+    connections.players().into_iter()
+        .filter(|player| player_position(player).is_some_and(|player| player.vec3().distance_squared(position) <= range * range))
+        .collect()
+}
+
+/// Shows particles at a position to every player in range, returning how many see them.
+pub fn spawn_particles(connections: &ConnectionRegistry, effect: &ParticleEffect, position: Vec3) -> usize {
+    let packet = effect.at(position);
+    particle_viewers(connections, effect, position).iter()
+        .filter(|player| player.send(&packet).is_ok())
+        .count()
+}
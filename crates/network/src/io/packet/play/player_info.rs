@@ -0,0 +1,197 @@
+use std::io::{self, Read, Write};
+use std::time::Duration;
+use dolls_core::datatype::{read_bounded_bytes, Decode, Encode, Uuid, VarInt};
+use dolls_core::text::TextComponent;
+use crate::prelude::{chat_session, game_mode, ClientboundPacket, ClientboundPacketType, ConnectionHandle, ConnectionRegistry, PacketContext, ProfileProperty};
+
+/// Maximum size of a DER encoded profile public key.
+pub const MAX_PUBLIC_KEY_LENGTH: usize = 512;
+/// Maximum size of the signature Mojang put on a profile public key.
+pub const MAX_KEY_SIGNATURE_LENGTH: usize = 4096;
+
+/// Chat session a client announces with Player Session, shared with other players to verify its messages.
+#[derive(Debug, Clone, Eq, PartialEq, Encode)]
+pub struct ChatSessionData {
+    pub session_id: Uuid,
+    /// Expiry of the public key, in milliseconds since the epoch.
+    pub expires_at: i64,
+    /// X.509 `SubjectPublicKeyInfo`, DER encoded.
+    pub public_key: Vec<u8>,
+    pub key_signature: Vec<u8>,
+}
+
+impl Decode for ChatSessionData {
+    fn decode(reader: &mut impl Read) -> io::Result<Self> {
+        Ok(Self {
+            session_id: Uuid::decode(reader)?,
+            expires_at: i64::decode(reader)?,
+            public_key: read_bounded_bytes(reader, MAX_PUBLIC_KEY_LENGTH)?,
+            key_signature: read_bounded_bytes(reader, MAX_KEY_SIGNATURE_LENGTH)?,
+        })
+    }
+}
+
+/// A player as shown in the tab list, only the fields selected by the update's actions are sent.
+#[derive(Debug, Clone, Default)]
+pub struct PlayerInfoEntry {
+    pub uuid: Uuid,
+    pub name: String,
+    pub properties: Vec<ProfileProperty>,
+    pub chat_session: Option<ChatSessionData>,
+    pub game_mode: i32,
+    pub listed: bool,
+    pub latency: i32,
+    pub display_name: Option<TextComponent>,
+}
+
+/// Adds players to the client's player list or changes some of their fields.
+#[derive(Debug, Clone)]
+pub struct PlayerInfoUpdate {
+    pub actions: u8,
+    pub entries: Vec<PlayerInfoEntry>,
+}
+
+impl PlayerInfoUpdate {
+    pub const ADD_PLAYER: u8 = 0x01;
+    pub const INITIALIZE_CHAT: u8 = 0x02;
+    pub const UPDATE_GAME_MODE: u8 = 0x04;
+    pub const UPDATE_LISTED: u8 = 0x08;
+    pub const UPDATE_LATENCY: u8 = 0x10;
+    pub const UPDATE_DISPLAY_NAME: u8 = 0x20;
+
+    pub fn new(actions: u8, entries: Vec<PlayerInfoEntry>) -> Self {
+        Self { actions, entries }
+    }
+}
+
+impl Encode for PlayerInfoUpdate {
+    fn encode(&self, writer: &mut impl Write) -> io::Result<()> {
+        self.actions.encode(writer)?;
+        VarInt(self.entries.len() as i32).encode(writer)?;
+        for entry in &self.entries {
+            entry.uuid.encode(writer)?;
+            if self.actions & Self::ADD_PLAYER != 0 {
+                entry.name.encode(writer)?;
+                entry.properties.encode(writer)?;
+            }
+            if self.actions & Self::INITIALIZE_CHAT != 0 {
+                entry.chat_session.encode(writer)?;
+            }
+            if self.actions & Self::UPDATE_GAME_MODE != 0 {
+                VarInt(entry.game_mode).encode(writer)?;
+            }
+            if self.actions & Self::UPDATE_LISTED != 0 {
+                entry.listed.encode(writer)?;
+            }
+            if self.actions & Self::UPDATE_LATENCY != 0 {
+                VarInt(entry.latency).encode(writer)?;
+            }
+            if self.actions & Self::UPDATE_DISPLAY_NAME != 0 {
+                entry.display_name.encode(writer)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ClientboundPacket for PlayerInfoUpdate {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::PlayerInfoUpdate;
+}
+
+#[derive(Debug, Clone, Encode)]
+pub struct PlayerInfoRemove {
+    pub uuids: Vec<Uuid>,
+}
+
+impl ClientboundPacket for PlayerInfoRemove {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::PlayerInfoRemove;
+}
+
+/// How a player appears in the tab list, apart from the name.
+#[derive(Debug, Clone, Default)]
+struct PlayerListState {
+    game_mode: i32,
+    latency: i32,
+    display_name: Option<TextComponent>,
+}
+
+/// Tab list entry of a player in Play.
+pub fn player_info_entry(connection: &ConnectionHandle) -> Option<PlayerInfoEntry> {
+    let info = connection.info();
+    let state = connection.extensions(|extensions| extensions.get::<PlayerListState>().cloned()).unwrap_or_default();
+    Some(PlayerInfoEntry {
+        uuid: info.uuid?,
+        name: info.username?,
+        chat_session: chat_session(connection),
+        game_mode: state.game_mode,
+        listed: true,
+        latency: state.latency,
+        display_name: state.display_name,
+        ..Default::default()
+    })
+}
+
+/// Changes a player's tab list state and sends the fields selected by `actions` to everyone.
+fn update_entry(connections: &ConnectionRegistry, player: &ConnectionHandle, actions: u8, update: impl FnOnce(&mut PlayerListState)) -> anyhow::Result<()> {
+    player.extensions(|extensions| update(extensions.get_or_default::<PlayerListState>()));
+    match player_info_entry(player) {
+        Some(entry) => connections.broadcast(&PlayerInfoUpdate::new(actions, vec![entry])),
+        None => Ok(()),
+    }
+}
+
+/// Shows `display_name` instead of the player's name in the tab list, `None` restores the name.
+pub fn set_display_name(connections: &ConnectionRegistry, player: &ConnectionHandle, display_name: Option<TextComponent>) -> anyhow::Result<()> {
+    update_entry(connections, player, PlayerInfoUpdate::UPDATE_DISPLAY_NAME, |state| state.display_name = display_name)
+}
+
+/// Game mode shown in the tab list, e.g. spectators are listed last and greyed out.
+pub fn set_listed_game_mode(connections: &ConnectionRegistry, player: &ConnectionHandle, game_mode: i32) -> anyhow::Result<()> {
+    update_entry(connections, player, PlayerInfoUpdate::UPDATE_GAME_MODE, |state| state.game_mode = game_mode)
+}
+
+/// Smoothed round trip time shown as the connection bars, in milliseconds.
+pub fn latency(player: &ConnectionHandle) -> i32 {
+    player.extensions(|extensions| extensions.get::<PlayerListState>().map(|state| state.latency)).unwrap_or_default()
+}
+
+/// Folds a keep-alive round trip into the player's latency, weighted like vanilla.
+pub(crate) fn update_latency(connections: &ConnectionRegistry, player: &ConnectionHandle, round_trip: Duration) -> anyhow::Result<()> {
+    let round_trip = round_trip.as_millis().min(i32::MAX as u128) as i32;
+    update_entry(connections, player, PlayerInfoUpdate::UPDATE_LATENCY, |state| {
+        state.latency = (state.latency * 3 + round_trip) / 4;
+    })
+}
+
+/// Every field of an entry except the chat session.
+const LIST_ACTIONS: u8 = PlayerInfoUpdate::ADD_PLAYER
+    | PlayerInfoUpdate::UPDATE_GAME_MODE
+    | PlayerInfoUpdate::UPDATE_LISTED
+    | PlayerInfoUpdate::UPDATE_LATENCY
+    | PlayerInfoUpdate::UPDATE_DISPLAY_NAME;
+
+/// Introduces a player entering Play to everyone and everyone to them.
+pub(crate) fn announce_player(context: &mut PacketContext) -> anyhow::Result<()> {
+    let (Some(uuid), Some(name)) = (context.uuid, context.username.clone()) else { return Ok(()) };
+    let game_mode = game_mode(&context.connection).id() as i32;
+    context.connection.extensions(|extensions| extensions.get_or_default::<PlayerListState>().game_mode = game_mode);
+    let entry = PlayerInfoEntry { uuid, name, game_mode, listed: true, ..Default::default() };
+    let others = context.connections.players().into_iter()
+        .filter(|player| player.id() != context.connection.id())
+        .collect::<Vec<_>>();
+
+    let joined = PlayerInfoUpdate::new(LIST_ACTIONS, vec![entry.clone()]);
+    for player in &others {
+        let _ = player.send(&joined);
+    }
+
+    let mut entries = others.iter().filter_map(player_info_entry).collect::<Vec<_>>();
+    entries.push(entry);
+    context.send(&PlayerInfoUpdate::new(LIST_ACTIONS | PlayerInfoUpdate::INITIALIZE_CHAT, entries))
+}
+
+/// Removes a player that left from everyone's player list.
+pub(crate) fn remove_player(left: &ConnectionHandle, connections: &ConnectionRegistry) {
+    let Some(uuid) = left.uuid() else { return };
+    let _ = connections.broadcast(&PlayerInfoRemove { uuids: vec![uuid] });
+}
@@ -0,0 +1,340 @@
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+use dolls_core::datatype::{decode_from_slice, Decode, Encode, Identifier, Uuid, VarInt};
+use dolls_core::item::ItemStack;
+use dolls_core::nbt::{NbtCompound, NbtTag};
+use dolls_core::recipe::{recipes, CookingKind, Ingredient, Recipe};
+use dolls_macros::packet_processor;
+use dolls_world::prelude::player_data;
+use log::warn;
+use crate::prelude::{place_recipe, trigger_recipes_unlocked, ClientboundPacket, ClientboundPacketType, ConnectionHandle, PacketContext, PacketType, RawPacket};
+
+/// Recipe books of the client, in the order their settings are sent.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum RecipeBookType {
+    Crafting,
+    Furnace,
+    BlastFurnace,
+    Smoker,
+}
+
+impl RecipeBookType {
+    pub const ALL: [RecipeBookType; 4] = [RecipeBookType::Crafting, RecipeBookType::Furnace, RecipeBookType::BlastFurnace, RecipeBookType::Smoker];
+
+    pub fn from_id(id: i32) -> Option<Self> {
+        Self::ALL.get(usize::try_from(id).ok()?).copied()
+    }
+
+    /// NBT keys of the book's open and filtering flags in vanilla player data.
+    fn nbt_keys(self) -> (&'static str, &'static str) {
+        match self {
+            RecipeBookType::Crafting => ("isGuiOpen", "isFilteringCraftable"),
+            RecipeBookType::Furnace => ("isFurnaceGuiOpen", "isFurnaceFilteringCraftable"),
+            RecipeBookType::BlastFurnace => ("isBlastingFurnaceGuiOpen", "isBlastingFurnaceFilteringCraftable"),
+            RecipeBookType::Smoker => ("isSmokerGuiOpen", "isSmokerFilteringCraftable"),
+        }
+    }
+}
+
+/// Whether a recipe book is open and only shows what can be made.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct RecipeBookSettings {
+    pub open: bool,
+    pub filtering: bool,
+}
+
+/// Declares the server's recipes, the client only shows recipes it was told about.
+#[derive(Debug, Clone)]
+pub struct UpdateRecipes {
+    pub recipes: Vec<(Identifier, Recipe)>,
+}
+
+impl ClientboundPacket for UpdateRecipes {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::UpdateRecipes;
+}
+
+impl Encode for UpdateRecipes {
+    fn encode(&self, writer: &mut impl Write) -> io::Result<()> {
+        VarInt(self.recipes.len() as i32).encode(writer)?;
+        for (id, recipe) in &self.recipes {
+            id.encode(writer)?;
+            encode_recipe(recipe, writer)?;
+        }
+        Ok(())
+    }
+}
+
+/// A recipe as its serializer id followed by the serializer's fields.
+fn encode_recipe(recipe: &Recipe, writer: &mut impl Write) -> io::Result<()> {
+    match recipe {
+        Recipe::Shaped(recipe) => {
+            VarInt(0).encode(writer)?;
+            recipe.group.encode(writer)?;
+            VarInt(recipe.category as i32).encode(writer)?;
+            VarInt(recipe.width as i32).encode(writer)?;
+            VarInt(recipe.height as i32).encode(writer)?;
+            for ingredient in &recipe.pattern {
+                encode_ingredient(ingredient.as_ref(), writer)?;
+            }
+            recipe.result.encode(writer)?;
+            recipe.show_notification.encode(writer)
+        }
+        Recipe::Shapeless(recipe) => {
+            VarInt(1).encode(writer)?;
+            recipe.group.encode(writer)?;
+            VarInt(recipe.category as i32).encode(writer)?;
+            VarInt(recipe.ingredients.len() as i32).encode(writer)?;
+            for ingredient in &recipe.ingredients {
+                encode_ingredient(Some(ingredient), writer)?;
+            }
+            recipe.result.encode(writer)
+        }
+        Recipe::Cooking(recipe) => {
+            let serializer = match recipe.kind {
+                CookingKind::Smelting => 15,
+                CookingKind::Blasting => 16,
+                CookingKind::Smoking => 17,
+                CookingKind::CampfireCooking => 18,
+            };
+            VarInt(serializer).encode(writer)?;
+            recipe.group.encode(writer)?;
+            VarInt(recipe.category as i32).encode(writer)?;
+            encode_ingredient(Some(&recipe.ingredient), writer)?;
+            recipe.result.encode(writer)?;
+            recipe.experience.encode(writer)?;
+            VarInt(recipe.cooking_time as i32).encode(writer)
+        }
+    }
+}
+
+/// An ingredient as the item stacks it accepts, none for places which stay empty.
+fn encode_ingredient(ingredient: Option<&Ingredient>, writer: &mut impl Write) -> io::Result<()> {
+    let items = ingredient.map_or(&[][..], |ingredient| &ingredient.0);
+    VarInt(items.len() as i32).encode(writer)?;
+    for item in items {
+        ItemStack::new(*item, 1).encode(writer)?;
+    }
+    Ok(())
+}
+
+/// Unlocks or locks recipes in the client's recipe book, `Init` also carries the book settings
+/// and the recipes to highlight.
+#[derive(Debug, Clone)]
+pub struct UpdateRecipeBook {
+    pub action: RecipeBookAction,
+    pub settings: [RecipeBookSettings; 4],
+    pub recipes: Vec<Identifier>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum RecipeBookAction {
+    Init { highlighted: Vec<Identifier> },
+    Add,
+    Remove,
+}
+
+impl ClientboundPacket for UpdateRecipeBook {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::UpdateRecipeBook;
+}
+
+impl Encode for UpdateRecipeBook {
+    fn encode(&self, writer: &mut impl Write) -> io::Result<()> {
+        let action = match self.action {
+            RecipeBookAction::Init { .. } => 0,
+            RecipeBookAction::Add => 1,
+            RecipeBookAction::Remove => 2,
+        };
+        VarInt(action).encode(writer)?;
+        for settings in &self.settings {
+            settings.open.encode(writer)?;
+            settings.filtering.encode(writer)?;
+        }
+        self.recipes.encode(writer)?;
+        if let RecipeBookAction::Init { highlighted } = &self.action {
+            highlighted.encode(writer)?;
+        }
+        Ok(())
+    }
+}
+
+/// Shows the ingredients of a recipe the player could not place as ghost items in the grid.
+#[derive(Debug, Clone, Encode)]
+pub struct PlaceGhostRecipe {
+    pub window_id: i8,
+    pub recipe: Identifier,
+}
+
+impl ClientboundPacket for PlaceGhostRecipe {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::PlaceGhostRecipe;
+}
+
+#[derive(Debug, Clone, Decode)]
+struct ChangeRecipeBookSettings {
+    book: VarInt,
+    open: bool,
+    filtering: bool,
+}
+
+#[derive(Debug, Clone, Decode)]
+struct PlaceRecipe {
+    window_id: i8,
+    recipe: Identifier,
+    make_all: bool,
+}
+
+/// Recipes a player unlocked, kept in its connection's extensions and its player data.
+#[derive(Debug, Clone, Default)]
+struct RecipeBook {
+    unlocked: BTreeSet<Identifier>,
+    /// Unlocked recipes the player has not looked at yet.
+    to_be_displayed: BTreeSet<Identifier>,
+    settings: [RecipeBookSettings; 4],
+}
+
+impl RecipeBook {
+    const NBT_KEY: &'static str = "recipeBook";
+
+    fn from_nbt(nbt: &NbtCompound) -> Self {
+        let ids = |key: &str| nbt.get_list(key).unwrap_or_default().iter()
+            .filter_map(NbtTag::as_str)
+            .filter_map(|id| id.parse().ok())
+            .collect::<BTreeSet<Identifier>>();
+        let settings = RecipeBookType::ALL.map(|book| {
+            let (open, filtering) = book.nbt_keys();
+            RecipeBookSettings { open: nbt.get_bool(open).unwrap_or(false), filtering: nbt.get_bool(filtering).unwrap_or(false) }
+        });
+        Self { unlocked: ids("recipes"), to_be_displayed: ids("toBeDisplayed"), settings }
+    }
+
+    fn to_nbt(&self) -> NbtCompound {
+        let ids = |ids: &BTreeSet<Identifier>| ids.iter().map(|id| NbtTag::String(id.to_string())).collect::<Vec<_>>();
+        let mut nbt = NbtCompound::new();
+        nbt.insert("recipes", ids(&self.unlocked));
+        nbt.insert("toBeDisplayed", ids(&self.to_be_displayed));
+        for (book, settings) in RecipeBookType::ALL.into_iter().zip(self.settings) {
+            let (open, filtering) = book.nbt_keys();
+            nbt.insert(open, settings.open);
+            nbt.insert(filtering, settings.filtering);
+        }
+        nbt
+    }
+}
+
+fn recipe_book(connection: &ConnectionHandle) -> RecipeBook {
+    connection.extensions(|extensions| extensions.get::<RecipeBook>().cloned()).unwrap_or_default()
+}
+
+/// Writes the recipe book into the player's data, failures are logged since the book stays usable.
+fn save_recipe_book(uuid: Option<Uuid>, book: &RecipeBook) {
+    let Some(uuid) = uuid else { return };
+    let nbt = book.to_nbt();
+    if let Err(err) = player_data().read().unwrap().update(uuid, |data| { data.insert(RecipeBook::NBT_KEY, nbt); }) {
+        warn!("Failed to save the recipe book of {}: {}", uuid.hyphenated(), err);
+    }
+}
+
+/// Recipes the player has unlocked.
+pub fn unlocked_recipes(connection: &ConnectionHandle) -> BTreeSet<Identifier> {
+    recipe_book(connection).unlocked
+}
+
+pub fn has_recipe(connection: &ConnectionHandle, recipe: &Identifier) -> bool {
+    connection.extensions(|extensions| extensions.get::<RecipeBook>().is_some_and(|book| book.unlocked.contains(recipe)))
+}
+
+/// Adds recipes to the player's recipe book, highlighting them until looked at. Returns how many
+/// were not unlocked before.
+pub fn unlock_recipes(connection: &ConnectionHandle, recipes: &[Identifier]) -> anyhow::Result<usize> {
+    let (added, book) = connection.extensions(|extensions| {
+        let book = extensions.get_or_default::<RecipeBook>();
+        let added = recipes.iter().filter(|recipe| book.unlocked.insert((*recipe).clone())).cloned().collect::<Vec<_>>();
+        book.to_be_displayed.extend(added.iter().cloned());
+        (added, book.clone())
+    });
+    if added.is_empty() {
+        return Ok(0);
+    }
+    save_recipe_book(connection.uuid(), &book);
+    connection.send(&UpdateRecipeBook { action: RecipeBookAction::Add, settings: book.settings, recipes: added.clone() })?;
+    trigger_recipes_unlocked(connection, &added)?;
+    Ok(added.len())
+}
+
+/// Removes recipes from the player's recipe book, returning how many were unlocked.
+pub fn lock_recipes(connection: &ConnectionHandle, recipes: &[Identifier]) -> anyhow::Result<usize> {
+    let (removed, book) = connection.extensions(|extensions| {
+        let book = extensions.get_or_default::<RecipeBook>();
+        let removed = recipes.iter().filter(|recipe| book.unlocked.remove(*recipe)).cloned().collect::<Vec<_>>();
+        for recipe in &removed {
+            book.to_be_displayed.remove(recipe);
+        }
+        (removed, book.clone())
+    });
+    if removed.is_empty() {
+        return Ok(0);
+    }
+    save_recipe_book(connection.uuid(), &book);
+    connection.send(&UpdateRecipeBook { action: RecipeBookAction::Remove, settings: book.settings, recipes: removed.clone() })?;
+    Ok(removed.len())
+}
+
+/// Declares the recipes and sends the recipe book saved for the joining player. Recipes which no
+/// longer exist are dropped from the book.
+pub(crate) fn send_recipe_book(context: &mut PacketContext) -> anyhow::Result<()> {
+    let registry = recipes().read().unwrap().clone();
+    let data = context.uuid.and_then(|uuid| player_data().read().unwrap().load(uuid).unwrap_or_else(|err| {
+        warn!("Failed to load the player data of {}: {}", uuid.hyphenated(), err);
+        None
+    }));
+    let mut book = data.and_then(|data| data.get_compound(RecipeBook::NBT_KEY).map(RecipeBook::from_nbt)).unwrap_or_default();
+    book.unlocked.retain(|recipe| registry.get(recipe).is_some());
+    book.to_be_displayed.retain(|recipe| book.unlocked.contains(recipe));
+
+    context.send(&UpdateRecipes { recipes: registry.iter().map(|(id, recipe)| (id.clone(), recipe.clone())).collect() })?;
+    context.send(&UpdateRecipeBook {
+        action: RecipeBookAction::Init { highlighted: book.to_be_displayed.iter().cloned().collect() },
+        settings: book.settings,
+        recipes: book.unlocked.iter().cloned().collect(),
+    })?;
+    context.connection.extensions(|extensions| extensions.insert(book));
+    Ok(())
+}
+
+#[packet_processor(PacketType::ChangeRecipeBookSettings)]
+pub(crate) fn change_recipe_book_settings_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    let packet = decode_from_slice::<ChangeRecipeBookSettings>(&packet.payload)?;
+    let Some(book) = RecipeBookType::from_id(packet.book.0) else { anyhow::bail!("Unknown recipe book {}", packet.book.0) };
+    let book = context.connection.extensions(|extensions| {
+        let recipe_book = extensions.get_or_default::<RecipeBook>();
+        recipe_book.settings[book as usize] = RecipeBookSettings { open: packet.open, filtering: packet.filtering };
+        recipe_book.clone()
+    });
+    save_recipe_book(context.connection.uuid(), &book);
+    Ok(())
+}
+
+#[packet_processor(PacketType::SetSeenRecipe)]
+pub(crate) fn set_seen_recipe_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    let recipe = decode_from_slice::<Identifier>(&packet.payload)?;
+    let book = context.connection.extensions(|extensions| {
+        let book = extensions.get_or_default::<RecipeBook>();
+        book.to_be_displayed.remove(&recipe).then(|| book.clone())
+    });
+    if let Some(book) = book {
+        save_recipe_book(context.connection.uuid(), &book);
+    }
+    Ok(())
+}
+
+#[packet_processor(PacketType::PlaceRecipe)]
+pub(crate) fn place_recipe_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    let packet = decode_from_slice::<PlaceRecipe>(&packet.payload)?;
+    if !has_recipe(&context.connection, &packet.recipe) {
+        anyhow::bail!("Recipe {} is not unlocked", packet.recipe);
+    }
+    let Some(recipe) = recipes().read().unwrap().get(&packet.recipe).cloned() else { anyhow::bail!("Unknown recipe {}", packet.recipe) };
+    if !place_recipe(&context.connection, packet.window_id as i32, &recipe, packet.make_all)? {
+        context.connection.send(&PlaceGhostRecipe { window_id: packet.window_id, recipe: packet.recipe })?;
+    }
+    Ok(())
+}
@@ -0,0 +1,286 @@
+use log::{info, warn};
+use dolls_core::datatype::{BlockPos, Encode, GlobalPos, Identifier, VarInt};
+use dolls_core::nbt::{NbtCompound, NbtTag};
+use dolls_core::registry::registries;
+use dolls_core::text::TextComponent;
+use dolls_entities::prelude::entities;
+use dolls_world::player_data::player_data;
+use dolls_world::prelude::{blocks, level, scoreboard, Dimension, KEEP_INVENTORY, SHOW_DEATH_MESSAGES};
+use crate::prelude::{add_score, drop_inventory, enter_dimension, entity_viewers, forget_viewers, increment_custom_stat, player_dimension, player_position, abilities, reset_vitals, send_inventory, send_respawn,
+    send_time_and_weather, send_world_border, send_world_spawn, teleport, world_spawn_position,
+    ClientboundPacket, ClientboundPacketType, ConnectionHandle, ConnectionRegistry, DamageSource, EntityEvent, GameEvent, PacketContext, PlayerPosition, SystemChatMessage};
+
+/// Entity status playing the death animation.
+const DEATH_ANIMATION: i8 = 3;
+/// Respawn data flags, nothing is kept when dying.
+pub const KEEP_ATTRIBUTES: u8 = 0x01;
+pub const KEEP_METADATA: u8 = 0x02;
+
+/// Shows the death screen with the player's death message.
+#[derive(Debug, Clone, Encode)]
+pub struct CombatDeath {
+    pub player_id: VarInt,
+    pub message: TextComponent,
+}
+
+impl ClientboundPacket for CombatDeath {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::CombatDeath;
+}
+
+/// Recreates the client's player, after dying or when changing dimensions.
+#[derive(Debug, Clone, Encode)]
+pub struct Respawn {
+    /// Network id in the `minecraft:dimension_type` registry.
+    pub dimension_type: VarInt,
+    pub dimension_name: Identifier,
+    pub hashed_seed: i64,
+    pub game_mode: u8,
+    /// -1 when there is none.
+    pub previous_game_mode: i8,
+    pub debug: bool,
+    pub flat: bool,
+    pub death_location: Option<GlobalPos>,
+    pub portal_cooldown: VarInt,
+    /// [`KEEP_ATTRIBUTES`] and [`KEEP_METADATA`].
+    pub data_kept: u8,
+}
+
+impl ClientboundPacket for Respawn {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::Respawn;
+}
+
+/// Where a player respawns instead of the world spawn, set by beds, respawn anchors and `spawnpoint`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpawnPoint {
+    pub dimension: Identifier,
+    pub position: BlockPos,
+    pub angle: f32,
+    /// Used even without a bed or anchor at the position.
+    pub forced: bool,
+}
+
+impl SpawnPoint {
+    /// Player data keys, as vanilla writes them.
+    const X_KEY: &'static str = "SpawnX";
+    const Y_KEY: &'static str = "SpawnY";
+    const Z_KEY: &'static str = "SpawnZ";
+    const ANGLE_KEY: &'static str = "SpawnAngle";
+    const DIMENSION_KEY: &'static str = "SpawnDimension";
+    const FORCED_KEY: &'static str = "SpawnForced";
+
+    pub fn new(position: BlockPos) -> Self {
+        Self { dimension: Identifier::minecraft("overworld"), position, angle: 0.0, forced: false }
+    }
+
+    pub fn from_nbt(nbt: &NbtCompound) -> Option<Self> {
+        let coordinate = |key| nbt.get_i64(key).map(|value| value as i32);
+        Some(Self {
+            dimension: nbt.get_str(Self::DIMENSION_KEY).and_then(|name| name.parse().ok()).unwrap_or_else(|| Identifier::minecraft("overworld")),
+            position: BlockPos::new(coordinate(Self::X_KEY)?, coordinate(Self::Y_KEY)?, coordinate(Self::Z_KEY)?),
+            angle: nbt.get_f64(Self::ANGLE_KEY).unwrap_or_default() as f32,
+            forced: nbt.get_bool(Self::FORCED_KEY).unwrap_or_default(),
+        })
+    }
+
+    pub fn write_nbt(&self, nbt: &mut NbtCompound) {
+        nbt.insert(Self::X_KEY, self.position.x);
+        nbt.insert(Self::Y_KEY, self.position.y);
+        nbt.insert(Self::Z_KEY, self.position.z);
+        nbt.insert(Self::ANGLE_KEY, self.angle);
+        nbt.insert(Self::DIMENSION_KEY, self.dimension.to_string());
+        nbt.insert(Self::FORCED_KEY, self.forced);
+    }
+
+    fn remove_nbt(nbt: &mut NbtCompound) {
+        for key in [Self::X_KEY, Self::Y_KEY, Self::Z_KEY, Self::ANGLE_KEY, Self::DIMENSION_KEY, Self::FORCED_KEY] {
+            nbt.remove(key);
+        }
+    }
+
+    /// Where and in which dimension the player appears, `None` if the bed or anchor is gone, the
+    /// anchor has no charges or the dimension does not exist.
+    fn respawn_position(&self) -> Option<(Dimension, PlayerPosition)> {
+        let dimension = Dimension::from_name(&self.dimension)?;
+        let position = PlayerPosition { yaw: self.angle, ..PlayerPosition::on_block(self.position) };
+        let state = dimension.world().read().unwrap().get_block(self.position);
+        let block = state.and_then(|state| blocks().read().unwrap().state(state).map(|info| (info.block.clone(), info.properties.clone())));
+        let position = match block {
+            Some((block, _)) if block.path().ends_with("_bed") => Some(PlayerPosition { y: position.y + 0.5625, ..position }),
+            Some((block, properties)) if block.path() == "respawn_anchor" => {
+                let charged = properties.iter().any(|(name, value)| name == "charges" && value != "0");
+                charged.then_some(PlayerPosition { y: position.y + 1.0, ..position })
+            }
+            _ if self.forced => Some(position),
+            _ => None,
+        };
+        position.map(|position| (dimension, position))
+    }
+}
+
+/// The player's last death, shown by recovery compasses.
+#[derive(Debug, Clone)]
+struct DeathLocation(GlobalPos);
+
+const DEATH_LOCATION_KEY: &str = "LastDeathLocation";
+
+pub fn spawn_point(connection: &ConnectionHandle) -> Option<SpawnPoint> {
+    connection.extensions(|extensions| extensions.get::<SpawnPoint>().cloned())
+}
+
+/// Sets or clears where the player respawns, and saves it.
+pub fn set_spawn_point(connection: &ConnectionHandle, spawn_point: Option<SpawnPoint>) {
+    connection.extensions(|extensions| match &spawn_point {
+        Some(spawn_point) => extensions.insert(spawn_point.clone()),
+        None => extensions.remove::<SpawnPoint>(),
+    });
+    let Some(uuid) = connection.uuid() else { return };
+    if let Err(err) = player_data().read().unwrap().update(uuid, |data| match &spawn_point {
+        Some(spawn_point) => spawn_point.write_nbt(data),
+        None => SpawnPoint::remove_nbt(data),
+    }) {
+        warn!("Failed to save the spawn point of {}: {}", uuid.hyphenated(), err);
+    }
+}
+
+pub fn death_location(connection: &ConnectionHandle) -> Option<GlobalPos> {
+    connection.extensions(|extensions| extensions.get::<DeathLocation>().map(|location| location.0.clone()))
+}
+
+/// Reads the joining player's spawn point and last death location.
+pub(crate) fn load_spawn_point(context: &mut PacketContext) {
+    let data = context.uuid.and_then(|uuid| player_data().read().unwrap().load(uuid).unwrap_or_else(|err| {
+        warn!("Failed to load the player data of {}: {}", uuid.hyphenated(), err);
+        None
+    }));
+    let Some(data) = data else { return };
+    let death = data.get_compound(DEATH_LOCATION_KEY).and_then(|location| {
+        let dimension = location.get_str("dimension")?.parse().ok()?;
+        let Some(NbtTag::IntArray(pos)) = location.get("pos") else { return None };
+        let [x, y, z] = pos.as_slice() else { return None };
+        Some(GlobalPos::new(dimension, BlockPos::new(*x, *y, *z)))
+    });
+    context.connection.extensions(|extensions| {
+        if let Some(spawn_point) = SpawnPoint::from_nbt(&data) {
+            extensions.insert(spawn_point);
+        }
+        if let Some(death) = death {
+            extensions.insert(DeathLocation(death));
+        }
+    });
+}
+
+/// The death message of a player, e.g. "Alice starved to death".
+pub fn death_message(connections: &ConnectionRegistry, player: &ConnectionHandle, source: &DamageSource) -> TextComponent {
+    let victim = TextComponent::text(player.username().unwrap_or_default());
+    if source.is_fall() {
+        return TextComponent::translatable("death.fell.accident.generic", vec![victim]).fallback("%s fell from a high place");
+    }
+    let key = format!("death.attack.{}", source.message_id());
+    let attacker = source.attacker.and_then(|entity_id| {
+        let player = connections.players().into_iter().find(|player| player.entity_id() == Some(entity_id));
+        match player {
+            Some(player) => Some(TextComponent::text(player.username().unwrap_or_default())),
+            None => entities().read().unwrap().get(entity_id)
+                .map(|entity| TextComponent::translatable(format!("entity.minecraft.{}", entity.entity_type().name()), vec![])),
+        }
+    });
+    match attacker {
+        Some(attacker) => TextComponent::translatable(key, vec![victim, attacker]).fallback("%s was killed by %s"),
+        None => TextComponent::translatable(key, vec![victim]).fallback("%s died"),
+    }
+}
+
+/// Kills a player whose health ran out: shows the death screen, announces the death, drops the
+/// inventory unless `keepInventory` is on and remembers where it happened.
+pub fn die(connections: &ConnectionRegistry, player: &ConnectionHandle, source: &DamageSource) -> anyhow::Result<()> {
+    let message = death_message(connections, player, source);
+    info!("{}", message.to_plain_text());
+    let (show_message, keep_inventory) = {
+        let level = level().read().unwrap();
+        (level.game_rules.get_bool(SHOW_DEATH_MESSAGES), level.game_rules.get_bool(KEEP_INVENTORY))
+    };
+    if let Some(entity_id) = player.entity_id() {
+        let content = if show_message { message.clone() } else { TextComponent::text("") };
+        player.send(&CombatDeath { player_id: VarInt(entity_id), message: content })?;
+        for viewer in entity_viewers(connections, entity_id) {
+            let _ = viewer.send(&EntityEvent { entity_id, status: DEATH_ANIMATION });
+        }
+    }
+    if show_message {
+        connections.broadcast(&SystemChatMessage { content: message, overlay: false })?;
+    }
+    if !keep_inventory {
+        drop_inventory(player)?;
+    }
+    if let Some(position) = player_position(player) {
+        let location = GlobalPos::new(player_dimension(player).name(), position.block_pos());
+        save_death_location(player, &location);
+        player.extensions(|extensions| extensions.insert(DeathLocation(location)));
+    }
+    increment_custom_stat(player, "deaths", 1);
+    let username = player.username().unwrap_or_default();
+    let objectives = scoreboard().read().unwrap().objectives()
+        .filter(|objective| objective.criterion == "deathCount")
+        .map(|objective| objective.name.clone())
+        .collect::<Vec<_>>();
+    for objective in objectives {
+        add_score(connections, &username, &objective, 1)?;
+    }
+    Ok(())
+}
+
+fn save_death_location(connection: &ConnectionHandle, location: &GlobalPos) {
+    let Some(uuid) = connection.uuid() else { return };
+    let nbt = NbtCompound::new()
+        .with("dimension", location.dimension.to_string())
+        .with("pos", vec![location.position.x, location.position.y, location.position.z]);
+    if let Err(err) = player_data().read().unwrap().update(uuid, |data| { data.insert(DEATH_LOCATION_KEY, nbt); }) {
+        warn!("Failed to save the death location of {}: {}", uuid.hyphenated(), err);
+    }
+}
+
+/// Network id of a dimension type, as sent in Login (play) and Respawn.
+pub(crate) fn dimension_type_id(dimension: &Identifier) -> i32 {
+    registries().read().unwrap().opaque.iter()
+        .find(|registry| *registry.id() == Identifier::minecraft("dimension_type"))
+        .and_then(|registry| registry.network_id(dimension))
+        .unwrap_or_default()
+}
+
+/// Brings a dead player back at its spawn point, or at the world spawn in the overworld if it has
+/// none or its bed or anchor is gone, in which case the spawn point is cleared.
+pub(crate) fn respawn(context: &mut PacketContext) -> anyhow::Result<()> {
+    let connection = context.connection.clone();
+    let position = match spawn_point(&connection) {
+        Some(spawn_point) => match spawn_point.respawn_position() {
+            Some(position) => Some(position),
+            None => {
+                set_spawn_point(&connection, None);
+                context.send(&GameEvent { event: GameEvent::NO_RESPAWN_BLOCK_AVAILABLE, value: 0.0 })?;
+                None
+            }
+        },
+        None => None,
+    };
+    let (dimension, position) = position.unwrap_or_else(|| (Dimension::Overworld, world_spawn_position()));
+    let changes_dimension = player_dimension(&connection) != dimension;
+    if changes_dimension {
+        enter_dimension(context, dimension);
+    }
+    send_respawn(context, dimension, 0)?;
+    context.send(&abilities(&connection).packet())?;
+    reset_vitals(&connection)?;
+    if changes_dimension {
+        send_world_border(context)?;
+        send_time_and_weather(context)?;
+        send_world_spawn(context)?;
+        context.send(&GameEvent { event: GameEvent::START_WAITING_FOR_CHUNKS, value: 0.0 })?;
+    }
+    teleport(context, position)?;
+    send_inventory(&connection)?;
+    // Viewers removed the dead body, the entity tracker spawns the player for them again.
+    if let Some(entity_id) = connection.entity_id() {
+        forget_viewers(entity_id);
+    }
+    Ok(())
+}
@@ -0,0 +1,167 @@
+use std::io::{self, Write};
+use dolls_core::datatype::{Encode, VarInt};
+use dolls_core::text::TextComponent;
+use dolls_world::prelude::{scoreboard, DisplaySlot, NumberFormat, Objective, Score};
+use crate::prelude::{ClientboundPacket, ClientboundPacketType, ConnectionRegistry, PacketContext};
+
+/// Creates, changes or removes an objective on the client.
+#[derive(Debug, Clone)]
+pub enum UpdateObjectives {
+    Create(Objective),
+    Remove(String),
+    Update(Objective),
+}
+
+impl ClientboundPacket for UpdateObjectives {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::UpdateObjectives;
+}
+
+impl Encode for UpdateObjectives {
+    fn encode(&self, writer: &mut impl Write) -> io::Result<()> {
+        let (name, mode, objective) = match self {
+            UpdateObjectives::Create(objective) => (&objective.name, 0i8, Some(objective)),
+            UpdateObjectives::Remove(name) => (name, 1, None),
+            UpdateObjectives::Update(objective) => (&objective.name, 2, Some(objective)),
+        };
+        name.encode(writer)?;
+        mode.encode(writer)?;
+        if let Some(objective) = objective {
+            objective.display_name.encode(writer)?;
+            VarInt(objective.render_type.id()).encode(writer)?;
+            encode_number_format(objective.number_format.as_ref(), writer)?;
+        }
+        Ok(())
+    }
+}
+
+fn encode_number_format(format: Option<&NumberFormat>, writer: &mut impl Write) -> io::Result<()> {
+    format.is_some().encode(writer)?;
+    let Some(format) = format else { return Ok(()) };
+    VarInt(format.id()).encode(writer)?;
+    match format {
+        NumberFormat::Blank => Ok(()),
+        NumberFormat::Styled(style) => style.to_nbt().encode(writer),
+        NumberFormat::Fixed(value) => value.encode(writer),
+    }
+}
+
+/// Shows an objective in a slot, an empty name clears it.
+#[derive(Debug, Clone, Encode)]
+pub struct DisplayObjective {
+    pub slot: VarInt,
+    pub objective: String,
+}
+
+impl ClientboundPacket for DisplayObjective {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::DisplayObjective;
+}
+
+#[derive(Debug, Clone)]
+pub struct UpdateScore {
+    pub holder: String,
+    pub objective: String,
+    pub score: Score,
+}
+
+impl ClientboundPacket for UpdateScore {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::UpdateScore;
+}
+
+impl Encode for UpdateScore {
+    fn encode(&self, writer: &mut impl Write) -> io::Result<()> {
+        self.holder.encode(writer)?;
+        self.objective.encode(writer)?;
+        VarInt(self.score.value).encode(writer)?;
+        self.score.display_name.encode(writer)?;
+        encode_number_format(self.score.number_format.as_ref(), writer)
+    }
+}
+
+/// Removes a holder's score for one objective, or for all of them without one.
+#[derive(Debug, Clone, Encode)]
+pub struct ResetScore {
+    pub holder: String,
+    pub objective: Option<String>,
+}
+
+impl ClientboundPacket for ResetScore {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::ResetScore;
+}
+
+/// Sends a joining player every objective, score and display slot.
+pub(crate) fn send_scoreboard(context: &mut PacketContext) -> anyhow::Result<()> {
+    let scoreboard = scoreboard().read().unwrap().clone();
+    for objective in scoreboard.objectives() {
+        context.send(&UpdateObjectives::Create(objective.clone()))?;
+    }
+    for holder in scoreboard.holders() {
+        for (objective, score) in scoreboard.scores(holder) {
+            context.send(&UpdateScore { holder: holder.to_string(), objective: objective.to_string(), score: score.clone() })?;
+        }
+    }
+    for (slot, objective) in scoreboard.display_slots() {
+        context.send(&DisplayObjective { slot: VarInt(slot.id()), objective: objective.to_string() })?;
+    }
+    Ok(())
+}
+
+/// Adds an objective to the scoreboard, failing when one by that name exists.
+pub fn add_objective(connections: &ConnectionRegistry, objective: Objective) -> anyhow::Result<()> {
+    scoreboard().write().unwrap().add_objective(objective.clone())?;
+    connections.broadcast(&UpdateObjectives::Create(objective))
+}
+
+/// Changes an objective's display name, render type or number format.
+pub fn modify_objective(connections: &ConnectionRegistry, name: &str, modify: impl FnOnce(&mut Objective)) -> anyhow::Result<()> {
+    let objective = scoreboard().write().unwrap().modify_objective(name, modify)?;
+    connections.broadcast(&UpdateObjectives::Update(objective))
+}
+
+/// Removes an objective with all of its scores, telling whether it existed.
+pub fn remove_objective(connections: &ConnectionRegistry, name: &str) -> anyhow::Result<bool> {
+    if scoreboard().write().unwrap().remove_objective(name).is_none() {
+        return Ok(false);
+    }
+    connections.broadcast(&UpdateObjectives::Remove(name.to_string()))?;
+    Ok(true)
+}
+
+/// Shows an objective in a slot, `None` clears the slot.
+pub fn set_display_slot(connections: &ConnectionRegistry, slot: DisplaySlot, objective: Option<&str>) -> anyhow::Result<()> {
+    scoreboard().write().unwrap().set_display(slot, objective)?;
+    connections.broadcast(&DisplayObjective { slot: VarInt(slot.id()), objective: objective.unwrap_or_default().to_string() })
+}
+
+pub fn score(holder: &str, objective: &str) -> Option<i32> {
+    scoreboard().read().unwrap().score(holder, objective).map(|score| score.value)
+}
+
+/// Changes a holder's score, starting from zero when it has none, and returns it.
+pub fn update_score(connections: &ConnectionRegistry, holder: &str, objective: &str, update: impl FnOnce(&mut Score)) -> anyhow::Result<Score> {
+    let score = scoreboard().write().unwrap().update_score(holder, objective, update)?;
+    connections.broadcast(&UpdateScore { holder: holder.to_string(), objective: objective.to_string(), score: score.clone() })?;
+    Ok(score)
+}
+
+pub fn set_score(connections: &ConnectionRegistry, holder: &str, objective: &str, value: i32) -> anyhow::Result<()> {
+    update_score(connections, holder, objective, |score| score.value = value).map(drop)
+}
+
+/// Adds `amount` to a holder's score, wrapping around like vanilla, and returns the new value.
+pub fn add_score(connections: &ConnectionRegistry, holder: &str, objective: &str, amount: i32) -> anyhow::Result<i32> {
+    update_score(connections, holder, objective, |score| score.value = score.value.wrapping_add(amount)).map(|score| score.value)
+}
+
+/// Shows a name other than the holder's next to its score, `None` shows the holder's name again.
+pub fn set_score_display_name(connections: &ConnectionRegistry, holder: &str, objective: &str, display_name: Option<TextComponent>) -> anyhow::Result<()> {
+    update_score(connections, holder, objective, |score| score.display_name = display_name).map(drop)
+}
+
+/// Removes a holder's score for one objective or all of them, telling whether it had any.
+pub fn reset_score(connections: &ConnectionRegistry, holder: &str, objective: Option<&str>) -> anyhow::Result<bool> {
+    if !scoreboard().write().unwrap().reset_score(holder, objective) {
+        return Ok(false);
+    }
+    connections.broadcast(&ResetScore { holder: holder.to_string(), objective: objective.map(str::to_string) })?;
+    Ok(true)
+}
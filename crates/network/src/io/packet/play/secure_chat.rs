@@ -0,0 +1,440 @@
+use std::collections::HashSet;
+use std::io::{self, Read, Write};
+use std::sync::{Arc, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use anyhow::{anyhow, bail};
+use once_cell::sync::Lazy;
+use rsa::pkcs1v15::{Signature, VerifyingKey};
+use rsa::pkcs8::DecodePublicKey;
+use rsa::sha2::Sha256;
+use rsa::signature::Verifier;
+use rsa::RsaPublicKey;
+use log::{debug, info, warn};
+use dolls_core::datatype::{decode_from_slice, read_bounded_string, Decode, Encode, Uuid, VarInt};
+use dolls_core::registry::ChatTypeBound;
+use dolls_core::text::TextComponent;
+use dolls_macros::packet_processor;
+use crate::prelude::{player_info_entry, ChatLine, ChatSessionData, ChatVisibility, ClientboundPacket, ClientboundPacketType, ConnectionHandle, ConnectionRegistry, ConnectionState, PacketContext, PacketType, PlayerInfoUpdate, RawPacket};
+
+pub const MESSAGE_SIGNATURE_LENGTH: usize = 256;
+/// How many previously seen messages a client acknowledges with each message.
+pub const LAST_SEEN_MESSAGES: usize = 20;
+/// Signatures a client remembers to refer to them by index.
+pub const SIGNATURE_CACHE_SIZE: usize = 128;
+/// Unacknowledged messages after which a client is considered to ignore chat.
+const MAX_PENDING_MESSAGES: usize = 4096;
+
+/// SHA256withRSA signature of a chat message.
+pub type MessageSignature = [u8; MESSAGE_SIGNATURE_LENGTH];
+
+/// Decides whether a profile public key was issued by Mojang to the player presenting it.
+pub trait ProfileKeyValidator: Send + Sync {
+    /// `payload` is what the key signature covers: the player's UUID, the key expiry and the key.
+    fn validate(&self, payload: &[u8], key_signature: &[u8]) -> bool;
+}
+
+static PROFILE_KEY_VALIDATOR: Lazy<RwLock<Option<Arc<dyn ProfileKeyValidator>>>> = Lazy::new(|| RwLock::new(None));
+
+/// Without a validator, as in offline mode, any well formed key is accepted.
+pub fn set_profile_key_validator(validator: Arc<dyn ProfileKeyValidator>) {
+    *PROFILE_KEY_VALIDATOR.write().unwrap() = Some(validator);
+}
+
+/// Bytes Mojang signed for a profile public key.
+pub fn profile_key_payload(uuid: Uuid, session: &ChatSessionData) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(24 + session.public_key.len());
+    payload.extend_from_slice(uuid.as_bytes());
+    payload.extend_from_slice(&session.expires_at.to_be_bytes());
+    payload.extend_from_slice(&session.public_key);
+    payload
+}
+
+fn now_millis() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_millis() as i64).unwrap_or_default()
+}
+
+/// What the client signed for a message, apart from the chain link.
+#[derive(Debug, Clone)]
+pub struct MessageBody {
+    pub content: String,
+    /// Milliseconds since the epoch.
+    pub timestamp: i64,
+    pub salt: i64,
+    pub last_seen: Vec<MessageSignature>,
+}
+
+/// A chat message whose signature was verified against the sender's chat session.
+#[derive(Debug, Clone)]
+pub struct SignedMessage {
+    pub sender: Uuid,
+    pub session_id: Uuid,
+    /// Position of the message in the sender's chain, starting over with every session.
+    pub link_index: i32,
+    pub signature: MessageSignature,
+    pub body: MessageBody,
+}
+
+impl SignedMessage {
+    /// The data covered by the signature.
+    pub fn signed_payload(&self) -> Vec<u8> {
+        let body = &self.body;
+        let mut payload = Vec::with_capacity(76 + body.content.len() + body.last_seen.len() * MESSAGE_SIGNATURE_LENGTH);
+        payload.extend_from_slice(&1i32.to_be_bytes());
+        payload.extend_from_slice(self.sender.as_bytes());
+        payload.extend_from_slice(self.session_id.as_bytes());
+        payload.extend_from_slice(&self.link_index.to_be_bytes());
+        payload.extend_from_slice(&body.salt.to_be_bytes());
+        payload.extend_from_slice(&(body.timestamp / 1000).to_be_bytes());
+        payload.extend_from_slice(&(body.content.len() as i32).to_be_bytes());
+        payload.extend_from_slice(body.content.as_bytes());
+        payload.extend_from_slice(&(body.last_seen.len() as i32).to_be_bytes());
+        for signature in &body.last_seen {
+            payload.extend_from_slice(signature);
+        }
+        payload
+    }
+}
+
+/// Acknowledgement of the last seen messages sent along with chat.
+#[derive(Debug, Clone, Decode)]
+pub struct LastSeenUpdate {
+    /// Messages dropped from the front of the window.
+    pub offset: VarInt,
+    /// One bit per tracked message, little-endian bit order.
+    pub acknowledged: [u8; 3],
+}
+
+impl LastSeenUpdate {
+    fn is_acknowledged(&self, index: usize) -> bool {
+        self.acknowledged[index / 8] & (1 << (index % 8)) != 0
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct TrackedMessage {
+    signature: MessageSignature,
+    pending: bool,
+}
+
+/// Follows which signed messages a client has seen, to check what it claims in the messages it signs.
+#[derive(Debug, Clone)]
+pub struct LastSeenMessagesValidator {
+    tracked: Vec<Option<TrackedMessage>>,
+    last_pending: Option<MessageSignature>,
+}
+
+impl Default for LastSeenMessagesValidator {
+    fn default() -> Self {
+        Self {
+            tracked: vec![None; LAST_SEEN_MESSAGES],
+            last_pending: None,
+        }
+    }
+}
+
+impl LastSeenMessagesValidator {
+    /// Records a message sent to the client, which it has to acknowledge or ignore later.
+    pub fn add_pending(&mut self, signature: MessageSignature) {
+        if self.last_pending != Some(signature) {
+            self.tracked.push(Some(TrackedMessage { signature, pending: true }));
+            self.last_pending = Some(signature);
+        }
+    }
+
+    pub fn tracked_count(&self) -> usize {
+        self.tracked.len()
+    }
+
+    pub fn apply_offset(&mut self, offset: i32) -> anyhow::Result<()> {
+        let available = self.tracked.len() - LAST_SEEN_MESSAGES;
+        if offset < 0 || offset as usize > available {
+            bail!("Advanced last seen window by offset {} beyond {} acknowledged messages", offset, available);
+        }
+        self.tracked.drain(..offset as usize);
+        Ok(())
+    }
+
+    /// Returns the signatures the client claims to have seen, oldest first.
+    pub fn apply_update(&mut self, update: &LastSeenUpdate) -> anyhow::Result<Vec<MessageSignature>> {
+        self.apply_offset(update.offset.0)?;
+        if update.acknowledged[2] >> (LAST_SEEN_MESSAGES - 16) != 0 {
+            bail!("Last seen update contained too many acknowledged messages");
+        }
+
+        let mut last_seen = Vec::new();
+        for index in 0..LAST_SEEN_MESSAGES {
+            let tracked = &mut self.tracked[index];
+            if update.is_acknowledged(index) {
+                let Some(message) = tracked else {
+                    bail!("Last seen update acknowledged unknown or previously ignored message at index {}", index);
+                };
+                message.pending = false;
+                last_seen.push(message.signature);
+            } else {
+                if tracked.is_some_and(|message| !message.pending) {
+                    bail!("Last seen update ignored previously acknowledged message at index {}", index);
+                }
+                *tracked = None;
+            }
+        }
+        Ok(last_seen)
+    }
+}
+
+/// A signature in Player Chat, by its index in the recipient's cache when the client has it.
+#[derive(Debug, Clone)]
+pub enum PackedSignature {
+    Cached(i32),
+    Full(Box<MessageSignature>),
+}
+
+impl Encode for PackedSignature {
+    fn encode(&self, writer: &mut impl Write) -> io::Result<()> {
+        match self {
+            PackedSignature::Cached(index) => VarInt(index + 1).encode(writer),
+            PackedSignature::Full(signature) => {
+                VarInt(0).encode(writer)?;
+                signature.as_ref().encode(writer)
+            }
+        }
+    }
+}
+
+/// Mirror of the signatures a client remembers, updated the same way the client does.
+#[derive(Debug, Clone)]
+pub struct MessageSignatureCache {
+    entries: Vec<Option<MessageSignature>>,
+}
+
+impl Default for MessageSignatureCache {
+    fn default() -> Self {
+        Self { entries: vec![None; SIGNATURE_CACHE_SIZE] }
+    }
+}
+
+impl MessageSignatureCache {
+    pub fn pack(&self, signature: &MessageSignature) -> PackedSignature {
+        match self.entries.iter().position(|entry| entry.as_ref() == Some(signature)) {
+            Some(index) => PackedSignature::Cached(index as i32),
+            None => PackedSignature::Full(Box::new(*signature)),
+        }
+    }
+
+    /// Moves the message and what it acknowledged to the front, keeping other entries in order.
+    pub fn push(&mut self, body: &MessageBody, signature: &MessageSignature) {
+        let mut queue = body.last_seen.clone();
+        queue.push(*signature);
+        let pushed = queue.iter().copied().collect::<HashSet<_>>();
+        let mut queue = std::collections::VecDeque::from(queue);
+
+        for entry in &mut self.entries {
+            let Some(next) = queue.pop_back() else { break };
+            if let Some(previous) = entry.replace(next) {
+                if !pushed.contains(&previous) {
+                    queue.push_front(previous);
+                }
+            }
+        }
+    }
+}
+
+/// Signed chat announced by a client, messages are verified against its key.
+struct ChatSession {
+    data: ChatSessionData,
+    key: VerifyingKey<Sha256>,
+    /// Index the next message must be signed with, `None` once the chain is broken.
+    next_link: Option<i32>,
+}
+
+/// Secure chat state of a connection, both as a sender and as a recipient.
+#[derive(Default)]
+struct SecureChat {
+    session: Option<ChatSession>,
+    last_timestamp: i64,
+    last_seen: LastSeenMessagesValidator,
+    signature_cache: MessageSignatureCache,
+}
+
+/// The chat session other players need to verify messages of `connection`.
+pub fn chat_session(connection: &ConnectionHandle) -> Option<ChatSessionData> {
+    connection.extensions(|extensions| {
+        extensions.get::<SecureChat>()?.session.as_ref().map(|session| session.data.clone())
+    })
+}
+
+/// Checks the last seen messages a client sent with a chat message or signed command.
+pub(crate) fn apply_last_seen_update(connection: &ConnectionHandle, update: &LastSeenUpdate) -> anyhow::Result<Vec<MessageSignature>> {
+    connection.extensions(|extensions| extensions.get_or_default::<SecureChat>().last_seen.apply_update(update))
+}
+
+/// Why a chat message was refused, `chain_broken` ones also refuse everything after them.
+#[derive(Debug)]
+pub(crate) struct ChatRejection {
+    /// Translation key of the reason shown to the sender.
+    pub reason: &'static str,
+    pub chain_broken: bool,
+}
+
+impl ChatRejection {
+    fn new(reason: &'static str, chain_broken: bool) -> Self {
+        Self { reason, chain_broken }
+    }
+}
+
+/// Verifies a chat message against the sender's chain, `None` for unsigned chat the server accepts.
+pub(crate) fn unpack_chat_message(context: &PacketContext, body: MessageBody, signature: Option<MessageSignature>) -> Result<Option<SignedMessage>, ChatRejection> {
+    let enforce = context.config.server.enforce_secure_profile;
+    let sender = context.uuid.unwrap_or_default();
+    context.connection.extensions(|extensions| {
+        let state = extensions.get_or_default::<SecureChat>();
+        if body.timestamp < state.last_timestamp {
+            return Err(ChatRejection::new("multiplayer.disconnect.out_of_order_chat", true));
+        }
+        state.last_timestamp = body.timestamp;
+
+        let Some(session) = state.session.as_mut() else {
+            return match enforce {
+                true => Err(ChatRejection::new("chat.disabled.missingProfileKey", false)),
+                false => Ok(None),
+            };
+        };
+        let Some(signature) = signature else {
+            return Err(ChatRejection::new("chat.disabled.missingProfileKey", false));
+        };
+        if session.data.expires_at < now_millis() {
+            return Err(ChatRejection::new("chat.disabled.expiredProfileKey", false));
+        }
+        let Some(link_index) = session.next_link else {
+            return Err(ChatRejection::new("chat.disabled.chain_broken", true));
+        };
+
+        let message = SignedMessage { sender, session_id: session.data.session_id, link_index, signature, body };
+        let verified = Signature::try_from(signature.as_slice())
+            .is_ok_and(|signature| session.key.verify(&message.signed_payload(), &signature).is_ok());
+        if !verified {
+            session.next_link = None;
+            return Err(ChatRejection::new("chat.disabled.invalid_signature", true));
+        }
+        session.next_link = Some(link_index + 1);
+        Ok(Some(message))
+    })
+}
+
+/// Chat signed by a player, verifiable by every recipient.
+#[derive(Debug, Clone, Encode)]
+pub struct PlayerChatMessage {
+    pub sender: Uuid,
+    pub index: VarInt,
+    pub signature: Option<MessageSignature>,
+    pub message: String,
+    pub timestamp: i64,
+    pub salt: i64,
+    pub previous_messages: Vec<PackedSignature>,
+    /// Shown instead of `message` when the server decorated it.
+    pub unsigned_content: Option<TextComponent>,
+    /// 0 for messages which are not filtered.
+    pub filter_type: VarInt,
+    pub chat_type: ChatTypeBound,
+}
+
+impl ClientboundPacket for PlayerChatMessage {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::PlayerChatMessage;
+}
+
+/// Sends a signed message to every player who has chat enabled.
+pub fn broadcast_signed_chat(connections: &ConnectionRegistry, message: &SignedMessage, line: &ChatLine) -> anyhow::Result<()> {
+    let chat_type = ChatTypeBound::registered(&line.chat_type, line.sender_name.clone(), None)
+        .ok_or_else(|| anyhow!("Chat type {} is not registered", line.chat_type))?;
+    info!("<{}> {}", line.sender_name.to_plain_text(), line.content.to_plain_text());
+    let unsigned_content = (line.content != TextComponent::text(message.body.content.clone())).then(|| line.content.clone());
+
+    for player in connections.players() {
+        if player.chat_visibility() != ChatVisibility::Full {
+            continue;
+        }
+        // Previous messages are packed against the cache as it was before this message.
+        let (previous_messages, pending) = player.extensions(|extensions| {
+            let state = extensions.get_or_default::<SecureChat>();
+            let previous_messages = message.body.last_seen.iter().map(|signature| state.signature_cache.pack(signature)).collect();
+            state.signature_cache.push(&message.body, &message.signature);
+            state.last_seen.add_pending(message.signature);
+            (previous_messages, state.last_seen.tracked_count())
+        });
+        if pending > MAX_PENDING_MESSAGES {
+            warn!("{:?} does not acknowledge chat anymore", player.username());
+        }
+        let _ = player.send(&PlayerChatMessage {
+            sender: message.sender,
+            index: VarInt(message.link_index),
+            signature: Some(message.signature),
+            message: message.body.content.clone(),
+            timestamp: message.body.timestamp,
+            salt: message.body.salt,
+            previous_messages,
+            unsigned_content: unsigned_content.clone(),
+            filter_type: VarInt(0),
+            chat_type: chat_type.clone(),
+        });
+    }
+    Ok(())
+}
+
+/// The client announces the key it signs chat with, and renews it when it expires.
+#[packet_processor(PacketType::PlayerSession)]
+pub(crate) fn player_session_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    let data: ChatSessionData = decode_from_slice(&packet.payload)?;
+    let uuid = context.uuid.ok_or_else(|| anyhow!("Player session from {} before login", context.peer_addr))?;
+    if data.expires_at < now_millis() {
+        bail!("Expired profile public key from {:?}", context.username);
+    }
+    let validator = PROFILE_KEY_VALIDATOR.read().unwrap().clone();
+    if validator.is_some_and(|validator| !validator.validate(&profile_key_payload(uuid, &data), &data.key_signature)) {
+        bail!("Invalid profile public key signature from {:?}", context.username);
+    }
+    let key = RsaPublicKey::from_public_key_der(&data.public_key)
+        .map_err(|err| anyhow!("Malformed profile public key from {:?}: {}", context.username, err))?;
+
+    debug!("Chat session {} of {:?}", data.session_id, context.username);
+    context.connection.extensions(|extensions| {
+        extensions.get_or_default::<SecureChat>().session = Some(ChatSession {
+            data: data.clone(),
+            key: VerifyingKey::new(key),
+            next_link: Some(0),
+        });
+    });
+
+    // Everyone, the player included, needs the session to verify the player's messages.
+    let mut entry = player_info_entry(&context.connection).unwrap_or_default();
+    entry.uuid = uuid;
+    entry.chat_session = Some(data);
+    let update = PlayerInfoUpdate::new(PlayerInfoUpdate::INITIALIZE_CHAT, vec![entry]);
+    context.connections.broadcast(&update)?;
+    // Only listed as a player once a processor in Play returned.
+    if context.connection.state() != ConnectionState::Play {
+        context.send(&update)?;
+    }
+    Ok(())
+}
+
+/// Sent once many messages arrived without the client sending chat to acknowledge them.
+#[packet_processor(PacketType::MessageAcknowledgment)]
+pub(crate) fn message_acknowledgment_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    let offset = VarInt::decode(&mut packet.payload.as_slice())?;
+    context.connection.extensions(|extensions| extensions.get_or_default::<SecureChat>().last_seen.apply_offset(offset.0))
+}
+
+/// Signatures of a signed command's message arguments.
+#[derive(Debug, Clone)]
+pub struct ArgumentSignature {
+    pub name: String,
+    pub signature: MessageSignature,
+}
+
+impl Decode for ArgumentSignature {
+    fn decode(reader: &mut impl Read) -> io::Result<Self> {
+        Ok(Self {
+            name: read_bounded_string(reader, 16)?,
+            signature: MessageSignature::decode(reader)?,
+        })
+    }
+}
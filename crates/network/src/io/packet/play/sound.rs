@@ -0,0 +1,191 @@
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use dolls_core::datatype::{Encode, IdOr, Identifier, VarInt};
+use dolls_entities::prelude::{EntityId, Vec3};
+use crate::prelude::{entity_viewers, player_position, ClientboundPacket, ClientboundPacketType, ConnectionHandle, ConnectionRegistry};
+
+/// Sounds are heard up to this many blocks away, further for volumes above 1.
+pub const SOUND_RANGE: f64 = 16.0;
+
+/// Volume slider a sound plays under in the client's settings.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub enum SoundCategory {
+    #[default]
+    Master,
+    Music,
+    Record,
+    Weather,
+    Block,
+    Hostile,
+    Neutral,
+    Player,
+    Ambient,
+    Voice,
+}
+
+impl SoundCategory {
+    pub const ALL: [SoundCategory; 10] = [SoundCategory::Master, SoundCategory::Music, SoundCategory::Record, SoundCategory::Weather,
+        SoundCategory::Block, SoundCategory::Hostile, SoundCategory::Neutral, SoundCategory::Player, SoundCategory::Ambient, SoundCategory::Voice];
+
+    pub const fn id(self) -> i32 {
+        self as i32
+    }
+
+    /// The name used by commands, e.g. `hostile`.
+    pub const fn name(self) -> &'static str {
+        match self {
+            SoundCategory::Master => "master",
+            SoundCategory::Music => "music",
+            SoundCategory::Record => "record",
+            SoundCategory::Weather => "weather",
+            SoundCategory::Block => "block",
+            SoundCategory::Hostile => "hostile",
+            SoundCategory::Neutral => "neutral",
+            SoundCategory::Player => "player",
+            SoundCategory::Ambient => "ambient",
+            SoundCategory::Voice => "voice",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|category| category.name() == name)
+    }
+}
+
+/// A sound by its resource location, for sounds outside the client's sound event registry or
+/// ones heard from further away than their volume allows.
+#[derive(Debug, Clone, PartialEq, Encode)]
+pub struct SoundEvent {
+    pub name: Identifier,
+    pub fixed_range: Option<f32>,
+}
+
+/// A sound to play: which one, under which category, how loud and how high.
+#[derive(Debug, Clone)]
+pub struct Sound {
+    /// Id in the `minecraft:sound_event` registry, or a named sound.
+    pub sound: IdOr<SoundEvent>,
+    pub category: SoundCategory,
+    pub volume: f32,
+    pub pitch: f32,
+    /// Picks among the sound's variants, the same seed plays the same one on every client.
+    pub seed: i64,
+}
+
+impl Sound {
+    /// A named sound such as `minecraft:entity.experience_orb.pickup`, at full volume and normal pitch.
+    pub fn named(name: Identifier) -> Self {
+        Self::new(IdOr::Inline(SoundEvent { name, fixed_range: None }))
+    }
+
+    /// A sound by its id in the sound event registry.
+    pub fn id(id: i32) -> Self {
+        Self::new(IdOr::Id(id))
+    }
+
+    fn new(sound: IdOr<SoundEvent>) -> Self {
+        Self { sound, category: SoundCategory::Master, volume: 1.0, pitch: 1.0, seed: random_seed() }
+    }
+
+    pub fn category(mut self, category: SoundCategory) -> Self {
+        self.category = category;
+        self
+    }
+
+    pub fn volume(mut self, volume: f32) -> Self {
+        self.volume = volume;
+        self
+    }
+
+    /// From 0.5 to 2, 1 plays the sound as recorded.
+    pub fn pitch(mut self, pitch: f32) -> Self {
+        self.pitch = pitch.clamp(0.5, 2.0);
+        self
+    }
+
+    pub fn seed(mut self, seed: i64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// How far away the sound can be heard, like vanilla louder sounds carry further.
+    pub fn range(&self) -> f64 {
+        match &self.sound {
+            IdOr::Inline(SoundEvent { fixed_range: Some(range), .. }) => *range as f64,
+            _ => SOUND_RANGE * (self.volume as f64).max(1.0),
+        }
+    }
+
+    fn at(&self, position: Vec3) -> SoundEffect {
+        let [x, y, z] = [position.x, position.y, position.z].map(|value| (value * 8.0) as i32);
+        SoundEffect { sound: self.sound.clone(), category: VarInt(self.category.id()), x, y, z, volume: self.volume, pitch: self.pitch, seed: self.seed }
+    }
+}
+
+fn random_seed() -> i64 {
+    RandomState::new().hash_one(std::time::SystemTime::now()) as i64
+}
+
+/// Plays a sound at a position, in 1/8 of a block.
+#[derive(Debug, Clone, Encode)]
+pub struct SoundEffect {
+    pub sound: IdOr<SoundEvent>,
+    pub category: VarInt,
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+    pub volume: f32,
+    pub pitch: f32,
+    pub seed: i64,
+}
+
+impl ClientboundPacket for SoundEffect {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::SoundEffect;
+}
+
+/// Plays a sound following an entity as it moves.
+#[derive(Debug, Clone, Encode)]
+pub struct EntitySoundEffect {
+    pub sound: IdOr<SoundEvent>,
+    pub category: VarInt,
+    pub entity_id: VarInt,
+    pub volume: f32,
+    pub pitch: f32,
+    pub seed: i64,
+}
+
+impl ClientboundPacket for EntitySoundEffect {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::EntitySoundEffect;
+}
+
+impl ConnectionHandle {
+    /// Plays a sound for this player only.
+    pub fn play_sound(&self, sound: &Sound, position: Vec3) -> anyhow::Result<()> {
+        self.send(&sound.at(position))
+    }
+}
+
+/// Plays a sound at a position for every player within its range, returning how many hear it.
+pub fn play_sound(connections: &ConnectionRegistry, sound: &Sound, position: Vec3) -> usize {
+    let range = sound.range();
+    let packet = sound.at(position);
+    connections.players().into_iter()
+        .filter(|player| player_position(player).is_some_and(|player| player.vec3().distance_squared(position) <= range * range))
+        .filter(|player| player.send(&packet).is_ok())
+        .count()
+}
+
+/// Plays a sound following an entity for the players who see it, and the player it is if any.
+pub fn play_entity_sound(connections: &ConnectionRegistry, sound: &Sound, entity_id: EntityId) -> usize {
+    let packet = EntitySoundEffect {
+        sound: sound.sound.clone(),
+        category: VarInt(sound.category.id()),
+        entity_id: VarInt(entity_id),
+        volume: sound.volume,
+        pitch: sound.pitch,
+        seed: sound.seed,
+    };
+    let mut listeners = entity_viewers(connections, entity_id);
+    listeners.extend(connections.players().into_iter().filter(|player| player.entity_id() == Some(entity_id)));
+    listeners.iter().filter(|player| player.send(&packet).is_ok()).count()
+}
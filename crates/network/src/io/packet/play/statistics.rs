@@ -0,0 +1,182 @@
+use dolls_core::datatype::{decode_from_slice, Encode, Identifier, Uuid, VarInt};
+use dolls_core::item::items;
+use dolls_core::statistic::{statistics, StatType};
+use dolls_macros::packet_processor;
+use dolls_tick::prelude::scheduler;
+use dolls_world::prelude::{stats_data, PlayerStatistics};
+use log::warn;
+use crate::prelude::{respawn, vitals, ClientboundPacket, ClientboundPacketType, ConnectionHandle, ConnectionRegistry, PacketContext, PacketType, PlayerPosition, RawPacket};
+
+/// Actions of the Client Status packet.
+const PERFORM_RESPAWN: i32 = 0;
+const REQUEST_STATS: i32 = 1;
+
+#[derive(Debug, Clone, Encode)]
+pub struct StatisticEntry {
+    pub category: VarInt,
+    pub statistic: VarInt,
+    pub value: VarInt,
+}
+
+/// Answers the client asking for its statistics, it only shows the statistics it got.
+#[derive(Debug, Clone, Encode)]
+pub struct AwardStatistics {
+    pub statistics: Vec<StatisticEntry>,
+}
+
+impl ClientboundPacket for AwardStatistics {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::AwardStatistics;
+}
+
+/// A player's statistics, kept in its connection's extensions.
+#[derive(Debug, Default)]
+struct PlayerStats {
+    values: PlayerStatistics,
+    /// Tick up to which the time statistics were counted.
+    counted_tick: u64,
+}
+
+impl PlayerStats {
+    fn add(&mut self, kind: StatType, name: &Identifier, amount: i32) {
+        let value = self.values.entry(kind.name()).or_default().entry(name.clone()).or_default();
+        *value = value.saturating_add(amount);
+    }
+
+    /// Adds the ticks passed since the last call to the statistics counting time.
+    fn count_time(&mut self) {
+        let tick = scheduler().current_tick();
+        let elapsed = tick.saturating_sub(self.counted_tick).min(i32::MAX as u64) as i32;
+        self.counted_tick = tick;
+        for name in ["play_time", "total_world_time", "time_since_death", "time_since_rest"] {
+            self.add(StatType::Custom, &Identifier::minecraft(name), elapsed);
+        }
+    }
+}
+
+/// Adds `amount` to a statistic, e.g. `StatType::Mined` of `minecraft:stone`.
+pub fn increment_stat(connection: &ConnectionHandle, kind: StatType, name: &Identifier, amount: i32) {
+    if amount != 0 {
+        connection.extensions(|extensions| extensions.get_or_default::<PlayerStats>().add(kind, name, amount));
+    }
+}
+
+/// Adds `amount` to a `minecraft:custom` statistic such as `jump`.
+pub fn increment_custom_stat(connection: &ConnectionHandle, name: &str, amount: i32) {
+    increment_stat(connection, StatType::Custom, &Identifier::minecraft(name), amount);
+}
+
+pub fn set_stat(connection: &ConnectionHandle, kind: StatType, name: &Identifier, value: i32) {
+    connection.extensions(|extensions| {
+        let stats = extensions.get_or_default::<PlayerStats>();
+        stats.count_time();
+        stats.values.entry(kind.name()).or_default().insert(name.clone(), value);
+    });
+}
+
+pub fn stat(connection: &ConnectionHandle, kind: StatType, name: &Identifier) -> i32 {
+    connection.extensions(|extensions| {
+        let stats = extensions.get_or_default::<PlayerStats>();
+        stats.count_time();
+        stats.values.get(&kind.name()).and_then(|values| values.get(name)).copied().unwrap_or(0)
+    })
+}
+
+/// Counts the distance of a move in centimeters: walked on the ground, fallen or flown in the air.
+/// Leaving the ground upwards counts as a jump.
+pub(crate) fn record_move(connection: &ConnectionHandle, from: &PlayerPosition, to: &PlayerPosition) {
+    let (dx, dy, dz) = (to.x - from.x, to.y - from.y, to.z - from.z);
+    let horizontal = ((dx * dx + dz * dz).sqrt() * 100.0).round() as i32;
+    connection.extensions(|extensions| {
+        let stats = extensions.get_or_default::<PlayerStats>();
+        let custom = |name: &str| Identifier::minecraft(name);
+        match (from.on_ground, to.on_ground) {
+            (_, true) => stats.add(StatType::Custom, &custom("walk_one_cm"), horizontal),
+            (true, false) if dy > 0.0 => {
+                stats.add(StatType::Custom, &custom("jump"), 1);
+                stats.add(StatType::Custom, &custom("walk_one_cm"), horizontal);
+            }
+            _ => stats.add(StatType::Custom, &custom("fly_one_cm"), horizontal),
+        }
+        if dy < 0.0 && !to.on_ground {
+            stats.add(StatType::Custom, &custom("fall_one_cm"), (-dy * 100.0).round() as i32);
+        }
+    });
+}
+
+/// Reads the joining player's saved statistics.
+pub(crate) fn load_statistics(context: &mut PacketContext) {
+    let values = context.uuid.map(|uuid| stats_data().read().unwrap().load(uuid).unwrap_or_else(|err| {
+        warn!("Failed to load the statistics of {}: {}", uuid.hyphenated(), err);
+        PlayerStatistics::new()
+    })).unwrap_or_default();
+    let stats = PlayerStats { values, counted_tick: scheduler().current_tick() };
+    context.connection.extensions(|extensions| extensions.insert(stats));
+}
+
+fn save(uuid: Option<Uuid>, values: &PlayerStatistics) {
+    let Some(uuid) = uuid else { return };
+    if let Err(err) = stats_data().read().unwrap().save(uuid, values) {
+        warn!("Failed to save the statistics of {}: {}", uuid.hyphenated(), err);
+    }
+}
+
+/// Writes a player's statistics to the world.
+pub fn save_statistics(connection: &ConnectionHandle) {
+    let values = connection.extensions(|extensions| extensions.get_mut::<PlayerStats>().map(|stats| {
+        stats.count_time();
+        stats.values.clone()
+    }));
+    if let Some(values) = values {
+        save(connection.uuid(), &values);
+    }
+}
+
+/// Writes the statistics of every player, e.g. when the world is saved.
+pub fn save_all_statistics(connections: &ConnectionRegistry) {
+    for player in connections.players() {
+        save_statistics(&player);
+    }
+}
+
+/// Counts the player leaving and saves its statistics one last time.
+pub(crate) fn statistics_left(connection: &ConnectionHandle) {
+    increment_custom_stat(connection, "leave_game", 1);
+    save_statistics(connection);
+}
+
+/// Every statistic of the player the client can show, with its network ids.
+pub fn statistics_packet(connection: &ConnectionHandle) -> AwardStatistics {
+    let values = connection.extensions(|extensions| {
+        let stats = extensions.get_or_default::<PlayerStats>();
+        stats.count_time();
+        stats.values.clone()
+    });
+    let registry = statistics().read().unwrap();
+    let items = items().read().unwrap();
+    let statistics = values.iter()
+        .filter_map(|(kind, values)| StatType::from_name(kind).map(|kind| (kind, values)))
+        .flat_map(|(kind, values)| values.iter().map(move |(name, value)| (kind, name, *value)))
+        .filter_map(|(kind, name, value)| {
+            let id = match kind {
+                StatType::Crafted | StatType::Used | StatType::Broken | StatType::PickedUp | StatType::Dropped => items.id(name),
+                kind => registry.id(kind, name),
+            }?;
+            Some(StatisticEntry { category: VarInt(kind.id()), statistic: VarInt(id), value: VarInt(value) })
+        })
+        .collect();
+    AwardStatistics { statistics }
+}
+
+#[packet_processor(PacketType::ClientStatus)]
+pub(crate) fn client_status_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    let VarInt(action) = decode_from_slice(&packet.payload)?;
+    match action {
+        PERFORM_RESPAWN if vitals(&context.connection).is_dead() => respawn(context),
+        PERFORM_RESPAWN => Ok(()),
+        REQUEST_STATS => {
+            let packet = statistics_packet(&context.connection);
+            context.send(&packet)
+        }
+        action => anyhow::bail!("Unknown client status action {}", action),
+    }
+}
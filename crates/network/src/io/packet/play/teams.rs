@@ -0,0 +1,133 @@
+use std::io::{self, Write};
+use dolls_core::datatype::{Encode, VarInt};
+use dolls_core::text::TextComponent;
+use dolls_world::prelude::{scoreboard, Team};
+use crate::prelude::{ClientboundPacket, ClientboundPacketType, ConnectionRegistry, PacketContext};
+
+/// Formatting id the client reads as "no color".
+const RESET_COLOR: i32 = 21;
+
+/// Creates, changes or removes a team on the client, or changes who is in it.
+#[derive(Debug, Clone)]
+pub enum UpdateTeams {
+    Create(Team),
+    Remove(String),
+    Update(Team),
+    AddMembers { team: String, members: Vec<String> },
+    RemoveMembers { team: String, members: Vec<String> },
+}
+
+impl ClientboundPacket for UpdateTeams {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::UpdateTeams;
+}
+
+impl Encode for UpdateTeams {
+    fn encode(&self, writer: &mut impl Write) -> io::Result<()> {
+        match self {
+            UpdateTeams::Create(team) => {
+                team.name.encode(writer)?;
+                0i8.encode(writer)?;
+                encode_team_info(team, writer)?;
+                team.members().map(str::to_string).collect::<Vec<_>>().encode(writer)
+            }
+            UpdateTeams::Remove(name) => {
+                name.encode(writer)?;
+                1i8.encode(writer)
+            }
+            UpdateTeams::Update(team) => {
+                team.name.encode(writer)?;
+                2i8.encode(writer)?;
+                encode_team_info(team, writer)
+            }
+            UpdateTeams::AddMembers { team, members } => {
+                team.encode(writer)?;
+                3i8.encode(writer)?;
+                members.encode(writer)
+            }
+            UpdateTeams::RemoveMembers { team, members } => {
+                team.encode(writer)?;
+                4i8.encode(writer)?;
+                members.encode(writer)
+            }
+        }
+    }
+}
+
+fn encode_team_info(team: &Team, writer: &mut impl Write) -> io::Result<()> {
+    team.display_name.encode(writer)?;
+    let flags = team.friendly_fire as i8 | (team.see_friendly_invisibles as i8) << 1;
+    flags.encode(writer)?;
+    team.name_tag_visibility.name().to_string().encode(writer)?;
+    team.collision_rule.name().to_string().encode(writer)?;
+    VarInt(team.color.map_or(RESET_COLOR, i32::from)).encode(writer)?;
+    team.prefix.encode(writer)?;
+    team.suffix.encode(writer)
+}
+
+/// Sends a joining player every team with its members.
+pub(crate) fn send_teams(context: &mut PacketContext) -> anyhow::Result<()> {
+    let teams = scoreboard().read().unwrap().teams().cloned().collect::<Vec<_>>();
+    for team in teams {
+        context.send(&UpdateTeams::Create(team))?;
+    }
+    Ok(())
+}
+
+pub fn team_of(holder: &str) -> Option<Team> {
+    scoreboard().read().unwrap().team_of(holder).cloned()
+}
+
+/// A holder's name formatted by its team, the plain name for holders in none.
+pub fn team_formatted_name(holder: &str) -> TextComponent {
+    match scoreboard().read().unwrap().team_of(holder) {
+        Some(team) => team.format_name(holder),
+        None => TextComponent::text(holder),
+    }
+}
+
+/// Adds a team without members, failing when one by that name exists.
+pub fn add_team(connections: &ConnectionRegistry, team: Team) -> anyhow::Result<()> {
+    let name = team.name.clone();
+    scoreboard().write().unwrap().add_team(team)?;
+    let team = scoreboard().read().unwrap().team(&name).cloned().expect("Team was just added");
+    connections.broadcast(&UpdateTeams::Create(team))
+}
+
+/// Changes a team's settings such as its color, prefix or collision rule.
+pub fn modify_team(connections: &ConnectionRegistry, name: &str, modify: impl FnOnce(&mut Team)) -> anyhow::Result<()> {
+    let team = scoreboard().write().unwrap().modify_team(name, modify)?;
+    connections.broadcast(&UpdateTeams::Update(team))
+}
+
+/// Removes a team, telling whether it existed.
+pub fn remove_team(connections: &ConnectionRegistry, name: &str) -> anyhow::Result<bool> {
+    if scoreboard().write().unwrap().remove_team(name).is_none() {
+        return Ok(false);
+    }
+    connections.broadcast(&UpdateTeams::Remove(name.to_string()))?;
+    Ok(true)
+}
+
+/// Makes holders members of a team, they leave the teams they were in.
+pub fn join_team(connections: &ConnectionRegistry, team: &str, holders: &[String]) -> anyhow::Result<()> {
+    {
+        let mut scoreboard = scoreboard().write().unwrap();
+        for holder in holders {
+            scoreboard.join_team(holder, team)?;
+        }
+    }
+    // Clients drop members from their old team themselves.
+    connections.broadcast(&UpdateTeams::AddMembers { team: team.to_string(), members: holders.to_vec() })
+}
+
+/// Removes holders from their teams, returning how many were in one.
+pub fn leave_team(connections: &ConnectionRegistry, holders: &[String]) -> anyhow::Result<usize> {
+    let left = {
+        let mut scoreboard = scoreboard().write().unwrap();
+        holders.iter().filter_map(|holder| scoreboard.leave_team(holder).map(|team| (team, holder.clone()))).collect::<Vec<_>>()
+    };
+    for (team, holder) in &left {
+        connections.broadcast(&UpdateTeams::RemoveMembers { team: team.clone(), members: vec![holder.clone()] })?;
+    }
+    Ok(left.len())
+}
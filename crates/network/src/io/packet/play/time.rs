@@ -0,0 +1,108 @@
+use std::sync::Arc;
+use dolls_core::datatype::Encode;
+use dolls_tick::prelude::{scheduler, TaskGuard};
+use dolls_world::prelude::{level, LevelData, Weather, DO_DAYLIGHT_CYCLE, DO_WEATHER_CYCLE};
+use crate::prelude::{ClientboundPacket, ClientboundPacketType, ConnectionRegistry, GameEvent, PacketContext};
+
+/// Ticks in a full day and night.
+pub const DAY_LENGTH: i64 = 24_000;
+/// Clients advance the time themselves, it is only corrected this often, like vanilla.
+const TIME_SYNC_INTERVAL: u64 = 20;
+
+#[derive(Debug, Clone, Encode)]
+pub struct UpdateTime {
+    pub world_age: i64,
+    /// Negative values stop the client from advancing the time.
+    pub time_of_day: i64,
+}
+
+impl ClientboundPacket for UpdateTime {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::UpdateTime;
+}
+
+impl UpdateTime {
+    pub fn of(level: &LevelData) -> Self {
+        let time_of_day = match level.game_rules.get_bool(DO_DAYLIGHT_CYCLE) {
+            true => level.day_time,
+            // Zero cannot be negated, vanilla sends -1 for it.
+            false if level.day_time == 0 => -1,
+            false => -level.day_time,
+        };
+        Self { world_age: level.time, time_of_day }
+    }
+}
+
+/// Game events telling a client the whole weather, for players joining.
+fn weather_events(weather: &Weather) -> Vec<GameEvent> {
+    if !weather.raining && weather.rain_level() == 0.0 {
+        return Vec::new();
+    }
+    vec![
+        GameEvent { event: GameEvent::BEGIN_RAINING, value: 0.0 },
+        GameEvent { event: GameEvent::RAIN_LEVEL_CHANGE, value: weather.rain_level() },
+        GameEvent { event: GameEvent::THUNDER_LEVEL_CHANGE, value: weather.thunder_level() },
+    ]
+}
+
+/// Sends a joining player the time and weather.
+pub(crate) fn send_time_and_weather(context: &mut PacketContext) -> anyhow::Result<()> {
+    let (time, events) = {
+        let level = level().read().unwrap();
+        (UpdateTime::of(&level), weather_events(&level.weather))
+    };
+    context.send(&time)?;
+    for event in events {
+        context.send(&event)?;
+    }
+    Ok(())
+}
+
+/// Advances the time and weather by a tick, telling everyone when it starts or stops raining and
+/// correcting their clocks every second.
+pub fn tick_world_time(connections: &ConnectionRegistry) {
+    let (time, change) = {
+        let mut level = level().write().unwrap();
+        level.time += 1;
+        if level.game_rules.get_bool(DO_DAYLIGHT_CYCLE) {
+            level.day_time += 1;
+        }
+        let cycle = level.game_rules.get_bool(DO_WEATHER_CYCLE);
+        let change = level.weather.tick(cycle);
+        (UpdateTime::of(&level), change)
+    };
+    if change.started_raining {
+        let _ = connections.broadcast(&GameEvent { event: GameEvent::BEGIN_RAINING, value: 0.0 });
+    }
+    if change.stopped_raining {
+        let _ = connections.broadcast(&GameEvent { event: GameEvent::END_RAINING, value: 0.0 });
+    }
+    if let Some(value) = change.rain_level {
+        let _ = connections.broadcast(&GameEvent { event: GameEvent::RAIN_LEVEL_CHANGE, value });
+    }
+    if let Some(value) = change.thunder_level {
+        let _ = connections.broadcast(&GameEvent { event: GameEvent::THUNDER_LEVEL_CHANGE, value });
+    }
+    if scheduler().current_tick().is_multiple_of(TIME_SYNC_INTERVAL) {
+        let _ = connections.broadcast(&time);
+    }
+}
+
+/// Runs the day cycle and weather on every tick, dropping the guard stops them.
+pub fn start_world_time(connections: Arc<ConnectionRegistry>) -> TaskGuard {
+    scheduler().run_repeating("World time", 0, 1, move || tick_world_time(&connections)).guard()
+}
+
+/// Sets the time of day, e.g. 1000 for the morning or 13000 for the night.
+pub fn set_day_time(connections: &ConnectionRegistry, day_time: i64) -> anyhow::Result<()> {
+    let time = {
+        let mut level = level().write().unwrap();
+        level.day_time = day_time;
+        UpdateTime::of(&level)
+    };
+    connections.broadcast(&time)
+}
+
+/// Changes the weather, e.g. with [`Weather::set_rain`]. Clients follow on the next tick.
+pub fn set_weather(change: impl FnOnce(&mut Weather)) {
+    change(&mut level().write().unwrap().weather);
+}
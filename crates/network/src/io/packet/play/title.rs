@@ -0,0 +1,98 @@
+use dolls_core::datatype::Encode;
+use dolls_core::text::TextComponent;
+use crate::prelude::{ClientboundPacket, ClientboundPacketType, ConnectionHandle};
+
+/// Ticks titles fade in, stay and fade out for when no times were set, like vanilla.
+pub const DEFAULT_TITLE_TIMES: TitleTimes = TitleTimes { fade_in: 10, stay: 70, fade_out: 20 };
+
+/// Shows a title in the middle of the screen, together with the last subtitle sent.
+#[derive(Debug, Clone, Encode)]
+pub struct SetTitleText {
+    pub text: TextComponent,
+}
+
+impl ClientboundPacket for SetTitleText {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::SetTitleText;
+}
+
+/// Text below the title, only shown with the next title.
+#[derive(Debug, Clone, Encode)]
+pub struct SetSubtitleText {
+    pub text: TextComponent,
+}
+
+impl ClientboundPacket for SetSubtitleText {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::SetSubtitleText;
+}
+
+/// How long titles fade in, stay and fade out, in ticks.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Encode)]
+pub struct TitleTimes {
+    pub fade_in: i32,
+    pub stay: i32,
+    pub fade_out: i32,
+}
+
+impl ClientboundPacket for TitleTimes {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::SetTitleAnimationTimes;
+}
+
+/// Text above the hotbar.
+#[derive(Debug, Clone, Encode)]
+pub struct SetActionBarText {
+    pub text: TextComponent,
+}
+
+impl ClientboundPacket for SetActionBarText {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::SetActionBarText;
+}
+
+/// Hides the title and subtitle, `reset` also forgets the subtitle and restores the default times.
+#[derive(Debug, Clone, Encode)]
+pub struct ClearTitles {
+    pub reset: bool,
+}
+
+impl ClientboundPacket for ClearTitles {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::ClearTitles;
+}
+
+impl ConnectionHandle {
+    /// Shows a title, with the subtitle sent before it if there was one.
+    pub fn send_title(&self, title: TextComponent) -> anyhow::Result<()> {
+        self.send(&SetTitleText { text: title })
+    }
+
+    /// Sets the subtitle shown with the next title.
+    pub fn send_subtitle(&self, subtitle: TextComponent) -> anyhow::Result<()> {
+        self.send(&SetSubtitleText { text: subtitle })
+    }
+
+    /// Shows a title and subtitle together with their own times.
+    pub fn show_title(&self, title: TextComponent, subtitle: Option<TextComponent>, times: TitleTimes) -> anyhow::Result<()> {
+        self.set_title_times(times)?;
+        if let Some(subtitle) = subtitle {
+            self.send_subtitle(subtitle)?;
+        }
+        self.send_title(title)
+    }
+
+    /// Times of this and later titles, until reset.
+    pub fn set_title_times(&self, times: TitleTimes) -> anyhow::Result<()> {
+        self.send(&times)
+    }
+
+    pub fn send_action_bar(&self, text: TextComponent) -> anyhow::Result<()> {
+        self.send(&SetActionBarText { text })
+    }
+
+    /// Hides the title currently shown.
+    pub fn clear_title(&self) -> anyhow::Result<()> {
+        self.send(&ClearTitles { reset: false })
+    }
+
+    /// Hides the title and restores the subtitle and times to their defaults.
+    pub fn reset_title(&self) -> anyhow::Result<()> {
+        self.send(&ClearTitles { reset: true })
+    }
+}
@@ -0,0 +1,155 @@
+use std::sync::{Arc, Mutex};
+use dolls_core::datatype::{decode_from_slice, Encode, VarInt};
+use dolls_core::item::ItemStack;
+use dolls_core::text::TextComponent;
+use dolls_macros::packet_processor;
+use crate::prelude::{close_container, open_container, ClientboundPacket, ClientboundPacketType, ConnectionHandle, PacketContext, PacketType, RawPacket};
+
+/// Menu types of the `minecraft:menu` registry, in network id order.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum WindowType {
+    Generic9x1,
+    Generic9x2,
+    Generic9x3,
+    Generic9x4,
+    Generic9x5,
+    Generic9x6,
+    Generic3x3,
+    Crafter3x3,
+    Anvil,
+    Beacon,
+    BlastFurnace,
+    BrewingStand,
+    Crafting,
+    Enchantment,
+    Furnace,
+    Grindstone,
+    Hopper,
+    Lectern,
+    Loom,
+    Merchant,
+    ShulkerBox,
+    Smithing,
+    Smoker,
+    CartographyTable,
+    Stonecutter,
+}
+
+impl WindowType {
+    pub const fn network_id(self) -> i32 {
+        self as i32
+    }
+}
+
+/// What a window shows, menus come from the registry while horse screens are tied to an entity.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum WindowKind {
+    Menu(WindowType),
+    Horse { entity_id: i32, columns: i32 },
+}
+
+/// Container window currently shown to a player, kept in the connection's extensions.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct OpenWindow {
+    pub id: i32,
+    pub kind: WindowKind,
+}
+
+/// Last window id handed out, vanilla cycles through 1 to 100.
+#[derive(Debug, Default)]
+struct WindowCounter(i32);
+
+#[derive(Debug, Clone, Encode)]
+pub struct OpenScreen {
+    pub window_id: VarInt,
+    pub window_type: VarInt,
+    pub title: TextComponent,
+}
+
+impl ClientboundPacket for OpenScreen {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::OpenScreen;
+}
+
+#[derive(Debug, Clone, Encode)]
+pub struct CloseContainer {
+    pub window_id: u8,
+}
+
+impl ClientboundPacket for CloseContainer {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::CloseContainer;
+}
+
+/// Replaces all slots of a window, `state_id` is echoed back by the client when it clicks.
+#[derive(Debug, Clone, Encode)]
+pub struct SetContainerContent {
+    pub window_id: u8,
+    pub state_id: VarInt,
+    pub slots: Vec<ItemStack>,
+    pub carried_item: ItemStack,
+}
+
+impl ClientboundPacket for SetContainerContent {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::SetContainerContent;
+}
+
+/// Updates a menu specific value, e.g. the beacon level or enchantment costs.
+#[derive(Debug, Clone, Encode)]
+pub struct SetContainerProperty {
+    pub window_id: u8,
+    pub property: i16,
+    pub value: i16,
+}
+
+impl ClientboundPacket for SetContainerProperty {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::SetContainerProperty;
+}
+
+/// Hands out the next window id and records the window as the open one, closing the previous one.
+pub fn allocate_window(connection: &ConnectionHandle, kind: WindowKind) -> i32 {
+    if let Some(previous) = open_window_of(connection) {
+        close_container(connection, previous.id);
+    }
+    connection.extensions(|extensions| {
+        let counter = extensions.get_or_default::<WindowCounter>();
+        counter.0 = counter.0 % 100 + 1;
+        let window_id = counter.0;
+        extensions.insert(OpenWindow { id: window_id, kind });
+        window_id
+    })
+}
+
+/// Opens a window with empty slots of its own on the client, replacing the current one, and returns its id.
+pub fn open_window(connection: &ConnectionHandle, window_type: WindowType, title: TextComponent) -> anyhow::Result<i32> {
+    open_container(connection, window_type, title, Arc::new(Mutex::new(vec![ItemStack::EMPTY; window_type.slot_count()])))
+}
+
+/// Closes the window the client has open, if any.
+pub fn close_window(connection: &ConnectionHandle) -> anyhow::Result<()> {
+    if let Some(window) = connection.extensions(|extensions| extensions.remove::<OpenWindow>()) {
+        close_container(connection, window.id);
+        connection.send(&CloseContainer { window_id: window.id as u8 })?;
+    }
+    Ok(())
+}
+
+pub fn set_container_property(connection: &ConnectionHandle, property: i16, value: i16) -> anyhow::Result<()> {
+    let Some(window) = open_window_of(connection) else { anyhow::bail!("No window is open") };
+    connection.send(&SetContainerProperty { window_id: window.id as u8, property, value })
+}
+
+pub fn open_window_of(connection: &ConnectionHandle) -> Option<OpenWindow> {
+    connection.extensions(|extensions| extensions.get::<OpenWindow>().copied())
+}
+
+#[packet_processor(PacketType::CloseContainer)]
+pub(crate) fn close_container_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    let window_id = decode_from_slice::<u8>(&packet.payload)? as i32;
+    context.connection.extensions(|extensions| {
+        // Id 0 is the player inventory, which is never tracked here.
+        if extensions.get::<OpenWindow>().is_some_and(|window| window.id == window_id) {
+            extensions.remove::<OpenWindow>();
+        }
+    });
+    close_container(&context.connection, window_id);
+    Ok(())
+}
@@ -0,0 +1,142 @@
+use std::time::Duration;
+use dolls_core::datatype::{Encode, VarInt, VarLong};
+use dolls_world::prelude::{level, WorldBorder, MAX_BORDER_DISTANCE};
+use crate::prelude::{ClientboundPacket, ClientboundPacketType, ConnectionRegistry, PacketContext};
+
+/// The whole border state, sent when players join.
+#[derive(Debug, Clone, Encode)]
+pub struct InitializeWorldBorder {
+    pub x: f64,
+    pub z: f64,
+    pub old_diameter: f64,
+    pub new_diameter: f64,
+    /// Milliseconds until the new diameter is reached.
+    pub speed: VarLong,
+    pub portal_teleport_boundary: VarInt,
+    pub warning_blocks: VarInt,
+    pub warning_time: VarInt,
+}
+
+impl ClientboundPacket for InitializeWorldBorder {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::InitializeWorldBorder;
+}
+
+impl InitializeWorldBorder {
+    pub fn of(border: &WorldBorder) -> Self {
+        Self {
+            x: border.center_x,
+            z: border.center_z,
+            old_diameter: border.diameter(),
+            new_diameter: border.target_diameter(),
+            speed: VarLong(border.remaining().as_millis() as i64),
+            portal_teleport_boundary: VarInt(MAX_BORDER_DISTANCE),
+            warning_blocks: VarInt(border.warning_blocks),
+            warning_time: VarInt(border.warning_time),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Encode)]
+pub struct SetBorderCenter {
+    pub x: f64,
+    pub z: f64,
+}
+
+impl ClientboundPacket for SetBorderCenter {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::SetBorderCenter;
+}
+
+#[derive(Debug, Clone, Encode)]
+pub struct SetBorderLerpSize {
+    pub old_diameter: f64,
+    pub new_diameter: f64,
+    pub speed: VarLong,
+}
+
+impl ClientboundPacket for SetBorderLerpSize {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::SetBorderLerpSize;
+}
+
+#[derive(Debug, Clone, Encode)]
+pub struct SetBorderSize {
+    pub diameter: f64,
+}
+
+impl ClientboundPacket for SetBorderSize {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::SetBorderSize;
+}
+
+/// Seconds, see [`WorldBorder::warning_time`].
+#[derive(Debug, Clone, Encode)]
+pub struct SetBorderWarningDelay {
+    pub warning_time: VarInt,
+}
+
+impl ClientboundPacket for SetBorderWarningDelay {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::SetBorderWarningDelay;
+}
+
+#[derive(Debug, Clone, Encode)]
+pub struct SetBorderWarningDistance {
+    pub warning_blocks: VarInt,
+}
+
+impl ClientboundPacket for SetBorderWarningDistance {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::SetBorderWarningDistance;
+}
+
+/// Sends a joining player the border of the level.
+pub fn send_world_border(context: &mut PacketContext) -> anyhow::Result<()> {
+    let packet = InitializeWorldBorder::of(&level().read().unwrap().world_border);
+    context.send(&packet)
+}
+
+/// The border right now, between the old and the target size while resizing.
+pub fn world_border() -> WorldBorder {
+    level().read().unwrap().world_border.clone()
+}
+
+fn broadcast<T: ClientboundPacket>(connections: &ConnectionRegistry, packet: &T) -> anyhow::Result<()> {
+    for player in connections.players() {
+        player.send(packet)?;
+    }
+    Ok(())
+}
+
+/// Moves the border of the level and tells every player.
+pub fn set_border_center(connections: &ConnectionRegistry, x: f64, z: f64) -> anyhow::Result<()> {
+    let (x, z) = {
+        let border = &mut level().write().unwrap().world_border;
+        border.set_center(x, z);
+        (border.center_x, border.center_z)
+    };
+    broadcast(connections, &SetBorderCenter { x, z })
+}
+
+/// Resizes the border of the level to `diameter` over `duration`, right away for a zero duration.
+pub fn resize_border(connections: &ConnectionRegistry, diameter: f64, duration: Duration) -> anyhow::Result<()> {
+    let (old_diameter, new_diameter) = {
+        let border = &mut level().write().unwrap().world_border;
+        let old_diameter = border.diameter();
+        border.lerp_to(diameter, duration);
+        (old_diameter, border.target_diameter())
+    };
+    match duration.is_zero() {
+        true => broadcast(connections, &SetBorderSize { diameter: new_diameter }),
+        false => broadcast(connections, &SetBorderLerpSize {
+            old_diameter,
+            new_diameter,
+            speed: VarLong(duration.as_millis() as i64),
+        }),
+    }
+}
+
+pub fn set_border_warning_distance(connections: &ConnectionRegistry, blocks: i32) -> anyhow::Result<()> {
+    level().write().unwrap().world_border.warning_blocks = blocks;
+    broadcast(connections, &SetBorderWarningDistance { warning_blocks: VarInt(blocks) })
+}
+
+pub fn set_border_warning_delay(connections: &ConnectionRegistry, seconds: i32) -> anyhow::Result<()> {
+    level().write().unwrap().world_border.warning_time = seconds;
+    broadcast(connections, &SetBorderWarningDelay { warning_time: VarInt(seconds) })
+}
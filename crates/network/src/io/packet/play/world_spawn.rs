@@ -0,0 +1,65 @@
+use log::warn;
+use dolls_core::datatype::{BlockPos, Encode};
+use dolls_world::prelude::{level, Dimension};
+use crate::prelude::{ClientboundPacket, ClientboundPacketType, ConnectionRegistry, PacketContext, PlayerPosition};
+
+/// The world spawn, where compasses point and the client places players before their position is known.
+#[derive(Debug, Clone, Encode)]
+pub struct SetDefaultSpawnPosition {
+    pub location: BlockPos,
+    pub angle: f32,
+}
+
+impl ClientboundPacket for SetDefaultSpawnPosition {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::SetDefaultSpawnPosition;
+}
+
+impl SetDefaultSpawnPosition {
+    fn current() -> Self {
+        let level = level().read().unwrap();
+        Self { location: level.spawn(), angle: level.spawn_angle() }
+    }
+}
+
+/// Where players without a spawn point appear: on the highest block blocking motion in the column
+/// of the world spawn, or at the spawn itself if the column is empty.
+pub fn world_spawn_position() -> PlayerPosition {
+    let (spawn, angle) = {
+        let level = level().read().unwrap();
+        (level.spawn(), level.spawn_angle())
+    };
+    let surface = Dimension::Overworld.world().write().unwrap().surface_y(spawn.x, spawn.z).unwrap_or_else(|err| {
+        warn!("Failed to find the surface at the world spawn: {:#}", err);
+        None
+    });
+    let position = BlockPos::new(spawn.x, surface.unwrap_or(spawn.y), spawn.z);
+    PlayerPosition { yaw: angle, ..PlayerPosition::on_block(position) }
+}
+
+/// Picks the spawn of the level: `configured` if set, else the surface at the origin for worlds
+/// which have none yet. Chunks of the overworld must be readable.
+pub fn choose_world_spawn(configured: Option<[i32; 3]>) -> anyhow::Result<()> {
+    let position = match configured {
+        Some([x, y, z]) => BlockPos::new(x, y, z),
+        None if level().read().unwrap().has_spawn() => return Ok(()),
+        None => {
+            let surface = Dimension::Overworld.world().write().unwrap().surface_y(0, 0)?;
+            BlockPos::new(0, surface.unwrap_or(level().read().unwrap().spawn().y), 0)
+        }
+    };
+    let mut level = level().write().unwrap();
+    let angle = level.spawn_angle();
+    level.set_spawn(position, angle);
+    Ok(())
+}
+
+/// Sends a joining player the world spawn.
+pub(crate) fn send_world_spawn(context: &mut PacketContext) -> anyhow::Result<()> {
+    context.send(&SetDefaultSpawnPosition::current())
+}
+
+/// Moves the world spawn and tells every player.
+pub fn set_world_spawn(connections: &ConnectionRegistry, position: BlockPos, angle: f32) -> anyhow::Result<()> {
+    level().write().unwrap().set_spawn(position, angle);
+    connections.broadcast(&SetDefaultSpawnPosition::current())
+}
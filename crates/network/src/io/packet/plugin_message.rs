@@ -0,0 +1,171 @@
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, RwLock};
+use anyhow::bail;
+use once_cell::sync::Lazy;
+use log::debug;
+use dolls_core::datatype::{decode_from_slice, Decode, Encode, Identifier, RemainingBytes, DEFAULT_NAMESPACE};
+use dolls_macros::packet_processor;
+use crate::prelude::{ClientboundPacket, ClientboundPacketType, ConnectionHandle, ConnectionState, PacketContext, PacketType, RawPacket};
+
+/// Longest payload clients may send, as in vanilla.
+pub const MAX_SERVERBOUND_PLUGIN_MESSAGE: usize = 32767;
+/// Longest payload clients accept.
+pub const MAX_CLIENTBOUND_PLUGIN_MESSAGE: usize = 1 << 20;
+
+#[derive(Debug, Clone, Encode)]
+pub struct ConfigurationPluginMessage {
+    pub channel: Identifier,
+    pub data: RemainingBytes,
+}
+
+impl ClientboundPacket for ConfigurationPluginMessage {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::ConfigurationPluginMessage;
+}
+
+#[derive(Debug, Clone, Encode)]
+pub struct PlayPluginMessage {
+    pub channel: Identifier,
+    pub data: RemainingBytes,
+}
+
+impl ClientboundPacket for PlayPluginMessage {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::PlayPluginMessage;
+}
+
+/// The Plugin Message packet of `state`, only Configuration and Play have one.
+pub fn plugin_message_packet(state: ConnectionState, channel: Identifier, data: Vec<u8>) -> anyhow::Result<RawPacket> {
+    if data.len() > MAX_CLIENTBOUND_PLUGIN_MESSAGE {
+        bail!("Plugin message on {} has {} bytes, at most {} fit", channel, data.len(), MAX_CLIENTBOUND_PLUGIN_MESSAGE);
+    }
+    let data = RemainingBytes(data);
+    Ok(match state {
+        ConnectionState::Configuration => RawPacket::from_packet(&ConfigurationPluginMessage { channel, data })?,
+        ConnectionState::Play => RawPacket::from_packet(&PlayPluginMessage { channel, data })?,
+        state => bail!("No plugin messages in {:?}", state),
+    })
+}
+
+/// Sends a custom payload to a client in Configuration or Play.
+pub fn send_plugin_message(connection: &ConnectionHandle, channel: Identifier, data: Vec<u8>) -> anyhow::Result<()> {
+    connection.send_raw(plugin_message_packet(connection.state(), channel, data)?)
+}
+
+/// Handles the payloads clients send on a channel.
+pub trait PluginChannelHandler: Send + Sync {
+    /// Returning an error is logged, the connection stays open.
+    fn on_message(&self, context: &mut PacketContext, data: &[u8]) -> anyhow::Result<()>;
+}
+
+static PLUGIN_CHANNELS: Lazy<RwLock<HashMap<Identifier, Arc<dyn PluginChannelHandler>>>> = Lazy::new(|| {
+    let mut channels = HashMap::<Identifier, Arc<dyn PluginChannelHandler>>::new();
+    channels.insert(Identifier::minecraft("brand"), Arc::new(BrandChannel));
+    channels.insert(Identifier::minecraft("register"), Arc::new(RegisterChannel { register: true }));
+    channels.insert(Identifier::minecraft("unregister"), Arc::new(RegisterChannel { register: false }));
+    RwLock::new(channels)
+});
+
+/// Registers or replaces the handler of a channel, messages on channels without one are ignored.
+pub fn register_plugin_channel(channel: Identifier, handler: Arc<dyn PluginChannelHandler>) {
+    PLUGIN_CHANNELS.write().unwrap().insert(channel, handler);
+}
+
+pub fn unregister_plugin_channel(channel: &Identifier) {
+    PLUGIN_CHANNELS.write().unwrap().remove(channel);
+}
+
+/// Channels with a handler, the ones outside the `minecraft` namespace are announced to joining players.
+pub fn plugin_channels() -> Vec<Identifier> {
+    let mut channels = PLUGIN_CHANNELS.read().unwrap().keys().cloned().collect::<Vec<_>>();
+    channels.sort();
+    channels
+}
+
+/// The client's brand, e.g. `vanilla` or `fabric`, once it sent it.
+#[derive(Debug, Clone)]
+struct ClientBrand(String);
+
+pub fn client_brand(connection: &ConnectionHandle) -> Option<String> {
+    connection.extensions(|extensions| extensions.get::<ClientBrand>().map(|brand| brand.0.clone()))
+}
+
+/// Channels the client listens on, from its `minecraft:register` messages.
+#[derive(Debug, Default)]
+struct ClientChannels(BTreeSet<Identifier>);
+
+pub fn client_channels(connection: &ConnectionHandle) -> Vec<Identifier> {
+    connection.extensions(|extensions| extensions.get::<ClientChannels>().map(|channels| channels.0.iter().cloned().collect()).unwrap_or_default())
+}
+
+struct BrandChannel;
+
+impl PluginChannelHandler for BrandChannel {
+    fn on_message(&self, context: &mut PacketContext, data: &[u8]) -> anyhow::Result<()> {
+        let brand: String = decode_from_slice(data)?;
+        debug!("{:?} plays on {}", context.username, brand);
+        context.connection.extensions(|extensions| extensions.insert(ClientBrand(brand)));
+        Ok(())
+    }
+}
+
+/// `minecraft:register` and `minecraft:unregister`, zero-separated channel names.
+struct RegisterChannel {
+    register: bool,
+}
+
+impl PluginChannelHandler for RegisterChannel {
+    fn on_message(&self, context: &mut PacketContext, data: &[u8]) -> anyhow::Result<()> {
+        let names = String::from_utf8_lossy(data);
+        let channels = names.split('\0').filter_map(|name| name.parse::<Identifier>().ok()).collect::<Vec<_>>();
+        context.connection.extensions(|extensions| {
+            let known = &mut extensions.get_or_default::<ClientChannels>().0;
+            for channel in channels {
+                match self.register {
+                    true => known.insert(channel),
+                    false => known.remove(&channel),
+                };
+            }
+        });
+        Ok(())
+    }
+}
+
+/// Tells a player entering Play the server's brand and the channels it listens on.
+pub(crate) fn send_brand(context: &mut PacketContext) -> anyhow::Result<()> {
+    let mut brand = Vec::new();
+    context.config.server.brand.encode(&mut brand)?;
+    context.send_raw(plugin_message_packet(context.state, Identifier::minecraft("brand"), brand)?);
+    let channels = plugin_channels().iter()
+        .filter(|channel| channel.namespace() != DEFAULT_NAMESPACE)
+        .map(Identifier::to_string)
+        .collect::<Vec<_>>();
+    if !channels.is_empty() {
+        context.send_raw(plugin_message_packet(context.state, Identifier::minecraft("register"), channels.join("\0").into_bytes())?);
+    }
+    Ok(())
+}
+
+fn handle_plugin_message(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    let mut payload = packet.payload.as_slice();
+    let channel = Identifier::decode(&mut payload)?;
+    if payload.len() > MAX_SERVERBOUND_PLUGIN_MESSAGE {
+        bail!("Plugin message on {} has {} bytes, at most {} are allowed", channel, payload.len(), MAX_SERVERBOUND_PLUGIN_MESSAGE);
+    }
+    let Some(handler) = PLUGIN_CHANNELS.read().unwrap().get(&channel).cloned() else {
+        debug!("Plugin message on unknown channel {} from {} ({} bytes)", channel, context.peer_addr, payload.len());
+        return Ok(());
+    };
+    if let Err(err) = handler.on_message(context, payload) {
+        debug!("Plugin message on {} from {} failed: {:#}", channel, context.peer_addr, err);
+    }
+    Ok(())
+}
+
+#[packet_processor(PacketType::ConfigurationPluginMessage)]
+pub(crate) fn configuration_plugin_message_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    handle_plugin_message(context, packet)
+}
+
+#[packet_processor(PacketType::PluginMessage)]
+pub(crate) fn play_plugin_message_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    handle_plugin_message(context, packet)
+}
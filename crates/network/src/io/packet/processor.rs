@@ -1,18 +1,19 @@
 use async_std::sync::RwLock;
 use once_cell::sync::Lazy;
 use std::collections::HashMap;
-use crate::prelude::{PacketType, RawPacket};
+use crate::prelude::{ConnectionState, PacketContext, PacketType, RawPacket};
 
-pub type PacketProcessorFn = fn(RawPacket) -> anyhow::Result<()>;
+pub type PacketProcessorFn = fn(&mut PacketContext, RawPacket) -> anyhow::Result<()>;
 
-static HANDLERS: Lazy<RwLock<HashMap<u32, PacketProcessorFn>>> = Lazy::new(|| RwLock::new(HashMap::new()));
+#[cfg_attr(feature = "static-dispatch", allow(dead_code))]
+static HANDLERS: Lazy<RwLock<HashMap<(ConnectionState, u32), PacketProcessorFn>>> = Lazy::new(|| RwLock::new(HashMap::new()));
 
 #[macro_export]
 macro_rules! register_packet_processor {
-    ($packet_id:expr, $handler:expr) => {
+    ($packet_type:expr, $handler:expr) => {
         inventory::submit! {
-            PacketProcessorRegistration {
-                packet_id: $packet_id,
+            $crate::prelude::PacketProcessorRegistration {
+                packet_type: $packet_type,
                 processor: $handler,
             }
         }
@@ -20,20 +21,46 @@ macro_rules! register_packet_processor {
 }
 
 pub struct PacketProcessorRegistration {
-    pub packet_id: PacketType,
+    pub packet_type: PacketType,
     pub processor: PacketProcessorFn,
 }
 
 inventory::collect!(PacketProcessorRegistration);
 
-pub async fn init_packet_processors() {
+#[cfg(not(feature = "static-dispatch"))]
+pub async fn init_packet_processors() -> anyhow::Result<()> {
     if HANDLERS.read().await.is_empty() {
         for registration in inventory::iter::<PacketProcessorRegistration> {
-            HANDLERS.write().await.insert(registration.packet_id as u32, registration.processor);
+            let packet_type = registration.packet_type;
+            HANDLERS.write().await.insert((packet_type.state(), packet_type.id()), registration.processor);
         }
     }
+    Ok(())
 }
 
-pub async fn get_handler(packet_id: u32) -> Option<PacketProcessorFn> {
-    HANDLERS.read().await.get(&packet_id).cloned()
+/// With static dispatch the build script generates the table from the `#[packet_processor]`
+/// attributes, so only check that every registered processor is reachable through it, e.g. one
+/// registered with `register_packet_processor!` directly. A missing entry is an error, the server
+/// would otherwise drop those packets without a word.
+#[cfg(feature = "static-dispatch")]
+pub async fn init_packet_processors() -> anyhow::Result<()> {
+    let missing = inventory::iter::<PacketProcessorRegistration>
+        .into_iter()
+        .map(|registration| registration.packet_type)
+        .filter(|packet_type| crate::io::dispatch_packet(packet_type.state(), packet_type.id()).is_none())
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        anyhow::bail!("Processors for {:?} are missing from the static dispatch table", missing);
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "static-dispatch"))]
+pub async fn get_handler(state: ConnectionState, packet_id: u32) -> Option<PacketProcessorFn> {
+    HANDLERS.read().await.get(&(state, packet_id)).cloned()
+}
+
+#[cfg(feature = "static-dispatch")]
+pub async fn get_handler(state: ConnectionState, packet_id: u32) -> Option<PacketProcessorFn> {
+    crate::io::dispatch_packet(state, packet_id)
 }
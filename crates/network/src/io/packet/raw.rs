@@ -1,12 +1,249 @@
+use dolls_core::datatype::{Encode, VarInt};
+use crate::prelude::ConnectionState;
 
-#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq)]
-pub enum PacketType {
-    Handshake = 0x00,
+/// Declares a packet type enum with its packets grouped by connection state.
+macro_rules! packet_types {
+    ($(#[$meta:meta])* $enum_name:ident { $($state:ident { $($name:ident = $id:literal),* $(,)? })* }) => {
+        $(#[$meta])*
+        #[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+        pub enum $enum_name {
+            $($($name,)*)*
+        }
+
+        impl $enum_name {
+            pub const fn state(self) -> ConnectionState {
+                match self {
+                    $($($enum_name::$name => ConnectionState::$state,)*)*
+                }
+            }
+
+            pub const fn id(self) -> u32 {
+                match self {
+                    $($($enum_name::$name => $id,)*)*
+                }
+            }
+
+            pub const fn name(self) -> &'static str {
+                match self {
+                    $($($enum_name::$name => stringify!($name),)*)*
+                }
+            }
+
+            pub const fn from_parts(state: ConnectionState, packet_id: u32) -> Option<Self> {
+                match (state, packet_id) {
+                    $($((ConnectionState::$state, $id) => Some($enum_name::$name),)*)*
+                    _ => None,
+                }
+            }
+
+            /// The packet called `name`, see [`name`](Self::name).
+            pub fn from_name(name: &str) -> Option<Self> {
+                match name {
+                    $($(stringify!($name) => Some($enum_name::$name),)*)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+packet_types! {
+    /// Packets sent by the client.
+    PacketType {
+        Handshaking {
+            Handshake = 0x00,
+        }
+        Status {
+            StatusRequest = 0x00,
+            PingRequest = 0x01,
+        }
+        Login {
+            LoginStart = 0x00,
+            LoginPluginResponse = 0x02,
+            LoginAcknowledged = 0x03,
+            LoginCookieResponse = 0x04,
+        }
+        Configuration {
+            ConfigurationClientInformation = 0x00,
+            ConfigurationCookieResponse = 0x01,
+            ConfigurationPluginMessage = 0x02,
+            AcknowledgeFinishConfiguration = 0x03,
+            ConfigurationResourcePackResponse = 0x06,
+            ServerboundKnownPacks = 0x07,
+        }
+        Play {
+            ConfirmTeleportation = 0x00,
+            ChangeDifficulty = 0x02,
+            MessageAcknowledgment = 0x03,
+            ChatCommand = 0x04,
+            SignedChatCommand = 0x05,
+            ChatMessage = 0x06,
+            PlayerSession = 0x07,
+            ChunkBatchReceived = 0x08,
+            ClientStatus = 0x09,
+            ClientInformation = 0x0A,
+            CommandSuggestionsRequest = 0x0B,
+            ClickContainerButton = 0x0D,
+            ClickContainer = 0x0E,
+            CloseContainer = 0x0F,
+            CookieResponse = 0x11,
+            PluginMessage = 0x12,
+            KeepAlive = 0x18,
+            LockDifficulty = 0x19,
+            SetPlayerPosition = 0x1A,
+            SetPlayerPositionAndRotation = 0x1B,
+            SetPlayerRotation = 0x1C,
+            SetPlayerOnGround = 0x1D,
+            PlaceRecipe = 0x22,
+            PlayerAbilities = 0x23,
+            PlayerAction = 0x24,
+            PlayerInput = 0x26,
+            ChangeRecipeBookSettings = 0x28,
+            SetSeenRecipe = 0x29,
+            RenameItem = 0x2A,
+            ResourcePackResponse = 0x2B,
+            SeenAdvancements = 0x2C,
+            SelectTrade = 0x2D,
+            SetBeaconEffect = 0x2E,
+            SetHeldItem = 0x2F,
+            SetCreativeModeSlot = 0x32,
+            UseItemOn = 0x38,
+        }
+    }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+packet_types! {
+    /// Packets sent by the server.
+    ClientboundPacketType {
+        Status {
+            StatusResponse = 0x00,
+            PongResponse = 0x01,
+        }
+        Login {
+            LoginDisconnect = 0x00,
+            LoginSuccess = 0x02,
+            SetCompression = 0x03,
+            LoginPluginRequest = 0x04,
+            LoginCookieRequest = 0x05,
+        }
+        Configuration {
+            ConfigurationCookieRequest = 0x00,
+            ConfigurationPluginMessage = 0x01,
+            ConfigurationDisconnect = 0x02,
+            FinishConfiguration = 0x03,
+            RegistryData = 0x07,
+            ConfigurationRemoveResourcePack = 0x08,
+            ConfigurationAddResourcePack = 0x09,
+            ConfigurationStoreCookie = 0x0A,
+            ConfigurationTransfer = 0x0B,
+            ClientboundKnownPacks = 0x0E,
+        }
+        Play {
+            SpawnEntity = 0x01,
+            AwardStatistics = 0x04,
+            AcknowledgeBlockChange = 0x05,
+            BlockUpdate = 0x09,
+            ChangeDifficulty = 0x0B,
+            ChunkBatchFinished = 0x0C,
+            ChunkBatchStart = 0x0D,
+            ClearTitles = 0x0F,
+            CommandSuggestionsResponse = 0x10,
+            Commands = 0x11,
+            CloseContainer = 0x12,
+            SetContainerContent = 0x13,
+            SetContainerProperty = 0x14,
+            SetContainerSlot = 0x15,
+            CookieRequest = 0x16,
+            PlayPluginMessage = 0x19,
+            DamageEvent = 0x1A,
+            PlayDisconnect = 0x1D,
+            DisguisedChatMessage = 0x1E,
+            EntityEvent = 0x1F,
+            UnloadChunk = 0x21,
+            GameEvent = 0x22,
+            OpenHorseScreen = 0x23,
+            InitializeWorldBorder = 0x25,
+            KeepAlive = 0x26,
+            ChunkDataAndUpdateLight = 0x27,
+            Particle = 0x29,
+            UpdateLight = 0x2A,
+            Login = 0x2B,
+            MerchantOffers = 0x2D,
+            UpdateEntityPosition = 0x2E,
+            UpdateEntityPositionAndRotation = 0x2F,
+            UpdateEntityRotation = 0x30,
+            OpenScreen = 0x33,
+            PlaceGhostRecipe = 0x37,
+            PlayerAbilities = 0x38,
+            PlayerChatMessage = 0x39,
+            CombatDeath = 0x3C,
+            PlayerInfoRemove = 0x3D,
+            PlayerInfoUpdate = 0x3E,
+            SynchronizePlayerPosition = 0x40,
+            UpdateRecipeBook = 0x41,
+            RemoveEntities = 0x42,
+            ResetScore = 0x44,
+            RemoveResourcePack = 0x45,
+            AddResourcePack = 0x46,
+            Respawn = 0x47,
+            SetHeadRotation = 0x48,
+            SelectAdvancementsTab = 0x4A,
+            SetActionBarText = 0x4C,
+            SetBorderCenter = 0x4D,
+            SetBorderLerpSize = 0x4E,
+            SetBorderSize = 0x4F,
+            SetBorderWarningDelay = 0x50,
+            SetBorderWarningDistance = 0x51,
+            SetCamera = 0x52,
+            SetCenterChunk = 0x54,
+            SetDefaultSpawnPosition = 0x56,
+            DisplayObjective = 0x57,
+            SetEntityMetadata = 0x58,
+            SetEntityVelocity = 0x5A,
+            SetHealth = 0x5D,
+            UpdateObjectives = 0x5E,
+            UpdateTeams = 0x60,
+            UpdateScore = 0x61,
+            SetSubtitleText = 0x63,
+            UpdateTime = 0x64,
+            SetTitleText = 0x65,
+            SetTitleAnimationTimes = 0x66,
+            EntitySoundEffect = 0x67,
+            SoundEffect = 0x68,
+            StoreCookie = 0x6B,
+            SystemChatMessage = 0x6C,
+            TeleportEntity = 0x70,
+            Transfer = 0x73,
+            UpdateAdvancements = 0x74,
+            UpdateRecipes = 0x77,
+        }
+    }
+}
+
+/// A typed clientbound packet which can be turned into a [`RawPacket`].
+pub trait ClientboundPacket: Encode {
+    const PACKET_TYPE: ClientboundPacketType;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct RawPacket {
     pub size_in_bytes: u32,
     pub packet_id: u32,
     pub payload: Vec<u8>,
 }
+
+impl RawPacket {
+    pub fn new(packet_id: u32, payload: Vec<u8>) -> Self {
+        Self {
+            size_in_bytes: (VarInt(packet_id as i32).written_size() + payload.len()) as u32,
+            packet_id,
+            payload,
+        }
+    }
+
+    pub fn from_packet<T: ClientboundPacket>(packet: &T) -> std::io::Result<Self> {
+        let mut payload = Vec::new();
+        packet.encode(&mut payload)?;
+        Ok(Self::new(T::PACKET_TYPE.id(), payload))
+    }
+}
@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use anyhow::{anyhow, bail};
+use once_cell::sync::Lazy;
+use log::{debug, error, info};
+use dolls_config::ResourcePackConfig;
+use dolls_core::datatype::{Decode, Encode, Uuid, VarInt};
+use dolls_core::text::TextComponent;
+use dolls_macros::packet_processor;
+use crate::prelude::{ClientboundPacket, ClientboundPacketType, ConnectionHandle, ConnectionState, FinishConfiguration, PacketContext, PacketType, RawPacket};
+
+/// Longest URL clients accept.
+pub const MAX_RESOURCE_PACK_URL: usize = 32767;
+
+/// A resource pack for clients to download and apply.
+#[derive(Debug, Clone, PartialEq, Encode)]
+pub struct ResourcePack {
+    pub id: Uuid,
+    pub url: String,
+    /// SHA-1 of the zip in lowercase hex, empty if unknown.
+    pub hash: String,
+    /// Clients cannot join without it, players declining it are disconnected.
+    pub forced: bool,
+    pub prompt: Option<TextComponent>,
+}
+
+impl ResourcePack {
+    /// A pack identified by its URL, the way vanilla identifies the pack of `server.properties`.
+    pub fn new(url: impl Into<String>, hash: impl Into<String>) -> Self {
+        let url = url.into();
+        let id = uuid::Builder::from_md5_bytes(md5::compute(url.as_bytes()).0).into_uuid();
+        Self { id, url, hash: hash.into(), forced: false, prompt: None }
+    }
+
+    pub fn forced(mut self, forced: bool) -> Self {
+        self.forced = forced;
+        self
+    }
+
+    pub fn prompt(mut self, prompt: TextComponent) -> Self {
+        self.prompt = Some(prompt);
+        self
+    }
+
+    /// The pack of the `[resource-pack]` section, `None` when it has no URL.
+    pub fn from_config(config: &ResourcePackConfig) -> anyhow::Result<Option<Self>> {
+        if config.url.is_empty() {
+            return Ok(None);
+        }
+        if config.url.len() > MAX_RESOURCE_PACK_URL {
+            bail!("Resource pack URL is longer than {} characters", MAX_RESOURCE_PACK_URL);
+        }
+        if !config.sha1.is_empty() && (config.sha1.len() != 40 || !config.sha1.chars().all(|c| c.is_ascii_hexdigit())) {
+            bail!("Resource pack SHA-1 {:?} is not 40 hexadecimal digits", config.sha1);
+        }
+        let mut pack = Self::new(config.url.clone(), config.sha1.to_ascii_lowercase()).forced(config.required);
+        if !config.id.is_empty() {
+            pack.id = config.id.parse().map_err(|err| anyhow!("Invalid resource pack id {:?}: {}", config.id, err))?;
+        }
+        if !config.prompt.is_empty() {
+            pack = pack.prompt(TextComponent::from_legacy(&config.prompt));
+        }
+        Ok(Some(pack))
+    }
+}
+
+#[derive(Debug, Clone, Encode)]
+pub struct ConfigurationAddResourcePack {
+    pub pack: ResourcePack,
+}
+
+impl ClientboundPacket for ConfigurationAddResourcePack {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::ConfigurationAddResourcePack;
+}
+
+#[derive(Debug, Clone, Encode)]
+pub struct AddResourcePack {
+    pub pack: ResourcePack,
+}
+
+impl ClientboundPacket for AddResourcePack {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::AddResourcePack;
+}
+
+/// Removes one pack, or all of them when `id` is `None`.
+#[derive(Debug, Clone, Encode)]
+pub struct ConfigurationRemoveResourcePack {
+    pub id: Option<Uuid>,
+}
+
+impl ClientboundPacket for ConfigurationRemoveResourcePack {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::ConfigurationRemoveResourcePack;
+}
+
+#[derive(Debug, Clone, Encode)]
+pub struct RemoveResourcePack {
+    pub id: Option<Uuid>,
+}
+
+impl ClientboundPacket for RemoveResourcePack {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::RemoveResourcePack;
+}
+
+/// What the client reports about a pack, several times while it downloads and applies it.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ResourcePackStatus {
+    SuccessfullyLoaded,
+    Declined,
+    FailedDownload,
+    Accepted,
+    Downloaded,
+    InvalidUrl,
+    FailedReload,
+    Discarded,
+}
+
+impl ResourcePackStatus {
+    pub fn from_id(id: i32) -> Option<Self> {
+        Some(match id {
+            0 => Self::SuccessfullyLoaded,
+            1 => Self::Declined,
+            2 => Self::FailedDownload,
+            3 => Self::Accepted,
+            4 => Self::Downloaded,
+            5 => Self::InvalidUrl,
+            6 => Self::FailedReload,
+            7 => Self::Discarded,
+            _ => return None,
+        })
+    }
+
+    /// Whether the client is done with the pack, either way.
+    pub fn is_terminal(self) -> bool {
+        !matches!(self, Self::Accepted | Self::Downloaded)
+    }
+}
+
+/// Notified of every Resource Pack Response, e.g. to give players a pack only once it is applied.
+pub trait ResourcePackListener: Send + Sync {
+    /// Returning an error is logged, it does not stop the other listeners.
+    fn on_response(&self, context: &mut PacketContext, id: Uuid, status: ResourcePackStatus) -> anyhow::Result<()>;
+}
+
+static RESOURCE_PACK_LISTENERS: Lazy<RwLock<Vec<Arc<dyn ResourcePackListener>>>> = Lazy::new(|| RwLock::new(Vec::new()));
+
+/// Registers a listener run for every Resource Pack Response, in registration order.
+pub fn register_resource_pack_listener(listener: Arc<dyn ResourcePackListener>) {
+    RESOURCE_PACK_LISTENERS.write().unwrap().push(listener);
+}
+
+/// Packs sent to a connection and what the client last reported about them.
+#[derive(Debug, Default)]
+struct ResourcePacks {
+    packs: HashMap<Uuid, (bool, Option<ResourcePackStatus>)>,
+    /// The configured pack Configuration waits for before it finishes.
+    awaited: Option<Uuid>,
+}
+
+/// The Add Resource Pack packet of `state`, only Configuration and Play have one.
+pub fn add_resource_pack_packet(state: ConnectionState, pack: ResourcePack) -> anyhow::Result<RawPacket> {
+    Ok(match state {
+        ConnectionState::Configuration => RawPacket::from_packet(&ConfigurationAddResourcePack { pack })?,
+        ConnectionState::Play => RawPacket::from_packet(&AddResourcePack { pack })?,
+        state => bail!("No resource packs in {:?}", state),
+    })
+}
+
+/// The Remove Resource Pack packet of `state`, `None` removes every pack.
+pub fn remove_resource_pack_packet(state: ConnectionState, id: Option<Uuid>) -> anyhow::Result<RawPacket> {
+    Ok(match state {
+        ConnectionState::Configuration => RawPacket::from_packet(&ConfigurationRemoveResourcePack { id })?,
+        ConnectionState::Play => RawPacket::from_packet(&RemoveResourcePack { id })?,
+        state => bail!("No resource packs in {:?}", state),
+    })
+}
+
+/// Offers a pack to a client in Configuration or Play, its answers are tracked in [`resource_pack_status`].
+pub fn push_resource_pack(connection: &ConnectionHandle, pack: ResourcePack) -> anyhow::Result<()> {
+    track_pack(connection, &pack);
+    connection.send_raw(add_resource_pack_packet(connection.state(), pack)?)
+}
+
+/// Makes the client drop a pack, or all of them when `id` is `None`.
+pub fn pop_resource_pack(connection: &ConnectionHandle, id: Option<Uuid>) -> anyhow::Result<()> {
+    connection.extensions(|extensions| {
+        let packs = &mut extensions.get_or_default::<ResourcePacks>().packs;
+        match id {
+            Some(id) => drop(packs.remove(&id)),
+            None => packs.clear(),
+        }
+    });
+    connection.send_raw(remove_resource_pack_packet(connection.state(), id)?)
+}
+
+/// What the client last reported about a pack it was sent, `None` before its first answer.
+pub fn resource_pack_status(connection: &ConnectionHandle, id: Uuid) -> Option<ResourcePackStatus> {
+    connection.extensions(|extensions| extensions.get::<ResourcePacks>().and_then(|packs| packs.packs.get(&id)?.1))
+}
+
+fn track_pack(connection: &ConnectionHandle, pack: &ResourcePack) {
+    connection.extensions(|extensions| extensions.get_or_default::<ResourcePacks>().packs.insert(pack.id, (pack.forced, None)));
+}
+
+/// Offers the configured pack during Configuration, returns whether the client's answer has to be
+/// awaited before Configuration finishes.
+pub(crate) fn send_configured_resource_pack(context: &mut PacketContext) -> anyhow::Result<bool> {
+    let Some(pack) = ResourcePack::from_config(&context.config.resource_pack)? else { return Ok(false) };
+    track_pack(&context.connection, &pack);
+    context.connection.extensions(|extensions| extensions.get_or_default::<ResourcePacks>().awaited = Some(pack.id));
+    context.send(&ConfigurationAddResourcePack { pack })?;
+    Ok(true)
+}
+
+/// Disconnect reason of players declining a required pack.
+pub fn required_resource_pack_reason() -> TextComponent {
+    TextComponent::translatable("multiplayer.requiredTexturePrompt.disconnect", vec![])
+        .fallback("Server requires a custom resource pack")
+}
+
+fn handle_resource_pack_response(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    let mut payload = packet.payload.as_slice();
+    let id = Uuid::decode(&mut payload)?;
+    let result = VarInt::decode(&mut payload)?.0;
+    let status = ResourcePackStatus::from_id(result).ok_or_else(|| anyhow!("Unknown resource pack result {}", result))?;
+    debug!("Resource pack {} of {:?}: {:?}", id, context.username, status);
+
+    let (forced, finishes_configuration) = context.connection.extensions(|extensions| {
+        let packs = extensions.get_or_default::<ResourcePacks>();
+        let forced = match packs.packs.get_mut(&id) {
+            Some((forced, last)) => {
+                *last = Some(status);
+                *forced
+            }
+            None => false,
+        };
+        let finishes_configuration = status.is_terminal() && packs.awaited == Some(id);
+        if finishes_configuration {
+            packs.awaited = None;
+        }
+        (forced, finishes_configuration)
+    });
+
+    let listeners = RESOURCE_PACK_LISTENERS.read().unwrap().clone();
+    for listener in listeners {
+        if let Err(err) = listener.on_response(context, id, status) {
+            error!("Resource pack listener failed for {:?}: {}", context.username, err);
+        }
+    }
+
+    if forced && status == ResourcePackStatus::Declined {
+        info!("{} rejected the required resource pack", context.username.as_deref().unwrap_or("?"));
+        return context.disconnect(required_resource_pack_reason());
+    }
+    if finishes_configuration && context.state == ConnectionState::Configuration {
+        context.send(&FinishConfiguration)?;
+    }
+    Ok(())
+}
+
+#[packet_processor(PacketType::ConfigurationResourcePackResponse)]
+pub(crate) fn configuration_resource_pack_response_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    handle_resource_pack_response(context, packet)
+}
+
+#[packet_processor(PacketType::ResourcePackResponse)]
+pub(crate) fn resource_pack_response_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    handle_resource_pack_response(context, packet)
+}
@@ -0,0 +1,10 @@
+/// Protocol state of a connection, which decides how packet ids are interpreted.
+#[derive(Debug, Default, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub enum ConnectionState {
+    #[default]
+    Handshaking,
+    Status,
+    Login,
+    Configuration,
+    Play,
+}
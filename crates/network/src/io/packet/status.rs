@@ -0,0 +1,304 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::net::{IpAddr, Ipv6Addr};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock, Weak};
+use std::time::{Duration, Instant};
+use anyhow::{bail, Context};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use once_cell::sync::Lazy;
+use serde_json::json;
+use dolls_config::ServerConfig;
+use dolls_core::datatype::{decode_from_slice, Decode, Encode, Uuid};
+use dolls_core::registry::CORE_PACK_VERSION;
+use dolls_core::text::TextComponent;
+use dolls_macros::packet_processor;
+use crate::prelude::{placeholders, ClientboundPacket, ClientboundPacketType, ConnectionRegistry, PacketContext, PacketType, PlaceholderContext, RawPacket};
+
+/// Protocol version of Minecraft 1.21.1, the only one Dolls speaks.
+pub const PROTOCOL_VERSION: i32 = 767;
+/// Server list icon in the working directory, where vanilla looks for it.
+pub const SERVER_ICON_FILE: &str = "server-icon.png";
+/// Width and height clients expect the icon to have.
+const SERVER_ICON_SIZE: u32 = 64;
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+/// Most players listed when hovering the player count, as in vanilla.
+pub const STATUS_SAMPLE_SIZE: usize = 12;
+const ANONYMOUS_PLAYER_NAME: &str = "Anonymous Player";
+/// How long a built status is reused while the players stay the same, so that placeholders and
+/// status hooks still refresh now and then.
+const STATUS_CACHE_LIFETIME: Duration = Duration::from_secs(5);
+/// Window `status-rate-limit` counts the pings of an address in.
+const STATUS_RATE_WINDOW: Duration = Duration::from_secs(60);
+/// Addresses whose pings are counted at most, beyond that the one counting the longest is forgotten.
+pub const MAX_TRACKED_PINGERS: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq, Encode, Decode)]
+pub struct StatusResponse {
+    pub json: String,
+}
+
+impl ClientboundPacket for StatusResponse {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::StatusResponse;
+}
+
+#[derive(Debug, Clone, PartialEq, Encode, Decode)]
+pub struct PingPong {
+    pub payload: i64,
+}
+
+impl ClientboundPacket for PingPong {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::PongResponse;
+}
+
+static FAVICON: Lazy<RwLock<Option<String>>> = Lazy::new(|| RwLock::new(None));
+
+/// Icon shown in the server list as a `data:image/png;base64,` URI, loaded from `server-icon.png`
+/// at startup.
+pub fn favicon() -> &'static RwLock<Option<String>> {
+    &FAVICON
+}
+
+/// Reads a server list icon, `None` if the file does not exist. Like vanilla, only 64x64 PNGs are accepted.
+pub fn load_favicon(path: impl AsRef<Path>) -> anyhow::Result<Option<String>> {
+    let path = path.as_ref();
+    if !path.exists() {
+        return Ok(None);
+    }
+    let image = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    // The IHDR chunk always comes first, its width and height follow the signature and chunk header.
+    if !image.starts_with(PNG_SIGNATURE) || image.len() < 24 || &image[12..16] != b"IHDR" {
+        bail!("{} is not a PNG image", path.display());
+    }
+    let width = u32::from_be_bytes(image[16..20].try_into()?);
+    let height = u32::from_be_bytes(image[20..24].try_into()?);
+    if width != SERVER_ICON_SIZE || height != SERVER_ICON_SIZE {
+        bail!("{} is {}x{} pixels, it must be {}x{}", path.display(), width, height, SERVER_ICON_SIZE, SERVER_ICON_SIZE);
+    }
+    Ok(Some(format!("data:image/png;base64,{}", STANDARD.encode(image))))
+}
+
+/// The configured MOTD: a JSON text component, or text with legacy `§` or `&` formatting codes.
+pub fn motd_component(motd: &str) -> TextComponent {
+    let trimmed = motd.trim_start();
+    match trimmed.starts_with(['{', '[', '"']) {
+        true => TextComponent::from_json(trimmed).unwrap_or_else(|_| TextComponent::from_legacy(motd)),
+        false => TextComponent::from_legacy(motd),
+    }
+}
+
+/// A player listed when hovering the player count.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SamplePlayer {
+    pub name: String,
+    pub uuid: Uuid,
+}
+
+impl SamplePlayer {
+    /// How players who turned off server listings in their settings appear, as in vanilla.
+    pub fn anonymous() -> Self {
+        Self { name: ANONYMOUS_PLAYER_NAME.to_string(), uuid: Uuid::nil() }
+    }
+}
+
+/// What the server list shows, status hooks may change it before it is sent.
+#[derive(Debug, Clone)]
+pub struct ServerStatus {
+    pub version_name: String,
+    pub protocol: i32,
+    pub max_players: u32,
+    pub online_players: usize,
+    pub sample: Vec<SamplePlayer>,
+    pub description: TextComponent,
+    /// `data:image/png;base64,` URI of the icon.
+    pub favicon: Option<String>,
+    pub enforces_secure_chat: bool,
+}
+
+impl ServerStatus {
+    pub fn to_json(&self) -> String {
+        let sample = self.sample.iter()
+            .map(|player| json!({ "name": player.name, "id": player.uuid.hyphenated().to_string() }))
+            .collect::<Vec<_>>();
+        let mut status = json!({
+            "version": { "name": self.version_name, "protocol": self.protocol },
+            "players": { "max": self.max_players, "online": self.online_players, "sample": sample },
+            "description": self.description,
+            "enforcesSecureChat": self.enforces_secure_chat,
+        });
+        if let Some(favicon) = &self.favicon {
+            status["favicon"] = json!(favicon);
+        }
+        status.to_string()
+    }
+}
+
+/// Changes the status before it is sent, e.g. to report another player count.
+pub type StatusHook = Arc<dyn Fn(&mut ServerStatus) + Send + Sync>;
+
+static STATUS_HOOKS: Lazy<RwLock<Vec<StatusHook>>> = Lazy::new(|| RwLock::new(Vec::new()));
+
+/// Adds a hook run on every status built, in registration order.
+pub fn register_status_hook(hook: impl Fn(&mut ServerStatus) + Send + Sync + 'static) {
+    STATUS_HOOKS.write().unwrap().push(Arc::new(hook));
+    invalidate_status();
+}
+
+/// The server list entry: version, player count and sample, MOTD and icon, as the status hooks leave it.
+pub fn server_status(config: &ServerConfig, connections: &ConnectionRegistry) -> ServerStatus {
+    let server = &config.server;
+    let players = connections.players();
+    let placeholder_context = PlaceholderContext { player: None, connections, config };
+    let sample = if server.hide_online_players {
+        Vec::new()
+    } else if !server.status_sample.is_empty() {
+        // Lines of text rather than players, clients only show the names.
+        server.status_sample.iter()
+            .map(|line| SamplePlayer { name: placeholders().apply(line, &placeholder_context), uuid: Uuid::nil() })
+            .collect()
+    } else {
+        players.iter()
+            .take(STATUS_SAMPLE_SIZE)
+            .filter_map(|player| {
+                let info = player.info();
+                match info.client_information.is_some_and(|information| !information.allow_server_listings) {
+                    true => Some(SamplePlayer::anonymous()),
+                    false => Some(SamplePlayer { name: info.username?, uuid: info.uuid? }),
+                }
+            })
+            .collect()
+    };
+    let mut status = ServerStatus {
+        version_name: CORE_PACK_VERSION.to_string(),
+        protocol: PROTOCOL_VERSION,
+        max_players: server.max_players,
+        online_players: players.len(),
+        sample,
+        description: placeholders().apply_component(&motd_component(&server.motd), &placeholder_context),
+        favicon: favicon().read().unwrap().clone(),
+        enforces_secure_chat: server.enforce_secure_profile,
+    };
+    let hooks = STATUS_HOOKS.read().unwrap().clone();
+    for hook in hooks {
+        hook(&mut status);
+    }
+    status
+}
+
+/// A status JSON and what it was built from.
+struct CachedStatus {
+    config: Weak<ServerConfig>,
+    connections: Weak<ConnectionRegistry>,
+    generation: u64,
+    /// Name of every player and whether it is listed, in the registry's order.
+    players: Vec<(Option<String>, bool)>,
+    built: Instant,
+    json: String,
+}
+
+static STATUS_CACHE: Lazy<Mutex<Option<CachedStatus>>> = Lazy::new(|| Mutex::new(None));
+static STATUS_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Makes the next status ping build the status again instead of reusing the last one, e.g. after
+/// the favicon was replaced or what a status hook reports changed.
+pub fn invalidate_status() {
+    STATUS_GENERATION.fetch_add(1, Ordering::Relaxed);
+}
+
+/// The JSON of [`server_status`], reused until the players change, [`invalidate_status`] is called
+/// or it is a few seconds old, as server list scanners may ping a lot.
+pub fn status_json(config: &Arc<ServerConfig>, connections: &Arc<ConnectionRegistry>) -> String {
+    let generation = STATUS_GENERATION.load(Ordering::Relaxed);
+    let players = connections.players().iter()
+        .map(|player| {
+            let info = player.info();
+            (info.username, info.client_information.is_none_or(|information| information.allow_server_listings))
+        })
+        .collect::<Vec<_>>();
+    let mut cache = STATUS_CACHE.lock().unwrap();
+    if let Some(cached) = cache.as_ref() {
+        let current = Weak::ptr_eq(&cached.config, &Arc::downgrade(config))
+            && Weak::ptr_eq(&cached.connections, &Arc::downgrade(connections))
+            && cached.generation == generation
+            && cached.players == players
+            && cached.built.elapsed() < STATUS_CACHE_LIFETIME;
+        if current {
+            return cached.json.clone();
+        }
+    }
+    let json = server_status(config, connections).to_json();
+    *cache = Some(CachedStatus {
+        config: Arc::downgrade(config),
+        connections: Arc::downgrade(connections),
+        generation,
+        players,
+        built: Instant::now(),
+        json: json.clone(),
+    });
+    json
+}
+
+/// Pings counted per address in its current window.
+#[derive(Default)]
+struct StatusPings {
+    windows: HashMap<IpAddr, (Instant, u32)>,
+    /// Addresses by the start of their window, oldest first.
+    order: VecDeque<(Instant, IpAddr)>,
+}
+
+impl StatusPings {
+    fn count(&mut self, source: IpAddr, now: Instant) -> u32 {
+        while self.order.front().is_some_and(|(start, _)| now.duration_since(*start) >= STATUS_RATE_WINDOW) {
+            self.forget_oldest();
+        }
+        if !self.windows.contains_key(&source) {
+            if self.windows.len() >= MAX_TRACKED_PINGERS {
+                self.forget_oldest();
+            }
+            self.windows.insert(source, (now, 0));
+            self.order.push_back((now, source));
+        }
+        let (_, count) = self.windows.get_mut(&source).unwrap();
+        *count += 1;
+        *count
+    }
+
+    fn forget_oldest(&mut self) {
+        if let Some((_, source)) = self.order.pop_front() {
+            self.windows.remove(&source);
+        }
+    }
+}
+
+static STATUS_PINGS: Lazy<Mutex<StatusPings>> = Lazy::new(|| Mutex::new(StatusPings::default()));
+
+/// Addresses counted together: IPv6 ones by their /64 network, which a single host often gets whole.
+fn ping_source(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => IpAddr::V4(ip),
+            None => IpAddr::V6(Ipv6Addr::from(u128::from(ip) & (u128::MAX << 64))),
+        },
+        ip => ip,
+    }
+}
+
+/// Counts a status ping from `ip`, returns whether it is within `limit` pings a minute. A limit of 0
+/// allows every ping.
+pub fn allow_status_ping(ip: IpAddr, limit: u32) -> bool {
+    limit == 0 || STATUS_PINGS.lock().unwrap().count(ping_source(ip), Instant::now()) <= limit
+}
+
+#[packet_processor(PacketType::StatusRequest)]
+pub(crate) fn status_request_packet(context: &mut PacketContext, _packet: RawPacket) -> anyhow::Result<()> {
+    let json = status_json(&context.config, &context.connections);
+    context.send(&StatusResponse { json })
+}
+
+#[packet_processor(PacketType::PingRequest)]
+pub(crate) fn ping_request_packet(context: &mut PacketContext, packet: RawPacket) -> anyhow::Result<()> {
+    let ping: PingPong = decode_from_slice(&packet.payload)?;
+    context.send(&ping)
+}
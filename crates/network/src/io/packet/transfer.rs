@@ -0,0 +1,55 @@
+use anyhow::bail;
+use dolls_core::datatype::{Encode, VarInt};
+use dolls_core::text::TextComponent;
+use crate::prelude::{ClientboundPacket, ClientboundPacketType, ConnectionHandle, ConnectionState, RawPacket};
+
+/// Sends the client to another server, which it joins with a handshake of intent Transfer.
+#[derive(Debug, Clone, Encode)]
+pub struct ConfigurationTransfer {
+    pub host: String,
+    pub port: VarInt,
+}
+
+impl ClientboundPacket for ConfigurationTransfer {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::ConfigurationTransfer;
+}
+
+#[derive(Debug, Clone, Encode)]
+pub struct Transfer {
+    pub host: String,
+    pub port: VarInt,
+}
+
+impl ClientboundPacket for Transfer {
+    const PACKET_TYPE: ClientboundPacketType = ClientboundPacketType::Transfer;
+}
+
+/// Marks connections which came from another server with a Transfer packet.
+#[derive(Debug)]
+pub(crate) struct Transferred;
+
+/// The Transfer packet of `state`, only Configuration and Play have one.
+pub fn transfer_packet(state: ConnectionState, host: impl Into<String>, port: u16) -> anyhow::Result<RawPacket> {
+    let (host, port) = (host.into(), VarInt(port as i32));
+    Ok(match state {
+        ConnectionState::Configuration => RawPacket::from_packet(&ConfigurationTransfer { host, port })?,
+        ConnectionState::Play => RawPacket::from_packet(&Transfer { host, port })?,
+        state => bail!("Cannot transfer clients in {:?}", state),
+    })
+}
+
+/// Sends a client in Configuration or Play to another server. Cookies stored on it are kept, the
+/// other server can read them back.
+pub fn transfer(connection: &ConnectionHandle, host: impl Into<String>, port: u16) -> anyhow::Result<()> {
+    connection.send_raw(transfer_packet(connection.state(), host, port)?)
+}
+
+/// Whether the client joined through a transfer from another server rather than directly.
+pub fn was_transferred(connection: &ConnectionHandle) -> bool {
+    connection.extensions(|extensions| extensions.get::<Transferred>().is_some())
+}
+
+/// Disconnect reason of transferred clients while `accepts-transfers` is off.
+pub fn transfers_disabled_reason() -> TextComponent {
+    TextComponent::translatable("multiplayer.disconnect.transfers_disabled", vec![]).fallback("Server does not accept transfers")
+}
@@ -49,7 +49,7 @@ pub async fn read_varint_and_get_size(stream: &mut (impl ReadExt + Unpin)) -> io
     loop {
         let mut buffer = [0u8; 1];
         stream.read_exact(&mut buffer).await?;
-        size = size + 1;
+        size += 1;
         let current_byte = buffer[0];
 
         value |= ((current_byte & SEGMENT_BITS) as u32) << position;
@@ -0,0 +1,63 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+use async_std::net::TcpStream;
+use dolls_core::datatype::{decode_from_slice, VarInt};
+use crate::prelude::{Capture, ClientboundPacketType, ConnectionState, FrameDirection, PacketHandler};
+
+/// How long the replayer waits for each packet the capture expects from the server.
+pub const REPLAY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A packet the server sent which differs from the capture, or one which never came.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayMismatch {
+    /// Index of the expected packet in the capture.
+    pub index: usize,
+    pub state: ConnectionState,
+    pub expected: u32,
+    /// `None` if nothing came within [`REPLAY_TIMEOUT`].
+    pub received: Option<u32>,
+}
+
+/// What a replay sent and how the answers compared to the capture.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReplayReport {
+    pub sent: usize,
+    pub received: usize,
+    pub mismatches: Vec<ReplayMismatch>,
+}
+
+/// Connects to the server at `address` as the captured client and sends its packets through a
+/// [`PacketHandler`] in order, waiting for the packets the server sent in between and comparing their ids.
+///
+/// Answers which depend on time, such as keep alives and time updates in Play, differ from run to run.
+/// Captures of encrypted connections cannot be replayed.
+pub async fn replay_capture(capture: &Capture, address: SocketAddr) -> anyhow::Result<ReplayReport> {
+    let mut handler = PacketHandler::new(TcpStream::connect(address).await?);
+    let mut report = ReplayReport::default();
+    for (index, captured) in capture.packets.iter().enumerate() {
+        match captured.direction {
+            FrameDirection::Inbound => {
+                handler.write_packet(&captured.packet).await?;
+                report.sent += 1;
+            }
+            FrameDirection::Outbound => {
+                let packet = match async_std::future::timeout(REPLAY_TIMEOUT, handler.next_packet()).await {
+                    Ok(Ok(packet)) => packet,
+                    Ok(Err(_)) | Err(_) => {
+                        report.mismatches.push(ReplayMismatch { index, state: captured.state, expected: captured.packet.packet_id, received: None });
+                        break;
+                    }
+                };
+                report.received += 1;
+                if packet.packet_id != captured.packet.packet_id {
+                    report.mismatches.push(ReplayMismatch { index, state: captured.state, expected: captured.packet.packet_id, received: Some(packet.packet_id) });
+                } else if ClientboundPacketType::from_parts(captured.state, packet.packet_id) == Some(ClientboundPacketType::SetCompression) {
+                    let threshold: VarInt = decode_from_slice(&packet.payload)?;
+                    handler.set_compression(usize::try_from(threshold.0).ok());
+                }
+            }
+        }
+    }
+    let _ = handler.get_ref().shutdown(std::net::Shutdown::Both);
+    Ok(report)
+}
@@ -0,0 +1,71 @@
+use std::fmt::{Display, Formatter};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use anyhow::Context;
+use flate2::Crc;
+
+/// Appends one line per frame to a file outside the connection, to locate corruption introduced by proxies.
+#[derive(Debug)]
+pub struct FrameTracer {
+    file: Mutex<File>,
+}
+
+impl FrameTracer {
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file = OpenOptions::new().create(true).append(true).open(path)
+            .with_context(|| format!("Failed to open frame trace {}", path.display()))?;
+        Ok(Self { file: Mutex::new(file) })
+    }
+
+    fn record(&self, line: &str) {
+        // Unbuffered on purpose, the interesting frames are often the last ones before a crash.
+        let _ = writeln!(self.file.lock().unwrap(), "{}", line);
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum FrameDirection {
+    Inbound,
+    Outbound,
+}
+
+impl Display for FrameDirection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FrameDirection::Inbound => f.write_str("in"),
+            FrameDirection::Outbound => f.write_str("out"),
+        }
+    }
+}
+
+/// Trace of one direction of a connection, frames are numbered from 0.
+#[derive(Debug)]
+pub struct FrameTrace {
+    tracer: Arc<FrameTracer>,
+    connection_id: u64,
+    peer_addr: SocketAddr,
+    direction: FrameDirection,
+    sequence: u64,
+}
+
+impl FrameTrace {
+    pub fn new(tracer: Arc<FrameTracer>, connection_id: u64, peer_addr: SocketAddr, direction: FrameDirection) -> Self {
+        Self { tracer, connection_id, peer_addr, direction, sequence: 0 }
+    }
+
+    /// Records a frame as on the wire without its length prefix, i.e. still compressed.
+    pub fn record(&mut self, frame: &[u8], packet_id: Option<u32>) {
+        let mut crc = Crc::new();
+        crc.update(frame);
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_millis()).unwrap_or_default();
+        let packet_id = packet_id.map(|id| format!("0x{:02X}", id)).unwrap_or_else(|| "?".to_string());
+        self.tracer.record(&format!("{} conn={} peer={} {} seq={} len={} crc={:08x} id={}",
+            timestamp, self.connection_id, self.peer_addr, self.direction, self.sequence, frame.len(), crc.sum(), packet_id));
+        self.sequence += 1;
+    }
+}
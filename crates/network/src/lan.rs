@@ -0,0 +1,43 @@
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use async_std::net::UdpSocket;
+use log::{debug, info, warn};
+use dolls_config::ServerConfig;
+use crate::prelude::{schedule_repeating, server_status, ConnectionRegistry, RepeatingTask};
+
+/// Multicast group clients listen on for LAN worlds.
+pub const LAN_BROADCAST_ADDRESS: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(224, 0, 2, 60)), 4445);
+/// Time between announcements, as in vanilla.
+const LAN_BROADCAST_INTERVAL: Duration = Duration::from_millis(1500);
+
+/// The announcement clients list in the LAN tab, a closing MOTD tag in the MOTD would cut it short
+/// and is dropped.
+pub fn lan_announcement(motd: &str, port: u16) -> String {
+    format!("[MOTD]{}[/MOTD][AD]{}[/AD]", motd.replace("[/MOTD]", ""), port)
+}
+
+/// Announces the server on the local network every 1.5 seconds, `None` unless `lan-broadcast` is on.
+pub fn start_lan_broadcast(config: Arc<ServerConfig>, connections: Arc<ConnectionRegistry>) -> Option<RepeatingTask> {
+    if !config.network.lan_broadcast {
+        return None;
+    }
+    let socket = match std::net::UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)) {
+        Ok(socket) => Arc::new(UdpSocket::from(socket)),
+        Err(err) => {
+            warn!("Failed to open the LAN broadcast socket: {}", err);
+            return None;
+        }
+    };
+    info!("Announcing the server to the local network on {}", LAN_BROADCAST_ADDRESS);
+    Some(schedule_repeating("LAN broadcast", LAN_BROADCAST_INTERVAL, move || {
+        let (config, connections, socket) = (config.clone(), connections.clone(), socket.clone());
+        async move {
+            let motd = server_status(&config, &connections).description.to_plain_text();
+            let announcement = lan_announcement(&motd, config.network.port);
+            if let Err(err) = socket.send_to(announcement.as_bytes(), LAN_BROADCAST_ADDRESS).await {
+                debug!("Failed to announce the server to the local network: {}", err);
+            }
+        }
+    }))
+}
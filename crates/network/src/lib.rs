@@ -1,7 +1,33 @@
 pub mod server;
 pub mod io;
+pub mod connection;
+pub mod placeholder;
+pub mod listener;
+pub mod scheduler;
+pub mod heartbeat;
+pub mod permissions;
+pub mod whitelist;
+pub mod bans;
+pub mod query;
+pub mod lan;
+pub mod proxy;
+pub mod transport;
+#[cfg(feature = "testing")]
+pub mod testing;
 
 pub mod prelude {
     pub use crate::server::*;
     pub use crate::io::*;
+    pub use crate::connection::*;
+    pub use crate::placeholder::*;
+    pub use crate::listener::*;
+    pub use crate::scheduler::*;
+    pub use crate::heartbeat::*;
+    pub use crate::permissions::*;
+    pub use crate::whitelist::*;
+    pub use crate::bans::*;
+    pub use crate::query::*;
+    pub use crate::lan::*;
+    pub use crate::proxy::*;
+    pub use crate::transport::*;
 }
@@ -0,0 +1,157 @@
+use std::io::{self, ErrorKind};
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use anyhow::{anyhow, bail};
+use async_std::net::TcpListener;
+#[cfg(not(unix))]
+use log::warn;
+#[cfg(unix)]
+use socket2::{Domain, Protocol, Socket, Type};
+
+/// Connections each shared socket queues until its accept loop takes them.
+#[cfg(unix)]
+const BACKLOG: i32 = 1024;
+
+/// Pause after the first accept error in a row, doubled with every further one up to [`MAX_ACCEPT_BACKOFF`].
+pub const ACCEPT_BACKOFF: Duration = Duration::from_millis(10);
+pub const MAX_ACCEPT_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Whether an accept error means the listening socket itself is unusable. Other errors, such as running
+/// out of file descriptors or a client resetting before it was accepted, pass after a while.
+pub fn is_listener_gone(err: &io::Error) -> bool {
+    #[cfg(unix)]
+    if let Some(code) = err.raw_os_error() {
+        return matches!(code, libc::EBADF | libc::EINVAL | libc::ENOTSOCK | libc::EOPNOTSUPP);
+    }
+    matches!(err.kind(), ErrorKind::InvalidInput | ErrorKind::Unsupported)
+}
+
+/// How long an accept loop waits before trying again after errors.
+#[derive(Debug, Default)]
+pub struct AcceptBackoff {
+    failures: u32,
+}
+
+impl AcceptBackoff {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pause before the next accept, none after a success.
+    pub fn delay(&self) -> Duration {
+        match self.failures {
+            0 => Duration::ZERO,
+            failures => ACCEPT_BACKOFF.saturating_mul(1 << (failures - 1).min(16)).min(MAX_ACCEPT_BACKOFF),
+        }
+    }
+
+    pub async fn wait(&self) {
+        let delay = self.delay();
+        if !delay.is_zero() {
+            async_std::task::sleep(delay).await;
+        }
+    }
+
+    pub fn failed(&mut self) {
+        self.failures = self.failures.saturating_add(1);
+    }
+
+    pub fn succeeded(&mut self) {
+        self.failures = 0;
+    }
+}
+
+/// Whether IPv6 listeners also take IPv4 connections, as on Linux unless `net.ipv6.bindv6only` is set.
+/// Other systems make IPv6 sockets v6-only by default.
+pub fn ipv6_dual_stack() -> bool {
+    cfg!(target_os = "linux")
+        && !std::fs::read_to_string("/proc/sys/net/ipv6/bindv6only").is_ok_and(|value| value.trim() == "1")
+}
+
+/// Whether two listeners would claim the same port, a wildcard address overlaps every address of its family.
+/// On dual-stack systems the IPv6 wildcard claims the IPv4 addresses too.
+fn overlaps(a: &SocketAddr, b: &SocketAddr) -> bool {
+    if a.port() != b.port() {
+        return false;
+    }
+    match (a.ip().to_canonical(), b.ip().to_canonical()) {
+        (IpAddr::V6(v6), IpAddr::V4(_)) | (IpAddr::V4(_), IpAddr::V6(v6)) => v6.is_unspecified() && ipv6_dual_stack(),
+        (a, b) => a == b || a.is_unspecified() || b.is_unspecified(),
+    }
+}
+
+/// Rejects configurations in which listeners conflict with each other, before anything is bound.
+pub fn validate_listeners(addresses: &[SocketAddr]) -> anyhow::Result<()> {
+    if addresses.is_empty() {
+        bail!("No address to listen on is configured");
+    }
+    for (index, address) in addresses.iter().enumerate() {
+        if let Some(other) = addresses[..index].iter().find(|other| overlaps(other, address)) {
+            match other == address {
+                true => bail!("Listener {} is configured twice", address),
+                false => bail!("Listeners {} and {} both claim port {}", other, address, address.port()),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Binds one listener, turning the usual failures into something an operator can act on.
+pub async fn bind_listener(address: SocketAddr) -> anyhow::Result<TcpListener> {
+    TcpListener::bind(address).await.map_err(|err| bind_error(address, err))
+}
+
+/// Binds `shards` listeners to `address` with `SO_REUSEPORT`, among which the kernel spreads incoming
+/// connections. With port 0 they all share the port the first one got.
+#[cfg(unix)]
+pub fn bind_shared_listeners(mut address: SocketAddr, shards: usize) -> anyhow::Result<Vec<TcpListener>> {
+    let mut listeners = Vec::with_capacity(shards);
+    for _ in 0..shards {
+        let listener = bind_reuse_port(address).map_err(|err| bind_error(address, err))?;
+        address = listener.local_addr()?;
+        listeners.push(TcpListener::from(listener));
+    }
+    Ok(listeners)
+}
+
+#[cfg(unix)]
+fn bind_reuse_port(address: SocketAddr) -> io::Result<std::net::TcpListener> {
+    let socket = Socket::new(Domain::for_address(address), Type::STREAM, Some(Protocol::TCP))?;
+    // As std does for listeners, so that a restarted server does not wait for old connections to time out.
+    socket.set_reuse_address(true)?;
+    socket.set_reuse_port(true)?;
+    socket.bind(&address.into())?;
+    socket.listen(BACKLOG)?;
+    Ok(socket.into())
+}
+
+fn bind_error(address: SocketAddr, err: io::Error) -> anyhow::Error {
+    match err.kind() {
+        ErrorKind::AddrInUse => anyhow!("Cannot listen on {}: the port is already in use by another program", address),
+        ErrorKind::PermissionDenied if address.port() < 1024 => {
+            anyhow!("Cannot listen on {}: ports below 1024 require elevated privileges", address)
+        }
+        ErrorKind::PermissionDenied => anyhow!("Cannot listen on {}: permission denied", address),
+        ErrorKind::AddrNotAvailable => anyhow!("Cannot listen on {}: the address does not belong to any network interface", address),
+        _ => anyhow!("Cannot listen on {}: {}", address, err),
+    }
+}
+
+/// Validates and binds all listeners, `shards` sockets for each address. None stays bound if one of them fails.
+pub async fn bind_listeners(addresses: &[SocketAddr], shards: usize) -> anyhow::Result<Vec<TcpListener>> {
+    validate_listeners(addresses)?;
+    let mut listeners = Vec::with_capacity(addresses.len() * shards);
+    for address in addresses {
+        match shards {
+            0 | 1 => listeners.push(bind_listener(*address).await?),
+            #[cfg(unix)]
+            shards => listeners.extend(bind_shared_listeners(*address, shards)?),
+            #[cfg(not(unix))]
+            shards => {
+                warn!("Sharing {} between {} accept loops needs SO_REUSEPORT, using one", address, shards);
+                listeners.push(bind_listener(*address).await?);
+            }
+        }
+    }
+    Ok(listeners)
+}
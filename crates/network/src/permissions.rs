@@ -0,0 +1,157 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use anyhow::Context;
+use once_cell::sync::Lazy;
+use serde_json::Value;
+use dolls_core::datatype::Uuid;
+use crate::prelude::{ConnectionHandle, EntityEvent};
+
+/// Operators file in the working directory, where vanilla keeps it.
+pub const OPS_FILE: &str = "ops.json";
+/// Highest permission level, which the console always has.
+pub const MAX_PERMISSION_LEVEL: u8 = 4;
+/// Entity status telling a client its own permission level, level 0 up to this plus 4.
+const PERMISSION_LEVEL_STATUS: i8 = 24;
+
+/// An entry of `ops.json`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Operator {
+    pub uuid: Uuid,
+    pub name: String,
+    /// Permission level from 1 to 4.
+    pub level: u8,
+    /// May join when the server is full.
+    pub bypasses_player_limit: bool,
+}
+
+/// The server operators, kept in `ops.json` in vanilla's format.
+#[derive(Debug, Clone, Default)]
+pub struct OpsList {
+    path: Option<PathBuf>,
+    operators: Vec<Operator>,
+}
+
+impl OpsList {
+    /// Reads the list, an empty one if the file does not exist yet. Malformed entries are skipped.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let mut list = Self { path: Some(path.to_path_buf()), operators: Vec::new() };
+        if !path.exists() {
+            return Ok(list);
+        }
+        let content = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let json: Value = serde_json::from_str(&content).with_context(|| format!("Malformed {}", path.display()))?;
+        list.operators = json.as_array().into_iter().flatten()
+            .filter_map(|entry| Some(Operator {
+                uuid: entry.get("uuid")?.as_str()?.parse().ok()?,
+                name: entry.get("name").and_then(Value::as_str).unwrap_or_default().to_string(),
+                level: entry.get("level").and_then(Value::as_u64).unwrap_or(MAX_PERMISSION_LEVEL as u64).min(MAX_PERMISSION_LEVEL as u64) as u8,
+                bypasses_player_limit: entry.get("bypassesPlayerLimit").and_then(Value::as_bool).unwrap_or_default(),
+            }))
+            .collect();
+        Ok(list)
+    }
+
+    /// Writes the list back to the file it was loaded from, lists never loaded are not saved.
+    pub fn save(&self) -> anyhow::Result<()> {
+        let Some(path) = &self.path else { return Ok(()) };
+        let entries = self.operators.iter()
+            .map(|operator| serde_json::json!({
+                "uuid": operator.uuid.hyphenated().to_string(),
+                "name": operator.name,
+                "level": operator.level,
+                "bypassesPlayerLimit": operator.bypasses_player_limit,
+            }))
+            .collect::<Vec<_>>();
+        fs::write(path, serde_json::to_string_pretty(&entries)?).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    pub fn get(&self, uuid: Uuid) -> Option<&Operator> {
+        self.operators.iter().find(|operator| operator.uuid == uuid)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Operator> {
+        self.operators.iter()
+    }
+
+    /// Adds or replaces an operator, returns whether it was not one before.
+    pub fn add(&mut self, operator: Operator) -> bool {
+        let added = self.remove(operator.uuid).is_none();
+        self.operators.push(operator);
+        added
+    }
+
+    pub fn remove(&mut self, uuid: Uuid) -> Option<Operator> {
+        let index = self.operators.iter().position(|operator| operator.uuid == uuid)?;
+        Some(self.operators.remove(index))
+    }
+}
+
+static OPS: Lazy<RwLock<OpsList>> = Lazy::new(|| RwLock::new(OpsList::default()));
+
+/// Operators of the running server, loaded from `ops.json` at startup.
+pub fn ops() -> &'static RwLock<OpsList> {
+    &OPS
+}
+
+/// Decides what players may do. Providers answer `None` for what they do not know about.
+pub trait PermissionProvider: Send + Sync {
+    /// Level from 0 to 4, players get the highest any provider gives them.
+    fn permission_level(&self, _player: &ConnectionHandle) -> Option<u8> {
+        None
+    }
+
+    /// Whether the player has a permission node, e.g. `minecraft.command.gamemode`. The last
+    /// registered provider to answer decides, without an answer the node's default level does.
+    fn has_permission(&self, _player: &ConnectionHandle, _node: &str) -> Option<bool> {
+        None
+    }
+}
+
+/// Gives operators their level from `ops.json`.
+struct OpsPermissions;
+
+impl PermissionProvider for OpsPermissions {
+    fn permission_level(&self, player: &ConnectionHandle) -> Option<u8> {
+        let uuid = player.uuid()?;
+        ops().read().unwrap().get(uuid).map(|operator| operator.level)
+    }
+}
+
+static PROVIDERS: Lazy<RwLock<Vec<Arc<dyn PermissionProvider>>>> = Lazy::new(|| RwLock::new(vec![Arc::new(OpsPermissions)]));
+
+/// Adds a provider after the built-in one reading `ops.json`. Players whose permissions change
+/// should be sent their commands again.
+pub fn register_permission_provider(provider: Arc<dyn PermissionProvider>) {
+    PROVIDERS.write().unwrap().push(provider);
+}
+
+pub fn permission_level(player: &ConnectionHandle) -> u8 {
+    let providers = PROVIDERS.read().unwrap().clone();
+    providers.iter()
+        .filter_map(|provider| provider.permission_level(player))
+        .max()
+        .unwrap_or_default()
+        .min(MAX_PERMISSION_LEVEL)
+}
+
+/// Whether the player has `node`, players without an answer from any provider have it from
+/// `default_level` on.
+pub fn has_permission(player: &ConnectionHandle, node: &str, default_level: u8) -> bool {
+    let providers = PROVIDERS.read().unwrap().clone();
+    providers.iter().rev()
+        .find_map(|provider| provider.has_permission(player, node))
+        .unwrap_or_else(|| permission_level(player) >= default_level)
+}
+
+/// Tells the client its permission level, which unlocks e.g. the game mode switcher at level 2.
+pub fn permission_level_event(entity_id: i32, level: u8) -> EntityEvent {
+    EntityEvent { entity_id, status: PERMISSION_LEVEL_STATUS + level.min(MAX_PERMISSION_LEVEL) as i8 }
+}
+
+/// Sends a player in Play its current permission level.
+pub fn send_permission_level(player: &ConnectionHandle) -> anyhow::Result<()> {
+    let Some(entity_id) = player.entity_id() else { return Ok(()) };
+    player.send(&permission_level_event(entity_id, permission_level(player)))
+}
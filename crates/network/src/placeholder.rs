@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use once_cell::sync::Lazy;
+use dolls_config::ServerConfig;
+use dolls_core::text::{TextComponent, TextContent};
+use crate::prelude::{ConnectionHandle, ConnectionRegistry};
+
+/// What a placeholder is resolved for, `player` is absent e.g. for the server list MOTD.
+#[derive(Debug, Clone, Copy)]
+pub struct PlaceholderContext<'a> {
+    pub player: Option<&'a ConnectionHandle>,
+    pub connections: &'a ConnectionRegistry,
+    pub config: &'a ServerConfig,
+}
+
+/// Returns `None` when the placeholder has no value in this context, which leaves it untouched.
+pub type PlaceholderResolver = Arc<dyn Fn(&PlaceholderContext) -> Option<String> + Send + Sync>;
+
+/// Expands `{name}` placeholders in MOTDs, chat formats, tab list and scoreboard texts.
+pub struct PlaceholderService {
+    resolvers: RwLock<HashMap<String, PlaceholderResolver>>,
+}
+
+impl PlaceholderService {
+    /// A service knowing the built-in placeholders.
+    pub fn new() -> Self {
+        let service = Self { resolvers: RwLock::new(HashMap::new()) };
+        service.register("player", |context| context.player?.username());
+        service.register("uuid", |context| context.player?.info().uuid.map(|uuid| uuid.to_string()));
+        service.register("online", |context| Some(context.connections.players().len().to_string()));
+        service.register("max_players", |context| Some(context.config.server.max_players.to_string()));
+        service
+    }
+
+    /// Adds or replaces the resolver for `{name}`.
+    pub fn register(&self, name: impl Into<String>, resolver: impl Fn(&PlaceholderContext) -> Option<String> + Send + Sync + 'static) {
+        self.resolvers.write().unwrap().insert(name.into(), Arc::new(resolver));
+    }
+
+    pub fn unregister(&self, name: &str) {
+        self.resolvers.write().unwrap().remove(name);
+    }
+
+    pub fn resolve(&self, name: &str, context: &PlaceholderContext) -> Option<String> {
+        let resolver = self.resolvers.read().unwrap().get(name).cloned()?;
+        resolver(context)
+    }
+
+    /// Replaces every known placeholder in `template`, `{{` and `}}` stand for literal braces.
+    pub fn apply(&self, template: &str, context: &PlaceholderContext) -> String {
+        let mut result = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(index) = rest.find(['{', '}']) {
+            result.push_str(&rest[..index]);
+            rest = &rest[index..];
+            if rest.starts_with("{{") || rest.starts_with("}}") {
+                result.push_str(&rest[..1]);
+                rest = &rest[2..];
+                continue;
+            }
+            let name = rest.strip_prefix('{')
+                .and_then(|inner| inner.find('}').map(|end| &inner[..end]))
+                .filter(|name| name.chars().all(is_placeholder_char));
+            match name.and_then(|name| self.resolve(name, context).map(|value| (name, value))) {
+                Some((name, value)) => {
+                    result.push_str(&value);
+                    rest = &rest[name.len() + 2..];
+                }
+                None => {
+                    result.push_str(&rest[..1]);
+                    rest = &rest[1..];
+                }
+            }
+        }
+        result.push_str(rest);
+        result
+    }
+
+    /// Applies placeholders to the literal texts of a component and its children, styles are kept.
+    pub fn apply_component(&self, component: &TextComponent, context: &PlaceholderContext) -> TextComponent {
+        let mut component = component.clone();
+        match &mut component.content {
+            TextContent::Text { text } => *text = self.apply(text, context),
+            TextContent::Translatable { with, .. } => {
+                for argument in with.iter_mut() {
+                    *argument = self.apply_component(argument, context);
+                }
+            }
+            TextContent::Keybind { .. } => {}
+        }
+        for child in component.extra.iter_mut() {
+            *child = self.apply_component(child, context);
+        }
+        component
+    }
+}
+
+impl Default for PlaceholderService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for PlaceholderService {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut names = self.resolvers.read().unwrap().keys().cloned().collect::<Vec<_>>();
+        names.sort();
+        f.debug_struct("PlaceholderService").field("placeholders", &names).finish()
+    }
+}
+
+fn is_placeholder_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | ':' | '-')
+}
+
+static PLACEHOLDERS: Lazy<PlaceholderService> = Lazy::new(PlaceholderService::new);
+
+pub fn placeholders() -> &'static PlaceholderService {
+    &PLACEHOLDERS
+}
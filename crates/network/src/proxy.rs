@@ -0,0 +1,142 @@
+use std::net::{Shutdown, SocketAddr};
+use std::sync::{Arc, Mutex};
+use anyhow::{bail, Context};
+use async_std::net::{TcpListener, TcpStream};
+use async_std::prelude::StreamExt;
+use futures_lite::FutureExt;
+use log::{info, warn};
+use dolls_core::datatype::{decode_from_slice, VarInt};
+use crate::prelude::{hex_dump, ClientboundPacketType, ConnectionState, FrameDirection, Handshake, PacketHandler, PacketType, RawPacket,
+    PACKET_DUMP_LIMIT};
+
+/// Login packet of servers in online mode, which the proxy cannot follow past since it cannot decrypt.
+const ENCRYPTION_REQUEST: u32 = 0x01;
+/// Play packet the client answers Start Configuration with, the server is not able to reconfigure yet.
+const CONFIGURATION_ACKNOWLEDGED: u32 = 0x0C;
+
+/// Where the proxy listens and forwards to.
+#[derive(Debug, Clone)]
+pub struct ProxyOptions {
+    pub listen: SocketAddr,
+    /// `host:port` of the server, which must be in offline mode.
+    pub upstream: String,
+    /// Logs a hex dump of every payload along with the packet.
+    pub dump: bool,
+}
+
+/// States of a proxied connection, both sides switch when the client acknowledges a switch.
+#[derive(Debug, Default)]
+struct ProxySession {
+    state: ConnectionState,
+    compression: Option<usize>,
+}
+
+/// Sits between clients and a vanilla server, logging what both sides send with the packet definitions
+/// of this crate. Packets are decoded and encoded again on the way, so framing bugs show up as well.
+pub async fn run_proxy(options: ProxyOptions) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(options.listen).await.with_context(|| format!("Failed to bind {}", options.listen))?;
+    info!("Proxying {} to {}", options.listen, options.upstream);
+    let mut incoming = listener.incoming();
+    let mut next_id = 0;
+    while let Some(client) = incoming.next().await {
+        let client = client?;
+        let (id, options) = (next_id, options.clone());
+        next_id += 1;
+        async_std::task::spawn(async move {
+            let peer_addr = client.peer_addr().ok();
+            info!("conn={} Client {:?} connected", id, peer_addr);
+            match proxy_connection(id, client, &options).await {
+                Ok(()) => info!("conn={} Closed", id),
+                Err(err) => warn!("conn={} Closed: {:#}", id, err),
+            }
+        });
+    }
+    Ok(())
+}
+
+async fn proxy_connection(id: u64, client: TcpStream, options: &ProxyOptions) -> anyhow::Result<()> {
+    let server = TcpStream::connect(options.upstream.as_str()).await.with_context(|| format!("Failed to connect to {}", options.upstream))?;
+    client.set_nodelay(true)?;
+    server.set_nodelay(true)?;
+    let session = Arc::new(Mutex::new(ProxySession::default()));
+    let serverbound = forward(id, FrameDirection::Inbound, client.clone(), server.clone(), session.clone(), options.dump);
+    let clientbound = forward(id, FrameDirection::Outbound, server.clone(), client.clone(), session, options.dump);
+    // Either side closing ends the other.
+    let result = serverbound.or(clientbound).await;
+    let _ = client.shutdown(Shutdown::Both);
+    let _ = server.shutdown(Shutdown::Both);
+    result
+}
+
+/// Forwards the packets of one side, `Inbound` being those of the client.
+async fn forward(id: u64, direction: FrameDirection, mut from: TcpStream, mut to: TcpStream, session: Arc<Mutex<ProxySession>>, dump: bool) -> anyhow::Result<()> {
+    let mut reader = PacketHandler::new(&mut from);
+    let mut writer = PacketHandler::new(&mut to);
+    loop {
+        let frame = match reader.next_frame().await {
+            Ok(frame) => frame,
+            Err(err) if err.downcast_ref::<std::io::Error>().is_some_and(|err| err.kind() == std::io::ErrorKind::UnexpectedEof) => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        // The other side may have switched compression while this one waited for the frame.
+        let (state, compression) = {
+            let session = session.lock().unwrap();
+            (session.state, session.compression)
+        };
+        reader.set_compression(compression);
+        let packet = reader.parse_frame(&frame).await.with_context(|| format!("Malformed frame in {:?}", state))?;
+        log_packet(id, direction, state, &packet, dump);
+        follow(&mut session.lock().unwrap(), direction, state, &packet)?;
+        // The switching packet itself still goes out as before the switch.
+        writer.set_compression(compression);
+        writer.write_packet(&packet).await?;
+    }
+}
+
+/// Switches states and compression as the packet tells both sides to.
+fn follow(session: &mut ProxySession, direction: FrameDirection, state: ConnectionState, packet: &RawPacket) -> anyhow::Result<()> {
+    match direction {
+        FrameDirection::Inbound => match (state, PacketType::from_parts(state, packet.packet_id)) {
+            (_, Some(PacketType::Handshake)) => {
+                let handshake: Handshake = decode_from_slice(&packet.payload)?;
+                session.state = match handshake.next_state.0 {
+                    1 => ConnectionState::Status,
+                    2 | 3 => ConnectionState::Login,
+                    state => bail!("Invalid next state {} in handshake", state),
+                };
+            }
+            (_, Some(PacketType::LoginAcknowledged)) => session.state = ConnectionState::Configuration,
+            (_, Some(PacketType::AcknowledgeFinishConfiguration)) => session.state = ConnectionState::Play,
+            (ConnectionState::Play, None) if packet.packet_id == CONFIGURATION_ACKNOWLEDGED => session.state = ConnectionState::Configuration,
+            _ => {}
+        },
+        FrameDirection::Outbound => match ClientboundPacketType::from_parts(state, packet.packet_id) {
+            Some(ClientboundPacketType::SetCompression) => {
+                let threshold: VarInt = decode_from_slice(&packet.payload)?;
+                session.compression = usize::try_from(threshold.0).ok();
+            }
+            None if state == ConnectionState::Login && packet.packet_id == ENCRYPTION_REQUEST => {
+                bail!("The server asked for encryption, only servers in offline mode can be proxied");
+            }
+            _ => {}
+        },
+    }
+    Ok(())
+}
+
+fn log_packet(id: u64, direction: FrameDirection, state: ConnectionState, packet: &RawPacket, dump: bool) {
+    let (arrow, name) = match direction {
+        FrameDirection::Inbound => ("C->S", PacketType::from_parts(state, packet.packet_id).map(PacketType::name)),
+        FrameDirection::Outbound => ("S->C", ClientboundPacketType::from_parts(state, packet.packet_id).map(ClientboundPacketType::name)),
+    };
+    let mut message = format!("conn={} {} {:?} {} (0x{:02X}) len={}", id, arrow, state, name.unwrap_or("Unknown"), packet.packet_id, packet.payload.len());
+    if dump && !packet.payload.is_empty() {
+        message.push('\n');
+        message.push_str(&hex_dump(&packet.payload, PACKET_DUMP_LIMIT));
+    }
+    // Packets missing from the definitions are what the proxy is for, make them stand out.
+    match name {
+        Some(_) => info!("{}", message),
+        None => warn!("{}", message),
+    }
+}
@@ -0,0 +1,151 @@
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use async_std::net::UdpSocket;
+use log::{debug, error, info};
+use dolls_config::ServerConfig;
+use dolls_core::registry::CORE_PACK_VERSION;
+use crate::prelude::{server_status, ConnectionRegistry};
+
+const MAGIC: [u8; 2] = [0xFE, 0xFD];
+const HANDSHAKE: u8 = 9;
+const STAT: u8 = 0;
+/// Bits of the session id clients may use, the others are cleared in answers like in vanilla.
+const SESSION_ID_MASK: i32 = 0x0F0F_0F0F;
+/// How long a challenge token handed out by a handshake stays valid.
+const CHALLENGE_LIFETIME: Duration = Duration::from_secs(30);
+/// Magic, type, session id and challenge token of a basic stat request, full stat requests add padding.
+const BASIC_STAT_LENGTH: usize = 11;
+/// Constant start and end of the key-value section and the player section of a full stat.
+const FULL_STAT_PADDING: &[u8] = b"splitnum\0\x80\0";
+const PLAYERS_PADDING: &[u8] = b"\x01player_\0\0";
+/// Largest request read, anything longer is not a query.
+const MAX_REQUEST_SIZE: usize = 1460;
+
+/// Answers GameSpy4 queries until the task is cancelled, if the query protocol is enabled.
+pub async fn run_query(config: Arc<ServerConfig>, connections: Arc<ConnectionRegistry>) {
+    if !config.query.enabled {
+        return;
+    }
+    let address = SocketAddr::new(config.network.bind_address, config.query.port);
+    let socket = match UdpSocket::bind(address).await {
+        Ok(socket) => socket,
+        Err(err) => {
+            error!("Failed to bind the query listener to {}: {}", address, err);
+            return;
+        }
+    };
+    info!("Query running on {}", address);
+    let mut challenges = Challenges::default();
+    let mut buffer = [0; MAX_REQUEST_SIZE];
+    loop {
+        let (length, peer) = match socket.recv_from(&mut buffer).await {
+            Ok(received) => received,
+            Err(err) => {
+                debug!("Failed to receive a query: {}", err);
+                continue;
+            }
+        };
+        let Some(response) = answer(&buffer[..length], peer, &mut challenges, &config, &connections) else { continue };
+        if let Err(err) = socket.send_to(&response, peer).await {
+            debug!("Failed to answer the query of {}: {}", peer, err);
+        }
+    }
+}
+
+/// Challenge tokens handed out by handshakes, by address.
+#[derive(Default)]
+struct Challenges {
+    random: RandomState,
+    tokens: HashMap<SocketAddr, (i32, Instant)>,
+}
+
+impl Challenges {
+    fn issue(&mut self, peer: SocketAddr) -> i32 {
+        let now = Instant::now();
+        self.tokens.retain(|_, (_, issued)| now.duration_since(*issued) < CHALLENGE_LIFETIME);
+        // Vanilla's tokens have 24 bits, some clients assume so.
+        let token = (self.random.hash_one((peer, now)) & 0xFF_FFFF) as i32;
+        self.tokens.insert(peer, (token, now));
+        token
+    }
+
+    fn is_valid(&self, peer: SocketAddr, token: i32) -> bool {
+        self.tokens.get(&peer).is_some_and(|(issued_token, issued)| *issued_token == token && issued.elapsed() < CHALLENGE_LIFETIME)
+    }
+}
+
+/// The answer to a request, `None` for requests which are ignored.
+fn answer(request: &[u8], peer: SocketAddr, challenges: &mut Challenges, config: &ServerConfig, connections: &ConnectionRegistry) -> Option<Vec<u8>> {
+    if request.len() < 7 || request[..2] != MAGIC {
+        return None;
+    }
+    let kind = request[2];
+    let session_id = i32::from_be_bytes(request[3..7].try_into().ok()?) & SESSION_ID_MASK;
+    let mut response = vec![kind];
+    response.extend_from_slice(&session_id.to_be_bytes());
+    match kind {
+        HANDSHAKE => write_string(&mut response, &challenges.issue(peer).to_string()),
+        STAT => {
+            let token = i32::from_be_bytes(request.get(7..11)?.try_into().ok()?);
+            if !challenges.is_valid(peer, token) {
+                return None;
+            }
+            match request.len() == BASIC_STAT_LENGTH {
+                true => write_basic_stat(&mut response, config, connections),
+                false => write_full_stat(&mut response, config, connections),
+            }
+        }
+        _ => return None,
+    }
+    Some(response)
+}
+
+fn write_basic_stat(response: &mut Vec<u8>, config: &ServerConfig, connections: &ConnectionRegistry) {
+    let status = server_status(config, connections);
+    write_string(response, &status.description.to_plain_text());
+    write_string(response, "SMP");
+    write_string(response, &config.world.level_name);
+    write_string(response, &status.online_players.to_string());
+    write_string(response, &status.max_players.to_string());
+    response.extend_from_slice(&config.network.port.to_le_bytes());
+    write_string(response, &config.network.bind_address.to_string());
+}
+
+fn write_full_stat(response: &mut Vec<u8>, config: &ServerConfig, connections: &ConnectionRegistry) {
+    let status = server_status(config, connections);
+    response.extend_from_slice(FULL_STAT_PADDING);
+    let values = [
+        ("hostname", status.description.to_plain_text()),
+        ("gametype", "SMP".to_string()),
+        ("game_id", "MINECRAFT".to_string()),
+        ("version", CORE_PACK_VERSION.to_string()),
+        ("plugins", format!("Dolls {}", env!("CARGO_PKG_VERSION"))),
+        ("map", config.world.level_name.clone()),
+        ("numplayers", status.online_players.to_string()),
+        ("maxplayers", status.max_players.to_string()),
+        ("hostport", config.network.port.to_string()),
+        ("hostip", config.network.bind_address.to_string()),
+    ];
+    for (key, value) in values {
+        write_string(response, key);
+        write_string(response, &value);
+    }
+    response.push(0);
+    response.extend_from_slice(PLAYERS_PADDING);
+    if !config.server.hide_online_players {
+        for name in connections.players().iter().filter_map(|player| player.username()) {
+            write_string(response, &name);
+        }
+    }
+    response.push(0);
+}
+
+/// A zero-terminated ISO-8859-1 string, characters it lacks become `?`.
+fn write_string(response: &mut Vec<u8>, value: &str) {
+    response.extend(value.chars().map(|c| u8::try_from(c).unwrap_or(b'?')));
+    response.push(0);
+}
@@ -0,0 +1,51 @@
+use std::future::Future;
+use std::time::Duration;
+use async_std::channel::{bounded, Sender};
+use futures_lite::FutureExt;
+
+/// A task started by [`schedule_repeating`], dropping the handle stops it.
+#[derive(Debug)]
+pub struct RepeatingTask {
+    name: String,
+    _stop: Sender<()>,
+}
+
+impl RepeatingTask {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Stops the task, interrupting a run in progress.
+    pub fn stop(self) {}
+}
+
+/// Runs `task` right away and then every `interval` after the previous run finished.
+pub fn schedule_repeating<F, Fut>(name: impl Into<String>, interval: Duration, mut task: F) -> RepeatingTask
+where
+    F: FnMut() -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let name = name.into();
+    let (stop_sender, stop_receiver) = bounded::<()>(1);
+    async_std::task::Builder::new()
+        .name(name.clone())
+        .spawn(async move {
+            loop {
+                let run = async {
+                    task().await;
+                    async_std::task::sleep(interval).await;
+                    false
+                };
+                // Closed once the handle is dropped.
+                let stopped = async {
+                    let _ = stop_receiver.recv().await;
+                    true
+                };
+                if run.or(stopped).await {
+                    break;
+                }
+            }
+        })
+        .expect("Spawning a task does not fail");
+    RepeatingTask { name, _stop: stop_sender }
+}
@@ -1,73 +1,480 @@
-use async_std::net::{TcpListener, TcpStream};
-use async_std::prelude::StreamExt;
-use spdlog::{critical, debug, error};
-use std::net::{IpAddr, SocketAddr};
+use async_std::channel::{bounded, unbounded, Receiver, Sender};
+use futures_lite::FutureExt;
+use log::{debug, error, info, warn};
+use std::io;
+use std::net::{IpAddr, Shutdown, SocketAddr};
 use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use tracing::{field, info_span, Instrument};
 use async_std::sync::Mutex;
 use async_std::task::JoinHandle;
-use crate::prelude::{get_handler, init_packet_processors, PacketHandler};
+use dolls_config::ServerConfig;
+use dolls_events::prelude::{event_bus, Event, PacketReceiveEvent};
+use crate::prelude::{is_listener_gone, AcceptBackoff, bind_listeners, capture_packet, dump_packet, get_handler, record_packet, set_packet_dump, FrameDirection, FrameTrace, FrameTracer, init_packet_processors, player_left, record_handler, PacketType, send_protocol_error, DisconnectCode, ProtocolError, ConnectionRegistry, PacketCapture, ConnectionState, Outbound, PacketContext, PacketHandler, Listener, Stream};
 
 /// A TCP Server wrapper
 #[derive(Debug)]
 pub struct DollNetworkServer {
-    ip_address: IpAddr,
-    port: u16,
+    config: Arc<ServerConfig>,
+    listeners: Mutex<Vec<Listener>>,
+    local_addresses: Mutex<Vec<SocketAddr>>,
+    frame_tracer: Mutex<Option<Arc<FrameTracer>>>,
+    connections: Arc<ConnectionRegistry>,
     is_running: AtomicBool,
-    workers: Arc<Mutex<Vec<JoinHandle<()>>>>,
+    workers: Arc<AtomicUsize>,
+    shutdown_sender: Sender<()>,
+    shutdown_receiver: Receiver<()>,
+}
+
+/// Stops the accept loop of a [`DollNetworkServer`], usable from any thread.
+#[derive(Debug, Clone)]
+pub struct ShutdownHandle {
+    sender: Sender<()>,
+}
+
+impl ShutdownHandle {
+    pub fn shutdown(&self) {
+        // A full channel means a shutdown is already pending.
+        let _ = self.sender.try_send(());
+    }
 }
 
 /// Worker context
 #[derive(Debug)]
 struct WorkerContext {
-    pub stream: TcpStream,
+    pub stream: Stream,
+    pub config: Arc<ServerConfig>,
+    pub frame_tracer: Option<Arc<FrameTracer>>,
+}
+
+/// What every accept loop needs to spawn workers.
+#[derive(Debug, Clone)]
+struct AcceptContext {
+    config: Arc<ServerConfig>,
+    frame_tracer: Option<Arc<FrameTracer>>,
+    connections: Arc<ConnectionRegistry>,
+    /// Workers all accept loops track.
+    workers: Arc<AtomicUsize>,
+}
+
+/// Tells the accept loop that spawned a worker that it finished, when dropped with the worker's task.
+#[derive(Debug)]
+struct WorkerExit {
+    id: u64,
+    finished: Sender<u64>,
+}
+
+impl Drop for WorkerExit {
+    fn drop(&mut self) {
+        let _ = self.finished.try_send(self.id);
+    }
+}
+
+enum AcceptEvent {
+    Accepted(io::Result<Stream>),
+    Finished(u64),
+    Stopped,
+}
+
+/// How long a connection closed for being idle may take to send what is queued.
+const CLOSE_GRACE: Duration = Duration::from_secs(5);
+
+/// Login and Configuration, where protocol errors close the connection with a Disconnect packet.
+fn in_handshake(state: ConnectionState) -> bool {
+    matches!(state, ConnectionState::Login | ConnectionState::Configuration)
 }
 
 impl DollNetworkServer {
     pub fn new(ip_addr: IpAddr, port: u16) -> Self {
+        let mut config = ServerConfig::default();
+        config.network.bind_address = ip_addr;
+        config.network.port = port;
+        Self::from_config(Arc::new(config))
+    }
+
+    pub fn from_config(config: Arc<ServerConfig>) -> Self {
+        let (shutdown_sender, shutdown_receiver) = bounded(1);
         Self {
-            ip_address: ip_addr,
-            port,
+            config,
+            listeners: Mutex::new(Vec::new()),
+            local_addresses: Mutex::new(Vec::new()),
+            frame_tracer: Mutex::new(None),
+            connections: Arc::new(ConnectionRegistry::new()),
             is_running: AtomicBool::new(false),
-            workers: Arc::new(Mutex::new(Vec::new())),
+            workers: Arc::new(AtomicUsize::new(0)),
+            shutdown_sender,
+            shutdown_receiver,
+        }
+    }
+
+    pub fn config(&self) -> &Arc<ServerConfig> {
+        &self.config
+    }
+
+    pub fn connections(&self) -> &Arc<ConnectionRegistry> {
+        &self.connections
+    }
+
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle {
+            sender: self.shutdown_sender.clone(),
+        }
+    }
+
+    /// Makes [`DollNetworkServer::accept`] stop listening and drop all connections.
+    pub fn shutdown(&self) {
+        self.shutdown_handle().shutdown();
+    }
+
+    /// Binds every configured listener, so that conflicts surface before [`DollNetworkServer::accept`] is spawned.
+    pub async fn bind(&self) -> anyhow::Result<()> {
+        if let Some(path) = &self.config.network.frame_trace {
+            *self.frame_tracer.lock().await = Some(Arc::new(FrameTracer::open(path)?));
+            info!("Tracing frames to {}", path.display());
         }
+        set_packet_dump(self.config.network.packet_dump);
+        let listeners: Vec<_> = bind_listeners(&self.config.network.listeners(), self.config.network.accept_loops()).await?.into_iter()
+            .map(|listener| Listener::new(listener, self.config.network.io_backend))
+            .collect();
+        let mut addresses: Vec<_> = listeners.iter().filter_map(|listener| listener.local_addr().ok()).collect();
+        // The sockets of an address are bound one after another.
+        addresses.dedup();
+        *self.local_addresses.lock().await = addresses;
+        *self.listeners.lock().await = listeners;
+        Ok(())
     }
 
-    pub async fn accept(&self) {
+    /// Connections whose worker is still running.
+    pub fn worker_count(&self) -> usize {
+        self.workers.load(Ordering::Acquire)
+    }
+
+    /// Addresses the listeners are bound to, e.g. to learn the port picked for port 0.
+    pub async fn local_addresses(&self) -> Vec<SocketAddr> {
+        self.local_addresses.lock().await.clone()
+    }
+
+    /// Accepts players until shut down, binding the listeners first unless [`DollNetworkServer::bind`] did.
+    pub async fn accept(&self) -> anyhow::Result<()> {
         if self.is_running.load(Ordering::Acquire) {
-            critical!("DollNetworkServer already running");
+            error!("DollNetworkServer already running");
             panic!("DollNetworkServer already running");
         }
 
-        init_packet_processors().await;
+        init_packet_processors().await?;
 
+        if self.listeners.lock().await.is_empty() {
+            self.bind().await?;
+        }
+        let listeners = std::mem::take(&mut *self.listeners.lock().await);
+        let frame_tracer = self.frame_tracer.lock().await.clone();
         self.is_running.store(true, Ordering::Release);
-        let tcp_listener = TcpListener::bind(SocketAddr::new(self.ip_address, self.port)).await.unwrap();
-        let mut incoming = tcp_listener.incoming();
 
-        while let Some(Ok(stream)) = incoming.next().await {
-            debug!("Incoming stream from {}", stream.peer_addr().unwrap());
-            self.workers.lock().await.push(DollNetworkServer::create_new_worker(stream));
+        let mut addresses = Vec::new();
+        // Never sent on, dropping the sender on shutdown stops every accept loop.
+        let (stop_sender, stop_receiver) = bounded::<()>(1);
+        let mut acceptors = Vec::with_capacity(listeners.len());
+        for listener in listeners {
+            if let Ok(address) = listener.local_addr() {
+                if !addresses.contains(&address) {
+                    info!("Listening on {}", address);
+                    addresses.push(address);
+                }
+            }
+            let context = AcceptContext {
+                config: self.config.clone(),
+                frame_tracer: frame_tracer.clone(),
+                connections: self.connections.clone(),
+                workers: self.workers.clone(),
+            };
+            acceptors.push(async_std::task::spawn(DollNetworkServer::accept_loop(listener, stop_receiver.clone(), context)));
+        }
+        if acceptors.len() > addresses.len() {
+            info!("Accepting with {} loops per listener", acceptors.len() / addresses.len().max(1));
+        }
+
+        let _ = self.shutdown_receiver.recv().await;
+        drop(stop_sender);
+        for acceptor in acceptors {
+            acceptor.await;
         }
+        self.is_running.store(false, Ordering::Release);
+        info!("Network service stopped.");
+        Ok(())
     }
 
-    fn create_new_worker(stream: TcpStream) -> JoinHandle<()> {
+    /// Accepts on one listener until `stop` closes, then cancels the workers of the connections it accepted.
+    /// Workers are forgotten as they finish.
+    async fn accept_loop(mut listener: Listener, stop: Receiver<()>, context: AcceptContext) {
+        let mut workers = HashMap::new();
+        let (finished_sender, finished_receiver) = unbounded();
+        let mut next_worker = 0;
+        let mut accepting = true;
+        let mut backoff = AcceptBackoff::new();
+        loop {
+            let stopped = async {
+                let _ = stop.recv().await;
+                AcceptEvent::Stopped
+            };
+            // The loop holds a sender, so that the channel stays open.
+            let finished = async { finished_receiver.recv().await.map_or(AcceptEvent::Stopped, AcceptEvent::Finished) };
+            let accepted = async {
+                match accepting {
+                    true => {
+                        backoff.wait().await;
+                        AcceptEvent::Accepted(listener.accept().await)
+                    }
+                    false => std::future::pending().await,
+                }
+            };
+            match stopped.or(finished).or(accepted).await {
+                AcceptEvent::Accepted(Ok(stream)) => {
+                    backoff.succeeded();
+                    // A client may reset before its stream is looked at, which only concerns that stream.
+                    let socket_addr = match stream.peer_addr() {
+                        Ok(socket_addr) => socket_addr,
+                        Err(err) => {
+                            debug!("Dropping a stream that closed on accept: {}", err);
+                            continue;
+                        }
+                    };
+                    debug!("Incoming stream from {}", socket_addr);
+                    if let Err(err) = stream.set_nodelay(true) {
+                        debug!("Failed to disable Nagle's algorithm for {}: {}", socket_addr, err);
+                    }
+                    let exit = WorkerExit { id: next_worker, finished: finished_sender.clone() };
+                    workers.insert(next_worker, DollNetworkServer::create_new_worker(stream, socket_addr, &context, exit));
+                    context.workers.fetch_add(1, Ordering::AcqRel);
+                    next_worker += 1;
+                }
+                AcceptEvent::Accepted(Err(err)) if is_listener_gone(&err) => {
+                    error!("Stopped accepting on {:?}: {}", listener.local_addr().ok(), err);
+                    accepting = false;
+                }
+                AcceptEvent::Accepted(Err(err)) => {
+                    // E.g. out of file descriptors, which frees up as connections close.
+                    backoff.failed();
+                    warn!("Failed to accept on {:?}, retrying in {:?}: {}", listener.local_addr().ok(), backoff.delay(), err);
+                }
+                AcceptEvent::Finished(id) => {
+                    if workers.remove(&id).is_some() {
+                        context.workers.fetch_sub(1, Ordering::AcqRel);
+                    }
+                }
+                AcceptEvent::Stopped => break,
+            }
+        }
+        for (_, worker) in workers.drain() {
+            worker.cancel().await;
+            context.workers.fetch_sub(1, Ordering::AcqRel);
+        }
+    }
+
+    fn create_new_worker(stream: Stream, socket_addr: SocketAddr, context: &AcceptContext, exit: WorkerExit) -> JoinHandle<()> {
+        let connections = context.connections.clone();
         let mut worker_context = WorkerContext {
             stream,
+            config: context.config.clone(),
+            frame_tracer: context.frame_tracer.clone(),
         };
+        let (sender, receiver) = unbounded();
+        let connection = connections.register(socket_addr, sender);
+        if let Some(directory) = &worker_context.config.network.capture_directory {
+            match PacketCapture::create(directory, connection.id(), socket_addr) {
+                Ok(capture) => {
+                    debug!("Capturing packets of {} to {}", socket_addr, capture.path().display());
+                    connection.extensions(|extensions| extensions.insert(capture));
+                }
+                Err(err) => error!("Failed to capture packets of {}: {:#}", socket_addr, err),
+            }
+        }
+        let span = info_span!("connection", id = connection.id(), peer = %socket_addr);
         async_std::task::spawn(async move {
-            worker_context.stream.set_nodelay(true).unwrap();
+            let _exit = exit;
+            let trace = |direction| worker_context.frame_tracer.clone()
+                .map(|tracer| FrameTrace::new(tracer, connection.id(), socket_addr, direction));
+            let mut writer_handle = DollNetworkServer::create_writer(worker_context.stream.clone(), receiver, trace(FrameDirection::Outbound));
+            let inbound_trace = trace(FrameDirection::Inbound);
 
-            let socket_addr = worker_context.stream.peer_addr().unwrap();
+            let login_deadline = Instant::now() + Duration::from_secs(worker_context.config.network.login_timeout);
+            let configuration_deadline = login_deadline + Duration::from_secs(worker_context.config.network.configuration_timeout);
             let mut packet_handler = PacketHandler::new(&mut worker_context.stream);
+            if let Some(trace) = inbound_trace {
+                packet_handler.set_trace(trace);
+            }
+            let mut packet_context = PacketContext::new(worker_context.config.clone(), connection.clone(), connections.clone());
+            let verbosity = worker_context.config.network.disconnect_verbosity;
+            let time_budget = worker_context.config.network.handler_time_budget();
+            let idle_timeout = worker_context.config.network.idle_timeout();
+            let mut timed_out = false;
+
+            loop {
+                // Configuration may wait on the player, e.g. to answer the resource pack prompt, so it gets a deadline
+                // of its own instead of the idle timeout. Only Play has keep-alives for the client to answer meanwhile.
+                let deadline = match packet_context.state {
+                    ConnectionState::Configuration => Some(configuration_deadline),
+                    ConnectionState::Play => None,
+                    _ => Some(login_deadline),
+                };
+                let packet = if let Some(deadline) = deadline {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    match async_std::future::timeout(remaining, packet_handler.next_packet()).await {
+                        Ok(packet) => packet,
+                        Err(_) => {
+                            debug!("Client {:?} did not finish {:?} in time.", socket_addr, packet_context.state);
+                            let err = ProtocolError::new(DisconnectCode::LoginTimeout, DisconnectCode::LoginTimeout.summary());
+                            send_protocol_error(&connection, packet_context.state, &err.into(), verbosity);
+                            // Other handles may keep the writer running, closing ends it once the Disconnect is out.
+                            let _ = connection.send_outbound(Outbound::Close);
+                            timed_out = true;
+                            break;
+                        }
+                    }
+                } else if let Some(idle_timeout) = idle_timeout {
+                    match async_std::future::timeout(idle_timeout, packet_handler.next_packet()).await {
+                        Ok(packet) => packet,
+                        Err(_) => {
+                            debug!("Client {:?} sent nothing for {:?}.", socket_addr, idle_timeout);
+                            let err = ProtocolError::new(DisconnectCode::IdleTimeout, DisconnectCode::IdleTimeout.summary());
+                            send_protocol_error(&connection, packet_context.state, &err.into(), verbosity);
+                            let _ = connection.send_outbound(Outbound::Close);
+                            timed_out = true;
+                            break;
+                        }
+                    }
+                } else {
+                    packet_handler.next_packet().await
+                };
+                let packet = match packet {
+                    Ok(packet) => {
+                        record_packet(FrameDirection::Inbound, packet_context.state, &packet);
+                        dump_packet(connection.id(), FrameDirection::Inbound, packet_context.state, &packet);
+                        capture_packet(&connection, FrameDirection::Inbound, packet_context.state, &packet);
+                        packet
+                    }
+                    Err(err) => {
+                        // Anything but the client going away is a frame we could not read.
+                        let closed = err.downcast_ref::<io::Error>().is_some_and(|err| matches!(err.kind(),
+                            io::ErrorKind::UnexpectedEof | io::ErrorKind::ConnectionReset | io::ErrorKind::ConnectionAborted));
+                        if !closed && in_handshake(packet_context.state) {
+                            debug!("Malformed frame from client {:?}: {:#}", socket_addr, err);
+                            let err = anyhow::Error::new(ProtocolError::new(DisconnectCode::MalformedPacket, DisconnectCode::MalformedPacket.summary()))
+                                .context(format!("{:#}", err));
+                            send_protocol_error(&connection, packet_context.state, &err, verbosity);
+                        }
+                        break;
+                    }
+                };
 
-            while let Ok(packet) = packet_handler.next_packet().await {
-                if let Some(func) = get_handler(packet.packet_id).await {
-                    if let Err(err)  = func(packet) {
+                if let Some(func) = get_handler(packet_context.state, packet.packet_id).await {
+                    let (state, packet_id) = (packet_context.state, packet.packet_id);
+                    let mut packet = packet;
+                    if event_bus().has_subscribers::<PacketReceiveEvent>() {
+                        let mut event = PacketReceiveEvent {
+                            connection_id: connection.id(),
+                            packet: PacketType::from_parts(state, packet_id).map_or("Unknown", PacketType::name),
+                            packet_id,
+                            payload: std::mem::take(&mut packet.payload),
+                            cancelled: false,
+                        };
+                        event_bus().emit(&mut event);
+                        if event.is_cancelled() {
+                            continue;
+                        }
+                        packet.payload = event.payload;
+                    }
+                    let mut violation = None;
+                    let name = PacketType::from_parts(state, packet_id).map_or("Unknown", PacketType::name);
+                    let span = info_span!("packet", id = packet_id, name, state = ?state, otel.status_code = field::Empty);
+                    let started = Instant::now();
+                    let result = span.in_scope(|| func(&mut packet_context, packet));
+                    let elapsed = started.elapsed();
+                    record_handler(state, packet_id, elapsed, result.is_err());
+                    if result.is_err() {
+                        span.record("otel.status_code", "ERROR");
+                    }
+                    if time_budget.is_some_and(|budget| elapsed > budget) {
+                        warn!("Handler of {} ({:?} {:#04x}) took {:?}, over the budget of {:?}", name, state, packet_id, elapsed, time_budget.unwrap());
+                    }
+                    if let Err(err) = result {
                         error!("Error processing packet: {}", err);
+                        if in_handshake(state) {
+                            violation = Some(err.context(format!("Packet {:#04x} in {:?}", packet_id, state)));
+                        }
+                    }
+                    packet_context.sync_connection_info();
+                    for outbound in packet_context.take_outbound() {
+                        // The client compresses everything it sends after receiving Set Compression.
+                        if let Outbound::SetCompression(threshold) = outbound {
+                            packet_handler.set_compression(threshold);
+                        }
+                        if let Err(err) = connection.send_outbound(outbound) {
+                            error!("Error sending packet to client {:?}: {}", socket_addr, err);
+                        }
+                    }
+                    if let Some(err) = violation {
+                        send_protocol_error(&connection, state, &err, verbosity);
+                        break;
+                    }
+                    if packet_context.is_disconnected() {
+                        break;
                     }
                 } else {
-                    error!("Unexpected packet(id={}) from client {:?}.", packet.packet_id, socket_addr);
+                    error!("Unexpected packet(id={}, state={:?}) from client {:?}.", packet.packet_id, packet_context.state, socket_addr);
+                    if in_handshake(packet_context.state) {
+                        let err = ProtocolError::new(DisconnectCode::UnexpectedPacket, format!("Unexpected packet {:#04x}", packet.packet_id));
+                        send_protocol_error(&connection, packet_context.state, &err.into(), verbosity);
+                        break;
+                    }
+                }
+            }
+
+            connections.unregister(connection.id());
+            player_left(&connection, &connections);
+            // Dropping the last senders lets the writer flush what is queued and stop.
+            drop(packet_context);
+            if let Some(capture) = connection.extensions(|extensions| extensions.remove::<PacketCapture>()) {
+                if let Err(err) = capture.finish() {
+                    error!("Failed to write capture of {}: {}", socket_addr, err);
+                }
+            }
+            drop(connection);
+            // A peer which stopped reading may never take what is queued, it is cut off after a while.
+            if !timed_out {
+                writer_handle.await;
+            } else if async_std::future::timeout(CLOSE_GRACE, &mut writer_handle).await.is_err() {
+                let _ = packet_handler.get_ref().shutdown(Shutdown::Both);
+                writer_handle.await;
+            }
+        }.instrument(span))
+    }
+
+    fn create_writer(mut stream: Stream, receiver: Receiver<Outbound>, trace: Option<FrameTrace>) -> JoinHandle<()> {
+        async_std::task::spawn(async move {
+            let socket_addr = stream.peer_addr().ok();
+            let closed_stream = stream.clone();
+            let mut packet_handler = PacketHandler::new(&mut stream);
+            if let Some(trace) = trace {
+                packet_handler.set_trace(trace);
+            }
+            while let Ok(outbound) = receiver.recv().await {
+                let result = match outbound {
+                    Outbound::Packet(packet) => packet_handler.write_packet(&packet).await,
+                    Outbound::SetCompression(threshold) => {
+                        packet_handler.set_compression(threshold);
+                        Ok(())
+                    }
+                    Outbound::Close => {
+                        // The reader sees the end of the stream and lets the worker clean up.
+                        let _ = closed_stream.shutdown(Shutdown::Both);
+                        break;
+                    }
+                };
+                if let Err(err) = result {
+                    error!("Error sending packet to client {:?}: {}", socket_addr, err);
+                    break;
                 }
             }
         })
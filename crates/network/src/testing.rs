@@ -0,0 +1,135 @@
+//! Proptest strategies for packets and frame round-trip assertions, see `dolls_core::testing` for those
+//! of the field types, and a server on an ephemeral port for tests over real sockets.
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use async_std::task::JoinHandle;
+use proptest::prelude::*;
+use proptest::test_runner::TestCaseError;
+use dolls_config::ServerConfig;
+use dolls_core::datatype::VarInt;
+use dolls_world::prelude::{advancement_data, player_data, stats_data, AdvancementStorage, PlayerDataStorage, StatsStorage};
+use crate::prelude::{DollNetworkServer, Handshake, KeepAlive, KnownPack, PacketHandler, PingPong, RawPacket, StatusResponse, PROTOCOL_VERSION};
+
+pub use dolls_core::testing::*;
+
+/// A server listening on an ephemeral loopback port.
+pub struct TestServer {
+    pub server: Arc<DollNetworkServer>,
+    pub address: SocketAddr,
+    task: JoinHandle<()>,
+}
+
+impl TestServer {
+    pub async fn start() -> Self {
+        Self::start_with(ServerConfig::default()).await
+    }
+
+    /// Starts a server with `config`, its listeners are replaced by one on `127.0.0.1:0`.
+    pub async fn start_with(mut config: ServerConfig) -> Self {
+        config.network.bind_address = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        config.network.port = 0;
+        config.network.additional_listeners.clear();
+        // Players who leave are saved, keep their files out of the source tree.
+        let world = std::env::temp_dir().join(format!("dolls-test-{}", std::process::id()));
+        *player_data().write().unwrap() = PlayerDataStorage::new(world.join("playerdata"));
+        *advancement_data().write().unwrap() = AdvancementStorage::new(world.join("advancements"));
+        *stats_data().write().unwrap() = StatsStorage::new(world.join("stats"));
+        let server = Arc::new(DollNetworkServer::from_config(Arc::new(config)));
+        server.bind().await.expect("Failed to bind the test server");
+        let address = server.local_addresses().await[0];
+        let task = {
+            let server = server.clone();
+            async_std::task::spawn(async move {
+                server.accept().await.expect("Test server failed");
+            })
+        };
+        Self { server, address, task }
+    }
+
+    pub async fn stop(self) {
+        self.server.shutdown();
+        self.task.await;
+    }
+}
+
+/// Writes `packet` through a [`PacketHandler`] with the compression `threshold` and reads it back,
+/// checking the id and payload survived the framing.
+pub fn assert_frame_round_trip(packet: &RawPacket, threshold: Option<usize>) -> Result<(), TestCaseError> {
+    let mut stream = Vec::new();
+    let mut writer = PacketHandler::new(&mut stream);
+    writer.set_compression(threshold);
+    async_std::task::block_on(writer.write_packet(packet)).map_err(|err| TestCaseError::fail(format!("Failed to write {:?}: {}", packet, err)))?;
+    let mut reader = stream.as_slice();
+    let mut handler = PacketHandler::new(&mut reader);
+    handler.set_compression(threshold);
+    let read = async_std::task::block_on(handler.next_packet()).map_err(|err| TestCaseError::fail(format!("Failed to read {:?}: {}", packet, err)))?;
+    prop_assert_eq!(read.packet_id, packet.packet_id);
+    prop_assert_eq!(&read.payload, &packet.payload);
+    prop_assert!(reader.is_empty(), "{} bytes left after the frame", reader.len());
+    Ok(())
+}
+
+/// Packets with ids of any state and payloads up to `max_length` bytes.
+pub fn raw_packet(max_length: usize) -> impl Strategy<Value = RawPacket> {
+    (0u32..0x80, prop::collection::vec(any::<u8>(), 0..=max_length)).prop_map(|(packet_id, payload)| RawPacket::new(packet_id, payload))
+}
+
+/// Compression thresholds, including none and compressing every packet.
+pub fn compression_threshold() -> impl Strategy<Value = Option<usize>> {
+    prop_oneof![Just(None), Just(Some(0)), (1usize..1024).prop_map(Some)]
+}
+
+impl Arbitrary for Handshake {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        (prop_oneof![Just(PROTOCOL_VERSION), any::<i32>()], protocol_string(255), any::<u16>(), 1..=3)
+            .prop_map(|(protocol_version, server_address, server_port, next_state)| Handshake {
+                protocol_version: VarInt(protocol_version),
+                server_address,
+                server_port,
+                next_state: VarInt(next_state),
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for StatusResponse {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        protocol_string(1024).prop_map(|json| StatusResponse { json }).boxed()
+    }
+}
+
+impl Arbitrary for PingPong {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        any::<i64>().prop_map(|payload| PingPong { payload }).boxed()
+    }
+}
+
+impl Arbitrary for KeepAlive {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        any::<i64>().prop_map(|id| KeepAlive { id }).boxed()
+    }
+}
+
+impl Arbitrary for KnownPack {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        (protocol_string(32), protocol_string(32), protocol_string(16))
+            .prop_map(|(namespace, id, version)| KnownPack { namespace, id, version })
+            .boxed()
+    }
+}
@@ -0,0 +1,140 @@
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+mod uring;
+
+use std::io;
+use std::net::{Shutdown, SocketAddr};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use async_std::io::{Read, Write};
+use async_std::net::{TcpListener, TcpStream};
+use dolls_config::IoBackend;
+use log::warn;
+
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+pub use uring::*;
+
+/// Whether this build and kernel can use `backend`, with the reason when not.
+pub fn backend_support(backend: IoBackend) -> Result<(), String> {
+    match backend {
+        IoBackend::AsyncStd => Ok(()),
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        IoBackend::IoUring => uring_support().map_err(|err| format!("io_uring is unavailable: {}", err)),
+        #[cfg(not(all(target_os = "linux", feature = "io-uring")))]
+        IoBackend::IoUring => Err("this build has no io_uring support, see the `io-uring` feature".to_string()),
+    }
+}
+
+/// A listening socket of either backend.
+#[derive(Debug)]
+pub enum Listener {
+    Tcp(TcpListener),
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    Uring(UringListener),
+}
+
+impl Listener {
+    /// Moves a bound listener to `backend`, keeping it on async-std if the backend is unavailable.
+    pub fn new(listener: TcpListener, backend: IoBackend) -> Self {
+        if let Err(reason) = backend_support(backend) {
+            warn!("Falling back to the async-std backend: {}", reason);
+            return Listener::Tcp(listener);
+        }
+        match backend {
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            IoBackend::IoUring => Listener::Uring(UringListener::new(listener)),
+            _ => Listener::Tcp(listener),
+        }
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        match self {
+            Listener::Tcp(listener) => listener.local_addr(),
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            Listener::Uring(listener) => listener.local_addr(),
+        }
+    }
+
+    pub async fn accept(&mut self) -> io::Result<Stream> {
+        match self {
+            Listener::Tcp(listener) => Ok(Stream::Tcp(listener.accept().await?.0)),
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            Listener::Uring(listener) => Ok(Stream::Uring(listener.accept().await?)),
+        }
+    }
+}
+
+/// A connection accepted by a [`Listener`]. Clones share the socket, as clones of a `TcpStream` do.
+#[derive(Debug, Clone)]
+pub enum Stream {
+    Tcp(TcpStream),
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    Uring(UringStream),
+}
+
+impl Stream {
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        match self {
+            Stream::Tcp(stream) => stream.peer_addr(),
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            Stream::Uring(stream) => stream.peer_addr(),
+        }
+    }
+
+    pub fn set_nodelay(&self, nodelay: bool) -> io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.set_nodelay(nodelay),
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            Stream::Uring(stream) => stream.set_nodelay(nodelay),
+        }
+    }
+
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.shutdown(how),
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            Stream::Uring(stream) => stream.shutdown(how),
+        }
+    }
+}
+
+impl From<TcpStream> for Stream {
+    fn from(stream: TcpStream) -> Self {
+        Stream::Tcp(stream)
+    }
+}
+
+impl Read for Stream {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Stream::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            Stream::Uring(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Stream::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            Stream::Uring(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Stream::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            Stream::Uring(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Stream::Tcp(stream) => Pin::new(stream).poll_close(cx),
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            Stream::Uring(stream) => Pin::new(stream).poll_close(cx),
+        }
+    }
+}
@@ -0,0 +1,408 @@
+use std::fmt;
+use std::future::poll_fn;
+use std::io::{self, Read as _, Write as _};
+use std::net::{Shutdown, SocketAddr};
+use std::os::fd::{AsRawFd, FromRawFd, IntoRawFd};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::{ready, Context, Poll, Waker};
+use async_std::io::{Read, Write};
+use async_std::net::TcpListener;
+use io_uring::{opcode, squeue, types, IoUring, Probe};
+use log::error;
+use once_cell::sync::Lazy;
+
+/// Submission queue entries of the shared ring, the completion queue holds twice as many.
+const RING_ENTRIES: u32 = 1024;
+/// Largest read or write handed to the kernel at once.
+const MAX_TRANSFER: usize = 64 * 1024;
+/// Set in the user data of cancellations, which hold a reference to the operation they cancel until they
+/// complete. Operations are aligned, so the bit is free otherwise.
+const CANCEL_TAG: u64 = 1;
+const _: () = assert!(std::mem::align_of::<Operation>() > CANCEL_TAG as usize);
+
+static DRIVER: Lazy<io::Result<&'static Driver>> = Lazy::new(Driver::start);
+
+/// Starts the shared ring unless it runs already, failing if the kernel lacks what the backend needs.
+pub(crate) fn uring_support() -> io::Result<()> {
+    driver().map(|_| ())
+}
+
+fn driver() -> io::Result<&'static Driver> {
+    match &*DRIVER {
+        Ok(driver) if driver.stopped.load(Ordering::Acquire) => Err(io::Error::other("the io_uring driver stopped")),
+        Ok(driver) => Ok(driver),
+        Err(err) => Err(io::Error::new(err.kind(), err.to_string())),
+    }
+}
+
+/// The ring shared by every io_uring socket, completions are reaped by a thread of its own. The user data of
+/// an entry is a reference to its [`Operation`] given to the kernel, tagged with [`CANCEL_TAG`] for cancellations.
+struct Driver {
+    ring: IoUring,
+    /// Held while pushing to the submission queue.
+    submissions: Mutex<()>,
+    /// Held while consuming the completion queue.
+    completions: Mutex<()>,
+    stopped: AtomicBool,
+}
+
+impl Driver {
+    fn start() -> io::Result<&'static Driver> {
+        let ring = IoUring::new(RING_ENTRIES)?;
+        let mut probe = Probe::new();
+        ring.submitter().register_probe(&mut probe)?;
+        for code in [opcode::Accept::CODE, opcode::Recv::CODE, opcode::Send::CODE, opcode::AsyncCancel::CODE] {
+            if !probe.is_supported(code) {
+                return Err(io::Error::new(io::ErrorKind::Unsupported, format!("the kernel lacks io_uring opcode {}", code)));
+            }
+        }
+        let driver: &'static Driver = Box::leak(Box::new(Driver {
+            ring,
+            submissions: Mutex::new(()),
+            completions: Mutex::new(()),
+            stopped: AtomicBool::new(false),
+        }));
+        std::thread::Builder::new().name("dolls-io-uring".to_string()).spawn(move || driver.run())?;
+        Ok(driver)
+    }
+
+    fn run(&self) {
+        loop {
+            if let Err(err) = self.ring.submit_and_wait(1) {
+                if !transient(&err) {
+                    error!("The io_uring driver stopped, sockets using it hang: {}", err);
+                    self.stopped.store(true, Ordering::Release);
+                    return;
+                }
+            }
+            self.reap(self.completions.lock().unwrap());
+        }
+    }
+
+    /// Completes the operations whose results are in the completion queue.
+    fn reap(&self, _completions: MutexGuard<()>) {
+        // SAFETY: the guard keeps other threads from consuming completions at the same time.
+        for entry in unsafe { self.ring.completion_shared() } {
+            let user_data = entry.user_data();
+            // SAFETY: the reference was leaked by `submit` or `cancel` and every entry completes once.
+            let operation = unsafe { Arc::from_raw((user_data & !CANCEL_TAG) as *const Operation) };
+            if user_data & CANCEL_TAG == 0 {
+                operation.complete(entry.result());
+            }
+        }
+    }
+
+    /// Queues `entry` for `operation`, whose buffer and socket it may point into.
+    fn submit(&self, entry: squeue::Entry, operation: Arc<Operation>) -> io::Result<()> {
+        let user_data = Arc::into_raw(operation);
+        if let Err(err) = self.push(&entry.user_data(user_data as u64)) {
+            // SAFETY: the kernel never saw the entry, the reference is taken back.
+            drop(unsafe { Arc::from_raw(user_data) });
+            return Err(err);
+        }
+        self.enter()?;
+        // Sockets often finish right away, e.g. sends with room in the buffer, which spares a trip through the
+        // driver thread unless that is reaping already.
+        if let Ok(completions) = self.completions.try_lock() {
+            self.reap(completions);
+        }
+        Ok(())
+    }
+
+    /// Asks the kernel to end the operation early, it completes with `ECANCELED` unless it is done already.
+    fn cancel(&self, operation: &Arc<Operation>) {
+        if operation.state.lock().unwrap().result.is_some() {
+            return;
+        }
+        // The operation may complete before the kernel looks at the cancellation. The cancellation's own
+        // reference keeps its address from being reused by another operation meanwhile, which would be
+        // cancelled instead.
+        let reference = Arc::into_raw(operation.clone());
+        let entry = opcode::AsyncCancel::new(reference as u64).build().user_data(reference as u64 | CANCEL_TAG);
+        if let Err(err) = self.push(&entry) {
+            // SAFETY: the kernel never saw the entry, the reference is taken back.
+            drop(unsafe { Arc::from_raw(reference) });
+            error!("Failed to cancel an io_uring operation: {}", err);
+        } else if let Err(err) = self.enter() {
+            error!("Failed to cancel an io_uring operation: {}", err);
+        }
+    }
+
+    fn push(&self, entry: &squeue::Entry) -> io::Result<()> {
+        let _submissions = self.submissions.lock().unwrap();
+        // SAFETY: the guard serializes pushes, and entries only point into memory their operation owns.
+        while unsafe { self.ring.submission_shared().push(entry) }.is_err() {
+            self.enter()?;
+        }
+        Ok(())
+    }
+
+    /// Hands queued entries to the kernel. Entries a busy kernel refuses stay queued for the driver thread.
+    fn enter(&self) -> io::Result<()> {
+        match self.ring.submit() {
+            Err(err) if !transient(&err) => Err(err),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn transient(err: &io::Error) -> bool {
+    matches!(err.raw_os_error(), Some(libc::EINTR | libc::EBUSY | libc::EAGAIN))
+}
+
+/// One submitted operation, shared with the driver until it completes.
+struct Operation {
+    state: Mutex<OperationState>,
+}
+
+struct OperationState {
+    result: Option<i32>,
+    waker: Option<Waker>,
+    /// Read from or written to by the kernel until the operation completes.
+    buffer: Vec<u8>,
+    /// Keeps the descriptor open for the kernel even if every handle is dropped.
+    socket: Option<Arc<dyn Send + Sync>>,
+}
+
+impl Operation {
+    fn new(buffer: Vec<u8>, socket: Arc<dyn Send + Sync>) -> Arc<Self> {
+        Arc::new(Self {
+            state: Mutex::new(OperationState { result: None, waker: None, buffer, socket: Some(socket) }),
+        })
+    }
+
+    fn complete(&self, result: i32) {
+        let waker = {
+            let mut state = self.state.lock().unwrap();
+            state.result = Some(result);
+            state.socket = None;
+            state.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    /// Whether the operation sends `data` or the start of it.
+    fn sends_start_of(&self, data: &[u8]) -> bool {
+        data.starts_with(&self.state.lock().unwrap().buffer)
+    }
+
+    /// The result and the buffer once the operation completed.
+    fn poll(&self, cx: &mut Context<'_>) -> Poll<(i32, Vec<u8>)> {
+        let mut state = self.state.lock().unwrap();
+        match state.result {
+            Some(result) => Poll::Ready((result, std::mem::take(&mut state.buffer))),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Submits an operation unless `slot` holds one in flight, then waits for it.
+fn poll_operation(
+    slot: &mut Option<Arc<Operation>>,
+    cx: &mut Context<'_>,
+    submit: impl FnOnce() -> io::Result<(squeue::Entry, Arc<Operation>)>,
+) -> Poll<io::Result<(i32, Vec<u8>)>> {
+    let operation = match slot {
+        Some(operation) => operation.clone(),
+        None => {
+            let (entry, operation) = submit()?;
+            driver()?.submit(entry, operation.clone())?;
+            slot.insert(operation).clone()
+        }
+    };
+    let (result, buffer) = ready!(operation.poll(cx));
+    *slot = None;
+    match result {
+        result if result < 0 => Poll::Ready(Err(io::Error::from_raw_os_error(-result))),
+        result => Poll::Ready(Ok((result, buffer))),
+    }
+}
+
+fn cancel(slot: &mut Option<Arc<Operation>>) {
+    if let (Some(operation), Ok(driver)) = (slot.take(), driver()) {
+        driver.cancel(&operation);
+    }
+}
+
+/// A listener whose accepts complete through the shared io_uring.
+pub struct UringListener {
+    listener: Arc<std::net::TcpListener>,
+    accept: Option<Arc<Operation>>,
+}
+
+impl UringListener {
+    pub fn new(listener: TcpListener) -> Self {
+        // SAFETY: the descriptor is taken over from the async-std listener.
+        let listener = unsafe { std::net::TcpListener::from_raw_fd(listener.into_raw_fd()) };
+        Self { listener: Arc::new(listener), accept: None }
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub async fn accept(&mut self) -> io::Result<UringStream> {
+        poll_fn(|cx| self.poll_accept(cx)).await
+    }
+
+    fn poll_accept(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<UringStream>> {
+        let listener = &self.listener;
+        let (fd, _) = ready!(poll_operation(&mut self.accept, cx, || {
+            let entry = opcode::Accept::new(types::Fd(listener.as_raw_fd()), std::ptr::null_mut(), std::ptr::null_mut())
+                .flags(libc::SOCK_CLOEXEC | libc::SOCK_NONBLOCK)
+                .build();
+            Ok((entry, Operation::new(Vec::new(), listener.clone())))
+        }))?;
+        // SAFETY: the kernel returned a new descriptor of the accepted connection.
+        Poll::Ready(Ok(UringStream::new(unsafe { std::net::TcpStream::from_raw_fd(fd) })))
+    }
+}
+
+impl Drop for UringListener {
+    fn drop(&mut self) {
+        cancel(&mut self.accept);
+    }
+}
+
+impl fmt::Debug for UringListener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UringListener").field("listener", &self.listener).finish_non_exhaustive()
+    }
+}
+
+/// A connection whose reads and writes complete through the shared io_uring. Each clone has an operation of
+/// each kind in flight at most, which carries on when a read or write future is dropped and is picked up by
+/// the next call.
+///
+/// Reads and writes are tried on the non-blocking socket first and only go through the ring if they would
+/// block, a round trip to the driver thread costs more than a send that fits in the socket buffer.
+pub struct UringStream {
+    socket: Arc<std::net::TcpStream>,
+    read: Option<Arc<Operation>>,
+    write: Option<Arc<Operation>>,
+    /// Bytes received but not read yet, from `received_start` on.
+    received: Vec<u8>,
+    received_start: usize,
+    /// Buffer of the last send, reused for the next one.
+    sent: Vec<u8>,
+}
+
+impl UringStream {
+    /// Takes over a non-blocking stream.
+    fn new(stream: std::net::TcpStream) -> Self {
+        Self { socket: Arc::new(stream), read: None, write: None, received: Vec::new(), received_start: 0, sent: Vec::new() }
+    }
+
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.peer_addr()
+    }
+
+    pub fn set_nodelay(&self, nodelay: bool) -> io::Result<()> {
+        self.socket.set_nodelay(nodelay)
+    }
+
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        self.socket.shutdown(how)
+    }
+}
+
+impl Clone for UringStream {
+    fn clone(&self) -> Self {
+        Self { socket: self.socket.clone(), read: None, write: None, received: Vec::new(), received_start: 0, sent: Vec::new() }
+    }
+}
+
+impl Drop for UringStream {
+    fn drop(&mut self) {
+        cancel(&mut self.read);
+        cancel(&mut self.write);
+    }
+}
+
+impl fmt::Debug for UringStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UringStream").field("socket", &self.socket).finish_non_exhaustive()
+    }
+}
+
+impl Read for UringStream {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if this.received_start == this.received.len() && !buf.is_empty() {
+            if this.read.is_none() {
+                match (&*this.socket).read(buf) {
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => {}
+                    result => return Poll::Ready(result),
+                }
+            }
+            // Everything was read, the buffer is reused for the next receive.
+            let mut buffer = std::mem::take(&mut this.received);
+            this.received_start = 0;
+            let socket = &this.socket;
+            let (length, buffer) = ready!(poll_operation(&mut this.read, cx, || {
+                buffer.clear();
+                buffer.resize(buf.len().min(MAX_TRANSFER), 0);
+                let entry = opcode::Recv::new(types::Fd(socket.as_raw_fd()), buffer.as_mut_ptr(), buffer.len() as u32).build();
+                Ok((entry, Operation::new(buffer, socket.clone())))
+            }))?;
+            this.received = buffer;
+            this.received.truncate(length as usize);
+            this.received_start = 0;
+        }
+        let unread = &this.received[this.received_start..];
+        let length = unread.len().min(buf.len());
+        buf[..length].copy_from_slice(&unread[..length]);
+        this.received_start += length;
+        Poll::Ready(Ok(length))
+    }
+}
+
+impl Write for UringStream {
+    /// A write whose future was dropped still completes. The next call reports it if `buf` starts with the
+    /// bytes it sends, as when retrying with the same buffer, and otherwise waits for it before sending `buf`.
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        if let Some(operation) = this.write.clone().filter(|operation| !operation.sends_start_of(buf)) {
+            // The caller gave up on these bytes, but the kernel may have sent them already.
+            let (_, buffer) = ready!(operation.poll(cx));
+            this.write = None;
+            this.sent = buffer;
+        }
+        if this.write.is_none() {
+            match (&*this.socket).write(buf) {
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {}
+                result => return Poll::Ready(result),
+            }
+        }
+        let (socket, sent) = (&this.socket, &mut this.sent);
+        let (length, buffer) = ready!(poll_operation(&mut this.write, cx, || {
+            let mut buffer = std::mem::take(sent);
+            buffer.clear();
+            buffer.extend_from_slice(&buf[..buf.len().min(MAX_TRANSFER)]);
+            let entry = opcode::Send::new(types::Fd(socket.as_raw_fd()), buffer.as_ptr(), buffer.len() as u32)
+                .flags(libc::MSG_NOSIGNAL)
+                .build();
+            Ok((entry, Operation::new(buffer, socket.clone())))
+        }))?;
+        this.sent = buffer;
+        Poll::Ready(Ok(length as usize))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(self.socket.shutdown(Shutdown::Write))
+    }
+}
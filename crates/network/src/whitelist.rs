@@ -0,0 +1,103 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use anyhow::Context;
+use once_cell::sync::Lazy;
+use serde_json::Value;
+use dolls_core::datatype::Uuid;
+use dolls_core::text::TextComponent;
+use crate::prelude::ops;
+
+/// Whitelist file in the working directory, where vanilla keeps it.
+pub const WHITELIST_FILE: &str = "whitelist.json";
+
+/// A player allowed to join while the whitelist is on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WhitelistEntry {
+    pub uuid: Uuid,
+    pub name: String,
+}
+
+/// Players in `whitelist.json`, in vanilla's format, and whether only they may join.
+#[derive(Debug, Clone, Default)]
+pub struct Whitelist {
+    path: Option<PathBuf>,
+    pub enabled: bool,
+    entries: Vec<WhitelistEntry>,
+}
+
+impl Whitelist {
+    /// Reads the list, an empty one if the file does not exist yet. Malformed entries are skipped.
+    pub fn load(path: impl AsRef<Path>, enabled: bool) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let mut whitelist = Self { path: Some(path.to_path_buf()), enabled, entries: Vec::new() };
+        whitelist.reload()?;
+        Ok(whitelist)
+    }
+
+    /// Reads the file again, e.g. after it was edited by hand.
+    pub fn reload(&mut self) -> anyhow::Result<()> {
+        let Some(path) = &self.path else { return Ok(()) };
+        if !path.exists() {
+            self.entries.clear();
+            return Ok(());
+        }
+        let content = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let json: Value = serde_json::from_str(&content).with_context(|| format!("Malformed {}", path.display()))?;
+        self.entries = json.as_array().into_iter().flatten()
+            .filter_map(|entry| Some(WhitelistEntry {
+                uuid: entry.get("uuid")?.as_str()?.parse().ok()?,
+                name: entry.get("name").and_then(Value::as_str).unwrap_or_default().to_string(),
+            }))
+            .collect();
+        Ok(())
+    }
+
+    /// Writes the list back to the file it was loaded from, lists never loaded are not saved.
+    pub fn save(&self) -> anyhow::Result<()> {
+        let Some(path) = &self.path else { return Ok(()) };
+        let entries = self.entries.iter()
+            .map(|entry| serde_json::json!({ "uuid": entry.uuid.hyphenated().to_string(), "name": entry.name }))
+            .collect::<Vec<_>>();
+        fs::write(path, serde_json::to_string_pretty(&entries)?).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    pub fn contains(&self, uuid: Uuid) -> bool {
+        self.entries.iter().any(|entry| entry.uuid == uuid)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &WhitelistEntry> {
+        self.entries.iter()
+    }
+
+    /// Returns whether the player was not on the list before.
+    pub fn add(&mut self, entry: WhitelistEntry) -> bool {
+        if self.contains(entry.uuid) {
+            return false;
+        }
+        self.entries.push(entry);
+        true
+    }
+
+    pub fn remove(&mut self, uuid: Uuid) -> Option<WhitelistEntry> {
+        let index = self.entries.iter().position(|entry| entry.uuid == uuid)?;
+        Some(self.entries.remove(index))
+    }
+
+    /// Whether the player may join: the whitelist is off, it is on the list or it is an operator.
+    pub fn allows(&self, uuid: Uuid) -> bool {
+        !self.enabled || self.contains(uuid) || ops().read().unwrap().get(uuid).is_some()
+    }
+}
+
+static WHITELIST: Lazy<RwLock<Whitelist>> = Lazy::new(|| RwLock::new(Whitelist::default()));
+
+/// Whitelist of the running server, loaded from `whitelist.json` at startup.
+pub fn whitelist() -> &'static RwLock<Whitelist> {
+    &WHITELIST
+}
+
+/// Disconnect reason of players the whitelist keeps out.
+pub fn not_whitelisted_reason() -> TextComponent {
+    TextComponent::translatable("multiplayer.disconnect.not_whitelisted", vec![]).fallback("You are not white-listed on this server!")
+}
@@ -0,0 +1,59 @@
+mod common;
+
+use dolls_core::datatype::BlockPos;
+use dolls_entities::prelude::{entities, AiPath, EntityType, GoalState, Vec3};
+use dolls_network::prelude::{player_position, set_ai_debug, tick_ai_debug, ClientboundPacketType};
+use common::{TestClient, TestServer};
+
+#[test]
+fn reported_ai_is_kept_per_entity() {
+    let mut entities = entities().write().unwrap();
+    // Far from the players of the other test, who would be shown its path.
+    let id = entities.spawn(EntityType::ZOMBIE, None, Vec3::new(10_000.0, 64.0, 10_000.0));
+    entities.report_goals(id, vec![GoalState::new(8, "look_at_player", false), GoalState::new(2, "melee_attack", true)]);
+    entities.report_path(id, Some(AiPath::new(vec![BlockPos::new(0, 64, 0), BlockPos::new(1, 64, 0)])));
+    entities.advance_path(id, 5);
+
+    let info = entities.ai_debug_info(id).unwrap();
+    assert_eq!(info.goals.iter().map(|goal| goal.priority).collect::<Vec<_>>(), vec![2, 8]);
+    assert_eq!(info.running_goals().map(|goal| goal.name.as_str()).collect::<Vec<_>>(), vec!["melee_attack"]);
+    let path = info.path.as_ref().unwrap();
+    assert!(path.is_done());
+    assert_eq!(path.target(), Some(BlockPos::new(1, 64, 0)));
+
+    entities.remove(id);
+    entities.report_goals(id, vec![GoalState::new(1, "float", true)]);
+    assert!(entities.ai_debug_info(id).is_none());
+}
+
+#[test]
+fn paths_are_drawn_for_players_with_ai_debug_on() {
+    async_std::task::block_on(async {
+        let server = TestServer::start().await;
+        let mut client = TestClient::connect(server.address).await;
+        client.join("Alice").await;
+        let player = server.server.connections().find_player("Alice").unwrap();
+        let position = player_position(&player).unwrap().vec3();
+
+        let id = {
+            let mut entities = entities().write().unwrap();
+            let id = entities.spawn(EntityType::ZOMBIE, None, position);
+            let start = position.block_pos();
+            let nodes = (1..=3).map(|step| BlockPos::new(start.x + step, start.y, start.z)).collect();
+            entities.report_path(id, Some(AiPath::new(nodes)));
+            id
+        };
+        assert_eq!(tick_ai_debug(server.server.connections()), 0);
+
+        set_ai_debug(&player, true);
+        assert_eq!(tick_ai_debug(server.server.connections()), 3);
+        client.receive_until(ClientboundPacketType::Particle).await;
+        client.expect(ClientboundPacketType::Particle).await;
+        client.expect(ClientboundPacketType::Particle).await;
+
+        set_ai_debug(&player, false);
+        assert_eq!(tick_ai_debug(server.server.connections()), 0);
+        entities().write().unwrap().remove(id);
+        server.stop().await;
+    });
+}
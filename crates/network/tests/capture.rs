@@ -0,0 +1,48 @@
+mod common;
+
+use std::time::{Duration, Instant};
+use dolls_config::ServerConfig;
+use dolls_network::prelude::{replay_capture, Capture, ConnectionState, FrameDirection, CAPTURE_EXTENSION};
+use common::{TestClient, TestServer};
+
+#[test]
+fn captured_status_ping_replays() {
+    async_std::task::block_on(async {
+        let directory = std::env::temp_dir().join(format!("dolls-capture-{}", std::process::id()));
+        let mut config = ServerConfig::default();
+        config.network.capture_directory = Some(directory.clone());
+        let server = TestServer::start_with(config).await;
+
+        let mut client = TestClient::connect(server.address).await;
+        client.status().await;
+        client.disconnect().await;
+
+        // The capture is complete once the server noticed the disconnect.
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let capture = loop {
+            let path = std::fs::read_dir(&directory).unwrap()
+                .map(|entry| entry.unwrap().path())
+                .find(|path| path.extension().is_some_and(|extension| extension == CAPTURE_EXTENSION));
+            if let Some(capture) = path.and_then(|path| Capture::open(path).ok()).filter(|capture| capture.packets.len() == 5) {
+                break capture;
+            }
+            assert!(Instant::now() < deadline, "The capture was not written");
+            async_std::task::sleep(Duration::from_millis(20)).await;
+        };
+        let packets = capture.packets.iter().map(|captured| (captured.direction, captured.state, captured.packet.packet_id)).collect::<Vec<_>>();
+        assert_eq!(packets, [
+            (FrameDirection::Inbound, ConnectionState::Handshaking, 0x00),
+            (FrameDirection::Inbound, ConnectionState::Status, 0x00),
+            (FrameDirection::Outbound, ConnectionState::Status, 0x00),
+            (FrameDirection::Inbound, ConnectionState::Status, 0x01),
+            (FrameDirection::Outbound, ConnectionState::Status, 0x01),
+        ]);
+
+        let report = replay_capture(&capture, server.address).await.unwrap();
+        assert_eq!((report.sent, report.received), (3, 2));
+        assert!(report.mismatches.is_empty(), "{:?}", report.mismatches);
+
+        server.stop().await;
+        let _ = std::fs::remove_dir_all(&directory);
+    });
+}
@@ -0,0 +1,150 @@
+//! A scripted headless client for smoke tests against the in-process [`TestServer`].
+
+// Every test binary compiles the helpers, most use only some of them.
+#![allow(dead_code)]
+
+use std::net::SocketAddr;
+use std::time::Duration;
+use async_std::net::TcpStream;
+use dolls_core::datatype::{decode_from_slice, VarInt};
+use dolls_network::prelude::{ClientboundPacketType, ConnectionState, KnownPack, PacketHandler, PacketType, PingPong, RawPacket,
+    StatusResponse, PROTOCOL_VERSION};
+use uuid::Uuid;
+
+pub use dolls_network::testing::TestServer;
+
+/// How long the client waits for the next packet before failing the test.
+pub const RECEIVE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Encodes fields back to back into a packet payload.
+#[macro_export]
+macro_rules! payload {
+    ($($field:expr),* $(,)?) => {{
+        let mut payload = Vec::new();
+        $(dolls_core::datatype::Encode::encode(&$field, &mut payload).unwrap();)*
+        payload
+    }};
+}
+
+/// A client which walks through the protocol states and checks what the server answers.
+pub struct TestClient {
+    connection: PacketHandler,
+    pub state: ConnectionState,
+    /// Every clientbound packet received so far, in order.
+    pub received: Vec<ClientboundPacketType>,
+}
+
+impl TestClient {
+    pub async fn connect(address: SocketAddr) -> Self {
+        let stream = TcpStream::connect(address).await.expect("Failed to connect to the test server");
+        Self { connection: PacketHandler::new(stream), state: ConnectionState::Handshaking, received: Vec::new() }
+    }
+
+    pub async fn send(&mut self, packet_type: PacketType, payload: Vec<u8>) {
+        assert_eq!(packet_type.state(), self.state, "{} is not sent in {:?}", packet_type.name(), self.state);
+        let packet = RawPacket::new(packet_type.id(), payload);
+        self.connection.write_packet(&packet).await.expect("Failed to send a packet");
+    }
+
+    /// The next packet, failing the test on timeouts and ids unknown in the current state.
+    pub async fn receive(&mut self) -> (ClientboundPacketType, RawPacket) {
+        let packet = async_std::future::timeout(RECEIVE_TIMEOUT, self.connection.next_packet()).await
+            .unwrap_or_else(|_| panic!("No packet within {:?} in {:?}, received {:?}", RECEIVE_TIMEOUT, self.state, self.received))
+            .expect("Connection closed");
+        let packet_type = ClientboundPacketType::from_parts(self.state, packet.packet_id)
+            .unwrap_or_else(|| panic!("Unknown packet 0x{:02X} in {:?}", packet.packet_id, self.state));
+        self.received.push(packet_type);
+        (packet_type, packet)
+    }
+
+    /// Asserts that the server closes the connection without sending anything else.
+    pub async fn expect_closed(&mut self) {
+        let next = async_std::future::timeout(RECEIVE_TIMEOUT, self.connection.next_packet()).await
+            .unwrap_or_else(|_| panic!("The connection is still open in {:?}", self.state));
+        assert!(next.is_err(), "Received packet 0x{:02X} instead of the end of the connection", next.unwrap().packet_id);
+    }
+
+    /// Receives the next packet and asserts its type.
+    pub async fn expect(&mut self, expected: ClientboundPacketType) -> RawPacket {
+        let (packet_type, packet) = self.receive().await;
+        assert_eq!(packet_type, expected, "Unexpected packet in {:?}, received {:?}", self.state, self.received);
+        packet
+    }
+
+    /// Receives packets until one of type `until`, returning the types before it.
+    pub async fn receive_until(&mut self, until: ClientboundPacketType) -> (Vec<ClientboundPacketType>, RawPacket) {
+        let mut before = Vec::new();
+        loop {
+            let (packet_type, packet) = self.receive().await;
+            if packet_type == until {
+                return (before, packet);
+            }
+            before.push(packet_type);
+        }
+    }
+
+    pub async fn handshake(&mut self, next_state: ConnectionState) {
+        let port = self.connection.get_ref().peer_addr().unwrap().port();
+        let next = match next_state {
+            ConnectionState::Status => 1,
+            ConnectionState::Login => 2,
+            state => panic!("Cannot switch to {:?} from a handshake", state),
+        };
+        self.send(PacketType::Handshake, payload!(VarInt(PROTOCOL_VERSION), "localhost", port, VarInt(next))).await;
+        self.state = next_state;
+    }
+
+    /// Requests the status JSON and checks that a ping is answered with the same payload.
+    pub async fn status(&mut self) -> serde_json::Value {
+        self.handshake(ConnectionState::Status).await;
+        self.send(PacketType::StatusRequest, Vec::new()).await;
+        let response: StatusResponse = decode_from_slice(&self.expect(ClientboundPacketType::StatusResponse).await.payload).unwrap();
+        self.send(PacketType::PingRequest, payload!(0x0123_4567_89AB_CDEFi64)).await;
+        let pong: PingPong = decode_from_slice(&self.expect(ClientboundPacketType::PongResponse).await.payload).unwrap();
+        assert_eq!(pong.payload, 0x0123_4567_89AB_CDEF);
+        serde_json::from_str(&response.json).expect("Status response is not JSON")
+    }
+
+    /// Logs in as an offline player, following Set Compression if the server sends it.
+    pub async fn login(&mut self, username: &str) -> Uuid {
+        self.handshake(ConnectionState::Login).await;
+        self.send(PacketType::LoginStart, payload!(username, Uuid::nil())).await;
+        let (packet_type, mut packet) = self.receive().await;
+        if packet_type == ClientboundPacketType::SetCompression {
+            let threshold: VarInt = decode_from_slice(&packet.payload).unwrap();
+            self.connection.set_compression(usize::try_from(threshold.0).ok());
+            packet = self.expect(ClientboundPacketType::LoginSuccess).await;
+        } else {
+            assert_eq!(packet_type, ClientboundPacketType::LoginSuccess, "Unexpected packet during login");
+        }
+        let uuid = decode_from_slice::<Uuid>(&packet.payload[..16]).unwrap();
+        self.send(PacketType::LoginAcknowledged, Vec::new()).await;
+        self.state = ConnectionState::Configuration;
+        uuid
+    }
+
+    /// Agrees on the core pack, receives the registries and finishes configuration.
+    /// Returns how many Registry Data packets arrived.
+    pub async fn configure(&mut self) -> usize {
+        let known_packs = self.expect(ClientboundPacketType::ClientboundKnownPacks).await;
+        let packs: Vec<KnownPack> = decode_from_slice(&known_packs.payload).unwrap();
+        self.send(PacketType::ServerboundKnownPacks, payload!(packs)).await;
+        let (before, _) = self.receive_until(ClientboundPacketType::FinishConfiguration).await;
+        assert!(before.iter().all(|packet_type| *packet_type == ClientboundPacketType::RegistryData), "Unexpected configuration packets {:?}", before);
+        self.send(PacketType::AcknowledgeFinishConfiguration, Vec::new()).await;
+        self.state = ConnectionState::Play;
+        before.len()
+    }
+
+    /// Logs in, configures and waits for the Play Login packet.
+    pub async fn join(&mut self, username: &str) -> Uuid {
+        let uuid = self.login(username).await;
+        self.configure().await;
+        self.expect(ClientboundPacketType::Login).await;
+        uuid
+    }
+
+    pub async fn disconnect(self) {
+        let _ = self.connection.get_ref().shutdown(std::net::Shutdown::Both);
+    }
+}
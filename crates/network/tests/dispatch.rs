@@ -0,0 +1,12 @@
+#![cfg(feature = "static-dispatch")]
+
+use dolls_network::prelude::init_packet_processors;
+
+#[test]
+fn every_registered_processor_is_in_the_static_table() {
+    async_std::task::block_on(async {
+        if let Err(err) = init_packet_processors().await {
+            panic!("{:#}", err);
+        }
+    });
+}
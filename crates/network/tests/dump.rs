@@ -0,0 +1,16 @@
+use dolls_network::prelude::hex_dump;
+
+#[test]
+fn hex_dump_shows_offsets_hex_and_ascii() {
+    let dump = hex_dump(b"\x00\x09localhost\x63\xdd\x01", 256);
+    assert_eq!(dump, "0000  00 09 6c 6f 63 61 6c 68 6f 73 74 63 dd 01        |..localhostc..|");
+}
+
+#[test]
+fn hex_dump_is_bounded() {
+    let dump = hex_dump(&[0x41; 40], 32);
+    assert_eq!(dump.lines().count(), 3);
+    assert!(dump.starts_with("0000  41 41"));
+    assert!(dump.contains("\n0010  41"));
+    assert!(dump.ends_with("\n... 8 more bytes"));
+}
@@ -0,0 +1,46 @@
+use dolls_core::item::{items, ItemRegistry, ItemStack};
+use dolls_entities::prelude::{entities, EntityType, MetadataValue, Vec3};
+use dolls_network::prelude::{save_all_entities, spawn_loaded_entities};
+use dolls_world::prelude::{ChunkPos, Dimension, RegionStorage, World};
+
+const ITEM_REPORT: &str = r#"{"minecraft:item": {"entries": {"minecraft:air": {"protocol_id": 0}, "minecraft:stone": {"protocol_id": 1}}}}"#;
+
+#[test]
+fn dropped_items_survive_their_chunk_unloading() {
+    *items().write().unwrap() = ItemRegistry::from_report(ITEM_REPORT).unwrap();
+    let directory = std::env::temp_dir().join(format!("dolls-entities-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&directory);
+    let dimension = Dimension::Nether;
+    *dimension.world().write().unwrap() = World::new(dimension.min_y(), dimension.height())
+        .with_entity_storage(RegionStorage::new(&directory));
+    let chunk = ChunkPos::new(-1, 2);
+    dimension.world().write().unwrap().load_or_create_chunk(chunk).unwrap();
+
+    let stack = MetadataValue::Slot(ItemStack::new(1, 5));
+    let uuid = {
+        let mut entities = entities().write().unwrap();
+        let id = entities.spawn(EntityType::ITEM, None, Vec3::new(-7.5, 64.0, 40.25));
+        let entity = entities.get_mut(id).unwrap();
+        entity.dimension = dimension;
+        entity.velocity = Vec3::new(0.0, -0.04, 0.0);
+        entity.metadata.set(8, stack.clone());
+        entity.uuid()
+    };
+    save_all_entities();
+
+    // Unloading drops the entity with its chunk, loading the chunk again brings it back.
+    let id = entities().read().unwrap().by_uuid(uuid).unwrap().id();
+    entities().write().unwrap().remove(id);
+    dimension.world().write().unwrap().unload_chunk(chunk).unwrap();
+    dimension.world().write().unwrap().load_or_create_chunk(chunk).unwrap();
+    spawn_loaded_entities(dimension);
+
+    let entities = entities().read().unwrap();
+    let entity = entities.by_uuid(uuid).expect("The item was not loaded");
+    assert_ne!(entity.id(), id);
+    assert_eq!(entity.entity_type(), EntityType::ITEM);
+    assert_eq!(entity.dimension, dimension);
+    assert_eq!(entity.position(), Vec3::new(-7.5, 64.0, 40.25));
+    assert_eq!(entity.velocity, Vec3::new(0.0, -0.04, 0.0));
+    assert_eq!(entity.metadata.get(8), Some(&stack));
+}
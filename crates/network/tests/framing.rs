@@ -0,0 +1,101 @@
+use std::io::Write;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use async_std::io::Read;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use dolls_core::datatype::{Encode, VarInt};
+use dolls_network::prelude::{PacketHandler, MAX_DECOMPRESSED_LENGTH, MAX_FRAME_LENGTH};
+
+fn frame(content: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::new();
+    VarInt(content.len() as i32).encode(&mut frame).unwrap();
+    frame.extend_from_slice(content);
+    frame
+}
+
+/// Hands out one byte per read, as a slow connection may.
+struct Trickle<'a>(&'a [u8]);
+
+impl Read for Trickle<'_> {
+    fn poll_read(mut self: Pin<&mut Self>, _: &mut Context<'_>, buffer: &mut [u8]) -> Poll<std::io::Result<usize>> {
+        let Some((&byte, rest)) = self.0.split_first() else {
+            return Poll::Ready(Ok(0));
+        };
+        buffer[0] = byte;
+        self.0 = rest;
+        Poll::Ready(Ok(1))
+    }
+}
+
+#[test]
+fn frames_are_read_whole_or_across_reads() {
+    let packets: Vec<_> = [0, 1, 300, 20_000].into_iter()
+        .enumerate()
+        .map(|(index, size)| (index as u32, vec![index as u8; size]))
+        .collect();
+    let stream: Vec<u8> = packets.iter()
+        .flat_map(|(packet_id, payload)| {
+            let mut content = Vec::new();
+            VarInt(*packet_id as i32).encode(&mut content).unwrap();
+            content.extend_from_slice(payload);
+            frame(&content)
+        })
+        .collect();
+    async_std::task::block_on(async {
+        let mut whole = PacketHandler::new(stream.as_slice());
+        let mut trickled = PacketHandler::new(Trickle(&stream));
+        for (packet_id, payload) in &packets {
+            for handler_packet in [whole.next_packet().await.unwrap(), trickled.next_packet().await.unwrap()] {
+                assert_eq!((&handler_packet.packet_id, &handler_packet.payload), (packet_id, payload));
+            }
+        }
+        let closed = whole.next_packet().await.unwrap_err();
+        assert_eq!(closed.downcast_ref::<std::io::Error>().map(std::io::Error::kind), Some(std::io::ErrorKind::UnexpectedEof));
+        assert!(trickled.next_packet().await.is_err());
+    });
+}
+
+#[test]
+fn oversized_frames_are_rejected_before_reading() {
+    let mut stream = Vec::new();
+    VarInt(MAX_FRAME_LENGTH as i32 + 1).encode(&mut stream).unwrap();
+    let error = async_std::task::block_on(PacketHandler::new(&mut stream.as_slice()).next_packet()).unwrap_err();
+    assert!(error.to_string().contains("over the limit"), "{}", error);
+}
+
+#[test]
+fn compressed_frames_must_inflate_to_their_length() {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&[0x05, 1, 2, 3]).unwrap();
+    let compressed = encoder.finish().unwrap();
+    let compressed_frame = |data_length: u32| {
+        let mut content = Vec::new();
+        VarInt(data_length as i32).encode(&mut content).unwrap();
+        content.extend_from_slice(&compressed);
+        frame(&content)
+    };
+    async_std::task::block_on(async {
+        let stream = compressed_frame(4);
+        let mut reader = stream.as_slice();
+        let mut handler = PacketHandler::new(&mut reader);
+        handler.set_compression(Some(0));
+        let packet = handler.next_packet().await.unwrap();
+        assert_eq!((packet.packet_id, packet.payload), (0x05, vec![1, 2, 3]));
+
+        for data_length in [8, MAX_DECOMPRESSED_LENGTH + 1] {
+            let stream = compressed_frame(data_length);
+            let mut reader = stream.as_slice();
+            let mut handler = PacketHandler::new(&mut reader);
+            handler.set_compression(Some(0));
+            assert!(handler.next_packet().await.is_err());
+        }
+
+        let stream = compressed_frame(4);
+        let mut reader = stream.as_slice();
+        let mut handler = PacketHandler::new(&mut reader);
+        handler.set_compression(Some(256));
+        let error = handler.next_packet().await.unwrap_err();
+        assert!(error.to_string().contains("Badly compressed packet"), "{}", error);
+    });
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use std::io;
+use std::time::Duration;
+use dolls_config::ServerConfig;
+use dolls_network::prelude::{bind_listeners, ipv6_dual_stack, is_listener_gone, validate_listeners, AcceptBackoff, ACCEPT_BACKOFF, MAX_ACCEPT_BACKOFF};
+use common::{TestClient, TestServer};
+
+#[test]
+#[cfg(unix)]
+fn shards_of_an_address_share_its_port() {
+    async_std::task::block_on(async {
+        let listeners = bind_listeners(&["127.0.0.1:0".parse().unwrap()], 3).await.unwrap();
+        assert_eq!(listeners.len(), 3);
+        let address = listeners[0].local_addr().unwrap();
+        assert_ne!(address.port(), 0);
+        assert!(listeners.iter().all(|listener| listener.local_addr().unwrap() == address));
+    });
+}
+
+#[test]
+fn the_ipv6_wildcard_claims_ipv4_on_dual_stack_systems() {
+    let addresses = |list: &[&str]| list.iter().map(|address| address.parse().unwrap()).collect::<Vec<_>>();
+    assert!(validate_listeners(&addresses(&["0.0.0.0:25565", "[::1]:25565"])).is_ok());
+    assert!(validate_listeners(&addresses(&["0.0.0.0:25565", "[::]:25566"])).is_ok());
+    assert!(validate_listeners(&addresses(&["127.0.0.1:25565", "[::ffff:127.0.0.1]:25565"])).is_err());
+    for conflicting in [["0.0.0.0:25565", "[::]:25565"], ["[::]:25565", "127.0.0.1:25565"]] {
+        assert_eq!(validate_listeners(&addresses(&conflicting)).is_err(), ipv6_dual_stack(), "{:?}", conflicting);
+    }
+}
+
+#[test]
+fn every_accept_loop_serves_players() {
+    async_std::task::block_on(async {
+        let mut config = ServerConfig::default();
+        config.network.accept_loops = 4;
+        let server = TestServer::start_with(config).await;
+        assert_eq!(server.server.local_addresses().await, [server.address]);
+
+        let mut clients = Vec::new();
+        for _ in 0..16 {
+            let mut client = TestClient::connect(server.address).await;
+            client.status().await;
+            clients.push(client);
+        }
+        for client in clients {
+            client.disconnect().await;
+        }
+
+        server.stop().await;
+    });
+}
+
+#[test]
+#[cfg(unix)]
+fn only_a_broken_listener_stops_accepting() {
+    for code in [libc::EMFILE, libc::ENFILE, libc::ENOBUFS, libc::ENOMEM, libc::ECONNABORTED, libc::EINTR] {
+        assert!(!is_listener_gone(&io::Error::from_raw_os_error(code)), "{}", io::Error::from_raw_os_error(code));
+    }
+    for code in [libc::EBADF, libc::EINVAL, libc::ENOTSOCK] {
+        assert!(is_listener_gone(&io::Error::from_raw_os_error(code)), "{}", io::Error::from_raw_os_error(code));
+    }
+}
+
+#[test]
+fn accept_errors_back_off_up_to_a_limit() {
+    let mut backoff = AcceptBackoff::new();
+    assert_eq!(backoff.delay(), Duration::ZERO);
+    backoff.failed();
+    assert_eq!(backoff.delay(), ACCEPT_BACKOFF);
+    backoff.failed();
+    assert_eq!(backoff.delay(), ACCEPT_BACKOFF * 2);
+    for _ in 0..100 {
+        backoff.failed();
+    }
+    assert_eq!(backoff.delay(), MAX_ACCEPT_BACKOFF);
+    backoff.succeeded();
+    assert_eq!(backoff.delay(), Duration::ZERO);
+}
@@ -0,0 +1,114 @@
+mod common;
+
+use std::time::{Duration, Instant};
+use dolls_config::ServerConfig;
+use dolls_core::datatype::{decode_from_slice, Uuid, VarInt};
+use dolls_network::prelude::{offline_uuid, ClientboundPacketType, ConnectionState, KnownPack, PacketType, PROTOCOL_VERSION};
+use common::{TestClient, TestServer};
+
+#[test]
+fn status_then_login_reaches_play() {
+    async_std::task::block_on(async {
+        let server = TestServer::start().await;
+
+        let mut client = TestClient::connect(server.address).await;
+        let status = client.status().await;
+        assert_eq!(status["version"]["protocol"], PROTOCOL_VERSION);
+        assert_eq!(status["players"]["online"], 0);
+        assert_eq!(client.received, [ClientboundPacketType::StatusResponse, ClientboundPacketType::PongResponse]);
+        client.disconnect().await;
+
+        let mut client = TestClient::connect(server.address).await;
+        let uuid = client.join("Alice").await;
+        assert_eq!(uuid, offline_uuid("Alice"));
+        client.expect(ClientboundPacketType::PlayerAbilities).await;
+        client.expect(ClientboundPacketType::PlayerInfoUpdate).await;
+
+        let registries = client.received.iter().filter(|packet_type| **packet_type == ClientboundPacketType::RegistryData).count();
+        assert!(registries > 0);
+        let mut expected = vec![
+            ClientboundPacketType::SetCompression,
+            ClientboundPacketType::LoginSuccess,
+            ClientboundPacketType::ClientboundKnownPacks,
+        ];
+        expected.extend(std::iter::repeat_n(ClientboundPacketType::RegistryData, registries));
+        expected.extend([
+            ClientboundPacketType::FinishConfiguration,
+            ClientboundPacketType::Login,
+            ClientboundPacketType::PlayerAbilities,
+            ClientboundPacketType::PlayerInfoUpdate,
+        ]);
+        assert_eq!(client.received, expected);
+
+        client.disconnect().await;
+        server.stop().await;
+    });
+}
+
+#[test]
+fn silent_players_are_closed() {
+    async_std::task::block_on(async {
+        let mut config = ServerConfig::default();
+        config.network.idle_timeout = 1;
+        let server = TestServer::start_with(config).await;
+
+        let mut client = TestClient::connect(server.address).await;
+        client.join("Alice").await;
+        let joined = Instant::now();
+        client.receive_until(ClientboundPacketType::PlayDisconnect).await;
+        assert!(joined.elapsed() >= Duration::from_secs(1));
+        client.expect_closed().await;
+        assert!(joined.elapsed() < Duration::from_secs(3), "Closed after {:?}", joined.elapsed());
+
+        server.stop().await;
+    });
+}
+
+#[test]
+fn silent_configuration_is_closed() {
+    async_std::task::block_on(async {
+        let mut config = ServerConfig::default();
+        config.network.login_timeout = 1;
+        config.network.configuration_timeout = 1;
+        let server = TestServer::start_with(config).await;
+
+        let connected = Instant::now();
+        let mut client = TestClient::connect(server.address).await;
+        client.login("Alice").await;
+        client.receive_until(ClientboundPacketType::ConfigurationDisconnect).await;
+        assert!(connected.elapsed() >= Duration::from_secs(2));
+        client.expect_closed().await;
+        assert!(connected.elapsed() < Duration::from_secs(4), "Closed after {:?}", connected.elapsed());
+
+        server.stop().await;
+    });
+}
+
+#[test]
+fn slow_resource_pack_answers_are_not_timed_out() {
+    async_std::task::block_on(async {
+        let mut config = ServerConfig::default();
+        config.network.idle_timeout = 1;
+        config.resource_pack.url = "https://example.com/pack.zip".to_string();
+        let server = TestServer::start_with(config).await;
+
+        let mut client = TestClient::connect(server.address).await;
+        client.login("Alice").await;
+        let known_packs = client.expect(ClientboundPacketType::ClientboundKnownPacks).await;
+        let packs: Vec<KnownPack> = decode_from_slice(&known_packs.payload).unwrap();
+        client.send(PacketType::ServerboundKnownPacks, payload!(packs)).await;
+        let (_, pack) = client.receive_until(ClientboundPacketType::ConfigurationAddResourcePack).await;
+        let id: Uuid = decode_from_slice(&pack.payload[..16]).unwrap();
+
+        // The player takes longer than the idle timeout to accept the prompt and load the pack.
+        async_std::task::sleep(Duration::from_millis(1500)).await;
+        client.send(PacketType::ConfigurationResourcePackResponse, payload!(id, VarInt(0))).await;
+        client.expect(ClientboundPacketType::FinishConfiguration).await;
+        client.send(PacketType::AcknowledgeFinishConfiguration, Vec::new()).await;
+        client.state = ConnectionState::Play;
+        client.expect(ClientboundPacketType::Login).await;
+
+        client.disconnect().await;
+        server.stop().await;
+    });
+}
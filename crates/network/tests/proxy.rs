@@ -0,0 +1,29 @@
+mod common;
+
+use std::time::Duration;
+use dolls_network::prelude::{offline_uuid, run_proxy, ClientboundPacketType, ProxyOptions};
+use common::{TestClient, TestServer};
+
+#[test]
+fn clients_join_through_the_proxy() {
+    async_std::task::block_on(async {
+        let server = TestServer::start().await;
+        let listen = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let proxy = async_std::task::spawn(run_proxy(ProxyOptions { listen, upstream: server.address.to_string(), dump: true }));
+        async_std::task::sleep(Duration::from_millis(100)).await;
+
+        let mut client = TestClient::connect(listen).await;
+        client.status().await;
+        client.disconnect().await;
+
+        // Login switches both sides to compression, then to configuration and play.
+        let mut client = TestClient::connect(listen).await;
+        assert_eq!(client.join("Alice").await, offline_uuid("Alice"));
+        assert_eq!(client.received[..2], [ClientboundPacketType::SetCompression, ClientboundPacketType::LoginSuccess]);
+        client.expect(ClientboundPacketType::PlayerAbilities).await;
+        client.disconnect().await;
+
+        proxy.cancel().await;
+        server.stop().await;
+    });
+}
@@ -0,0 +1,40 @@
+use proptest::prelude::*;
+use dolls_network::prelude::{Handshake, KeepAlive, KnownPack, PingPong, RawPacket, StatusResponse};
+use dolls_network::testing::{assert_frame_round_trip, assert_round_trip, compression_threshold, raw_packet};
+
+proptest! {
+    #[test]
+    fn frames(packet in raw_packet(2048), threshold in compression_threshold()) {
+        assert_frame_round_trip(&packet, threshold)?;
+    }
+
+    #[test]
+    fn empty_frames(packet_id in 0u32..0x80, threshold in compression_threshold()) {
+        assert_frame_round_trip(&RawPacket::new(packet_id, Vec::new()), threshold)?;
+    }
+
+    #[test]
+    fn handshakes(packet: Handshake) {
+        assert_round_trip(&packet)?;
+    }
+
+    #[test]
+    fn status_responses(packet: StatusResponse) {
+        assert_round_trip(&packet)?;
+    }
+
+    #[test]
+    fn ping_pongs(packet: PingPong) {
+        assert_round_trip(&packet)?;
+    }
+
+    #[test]
+    fn keep_alives(packet: KeepAlive) {
+        assert_round_trip(&packet)?;
+    }
+
+    #[test]
+    fn known_packs(packet: Vec<KnownPack>) {
+        assert_round_trip(&packet)?;
+    }
+}
@@ -0,0 +1,22 @@
+use std::net::{IpAddr, Ipv4Addr};
+use dolls_network::prelude::{allow_status_ping, MAX_TRACKED_PINGERS};
+
+// One test, the counts are global.
+#[test]
+fn status_pings_are_counted_per_network_in_bounded_memory() {
+    // Addresses of an IPv6 /64 share their pings.
+    for ip in ["2001:db8:1:2::1", "2001:db8:1:2::2", "2001:db8:1:2:ffff::3"] {
+        assert!(allow_status_ping(ip.parse().unwrap(), 3));
+    }
+    assert!(!allow_status_ping("2001:db8:1:2::4".parse().unwrap(), 3));
+    assert!(allow_status_ping("2001:db8:1:3::1".parse().unwrap(), 3));
+
+    // Past the limit of tracked addresses the one counted the longest is forgotten.
+    let first = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    assert!(allow_status_ping(first, 1));
+    assert!(!allow_status_ping(first, 1));
+    for index in 0..MAX_TRACKED_PINGERS as u32 {
+        allow_status_ping(IpAddr::V4(Ipv4Addr::from(0x0B00_0000 + index)), 1);
+    }
+    assert!(allow_status_ping(first, 1));
+}
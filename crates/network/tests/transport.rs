@@ -0,0 +1,152 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::net::SocketAddr;
+use std::time::Duration;
+use async_std::io::{ReadExt, WriteExt};
+use async_std::net::TcpStream;
+use dolls_config::{IoBackend, ServerConfig};
+use dolls_network::prelude::{backend_support, bind_listener, offline_uuid, Listener, PacketHandler, RawPacket, Stream};
+use common::{TestClient, TestServer};
+
+/// Whether the kernel lets this process use io_uring, containers often forbid it.
+fn uring_supported() -> bool {
+    match backend_support(IoBackend::IoUring) {
+        Ok(()) => true,
+        Err(reason) => {
+            eprintln!("Skipping: {}", reason);
+            false
+        }
+    }
+}
+
+async fn bind_uring() -> (Listener, SocketAddr) {
+    let listener = Listener::new(bind_listener("127.0.0.1:0".parse().unwrap()).await.unwrap(), IoBackend::IoUring);
+    assert!(matches!(listener, Listener::Uring(_)));
+    let address = listener.local_addr().unwrap();
+    (listener, address)
+}
+
+#[test]
+fn uring_streams_carry_frames_both_ways() {
+    if !uring_supported() {
+        return;
+    }
+    async_std::task::block_on(async {
+        let (mut listener, address) = bind_uring().await;
+        let mut client = PacketHandler::new(TcpStream::connect(address).await.unwrap());
+        let stream = listener.accept().await.unwrap();
+        assert!(matches!(stream, Stream::Uring(_)));
+        assert_eq!(stream.peer_addr().unwrap(), client.get_ref().local_addr().unwrap());
+        let mut server = PacketHandler::new(stream.clone());
+
+        // Larger than a single transfer, so that both sides loop.
+        for size in [0, 10, 200_000] {
+            let packet = RawPacket::new(0x01, vec![size as u8; size]);
+            client.write_packet(&packet).await.unwrap();
+            assert_eq!(server.next_packet().await.unwrap().payload, packet.payload);
+            server.write_packet(&packet).await.unwrap();
+            assert_eq!(client.next_packet().await.unwrap().payload, packet.payload);
+        }
+
+        // A read given up on keeps its operation, the next read picks up what it receives.
+        assert!(async_std::future::timeout(Duration::from_millis(20), server.next_packet()).await.is_err());
+        client.write_packet(&RawPacket::new(0x02, vec![7; 3])).await.unwrap();
+        assert_eq!(server.next_packet().await.unwrap().payload, [7; 3]);
+
+        client.get_ref().shutdown(std::net::Shutdown::Both).unwrap();
+        assert!(server.next_packet().await.is_err());
+    });
+}
+
+#[test]
+fn writes_after_a_dropped_uring_write_are_sent_whole() {
+    if !uring_supported() {
+        return;
+    }
+    async_std::task::block_on(async {
+        let (mut listener, address) = bind_uring().await;
+        let mut client = TcpStream::connect(address).await.unwrap();
+        let mut server = listener.accept().await.unwrap();
+
+        // Fills the socket buffers until a write is given up on with its send in flight.
+        let given_up = vec![1; 64 * 1024];
+        while async_std::future::timeout(Duration::from_millis(20), server.write(&given_up)).await.is_ok() {}
+
+        let data = vec![2; 64 * 1024];
+        let writer = async_std::task::spawn(async move {
+            server.write_all(&data).await.unwrap();
+            server.shutdown(std::net::Shutdown::Write).unwrap();
+        });
+        let mut received = Vec::new();
+        client.read_to_end(&mut received).await.unwrap();
+        writer.await;
+        let start = received.iter().position(|byte| *byte == 2).unwrap();
+        assert!(received[..start].iter().all(|byte| *byte == 1));
+        assert_eq!(received[start..], [2; 64 * 1024]);
+    });
+}
+
+#[test]
+fn dropping_uring_streams_cancels_only_their_reads() {
+    if !uring_supported() {
+        return;
+    }
+    async_std::task::block_on(async {
+        let (mut listener, address) = bind_uring().await;
+        for _ in 0..100 {
+            // The read of the dropped stream completes around its cancellation, while another read is submitted.
+            let mut dropped_client = TcpStream::connect(address).await.unwrap();
+            let mut dropped = listener.accept().await.unwrap();
+            let mut client = TcpStream::connect(address).await.unwrap();
+            let mut server = listener.accept().await.unwrap();
+            assert!(async_std::future::timeout(Duration::from_millis(1), dropped.read(&mut [0; 1])).await.is_err());
+            dropped_client.write_all(&[1]).await.unwrap();
+            drop(dropped);
+            let read = async_std::task::spawn(async move {
+                let mut received = [0; 1];
+                server.read_exact(&mut received).await.map(|_| received)
+            });
+            client.write_all(&[2]).await.unwrap();
+            assert_eq!(read.await.unwrap(), [2]);
+        }
+    });
+}
+
+#[test]
+fn dropped_uring_listeners_release_their_port() {
+    if !uring_supported() {
+        return;
+    }
+    async_std::task::block_on(async {
+        let (mut listener, address) = bind_uring().await;
+        // Leaves an accept in flight, which dropping the listener cancels.
+        assert!(async_std::future::timeout(Duration::from_millis(20), listener.accept()).await.is_err());
+        drop(listener);
+        async_std::task::sleep(Duration::from_millis(50)).await;
+        bind_listener(address).await.unwrap();
+    });
+}
+
+#[test]
+fn players_join_over_io_uring() {
+    if !uring_supported() {
+        return;
+    }
+    async_std::task::block_on(async {
+        let mut config = ServerConfig::default();
+        config.network.io_backend = IoBackend::IoUring;
+        let server = TestServer::start_with(config).await;
+
+        let mut client = TestClient::connect(server.address).await;
+        client.status().await;
+        client.disconnect().await;
+
+        let mut client = TestClient::connect(server.address).await;
+        assert_eq!(client.join("Alice").await, offline_uuid("Alice"));
+        client.disconnect().await;
+
+        server.stop().await;
+    });
+}
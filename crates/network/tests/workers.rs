@@ -0,0 +1,31 @@
+mod common;
+
+use std::time::{Duration, Instant};
+use common::{TestClient, TestServer};
+
+#[test]
+fn finished_workers_are_forgotten() {
+    async_std::task::block_on(async {
+        let server = TestServer::start().await;
+
+        let mut clients = Vec::new();
+        // A status ping ends the connection, logging in keeps it open.
+        for index in 0..8 {
+            let mut client = TestClient::connect(server.address).await;
+            client.login(&format!("Player{}", index)).await;
+            clients.push(client);
+        }
+        assert_eq!(server.server.worker_count(), 8);
+        for client in clients {
+            client.disconnect().await;
+        }
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while server.server.worker_count() > 0 {
+            assert!(Instant::now() < deadline, "{} workers are still tracked", server.server.worker_count());
+            async_std::task::sleep(Duration::from_millis(10)).await;
+        }
+
+        server.stop().await;
+    });
+}
@@ -0,0 +1,183 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use dolls_commands::prelude::{execute_command, register_command, CommandNode, CommandSource};
+use dolls_config::ServerConfig;
+use dolls_core::datatype::Identifier;
+use dolls_events::prelude::{event_bus, Event, EventBus, EventPriority, SubscriptionId};
+use dolls_network::prelude::{register_cookie_handler, register_join_listener, register_plugin_channel, register_resource_pack_listener, register_status_hook,
+    schedule_repeating, ConnectionRegistry, CookieHandler, DollNetworkServer, JoinListener, PluginChannelHandler, RepeatingTask, ResourcePackListener, ServerStatus};
+use dolls_tick::prelude::{scheduler, Scheduler, TaskGuard, TaskHandle};
+
+type StatusHook = Box<dyn Fn(&mut ServerStatus) + Send + Sync>;
+type AsyncTask = Box<dyn FnMut() -> Pin<Box<dyn Future<Output = ()> + Send>> + Send>;
+
+/// Entry points into the server's own registries. Plugins loaded from a dynamic library link their own
+/// copy of the server's crates, whose registries the server never reads, so the API calls through these.
+struct Host {
+    register_command: fn(CommandNode),
+    execute_command: fn(&CommandSource, &str) -> anyhow::Result<()>,
+    register_join_listener: fn(Arc<dyn JoinListener>),
+    register_plugin_channel: fn(Identifier, Arc<dyn PluginChannelHandler>),
+    register_cookie_handler: fn(Identifier, Arc<dyn CookieHandler>),
+    register_resource_pack_listener: fn(Arc<dyn ResourcePackListener>),
+    register_status_hook: fn(StatusHook),
+    scheduler: fn() -> &'static Scheduler,
+    event_bus: fn() -> &'static EventBus,
+    schedule_repeating: fn(String, Duration, AsyncTask) -> RepeatingTask,
+}
+
+impl std::fmt::Debug for Host {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Host")
+    }
+}
+
+static HOST: Host = Host {
+    register_command,
+    execute_command,
+    register_join_listener,
+    register_plugin_channel,
+    register_cookie_handler,
+    register_resource_pack_listener,
+    register_status_hook: |hook| register_status_hook(hook),
+    scheduler,
+    event_bus,
+    schedule_repeating: |name, interval, task| schedule_repeating(name, interval, task),
+};
+
+/// What a plugin may use of the running server. Every plugin gets its own, tasks scheduled and event
+/// subscribers added through it are stopped when the plugin is disabled.
+#[derive(Debug)]
+pub struct ServerApi {
+    plugin_name: String,
+    network_server: Arc<DollNetworkServer>,
+    host: &'static Host,
+    tick_tasks: Mutex<Vec<TaskGuard>>,
+    repeating_tasks: Mutex<Vec<RepeatingTask>>,
+    subscriptions: Mutex<Vec<SubscriptionId>>,
+}
+
+impl ServerApi {
+    pub fn new(plugin_name: impl Into<String>, network_server: Arc<DollNetworkServer>) -> Self {
+        Self {
+            plugin_name: plugin_name.into(),
+            network_server,
+            host: &HOST,
+            tick_tasks: Mutex::new(Vec::new()),
+            repeating_tasks: Mutex::new(Vec::new()),
+            subscriptions: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn plugin_name(&self) -> &str {
+        &self.plugin_name
+    }
+
+    pub fn config(&self) -> &Arc<ServerConfig> {
+        self.network_server.config()
+    }
+
+    pub fn connections(&self) -> &Arc<ConnectionRegistry> {
+        self.network_server.connections()
+    }
+
+    /// Stops the server, like the `stop` command.
+    pub fn shutdown(&self) {
+        self.network_server.shutdown();
+    }
+
+    /// Adds a command players and the console can run, merged with commands of the same name.
+    pub fn register_command(&self, command: CommandNode) {
+        (self.host.register_command)(command);
+    }
+
+    /// Runs a command as the console, its feedback goes to the log.
+    pub fn run_command(&self, command: &str) -> anyhow::Result<()> {
+        (self.host.execute_command)(&CommandSource::console(self.connections().clone()), command)
+    }
+
+    /// Called for every player entering Play and leaving it afterwards.
+    pub fn on_join(&self, listener: Arc<dyn JoinListener>) {
+        (self.host.register_join_listener)(listener);
+    }
+
+    /// Handles the plugin messages clients send on `channel`.
+    pub fn on_plugin_message(&self, channel: Identifier, handler: Arc<dyn PluginChannelHandler>) {
+        (self.host.register_plugin_channel)(channel, handler);
+    }
+
+    /// Handles the cookies clients send back for `key`.
+    pub fn on_cookie(&self, key: Identifier, handler: Arc<dyn CookieHandler>) {
+        (self.host.register_cookie_handler)(key, handler);
+    }
+
+    /// Called for every Resource Pack Response.
+    pub fn on_resource_pack(&self, listener: Arc<dyn ResourcePackListener>) {
+        (self.host.register_resource_pack_listener)(listener);
+    }
+
+    /// Changes the status shown in the server list before it is sent.
+    pub fn on_status(&self, hook: impl Fn(&mut ServerStatus) + Send + Sync + 'static) {
+        (self.host.register_status_hook)(Box::new(hook));
+    }
+
+    /// Called for every event of the type, see [`EventBus::subscribe`].
+    pub fn on_event<E: Event>(&self, priority: EventPriority, handler: impl Fn(&mut E) + Send + Sync + 'static) -> SubscriptionId {
+        let id = (self.host.event_bus)().subscribe(priority, handler);
+        self.subscriptions.lock().unwrap().push(id);
+        id
+    }
+
+    /// Called on a task of its own for every event of the type which was not cancelled, see
+    /// [`EventBus::subscribe_async`].
+    pub fn on_event_async<E, F, Fut>(&self, handler: F) -> SubscriptionId
+    where
+        E: Event,
+        F: Fn(E) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let id = (self.host.event_bus)().subscribe_async(handler);
+        self.subscriptions.lock().unwrap().push(id);
+        id
+    }
+
+    /// Runs `task` on the tick loop after `delay` ticks.
+    pub fn run_later(&self, delay: u64, task: impl FnOnce() + Send + 'static) -> TaskHandle {
+        let handle = (self.host.scheduler)().run_later(self.task_name(), delay, task);
+        self.tick_tasks.lock().unwrap().push(handle.clone().guard());
+        handle
+    }
+
+    /// Runs `task` on the tick loop after `delay` ticks, then every `period` ticks.
+    pub fn run_repeating(&self, delay: u64, period: u64, task: impl FnMut() + Send + 'static) -> TaskHandle {
+        let handle = (self.host.scheduler)().run_repeating(self.task_name(), delay, period, task);
+        self.tick_tasks.lock().unwrap().push(handle.clone().guard());
+        handle
+    }
+
+    /// Runs `task` off the tick loop right away and then every `interval` after the previous run
+    /// finished, e.g. for network requests.
+    pub fn schedule_repeating<F, Fut>(&self, interval: Duration, mut task: F)
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let task = (self.host.schedule_repeating)(self.task_name(), interval, Box::new(move || Box::pin(task())));
+        self.repeating_tasks.lock().unwrap().push(task);
+    }
+
+    /// Stops every task the plugin scheduled and removes its event subscribers.
+    pub(crate) fn cancel_tasks(&self) {
+        self.tick_tasks.lock().unwrap().clear();
+        self.repeating_tasks.lock().unwrap().clear();
+        for id in self.subscriptions.lock().unwrap().drain(..) {
+            (self.host.event_bus)().unsubscribe(id);
+        }
+    }
+
+    fn task_name(&self) -> String {
+        format!("Plugin {}", self.plugin_name)
+    }
+}
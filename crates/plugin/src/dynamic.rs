@@ -0,0 +1,159 @@
+use std::ffi::{c_char, c_void, CStr};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use anyhow::{bail, Context};
+use libloading::Library;
+use crate::plugin::isolate;
+use crate::prelude::{Plugin, ServerApi};
+
+/// Directory in the working directory dynamic plugins are loaded from.
+pub const PLUGINS_DIRECTORY: &str = "plugins";
+/// Bumped whenever [`PluginDeclaration`] changes, libraries declaring another version are refused.
+pub const PLUGIN_API_VERSION: u32 = 1;
+/// Compiler the server was built with, plugins passing Rust types to it must be built with the same.
+pub const RUSTC_VERSION: &str = env!("DOLLS_RUSTC_VERSION");
+/// Name of the static [`export_plugin!`](crate::export_plugin) defines.
+pub const PLUGIN_DECLARATION_SYMBOL: &[u8] = b"dolls_plugin_declaration\0";
+
+#[doc(hidden)]
+pub const RUSTC_VERSION_NUL: &str = concat!(env!("DOLLS_RUSTC_VERSION"), "\0");
+#[doc(hidden)]
+pub const DOLLS_VERSION_NUL: &str = concat!(env!("CARGO_PKG_VERSION"), "\0");
+
+/// What a plugin library exports under [`PLUGIN_DECLARATION_SYMBOL`]. Only `api_version` is read before
+/// it matched, the rest of the layout may change with it.
+#[repr(C)]
+pub struct PluginDeclaration {
+    pub api_version: u32,
+    /// `rustc --version` of the compiler the plugin was built with, nul-terminated.
+    pub rustc_version: *const c_char,
+    /// Version of `dolls_plugin` the plugin was built against, nul-terminated.
+    pub dolls_version: *const c_char,
+    /// Creates the plugin as a `Box<Box<dyn Plugin>>` turned into a raw pointer, null if it panicked.
+    pub create: unsafe extern "C" fn() -> *mut c_void,
+}
+
+// Only points to constants.
+unsafe impl Sync for PluginDeclaration {}
+
+/// Exports a plugin from a `cdylib` crate, created by the given function when the server loads it.
+#[macro_export]
+macro_rules! export_plugin {
+    ($create:expr) => {
+        #[doc(hidden)]
+        unsafe extern "C" fn __dolls_create_plugin() -> *mut ::std::ffi::c_void {
+            // Unwinding out of an `extern "C"` function aborts, the server reports the panic instead.
+            match ::std::panic::catch_unwind(|| -> ::std::boxed::Box<dyn $crate::prelude::Plugin> {
+                ::std::boxed::Box::new($crate::prelude::Isolated($create()))
+            }) {
+                Ok(plugin) => ::std::boxed::Box::into_raw(::std::boxed::Box::new(plugin)) as *mut ::std::ffi::c_void,
+                Err(_) => ::std::ptr::null_mut(),
+            }
+        }
+
+        #[no_mangle]
+        pub static dolls_plugin_declaration: $crate::prelude::PluginDeclaration = $crate::prelude::PluginDeclaration {
+            api_version: $crate::prelude::PLUGIN_API_VERSION,
+            rustc_version: $crate::prelude::RUSTC_VERSION_NUL.as_ptr() as *const ::std::ffi::c_char,
+            dolls_version: $crate::prelude::DOLLS_VERSION_NUL.as_ptr() as *const ::std::ffi::c_char,
+            create: __dolls_create_plugin,
+        };
+    }
+}
+
+/// Catches the panics of a plugin in a dynamic library. The library has its own copy of the standard
+/// library, the server cannot catch panics raised by it, so this is compiled into the library by
+/// [`export_plugin!`](crate::export_plugin).
+#[doc(hidden)]
+pub struct Isolated<P>(pub P);
+
+impl<P: Plugin> Plugin for Isolated<P> {
+    fn name(&self) -> &str {
+        self.0.name()
+    }
+
+    fn version(&self) -> &str {
+        self.0.version()
+    }
+
+    fn on_load(&mut self, api: &Arc<ServerApi>) -> anyhow::Result<()> {
+        isolate(|| self.0.on_load(api))
+    }
+
+    fn on_enable(&mut self, api: &Arc<ServerApi>) -> anyhow::Result<()> {
+        isolate(|| self.0.on_enable(api))
+    }
+
+    fn on_disable(&mut self, api: &Arc<ServerApi>) -> anyhow::Result<()> {
+        isolate(|| self.0.on_disable(api))
+    }
+}
+
+/// A plugin from a dynamic library, which stays loaded as long as the plugin lives.
+///
+/// The library links its own copy of the server's crates: registries it reaches without the
+/// [`ServerApi`] are not the server's, and panics in listeners it registers abort the server.
+pub struct DynamicPlugin {
+    // Dropped before the library its code lives in.
+    plugin: Box<dyn Plugin>,
+    path: PathBuf,
+    _library: Library,
+}
+
+impl DynamicPlugin {
+    /// Loads the plugin exported by the library at `path` after checking it was built for this server.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        // SAFETY: the library runs its initializers, which plugin authors are trusted with like the rest of
+        // their code. The declaration is only read as far as the API version it declares allows.
+        unsafe {
+            let library = Library::new(path).with_context(|| format!("Failed to open {}", path.display()))?;
+            let declaration = library.get::<*const PluginDeclaration>(PLUGIN_DECLARATION_SYMBOL)
+                .with_context(|| format!("{} does not export a plugin", path.display()))?;
+            let declaration = &**declaration;
+            if declaration.api_version != PLUGIN_API_VERSION {
+                bail!("{} uses plugin API version {}, the server version {}", path.display(), declaration.api_version, PLUGIN_API_VERSION);
+            }
+            let rustc_version = CStr::from_ptr(declaration.rustc_version).to_string_lossy();
+            if rustc_version != RUSTC_VERSION {
+                bail!("{} was built with {}, the server with {}", path.display(), rustc_version, RUSTC_VERSION);
+            }
+            let dolls_version = CStr::from_ptr(declaration.dolls_version).to_string_lossy();
+            if dolls_version != env!("CARGO_PKG_VERSION") {
+                bail!("{} was built against Dolls {}, the server is {}", path.display(), dolls_version, env!("CARGO_PKG_VERSION"));
+            }
+            let plugin = (declaration.create)();
+            if plugin.is_null() {
+                bail!("Creating the plugin of {} panicked", path.display());
+            }
+            let plugin = *Box::from_raw(plugin as *mut Box<dyn Plugin>);
+            Ok(Self { plugin, path: path.to_path_buf(), _library: library })
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Plugin for DynamicPlugin {
+    fn name(&self) -> &str {
+        self.plugin.name()
+    }
+
+    fn version(&self) -> &str {
+        self.plugin.version()
+    }
+
+    fn on_load(&mut self, api: &Arc<ServerApi>) -> anyhow::Result<()> {
+        self.plugin.on_load(api)
+    }
+
+    fn on_enable(&mut self, api: &Arc<ServerApi>) -> anyhow::Result<()> {
+        self.plugin.on_enable(api)
+    }
+
+    fn on_disable(&mut self, api: &Arc<ServerApi>) -> anyhow::Result<()> {
+        self.plugin.on_disable(api)
+    }
+}
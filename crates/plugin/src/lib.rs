@@ -0,0 +1,21 @@
+pub mod plugin;
+pub mod api;
+pub mod manager;
+pub mod dynamic;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "scripting")]
+pub mod script;
+
+pub use inventory;
+
+pub mod prelude {
+    pub use crate::plugin::*;
+    pub use crate::api::*;
+    pub use crate::manager::*;
+    pub use crate::dynamic::*;
+    #[cfg(feature = "wasm")]
+    pub use crate::wasm::*;
+    #[cfg(feature = "scripting")]
+    pub use crate::script::*;
+}
@@ -0,0 +1,126 @@
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use log::{error, info, warn};
+use dolls_network::prelude::DollNetworkServer;
+use crate::plugin::isolate;
+use crate::prelude::{DynamicPlugin, Plugin, PluginRegistration, ServerApi};
+
+struct LoadedPlugin {
+    plugin: Box<dyn Plugin>,
+    api: Arc<ServerApi>,
+    enabled: bool,
+}
+
+/// Drives the plugins of the server through their lifecycle.
+pub struct PluginManager {
+    network_server: Arc<DollNetworkServer>,
+    plugins: Vec<LoadedPlugin>,
+}
+
+impl std::fmt::Debug for PluginManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PluginManager").field("plugins", &self.names()).finish()
+    }
+}
+
+impl PluginManager {
+    /// A manager with the plugins compiled into the server, none of them loaded yet.
+    pub fn new(network_server: Arc<DollNetworkServer>) -> Self {
+        let mut manager = Self { network_server, plugins: Vec::new() };
+        for registration in inventory::iter::<PluginRegistration> {
+            manager.add((registration.create)());
+        }
+        manager
+    }
+
+    /// Adds a plugin to be loaded with the others, plugins with a name already taken are skipped.
+    pub fn add(&mut self, plugin: Box<dyn Plugin>) {
+        if self.plugins.iter().any(|loaded| loaded.plugin.name() == plugin.name()) {
+            warn!("Plugin {} is present twice, only the first one is used", plugin.name());
+            return;
+        }
+        let api = Arc::new(ServerApi::new(plugin.name(), self.network_server.clone()));
+        self.plugins.push(LoadedPlugin { plugin, api, enabled: false });
+    }
+
+    /// Adds the plugins of the dynamic libraries in `directory`, if it exists, of the WebAssembly modules
+    /// with the `wasm` feature and of the Rhai scripts with the `scripting` feature. Files failing to load
+    /// are skipped.
+    pub fn add_directory(&mut self, directory: impl AsRef<Path>) {
+        let directory = directory.as_ref();
+        let Ok(entries) = fs::read_dir(directory) else { return };
+        let mut paths = entries.filter_map(|entry| Some(entry.ok()?.path())).collect::<Vec<_>>();
+        paths.sort();
+        for path in paths {
+            let plugin: anyhow::Result<Box<dyn Plugin>> = match path.extension().and_then(|extension| extension.to_str()) {
+                Some(std::env::consts::DLL_EXTENSION) => DynamicPlugin::load(&path).map(|plugin| Box::new(plugin) as _),
+                #[cfg(feature = "wasm")]
+                Some("wasm") => crate::prelude::WasmPlugin::new(&path).map(|plugin| Box::new(plugin) as _),
+                #[cfg(feature = "scripting")]
+                Some("rhai") => crate::prelude::ScriptPlugin::new(&path).map(|plugin| Box::new(plugin) as _),
+                _ => continue,
+            };
+            match plugin {
+                Ok(plugin) => self.add(plugin),
+                Err(err) => error!("Failed to load plugin {}: {:#}", path.display(), err),
+            }
+        }
+    }
+
+    /// Names of the plugins, in load order.
+    pub fn names(&self) -> Vec<String> {
+        self.plugins.iter().map(|loaded| loaded.plugin.name().to_string()).collect()
+    }
+
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.plugins.iter().any(|loaded| loaded.enabled && loaded.plugin.name() == name)
+    }
+
+    /// Loads every plugin, those failing to are dropped.
+    pub fn load_all(&mut self) {
+        self.plugins.retain_mut(|loaded| {
+            info!("Loading plugin {} version {}.", loaded.plugin.name(), loaded.plugin.version());
+            match isolate(|| loaded.plugin.on_load(&loaded.api)) {
+                Ok(()) => true,
+                Err(err) => {
+                    error!("Failed to load plugin {}: {:#}", loaded.plugin.name(), err);
+                    false
+                }
+            }
+        });
+    }
+
+    pub fn enable_all(&mut self) {
+        for loaded in &mut self.plugins {
+            if loaded.enabled {
+                continue;
+            }
+            match isolate(|| loaded.plugin.on_enable(&loaded.api)) {
+                Ok(()) => {
+                    loaded.enabled = true;
+                    info!("Enabled plugin {}.", loaded.plugin.name());
+                }
+                Err(err) => {
+                    loaded.api.cancel_tasks();
+                    error!("Failed to enable plugin {}: {:#}", loaded.plugin.name(), err);
+                }
+            }
+        }
+    }
+
+    /// Disables the enabled plugins in reverse load order and stops their tasks.
+    pub fn disable_all(&mut self) {
+        for loaded in self.plugins.iter_mut().rev() {
+            if !loaded.enabled {
+                continue;
+            }
+            if let Err(err) = isolate(|| loaded.plugin.on_disable(&loaded.api)) {
+                error!("Failed to disable plugin {}: {:#}", loaded.plugin.name(), err);
+            }
+            loaded.api.cancel_tasks();
+            loaded.enabled = false;
+            info!("Disabled plugin {}.", loaded.plugin.name());
+        }
+    }
+}
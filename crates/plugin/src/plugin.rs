@@ -0,0 +1,63 @@
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::Arc;
+use anyhow::anyhow;
+use crate::prelude::ServerApi;
+
+/// A plugin compiled into the server or loaded at startup, driven by the [`PluginManager`].
+///
+/// Plugins are loaded in registration order once the configuration and the world are ready, enabled
+/// once the server accepts players, and disabled in reverse order when it shuts down.
+///
+/// [`PluginManager`]: crate::prelude::PluginManager
+pub trait Plugin: Send {
+    /// Unique name, used in logs and by [`ServerApi::plugin_name`].
+    fn name(&self) -> &str;
+
+    fn version(&self) -> &str {
+        "0.0.0"
+    }
+
+    /// Prepares the plugin, e.g. reads its files. Returning an error skips the plugin.
+    fn on_load(&mut self, _api: &Arc<ServerApi>) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Registers commands, listeners and tasks. Returning an error leaves the plugin disabled, what it
+    /// registered until then stays.
+    fn on_enable(&mut self, api: &Arc<ServerApi>) -> anyhow::Result<()>;
+
+    /// Saves the plugin's state. Tasks it scheduled and event subscribers it added through the
+    /// [`ServerApi`] are stopped afterwards.
+    fn on_disable(&mut self, _api: &Arc<ServerApi>) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// A plugin compiled into the server, see [`register_plugin!`](crate::register_plugin).
+pub struct PluginRegistration {
+    pub create: fn() -> Box<dyn Plugin>,
+}
+
+inventory::collect!(PluginRegistration);
+
+/// Adds a plugin compiled into the server, created by the given function when the server starts.
+#[macro_export]
+macro_rules! register_plugin {
+    ($create:expr) => {
+        $crate::inventory::submit! {
+            $crate::prelude::PluginRegistration {
+                create: $create,
+            }
+        }
+    }
+}
+
+/// Turns a panic of a plugin into an error, so that it only takes the plugin down.
+pub(crate) fn isolate<T>(call: impl FnOnce() -> anyhow::Result<T>) -> anyhow::Result<T> {
+    catch_unwind(AssertUnwindSafe(call)).unwrap_or_else(|panic| {
+        let message = panic.downcast_ref::<&str>().copied()
+            .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("unknown cause");
+        Err(anyhow!("Panicked: {}", message))
+    })
+}
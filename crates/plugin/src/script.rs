@@ -0,0 +1,392 @@
+//! Plugins written in [Rhai](https://rhai.rs), loaded from `.rhai` files in the plugins directory, for
+//! small behaviors which do not need a compiled plugin.
+//!
+//! A script runs once when it is enabled and registers its handlers with closures:
+//!
+//! ```rhai
+//! on_join(|player| broadcast(`${player.name} joined the game`));
+//! on_leave(|player| log(`${player.name} left`));
+//!
+//! register_command("hello", |sender, args| `Hello ${args}!`);
+//! register_command("heal-all", 2, |sender, args| { ... });
+//!
+//! every(20 * 60, || broadcast("A minute passed"));
+//! after(100, || log("Five seconds after startup"));
+//!
+//! on_event("chat", |event| event.message = `<${event.name}> ${event.message}`);
+//! on_event("block_break", "high", |event| if event.y < 0 { event.cancel() });
+//! ```
+//!
+//! Commands get the sending `Player`, or `()` for the console, and the rest of the line. A string they
+//! return is sent back to the sender.
+//!
+//! `on_event` subscribes to an event of the event bus, optionally with a priority from `"lowest"` to
+//! `"monitor"`. Every event has `player`, the `Player` it concerns or `()` once it left, and `id`:
+//! - `player_join` has `name` and `uuid`.
+//! - `chat` has `name`, `uuid` and `message`, which handlers may change.
+//! - `block_break` has `name`, `x`, `y`, `z`, `block` and `state_id`.
+//! - `packet` is every packet clients send, with `packet`, its name, `packet_id` and `payload`, a blob
+//!   handlers may change.
+//!
+//! Events but `player_join` have `cancelled` and the method `cancel()`. Besides `log` and `broadcast`, scripts find players with
+//! `players()` and `find_player(name)`. A `Player` has the properties `name`, `uuid` and `id`, and the
+//! methods `send_message(text)`, `send_action_bar(text)` and `kick(reason)`.
+//!
+//! Every run of a script is limited to [`SCRIPT_MAX_OPERATIONS`], so a script looping forever fails
+//! instead of blocking the server.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use anyhow::{anyhow, bail, Context};
+use rhai::{Array, Blob, Dynamic, Engine, EvalAltResult, FnPtr, AST};
+use log::{error, info};
+use dolls_commands::prelude::{argument, literal, ArgumentType, CommandContext, CommandNode, CommandSender, StringKind};
+use dolls_core::text::TextComponent;
+use dolls_events::prelude::{BlockBreakEvent, ChatEvent, Event, EventPriority, PacketReceiveEvent, PlayerJoinEvent};
+use dolls_network::prelude::{broadcast_system_message, ConnectionHandle, ConnectionRegistry, JoinListener, PacketContext, SystemChatMessage};
+use crate::prelude::{Plugin, ServerApi};
+
+/// Operations a single run of a script, or of one of its handlers, may perform.
+pub const SCRIPT_MAX_OPERATIONS: u64 = 1_000_000;
+
+/// What a script asked for while it ran.
+enum Registration {
+    Join(FnPtr),
+    Leave(FnPtr),
+    Command { name: String, permission_level: u8, handler: FnPtr },
+    Every { period: u64, handler: FnPtr },
+    After { delay: u64, handler: FnPtr },
+    Event { event: ScriptEventKind, priority: EventPriority, handler: FnPtr },
+}
+
+/// Events of the event bus a script may subscribe to.
+#[derive(Debug, Copy, Clone)]
+enum ScriptEventKind {
+    PlayerJoin,
+    Chat,
+    BlockBreak,
+    Packet,
+}
+
+impl ScriptEventKind {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "player_join" => Some(Self::PlayerJoin),
+            "chat" => Some(Self::Chat),
+            "block_break" => Some(Self::BlockBreak),
+            "packet" => Some(Self::Packet),
+            _ => None,
+        }
+    }
+}
+
+/// An event handed to a script, shared so that the changes of its handler reach the emitter.
+#[derive(Debug, Clone)]
+struct ScriptEvent<E> {
+    event: Arc<Mutex<E>>,
+    connections: Arc<ConnectionRegistry>,
+}
+
+impl<E: Clone> ScriptEvent<E> {
+    fn get<T>(&self, read: impl FnOnce(&E) -> T) -> T {
+        read(&self.event.lock().unwrap())
+    }
+
+    fn set(&mut self, write: impl FnOnce(&mut E)) {
+        write(&mut self.event.lock().unwrap());
+    }
+
+    fn player(&self, connection_id: u64) -> Dynamic {
+        self.connections.get(connection_id).map_or(Dynamic::UNIT, Dynamic::from)
+    }
+}
+
+/// A compiled script and the engine running its handlers.
+struct Script {
+    name: String,
+    engine: Engine,
+    ast: AST,
+    enabled: AtomicBool,
+}
+
+impl Script {
+    fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Runs a handler of the script, nothing once the script is disabled.
+    fn call(&self, handler: &FnPtr, args: impl rhai::FuncArgs) -> anyhow::Result<Dynamic> {
+        if !self.is_enabled() {
+            return Ok(Dynamic::UNIT);
+        }
+        handler.call::<Dynamic>(&self.engine, &self.ast, args)
+            .map_err(|err| anyhow!("Script {} failed: {}", self.name, err))
+    }
+}
+
+/// A plugin in a Rhai script, named after its file.
+pub struct ScriptPlugin {
+    name: String,
+    path: PathBuf,
+    source: Option<String>,
+    script: Option<Arc<Script>>,
+}
+
+impl ScriptPlugin {
+    pub fn new(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let name = path.file_stem().and_then(|stem| stem.to_str()).ok_or_else(|| anyhow!("Invalid plugin file name {}", path.display()))?;
+        Ok(Self { name: name.to_string(), path: path.to_path_buf(), source: None, script: None })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn engine(&self, connections: Arc<ConnectionRegistry>, registrations: Arc<Mutex<Vec<Registration>>>) -> Engine {
+        let mut engine = Engine::new();
+        engine.set_max_operations(SCRIPT_MAX_OPERATIONS);
+
+        let name = self.name.clone();
+        engine.on_print(move |line| info!("[{}] {}", name, line));
+        let name = self.name.clone();
+        engine.register_fn("log", move |line: &str| info!("[{}] {}", name, line));
+        let registry = connections.clone();
+        engine.register_fn("broadcast", move |message: &str| {
+            // Players leaving meanwhile miss the message.
+            let _ = broadcast_system_message(&registry, TextComponent::text(message), false);
+        });
+        let registry = connections.clone();
+        engine.register_fn("players", move || registry.players().into_iter().map(Dynamic::from).collect::<Array>());
+        let registry = connections;
+        engine.register_fn("find_player", move |name: &str| registry.find_player(name).map_or(Dynamic::UNIT, Dynamic::from));
+        register_event_types(&mut engine);
+
+        engine.register_type_with_name::<ConnectionHandle>("Player")
+            .register_get("name", |player: &mut ConnectionHandle| player.username().unwrap_or_default())
+            .register_get("uuid", |player: &mut ConnectionHandle| player.uuid().map(|uuid| uuid.to_string()).unwrap_or_default())
+            .register_get("id", |player: &mut ConnectionHandle| player.id() as i64)
+            .register_fn("send_message", |player: &mut ConnectionHandle, message: &str| send(player, message, false))
+            .register_fn("send_action_bar", |player: &mut ConnectionHandle, message: &str| send(player, message, true))
+            .register_fn("kick", |player: &mut ConnectionHandle, reason: &str| player.disconnect(TextComponent::text(reason)).is_ok())
+            .register_fn("to_string", |player: &mut ConnectionHandle| player.username().unwrap_or_default());
+
+        let pending = registrations.clone();
+        engine.register_fn("on_join", move |handler: FnPtr| pending.lock().unwrap().push(Registration::Join(handler)));
+        let pending = registrations.clone();
+        engine.register_fn("on_leave", move |handler: FnPtr| pending.lock().unwrap().push(Registration::Leave(handler)));
+        let pending = registrations.clone();
+        engine.register_fn("register_command", move |name: &str, handler: FnPtr| {
+            pending.lock().unwrap().push(Registration::Command { name: name.to_string(), permission_level: 0, handler });
+        });
+        let pending = registrations.clone();
+        engine.register_fn("register_command", move |name: &str, permission_level: i64, handler: FnPtr| {
+            let permission_level = permission_level.clamp(0, 4) as u8;
+            pending.lock().unwrap().push(Registration::Command { name: name.to_string(), permission_level, handler });
+        });
+        let pending = registrations.clone();
+        engine.register_fn("every", move |period: i64, handler: FnPtr| {
+            pending.lock().unwrap().push(Registration::Every { period: period.max(1) as u64, handler });
+        });
+        let pending = registrations.clone();
+        engine.register_fn("after", move |delay: i64, handler: FnPtr| {
+            pending.lock().unwrap().push(Registration::After { delay: delay.max(0) as u64, handler });
+        });
+        let pending = registrations.clone();
+        engine.register_fn("on_event", move |event: &str, handler: FnPtr| subscribe(&pending, event, "normal", handler));
+        let pending = registrations;
+        engine.register_fn("on_event", move |event: &str, priority: &str, handler: FnPtr| subscribe(&pending, event, priority, handler));
+        engine
+    }
+}
+
+impl Plugin for ScriptPlugin {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn on_load(&mut self, _api: &Arc<ServerApi>) -> anyhow::Result<()> {
+        self.source = Some(std::fs::read_to_string(&self.path).with_context(|| format!("Failed to read {}", self.path.display()))?);
+        Ok(())
+    }
+
+    fn on_enable(&mut self, api: &Arc<ServerApi>) -> anyhow::Result<()> {
+        let Some(source) = &self.source else { bail!("Plugin {} was not loaded", self.name) };
+        let registrations = Arc::new(Mutex::new(Vec::new()));
+        let engine = self.engine(api.connections().clone(), registrations.clone());
+        let ast = engine.compile(source).map_err(|err| anyhow!("Failed to compile {}: {}", self.path.display(), err))?;
+        engine.run_ast(&ast).map_err(|err| anyhow!("Script {} failed: {}", self.name, err))?;
+
+        let script = Arc::new(Script { name: self.name.clone(), engine, ast, enabled: AtomicBool::new(true) });
+        let registrations = std::mem::take(&mut *registrations.lock().unwrap());
+        for registration in registrations {
+            match registration {
+                Registration::Join(handler) => api.on_join(Arc::new(ScriptJoinListener { script: script.clone(), on_join: Some(handler), on_leave: None })),
+                Registration::Leave(handler) => api.on_join(Arc::new(ScriptJoinListener { script: script.clone(), on_join: None, on_leave: Some(handler) })),
+                Registration::Command { name, permission_level, handler } => api.register_command(command(&script, name, permission_level, handler)),
+                Registration::Every { period, handler } => {
+                    let script = script.clone();
+                    api.run_repeating(period, period, move || {
+                        if let Err(err) = script.call(&handler, ()) {
+                            error!("{:#}", err);
+                        }
+                    });
+                }
+                Registration::After { delay, handler } => {
+                    let script = script.clone();
+                    api.run_later(delay, move || {
+                        if let Err(err) = script.call(&handler, ()) {
+                            error!("{:#}", err);
+                        }
+                    });
+                }
+                Registration::Event { event, priority, handler } => match event {
+                    ScriptEventKind::PlayerJoin => on_event::<PlayerJoinEvent>(api, &script, priority, handler),
+                    ScriptEventKind::Chat => on_event::<ChatEvent>(api, &script, priority, handler),
+                    ScriptEventKind::BlockBreak => on_event::<BlockBreakEvent>(api, &script, priority, handler),
+                    ScriptEventKind::Packet => on_event::<PacketReceiveEvent>(api, &script, priority, handler),
+                },
+            }
+        }
+        self.script = Some(script);
+        Ok(())
+    }
+
+    fn on_disable(&mut self, _api: &Arc<ServerApi>) -> anyhow::Result<()> {
+        // Tasks and event subscribers are stopped by the manager, listeners and commands stay registered and do nothing from now on.
+        if let Some(script) = self.script.take() {
+            script.enabled.store(false, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+}
+
+/// Runs the join and leave handlers of a script.
+struct ScriptJoinListener {
+    script: Arc<Script>,
+    on_join: Option<FnPtr>,
+    on_leave: Option<FnPtr>,
+}
+
+impl JoinListener for ScriptJoinListener {
+    fn on_join(&self, context: &mut PacketContext) -> anyhow::Result<()> {
+        let Some(handler) = &self.on_join else { return Ok(()) };
+        self.script.call(handler, (context.connection.clone(),)).map(|_| ())
+    }
+
+    fn on_leave(&self, connection: &ConnectionHandle) {
+        let Some(handler) = &self.on_leave else { return };
+        if let Err(err) = self.script.call(handler, (connection.clone(),)) {
+            error!("{:#}", err);
+        }
+    }
+}
+
+fn command(script: &Arc<Script>, name: String, permission_level: u8, handler: FnPtr) -> CommandNode {
+    let (plain, with_arguments) = (script.clone(), script.clone());
+    let plain_handler = handler.clone();
+    literal(name)
+        .requires(permission_level)
+        .executes(move |context| run_command(&plain, &plain_handler, context, ""))
+        .then(argument("args", ArgumentType::String(StringKind::Greedy))
+            .executes(move |context| run_command(&with_arguments, &handler, context, context.get_string("args")?)))
+}
+
+fn run_command(script: &Arc<Script>, handler: &FnPtr, context: &CommandContext, arguments: &str) -> anyhow::Result<()> {
+    if !script.is_enabled() {
+        bail!("Plugin {} is disabled", script.name);
+    }
+    let sender = match &context.source.sender {
+        CommandSender::Player(connection) => Dynamic::from(connection.clone()),
+        CommandSender::Console | CommandSender::Remote(_) => Dynamic::UNIT,
+    };
+    match script.call(handler, (sender, arguments.to_string())) {
+        Ok(reply) => {
+            if let Some(reply) = reply.try_cast::<rhai::ImmutableString>() {
+                context.source.send_message(TextComponent::text(reply.as_str()));
+            }
+            Ok(())
+        }
+        Err(err) => {
+            error!("{:#}", err);
+            bail!("The command failed");
+        }
+    }
+}
+
+fn subscribe(registrations: &Mutex<Vec<Registration>>, event: &str, priority: &str, handler: FnPtr) -> Result<(), Box<EvalAltResult>> {
+    let kind = ScriptEventKind::from_name(event).ok_or_else(|| format!("Unknown event {}", event))?;
+    let priority = match priority {
+        "lowest" => EventPriority::Lowest,
+        "low" => EventPriority::Low,
+        "normal" => EventPriority::Normal,
+        "high" => EventPriority::High,
+        "highest" => EventPriority::Highest,
+        "monitor" => EventPriority::Monitor,
+        priority => return Err(format!("Unknown priority {}", priority).into()),
+    };
+    registrations.lock().unwrap().push(Registration::Event { event: kind, priority, handler });
+    Ok(())
+}
+
+/// Runs a handler of the script for every event of the type, taking over what it changed.
+fn on_event<E: Event>(api: &ServerApi, script: &Arc<Script>, priority: EventPriority, handler: FnPtr) {
+    let script = script.clone();
+    let connections = api.connections().clone();
+    api.on_event(priority, move |event: &mut E| {
+        let shared = ScriptEvent { event: Arc::new(Mutex::new(event.clone())), connections: connections.clone() };
+        match script.call(&handler, (shared.clone(),)) {
+            Ok(_) => *event = shared.event.lock().unwrap().clone(),
+            Err(err) => error!("{:#}", err),
+        }
+    });
+}
+
+fn register_event_types(engine: &mut Engine) {
+    engine.register_type_with_name::<ScriptEvent<PlayerJoinEvent>>("PlayerJoinEvent")
+        .register_get("player", |event: &mut ScriptEvent<PlayerJoinEvent>| event.player(event.get(|event| event.connection_id)))
+        .register_get("id", |event: &mut ScriptEvent<PlayerJoinEvent>| event.get(|event| event.connection_id as i64))
+        .register_get("name", |event: &mut ScriptEvent<PlayerJoinEvent>| event.get(|event| event.username.clone()))
+        .register_get("uuid", |event: &mut ScriptEvent<PlayerJoinEvent>| event.get(|event| event.uuid.to_string()));
+
+    engine.register_type_with_name::<ScriptEvent<ChatEvent>>("ChatEvent")
+        .register_get("player", |event: &mut ScriptEvent<ChatEvent>| event.player(event.get(|event| event.connection_id)))
+        .register_get("id", |event: &mut ScriptEvent<ChatEvent>| event.get(|event| event.connection_id as i64))
+        .register_get("name", |event: &mut ScriptEvent<ChatEvent>| event.get(|event| event.username.clone()))
+        .register_get("uuid", |event: &mut ScriptEvent<ChatEvent>| event.get(|event| event.uuid.to_string()))
+        .register_get_set("message",
+            |event: &mut ScriptEvent<ChatEvent>| event.get(|event| event.message.clone()),
+            |event: &mut ScriptEvent<ChatEvent>, message: String| event.set(|event| event.message = message))
+        .register_get("cancelled", |event: &mut ScriptEvent<ChatEvent>| event.get(ChatEvent::is_cancelled))
+        .register_fn("cancel", |event: &mut ScriptEvent<ChatEvent>| event.set(ChatEvent::cancel));
+
+    engine.register_type_with_name::<ScriptEvent<BlockBreakEvent>>("BlockBreakEvent")
+        .register_get("player", |event: &mut ScriptEvent<BlockBreakEvent>| event.player(event.get(|event| event.connection_id)))
+        .register_get("id", |event: &mut ScriptEvent<BlockBreakEvent>| event.get(|event| event.connection_id as i64))
+        .register_get("name", |event: &mut ScriptEvent<BlockBreakEvent>| event.get(|event| event.username.clone()))
+        .register_get("x", |event: &mut ScriptEvent<BlockBreakEvent>| event.get(|event| event.position.x as i64))
+        .register_get("y", |event: &mut ScriptEvent<BlockBreakEvent>| event.get(|event| event.position.y as i64))
+        .register_get("z", |event: &mut ScriptEvent<BlockBreakEvent>| event.get(|event| event.position.z as i64))
+        .register_get("block", |event: &mut ScriptEvent<BlockBreakEvent>| {
+            event.get(|event| event.block.as_ref().map_or(Dynamic::UNIT, |block| Dynamic::from(block.to_string())))
+        })
+        .register_get("state_id", |event: &mut ScriptEvent<BlockBreakEvent>| event.get(|event| event.state_id as i64))
+        .register_get("cancelled", |event: &mut ScriptEvent<BlockBreakEvent>| event.get(BlockBreakEvent::is_cancelled))
+        .register_fn("cancel", |event: &mut ScriptEvent<BlockBreakEvent>| event.set(BlockBreakEvent::cancel));
+
+    engine.register_type_with_name::<ScriptEvent<PacketReceiveEvent>>("PacketEvent")
+        .register_get("player", |event: &mut ScriptEvent<PacketReceiveEvent>| event.player(event.get(|event| event.connection_id)))
+        .register_get("id", |event: &mut ScriptEvent<PacketReceiveEvent>| event.get(|event| event.connection_id as i64))
+        .register_get("packet", |event: &mut ScriptEvent<PacketReceiveEvent>| event.get(|event| event.packet.to_string()))
+        .register_get("packet_id", |event: &mut ScriptEvent<PacketReceiveEvent>| event.get(|event| event.packet_id as i64))
+        .register_get_set("payload",
+            |event: &mut ScriptEvent<PacketReceiveEvent>| event.get(|event| event.payload.clone()),
+            |event: &mut ScriptEvent<PacketReceiveEvent>, payload: Blob| event.set(|event| event.payload = payload))
+        .register_get("cancelled", |event: &mut ScriptEvent<PacketReceiveEvent>| event.get(PacketReceiveEvent::is_cancelled))
+        .register_fn("cancel", |event: &mut ScriptEvent<PacketReceiveEvent>| event.set(PacketReceiveEvent::cancel));
+}
+
+fn send(player: &ConnectionHandle, message: &str, overlay: bool) -> bool {
+    player.send(&SystemChatMessage { content: TextComponent::text(message), overlay }).is_ok()
+}
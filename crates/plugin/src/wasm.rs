@@ -0,0 +1,440 @@
+//! Sandboxed plugins compiled to WebAssembly, loaded from `.wasm` files in the plugins directory.
+//!
+//! Modules get no WASI, only the functions below, a bounded memory and a fuel budget per call, so a
+//! plugin which misbehaves traps instead of taking the server down. Strings are UTF-8 passed as a
+//! pointer and a length into the module's memory, connections by their id.
+//!
+//! The module exports `memory` and `alloc(len) -> ptr`, which the server uses to pass data in, and
+//! optionally `dolls_enable()` and `dolls_disable()`. Handlers are registered from `dolls_enable` by
+//! importing from the `dolls` module:
+//!
+//! - `log(ptr, len)` logs a line.
+//! - `register_join_listener()` calls the export `on_join(connection: i64, name_ptr, name_len)` for
+//!   every player entering Play.
+//! - `register_channel(ptr, len) -> i32` calls the export `on_plugin_message(connection: i64,
+//!   channel_ptr, channel_len, data_ptr, data_len)` for plugin messages on the channel.
+//! - `register_command(ptr, len) -> i32` adds a command taking the rest of the line as arguments,
+//!   running the export `on_command(connection: i64, name_ptr, name_len, args_ptr, args_len)`. The
+//!   console runs commands as connection -1.
+//! - `reply(ptr, len)` answers the sender of the command being run.
+//! - `send_message(connection: i64, ptr, len) -> i32` sends a chat line to a player, -1 to every player.
+//! - `send_plugin_message(connection: i64, channel_ptr, channel_len, data_ptr, data_len) -> i32`.
+//! - `subscribe_event(ptr, len, priority: i32) -> i32` subscribes to an event of the event bus, with
+//!   the priority from 0 for `Lowest` to 5 for `Monitor`:
+//!   - `player_join` calls `on_player_join(connection: i64, name_ptr, name_len)`.
+//!   - `chat` calls `on_chat(connection: i64, message_ptr, message_len)`.
+//!   - `block_break` calls `on_block_break(connection: i64, x: i32, y: i32, z: i32, state_id: i32)`.
+//! - `subscribe_packet(ptr, len, priority: i32) -> i32` calls `on_packet(connection: i64, packet_id:
+//!   i32, payload_ptr, payload_len)` for every packet of the name, e.g. `ChatMessage`, the client sends.
+//! - `replace(ptr, len)` replaces the chat message or the packet payload being handled.
+//!
+//! Functions returning `i32` return 0 on success and -1 otherwise. Event and packet handlers may return
+//! an `i32`, anything but 0 cancels the event or drops the packet. They run under the same fuel budget
+//! and memory limit as every other call.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use anyhow::{anyhow, bail, Context};
+use log::{error, info};
+use wasmtime::{Caller, Config, Engine, Extern, Instance, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder, Val};
+use dolls_commands::prelude::{argument, literal, ArgumentType, CommandContext, CommandSender, StringKind};
+use dolls_core::datatype::Identifier;
+use dolls_core::text::TextComponent;
+use dolls_events::prelude::{BlockBreakEvent, ChatEvent, EventPriority, PacketReceiveEvent, PlayerJoinEvent};
+use dolls_network::prelude::{send_plugin_message, ConnectionHandle, ConnectionRegistry, JoinListener, PacketContext, PacketType, PluginChannelHandler, SystemChatMessage};
+use crate::prelude::{Plugin, ServerApi};
+
+/// Instructions a single call into a module may run.
+pub const WASM_FUEL_PER_CALL: u64 = 10_000_000;
+/// Largest linear memory a module may grow to.
+pub const WASM_MAX_MEMORY: usize = 64 << 20;
+/// Connection id standing for the console or every player.
+const NO_CONNECTION: i64 = -1;
+
+/// Events of the event bus a module may subscribe to.
+#[derive(Debug, Copy, Clone)]
+enum WasmEvent {
+    PlayerJoin,
+    Chat,
+    BlockBreak,
+}
+
+impl WasmEvent {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "player_join" => Some(Self::PlayerJoin),
+            "chat" => Some(Self::Chat),
+            "block_break" => Some(Self::BlockBreak),
+            _ => None,
+        }
+    }
+}
+
+/// What a module asked for while it was being enabled.
+enum Registration {
+    Join,
+    Channel(Identifier),
+    Command(String),
+    Event(WasmEvent, EventPriority),
+    Packet(PacketType, EventPriority),
+}
+
+struct WasmState {
+    plugin_name: String,
+    connections: Arc<ConnectionRegistry>,
+    limits: StoreLimits,
+    registrations: Vec<Registration>,
+    /// Answers of the module to the command being run.
+    replies: Vec<String>,
+    /// What the module replaced the data of the event being handled with.
+    replacement: Option<Vec<u8>>,
+}
+
+struct WasmInstance {
+    store: Store<WasmState>,
+    instance: Instance,
+    memory: Memory,
+    enabled: bool,
+}
+
+impl WasmInstance {
+    /// Calls an export with a fresh fuel budget, missing exports are skipped.
+    fn call(&mut self, export: &str, args: &[Val]) -> anyhow::Result<()> {
+        self.call_returning(export, args).map(|_| ())
+    }
+
+    /// Calls an export which may return an `i32`, missing exports and those returning nothing give 0.
+    fn call_returning(&mut self, export: &str, args: &[Val]) -> anyhow::Result<i32> {
+        let Some(function) = self.instance.get_func(&mut self.store, export) else { return Ok(0) };
+        let mut results = vec![Val::I32(0); function.ty(&self.store).results().len()];
+        self.store.set_fuel(WASM_FUEL_PER_CALL)?;
+        function.call(&mut self.store, args, &mut results)
+            .with_context(|| format!("{} of plugin {} failed", export, self.store.data().plugin_name))?;
+        Ok(results.first().and_then(Val::i32).unwrap_or(0))
+    }
+
+    /// Calls an event handler, returns whether it cancelled the event and what it replaced its data with.
+    fn call_handler(&mut self, export: &str, args: &[Val]) -> anyhow::Result<(bool, Option<Vec<u8>>)> {
+        self.store.data_mut().replacement = None;
+        let result = self.call_returning(export, args);
+        let replacement = self.store.data_mut().replacement.take();
+        Ok((result? != 0, replacement))
+    }
+
+    /// Copies bytes into memory the module allocated, returns their pointer and length.
+    fn pass(&mut self, bytes: &[u8]) -> anyhow::Result<[Val; 2]> {
+        let alloc = self.instance.get_typed_func::<i32, i32>(&mut self.store, "alloc")?;
+        self.store.set_fuel(WASM_FUEL_PER_CALL)?;
+        let pointer = alloc.call(&mut self.store, bytes.len() as i32)?;
+        self.memory.write(&mut self.store, pointer as usize, bytes)?;
+        Ok([Val::I32(pointer), Val::I32(bytes.len() as i32)])
+    }
+}
+
+/// A plugin in a WebAssembly module, named after its file.
+pub struct WasmPlugin {
+    name: String,
+    path: PathBuf,
+    engine: Engine,
+    module: Option<Module>,
+    instance: Option<Arc<Mutex<WasmInstance>>>,
+}
+
+impl WasmPlugin {
+    pub fn new(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let name = path.file_stem().and_then(|stem| stem.to_str()).ok_or_else(|| anyhow!("Invalid plugin file name {}", path.display()))?;
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        Ok(Self { name: name.to_string(), path: path.to_path_buf(), engine: Engine::new(&config)?, module: None, instance: None })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn linker(&self) -> anyhow::Result<Linker<WasmState>> {
+        let mut linker = Linker::new(&self.engine);
+        linker.func_wrap("dolls", "log", |mut caller: Caller<'_, WasmState>, pointer: i32, length: i32| {
+            let line = read_string(&mut caller, pointer, length)?;
+            info!("[{}] {}", caller.data().plugin_name, line);
+            Ok(())
+        })?;
+        linker.func_wrap("dolls", "register_join_listener", |mut caller: Caller<'_, WasmState>| {
+            caller.data_mut().registrations.push(Registration::Join);
+        })?;
+        linker.func_wrap("dolls", "register_channel", |mut caller: Caller<'_, WasmState>, pointer: i32, length: i32| {
+            let Ok(channel) = read_string(&mut caller, pointer, length)?.parse() else { return Ok(-1) };
+            caller.data_mut().registrations.push(Registration::Channel(channel));
+            Ok(0)
+        })?;
+        linker.func_wrap("dolls", "register_command", |mut caller: Caller<'_, WasmState>, pointer: i32, length: i32| {
+            let name = read_string(&mut caller, pointer, length)?;
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                return Ok(-1);
+            }
+            caller.data_mut().registrations.push(Registration::Command(name));
+            Ok(0)
+        })?;
+        linker.func_wrap("dolls", "reply", |mut caller: Caller<'_, WasmState>, pointer: i32, length: i32| {
+            let reply = read_string(&mut caller, pointer, length)?;
+            caller.data_mut().replies.push(reply);
+            Ok(())
+        })?;
+        linker.func_wrap("dolls", "send_message", |mut caller: Caller<'_, WasmState>, connection: i64, pointer: i32, length: i32| {
+            let message = SystemChatMessage { content: TextComponent::text(read_string(&mut caller, pointer, length)?), overlay: false };
+            let targets = find_targets(&caller.data().connections, connection);
+            if targets.is_empty() && connection != NO_CONNECTION {
+                return Ok(-1);
+            }
+            for target in targets {
+                // Players leaving meanwhile miss the message.
+                let _ = target.send(&message);
+            }
+            Ok(0)
+        })?;
+        linker.func_wrap("dolls", "send_plugin_message",
+            |mut caller: Caller<'_, WasmState>, connection: i64, channel_pointer: i32, channel_length: i32, data_pointer: i32, data_length: i32| {
+                let Ok(channel) = read_string(&mut caller, channel_pointer, channel_length)?.parse::<Identifier>() else { return Ok(-1) };
+                let data = read_bytes(&mut caller, data_pointer, data_length)?;
+                let Some(target) = caller.data().connections.get(connection as u64) else { return Ok(-1) };
+                Ok(if send_plugin_message(&target, channel, data).is_ok() { 0 } else { -1 })
+            })?;
+        linker.func_wrap("dolls", "subscribe_event", |mut caller: Caller<'_, WasmState>, pointer: i32, length: i32, priority: i32| {
+            let event = WasmEvent::from_name(&read_string(&mut caller, pointer, length)?);
+            let (Some(event), Some(priority)) = (event, event_priority(priority)) else { return Ok(-1) };
+            caller.data_mut().registrations.push(Registration::Event(event, priority));
+            Ok(0)
+        })?;
+        linker.func_wrap("dolls", "subscribe_packet", |mut caller: Caller<'_, WasmState>, pointer: i32, length: i32, priority: i32| {
+            let packet = PacketType::from_name(&read_string(&mut caller, pointer, length)?);
+            let (Some(packet), Some(priority)) = (packet, event_priority(priority)) else { return Ok(-1) };
+            caller.data_mut().registrations.push(Registration::Packet(packet, priority));
+            Ok(0)
+        })?;
+        linker.func_wrap("dolls", "replace", |mut caller: Caller<'_, WasmState>, pointer: i32, length: i32| {
+            let replacement = read_bytes(&mut caller, pointer, length)?;
+            caller.data_mut().replacement = Some(replacement);
+            Ok(())
+        })?;
+        Ok(linker)
+    }
+}
+
+impl Plugin for WasmPlugin {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn on_load(&mut self, _api: &Arc<ServerApi>) -> anyhow::Result<()> {
+        let bytes = std::fs::read(&self.path).with_context(|| format!("Failed to read {}", self.path.display()))?;
+        self.module = Some(Module::new(&self.engine, bytes)?);
+        Ok(())
+    }
+
+    fn on_enable(&mut self, api: &Arc<ServerApi>) -> anyhow::Result<()> {
+        let Some(module) = &self.module else { bail!("Plugin {} was not loaded", self.name) };
+        let state = WasmState {
+            plugin_name: self.name.clone(),
+            connections: api.connections().clone(),
+            limits: StoreLimitsBuilder::new().memory_size(WASM_MAX_MEMORY).instances(1).build(),
+            registrations: Vec::new(),
+            replies: Vec::new(),
+            replacement: None,
+        };
+        let mut store = Store::new(&self.engine, state);
+        store.limiter(|state| &mut state.limits);
+        store.set_fuel(WASM_FUEL_PER_CALL)?;
+        let instance = self.linker()?.instantiate(&mut store, module)?;
+        let memory = instance.get_memory(&mut store, "memory").ok_or_else(|| anyhow!("Plugin {} does not export its memory", self.name))?;
+        let mut wasm = WasmInstance { store, instance, memory, enabled: true };
+        wasm.call("dolls_enable", &[])?;
+
+        let registrations = std::mem::take(&mut wasm.store.data_mut().registrations);
+        let wasm = Arc::new(Mutex::new(wasm));
+        for registration in registrations {
+            match registration {
+                Registration::Join => api.on_join(Arc::new(WasmHandler(wasm.clone()))),
+                Registration::Channel(channel) => api.on_plugin_message(channel.clone(), Arc::new(WasmChannel { wasm: wasm.clone(), channel })),
+                Registration::Command(name) => {
+                    let (plain, with_arguments) = (wasm.clone(), wasm.clone());
+                    let (plain_name, arguments_name) = (name.clone(), name.clone());
+                    api.register_command(literal(name)
+                        .executes(move |context| run_command(&plain, context, &plain_name, ""))
+                        .then(argument("args", ArgumentType::String(StringKind::Greedy))
+                            .executes(move |context| run_command(&with_arguments, context, &arguments_name, context.get_string("args")?))));
+                }
+                Registration::Event(event, priority) => subscribe_event(api, &wasm, event, priority),
+                Registration::Packet(packet, priority) => {
+                    let wasm = wasm.clone();
+                    api.on_event(priority, move |event: &mut PacketReceiveEvent| {
+                        if event.packet == packet.name() {
+                            handle_event(&wasm, |wasm| wasm.packet(event));
+                        }
+                    });
+                }
+            }
+        }
+        self.instance = Some(wasm);
+        Ok(())
+    }
+
+    fn on_disable(&mut self, _api: &Arc<ServerApi>) -> anyhow::Result<()> {
+        // Event subscribers are removed by the manager, other handlers stay registered with the server
+        // and do nothing from now on.
+        let Some(wasm) = self.instance.take() else { return Ok(()) };
+        let mut wasm = wasm.lock().unwrap();
+        wasm.enabled = false;
+        wasm.call("dolls_disable", &[])
+    }
+}
+
+/// Forwards players entering Play to a module.
+struct WasmHandler(Arc<Mutex<WasmInstance>>);
+
+impl JoinListener for WasmHandler {
+    fn on_join(&self, context: &mut PacketContext) -> anyhow::Result<()> {
+        let mut wasm = self.0.lock().unwrap();
+        if !wasm.enabled {
+            return Ok(());
+        }
+        let [name_pointer, name_length] = wasm.pass(context.username.as_deref().unwrap_or_default().as_bytes())?;
+        wasm.call("on_join", &[Val::I64(context.connection.id() as i64), name_pointer, name_length])
+    }
+}
+
+/// Forwards the plugin messages of a channel to a module.
+struct WasmChannel {
+    wasm: Arc<Mutex<WasmInstance>>,
+    channel: Identifier,
+}
+
+impl PluginChannelHandler for WasmChannel {
+    fn on_message(&self, context: &mut PacketContext, data: &[u8]) -> anyhow::Result<()> {
+        let mut wasm = self.wasm.lock().unwrap();
+        if !wasm.enabled {
+            return Ok(());
+        }
+        let [channel_pointer, channel_length] = wasm.pass(self.channel.to_string().as_bytes())?;
+        let [data_pointer, data_length] = wasm.pass(data)?;
+        wasm.call("on_plugin_message", &[Val::I64(context.connection.id() as i64), channel_pointer, channel_length, data_pointer, data_length])
+    }
+}
+
+impl WasmInstance {
+    fn player_join(&mut self, event: &mut PlayerJoinEvent) -> anyhow::Result<()> {
+        let [name_pointer, name_length] = self.pass(event.username.as_bytes())?;
+        self.call("on_player_join", &[Val::I64(event.connection_id as i64), name_pointer, name_length])
+    }
+
+    fn chat(&mut self, event: &mut ChatEvent) -> anyhow::Result<()> {
+        let [message_pointer, message_length] = self.pass(event.message.as_bytes())?;
+        let (cancelled, replacement) = self.call_handler("on_chat", &[Val::I64(event.connection_id as i64), message_pointer, message_length])?;
+        if cancelled {
+            event.cancel();
+        }
+        if let Some(message) = replacement {
+            event.message = String::from_utf8(message).context("The replaced chat message is not UTF-8")?;
+        }
+        Ok(())
+    }
+
+    fn block_break(&mut self, event: &mut BlockBreakEvent) -> anyhow::Result<()> {
+        let position = event.position;
+        let args = [Val::I64(event.connection_id as i64), Val::I32(position.x), Val::I32(position.y), Val::I32(position.z), Val::I32(event.state_id as i32)];
+        if self.call_handler("on_block_break", &args)?.0 {
+            event.cancel();
+        }
+        Ok(())
+    }
+
+    fn packet(&mut self, event: &mut PacketReceiveEvent) -> anyhow::Result<()> {
+        let [payload_pointer, payload_length] = self.pass(&event.payload)?;
+        let args = [Val::I64(event.connection_id as i64), Val::I32(event.packet_id as i32), payload_pointer, payload_length];
+        let (cancelled, replacement) = self.call_handler("on_packet", &args)?;
+        if cancelled {
+            event.cancel();
+        }
+        if let Some(payload) = replacement {
+            event.payload = payload;
+        }
+        Ok(())
+    }
+}
+
+fn subscribe_event(api: &ServerApi, wasm: &Arc<Mutex<WasmInstance>>, event: WasmEvent, priority: EventPriority) {
+    let wasm = wasm.clone();
+    match event {
+        WasmEvent::PlayerJoin => api.on_event(priority, move |event: &mut PlayerJoinEvent| handle_event(&wasm, |wasm| wasm.player_join(event))),
+        WasmEvent::Chat => api.on_event(priority, move |event: &mut ChatEvent| handle_event(&wasm, |wasm| wasm.chat(event))),
+        WasmEvent::BlockBreak => api.on_event(priority, move |event: &mut BlockBreakEvent| handle_event(&wasm, |wasm| wasm.block_break(event))),
+    };
+}
+
+/// Runs a module's handler for an event, subscribers cannot fail so errors are logged.
+fn handle_event(wasm: &Mutex<WasmInstance>, handler: impl FnOnce(&mut WasmInstance) -> anyhow::Result<()>) {
+    let mut wasm = wasm.lock().unwrap();
+    if !wasm.enabled {
+        return;
+    }
+    if let Err(err) = handler(&mut wasm) {
+        error!("{:#}", err);
+    }
+}
+
+fn event_priority(priority: i32) -> Option<EventPriority> {
+    match priority {
+        0 => Some(EventPriority::Lowest),
+        1 => Some(EventPriority::Low),
+        2 => Some(EventPriority::Normal),
+        3 => Some(EventPriority::High),
+        4 => Some(EventPriority::Highest),
+        5 => Some(EventPriority::Monitor),
+        _ => None,
+    }
+}
+
+fn run_command(wasm: &Mutex<WasmInstance>, context: &CommandContext, name: &str, arguments: &str) -> anyhow::Result<()> {
+    let connection = match &context.source.sender {
+        CommandSender::Player(connection) => connection.id() as i64,
+        CommandSender::Console | CommandSender::Remote(_) => NO_CONNECTION,
+    };
+    let replies = {
+        let mut wasm = wasm.lock().unwrap();
+        if !wasm.enabled {
+            bail!("Plugin {} is disabled", wasm.store.data().plugin_name);
+        }
+        let [name_pointer, name_length] = wasm.pass(name.as_bytes())?;
+        let [arguments_pointer, arguments_length] = wasm.pass(arguments.as_bytes())?;
+        let result = wasm.call("on_command", &[Val::I64(connection), name_pointer, name_length, arguments_pointer, arguments_length]);
+        let replies = std::mem::take(&mut wasm.store.data_mut().replies);
+        if let Err(err) = result {
+            error!("{:#}", err);
+            bail!("The command failed");
+        }
+        replies
+    };
+    for reply in replies {
+        context.source.send_message(TextComponent::text(reply));
+    }
+    Ok(())
+}
+
+fn find_targets(connections: &ConnectionRegistry, connection: i64) -> Vec<ConnectionHandle> {
+    match connection {
+        NO_CONNECTION => connections.players(),
+        id => connections.get(id as u64).into_iter().collect(),
+    }
+}
+
+fn read_bytes(caller: &mut Caller<'_, WasmState>, pointer: i32, length: i32) -> anyhow::Result<Vec<u8>> {
+    let Some(Extern::Memory(memory)) = caller.get_export("memory") else { bail!("The module does not export its memory") };
+    let start = usize::try_from(pointer)?;
+    let end = start.checked_add(usize::try_from(length)?).ok_or_else(|| anyhow!("Out of bounds"))?;
+    let bytes = memory.data(&caller).get(start..end).ok_or_else(|| anyhow!("Out of bounds"))?;
+    Ok(bytes.to_vec())
+}
+
+fn read_string(caller: &mut Caller<'_, WasmState>, pointer: i32, length: i32) -> anyhow::Result<String> {
+    Ok(String::from_utf8(read_bytes(caller, pointer, length)?)?)
+}
@@ -0,0 +1,84 @@
+#![cfg(feature = "scripting")]
+
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use dolls_core::datatype::{BlockPos, Uuid};
+use dolls_events::prelude::{event_bus, BlockBreakEvent, ChatEvent, Event, PacketReceiveEvent};
+use dolls_network::prelude::DollNetworkServer;
+use dolls_plugin::prelude::{PluginManager, ScriptPlugin};
+
+const SCRIPT: &str = r#"
+on_event("chat", |event| {
+    if event.message.contains("heck") {
+        event.cancel();
+    }
+    event.message = `<${event.name}> ${event.message}`;
+});
+on_event("block_break", "high", |event| if event.y < 0 { event.cancel() });
+on_event("packet", |event| if event.packet == "ChatMessage" { event.payload = blob(2, 7) });
+"#;
+
+fn script(name: &str, source: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("dolls-{}-{}.rhai", name, std::process::id()));
+    std::fs::write(&path, source).unwrap();
+    path
+}
+
+fn plugin_name(path: &Path) -> &str {
+    path.file_stem().unwrap().to_str().unwrap()
+}
+
+fn chat(message: &str) -> ChatEvent {
+    ChatEvent { connection_id: 7, username: "Alice".to_string(), uuid: Uuid::nil(), message: message.to_string(), cancelled: false }
+}
+
+fn block_break(y: i32) -> BlockBreakEvent {
+    BlockBreakEvent { connection_id: 7, username: "Alice".to_string(), position: BlockPos::new(1, y, 3), block: None, state_id: 1, cancelled: false }
+}
+
+fn packet(packet: &'static str) -> PacketReceiveEvent {
+    PacketReceiveEvent { connection_id: 7, packet, packet_id: 0x06, payload: vec![1, 2, 3], cancelled: false }
+}
+
+#[test]
+fn scripts_handle_events() {
+    let path = script("events", SCRIPT);
+    let unknown = script("weather", r#"on_event("weather", |event| log("rain"));"#);
+    let mut manager = PluginManager::new(Arc::new(DollNetworkServer::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)));
+    manager.add(Box::new(ScriptPlugin::new(&path).unwrap()));
+    manager.add(Box::new(ScriptPlugin::new(&unknown).unwrap()));
+    manager.load_all();
+    manager.enable_all();
+    assert!(manager.is_enabled(plugin_name(&path)));
+    assert!(!manager.is_enabled(plugin_name(&unknown)));
+
+    let mut event = chat("hello");
+    event_bus().emit(&mut event);
+    assert_eq!(event.message, "<Alice> hello");
+    assert!(!event.is_cancelled());
+    let mut event = chat("what the heck");
+    event_bus().emit(&mut event);
+    assert!(event.is_cancelled());
+
+    let mut event = block_break(-5);
+    event_bus().emit(&mut event);
+    assert!(event.is_cancelled());
+    let mut event = block_break(5);
+    event_bus().emit(&mut event);
+    assert!(!event.is_cancelled());
+
+    let mut event = packet("ChatMessage");
+    event_bus().emit(&mut event);
+    assert_eq!(event.payload, [7, 7]);
+    let mut event = packet("ChatCommand");
+    event_bus().emit(&mut event);
+    assert_eq!(event.payload, [1, 2, 3]);
+
+    manager.disable_all();
+    let mut event = chat("hello");
+    event_bus().emit(&mut event);
+    assert_eq!(event.message, "hello");
+    let _ = std::fs::remove_file(&path);
+    let _ = std::fs::remove_file(&unknown);
+}
@@ -0,0 +1,86 @@
+#![cfg(feature = "wasm")]
+
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::Arc;
+use dolls_core::datatype::{BlockPos, Uuid};
+use dolls_events::prelude::{event_bus, BlockBreakEvent, ChatEvent, Event, PacketReceiveEvent};
+use dolls_network::prelude::DollNetworkServer;
+use dolls_plugin::prelude::{PluginManager, WasmPlugin};
+
+/// Replaces chat messages, drops the chat packets of connection 7 and loops forever on broken blocks.
+const MODULE: &str = r#"
+(module
+  (import "dolls" "subscribe_event" (func $subscribe_event (param i32 i32 i32) (result i32)))
+  (import "dolls" "subscribe_packet" (func $subscribe_packet (param i32 i32 i32) (result i32)))
+  (import "dolls" "replace" (func $replace (param i32 i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "chat")
+  (data (i32.const 16) "block_break")
+  (data (i32.const 32) "ChatMessage")
+  (data (i32.const 48) "weather")
+  (data (i32.const 64) "replaced")
+  (global $next (mut i32) (i32.const 1024))
+  (func (export "alloc") (param $length i32) (result i32)
+    (global.get $next)
+    (global.set $next (i32.add (global.get $next) (local.get $length))))
+  (func (export "dolls_enable")
+    (if (i32.ne (call $subscribe_event (i32.const 48) (i32.const 7) (i32.const 2)) (i32.const -1)) (then unreachable))
+    (if (i32.ne (call $subscribe_event (i32.const 0) (i32.const 4) (i32.const 6)) (i32.const -1)) (then unreachable))
+    (if (i32.ne (call $subscribe_packet (i32.const 48) (i32.const 7) (i32.const 2)) (i32.const -1)) (then unreachable))
+    (drop (call $subscribe_event (i32.const 0) (i32.const 4) (i32.const 2)))
+    (drop (call $subscribe_event (i32.const 16) (i32.const 11) (i32.const 2)))
+    (drop (call $subscribe_packet (i32.const 32) (i32.const 11) (i32.const 2))))
+  (func (export "on_chat") (param i64 i32 i32) (result i32)
+    (call $replace (i32.const 64) (i32.const 8))
+    (i32.const 0))
+  (func (export "on_block_break") (param i64 i32 i32 i32 i32) (result i32)
+    (loop $forever (br $forever))
+    (i32.const 1))
+  (func (export "on_packet") (param $connection i64) (param i32 i32 i32) (result i32)
+    (i64.eq (local.get $connection) (i64.const 7))))
+"#;
+
+fn chat(message: &str) -> ChatEvent {
+    ChatEvent { connection_id: 7, username: "Alice".to_string(), uuid: Uuid::nil(), message: message.to_string(), cancelled: false }
+}
+
+fn packet(connection_id: u64, packet: &'static str) -> PacketReceiveEvent {
+    PacketReceiveEvent { connection_id, packet, packet_id: 0x06, payload: vec![1, 2, 3], cancelled: false }
+}
+
+#[test]
+fn wasm_plugins_handle_events_and_packets() {
+    let path = std::env::temp_dir().join(format!("dolls-wasm-{}.wasm", std::process::id()));
+    std::fs::write(&path, wat::parse_str(MODULE).unwrap()).unwrap();
+    let mut manager = PluginManager::new(Arc::new(DollNetworkServer::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)));
+    manager.add(Box::new(WasmPlugin::new(&path).unwrap()));
+    manager.load_all();
+    manager.enable_all();
+    assert!(manager.is_enabled(path.file_stem().unwrap().to_str().unwrap()));
+
+    let mut event = chat("hello");
+    event_bus().emit(&mut event);
+    assert_eq!(event.message, "replaced");
+    assert!(!event.is_cancelled());
+
+    let mut event = packet(7, "ChatMessage");
+    event_bus().emit(&mut event);
+    assert!(event.is_cancelled());
+    let mut event = packet(8, "ChatMessage");
+    event_bus().emit(&mut event);
+    assert!(!event.is_cancelled());
+    let mut event = packet(7, "ChatCommand");
+    event_bus().emit(&mut event);
+    assert!(!event.is_cancelled());
+
+    // The handler runs out of fuel, which leaves the event as it was.
+    let mut event = BlockBreakEvent { connection_id: 7, username: "Alice".to_string(), position: BlockPos::new(1, 2, 3), block: None, state_id: 1, cancelled: false };
+    event_bus().emit(&mut event);
+    assert!(!event.is_cancelled());
+
+    manager.disable_all();
+    let mut event = chat("hello");
+    event_bus().emit(&mut event);
+    assert_eq!(event.message, "hello");
+    let _ = std::fs::remove_file(&path);
+}
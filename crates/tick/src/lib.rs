@@ -0,0 +1,7 @@
+pub mod scheduler;
+pub mod tick_loop;
+
+pub mod prelude {
+    pub use crate::scheduler::*;
+    pub use crate::tick_loop::*;
+}
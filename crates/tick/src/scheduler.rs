@@ -0,0 +1,178 @@
+use std::future::Future;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use once_cell::sync::Lazy;
+use log::error;
+
+static SCHEDULER: Lazy<Scheduler> = Lazy::new(Scheduler::default);
+
+/// The scheduler the tick loop runs, game subsystems hook into it.
+pub fn scheduler() -> &'static Scheduler {
+    &SCHEDULER
+}
+
+/// A task given to the [`Scheduler`], it does not stop when the handle is dropped unless
+/// turned into a [`TaskGuard`].
+#[derive(Debug, Clone)]
+pub struct TaskHandle {
+    id: u64,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl TaskHandle {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Keeps the task from running again, a run in progress finishes.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Ties the task to the returned guard, for tasks which belong to something like a connection.
+    pub fn guard(self) -> TaskGuard {
+        TaskGuard(self)
+    }
+}
+
+/// Cancels its task when dropped.
+#[derive(Debug)]
+pub struct TaskGuard(TaskHandle);
+
+impl TaskGuard {
+    pub fn handle(&self) -> &TaskHandle {
+        &self.0
+    }
+}
+
+impl Drop for TaskGuard {
+    fn drop(&mut self) {
+        self.0.cancel();
+    }
+}
+
+enum TaskKind {
+    Once(Box<dyn FnOnce() + Send>),
+    Repeating { period: u64, task: Box<dyn FnMut() + Send> },
+}
+
+struct Task {
+    name: String,
+    due: u64,
+    cancelled: Arc<AtomicBool>,
+    kind: Option<TaskKind>,
+}
+
+/// Runs tasks on the tick thread, delays and periods are counted in ticks.
+#[derive(Default)]
+pub struct Scheduler {
+    current_tick: AtomicU64,
+    next_id: AtomicU64,
+    /// Tasks added since the last tick, kept apart so that running tasks may add more.
+    incoming: Mutex<Vec<Task>>,
+    /// Only touched by the tick thread.
+    tasks: Mutex<Vec<Task>>,
+}
+
+impl std::fmt::Debug for Scheduler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Scheduler")
+            .field("current_tick", &self.current_tick())
+            .field("tasks", &self.pending_tasks())
+            .finish()
+    }
+}
+
+impl Scheduler {
+    /// Ticks run so far.
+    pub fn current_tick(&self) -> u64 {
+        self.current_tick.load(Ordering::Relaxed)
+    }
+
+    /// Tasks waiting to run, including repeating ones.
+    pub fn pending_tasks(&self) -> usize {
+        self.tasks.lock().unwrap().len() + self.incoming.lock().unwrap().len()
+    }
+
+    fn push(&self, name: String, delay: u64, kind: TaskKind) -> TaskHandle {
+        let handle = TaskHandle { id: self.next_id.fetch_add(1, Ordering::Relaxed), cancelled: Arc::default() };
+        // A delay of zero runs on the next tick, never in the middle of the current one.
+        let due = self.current_tick() + delay.max(1);
+        self.incoming.lock().unwrap().push(Task { name, due, cancelled: handle.cancelled.clone(), kind: Some(kind) });
+        handle
+    }
+
+    /// Runs `task` once, `delay` ticks from now.
+    pub fn run_later(&self, name: impl Into<String>, delay: u64, task: impl FnOnce() + Send + 'static) -> TaskHandle {
+        self.push(name.into(), delay, TaskKind::Once(Box::new(task)))
+    }
+
+    /// Runs `task` `delay` ticks from now and then every `period` ticks until cancelled.
+    pub fn run_repeating(&self, name: impl Into<String>, delay: u64, period: u64, task: impl FnMut() + Send + 'static) -> TaskHandle {
+        self.push(name.into(), delay, TaskKind::Repeating { period: period.max(1), task: Box::new(task) })
+    }
+
+    /// Runs `future` off the tick thread, then `then` with its output on the next tick, for work
+    /// like disk or network I/O whose result changes game state.
+    pub fn run_async<T, Fut, F>(&'static self, name: impl Into<String>, future: Fut, then: F) -> TaskHandle
+    where
+        T: Send + 'static,
+        Fut: Future<Output = T> + Send + 'static,
+        F: FnOnce(T) + Send + 'static,
+    {
+        let name = name.into();
+        let handle = TaskHandle { id: self.next_id.fetch_add(1, Ordering::Relaxed), cancelled: Arc::default() };
+        let cancelled = handle.cancelled.clone();
+        async_std::task::spawn(async move {
+            let output = future.await;
+            if !cancelled.load(Ordering::Relaxed) {
+                let task = Task { name, due: 0, cancelled, kind: Some(TaskKind::Once(Box::new(move || then(output)))) };
+                self.incoming.lock().unwrap().push(task);
+            }
+        });
+        handle
+    }
+
+    /// Advances one tick and runs the tasks due, the tick loop calls this every 50 ms.
+    pub fn tick(&self) {
+        let tick = self.current_tick.fetch_add(1, Ordering::Relaxed) + 1;
+        let mut tasks = self.tasks.lock().unwrap();
+        tasks.append(&mut self.incoming.lock().unwrap());
+        tasks.retain_mut(|task| {
+            if task.cancelled.load(Ordering::Relaxed) {
+                return false;
+            }
+            if task.due > tick {
+                return true;
+            }
+            let Some(kind) = task.kind.take() else { return false };
+            let result = catch_unwind(AssertUnwindSafe(|| match kind {
+                TaskKind::Once(run) => {
+                    run();
+                    None
+                }
+                TaskKind::Repeating { period, mut task } => {
+                    task();
+                    Some(TaskKind::Repeating { period, task })
+                }
+            }));
+            match result {
+                Ok(Some(kind @ TaskKind::Repeating { period, .. })) => {
+                    task.due = tick + period;
+                    task.kind = Some(kind);
+                    true
+                }
+                Ok(_) => false,
+                Err(_) => {
+                    error!("Task {} panicked and was cancelled", task.name);
+                    false
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,130 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use once_cell::sync::Lazy;
+use log::{info, warn};
+use crate::prelude::scheduler;
+
+pub const TICKS_PER_SECOND: u32 = 20;
+pub const TICK_DURATION: Duration = Duration::from_millis(1000 / TICKS_PER_SECOND as u64);
+/// How far the loop may fall behind before it gives up catching up, as in vanilla.
+const MAX_LAG: Duration = Duration::from_secs(2);
+/// Ticks the statistics are averaged over.
+const SAMPLED_TICKS: usize = 100;
+
+/// Upper bounds of the tick duration buckets, the last bucket holds everything slower.
+pub const TICK_BUCKETS: [Duration; 7] = [
+    Duration::from_millis(1),
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(25),
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(250),
+];
+
+static TICK_TIMES: Lazy<Mutex<VecDeque<Duration>>> = Lazy::new(|| Mutex::new(VecDeque::with_capacity(SAMPLED_TICKS)));
+static TICK_STATS: Lazy<Mutex<TickStats>> = Lazy::new(|| Mutex::new(TickStats::default()));
+
+/// Every tick since the loop started, for exporters.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TickStats {
+    pub ticks: u64,
+    pub total: Duration,
+    /// Ticks per bucket of [`TICK_BUCKETS`], plus one for slower ones.
+    pub buckets: [u64; TICK_BUCKETS.len() + 1],
+}
+
+pub fn tick_stats() -> TickStats {
+    TICK_STATS.lock().unwrap().clone()
+}
+
+/// Average time recent ticks took to run.
+pub fn mean_tick_time() -> Duration {
+    let times = TICK_TIMES.lock().unwrap();
+    match times.len() {
+        0 => Duration::ZERO,
+        count => times.iter().sum::<Duration>() / count as u32,
+    }
+}
+
+/// Ticks per second the loop keeps up with, at most [`TICKS_PER_SECOND`].
+pub fn ticks_per_second() -> f64 {
+    let mean = mean_tick_time().as_secs_f64();
+    match mean > TICK_DURATION.as_secs_f64() {
+        true => 1.0 / mean,
+        false => TICKS_PER_SECOND as f64,
+    }
+}
+
+/// The running tick loop, dropping it stops the loop after the tick in progress.
+#[derive(Debug)]
+pub struct TickLoop {
+    running: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl TickLoop {
+    /// Ticks the global [`scheduler`] [`TICKS_PER_SECOND`] times a second on its own thread.
+    pub fn start() -> Self {
+        let running = Arc::new(AtomicBool::new(true));
+        let thread = {
+            let running = running.clone();
+            std::thread::Builder::new()
+                .name("Tick".to_string())
+                .spawn(move || run(&running))
+                .expect("Spawning the tick thread does not fail")
+        };
+        info!("Tick loop started.");
+        Self { running, thread: Some(thread) }
+    }
+
+    pub fn stop(self) {}
+}
+
+impl Drop for TickLoop {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn run(running: &AtomicBool) {
+    let mut next = Instant::now();
+    while running.load(Ordering::Relaxed) {
+        let started = Instant::now();
+        scheduler().tick();
+        let took = started.elapsed();
+        {
+            let mut times = TICK_TIMES.lock().unwrap();
+            if times.len() == SAMPLED_TICKS {
+                times.pop_front();
+            }
+            times.push_back(took);
+        }
+        {
+            let mut stats = TICK_STATS.lock().unwrap();
+            stats.ticks += 1;
+            stats.total += took;
+            let bucket = TICK_BUCKETS.iter().position(|bound| took <= *bound).unwrap_or(TICK_BUCKETS.len());
+            stats.buckets[bucket] += 1;
+        }
+
+        next += TICK_DURATION;
+        let now = Instant::now();
+        match next.checked_duration_since(now) {
+            Some(wait) => std::thread::sleep(wait),
+            None if now - next > MAX_LAG => {
+                let behind = now - next;
+                warn!("Can't keep up! Running {} ms or {} ticks behind", behind.as_millis(), behind.as_millis() / TICK_DURATION.as_millis());
+                next = now;
+            }
+            // Behind by less, the next ticks run back to back to catch up.
+            None => {}
+        }
+    }
+}
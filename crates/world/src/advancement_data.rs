@@ -0,0 +1,76 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use anyhow::{anyhow, Context};
+use once_cell::sync::Lazy;
+use serde_json::{Map, Value};
+use dolls_core::datatype::{Identifier, Uuid};
+use dolls_core::date::{format_date, parse_date};
+use crate::level::DATA_VERSION;
+
+/// Criteria a player met, by advancement, with the time they were met in milliseconds since the epoch.
+pub type AdvancementProgress = BTreeMap<Identifier, BTreeMap<String, i64>>;
+
+/// `advancements/<uuid>.json` files of a world, in vanilla's format.
+#[derive(Debug, Clone)]
+pub struct AdvancementStorage {
+    directory: PathBuf,
+}
+
+impl AdvancementStorage {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self { directory: directory.into() }
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    pub fn path(&self, uuid: Uuid) -> PathBuf {
+        self.directory.join(format!("{}.json", uuid.hyphenated()))
+    }
+
+    /// The player's progress, empty for players who never joined.
+    pub fn load(&self, uuid: Uuid) -> anyhow::Result<AdvancementProgress> {
+        let path = self.path(uuid);
+        if !path.exists() {
+            return Ok(AdvancementProgress::new());
+        }
+        let content = fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let json: Map<String, Value> = serde_json::from_str(&content).with_context(|| format!("Malformed {}", path.display()))?;
+        let mut progress = AdvancementProgress::new();
+        for (id, entry) in json.iter().filter(|(key, _)| key.as_str() != "DataVersion") {
+            let id = id.parse::<Identifier>().map_err(|_| anyhow!("Invalid advancement {} in {}", id, path.display()))?;
+            let criteria = entry.get("criteria").and_then(Value::as_object).into_iter().flatten()
+                .filter_map(|(name, date)| Some((name.clone(), parse_date(date.as_str()?)?)))
+                .collect::<BTreeMap<_, _>>();
+            if !criteria.is_empty() {
+                progress.insert(id, criteria);
+            }
+        }
+        Ok(progress)
+    }
+
+    /// Writes the player's progress, `done` tells which advancements are complete.
+    pub fn save(&self, uuid: Uuid, progress: &AdvancementProgress, done: impl Fn(&Identifier) -> bool) -> anyhow::Result<()> {
+        fs::create_dir_all(&self.directory).with_context(|| format!("Failed to create {}", self.directory.display()))?;
+        let mut json = Map::new();
+        for (id, criteria) in progress.iter().filter(|(_, criteria)| !criteria.is_empty()) {
+            let criteria = criteria.iter().map(|(name, time)| (name.clone(), Value::String(format_date(*time)))).collect::<Map<_, _>>();
+            json.insert(id.to_string(), serde_json::json!({ "criteria": criteria, "done": done(id) }));
+        }
+        json.insert("DataVersion".to_string(), Value::from(DATA_VERSION));
+        let path = self.path(uuid);
+        let temporary = path.with_extension("json_new");
+        fs::write(&temporary, serde_json::to_string_pretty(&json)?).with_context(|| format!("Failed to write {}", temporary.display()))?;
+        fs::rename(&temporary, &path).with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+static ADVANCEMENT_DATA: Lazy<RwLock<AdvancementStorage>> = Lazy::new(|| RwLock::new(AdvancementStorage::new("world/advancements")));
+
+/// Advancement progress of the running server's world, pointed at its directory at startup.
+pub fn advancement_data() -> &'static RwLock<AdvancementStorage> {
+    &ADVANCEMENT_DATA
+}
@@ -0,0 +1,476 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use anyhow::{anyhow, bail, Context};
+use dolls_core::datatype::Identifier;
+use dolls_core::nbt::{NbtCompound, NbtTag};
+use dolls_core::registry::registries;
+use crate::prelude::{bits_for, blocks, default_biome, pack_indices, unpack_indices, BlockRegistry, BlockState, Chunk, ChunkCompression, ChunkPos, ChunkSection,
+    HeightmapKind, PalettedContainer, DATA_VERSION, SECTION_BIOMES, SECTION_SIZE, SECTION_VOLUME};
+
+/// Region files are allocated in sectors of this many bytes.
+pub const SECTOR_SIZE: usize = 4096;
+/// Chunks along each axis of a region.
+pub const REGION_SIZE: i32 = 32;
+const REGION_CHUNKS: usize = (REGION_SIZE * REGION_SIZE) as usize;
+/// Set on the compression id when the chunk is stored in its own `.mcc` file.
+const EXTERNAL_FLAG: u8 = 0x80;
+/// Chunks needing more sectors than a location can count are stored externally.
+const MAX_CHUNK_SECTORS: usize = 255;
+
+/// Position of a region, in regions of 32x32 chunks.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct RegionPos {
+    pub x: i32,
+    pub z: i32,
+}
+
+impl RegionPos {
+    /// The region containing a chunk.
+    pub const fn of(chunk: ChunkPos) -> Self {
+        Self { x: chunk.x >> 5, z: chunk.z >> 5 }
+    }
+
+    pub fn file_name(self) -> String {
+        format!("r.{}.{}.mca", self.x, self.z)
+    }
+}
+
+/// First fit of `count` free sectors, growing the file when no gap is large enough.
+fn allocate_sectors(used_sectors: &mut Vec<bool>, count: usize) -> usize {
+    let mut start = 2;
+    while start < used_sectors.len() {
+        match used_sectors[start..].iter().take(count).position(|used| *used) {
+            Some(offset) => start += offset + 1,
+            None => break,
+        }
+    }
+    if used_sectors.len() < start + count {
+        used_sectors.resize(start + count, false);
+    }
+    used_sectors[start..start + count].fill(true);
+    start
+}
+
+fn local_index(chunk: ChunkPos) -> usize {
+    ((chunk.x & (REGION_SIZE - 1)) + (chunk.z & (REGION_SIZE - 1)) * REGION_SIZE) as usize
+}
+
+/// An open `.mca` file: a table of chunk locations and timestamps followed by compressed chunk NBT.
+#[derive(Debug)]
+pub struct RegionFile {
+    path: PathBuf,
+    file: File,
+    /// Per chunk, the first sector in the upper 24 bits and the sector count in the lower 8, 0 when absent.
+    locations: Box<[u32; REGION_CHUNKS]>,
+    timestamps: Box<[u32; REGION_CHUNKS]>,
+    /// Sectors in use, the header included.
+    used_sectors: Vec<bool>,
+}
+
+impl RegionFile {
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        Self::open_with(path.as_ref(), false)
+    }
+
+    /// Opens the region file, creating an empty one if it does not exist.
+    pub fn create(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        Self::open_with(path.as_ref(), true)
+    }
+
+    fn open_with(path: &Path, create: bool) -> anyhow::Result<Self> {
+        let path = path.to_path_buf();
+        let mut file = OpenOptions::new().read(true).write(true).create(create).truncate(false).open(&path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        let mut header = Vec::with_capacity(SECTOR_SIZE * 2);
+        (&mut file).take(SECTOR_SIZE as u64 * 2).read_to_end(&mut header).with_context(|| format!("Failed to read {}", path.display()))?;
+        // Like vanilla, a truncated header means the missing chunks were never saved.
+        header.resize(SECTOR_SIZE * 2, 0);
+        let mut locations = Box::new([0u32; REGION_CHUNKS]);
+        let mut timestamps = Box::new([0u32; REGION_CHUNKS]);
+        for index in 0..REGION_CHUNKS {
+            locations[index] = u32::from_be_bytes(header[index * 4..index * 4 + 4].try_into()?);
+            timestamps[index] = u32::from_be_bytes(header[SECTOR_SIZE + index * 4..SECTOR_SIZE + index * 4 + 4].try_into()?);
+        }
+        let mut used_sectors = vec![true; 2];
+        for location in locations.iter().filter(|location| **location != 0) {
+            let (start, count) = ((location >> 8) as usize, (location & 0xFF) as usize);
+            if used_sectors.len() < start + count {
+                used_sectors.resize(start + count, false);
+            }
+            used_sectors[start..start + count].fill(true);
+        }
+        Ok(Self { path, file, locations, timestamps, used_sectors })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn has_chunk(&self, chunk: ChunkPos) -> bool {
+        self.locations[local_index(chunk)] != 0
+    }
+
+    /// Seconds since the epoch at which the chunk was last saved.
+    pub fn timestamp(&self, chunk: ChunkPos) -> u32 {
+        self.timestamps[local_index(chunk)]
+    }
+
+    fn external_path(&self, chunk: ChunkPos) -> PathBuf {
+        self.path.with_file_name(format!("c.{}.{}.mcc", chunk.x, chunk.z))
+    }
+
+    /// Writes the NBT of a chunk of this region. The old sectors are only released once the new
+    /// location is in the header, so a crash leaves either version readable.
+    pub fn write_chunk(&mut self, chunk: ChunkPos, nbt: &NbtCompound, compression: ChunkCompression) -> anyhow::Result<()> {
+        let context = || format!("Failed to write chunk {} {} to {}", chunk.x, chunk.z, self.path.display());
+        let mut raw = Vec::new();
+        nbt.write_named(&mut raw, "").with_context(context)?;
+        let payload = compression.compress(&raw).with_context(context)?;
+
+        let external = self.external_path(chunk);
+        let mut data = Vec::with_capacity(payload.len() + 5);
+        let is_external = payload.len() + 5 > MAX_CHUNK_SECTORS * SECTOR_SIZE;
+        match is_external {
+            true => {
+                fs::write(&external, &payload).with_context(|| format!("Failed to write {}", external.display()))?;
+                data.extend_from_slice(&1u32.to_be_bytes());
+                data.push(compression.id() | EXTERNAL_FLAG);
+            }
+            false => {
+                data.extend_from_slice(&(payload.len() as u32 + 1).to_be_bytes());
+                data.push(compression.id());
+                data.extend_from_slice(&payload);
+            }
+        }
+
+        let index = local_index(chunk);
+        let previous = self.locations[index];
+        let count = data.len().div_ceil(SECTOR_SIZE);
+        let start = allocate_sectors(&mut self.used_sectors, count);
+        data.resize(count * SECTOR_SIZE, 0);
+        self.file.seek(SeekFrom::Start((start * SECTOR_SIZE) as u64)).with_context(context)?;
+        self.file.write_all(&data).with_context(context)?;
+
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs() as u32);
+        self.locations[index] = ((start as u32) << 8) | count as u32;
+        self.timestamps[index] = timestamp;
+        self.file.seek(SeekFrom::Start((index * 4) as u64)).with_context(context)?;
+        self.file.write_all(&self.locations[index].to_be_bytes()).with_context(context)?;
+        self.file.seek(SeekFrom::Start((SECTOR_SIZE + index * 4) as u64)).with_context(context)?;
+        self.file.write_all(&timestamp.to_be_bytes()).with_context(context)?;
+
+        if previous != 0 {
+            let (start, count) = ((previous >> 8) as usize, (previous & 0xFF) as usize);
+            if let Some(sectors) = self.used_sectors.get_mut(start..start + count) {
+                sectors.fill(false);
+            }
+        }
+        if !is_external && external.exists() {
+            fs::remove_file(&external).with_context(|| format!("Failed to remove {}", external.display()))?;
+        }
+        Ok(())
+    }
+
+    /// Flushes written chunks to the disk.
+    pub fn sync(&self) -> anyhow::Result<()> {
+        self.file.sync_data().with_context(|| format!("Failed to sync {}", self.path.display()))
+    }
+
+    /// Reads the NBT of a chunk of this region, `None` if it was never saved.
+    pub fn read_chunk(&mut self, chunk: ChunkPos) -> anyhow::Result<Option<NbtCompound>> {
+        let location = self.locations[local_index(chunk)];
+        if location == 0 {
+            return Ok(None);
+        }
+        let (sector, sectors) = ((location >> 8) as u64, (location & 0xFF) as usize);
+        let context = || format!("Failed to read chunk {} {} from {}", chunk.x, chunk.z, self.path.display());
+
+        self.file.seek(SeekFrom::Start(sector * SECTOR_SIZE as u64)).with_context(context)?;
+        let mut header = [0u8; 5];
+        self.file.read_exact(&mut header).with_context(context)?;
+        let length = u32::from_be_bytes(header[..4].try_into()?) as usize;
+        if length == 0 || length + 4 > sectors * SECTOR_SIZE {
+            bail!("{}: length {} does not fit its {} sectors", context(), length, sectors);
+        }
+        let compression_id = header[4];
+
+        let data = match compression_id & EXTERNAL_FLAG != 0 {
+            true => {
+                let external = self.external_path(chunk);
+                fs::read(&external).with_context(|| format!("Failed to read {}", external.display()))?
+            }
+            false => {
+                let mut data = vec![0u8; length - 1];
+                self.file.read_exact(&mut data).with_context(context)?;
+                data
+            }
+        };
+        let (compression, data) = ChunkCompression::read_header(compression_id & !EXTERNAL_FLAG, &data).with_context(context)?;
+        let data = compression.decompress(data).with_context(context)?;
+        let (_, nbt) = NbtCompound::read_named(&mut data.as_slice()).with_context(context)?;
+        Ok(Some(nbt))
+    }
+}
+
+/// The region files of one dimension, opened as chunks are requested.
+#[derive(Debug)]
+pub struct RegionStorage {
+    directory: PathBuf,
+    /// Used when writing, chunks are read in whatever compression they were saved with.
+    compression: ChunkCompression,
+    regions: HashMap<RegionPos, Option<RegionFile>>,
+}
+
+impl RegionStorage {
+    /// `directory` is the `region` folder of a dimension, e.g. `world/region` for the overworld.
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self { directory: directory.into(), compression: ChunkCompression::Zlib, regions: HashMap::new() }
+    }
+
+    pub fn with_compression(mut self, compression: ChunkCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    pub fn compression(&self) -> ChunkCompression {
+        self.compression
+    }
+
+    fn region(&mut self, position: RegionPos) -> anyhow::Result<Option<&mut RegionFile>> {
+        if !self.regions.contains_key(&position) {
+            let path = self.directory.join(position.file_name());
+            let region = match path.exists() {
+                true => Some(RegionFile::open(path)?),
+                false => None,
+            };
+            self.regions.insert(position, region);
+        }
+        Ok(self.regions.get_mut(&position).and_then(Option::as_mut))
+    }
+
+    fn region_for_write(&mut self, position: RegionPos) -> anyhow::Result<&mut RegionFile> {
+        if !matches!(self.regions.get(&position), Some(Some(_))) {
+            fs::create_dir_all(&self.directory).with_context(|| format!("Failed to create {}", self.directory.display()))?;
+            let region = RegionFile::create(self.directory.join(position.file_name()))?;
+            self.regions.insert(position, Some(region));
+        }
+        Ok(self.regions.get_mut(&position).and_then(Option::as_mut).expect("Region was just opened"))
+    }
+
+    /// Whether the chunk was ever saved.
+    pub fn has_chunk(&mut self, chunk: ChunkPos) -> anyhow::Result<bool> {
+        Ok(self.region(RegionPos::of(chunk))?.is_some_and(|region| region.has_chunk(chunk)))
+    }
+
+    pub fn read_chunk_nbt(&mut self, chunk: ChunkPos) -> anyhow::Result<Option<NbtCompound>> {
+        match self.region(RegionPos::of(chunk))? {
+            Some(region) => region.read_chunk(chunk),
+            None => Ok(None),
+        }
+    }
+
+    pub fn write_chunk_nbt(&mut self, chunk: ChunkPos, nbt: &NbtCompound) -> anyhow::Result<()> {
+        let compression = self.compression;
+        self.region_for_write(RegionPos::of(chunk))?.write_chunk(chunk, nbt, compression)
+    }
+
+    /// Saves a chunk, it is left dirty: the caller decides when it counts as saved.
+    pub fn save_chunk(&mut self, chunk: &Chunk) -> anyhow::Result<()> {
+        self.write_chunk_nbt(chunk.position, &chunk_to_nbt(chunk))
+    }
+
+    /// Flushes every open region file to the disk.
+    pub fn sync(&self) -> anyhow::Result<()> {
+        self.regions.values().flatten().try_for_each(RegionFile::sync)
+    }
+
+    /// Loads a saved chunk, `None` if it was never generated.
+    pub fn load_chunk(&mut self, chunk: ChunkPos, min_y: i32, height: u32) -> anyhow::Result<Option<Chunk>> {
+        let Some(nbt) = self.read_chunk_nbt(chunk)? else { return Ok(None) };
+        chunk_from_nbt(&nbt, chunk, min_y, height)
+            .with_context(|| format!("Invalid chunk {} {} in {}", chunk.x, chunk.z, self.directory.display()))
+            .map(Some)
+    }
+}
+
+/// Reads a palette entry like `{Name: "minecraft:oak_log", Properties: {axis: "y"}}`.
+/// Blocks and properties the registry does not know fall back to the default state or air.
+fn read_block_state(registry: &BlockRegistry, entry: &NbtCompound) -> anyhow::Result<BlockState> {
+    let name = entry.get_str("Name").ok_or_else(|| anyhow!("Block state without Name"))?;
+    let block: Identifier = name.parse().map_err(|_| anyhow!("Invalid block name {}", name))?;
+    let properties = entry.get_compound("Properties")
+        .map(|properties| properties.iter()
+            .filter_map(|(key, value)| Some((key.as_str(), value.as_str()?)))
+            .collect::<Vec<_>>())
+        .unwrap_or_default();
+    Ok(registry.state_with(&block, &properties)
+        .or_else(|| registry.default_state(&block))
+        .unwrap_or(BlockState::AIR))
+}
+
+/// Reads `{palette: [...], data: [L; ...]}`, `data` is absent when the palette has a single entry.
+fn read_container<T: Copy + Eq>(
+    container: &NbtCompound,
+    size: usize,
+    min_bits: u32,
+    mut read_entry: impl FnMut(&NbtTag) -> anyhow::Result<T>,
+) -> anyhow::Result<PalettedContainer<T>> {
+    let palette = container.get_list("palette").ok_or_else(|| anyhow!("Container without palette"))?
+        .iter()
+        .map(&mut read_entry)
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    match (palette.len(), container.get_long_array("data")) {
+        (0, _) => bail!("Container with an empty palette"),
+        (1, _) | (_, None) => Ok(PalettedContainer::filled(size, palette[0])),
+        (length, Some(data)) => {
+            let bits = bits_for(length).max(min_bits);
+            PalettedContainer::from_indices(palette, unpack_indices(data, bits, size)?)
+        }
+    }
+}
+
+/// Converts the NBT of a saved chunk into a [`Chunk`] of a world spanning `min_y` with `height` blocks.
+pub fn chunk_from_nbt(nbt: &NbtCompound, position: ChunkPos, min_y: i32, height: u32) -> anyhow::Result<Chunk> {
+    let saved = ChunkPos::new(
+        nbt.get_i64("xPos").unwrap_or(position.x as i64) as i32,
+        nbt.get_i64("zPos").unwrap_or(position.z as i64) as i32,
+    );
+    if saved != position {
+        bail!("Chunk is stored for {} {}", saved.x, saved.z);
+    }
+
+    let (plains, biome_names) = biome_names();
+    let biome_ids = biome_names.into_iter()
+        .enumerate()
+        .map(|(network_id, biome)| (biome, network_id as u16))
+        .collect::<HashMap<_, _>>();
+
+    let min_section = min_y.div_euclid(SECTION_SIZE as i32);
+    let mut sections = (0..height as usize / SECTION_SIZE).map(|_| ChunkSection::new(plains)).collect::<Vec<_>>();
+    let registry = blocks().read().unwrap();
+    for tag in nbt.get_list("sections").unwrap_or_default() {
+        let section = tag.as_compound().ok_or_else(|| anyhow!("Section is not a compound"))?;
+        let y = section.get_i64("Y").ok_or_else(|| anyhow!("Section without Y"))? as i32;
+        // Vanilla keeps light-only sections above and below the world.
+        let Some(target) = usize::try_from(y - min_section).ok().and_then(|index| sections.get_mut(index)) else { continue };
+
+        let blocks = match section.get_compound("block_states") {
+            Some(states) => read_container(states, SECTION_VOLUME, 4, |entry| {
+                read_block_state(&registry, entry.as_compound().ok_or_else(|| anyhow!("Block state is not a compound"))?)
+            }).with_context(|| format!("Invalid blocks in section {}", y))?,
+            None => PalettedContainer::filled(SECTION_VOLUME, BlockState::AIR),
+        };
+        let biomes = match section.get_compound("biomes") {
+            Some(biomes) => read_container(biomes, SECTION_BIOMES, 1, |entry| {
+                let name = entry.as_str().ok_or_else(|| anyhow!("Biome is not a string"))?;
+                let biome = name.parse::<Identifier>().ok().and_then(|biome| biome_ids.get(&biome).copied());
+                Ok(biome.unwrap_or(plains))
+            }).with_context(|| format!("Invalid biomes in section {}", y))?,
+            None => PalettedContainer::filled(SECTION_BIOMES, plains),
+        };
+        *target = ChunkSection::from_parts_with(&registry, blocks, biomes);
+    }
+    drop(registry);
+
+    let mut chunk = Chunk::from_sections(position, min_y, sections);
+    chunk.extra_data = nbt.clone();
+    for key in GENERATED_KEYS {
+        chunk.extra_data.remove(key);
+    }
+    Ok(chunk)
+}
+
+/// Chunk fields written from the [`Chunk`] itself, everything else is kept as it was read.
+const GENERATED_KEYS: &[&str] = &["DataVersion", "xPos", "zPos", "yPos", "Status", "isLightOn", "sections", "Heightmaps"];
+
+/// Network id of plains and the biome names in network id order.
+fn biome_names() -> (u16, Vec<Identifier>) {
+    let names = registries().read().unwrap().opaque.iter()
+        .find(|registry| *registry.id() == Identifier::minecraft("worldgen/biome"))
+        .map(|biomes| biomes.entries().iter().map(|entry| entry.id.clone()).collect())
+        .unwrap_or_default();
+    (default_biome(), names)
+}
+
+fn write_block_state(registry: &BlockRegistry, state: BlockState) -> NbtTag {
+    let Some(info) = registry.state(state) else {
+        return NbtTag::Compound(NbtCompound::new().with("Name", "minecraft:air"));
+    };
+    let mut entry = NbtCompound::new().with("Name", info.block.to_string());
+    if !info.properties.is_empty() {
+        let properties = info.properties.iter()
+            .fold(NbtCompound::new(), |properties, (name, value)| properties.with(name.as_str(), value.as_str()));
+        entry.insert("Properties", properties);
+    }
+    NbtTag::Compound(entry)
+}
+
+fn write_container<T: Copy + Eq>(container: &PalettedContainer<T>, min_bits: u32, write_entry: impl Fn(T) -> NbtTag) -> NbtCompound {
+    let palette = container.palette();
+    let mut nbt = NbtCompound::new().with("palette", palette.iter().map(|entry| write_entry(*entry)).collect::<Vec<_>>());
+    if let (Some(indices), true) = (container.indices(), palette.len() > 1) {
+        let bits = bits_for(palette.len()).max(min_bits);
+        nbt.insert("data", pack_indices(indices, bits));
+    }
+    nbt
+}
+
+/// Converts a [`Chunk`] into the NBT vanilla saves, light is left for the reader to recompute.
+pub fn chunk_to_nbt(chunk: &Chunk) -> NbtCompound {
+    let (_, biome_names) = biome_names();
+    let min_section = chunk.min_y().div_euclid(SECTION_SIZE as i32);
+    let registry = blocks().read().unwrap();
+    let sections = chunk.sections().iter().enumerate()
+        .map(|(index, section)| NbtTag::Compound(NbtCompound::new()
+            .with("Y", (min_section + index as i32) as i8)
+            .with("block_states", write_container(section.blocks(), 4, |state| write_block_state(&registry, state)))
+            .with("biomes", write_container(section.biomes(), 1, |biome| {
+                let name = biome_names.get(biome as usize).map_or_else(|| "minecraft:plains".to_string(), ToString::to_string);
+                NbtTag::String(name)
+            }))))
+        .collect::<Vec<_>>();
+    drop(registry);
+
+    // Heights range from 0 to the world height, inclusive.
+    let bits = u32::BITS - chunk.height().leading_zeros();
+    let heightmaps = HeightmapKind::ALL.into_iter()
+        .fold(NbtCompound::new(), |heightmaps, kind| {
+            heightmaps.with(kind.name(), pack_indices(chunk.heightmap(kind).heights(), bits))
+        });
+
+    let mut nbt = chunk.extra_data.clone();
+    nbt.insert("DataVersion", DATA_VERSION)
+        .insert("xPos", chunk.position.x)
+        .insert("zPos", chunk.position.z)
+        .insert("yPos", min_section)
+        .insert("Status", "minecraft:full")
+        .insert("isLightOn", false)
+        .insert("sections", sections)
+        .insert("Heightmaps", heightmaps);
+    nbt
+}
+
+/// The entities of a chunk as vanilla saves them in the region files of a dimension's `entities` folder.
+pub fn entity_chunk_to_nbt(chunk: ChunkPos, entities: Vec<NbtCompound>) -> NbtCompound {
+    NbtCompound::new()
+        .with("DataVersion", DATA_VERSION)
+        .with("Position", vec![chunk.x, chunk.z])
+        .with("Entities", entities.into_iter().map(NbtTag::Compound).collect::<Vec<_>>())
+}
+
+/// The entities of an entity chunk, each as the entity saved itself.
+pub fn entity_chunk_from_nbt(nbt: &NbtCompound, chunk: ChunkPos) -> anyhow::Result<Vec<NbtCompound>> {
+    if let Some(NbtTag::IntArray(position)) = nbt.get("Position") {
+        if position[..] != [chunk.x, chunk.z] {
+            bail!("Entities are stored for chunk {:?}", position);
+        }
+    }
+    Ok(nbt.get_list("Entities").unwrap_or_default().iter().filter_map(NbtTag::as_compound).cloned().collect())
+}
@@ -0,0 +1,298 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::sync::RwLock;
+use anyhow::{anyhow, bail, Context};
+use once_cell::sync::Lazy;
+use serde_json::Value;
+use dolls_core::datatype::Identifier;
+
+/// A block with all its properties, by its id in the global palette.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub struct BlockState(pub u16);
+
+impl BlockState {
+    pub const AIR: BlockState = BlockState(0);
+
+    pub const fn id(self) -> u16 {
+        self.0
+    }
+
+    /// States missing from the registry count as blocks, only ids it knows to be air are air.
+    pub fn is_air(self) -> bool {
+        blocks().read().unwrap().state(self).is_some_and(|info| info.is_air)
+    }
+}
+
+/// What is known about a single block state.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockStateInfo {
+    pub block: Identifier,
+    /// Property values in the block's property order.
+    pub properties: Vec<(String, String)>,
+    pub is_air: bool,
+    /// Stops entities and counts for the `MOTION_BLOCKING` heightmap, fluids included.
+    pub blocks_motion: bool,
+    /// Block light the state gives off, 0 to 15.
+    pub light_emission: u8,
+    /// How much light is lost passing through the state, 0 for clear blocks and 15 for opaque ones.
+    pub light_opacity: u8,
+}
+
+impl Display for BlockStateInfo {
+    /// Formats like the vanilla commands accept it, e.g. `minecraft:oak_log[axis=y]`.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.block)?;
+        if !self.properties.is_empty() {
+            let properties = self.properties.iter().map(|(name, value)| format!("{}={}", name, value)).collect::<Vec<_>>();
+            write!(f, "[{}]", properties.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct BlockInfo {
+    default_state: BlockState,
+    states: Vec<BlockState>,
+}
+
+/// Maps block names and properties to global palette ids.
+///
+/// Only air is known out of the box. The ids of a Minecraft version come from the `blocks.json` report
+/// of its data generator, see [`BlockRegistry::from_report`].
+#[derive(Debug, Clone)]
+pub struct BlockRegistry {
+    states: Vec<Option<BlockStateInfo>>,
+    blocks: HashMap<Identifier, BlockInfo>,
+    /// Block placed by each block item, by `minecraft:item` id.
+    items: HashMap<i32, BlockState>,
+}
+
+impl Default for BlockRegistry {
+    fn default() -> Self {
+        let mut registry = Self { states: Vec::new(), blocks: HashMap::new(), items: HashMap::new() };
+        registry.insert(Identifier::minecraft("air"), vec![(BlockState::AIR, Vec::new())], BlockState::AIR);
+        registry
+    }
+}
+
+/// Blocks entities walk through even though they are not air, matched against the whole name or its last words.
+const PASSABLE_BLOCKS: &[&str] = &[
+    "sapling", "mangrove_propagule", "torch", "sign", "button", "pressure_plate", "rail", "redstone_wire", "tripwire",
+    "tripwire_hook", "lever", "vine", "vines", "vines_plant", "kelp", "kelp_plant", "seagrass", "fire", "tulip",
+    "orchid", "allium", "azure_bluet", "oxeye_daisy", "cornflower", "dandelion", "poppy", "wither_rose",
+    "lily_of_the_valley", "torchflower", "sunflower", "lilac", "rose_bush", "peony", "pitcher_plant", "fern",
+    "short_grass", "tall_grass", "dead_bush", "sugar_cane", "banner", "mushroom", "fungus", "crimson_roots",
+    "warped_roots", "nether_sprouts", "hanging_roots", "coral", "coral_fan", "coral_wall_fan", "nether_portal",
+    "end_portal", "end_gateway", "structure_void", "light", "cobweb", "pink_petals", "glow_lichen", "spore_blossom",
+    "frogspawn", "pitcher_crop", "torchflower_crop", "wheat", "carrots", "potatoes", "beetroots", "nether_wart",
+    "pumpkin_stem", "melon_stem", "sweet_berry_bush", "cocoa", "small_dripleaf", "big_dripleaf_stem",
+];
+
+/// Approximates collision from the name, the data generator reports do not include it.
+fn blocks_motion(block: &Identifier, properties: &[(String, String)]) -> bool {
+    let name = block.path();
+    if matches!(name, "water" | "lava" | "bubble_column") || properties.iter().any(|(key, value)| key == "waterlogged" && value == "true") {
+        return true;
+    }
+    if name == "snow" {
+        // A single snow layer has no collision.
+        return properties.iter().any(|(key, value)| key == "layers" && value != "1");
+    }
+    name.starts_with("potted_") || !PASSABLE_BLOCKS.iter().any(|passable| name == *passable || name.ends_with(&format!("_{}", passable)))
+}
+
+/// Light given off by blocks, matched against the whole name. Ignores properties apart from `lit`.
+const LIGHT_SOURCES: &[(&str, u8)] = &[
+    ("glowstone", 15), ("sea_lantern", 15), ("shroomlight", 15), ("jack_o_lantern", 15), ("lantern", 15),
+    ("beacon", 15), ("conduit", 15), ("end_gateway", 15), ("end_portal", 15), ("fire", 15), ("lava", 15),
+    ("campfire", 15), ("froglight", 15), ("ochre_froglight", 15), ("verdant_froglight", 15), ("pearlescent_froglight", 15),
+    ("redstone_lamp", 15), ("torch", 14), ("wall_torch", 14), ("end_rod", 14), ("furnace", 13), ("blast_furnace", 13),
+    ("smoker", 13), ("nether_portal", 11), ("soul_torch", 10), ("soul_wall_torch", 10), ("soul_lantern", 10),
+    ("soul_fire", 10), ("soul_campfire", 10), ("crying_obsidian", 10), ("respawn_anchor", 15), ("glow_lichen", 7),
+    ("enchanting_table", 7), ("ender_chest", 7), ("redstone_torch", 7), ("redstone_wall_torch", 7),
+    ("amethyst_cluster", 5), ("large_amethyst_bud", 4), ("magma_block", 3), ("medium_amethyst_bud", 2),
+    ("brewing_stand", 1), ("brown_mushroom", 1), ("dragon_egg", 1), ("end_portal_frame", 1), ("small_amethyst_bud", 1),
+    ("sculk_sensor", 1), ("calibrated_sculk_sensor", 1),
+];
+
+/// Blocks which only need `lit=true` to give off light, unlit ones are dark.
+const LIT_BLOCKS: &[&str] = &[
+    "redstone_lamp", "furnace", "blast_furnace", "smoker", "campfire", "soul_campfire", "redstone_torch", "redstone_wall_torch",
+];
+
+/// Blocks which let light through, matched against the whole name or its last words.
+const CLEAR_BLOCKS: &[&str] = &[
+    "glass", "glass_pane", "slab", "stairs", "fence", "fence_gate", "wall", "bars", "door", "trapdoor", "chain",
+    "lantern", "campfire", "chest", "ender_chest", "barrier", "beacon", "conduit", "end_rod", "bed", "carpet",
+    "enchanting_table", "brewing_stand", "cauldron", "hopper", "anvil", "lectern", "bell", "scaffolding", "candle",
+    "cake", "piston_head", "daylight_detector", "head", "skull", "pot", "amethyst_cluster", "bud", "honey_block",
+    "slime_block", "snow", "farmland", "dirt_path",
+];
+
+/// Blocks which dim light a little, like water.
+const TRANSLUCENT_BLOCKS: &[&str] = &["water", "lava", "bubble_column", "ice", "frosted_ice", "leaves", "cobweb"];
+
+/// Approximates the emission of a state from its name, the reports do not include it.
+fn light_emission(block: &Identifier, properties: &[(String, String)]) -> u8 {
+    let name = block.path();
+    let emission = LIGHT_SOURCES.iter().find(|(source, _)| name == *source).map_or(0, |(_, emission)| *emission);
+    let unlit = properties.iter().any(|(key, value)| key == "lit" && value == "false");
+    match LIT_BLOCKS.contains(&name) && unlit {
+        true => 0,
+        false => emission,
+    }
+}
+
+/// Approximates the opacity of a state from its name, like [`blocks_motion`].
+fn light_opacity(block: &Identifier, blocks_motion: bool) -> u8 {
+    let name = block.path();
+    let matches = |names: &[&str]| names.iter().any(|other| name == *other || name.ends_with(&format!("_{}", other)));
+    if matches(TRANSLUCENT_BLOCKS) {
+        1
+    } else if !blocks_motion || matches(CLEAR_BLOCKS) {
+        0
+    } else {
+        15
+    }
+}
+
+fn is_air(block: &Identifier) -> bool {
+    matches!(block.path(), "air" | "cave_air" | "void_air")
+}
+
+impl BlockRegistry {
+    fn insert(&mut self, block: Identifier, states: Vec<(BlockState, Vec<(String, String)>)>, default_state: BlockState) {
+        let ids = states.iter().map(|(state, _)| *state).collect();
+        for (state, properties) in states {
+            let index = state.0 as usize;
+            if self.states.len() <= index {
+                self.states.resize(index + 1, None);
+            }
+            let blocks_motion = !is_air(&block) && blocks_motion(&block, &properties);
+            self.states[index] = Some(BlockStateInfo {
+                block: block.clone(),
+                is_air: is_air(&block),
+                blocks_motion,
+                light_emission: light_emission(&block, &properties),
+                light_opacity: light_opacity(&block, blocks_motion),
+                properties,
+            });
+        }
+        self.blocks.insert(block, BlockInfo { default_state, states: ids });
+    }
+
+    /// Reads the `blocks.json` report written by `java -DbundlerMainClass=net.minecraft.data.Main -jar server.jar --reports`.
+    pub fn from_report(json: &str) -> anyhow::Result<Self> {
+        let report: Value = serde_json::from_str(json).context("Malformed block report")?;
+        let report = report.as_object().ok_or_else(|| anyhow!("Block report is not an object"))?;
+        let mut registry = Self { states: Vec::new(), blocks: HashMap::new(), items: HashMap::new() };
+
+        for (name, block) in report {
+            let id: Identifier = name.parse().map_err(|_| anyhow!("Invalid block name {}", name))?;
+            let property_order = block.get("properties").and_then(Value::as_object)
+                .map(|properties| properties.keys().cloned().collect::<Vec<_>>())
+                .unwrap_or_default();
+            let entries = block.get("states").and_then(Value::as_array).ok_or_else(|| anyhow!("Block {} has no states", name))?;
+
+            let mut states = Vec::with_capacity(entries.len());
+            let mut default_state = None;
+            for entry in entries {
+                let state_id = entry.get("id").and_then(Value::as_u64)
+                    .and_then(|state_id| u16::try_from(state_id).ok())
+                    .ok_or_else(|| anyhow!("State of {} has no valid id", name))?;
+                let values = entry.get("properties").and_then(Value::as_object);
+                let properties = property_order.iter()
+                    .map(|property| {
+                        let value = values.and_then(|values| values.get(property)).and_then(Value::as_str)
+                            .ok_or_else(|| anyhow!("State {} of {} lacks property {}", state_id, name, property))?;
+                        Ok((property.clone(), value.to_string()))
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                if entry.get("default").and_then(Value::as_bool).unwrap_or(false) {
+                    default_state = Some(BlockState(state_id));
+                }
+                states.push((BlockState(state_id), properties));
+            }
+            let default_state = default_state.or(states.first().map(|(state, _)| *state))
+                .ok_or_else(|| anyhow!("Block {} has no states", name))?;
+            registry.insert(id, states, default_state);
+        }
+
+        if registry.state(BlockState::AIR).is_none_or(|info| !info.is_air) {
+            bail!("Block report does not map state 0 to air");
+        }
+        Ok(registry)
+    }
+
+    /// Number of global palette ids, i.e. the highest state id plus one.
+    pub fn state_count(&self) -> usize {
+        self.states.len()
+    }
+
+    pub fn state(&self, state: BlockState) -> Option<&BlockStateInfo> {
+        self.states.get(state.0 as usize)?.as_ref()
+    }
+
+    pub fn default_state(&self, block: &Identifier) -> Option<BlockState> {
+        self.blocks.get(block).map(|info| info.default_state)
+    }
+
+    /// The state of `block` with the given properties, unspecified ones as in the default state.
+    pub fn state_with(&self, block: &Identifier, properties: &[(&str, &str)]) -> Option<BlockState> {
+        let info = self.blocks.get(block)?;
+        let default = &self.state(info.default_state)?.properties;
+        let wanted = default.iter()
+            .map(|(name, value)| {
+                let value = properties.iter().find(|(key, _)| key == name).map(|(_, value)| *value).unwrap_or(value);
+                (name.as_str(), value)
+            })
+            .collect::<Vec<_>>();
+        if properties.iter().any(|(key, _)| !default.iter().any(|(name, _)| name == key)) {
+            return None;
+        }
+        info.states.iter().copied().find(|state| {
+            self.state(*state).is_some_and(|state| {
+                state.properties.iter().zip(&wanted).all(|((_, value), (_, wanted))| value == wanted)
+            })
+        })
+    }
+
+    /// Makes the item with `item_id` place `state`.
+    pub fn register_item(&mut self, item_id: i32, state: BlockState) {
+        self.items.insert(item_id, state);
+    }
+
+    /// What the item with `item_id` places, `None` for items which are not blocks.
+    pub fn item_block(&self, item_id: i32) -> Option<BlockState> {
+        self.items.get(&item_id).copied()
+    }
+
+    /// Parses `name[property=value,...]`, the namespace defaults to `minecraft`.
+    pub fn parse_state(&self, input: &str) -> anyhow::Result<BlockState> {
+        let (name, properties) = match input.split_once('[') {
+            Some((name, rest)) => {
+                let rest = rest.strip_suffix(']').ok_or_else(|| anyhow!("Unclosed properties in {}", input))?;
+                let properties = rest.split(',')
+                    .filter(|property| !property.trim().is_empty())
+                    .map(|property| property.split_once('=').map(|(key, value)| (key.trim(), value.trim()))
+                        .ok_or_else(|| anyhow!("Expected property=value in {}", input)))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                (name, properties)
+            }
+            None => (input, Vec::new()),
+        };
+        let block: Identifier = name.parse().map_err(|_| anyhow!("Invalid block name {}", name))?;
+        if !self.blocks.contains_key(&block) {
+            bail!("Unknown block {}", block);
+        }
+        self.state_with(&block, &properties).ok_or_else(|| anyhow!("Invalid properties for {}", input))
+    }
+}
+
+static BLOCKS: Lazy<RwLock<BlockRegistry>> = Lazy::new(|| RwLock::new(BlockRegistry::default()));
+
+/// Block states of the server, replace it with one read from a report before loading worlds.
+pub fn blocks() -> &'static RwLock<BlockRegistry> {
+    &BLOCKS
+}
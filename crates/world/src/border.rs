@@ -0,0 +1,132 @@
+use std::time::{Duration, Instant};
+use dolls_core::nbt::NbtCompound;
+
+/// Largest distance from the origin the border may be moved to, also where portals stop placing players.
+pub const MAX_BORDER_DISTANCE: i32 = 29_999_984;
+/// Side length of the border of new worlds, covering the whole world.
+pub const DEFAULT_BORDER_DIAMETER: f64 = 59_999_968.0;
+
+/// The square players cannot leave, kept in `level.dat` like vanilla.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorldBorder {
+    pub center_x: f64,
+    pub center_z: f64,
+    /// Side length when the current resize started.
+    diameter: f64,
+    target_diameter: f64,
+    /// When the current resize started and how long it takes, `None` while the border stands still.
+    lerp: Option<(Instant, Duration)>,
+    /// Players closer than this to the border see the warning overlay.
+    pub warning_blocks: i32,
+    /// Seconds before a shrinking border reaches players at which they are warned.
+    pub warning_time: i32,
+}
+
+impl Default for WorldBorder {
+    fn default() -> Self {
+        Self {
+            center_x: 0.0,
+            center_z: 0.0,
+            diameter: DEFAULT_BORDER_DIAMETER,
+            target_diameter: DEFAULT_BORDER_DIAMETER,
+            lerp: None,
+            warning_blocks: 5,
+            warning_time: 15,
+        }
+    }
+}
+
+impl WorldBorder {
+    /// Reads the `Border*` keys of a level's `Data`, missing ones keep their defaults.
+    pub fn from_nbt(data: &NbtCompound) -> Self {
+        let default = Self::default();
+        let mut border = Self {
+            center_x: data.get_f64("BorderCenterX").unwrap_or(default.center_x),
+            center_z: data.get_f64("BorderCenterZ").unwrap_or(default.center_z),
+            diameter: data.get_f64("BorderSize").unwrap_or(default.diameter),
+            warning_blocks: data.get_f64("BorderWarningBlocks").map_or(default.warning_blocks, |blocks| blocks as i32),
+            warning_time: data.get_f64("BorderWarningTime").map_or(default.warning_time, |time| time as i32),
+            ..default
+        };
+        border.target_diameter = border.diameter;
+        let remaining = data.get_i64("BorderSizeLerpTime").unwrap_or(0);
+        if let (Some(target), true) = (data.get_f64("BorderSizeLerpTarget"), remaining > 0) {
+            border.lerp_to(target, Duration::from_millis(remaining as u64));
+        }
+        border
+    }
+
+    /// Writes the border into a level's `Data`, a resize in progress continues from where it is.
+    pub fn write_nbt(&self, data: &mut NbtCompound) {
+        data.insert("BorderCenterX", self.center_x)
+            .insert("BorderCenterZ", self.center_z)
+            .insert("BorderSize", self.diameter())
+            .insert("BorderSizeLerpTarget", self.target_diameter)
+            .insert("BorderSizeLerpTime", self.remaining().as_millis() as i64)
+            .insert("BorderWarningBlocks", self.warning_blocks as f64)
+            .insert("BorderWarningTime", self.warning_time as f64)
+            .insert("BorderSafeZone", 5.0)
+            .insert("BorderDamagePerBlock", 0.2);
+    }
+
+    /// Side length right now, between the old and the target one while resizing.
+    pub fn diameter(&self) -> f64 {
+        match self.lerp {
+            Some((started, duration)) if started.elapsed() < duration => {
+                let progress = started.elapsed().as_secs_f64() / duration.as_secs_f64();
+                self.diameter + (self.target_diameter - self.diameter) * progress
+            }
+            _ => self.target_diameter,
+        }
+    }
+
+    /// Side length once the current resize is done.
+    pub fn target_diameter(&self) -> f64 {
+        self.target_diameter
+    }
+
+    /// Time until the current resize is done.
+    pub fn remaining(&self) -> Duration {
+        self.lerp.map_or(Duration::ZERO, |(started, duration)| duration.saturating_sub(started.elapsed()))
+    }
+
+    /// Grows or shrinks the border to `diameter` over `duration`, right away for a zero duration.
+    pub fn lerp_to(&mut self, diameter: f64, duration: Duration) {
+        let diameter = diameter.clamp(1.0, DEFAULT_BORDER_DIAMETER);
+        self.diameter = self.diameter();
+        self.target_diameter = diameter;
+        self.lerp = match duration.is_zero() {
+            true => {
+                self.diameter = diameter;
+                None
+            }
+            false => Some((Instant::now(), duration)),
+        };
+    }
+
+    /// Moves the center, kept within [`MAX_BORDER_DISTANCE`] of the origin.
+    pub fn set_center(&mut self, x: f64, z: f64) {
+        let limit = MAX_BORDER_DISTANCE as f64;
+        self.center_x = x.clamp(-limit, limit);
+        self.center_z = z.clamp(-limit, limit);
+    }
+
+    /// Smallest and largest x and z inside the border right now.
+    pub fn bounds(&self) -> ((f64, f64), (f64, f64)) {
+        let radius = self.diameter() / 2.0;
+        let limit = MAX_BORDER_DISTANCE as f64;
+        let range = |center: f64| ((center - radius).max(-limit), (center + radius).min(limit));
+        (range(self.center_x), range(self.center_z))
+    }
+
+    pub fn contains(&self, x: f64, z: f64) -> bool {
+        let ((min_x, max_x), (min_z, max_z)) = self.bounds();
+        (min_x..=max_x).contains(&x) && (min_z..=max_z).contains(&z)
+    }
+
+    /// The point inside the border closest to `x` and `z`.
+    pub fn clamp(&self, x: f64, z: f64) -> (f64, f64) {
+        let ((min_x, max_x), (min_z, max_z)) = self.bounds();
+        (x.clamp(min_x, max_x), z.clamp(min_z, max_z))
+    }
+}
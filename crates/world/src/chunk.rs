@@ -0,0 +1,488 @@
+use anyhow::bail;
+use dolls_core::datatype::BlockPos;
+use dolls_core::nbt::NbtCompound;
+use crate::prelude::{blocks, BlockRegistry, BlockState, ChunkLight};
+
+/// Blocks along each axis of a section.
+pub const SECTION_SIZE: usize = 16;
+pub const SECTION_VOLUME: usize = SECTION_SIZE * SECTION_SIZE * SECTION_SIZE;
+/// Biomes are stored per 4x4x4 blocks.
+pub const BIOME_SIZE: usize = 4;
+pub const SECTION_BIOMES: usize = BIOME_SIZE * BIOME_SIZE * BIOME_SIZE;
+
+/// Position of a chunk, in chunks.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct ChunkPos {
+    pub x: i32,
+    pub z: i32,
+}
+
+impl ChunkPos {
+    pub const fn new(x: i32, z: i32) -> Self {
+        Self { x, z }
+    }
+
+    /// The chunk containing a block.
+    pub const fn of(position: BlockPos) -> Self {
+        Self {
+            x: position.x >> 4,
+            z: position.z >> 4,
+        }
+    }
+}
+
+/// Values of a fixed number of entries, stored as indices into a palette of the values present.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PalettedContainer<T> {
+    palette: Vec<T>,
+    /// `None` while the whole container holds `palette[0]`.
+    indices: Option<Box<[u16]>>,
+    size: usize,
+}
+
+impl<T: Copy + Eq> PalettedContainer<T> {
+    /// A container of `size` entries which all hold `value`.
+    pub fn filled(size: usize, value: T) -> Self {
+        Self { palette: vec![value], indices: None, size }
+    }
+
+    /// A container from palette indices, one per entry.
+    pub fn from_indices(palette: Vec<T>, indices: Vec<u16>) -> anyhow::Result<Self> {
+        if palette.is_empty() {
+            bail!("Palette is empty");
+        }
+        if let Some(index) = indices.iter().find(|index| **index as usize >= palette.len()) {
+            bail!("Palette index {} is out of bounds for {} entries", index, palette.len());
+        }
+        let size = indices.len();
+        let mut container = Self { palette, indices: Some(indices.into_boxed_slice()), size };
+        container.compact();
+        Ok(container)
+    }
+
+    /// A container holding `values`, with a palette of the distinct ones. `values` must not be empty.
+    pub fn from_values(values: &[T]) -> Self {
+        let mut container = Self::filled(values.len(), values[0]);
+        for (index, value) in values.iter().enumerate() {
+            container.set(index, *value);
+        }
+        container
+    }
+
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Values which may be present, some may not be in use anymore.
+    pub fn palette(&self) -> &[T] {
+        &self.palette
+    }
+
+    /// `Some` if every entry holds the same value.
+    pub fn single_value(&self) -> Option<T> {
+        match self.indices {
+            None => Some(self.palette[0]),
+            Some(_) => None,
+        }
+    }
+
+    pub fn get(&self, index: usize) -> T {
+        match &self.indices {
+            Some(indices) => self.palette[indices[index] as usize],
+            None => self.palette[0],
+        }
+    }
+
+    /// Stores `value` at `index`, returning the value it replaced.
+    pub fn set(&mut self, index: usize, value: T) -> T {
+        let previous = self.get(index);
+        if previous == value {
+            return previous;
+        }
+        let palette_index = match self.palette.iter().position(|entry| *entry == value) {
+            Some(palette_index) => palette_index,
+            None => {
+                self.palette.push(value);
+                self.palette.len() - 1
+            }
+        };
+        let size = self.size;
+        let indices = self.indices.get_or_insert_with(|| vec![0; size].into_boxed_slice());
+        indices[index] = palette_index as u16;
+        previous
+    }
+
+    pub fn fill(&mut self, value: T) {
+        self.palette = vec![value];
+        self.indices = None;
+    }
+
+    /// Palette index of every entry, `None` while the container holds a single value.
+    pub fn indices(&self) -> Option<&[u16]> {
+        self.indices.as_deref()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        (0..self.size).map(|index| self.get(index))
+    }
+
+    /// Drops palette entries no longer in use, collapsing to a single value when possible.
+    pub fn compact(&mut self) {
+        let Some(indices) = &self.indices else { return };
+        let mut used = vec![false; self.palette.len()];
+        for index in indices.iter() {
+            used[*index as usize] = true;
+        }
+        if self.palette.len() > 1 && used.iter().all(|used| *used) {
+            return;
+        }
+        *self = Self::from_values(&self.iter().collect::<Vec<_>>());
+    }
+}
+
+/// A 16x16x16 cube of blocks with their biomes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkSection {
+    /// Blocks which are not air, as sent to clients.
+    block_count: u16,
+    blocks: PalettedContainer<BlockState>,
+    /// Network ids in the `minecraft:worldgen/biome` registry.
+    biomes: PalettedContainer<u16>,
+}
+
+impl ChunkSection {
+    pub fn new(biome: u16) -> Self {
+        Self {
+            block_count: 0,
+            blocks: PalettedContainer::filled(SECTION_VOLUME, BlockState::AIR),
+            biomes: PalettedContainer::filled(SECTION_BIOMES, biome),
+        }
+    }
+
+    pub fn from_parts(blocks: PalettedContainer<BlockState>, biomes: PalettedContainer<u16>) -> Self {
+        Self::from_parts_with(&crate::block::blocks().read().unwrap(), blocks, biomes)
+    }
+
+    pub(crate) fn from_parts_with(registry: &BlockRegistry, blocks: PalettedContainer<BlockState>, biomes: PalettedContainer<u16>) -> Self {
+        let block_count = match blocks.single_value() {
+            Some(state) if is_air(registry, state) => 0,
+            Some(_) => SECTION_VOLUME as u16,
+            None => blocks.iter().filter(|state| !is_air(registry, *state)).count() as u16,
+        };
+        Self { block_count, blocks, biomes }
+    }
+
+    pub const fn index(x: usize, y: usize, z: usize) -> usize {
+        (y * SECTION_SIZE + z) * SECTION_SIZE + x
+    }
+
+    pub fn block_count(&self) -> u16 {
+        self.block_count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.block_count == 0
+    }
+
+    pub fn blocks(&self) -> &PalettedContainer<BlockState> {
+        &self.blocks
+    }
+
+    pub fn biomes(&self) -> &PalettedContainer<u16> {
+        &self.biomes
+    }
+
+    /// Coordinates are relative to the section.
+    pub fn get_block(&self, x: usize, y: usize, z: usize) -> BlockState {
+        self.blocks.get(Self::index(x, y, z))
+    }
+
+    fn set_block_with(&mut self, registry: &BlockRegistry, x: usize, y: usize, z: usize, state: BlockState) -> BlockState {
+        let previous = self.blocks.set(Self::index(x, y, z), state);
+        match (is_air(registry, previous), is_air(registry, state)) {
+            (true, false) => self.block_count += 1,
+            (false, true) => self.block_count -= 1,
+            _ => {}
+        }
+        previous
+    }
+
+    /// Coordinates are relative to the section, returns the replaced state.
+    pub fn set_block(&mut self, x: usize, y: usize, z: usize, state: BlockState) -> BlockState {
+        self.set_block_with(&blocks().read().unwrap(), x, y, z, state)
+    }
+
+    /// Coordinates are in biome cells of 4x4x4 blocks.
+    pub fn get_biome(&self, x: usize, y: usize, z: usize) -> u16 {
+        self.biomes.get((y * BIOME_SIZE + z) * BIOME_SIZE + x)
+    }
+
+    pub fn set_biome(&mut self, x: usize, y: usize, z: usize, biome: u16) {
+        self.biomes.set((y * BIOME_SIZE + z) * BIOME_SIZE + x, biome);
+    }
+}
+
+/// Unknown states count as blocks, see [`BlockState::is_air`].
+fn is_air(registry: &BlockRegistry, state: BlockState) -> bool {
+    registry.state(state).is_some_and(|info| info.is_air)
+}
+
+/// Heightmaps kept up to date by [`Chunk`], the ones clients need.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum HeightmapKind {
+    /// Highest block which is not air.
+    WorldSurface,
+    /// Highest block which blocks motion or holds a fluid.
+    MotionBlocking,
+}
+
+impl HeightmapKind {
+    pub const ALL: [HeightmapKind; 2] = [HeightmapKind::WorldSurface, HeightmapKind::MotionBlocking];
+
+    /// Name in chunk NBT and the Chunk Data packet.
+    pub const fn name(self) -> &'static str {
+        match self {
+            HeightmapKind::WorldSurface => "WORLD_SURFACE",
+            HeightmapKind::MotionBlocking => "MOTION_BLOCKING",
+        }
+    }
+
+    fn matches(self, registry: &BlockRegistry, state: BlockState) -> bool {
+        // States the registry does not know are treated as full blocks.
+        let Some(info) = registry.state(state) else { return true };
+        match self {
+            HeightmapKind::WorldSurface => !info.is_air,
+            HeightmapKind::MotionBlocking => info.blocks_motion,
+        }
+    }
+}
+
+/// Per column, one more than the height of the highest matching block above the bottom of the world, 0 for none.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heightmap {
+    kind: HeightmapKind,
+    heights: Box<[u16; 256]>,
+}
+
+impl Heightmap {
+    fn new(kind: HeightmapKind) -> Self {
+        Self { kind, heights: Box::new([0; 256]) }
+    }
+
+    pub fn kind(&self) -> HeightmapKind {
+        self.kind
+    }
+
+    /// Column values in x-fastest order, as stored in chunks.
+    pub fn heights(&self) -> &[u16; 256] {
+        &self.heights
+    }
+
+    fn get(&self, x: usize, z: usize) -> u16 {
+        self.heights[z * SECTION_SIZE + x]
+    }
+
+    fn set(&mut self, x: usize, z: usize, height: u16) {
+        self.heights[z * SECTION_SIZE + x] = height;
+    }
+}
+
+/// A 16 block wide column of sections spanning the height of the world.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    pub position: ChunkPos,
+    min_y: i32,
+    sections: Vec<ChunkSection>,
+    heightmaps: Vec<Heightmap>,
+    /// Computed by the world's light engine once the chunk is loaded, not saved.
+    light: ChunkLight,
+    /// Changed since it was last saved.
+    dirty: bool,
+    /// Saved fields Dolls does not model, written back as they were read.
+    pub(crate) extra_data: NbtCompound,
+}
+
+impl Chunk {
+    /// An empty chunk, `min_y` and `height` must be multiples of 16.
+    pub fn new(position: ChunkPos, min_y: i32, height: u32, biome: u16) -> Self {
+        let sections = (0..height as usize / SECTION_SIZE).map(|_| ChunkSection::new(biome)).collect::<Vec<_>>();
+        Self {
+            position,
+            min_y,
+            light: ChunkLight::new(sections.len()),
+            sections,
+            heightmaps: HeightmapKind::ALL.into_iter().map(Heightmap::new).collect(),
+            dirty: true,
+            extra_data: NbtCompound::new(),
+        }
+    }
+
+    /// Builds a chunk from sections ordered bottom to top, heightmaps are computed from the blocks.
+    /// It is not dirty, as if it had just been loaded.
+    pub fn from_sections(position: ChunkPos, min_y: i32, sections: Vec<ChunkSection>) -> Self {
+        let mut chunk = Self {
+            position,
+            min_y,
+            light: ChunkLight::new(sections.len()),
+            sections,
+            heightmaps: HeightmapKind::ALL.into_iter().map(Heightmap::new).collect(),
+            dirty: false,
+            extra_data: NbtCompound::new(),
+        };
+        chunk.recalculate_heightmaps();
+        chunk
+    }
+
+    pub fn min_y(&self) -> i32 {
+        self.min_y
+    }
+
+    /// Whether the chunk changed since it was created, loaded or last saved.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub fn set_dirty(&mut self, dirty: bool) {
+        self.dirty = dirty;
+    }
+
+    pub fn height(&self) -> u32 {
+        (self.sections.len() * SECTION_SIZE) as u32
+    }
+
+    /// Sections from the bottom of the world up.
+    pub fn sections(&self) -> &[ChunkSection] {
+        &self.sections
+    }
+
+    /// Marks the chunk dirty, heightmaps must be recalculated after changing blocks this way.
+    pub fn section_mut(&mut self, index: usize) -> Option<&mut ChunkSection> {
+        self.dirty = true;
+        self.sections.get_mut(index)
+    }
+
+    pub fn light(&self) -> &ChunkLight {
+        &self.light
+    }
+
+    pub(crate) fn light_mut(&mut self) -> &mut ChunkLight {
+        &mut self.light
+    }
+
+    pub fn heightmap(&self, kind: HeightmapKind) -> &Heightmap {
+        self.heightmaps.iter().find(|heightmap| heightmap.kind == kind).expect("Every heightmap kind is maintained")
+    }
+
+    /// Y of the first block above the highest matching one in the column, `min_y` if there is none.
+    pub fn height_at(&self, kind: HeightmapKind, x: usize, z: usize) -> i32 {
+        self.min_y + self.heightmap(kind).get(x, z) as i32
+    }
+
+    fn section_of(&self, y: i32) -> Option<(usize, usize)> {
+        let relative = usize::try_from(y - self.min_y).ok()?;
+        let section = relative / SECTION_SIZE;
+        (section < self.sections.len()).then_some((section, relative % SECTION_SIZE))
+    }
+
+    /// `x` and `z` are relative to the chunk, `y` is absolute. Blocks outside the world are air.
+    pub fn get_block(&self, x: usize, y: i32, z: usize) -> BlockState {
+        match self.section_of(y) {
+            Some((section, y)) => self.sections[section].get_block(x, y, z),
+            None => BlockState::AIR,
+        }
+    }
+
+    /// `x` and `z` are relative to the chunk, `y` is absolute. Returns the replaced state.
+    pub fn set_block(&mut self, x: usize, y: i32, z: usize, state: BlockState) -> anyhow::Result<BlockState> {
+        let Some((section, section_y)) = self.section_of(y) else {
+            bail!("Y {} is outside of the world ({} to {})", y, self.min_y, self.min_y + self.height() as i32 - 1);
+        };
+        let registry = blocks().read().unwrap();
+        let previous = self.sections[section].set_block_with(&registry, x, section_y, z, state);
+        if previous != state {
+            self.dirty = true;
+            let height = (y - self.min_y) as u16 + 1;
+            for index in 0..self.heightmaps.len() {
+                let kind = self.heightmaps[index].kind;
+                let top = self.heightmaps[index].get(x, z);
+                if kind.matches(&registry, state) {
+                    if height > top {
+                        self.heightmaps[index].set(x, z, height);
+                    }
+                } else if height == top {
+                    let below = self.scan_column(&registry, kind, x, z, height - 1);
+                    self.heightmaps[index].set(x, z, below);
+                }
+            }
+        }
+        Ok(previous)
+    }
+
+    /// Applies many changes at once, `x` and `z` relative to the chunk and `y` absolute. Heightmaps are
+    /// rescanned once per touched column instead of once per block. Returns how many blocks changed.
+    pub fn set_blocks(&mut self, changes: &[(usize, i32, usize, BlockState)]) -> anyhow::Result<usize> {
+        if let Some((_, y, _, _)) = changes.iter().find(|(_, y, _, _)| self.section_of(*y).is_none()) {
+            bail!("Y {} is outside of the world ({} to {})", y, self.min_y, self.min_y + self.height() as i32 - 1);
+        }
+        let registry = blocks().read().unwrap();
+        // Per column, one more than the highest changed relative height.
+        let mut touched = [0u16; SECTION_SIZE * SECTION_SIZE];
+        let mut changed = 0;
+        for (x, y, z, state) in changes {
+            let (section, section_y) = self.section_of(*y).expect("Heights were checked");
+            if self.sections[section].set_block_with(&registry, *x, section_y, *z, *state) != *state {
+                let column = &mut touched[z * SECTION_SIZE + x];
+                *column = (*column).max((y - self.min_y) as u16 + 1);
+                changed += 1;
+            }
+        }
+        if changed == 0 {
+            return Ok(0);
+        }
+        self.dirty = true;
+
+        for (column, highest) in touched.into_iter().enumerate().filter(|(_, highest)| *highest > 0) {
+            let (x, z) = (column % SECTION_SIZE, column / SECTION_SIZE);
+            for index in 0..self.heightmaps.len() {
+                let top = self.heightmaps[index].get(x, z);
+                // Changes below the top block leave it in place.
+                if highest >= top {
+                    let height = self.scan_column(&registry, self.heightmaps[index].kind, x, z, highest.max(top));
+                    self.heightmaps[index].set(x, z, height);
+                }
+            }
+        }
+        Ok(changed)
+    }
+
+    /// Height of the highest matching block strictly below relative height `below`, plus one.
+    fn scan_column(&self, registry: &BlockRegistry, kind: HeightmapKind, x: usize, z: usize, below: u16) -> u16 {
+        (0..below)
+            .rev()
+            .find(|relative| {
+                let relative = *relative as usize;
+                let section = &self.sections[relative / SECTION_SIZE];
+                !section.is_empty() && kind.matches(registry, section.get_block(x, relative % SECTION_SIZE, z))
+            })
+            .map_or(0, |relative| relative + 1)
+    }
+
+    /// Recomputes every heightmap from the blocks, e.g. after the sections were replaced.
+    pub fn recalculate_heightmaps(&mut self) {
+        let registry = blocks().read().unwrap();
+        let top = self.height() as u16;
+        for index in 0..self.heightmaps.len() {
+            let kind = self.heightmaps[index].kind;
+            for z in 0..SECTION_SIZE {
+                for x in 0..SECTION_SIZE {
+                    let height = self.scan_column(&registry, kind, x, z, top);
+                    self.heightmaps[index].set(x, z, height);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use std::io::{self, Read, Write};
+use anyhow::{anyhow, bail};
+use flate2::Compression;
+use flate2::read::{GzDecoder, ZlibDecoder};
+use flate2::write::{GzEncoder, ZlibEncoder};
+use twox_hash::XxHash32;
+use dolls_config::RegionCompression;
+
+/// Compression id announcing a named, non-vanilla compression.
+const CUSTOM_ID: u8 = 127;
+/// Name of the zstd compression after [`CUSTOM_ID`], only Dolls can read such chunks.
+const ZSTD_NAME: &str = "dolls:zstd";
+const ZSTD_LEVEL: i32 = 3;
+
+/// How a chunk payload is compressed, the byte in front of it.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ChunkCompression {
+    Gzip,
+    Zlib,
+    Uncompressed,
+    /// The block stream of lz4-java, vanilla's `lz4` option.
+    Lz4,
+    /// A custom compression, vanilla refuses to load these chunks.
+    Zstd,
+}
+
+impl From<RegionCompression> for ChunkCompression {
+    fn from(compression: RegionCompression) -> Self {
+        match compression {
+            RegionCompression::Deflate => ChunkCompression::Zlib,
+            RegionCompression::Lz4 => ChunkCompression::Lz4,
+            RegionCompression::Zstd => ChunkCompression::Zstd,
+            RegionCompression::None => ChunkCompression::Uncompressed,
+        }
+    }
+}
+
+impl ChunkCompression {
+    pub const fn id(self) -> u8 {
+        match self {
+            ChunkCompression::Gzip => 1,
+            ChunkCompression::Zlib => 2,
+            ChunkCompression::Uncompressed => 3,
+            ChunkCompression::Lz4 => 4,
+            ChunkCompression::Zstd => CUSTOM_ID,
+        }
+    }
+
+    /// Splits off the compression of a chunk payload, custom ids are followed by their name.
+    pub fn read_header(id: u8, payload: &[u8]) -> anyhow::Result<(Self, &[u8])> {
+        match id {
+            1 => Ok((ChunkCompression::Gzip, payload)),
+            2 => Ok((ChunkCompression::Zlib, payload)),
+            3 => Ok((ChunkCompression::Uncompressed, payload)),
+            4 => Ok((ChunkCompression::Lz4, payload)),
+            CUSTOM_ID => {
+                let length = payload.get(..2).map(|length| u16::from_be_bytes([length[0], length[1]]) as usize)
+                    .ok_or_else(|| anyhow!("Custom chunk compression without a name"))?;
+                let name = payload.get(2..2 + length).ok_or_else(|| anyhow!("Truncated chunk compression name"))?;
+                match name == ZSTD_NAME.as_bytes() {
+                    true => Ok((ChunkCompression::Zstd, &payload[2 + length..])),
+                    false => bail!("Unknown custom chunk compression {}", String::from_utf8_lossy(name)),
+                }
+            }
+            _ => bail!("Unknown chunk compression {}", id),
+        }
+    }
+
+    pub fn decompress(self, data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut decompressed = Vec::new();
+        match self {
+            ChunkCompression::Gzip => GzDecoder::new(data).read_to_end(&mut decompressed)?,
+            ChunkCompression::Zlib => ZlibDecoder::new(data).read_to_end(&mut decompressed)?,
+            ChunkCompression::Uncompressed => return Ok(data.to_vec()),
+            ChunkCompression::Lz4 => return lz4_block_decompress(data),
+            ChunkCompression::Zstd => return zstd::decode_all(data),
+        };
+        Ok(decompressed)
+    }
+
+    /// Compresses `data` into a payload for [`ChunkCompression::read_header`], including a custom name.
+    pub fn compress(self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            ChunkCompression::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            ChunkCompression::Zlib => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            ChunkCompression::Uncompressed => Ok(data.to_vec()),
+            ChunkCompression::Lz4 => Ok(lz4_block_compress(data)),
+            ChunkCompression::Zstd => {
+                let mut payload = (ZSTD_NAME.len() as u16).to_be_bytes().to_vec();
+                payload.extend_from_slice(ZSTD_NAME.as_bytes());
+                payload.extend(zstd::encode_all(data, ZSTD_LEVEL)?);
+                Ok(payload)
+            }
+        }
+    }
+}
+
+const LZ4_MAGIC: &[u8; 8] = b"LZ4Block";
+const LZ4_BLOCK_SIZE: usize = 64 * 1024;
+const LZ4_METHOD_RAW: u8 = 0x10;
+const LZ4_METHOD_LZ4: u8 = 0x20;
+/// log2 of the block size minus 10, stored next to the method.
+const LZ4_LEVEL: u8 = 6;
+const LZ4_SEED: u32 = 0x9747_B28C;
+
+fn lz4_checksum(data: &[u8]) -> u32 {
+    XxHash32::oneshot(LZ4_SEED, data) & 0x0FFF_FFFF
+}
+
+fn write_lz4_block(output: &mut Vec<u8>, method: u8, block: &[u8], original_length: usize, checksum: u32) {
+    output.extend_from_slice(LZ4_MAGIC);
+    output.push(method | LZ4_LEVEL);
+    output.extend_from_slice(&(block.len() as u32).to_le_bytes());
+    output.extend_from_slice(&(original_length as u32).to_le_bytes());
+    output.extend_from_slice(&checksum.to_le_bytes());
+    output.extend_from_slice(block);
+}
+
+/// Writes what `LZ4BlockOutputStream` writes: 64 KiB blocks, each compressed unless that does not help.
+fn lz4_block_compress(data: &[u8]) -> Vec<u8> {
+    let mut output = Vec::new();
+    for block in data.chunks(LZ4_BLOCK_SIZE) {
+        let compressed = lz4_flex::block::compress(block);
+        match compressed.len() < block.len() {
+            true => write_lz4_block(&mut output, LZ4_METHOD_LZ4, &compressed, block.len(), lz4_checksum(block)),
+            false => write_lz4_block(&mut output, LZ4_METHOD_RAW, block, block.len(), lz4_checksum(block)),
+        }
+    }
+    write_lz4_block(&mut output, LZ4_METHOD_RAW, &[], 0, 0);
+    output
+}
+
+fn lz4_block_decompress(mut data: &[u8]) -> io::Result<Vec<u8>> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid LZ4 block stream: {}", message));
+    let mut output = Vec::new();
+    while !data.is_empty() {
+        if data.len() < 21 || &data[..8] != LZ4_MAGIC {
+            return Err(invalid("bad block header"));
+        }
+        let (method, level) = (data[8] & 0xF0, (data[8] & 0x0F) as u32);
+        let compressed_length = u32::from_le_bytes(data[9..13].try_into().unwrap()) as usize;
+        let original_length = u32::from_le_bytes(data[13..17].try_into().unwrap()) as usize;
+        let checksum = u32::from_le_bytes(data[17..21].try_into().unwrap());
+        data = &data[21..];
+        if original_length > 1 << (10 + level) || compressed_length > data.len() {
+            return Err(invalid("block length out of bounds"));
+        }
+        let (block, rest) = data.split_at(compressed_length);
+        data = rest;
+        if original_length == 0 && compressed_length == 0 {
+            // Marks the end of the stream.
+            break;
+        }
+        let start = output.len();
+        match method {
+            LZ4_METHOD_RAW if compressed_length == original_length => output.extend_from_slice(block),
+            LZ4_METHOD_LZ4 => output.extend(lz4_flex::block::decompress(block, original_length).map_err(|err| invalid(&err.to_string()))?),
+            _ => return Err(invalid("unknown block method")),
+        }
+        if output.len() - start != original_length || lz4_checksum(&output[start..]) != checksum {
+            return Err(invalid("checksum mismatch"));
+        }
+    }
+    Ok(output)
+}
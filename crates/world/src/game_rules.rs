@@ -0,0 +1,184 @@
+use std::collections::BTreeMap;
+use anyhow::{anyhow, bail};
+use dolls_core::nbt::NbtCompound;
+
+/// A game rule that is on or off.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct BoolRule(pub &'static str);
+
+/// A game rule holding a number.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct IntRule(pub &'static str);
+
+pub const ANNOUNCE_ADVANCEMENTS: BoolRule = BoolRule("announceAdvancements");
+pub const COMMAND_BLOCK_OUTPUT: BoolRule = BoolRule("commandBlockOutput");
+pub const DO_DAYLIGHT_CYCLE: BoolRule = BoolRule("doDaylightCycle");
+pub const DO_IMMEDIATE_RESPAWN: BoolRule = BoolRule("doImmediateRespawn");
+pub const DO_LIMITED_CRAFTING: BoolRule = BoolRule("doLimitedCrafting");
+pub const DO_WEATHER_CYCLE: BoolRule = BoolRule("doWeatherCycle");
+pub const FALL_DAMAGE: BoolRule = BoolRule("fallDamage");
+pub const KEEP_INVENTORY: BoolRule = BoolRule("keepInventory");
+pub const LOG_ADMIN_COMMANDS: BoolRule = BoolRule("logAdminCommands");
+pub const NATURAL_REGENERATION: BoolRule = BoolRule("naturalRegeneration");
+pub const REDUCED_DEBUG_INFO: BoolRule = BoolRule("reducedDebugInfo");
+pub const SEND_COMMAND_FEEDBACK: BoolRule = BoolRule("sendCommandFeedback");
+pub const SHOW_DEATH_MESSAGES: BoolRule = BoolRule("showDeathMessages");
+pub const SPECTATORS_GENERATE_CHUNKS: BoolRule = BoolRule("spectatorsGenerateChunks");
+pub const MAX_ENTITY_CRAMMING: IntRule = IntRule("maxEntityCramming");
+pub const PLAYERS_SLEEPING_PERCENTAGE: IntRule = IntRule("playersSleepingPercentage");
+pub const RANDOM_TICK_SPEED: IntRule = IntRule("randomTickSpeed");
+pub const SPAWN_RADIUS: IntRule = IntRule("spawnRadius");
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum GameRuleValue {
+    Bool(bool),
+    Int(i32),
+}
+
+impl GameRuleValue {
+    /// Parses a value of the same kind as `self`.
+    fn parse_like(self, value: &str) -> Option<Self> {
+        match self {
+            GameRuleValue::Bool(_) => value.parse().ok().map(GameRuleValue::Bool),
+            GameRuleValue::Int(_) => value.parse().ok().map(GameRuleValue::Int),
+        }
+    }
+}
+
+impl std::fmt::Display for GameRuleValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GameRuleValue::Bool(value) => value.fmt(f),
+            GameRuleValue::Int(value) => value.fmt(f),
+        }
+    }
+}
+
+/// The game rules of 1.21.1 with their defaults.
+pub const GAME_RULES: &[(&str, GameRuleValue)] = &[
+    ("announceAdvancements", GameRuleValue::Bool(true)),
+    ("blockExplosionDropDecay", GameRuleValue::Bool(true)),
+    ("commandBlockOutput", GameRuleValue::Bool(true)),
+    ("commandModificationBlockLimit", GameRuleValue::Int(32768)),
+    ("disableElytraMovementCheck", GameRuleValue::Bool(false)),
+    ("disableRaids", GameRuleValue::Bool(false)),
+    ("doDaylightCycle", GameRuleValue::Bool(true)),
+    ("doEntityDrops", GameRuleValue::Bool(true)),
+    ("doFireTick", GameRuleValue::Bool(true)),
+    ("doImmediateRespawn", GameRuleValue::Bool(false)),
+    ("doInsomnia", GameRuleValue::Bool(true)),
+    ("doLimitedCrafting", GameRuleValue::Bool(false)),
+    ("doMobLoot", GameRuleValue::Bool(true)),
+    ("doMobSpawning", GameRuleValue::Bool(true)),
+    ("doPatrolSpawning", GameRuleValue::Bool(true)),
+    ("doTileDrops", GameRuleValue::Bool(true)),
+    ("doTraderSpawning", GameRuleValue::Bool(true)),
+    ("doVinesSpread", GameRuleValue::Bool(true)),
+    ("doWardenSpawning", GameRuleValue::Bool(true)),
+    ("doWeatherCycle", GameRuleValue::Bool(true)),
+    ("drowningDamage", GameRuleValue::Bool(true)),
+    ("enderPearlsVanishOnDeath", GameRuleValue::Bool(true)),
+    ("fallDamage", GameRuleValue::Bool(true)),
+    ("fireDamage", GameRuleValue::Bool(true)),
+    ("forgiveDeadPlayers", GameRuleValue::Bool(true)),
+    ("freezeDamage", GameRuleValue::Bool(true)),
+    ("globalSoundEvents", GameRuleValue::Bool(true)),
+    ("keepInventory", GameRuleValue::Bool(false)),
+    ("lavaSourceConversion", GameRuleValue::Bool(false)),
+    ("logAdminCommands", GameRuleValue::Bool(true)),
+    ("maxCommandChainLength", GameRuleValue::Int(65536)),
+    ("maxCommandForkCount", GameRuleValue::Int(65536)),
+    ("maxEntityCramming", GameRuleValue::Int(24)),
+    ("mobExplosionDropDecay", GameRuleValue::Bool(true)),
+    ("mobGriefing", GameRuleValue::Bool(true)),
+    ("naturalRegeneration", GameRuleValue::Bool(true)),
+    ("playersNetherPortalCreativeDelay", GameRuleValue::Int(1)),
+    ("playersNetherPortalDefaultDelay", GameRuleValue::Int(80)),
+    ("playersSleepingPercentage", GameRuleValue::Int(100)),
+    ("projectilesCanBreakBlocks", GameRuleValue::Bool(true)),
+    ("randomTickSpeed", GameRuleValue::Int(3)),
+    ("reducedDebugInfo", GameRuleValue::Bool(false)),
+    ("sendCommandFeedback", GameRuleValue::Bool(true)),
+    ("showDeathMessages", GameRuleValue::Bool(true)),
+    ("snowAccumulationHeight", GameRuleValue::Int(1)),
+    ("spawnChunkRadius", GameRuleValue::Int(2)),
+    ("spawnRadius", GameRuleValue::Int(10)),
+    ("spectatorsGenerateChunks", GameRuleValue::Bool(true)),
+    ("tntExplosionDropDecay", GameRuleValue::Bool(false)),
+    ("universalAnger", GameRuleValue::Bool(false)),
+    ("waterSourceConversion", GameRuleValue::Bool(true)),
+];
+
+fn default_value(name: &str) -> Option<GameRuleValue> {
+    GAME_RULES.iter().find(|(rule, _)| *rule == name).map(|(_, value)| *value)
+}
+
+/// Values of the game rules that differ from their defaults, kept as the `GameRules` of `level.dat`.
+/// Rules of other versions are kept as they were read.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GameRules {
+    values: BTreeMap<String, GameRuleValue>,
+    unknown: BTreeMap<String, String>,
+}
+
+impl GameRules {
+    pub fn from_nbt(compound: &NbtCompound) -> Self {
+        let mut rules = Self::default();
+        for (name, value) in compound.iter() {
+            let Some(value) = value.as_str() else { continue };
+            if rules.set(name, value).is_err() {
+                rules.unknown.insert(name.clone(), value.to_string());
+            }
+        }
+        rules
+    }
+
+    /// Every rule with its value as a string, like vanilla writes them.
+    pub fn to_nbt(&self) -> NbtCompound {
+        let mut compound = NbtCompound::new();
+        for (name, value) in &self.unknown {
+            compound.insert(name.as_str(), value.as_str());
+        }
+        for (name, _) in GAME_RULES {
+            compound.insert(*name, self.get(name).expect("Known rule").to_string());
+        }
+        compound
+    }
+
+    pub fn get_bool(&self, rule: BoolRule) -> bool {
+        matches!(self.get(rule.0), Some(GameRuleValue::Bool(true)))
+    }
+
+    pub fn get_int(&self, rule: IntRule) -> i32 {
+        match self.get(rule.0) {
+            Some(GameRuleValue::Int(value)) => value,
+            _ => 0,
+        }
+    }
+
+    /// The value of a rule by name, `None` for rules that do not exist.
+    pub fn get(&self, name: &str) -> Option<GameRuleValue> {
+        self.values.get(name).copied().or_else(|| default_value(name))
+    }
+
+    /// Changes a rule from a value given as text, returning the new value.
+    pub fn set(&mut self, name: &str, value: &str) -> anyhow::Result<GameRuleValue> {
+        let default = default_value(name).ok_or_else(|| anyhow!("Unknown game rule {}", name))?;
+        let Some(value) = default.parse_like(value) else {
+            bail!("Invalid value '{}' for game rule {}", value, name);
+        };
+        match value == default {
+            true => self.values.remove(name),
+            false => self.values.insert(name.to_string(), value),
+        };
+        Ok(value)
+    }
+
+    pub fn set_bool(&mut self, rule: BoolRule, value: bool) {
+        self.set(rule.0, &value.to_string()).expect("Bool rules take bools");
+    }
+
+    pub fn set_int(&mut self, rule: IntRule, value: i32) {
+        self.set(rule.0, &value.to_string()).expect("Int rules take ints");
+    }
+}
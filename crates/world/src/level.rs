@@ -0,0 +1,204 @@
+use std::collections::hash_map::RandomState;
+use std::fs::{self, File};
+use std::hash::BuildHasher;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+use std::sync::RwLock;
+use anyhow::{anyhow, Context};
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use once_cell::sync::Lazy;
+use sha2::{Digest, Sha256};
+use dolls_core::datatype::BlockPos;
+use dolls_core::nbt::NbtCompound;
+use crate::prelude::{GameRules, Weather, WorldBorder};
+
+/// Data version of the Minecraft release worlds are written for.
+pub const DATA_VERSION: i32 = 3955;
+
+/// How hard the game is, `Difficulty` of the level.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum Difficulty {
+    Peaceful,
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 4] = [Difficulty::Peaceful, Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
+
+    pub fn id(self) -> u8 {
+        self as u8
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        Self::ALL.get(id as usize).copied()
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Peaceful => "peaceful",
+            Difficulty::Easy => "easy",
+            Difficulty::Normal => "normal",
+            Difficulty::Hard => "hard",
+        }
+    }
+}
+
+/// Contents of `level.dat`, fields Dolls does not use are kept as they were read.
+#[derive(Debug, Clone)]
+pub struct LevelData {
+    pub level_name: String,
+    pub seed: i64,
+    pub world_border: WorldBorder,
+    /// Ticks the world has run for, `Time`.
+    pub time: i64,
+    /// Ticks into the day cycle, 24000 per day, `DayTime`.
+    pub day_time: i64,
+    pub weather: Weather,
+    pub game_rules: GameRules,
+    pub difficulty: Difficulty,
+    /// Set once a player locked the difficulty, it cannot change afterwards.
+    pub difficulty_locked: bool,
+    data: NbtCompound,
+}
+
+impl LevelData {
+    pub fn new(level_name: impl Into<String>, seed: i64) -> Self {
+        Self {
+            level_name: level_name.into(),
+            seed,
+            world_border: WorldBorder::default(),
+            time: 0,
+            day_time: 0,
+            weather: Weather::default(),
+            game_rules: GameRules::default(),
+            difficulty: Difficulty::default(),
+            difficulty_locked: false,
+            data: NbtCompound::new(),
+        }
+    }
+
+    /// Seed as sent to clients in Login and Respawn, which only use it for biome noise.
+    pub fn hashed_seed(&self) -> i64 {
+        hashed_seed(self.seed)
+    }
+
+    /// Where players appear in the overworld, `SpawnX`, `SpawnY` and `SpawnZ` of the level.
+    pub fn spawn(&self) -> BlockPos {
+        let coordinate = |key, default| self.data.get_i64(key).map_or(default, |value| value as i32);
+        BlockPos::new(coordinate("SpawnX", 0), coordinate("SpawnY", 64), coordinate("SpawnZ", 0))
+    }
+
+    /// Yaw players face when appearing at the spawn, `SpawnAngle`.
+    pub fn spawn_angle(&self) -> f32 {
+        self.data.get_f64("SpawnAngle").unwrap_or_default() as f32
+    }
+
+    /// Whether a spawn was chosen, new worlds pick one once their chunks can be read.
+    pub fn has_spawn(&self) -> bool {
+        self.data.get("SpawnX").is_some()
+    }
+
+    pub fn set_spawn(&mut self, position: BlockPos, angle: f32) {
+        self.data.insert("SpawnX", position.x)
+            .insert("SpawnY", position.y)
+            .insert("SpawnZ", position.z)
+            .insert("SpawnAngle", angle);
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        let (_, root) = NbtCompound::read_named(&mut GzDecoder::new(BufReader::new(file)))
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let data = root.get_compound("Data").cloned().ok_or_else(|| anyhow!("{} has no Data compound", path.display()))?;
+        let seed = data.get_compound("WorldGenSettings")
+            .and_then(|settings| settings.get_i64("seed"))
+            .ok_or_else(|| anyhow!("{} has no world generation seed", path.display()))?;
+        Ok(Self {
+            level_name: data.get_str("LevelName").unwrap_or_default().to_string(),
+            seed,
+            world_border: WorldBorder::from_nbt(&data),
+            time: data.get_i64("Time").unwrap_or(0),
+            day_time: data.get_i64("DayTime").unwrap_or(0),
+            weather: Weather::from_nbt(&data),
+            game_rules: data.get_compound("GameRules").map(GameRules::from_nbt).unwrap_or_default(),
+            difficulty: data.get_i64("Difficulty").and_then(|id| Difficulty::from_id(id as u8)).unwrap_or_default(),
+            difficulty_locked: data.get_bool("DifficultyLocked").unwrap_or(false),
+            data,
+        })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut data = self.data.clone();
+        let mut settings = data.get_compound("WorldGenSettings").cloned().unwrap_or_else(|| NbtCompound::new()
+            .with("generate_features", true)
+            .with("bonus_chest", false)
+            .with("dimensions", NbtCompound::new()));
+        settings.insert("seed", self.seed);
+        data.insert("WorldGenSettings", settings)
+            .insert("LevelName", self.level_name.as_str())
+            .insert("DataVersion", DATA_VERSION)
+            .insert("version", 19133);
+        data.insert("Time", self.time).insert("DayTime", self.day_time).insert("GameRules", self.game_rules.to_nbt());
+        data.insert("Difficulty", self.difficulty.id() as i8).insert("DifficultyLocked", self.difficulty_locked);
+        self.world_border.write_nbt(&mut data);
+        self.weather.write_nbt(&mut data);
+
+        // Write next to the old file first so a crash cannot leave a truncated level.dat behind.
+        let temporary = path.with_extension("dat_new");
+        {
+            let file = File::create(&temporary).with_context(|| format!("Failed to create {}", temporary.display()))?;
+            let mut encoder = GzEncoder::new(BufWriter::new(file), Compression::default());
+            NbtCompound::new().with("Data", data).write_named(&mut encoder, "")?;
+            encoder.finish()?;
+        }
+        fs::rename(&temporary, path).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Loads `level.dat` from `world_dir`, or creates it with `seed` for a new world.
+    pub fn load_or_create(world_dir: impl AsRef<Path>, level_name: &str, seed: &str) -> anyhow::Result<Self> {
+        let path = world_dir.as_ref().join("level.dat");
+        if path.exists() {
+            return Self::load(path);
+        }
+        let level = Self::new(level_name, parse_seed(seed));
+        level.save(path)?;
+        Ok(level)
+    }
+}
+
+/// Interprets a `level-seed` setting like vanilla: numbers are used as is, other text is hashed
+/// with Java's `String.hashCode` and an empty setting picks a random seed.
+pub fn parse_seed(seed: &str) -> i64 {
+    let seed = seed.trim();
+    if seed.is_empty() {
+        return RandomState::new().hash_one(std::time::SystemTime::now()) as i64;
+    }
+    seed.parse().unwrap_or_else(|_| java_string_hash(seed) as i64)
+}
+
+fn java_string_hash(text: &str) -> i32 {
+    text.encode_utf16().fold(0i32, |hash, unit| hash.wrapping_mul(31).wrapping_add(unit as i32))
+}
+
+/// First 8 bytes of the SHA-256 of the seed, both little endian like Guava's `hashLong`.
+pub fn hashed_seed(seed: i64) -> i64 {
+    let digest = Sha256::digest(seed.to_le_bytes());
+    i64::from_le_bytes(digest[..8].try_into().expect("SHA-256 digests are 32 bytes"))
+}
+
+static LEVEL: Lazy<RwLock<LevelData>> = Lazy::new(|| RwLock::new(LevelData::new("world", 0)));
+
+/// Level of the running server, replaced once `level.dat` is loaded at startup.
+pub fn level() -> &'static RwLock<LevelData> {
+    &LEVEL
+}
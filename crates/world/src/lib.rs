@@ -0,0 +1,33 @@
+pub mod level;
+pub mod block;
+pub mod chunk;
+pub mod palette;
+pub mod world;
+pub mod anvil;
+pub mod compression;
+pub mod light;
+pub mod border;
+pub mod player_data;
+pub mod advancement_data;
+pub mod stats_data;
+pub mod scoreboard;
+pub mod weather;
+pub mod game_rules;
+
+pub mod prelude {
+    pub use crate::level::*;
+    pub use crate::block::*;
+    pub use crate::chunk::*;
+    pub use crate::palette::*;
+    pub use crate::world::*;
+    pub use crate::anvil::*;
+    pub use crate::compression::*;
+    pub use crate::light::*;
+    pub use crate::border::*;
+    pub use crate::player_data::*;
+    pub use crate::advancement_data::*;
+    pub use crate::stats_data::*;
+    pub use crate::scoreboard::*;
+    pub use crate::weather::*;
+    pub use crate::game_rules::*;
+}
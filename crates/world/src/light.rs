@@ -0,0 +1,382 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use dolls_core::datatype::BlockPos;
+use crate::prelude::{BlockRegistry, Chunk, ChunkPos, ChunkSection, SECTION_SIZE, SECTION_VOLUME};
+
+pub const MAX_LIGHT: u8 = 15;
+/// Bytes of the light of one section, a nibble per block.
+pub const LIGHT_SECTION_BYTES: usize = SECTION_VOLUME / 2;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum LightKind {
+    /// Light from above the world, which falls straight down without dimming through clear blocks.
+    Sky,
+    /// Light given off by blocks.
+    Block,
+}
+
+impl LightKind {
+    pub const ALL: [LightKind; 2] = [LightKind::Sky, LightKind::Block];
+}
+
+/// Light levels of one section, only stored per block once they differ.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LightSection {
+    Uniform(u8),
+    /// Two blocks per byte, the even index in the lower nibble.
+    Nibbles(Box<[u8; LIGHT_SECTION_BYTES]>),
+}
+
+impl LightSection {
+    /// `index` as in [`ChunkSection::index`].
+    pub fn get(&self, index: usize) -> u8 {
+        match self {
+            LightSection::Uniform(level) => *level,
+            LightSection::Nibbles(nibbles) => (nibbles[index >> 1] >> ((index & 1) << 2)) & 0xF,
+        }
+    }
+
+    pub fn set(&mut self, index: usize, level: u8) {
+        if let LightSection::Uniform(current) = *self {
+            if current == level {
+                return;
+            }
+            *self = LightSection::Nibbles(Box::new([current | current << 4; LIGHT_SECTION_BYTES]));
+        }
+        if let LightSection::Nibbles(nibbles) = self {
+            let shift = (index & 1) << 2;
+            let byte = &mut nibbles[index >> 1];
+            *byte = (*byte & !(0xF << shift)) | ((level & 0xF) << shift);
+        }
+    }
+
+    /// Whether every block is dark.
+    pub fn is_dark(&self) -> bool {
+        match self {
+            LightSection::Uniform(level) => *level == 0,
+            LightSection::Nibbles(nibbles) => nibbles.iter().all(|byte| *byte == 0),
+        }
+    }
+
+    /// The nibbles as sent to clients.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            LightSection::Uniform(level) => vec![level | level << 4; LIGHT_SECTION_BYTES],
+            LightSection::Nibbles(nibbles) => nibbles.to_vec(),
+        }
+    }
+}
+
+/// Sky and block light of a chunk's sections plus one section below and one above the world, bottom first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkLight {
+    sky: Vec<LightSection>,
+    block: Vec<LightSection>,
+}
+
+impl ChunkLight {
+    /// Dark everywhere but above the world, for `sections` sections of blocks.
+    pub fn new(sections: usize) -> Self {
+        let mut sky = vec![LightSection::Uniform(0); sections + 2];
+        sky[sections + 1] = LightSection::Uniform(MAX_LIGHT);
+        Self { sky, block: vec![LightSection::Uniform(0); sections + 2] }
+    }
+
+    pub fn sections(&self, kind: LightKind) -> &[LightSection] {
+        match kind {
+            LightKind::Sky => &self.sky,
+            LightKind::Block => &self.block,
+        }
+    }
+
+    fn sections_mut(&mut self, kind: LightKind) -> &mut [LightSection] {
+        match kind {
+            LightKind::Sky => &mut self.sky,
+            LightKind::Block => &mut self.block,
+        }
+    }
+}
+
+const DOWN: (i32, i32, i32) = (0, -1, 0);
+const DIRECTIONS: [(i32, i32, i32); 6] = [DOWN, (0, 1, 0), (0, 0, -1), (0, 0, 1), (-1, 0, 0), (1, 0, 0)];
+
+fn offset(position: BlockPos, (x, y, z): (i32, i32, i32)) -> BlockPos {
+    BlockPos::new(position.x + x, position.y + y, position.z + z)
+}
+
+/// Light section and index within it of `position`, `None` above or below the light of the chunk.
+fn light_index(chunk: &Chunk, position: BlockPos) -> Option<(usize, usize)> {
+    let relative = usize::try_from(position.y - (chunk.min_y() - SECTION_SIZE as i32)).ok()?;
+    let section = relative / SECTION_SIZE;
+    (section < chunk.sections().len() + 2).then(|| {
+        (section, ChunkSection::index((position.x & 15) as usize, relative % SECTION_SIZE, (position.z & 15) as usize))
+    })
+}
+
+/// Light a neighbour gets from a block with `level`, moving in `direction` into a block with `opacity`.
+fn propagated(kind: LightKind, level: u8, direction: (i32, i32, i32), opacity: u8) -> u8 {
+    match kind == LightKind::Sky && direction == DOWN && level == MAX_LIGHT && opacity == 0 {
+        true => MAX_LIGHT,
+        false => level.saturating_sub(opacity.max(1)),
+    }
+}
+
+/// Spreads and removes light across the loaded chunks of a world, flood filling from changed blocks.
+pub(crate) struct LightEngine<'a> {
+    chunks: &'a mut HashMap<ChunkPos, Chunk>,
+    registry: &'a BlockRegistry,
+    /// Chunks whose light changed, so that clients can be told.
+    changed: &'a mut HashSet<ChunkPos>,
+}
+
+impl<'a> LightEngine<'a> {
+    pub(crate) fn new(chunks: &'a mut HashMap<ChunkPos, Chunk>, registry: &'a BlockRegistry, changed: &'a mut HashSet<ChunkPos>) -> Self {
+        Self { chunks, registry, changed }
+    }
+
+    /// `None` in unloaded chunks and outside of their light.
+    fn light(&self, kind: LightKind, position: BlockPos) -> Option<u8> {
+        let chunk = self.chunks.get(&ChunkPos::of(position))?;
+        let (section, index) = light_index(chunk, position)?;
+        Some(chunk.light().sections(kind)[section].get(index))
+    }
+
+    fn set_light(&mut self, kind: LightKind, position: BlockPos, level: u8) {
+        let chunk_pos = ChunkPos::of(position);
+        let Some(chunk) = self.chunks.get_mut(&chunk_pos) else { return };
+        let Some((section, index)) = light_index(chunk, position) else { return };
+        chunk.light_mut().sections_mut(kind)[section].set(index, level);
+        self.changed.insert(chunk_pos);
+    }
+
+    /// Opacity and emission of the block at `position`, states the registry does not know are opaque.
+    fn properties(&self, position: BlockPos) -> (u8, u8) {
+        let Some(chunk) = self.chunks.get(&ChunkPos::of(position)) else { return (MAX_LIGHT, 0) };
+        let state = chunk.get_block((position.x & 15) as usize, position.y, (position.z & 15) as usize);
+        self.registry.state(state).map_or((MAX_LIGHT, 0), |info| (info.light_opacity, info.light_emission))
+    }
+
+    /// The section above the world, where the sky is always bright.
+    fn is_sky(&self, position: BlockPos) -> bool {
+        self.chunks.get(&ChunkPos::of(position))
+            .and_then(|chunk| light_index(chunk, position).map(|(section, _)| section == chunk.sections().len() + 1))
+            .unwrap_or(false)
+    }
+
+    fn increase(&mut self, kind: LightKind, mut queue: VecDeque<BlockPos>) {
+        while let Some(position) = queue.pop_front() {
+            let Some(level) = self.light(kind, position) else { continue };
+            if level <= 1 {
+                continue;
+            }
+            for direction in DIRECTIONS {
+                let neighbour = offset(position, direction);
+                let Some(current) = self.light(kind, neighbour) else { continue };
+                let next = propagated(kind, level, direction, self.properties(neighbour).0);
+                if next > current {
+                    self.set_light(kind, neighbour, next);
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+    }
+
+    /// Darkens what was lit by the removed levels, collecting the blocks which still have light of their own.
+    fn decrease(&mut self, kind: LightKind, mut queue: VecDeque<(BlockPos, u8)>, relight: &mut VecDeque<BlockPos>) {
+        while let Some((position, level)) = queue.pop_front() {
+            for direction in DIRECTIONS {
+                let neighbour = offset(position, direction);
+                let Some(current) = self.light(kind, neighbour) else { continue };
+                if current == 0 {
+                    continue;
+                }
+                let from_here = current < level || (kind == LightKind::Sky && direction == DOWN && level == MAX_LIGHT && current == MAX_LIGHT);
+                if !from_here || (kind == LightKind::Sky && self.is_sky(neighbour)) {
+                    relight.push_back(neighbour);
+                    continue;
+                }
+                self.set_light(kind, neighbour, 0);
+                queue.push_back((neighbour, current));
+                let emission = self.properties(neighbour).1;
+                if kind == LightKind::Block && emission > 0 {
+                    self.set_light(kind, neighbour, emission);
+                    relight.push_back(neighbour);
+                }
+            }
+        }
+    }
+
+    /// Updates light after the block at `position` changed.
+    pub(crate) fn update_block(&mut self, position: BlockPos) {
+        let emission = self.properties(position).1;
+        for kind in LightKind::ALL {
+            let Some(level) = self.light(kind, position) else { continue };
+            let mut relight = VecDeque::new();
+            self.set_light(kind, position, 0);
+            self.decrease(kind, VecDeque::from([(position, level)]), &mut relight);
+            // Neighbours may shine into a clearer block now.
+            relight.extend(DIRECTIONS.map(|direction| offset(position, direction)));
+            if kind == LightKind::Block && emission > 0 {
+                self.set_light(kind, position, emission);
+                relight.push_back(position);
+            }
+            self.increase(kind, relight);
+        }
+    }
+
+    /// Computes the light of chunks from scratch and exchanges light with their loaded neighbours.
+    ///
+    /// With `neighbours`, the chunks around are relit as well, dropping light the chunks cast on them
+    /// before. Light reaches no further than one chunk. Without it, e.g. for chunks which were just
+    /// loaded, only changes to other chunks are recorded since the chunks are sent with their light.
+    pub(crate) fn relight_chunks(&mut self, positions: &[ChunkPos], neighbours: bool) {
+        let mut relit = positions.iter().copied().filter(|position| self.chunks.contains_key(position)).collect::<Vec<_>>();
+        if neighbours {
+            let around = relit.iter()
+                .flat_map(|position| (-1..=1).flat_map(move |dz| (-1..=1).map(move |dx| ChunkPos::new(position.x + dx, position.z + dz))))
+                .filter(|position| self.chunks.contains_key(position))
+                .collect::<HashSet<_>>();
+            relit = around.into_iter().collect();
+        }
+        let unrecorded = match neighbours {
+            true => Vec::new(),
+            false => relit.iter().copied().filter(|position| !self.changed.contains(position)).collect(),
+        };
+
+        let (mut sky, mut emitters) = (VecDeque::new(), VecDeque::new());
+        for position in &relit {
+            self.seed_chunk(*position, &mut sky, &mut emitters);
+        }
+        for position in &relit {
+            self.seed_borders(*position, &mut sky, &mut emitters);
+        }
+        self.increase(LightKind::Sky, sky);
+        self.increase(LightKind::Block, emitters);
+        for position in unrecorded {
+            self.changed.remove(&position);
+        }
+    }
+
+    /// Resets the light of a chunk to its sky and its own light sources, queueing where light spreads from.
+    fn seed_chunk(&mut self, position: ChunkPos, sky: &mut VecDeque<BlockPos>, emitters: &mut VecDeque<BlockPos>) {
+        let registry = self.registry;
+        let chunk = self.chunks.get_mut(&position).expect("Relit chunks are loaded");
+        let sections = chunk.sections().len();
+        *chunk.light_mut() = ChunkLight::new(sections);
+        self.changed.insert(position);
+        let (min_y, bottom) = (chunk.min_y(), chunk.min_y() - SECTION_SIZE as i32);
+        let top = min_y + (sections * SECTION_SIZE) as i32;
+        let (base_x, base_z) = (position.x * SECTION_SIZE as i32, position.z * SECTION_SIZE as i32);
+        let block = |x: usize, y: i32, z: usize| BlockPos::new(base_x + x as i32, y, base_z + z as i32);
+        let clear = |state| registry.state(state).is_some_and(|info| info.light_opacity == 0);
+
+        // Sky falls straight down until the first block which dims it.
+        let mut lowest = [[top; SECTION_SIZE]; SECTION_SIZE];
+        for (z, row) in lowest.iter_mut().enumerate() {
+            for (x, lowest) in row.iter_mut().enumerate() {
+                while *lowest > min_y {
+                    let relative = (*lowest - 1 - min_y) as usize;
+                    let section = &chunk.sections()[relative / SECTION_SIZE];
+                    match section.blocks().single_value() {
+                        Some(state) if clear(state) => *lowest = min_y + (relative / SECTION_SIZE * SECTION_SIZE) as i32,
+                        Some(_) => break,
+                        None if clear(section.get_block(x, relative % SECTION_SIZE, z)) => *lowest -= 1,
+                        None => break,
+                    }
+                }
+                // Nothing dims the sky below the world.
+                if *lowest == min_y {
+                    *lowest = bottom;
+                }
+            }
+        }
+        let sky_top = lowest.iter().flatten().copied().max().unwrap_or(top);
+        for section in 0..=sections {
+            let from = bottom + (section * SECTION_SIZE) as i32;
+            let light = &mut chunk.light_mut().sky[section];
+            if from >= sky_top {
+                *light = LightSection::Uniform(MAX_LIGHT);
+                continue;
+            }
+            for (z, row) in lowest.iter().enumerate() {
+                for (x, lowest) in row.iter().enumerate() {
+                    for y in (*lowest).max(from)..from + SECTION_SIZE as i32 {
+                        light.set(ChunkSection::index(x, (y - from) as usize, z), MAX_LIGHT);
+                    }
+                }
+            }
+        }
+
+        // Light spreads sideways from the lit part of each column into darker neighbouring columns.
+        for z in 0..SECTION_SIZE {
+            for x in 0..SECTION_SIZE {
+                let neighbours = [(x.wrapping_sub(1), z), (x + 1, z), (x, z.wrapping_sub(1)), (x, z + 1)];
+                let highest = neighbours.iter()
+                    .filter(|(x, z)| *x < SECTION_SIZE && *z < SECTION_SIZE)
+                    .map(|(x, z)| lowest[*z][*x])
+                    .max()
+                    .unwrap_or(top);
+                sky.extend((lowest[z][x]..highest.max(lowest[z][x] + 1).min(top)).map(|y| block(x, y, z)));
+            }
+        }
+
+        let emission = |state| registry.state(state).map_or(0, |info| info.light_emission);
+        let mut sources = Vec::new();
+        for (index, section) in chunk.sections().iter().enumerate() {
+            if !section.blocks().palette().iter().any(|state| emission(*state) > 0) {
+                continue;
+            }
+            for y in 0..SECTION_SIZE {
+                for z in 0..SECTION_SIZE {
+                    for x in 0..SECTION_SIZE {
+                        let level = emission(section.get_block(x, y, z));
+                        if level > 0 {
+                            sources.push((block(x, min_y + (index * SECTION_SIZE + y) as i32, z), level));
+                        }
+                    }
+                }
+            }
+        }
+        for (source, level) in sources {
+            self.set_light(LightKind::Block, source, level);
+            emitters.push_back(source);
+        }
+    }
+
+    /// Queues the blocks along the sides of a chunk which are brighter than their neighbour across.
+    fn seed_borders(&mut self, position: ChunkPos, sky: &mut VecDeque<BlockPos>, emitters: &mut VecDeque<BlockPos>) {
+        let chunk = &self.chunks[&position];
+        let (light_sections, bottom) = (chunk.sections().len() + 2, chunk.min_y() - SECTION_SIZE as i32);
+        for (dx, dz) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+            let Some(neighbour) = self.chunks.get(&ChunkPos::new(position.x + dx, position.z + dz)) else { continue };
+            for (kind, queue) in [(LightKind::Sky, &mut *sky), (LightKind::Block, &mut *emitters)] {
+                for section in 0..light_sections {
+                    let (inner, outer) = (&chunk.light().sections(kind)[section], &neighbour.light().sections(kind)[section]);
+                    // Nothing flows between sections which are evenly lit.
+                    if let (LightSection::Uniform(inner), LightSection::Uniform(outer)) = (inner, outer) {
+                        if inner.abs_diff(*outer) <= 1 {
+                            continue;
+                        }
+                    }
+                    for along in 0..SECTION_SIZE {
+                        let (x, z) = match (dx, dz) {
+                            (-1, _) => (0, along),
+                            (1, _) => (SECTION_SIZE - 1, along),
+                            (_, -1) => (along, 0),
+                            _ => (along, SECTION_SIZE - 1),
+                        };
+                        let (outer_x, outer_z) = ((x as i32 + dx).rem_euclid(16) as usize, (z as i32 + dz).rem_euclid(16) as usize);
+                        for y in 0..SECTION_SIZE {
+                            let (level, across) = (inner.get(ChunkSection::index(x, y, z)), outer.get(ChunkSection::index(outer_x, y, outer_z)));
+                            let inside = BlockPos::new(position.x * 16 + x as i32, bottom + (section * SECTION_SIZE + y) as i32, position.z * 16 + z as i32);
+                            if across > level + 1 {
+                                queue.push_back(offset(inside, (dx, 0, dz)));
+                            } else if level > across + 1 {
+                                queue.push_back(inside);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use std::io::{self, Read, Write};
+use anyhow::bail;
+use dolls_core::datatype::{Decode, Encode, VarInt};
+use crate::prelude::PalettedContainer;
+
+/// Bits needed to tell `entries` values apart, 0 for a single one.
+pub const fn bits_for(entries: usize) -> u32 {
+    usize::BITS - entries.saturating_sub(1).leading_zeros()
+}
+
+/// Reads `count` values of `bits` bits each from longs, low bits first. Values do not span longs.
+pub fn unpack_indices(data: &[i64], bits: u32, count: usize) -> anyhow::Result<Vec<u16>> {
+    if !(1..=16).contains(&bits) {
+        bail!("Unsupported entry size of {} bits", bits);
+    }
+    let per_long = (64 / bits) as usize;
+    let expected = count.div_ceil(per_long);
+    if data.len() != expected {
+        bail!("Expected {} longs for {} entries of {} bits, got {}", expected, count, bits, data.len());
+    }
+    let mask = (1u64 << bits) - 1;
+    Ok((0..count)
+        .map(|index| {
+            let long = data[index / per_long] as u64;
+            ((long >> ((index % per_long) as u32 * bits)) & mask) as u16
+        })
+        .collect())
+}
+
+/// Writes values of `bits` bits each into longs, low bits first. Values do not span longs.
+pub fn pack_indices(values: &[u16], bits: u32) -> Vec<i64> {
+    let per_long = (64 / bits) as usize;
+    values.chunks(per_long)
+        .map(|values| values.iter().enumerate()
+            .fold(0u64, |long, (index, value)| long | (*value as u64) << (index as u32 * bits)) as i64)
+        .collect()
+}
+
+/// How a paletted container is laid out on the wire.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum PaletteFormat {
+    /// One global id and no data.
+    Single,
+    /// A palette of global ids and indices of the given size into it.
+    Indirect(u32),
+    /// Global ids of the given size, without a palette.
+    Direct(u32),
+}
+
+impl PaletteFormat {
+    /// The bits per entry byte in front of the container.
+    pub const fn bits(self) -> u32 {
+        match self {
+            PaletteFormat::Single => 0,
+            PaletteFormat::Indirect(bits) | PaletteFormat::Direct(bits) => bits,
+        }
+    }
+}
+
+/// Which [`PaletteFormat`] a kind of container uses for how many distinct values, mirroring the client.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct PaletteStrategy {
+    /// Smallest size of palette indices, smaller sizes are rounded up.
+    pub min_bits: u32,
+    /// Beyond this the palette is left out and global ids are sent.
+    pub max_indirect_bits: u32,
+    pub global_bits: u32,
+}
+
+impl PaletteStrategy {
+    /// Block states of a section, 1.21.1 has 26684 of them.
+    pub const BLOCKS: PaletteStrategy = PaletteStrategy { min_bits: 4, max_indirect_bits: 8, global_bits: 15 };
+
+    /// Biomes of a section, for a biome registry of `biome_count` entries.
+    pub const fn biomes(biome_count: usize) -> Self {
+        let global_bits = bits_for(biome_count);
+        Self { min_bits: 1, max_indirect_bits: 3, global_bits: if global_bits == 0 { 1 } else { global_bits } }
+    }
+
+    /// The format for a palette of `palette_len` entries.
+    pub fn format(&self, palette_len: usize) -> PaletteFormat {
+        match bits_for(palette_len) {
+            0 => PaletteFormat::Single,
+            bits if bits <= self.max_indirect_bits => PaletteFormat::Indirect(bits.max(self.min_bits)),
+            _ => PaletteFormat::Direct(self.global_bits),
+        }
+    }
+
+    /// The format announced by a bits per entry byte, direct containers always use `global_bits`.
+    pub fn format_of_bits(&self, bits: u32) -> PaletteFormat {
+        match bits {
+            0 => PaletteFormat::Single,
+            bits if bits <= self.max_indirect_bits => PaletteFormat::Indirect(bits.max(self.min_bits)),
+            _ => PaletteFormat::Direct(self.global_bits),
+        }
+    }
+}
+
+fn invalid(err: impl ToString) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err.to_string())
+}
+
+/// Writes a container as the protocol's bits per entry, palette and packed longs.
+pub fn encode_paletted<T: Copy + Eq>(
+    writer: &mut impl Write,
+    container: &PalettedContainer<T>,
+    strategy: &PaletteStrategy,
+    global_id: impl Fn(T) -> u32,
+) -> io::Result<()> {
+    let palette = container.palette();
+    let format = match container.indices() {
+        Some(_) => strategy.format(palette.len()),
+        None => PaletteFormat::Single,
+    };
+    (format.bits() as u8).encode(writer)?;
+    match (format, container.indices()) {
+        (PaletteFormat::Indirect(bits), Some(indices)) => {
+            VarInt(palette.len() as i32).encode(writer)?;
+            for entry in palette {
+                VarInt(global_id(*entry) as i32).encode(writer)?;
+            }
+            pack_indices(indices, bits).encode(writer)
+        }
+        (PaletteFormat::Direct(bits), _) => {
+            let ids = container.iter().map(|value| global_id(value) as u16).collect::<Vec<_>>();
+            pack_indices(&ids, bits).encode(writer)
+        }
+        _ => {
+            VarInt(global_id(container.get(0)) as i32).encode(writer)?;
+            Vec::<i64>::new().encode(writer)
+        }
+    }
+}
+
+/// Reads a container of `size` entries written by [`encode_paletted`] or the vanilla server.
+pub fn decode_paletted<T: Copy + Eq>(
+    reader: &mut impl Read,
+    size: usize,
+    strategy: &PaletteStrategy,
+    from_global: impl Fn(u32) -> io::Result<T>,
+) -> io::Result<PalettedContainer<T>> {
+    let read_global = |reader: &mut _| {
+        let id = VarInt::decode(reader)?.0;
+        from_global(u32::try_from(id).map_err(|_| invalid(format!("Negative palette entry {}", id)))?)
+    };
+    match strategy.format_of_bits(u8::decode(reader)? as u32) {
+        PaletteFormat::Single => {
+            let value = read_global(reader)?;
+            // Vanilla sends an empty array, skip whatever is there.
+            Vec::<i64>::decode(reader)?;
+            Ok(PalettedContainer::filled(size, value))
+        }
+        PaletteFormat::Indirect(bits) => {
+            let length = VarInt::decode(reader)?.0;
+            if !(1..=1 << bits).contains(&length) {
+                return Err(invalid(format!("Palette of {} entries for {} bits", length, bits)));
+            }
+            let palette = (0..length).map(|_| read_global(reader)).collect::<io::Result<Vec<_>>>()?;
+            let indices = unpack_indices(&Vec::<i64>::decode(reader)?, bits, size).map_err(invalid)?;
+            PalettedContainer::from_indices(palette, indices).map_err(invalid)
+        }
+        PaletteFormat::Direct(bits) => {
+            let ids = unpack_indices(&Vec::<i64>::decode(reader)?, bits, size).map_err(invalid)?;
+            let values = ids.into_iter().map(|id| from_global(id as u32)).collect::<io::Result<Vec<_>>>()?;
+            Ok(PalettedContainer::from_values(&values))
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use anyhow::Context;
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use once_cell::sync::Lazy;
+use dolls_core::datatype::Uuid;
+use dolls_core::nbt::NbtCompound;
+
+/// `playerdata/<uuid>.dat` files of a world, gzipped NBT like vanilla's. Keys Dolls does not know
+/// are kept when a file is updated.
+#[derive(Debug, Clone)]
+pub struct PlayerDataStorage {
+    directory: PathBuf,
+}
+
+impl PlayerDataStorage {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self { directory: directory.into() }
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    pub fn path(&self, uuid: Uuid) -> PathBuf {
+        self.directory.join(format!("{}.dat", uuid.hyphenated()))
+    }
+
+    /// The player's data, `None` for players who never joined.
+    pub fn load(&self, uuid: Uuid) -> anyhow::Result<Option<NbtCompound>> {
+        let path = self.path(uuid);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let file = File::open(&path).with_context(|| format!("Failed to open {}", path.display()))?;
+        let (_, data) = NbtCompound::read_named(&mut GzDecoder::new(BufReader::new(file)))
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Ok(Some(data))
+    }
+
+    pub fn save(&self, uuid: Uuid, data: &NbtCompound) -> anyhow::Result<()> {
+        fs::create_dir_all(&self.directory).with_context(|| format!("Failed to create {}", self.directory.display()))?;
+        let path = self.path(uuid);
+        // Write next to the old file first so a crash cannot leave a truncated file behind.
+        let temporary = path.with_extension("dat_new");
+        {
+            let file = File::create(&temporary).with_context(|| format!("Failed to create {}", temporary.display()))?;
+            let mut encoder = GzEncoder::new(BufWriter::new(file), Compression::default());
+            data.write_named(&mut encoder, "")?;
+            encoder.finish()?;
+        }
+        fs::rename(&temporary, &path).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Changes part of a player's data and saves it.
+    pub fn update(&self, uuid: Uuid, update: impl FnOnce(&mut NbtCompound)) -> anyhow::Result<()> {
+        let mut data = self.load(uuid)?.unwrap_or_default();
+        update(&mut data);
+        self.save(uuid, &data)
+    }
+}
+
+static PLAYER_DATA: Lazy<RwLock<PlayerDataStorage>> = Lazy::new(|| RwLock::new(PlayerDataStorage::new("world/playerdata")));
+
+/// Player data of the running server's world, pointed at its directory at startup.
+pub fn player_data() -> &'static RwLock<PlayerDataStorage> {
+    &PLAYER_DATA
+}
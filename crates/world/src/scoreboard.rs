@@ -0,0 +1,600 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+use std::sync::RwLock;
+use anyhow::{anyhow, bail, Context};
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use once_cell::sync::Lazy;
+use dolls_core::nbt::{NbtCompound, NbtTag};
+use dolls_core::text::{Style, TextComponent};
+use crate::level::DATA_VERSION;
+
+/// Criteria an objective can be created with. Only `dummy` and `trigger` scores are left alone by
+/// the server, the others are kept up to date by it.
+pub const CRITERIA: &[&str] = &["dummy", "trigger", "deathCount", "playerKillCount", "totalKillCount", "health", "xp", "level",
+    "food", "air", "armor"];
+
+/// Criteria whose scores mirror a player's state and cannot be changed by commands.
+const READ_ONLY_CRITERIA: &[&str] = &["health", "xp", "level", "food", "air", "armor"];
+
+/// Names of the 16 chat colors, in the order of their ids.
+pub const COLOR_NAMES: [&str; 16] = ["black", "dark_blue", "dark_green", "dark_aqua", "dark_red", "dark_purple", "gold", "gray",
+    "dark_gray", "blue", "green", "aqua", "red", "light_purple", "yellow", "white"];
+
+/// How the client draws the scores of an objective in the player list.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum RenderType {
+    #[default]
+    Integer,
+    Hearts,
+}
+
+impl RenderType {
+    pub const fn id(self) -> i32 {
+        self as i32
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RenderType::Integer => "integer",
+            RenderType::Hearts => "hearts",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [RenderType::Integer, RenderType::Hearts].into_iter().find(|render_type| render_type.name() == name)
+    }
+}
+
+/// How the client shows score values, in place of the plain number.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NumberFormat {
+    /// Hides the value.
+    Blank,
+    /// The number in a style.
+    Styled(Style),
+    /// A fixed text instead of the number.
+    Fixed(TextComponent),
+}
+
+impl NumberFormat {
+    pub const fn id(&self) -> i32 {
+        match self {
+            NumberFormat::Blank => 0,
+            NumberFormat::Styled(_) => 1,
+            NumberFormat::Fixed(_) => 2,
+        }
+    }
+
+    fn to_nbt(&self) -> NbtCompound {
+        match self {
+            NumberFormat::Blank => NbtCompound::new().with("type", "minecraft:blank"),
+            NumberFormat::Styled(style) => NbtCompound::new().with("type", "minecraft:styled").with("style", style.to_nbt()),
+            NumberFormat::Fixed(value) => NbtCompound::new().with("type", "minecraft:fixed").with("value", value.to_nbt()),
+        }
+    }
+
+    fn from_nbt(compound: &NbtCompound) -> anyhow::Result<Self> {
+        Ok(match compound.get_str("type").unwrap_or_default().trim_start_matches("minecraft:") {
+            "blank" => NumberFormat::Blank,
+            "styled" => NumberFormat::Styled(Style::from_nbt(compound.get_compound("style").unwrap_or(&NbtCompound::new()))?),
+            "fixed" => NumberFormat::Fixed(TextComponent::from_nbt(compound.get("value").ok_or_else(|| anyhow!("Fixed number format without a value"))?)?),
+            other => bail!("Unknown number format {}", other),
+        })
+    }
+}
+
+/// Where the client shows an objective.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub enum DisplaySlot {
+    List,
+    Sidebar,
+    BelowName,
+    /// The sidebar of members of teams with the color of this id.
+    TeamSidebar(u8),
+}
+
+impl DisplaySlot {
+    pub fn all() -> impl Iterator<Item = DisplaySlot> {
+        [DisplaySlot::List, DisplaySlot::Sidebar, DisplaySlot::BelowName].into_iter()
+            .chain((0..COLOR_NAMES.len() as u8).map(DisplaySlot::TeamSidebar))
+    }
+
+    pub fn id(self) -> i32 {
+        match self {
+            DisplaySlot::List => 0,
+            DisplaySlot::Sidebar => 1,
+            DisplaySlot::BelowName => 2,
+            DisplaySlot::TeamSidebar(color) => 3 + color as i32,
+        }
+    }
+
+    /// The name used by commands and `scoreboard.dat`, e.g. `sidebar.team.red`.
+    pub fn name(self) -> String {
+        match self {
+            DisplaySlot::List => "list".to_string(),
+            DisplaySlot::Sidebar => "sidebar".to_string(),
+            DisplaySlot::BelowName => "below_name".to_string(),
+            DisplaySlot::TeamSidebar(color) => format!("sidebar.team.{}", COLOR_NAMES[color as usize]),
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::all().find(|slot| slot.name() == name)
+    }
+}
+
+/// Whose name tags, or death messages, the members of a team see.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum Visibility {
+    #[default]
+    Always,
+    Never,
+    HideForOtherTeams,
+    HideForOwnTeam,
+}
+
+impl Visibility {
+    pub const ALL: [Visibility; 4] = [Visibility::Always, Visibility::Never, Visibility::HideForOtherTeams, Visibility::HideForOwnTeam];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Visibility::Always => "always",
+            Visibility::Never => "never",
+            Visibility::HideForOtherTeams => "hideForOtherTeams",
+            Visibility::HideForOwnTeam => "hideForOwnTeam",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|visibility| visibility.name() == name)
+    }
+}
+
+/// Which entities the members of a team push.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum CollisionRule {
+    #[default]
+    Always,
+    Never,
+    PushOtherTeams,
+    PushOwnTeam,
+}
+
+impl CollisionRule {
+    pub const ALL: [CollisionRule; 4] = [CollisionRule::Always, CollisionRule::Never, CollisionRule::PushOtherTeams, CollisionRule::PushOwnTeam];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CollisionRule::Always => "always",
+            CollisionRule::Never => "never",
+            CollisionRule::PushOtherTeams => "pushOtherTeams",
+            CollisionRule::PushOwnTeam => "pushOwnTeam",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|rule| rule.name() == name)
+    }
+}
+
+/// A group of score holders sharing a name color, prefix and suffix, and rules for how they see
+/// and push each other.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Team {
+    pub name: String,
+    pub display_name: TextComponent,
+    /// Id of one of the [`COLOR_NAMES`], `None` leaves names uncolored.
+    pub color: Option<u8>,
+    pub prefix: TextComponent,
+    pub suffix: TextComponent,
+    pub friendly_fire: bool,
+    pub see_friendly_invisibles: bool,
+    pub name_tag_visibility: Visibility,
+    pub death_message_visibility: Visibility,
+    pub collision_rule: CollisionRule,
+    members: BTreeSet<String>,
+}
+
+impl Team {
+    pub fn new(name: impl Into<String>) -> Self {
+        let name = name.into();
+        Self {
+            display_name: TextComponent::text(name.clone()),
+            name,
+            color: None,
+            prefix: TextComponent::default(),
+            suffix: TextComponent::default(),
+            friendly_fire: true,
+            see_friendly_invisibles: true,
+            name_tag_visibility: Visibility::Always,
+            death_message_visibility: Visibility::Always,
+            collision_rule: CollisionRule::Always,
+            members: BTreeSet::new(),
+        }
+    }
+
+    pub fn members(&self) -> impl Iterator<Item = &str> {
+        self.members.iter().map(String::as_str)
+    }
+
+    /// A member's name in the team's color between its prefix and suffix, as shown in chat and the tab list.
+    pub fn format_name(&self, name: &str) -> TextComponent {
+        let mut name = TextComponent::text(name);
+        if let Some(color) = self.color {
+            name = name.color(COLOR_NAMES[color as usize]);
+        }
+        TextComponent::text("").append(self.prefix.clone()).append(name).append(self.suffix.clone())
+    }
+}
+
+/// Color names of teams and team sidebars, `None` for `reset`.
+pub fn color_from_name(name: &str) -> Option<Option<u8>> {
+    match name {
+        "reset" => Some(None),
+        name => COLOR_NAMES.iter().position(|color| *color == name).map(|color| Some(color as u8)),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Objective {
+    pub name: String,
+    /// One of [`CRITERIA`].
+    pub criterion: String,
+    pub display_name: TextComponent,
+    pub render_type: RenderType,
+    /// Format of scores which have none of their own, `None` shows plain numbers.
+    pub number_format: Option<NumberFormat>,
+}
+
+impl Objective {
+    /// A `dummy` objective showing its name.
+    pub fn new(name: impl Into<String>) -> Self {
+        let name = name.into();
+        Self {
+            display_name: TextComponent::text(name.clone()),
+            name,
+            criterion: "dummy".to_string(),
+            render_type: RenderType::Integer,
+            number_format: None,
+        }
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        READ_ONLY_CRITERIA.contains(&self.criterion.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Score {
+    pub value: i32,
+    /// Shown instead of the holder's name.
+    pub display_name: Option<TextComponent>,
+    pub number_format: Option<NumberFormat>,
+}
+
+/// Objectives, the scores of players and other named holders, which objectives are displayed and
+/// the teams holders are in. Kept in `data/scoreboard.dat` like vanilla.
+#[derive(Debug, Clone, Default)]
+pub struct Scoreboard {
+    objectives: BTreeMap<String, Objective>,
+    /// Scores by holder, then by objective.
+    scores: BTreeMap<String, BTreeMap<String, Score>>,
+    display_slots: BTreeMap<DisplaySlot, String>,
+    teams: BTreeMap<String, Team>,
+    /// Team of each holder in one.
+    holder_teams: BTreeMap<String, String>,
+}
+
+impl Scoreboard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn objective(&self, name: &str) -> Option<&Objective> {
+        self.objectives.get(name)
+    }
+
+    pub fn objectives(&self) -> impl Iterator<Item = &Objective> {
+        self.objectives.values()
+    }
+
+    pub fn add_objective(&mut self, objective: Objective) -> anyhow::Result<()> {
+        if self.objectives.contains_key(&objective.name) {
+            bail!("An objective already exists by the name {}", objective.name);
+        }
+        if !CRITERIA.contains(&objective.criterion.as_str()) {
+            bail!("Invalid criterion {}", objective.criterion);
+        }
+        self.objectives.insert(objective.name.clone(), objective);
+        Ok(())
+    }
+
+    /// Changes an objective's display name, render type or number format.
+    pub fn modify_objective(&mut self, name: &str, modify: impl FnOnce(&mut Objective)) -> anyhow::Result<Objective> {
+        let objective = self.objectives.get_mut(name).ok_or_else(|| anyhow!("Unknown scoreboard objective {}", name))?;
+        modify(objective);
+        Ok(objective.clone())
+    }
+
+    /// Removes an objective with its scores, and from the slots it was displayed in.
+    pub fn remove_objective(&mut self, name: &str) -> Option<Objective> {
+        let objective = self.objectives.remove(name)?;
+        self.display_slots.retain(|_, displayed| displayed != name);
+        self.scores.retain(|_, scores| {
+            scores.remove(name);
+            !scores.is_empty()
+        });
+        Some(objective)
+    }
+
+    pub fn displayed(&self, slot: DisplaySlot) -> Option<&Objective> {
+        self.display_slots.get(&slot).and_then(|name| self.objectives.get(name))
+    }
+
+    pub fn display_slots(&self) -> impl Iterator<Item = (DisplaySlot, &str)> {
+        self.display_slots.iter().map(|(slot, name)| (*slot, name.as_str()))
+    }
+
+    /// Shows an objective in a slot, `None` clears the slot.
+    pub fn set_display(&mut self, slot: DisplaySlot, objective: Option<&str>) -> anyhow::Result<()> {
+        match objective {
+            Some(name) if !self.objectives.contains_key(name) => bail!("Unknown scoreboard objective {}", name),
+            Some(name) => self.display_slots.insert(slot, name.to_string()),
+            None => self.display_slots.remove(&slot),
+        };
+        Ok(())
+    }
+
+    /// Names of everything with a score, players or not.
+    pub fn holders(&self) -> impl Iterator<Item = &str> {
+        self.scores.keys().map(String::as_str)
+    }
+
+    pub fn score(&self, holder: &str, objective: &str) -> Option<&Score> {
+        self.scores.get(holder).and_then(|scores| scores.get(objective))
+    }
+
+    /// Every score of a holder, by objective.
+    pub fn scores(&self, holder: &str) -> impl Iterator<Item = (&str, &Score)> {
+        self.scores.get(holder).into_iter().flatten().map(|(objective, score)| (objective.as_str(), score))
+    }
+
+    /// Changes a holder's score, starting from zero when it has none, and returns it.
+    pub fn update_score(&mut self, holder: &str, objective: &str, update: impl FnOnce(&mut Score)) -> anyhow::Result<Score> {
+        if !self.objectives.contains_key(objective) {
+            bail!("Unknown scoreboard objective {}", objective);
+        }
+        let score = self.scores.entry(holder.to_string()).or_default().entry(objective.to_string()).or_default();
+        update(score);
+        Ok(score.clone())
+    }
+
+    /// Removes a holder's score for one objective or all of them, telling whether it had any.
+    pub fn reset_score(&mut self, holder: &str, objective: Option<&str>) -> bool {
+        let Some(scores) = self.scores.get_mut(holder) else { return false };
+        let removed = match objective {
+            Some(objective) => scores.remove(objective).is_some(),
+            None => {
+                scores.clear();
+                true
+            }
+        };
+        if scores.is_empty() {
+            self.scores.remove(holder);
+        }
+        removed
+    }
+
+    pub fn team(&self, name: &str) -> Option<&Team> {
+        self.teams.get(name)
+    }
+
+    pub fn teams(&self) -> impl Iterator<Item = &Team> {
+        self.teams.values()
+    }
+
+    /// The team a holder is a member of.
+    pub fn team_of(&self, holder: &str) -> Option<&Team> {
+        self.holder_teams.get(holder).and_then(|name| self.teams.get(name))
+    }
+
+    /// Adds a team, its members are ignored, see [`Scoreboard::join_team`].
+    pub fn add_team(&mut self, mut team: Team) -> anyhow::Result<()> {
+        if self.teams.contains_key(&team.name) {
+            bail!("A team already exists by the name {}", team.name);
+        }
+        team.members.clear();
+        self.teams.insert(team.name.clone(), team);
+        Ok(())
+    }
+
+    /// Changes a team's settings, its members stay as they are.
+    pub fn modify_team(&mut self, name: &str, modify: impl FnOnce(&mut Team)) -> anyhow::Result<Team> {
+        let team = self.teams.get_mut(name).ok_or_else(|| anyhow!("Unknown team {}", name))?;
+        let members = team.members.clone();
+        modify(team);
+        team.members = members;
+        Ok(team.clone())
+    }
+
+    /// Removes a team, its members are no longer in any team.
+    pub fn remove_team(&mut self, name: &str) -> Option<Team> {
+        let team = self.teams.remove(name)?;
+        for member in &team.members {
+            self.holder_teams.remove(member);
+        }
+        Some(team)
+    }
+
+    /// Makes a holder a member of a team, leaving the team it was in. Returns the team it left.
+    pub fn join_team(&mut self, holder: &str, team: &str) -> anyhow::Result<Option<String>> {
+        if !self.teams.contains_key(team) {
+            bail!("Unknown team {}", team);
+        }
+        let left = self.leave_team(holder);
+        self.teams.get_mut(team).expect("Team exists").members.insert(holder.to_string());
+        self.holder_teams.insert(holder.to_string(), team.to_string());
+        Ok(left)
+    }
+
+    /// Removes a holder from its team, returning the name of the team it was in.
+    pub fn leave_team(&mut self, holder: &str) -> Option<String> {
+        let team = self.holder_teams.remove(holder)?;
+        if let Some(members) = self.teams.get_mut(&team).map(|team| &mut team.members) {
+            members.remove(holder);
+        }
+        Some(team)
+    }
+
+    /// Reads `scoreboard.dat`, an empty scoreboard when the world has none yet.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::new());
+        }
+        let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        let (_, root) = NbtCompound::read_named(&mut GzDecoder::new(BufReader::new(file)))
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let data = root.get_compound("data").ok_or_else(|| anyhow!("{} has no data compound", path.display()))?;
+        Self::from_nbt(data).with_context(|| format!("Malformed {}", path.display()))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let temporary = path.with_extension("dat_new");
+        {
+            let file = File::create(&temporary).with_context(|| format!("Failed to create {}", temporary.display()))?;
+            let mut encoder = GzEncoder::new(BufWriter::new(file), Compression::default());
+            NbtCompound::new().with("data", self.to_nbt()).with("DataVersion", DATA_VERSION).write_named(&mut encoder, "")?;
+            encoder.finish()?;
+        }
+        fs::rename(&temporary, path).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    fn from_nbt(data: &NbtCompound) -> anyhow::Result<Self> {
+        let compounds = |key| data.get_list(key).unwrap_or_default().iter().filter_map(NbtTag::as_compound);
+        let text = |json: Option<&str>| json.map(|json| TextComponent::from_json(json).unwrap_or_else(|_| TextComponent::text(json)));
+        let format = |compound: &NbtCompound| compound.get_compound("format").map(NumberFormat::from_nbt).transpose();
+
+        let mut scoreboard = Self::new();
+        for compound in compounds("Objectives") {
+            let name = compound.get_str("Name").ok_or_else(|| anyhow!("Objective without a name"))?.to_string();
+            let objective = Objective {
+                criterion: compound.get_str("CriteriaName").unwrap_or("dummy").to_string(),
+                display_name: text(compound.get_str("DisplayName")).unwrap_or_else(|| TextComponent::text(name.clone())),
+                render_type: compound.get_str("RenderType").and_then(RenderType::from_name).unwrap_or_default(),
+                number_format: format(compound)?,
+                name,
+            };
+            scoreboard.objectives.insert(objective.name.clone(), objective);
+        }
+        for compound in compounds("PlayerScores") {
+            let (Some(holder), Some(objective)) = (compound.get_str("Name"), compound.get_str("Objective")) else { continue };
+            if !scoreboard.objectives.contains_key(objective) {
+                continue;
+            }
+            let score = Score {
+                value: compound.get_i64("Score").unwrap_or(0) as i32,
+                display_name: text(compound.get_str("display")),
+                number_format: format(compound)?,
+            };
+            scoreboard.scores.entry(holder.to_string()).or_default().insert(objective.to_string(), score);
+        }
+        for (slot, name) in data.get_compound("DisplaySlots").into_iter().flat_map(NbtCompound::iter) {
+            if let (Some(slot), Some(name)) = (DisplaySlot::from_name(slot), name.as_str()) {
+                if scoreboard.objectives.contains_key(name) {
+                    scoreboard.display_slots.insert(slot, name.to_string());
+                }
+            }
+        }
+        for compound in compounds("Teams") {
+            let name = compound.get_str("Name").ok_or_else(|| anyhow!("Team without a name"))?;
+            let default = Team::new(name);
+            let visibility = |key| compound.get_str(key).and_then(Visibility::from_name).unwrap_or_default();
+            let team = Team {
+                display_name: text(compound.get_str("DisplayName")).unwrap_or(default.display_name),
+                color: compound.get_str("TeamColor").and_then(color_from_name).unwrap_or(default.color),
+                prefix: text(compound.get_str("MemberNamePrefix")).unwrap_or(default.prefix),
+                suffix: text(compound.get_str("MemberNameSuffix")).unwrap_or(default.suffix),
+                friendly_fire: compound.get_bool("AllowFriendlyFire").unwrap_or(default.friendly_fire),
+                see_friendly_invisibles: compound.get_bool("SeeFriendlyInvisibles").unwrap_or(default.see_friendly_invisibles),
+                name_tag_visibility: visibility("NameTagVisibility"),
+                death_message_visibility: visibility("DeathMessageVisibility"),
+                collision_rule: compound.get_str("CollisionRule").and_then(CollisionRule::from_name).unwrap_or_default(),
+                ..default
+            };
+            scoreboard.add_team(team)?;
+            for member in compound.get_list("Players").unwrap_or_default().iter().filter_map(NbtTag::as_str) {
+                scoreboard.join_team(member, name)?;
+            }
+        }
+        Ok(scoreboard)
+    }
+
+    fn to_nbt(&self) -> NbtCompound {
+        let objectives = self.objectives.values().map(|objective| {
+            let mut compound = NbtCompound::new()
+                .with("Name", objective.name.as_str())
+                .with("CriteriaName", objective.criterion.as_str())
+                .with("DisplayName", objective.display_name.to_json())
+                .with("RenderType", objective.render_type.name())
+                .with("display_auto_update", false);
+            if let Some(format) = &objective.number_format {
+                compound.insert("format", format.to_nbt());
+            }
+            NbtTag::Compound(compound)
+        }).collect::<Vec<_>>();
+        let scores = self.scores.iter()
+            .flat_map(|(holder, scores)| scores.iter().map(move |(objective, score)| (holder, objective, score)))
+            .map(|(holder, objective, score)| {
+                let mut compound = NbtCompound::new()
+                    .with("Name", holder.as_str())
+                    .with("Objective", objective.as_str())
+                    .with("Score", score.value)
+                    .with("Locked", false);
+                if let Some(display_name) = &score.display_name {
+                    compound.insert("display", display_name.to_json());
+                }
+                if let Some(format) = &score.number_format {
+                    compound.insert("format", format.to_nbt());
+                }
+                NbtTag::Compound(compound)
+            }).collect::<Vec<_>>();
+        let mut display_slots = NbtCompound::new();
+        for (slot, name) in &self.display_slots {
+            display_slots.insert(slot.name(), name.as_str());
+        }
+        let teams = self.teams.values().map(|team| NbtTag::Compound(NbtCompound::new()
+            .with("Name", team.name.as_str())
+            .with("DisplayName", team.display_name.to_json())
+            .with("TeamColor", team.color.map_or("reset", |color| COLOR_NAMES[color as usize]))
+            .with("AllowFriendlyFire", team.friendly_fire)
+            .with("SeeFriendlyInvisibles", team.see_friendly_invisibles)
+            .with("MemberNamePrefix", team.prefix.to_json())
+            .with("MemberNameSuffix", team.suffix.to_json())
+            .with("NameTagVisibility", team.name_tag_visibility.name())
+            .with("DeathMessageVisibility", team.death_message_visibility.name())
+            .with("CollisionRule", team.collision_rule.name())
+            .with("Players", team.members().map(NbtTag::from).collect::<Vec<_>>())
+        )).collect::<Vec<_>>();
+        NbtCompound::new()
+            .with("Objectives", objectives)
+            .with("PlayerScores", scores)
+            .with("DisplaySlots", display_slots)
+            .with("Teams", teams)
+    }
+}
+
+static SCOREBOARD: Lazy<RwLock<Scoreboard>> = Lazy::new(|| RwLock::new(Scoreboard::new()));
+
+/// The scoreboard of the running server's world, replaced with the saved one at startup.
+pub fn scoreboard() -> &'static RwLock<Scoreboard> {
+    &SCOREBOARD
+}
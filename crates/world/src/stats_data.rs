@@ -0,0 +1,76 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use anyhow::Context;
+use once_cell::sync::Lazy;
+use serde_json::{Map, Value};
+use dolls_core::datatype::{Identifier, Uuid};
+use crate::level::DATA_VERSION;
+
+/// Values of a player's statistics by stat type, e.g. `minecraft:custom`, and what they count.
+pub type PlayerStatistics = BTreeMap<Identifier, BTreeMap<Identifier, i32>>;
+
+/// `stats/<uuid>.json` files of a world, in vanilla's format.
+#[derive(Debug, Clone)]
+pub struct StatsStorage {
+    directory: PathBuf,
+}
+
+impl StatsStorage {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self { directory: directory.into() }
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    pub fn path(&self, uuid: Uuid) -> PathBuf {
+        self.directory.join(format!("{}.json", uuid.hyphenated()))
+    }
+
+    /// The player's statistics, empty for players who never joined. Malformed entries are skipped.
+    pub fn load(&self, uuid: Uuid) -> anyhow::Result<PlayerStatistics> {
+        let path = self.path(uuid);
+        if !path.exists() {
+            return Ok(PlayerStatistics::new());
+        }
+        let content = fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let json: Value = serde_json::from_str(&content).with_context(|| format!("Malformed {}", path.display()))?;
+        let mut statistics = PlayerStatistics::new();
+        for (kind, values) in json.get("stats").and_then(Value::as_object).into_iter().flatten() {
+            let Ok(kind) = kind.parse::<Identifier>() else { continue };
+            let values = values.as_object().into_iter().flatten()
+                .filter_map(|(name, value)| Some((name.parse().ok()?, i32::try_from(value.as_i64()?).ok()?)))
+                .collect::<BTreeMap<_, _>>();
+            if !values.is_empty() {
+                statistics.insert(kind, values);
+            }
+        }
+        Ok(statistics)
+    }
+
+    pub fn save(&self, uuid: Uuid, statistics: &PlayerStatistics) -> anyhow::Result<()> {
+        fs::create_dir_all(&self.directory).with_context(|| format!("Failed to create {}", self.directory.display()))?;
+        let stats = statistics.iter()
+            .filter(|(_, values)| !values.is_empty())
+            .map(|(kind, values)| {
+                let values = values.iter().map(|(name, value)| (name.to_string(), Value::from(*value))).collect::<Map<_, _>>();
+                (kind.to_string(), Value::Object(values))
+            })
+            .collect::<Map<_, _>>();
+        let json = serde_json::json!({ "stats": stats, "DataVersion": DATA_VERSION });
+        let path = self.path(uuid);
+        let temporary = path.with_extension("json_new");
+        fs::write(&temporary, serde_json::to_string(&json)?).with_context(|| format!("Failed to write {}", temporary.display()))?;
+        fs::rename(&temporary, &path).with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+static STATS_DATA: Lazy<RwLock<StatsStorage>> = Lazy::new(|| RwLock::new(StatsStorage::new("world/stats")));
+
+/// Statistics of the running server's world, pointed at its directory at startup.
+pub fn stats_data() -> &'static RwLock<StatsStorage> {
+    &STATS_DATA
+}
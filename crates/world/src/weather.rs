@@ -0,0 +1,149 @@
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::ops::RangeInclusive;
+use dolls_core::nbt::NbtCompound;
+
+/// Ticks of clear weather before it starts raining, when nobody set it.
+pub const RAIN_DELAY: RangeInclusive<i32> = 12_000..=180_000;
+pub const RAIN_DURATION: RangeInclusive<i32> = 12_000..=24_000;
+pub const THUNDER_DELAY: RangeInclusive<i32> = 12_000..=180_000;
+pub const THUNDER_DURATION: RangeInclusive<i32> = 3_600..=15_600;
+/// How much the rain and thunder levels change per tick while fading.
+const LEVEL_STEP: f32 = 0.01;
+
+/// Rain and thunder with the ticks until they change, kept in `level.dat` like vanilla.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Weather {
+    pub raining: bool,
+    pub rain_time: i32,
+    pub thundering: bool,
+    pub thunder_time: i32,
+    /// Ticks of clear weather forced by `weather clear`, the cycle is paused while it counts down.
+    pub clear_time: i32,
+    /// How strong the rain and thunder look right now, from 0 to 1, fading toward the state.
+    rain_level: f32,
+    thunder_level: f32,
+}
+
+impl Default for Weather {
+    fn default() -> Self {
+        Self { raining: false, rain_time: 0, thundering: false, thunder_time: 0, clear_time: 0, rain_level: 0.0, thunder_level: 0.0 }
+    }
+}
+
+/// What changed during a tick of the weather, for telling clients.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct WeatherChange {
+    pub started_raining: bool,
+    pub stopped_raining: bool,
+    pub rain_level: Option<f32>,
+    pub thunder_level: Option<f32>,
+}
+
+impl Weather {
+    /// Reads the weather keys of a level's `Data`, missing ones keep their defaults.
+    pub fn from_nbt(data: &NbtCompound) -> Self {
+        let time = |key| data.get_i64(key).unwrap_or(0) as i32;
+        let raining = data.get_bool("raining").unwrap_or(false);
+        let thundering = data.get_bool("thundering").unwrap_or(false);
+        Self {
+            raining,
+            rain_time: time("rainTime"),
+            thundering,
+            thunder_time: time("thunderTime"),
+            clear_time: time("clearWeatherTime"),
+            rain_level: if raining { 1.0 } else { 0.0 },
+            thunder_level: if raining && thundering { 1.0 } else { 0.0 },
+        }
+    }
+
+    pub fn write_nbt(&self, data: &mut NbtCompound) {
+        data.insert("raining", self.raining)
+            .insert("rainTime", self.rain_time)
+            .insert("thundering", self.thundering)
+            .insert("thunderTime", self.thunder_time)
+            .insert("clearWeatherTime", self.clear_time);
+    }
+
+    pub fn rain_level(&self) -> f32 {
+        self.rain_level
+    }
+
+    pub fn thunder_level(&self) -> f32 {
+        self.thunder_level
+    }
+
+    /// Clear skies for `duration` ticks, a random time without one.
+    pub fn set_clear(&mut self, duration: Option<i32>) {
+        self.clear_time = duration.unwrap_or_else(|| random_in(RAIN_DELAY));
+        (self.raining, self.rain_time, self.thundering, self.thunder_time) = (false, 0, false, 0);
+    }
+
+    /// Rain for `duration` ticks, a random time without one.
+    pub fn set_rain(&mut self, duration: Option<i32>) {
+        let duration = duration.unwrap_or_else(|| random_in(RAIN_DURATION));
+        (self.clear_time, self.raining, self.rain_time, self.thundering, self.thunder_time) = (0, true, duration, false, duration);
+    }
+
+    /// Rain and thunder for `duration` ticks, a random time without one.
+    pub fn set_thunder(&mut self, duration: Option<i32>) {
+        let duration = duration.unwrap_or_else(|| random_in(THUNDER_DURATION));
+        (self.clear_time, self.raining, self.rain_time, self.thundering, self.thunder_time) = (0, true, duration, true, duration);
+    }
+
+    /// Advances the weather by a tick. Without `cycle` only the levels fade toward the current state.
+    pub fn tick(&mut self, cycle: bool) -> WeatherChange {
+        let was_raining = self.raining;
+        if cycle {
+            if self.clear_time > 0 {
+                self.clear_time -= 1;
+                self.thunder_time = if self.thundering { 0 } else { 1 };
+                self.rain_time = if self.raining { 0 } else { 1 };
+                self.raining = false;
+                self.thundering = false;
+            } else {
+                self.thunder_time = next_time(self.thunder_time, &mut self.thundering, THUNDER_DURATION, THUNDER_DELAY);
+                self.rain_time = next_time(self.rain_time, &mut self.raining, RAIN_DURATION, RAIN_DELAY);
+            }
+        }
+
+        let mut change = WeatherChange {
+            started_raining: self.raining && !was_raining,
+            stopped_raining: !self.raining && was_raining,
+            ..Default::default()
+        };
+        let thunder = fade(self.thunder_level, self.thundering);
+        if thunder != self.thunder_level {
+            self.thunder_level = thunder;
+            change.thunder_level = Some(thunder);
+        }
+        let rain = fade(self.rain_level, self.raining);
+        if rain != self.rain_level {
+            self.rain_level = rain;
+            change.rain_level = Some(rain);
+        }
+        change
+    }
+}
+
+/// Counts a rain or thunder timer down, flipping `active` when it runs out. A timer at zero picks
+/// the time until the next flip from `duration` or `delay`, like vanilla.
+fn next_time(time: i32, active: &mut bool, duration: RangeInclusive<i32>, delay: RangeInclusive<i32>) -> i32 {
+    if time > 0 {
+        if time == 1 {
+            *active = !*active;
+        }
+        return time - 1;
+    }
+    if *active { random_in(duration) } else { random_in(delay) }
+}
+
+fn fade(level: f32, toward: bool) -> f32 {
+    let target = if toward { 1.0 } else { 0.0 };
+    (level + (target - level).clamp(-LEVEL_STEP, LEVEL_STEP)).clamp(0.0, 1.0)
+}
+
+fn random_in(range: RangeInclusive<i32>) -> i32 {
+    let random = RandomState::new().hash_one(std::time::SystemTime::now());
+    range.start() + (random % (range.end() - range.start() + 1) as u64) as i32
+}
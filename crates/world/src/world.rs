@@ -0,0 +1,365 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::RwLock;
+use anyhow::{anyhow, Context};
+use once_cell::sync::Lazy;
+use dolls_core::datatype::{BlockPos, Identifier};
+use dolls_core::nbt::NbtCompound;
+use dolls_core::registry::registries;
+use crate::prelude::{blocks, entity_chunk_from_nbt, entity_chunk_to_nbt, BlockState, Chunk, ChunkPos, HeightmapKind, LightEngine, RegionStorage};
+
+/// Lowest block of the overworld.
+pub const OVERWORLD_MIN_Y: i32 = -64;
+/// Number of blocks from the bottom to the top of the overworld.
+pub const OVERWORLD_HEIGHT: u32 = 384;
+/// The nether and the end span from y 0 to 255.
+pub const NETHER_HEIGHT: u32 = 256;
+pub const END_HEIGHT: u32 = 256;
+/// Changes by [`World::set_blocks`] beyond which touched chunks are relit as a whole.
+const INCREMENTAL_LIGHT_LIMIT: usize = 512;
+
+/// The loaded chunks of one dimension.
+#[derive(Debug)]
+pub struct World {
+    min_y: i32,
+    height: u32,
+    chunks: HashMap<ChunkPos, Chunk>,
+    /// Where chunks are loaded from and saved to, worlds without one only live in memory.
+    storage: Option<RegionStorage>,
+    /// Chunks whose light changed since [`World::take_light_changes`].
+    light_changes: HashSet<ChunkPos>,
+    /// Where the entities of chunks are saved, the `entities` folder next to `region`.
+    entity_storage: Option<RegionStorage>,
+    /// Entities read with the chunks loaded since [`World::take_loaded_entities`].
+    loaded_entities: Vec<(ChunkPos, Vec<NbtCompound>)>,
+}
+
+impl Default for World {
+    fn default() -> Self {
+        Self::new(OVERWORLD_MIN_Y, OVERWORLD_HEIGHT)
+    }
+}
+
+impl World {
+    pub fn new(min_y: i32, height: u32) -> Self {
+        Self {
+            min_y,
+            height,
+            chunks: HashMap::new(),
+            storage: None,
+            light_changes: HashSet::new(),
+            entity_storage: None,
+            loaded_entities: Vec::new(),
+        }
+    }
+
+    /// A world persisted in `storage`.
+    pub fn with_storage(min_y: i32, height: u32, storage: RegionStorage) -> Self {
+        Self { storage: Some(storage), ..Self::new(min_y, height) }
+    }
+
+    /// Reads the entities of chunks from `storage` as they load, see [`World::take_loaded_entities`].
+    pub fn with_entity_storage(mut self, storage: RegionStorage) -> Self {
+        self.entity_storage = Some(storage);
+        self
+    }
+
+    pub fn storage(&self) -> Option<&RegionStorage> {
+        self.storage.as_ref()
+    }
+
+    pub fn entity_storage(&self) -> Option<&RegionStorage> {
+        self.entity_storage.as_ref()
+    }
+
+    pub fn min_y(&self) -> i32 {
+        self.min_y
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn chunk(&self, position: ChunkPos) -> Option<&Chunk> {
+        self.chunks.get(&position)
+    }
+
+    pub fn chunk_mut(&mut self, position: ChunkPos) -> Option<&mut Chunk> {
+        self.chunks.get_mut(&position)
+    }
+
+    /// The chunk at `position`, created empty with `biome` if it is not loaded.
+    pub fn get_or_create_chunk(&mut self, position: ChunkPos, biome: u16) -> &mut Chunk {
+        if !self.chunks.contains_key(&position) {
+            self.insert_chunk(Chunk::new(position, self.min_y, self.height, biome));
+        }
+        self.chunks.get_mut(&position).expect("Chunk was just created")
+    }
+
+    /// Adds a chunk, replacing the one loaded at its position, and lights it.
+    pub fn insert_chunk(&mut self, chunk: Chunk) -> Option<Chunk> {
+        let position = chunk.position;
+        let previous = self.chunks.insert(position, chunk);
+        let registry = blocks().read().unwrap();
+        LightEngine::new(&mut self.chunks, &registry, &mut self.light_changes).relight_chunks(&[position], previous.is_some());
+        previous
+    }
+
+    /// Computes the light of a loaded chunk and the chunks around it again, e.g. after its sections were replaced.
+    pub fn relight_chunk(&mut self, position: ChunkPos) {
+        let registry = blocks().read().unwrap();
+        LightEngine::new(&mut self.chunks, &registry, &mut self.light_changes).relight_chunks(&[position], true);
+    }
+
+    /// Chunks whose light changed since the last call, their viewers need an update.
+    pub fn take_light_changes(&mut self) -> Vec<ChunkPos> {
+        self.light_changes.drain().collect()
+    }
+
+    /// Removes a chunk without saving it.
+    pub fn remove_chunk(&mut self, position: ChunkPos) -> Option<Chunk> {
+        self.chunks.remove(&position)
+    }
+
+    /// Loads a chunk from storage unless it is loaded already, `false` if it was never saved.
+    pub fn load_chunk(&mut self, position: ChunkPos) -> anyhow::Result<bool> {
+        if self.chunks.contains_key(&position) {
+            return Ok(true);
+        }
+        let Some(storage) = &mut self.storage else { return Ok(false) };
+        match storage.load_chunk(position, self.min_y, self.height)? {
+            Some(chunk) => {
+                self.read_entities(position)?;
+                self.insert_chunk(chunk);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn read_entities(&mut self, position: ChunkPos) -> anyhow::Result<()> {
+        let Some(storage) = &mut self.entity_storage else { return Ok(()) };
+        let Some(nbt) = storage.read_chunk_nbt(position)? else { return Ok(()) };
+        let entities = entity_chunk_from_nbt(&nbt, position)
+            .with_context(|| format!("Invalid entities of chunk {} {} in {}", position.x, position.z, storage.directory().display()))?;
+        if !entities.is_empty() {
+            self.loaded_entities.push((position, entities));
+        }
+        Ok(())
+    }
+
+    /// Entities saved in the chunks loaded since the last call, for the caller to spawn. Until then they
+    /// are not in the world, and saving the entities of those chunks would lose them.
+    pub fn take_loaded_entities(&mut self) -> Vec<(ChunkPos, Vec<NbtCompound>)> {
+        std::mem::take(&mut self.loaded_entities)
+    }
+
+    /// Saves the entities of a chunk, chunks without any are only written to clear what was saved before.
+    pub fn save_entities(&mut self, position: ChunkPos, entities: Vec<NbtCompound>) -> anyhow::Result<()> {
+        let Some(storage) = &mut self.entity_storage else { return Ok(()) };
+        if entities.is_empty() && !storage.has_chunk(position)? {
+            return Ok(());
+        }
+        storage.write_chunk_nbt(position, &entity_chunk_to_nbt(position, entities))
+    }
+
+    /// Loads a chunk, or creates an empty one of [`default_biome`] if it was never saved. Empty chunks are
+    /// not dirty, they are only saved once changed.
+    pub fn load_or_create_chunk(&mut self, position: ChunkPos) -> anyhow::Result<&mut Chunk> {
+        if !self.load_chunk(position)? {
+            let mut chunk = Chunk::new(position, self.min_y, self.height, default_biome());
+            chunk.set_dirty(false);
+            self.read_entities(position)?;
+            self.insert_chunk(chunk);
+        }
+        Ok(self.chunks.get_mut(&position).expect("Chunk was just loaded"))
+    }
+
+    /// Saves the chunk if it changed, then removes it.
+    pub fn unload_chunk(&mut self, position: ChunkPos) -> anyhow::Result<Option<Chunk>> {
+        if let (Some(storage), Some(chunk)) = (&mut self.storage, self.chunks.get_mut(&position)) {
+            if chunk.is_dirty() {
+                storage.save_chunk(chunk)?;
+                chunk.set_dirty(false);
+            }
+        }
+        Ok(self.chunks.remove(&position))
+    }
+
+    /// Saves every dirty chunk, returning how many were written, and syncs the entities saved so far.
+    pub fn flush(&mut self) -> anyhow::Result<usize> {
+        if let Some(storage) = &self.entity_storage {
+            storage.sync()?;
+        }
+        let Some(storage) = &mut self.storage else { return Ok(0) };
+        let mut saved = 0;
+        for chunk in self.chunks.values_mut().filter(|chunk| chunk.is_dirty()) {
+            storage.save_chunk(chunk)?;
+            chunk.set_dirty(false);
+            saved += 1;
+        }
+        if saved > 0 {
+            storage.sync()?;
+        }
+        Ok(saved)
+    }
+
+    pub fn chunks(&self) -> impl Iterator<Item = &Chunk> {
+        self.chunks.values()
+    }
+
+    pub fn is_loaded(&self, position: ChunkPos) -> bool {
+        self.chunks.contains_key(&position)
+    }
+
+    /// `None` if the chunk is not loaded, blocks above or below the world are air.
+    pub fn get_block(&self, position: BlockPos) -> Option<BlockState> {
+        let chunk = self.chunk(ChunkPos::of(position))?;
+        Some(chunk.get_block((position.x & 15) as usize, position.y, (position.z & 15) as usize))
+    }
+
+    /// Returns the replaced state, fails for unloaded chunks and heights outside of the world.
+    pub fn set_block(&mut self, position: BlockPos, state: BlockState) -> anyhow::Result<BlockState> {
+        let chunk = self.chunks.get_mut(&ChunkPos::of(position))
+            .ok_or_else(|| anyhow!("Chunk of {:?} is not loaded", position))?;
+        let previous = chunk.set_block((position.x & 15) as usize, position.y, (position.z & 15) as usize, state)?;
+        if previous != state {
+            let registry = blocks().read().unwrap();
+            LightEngine::new(&mut self.chunks, &registry, &mut self.light_changes).update_block(position);
+        }
+        Ok(previous)
+    }
+
+    /// Changes many blocks, updating each chunk once. Nothing changes if any chunk is not loaded or
+    /// any height is outside of the world. Returns how many blocks changed. Light is updated block by
+    /// block for small changes and per chunk for large ones.
+    pub fn set_blocks(&mut self, changes: impl IntoIterator<Item = (BlockPos, BlockState)>) -> anyhow::Result<usize> {
+        let mut per_chunk: HashMap<ChunkPos, Vec<(usize, i32, usize, BlockState)>> = HashMap::new();
+        for (position, state) in changes {
+            if position.y < self.min_y || position.y >= self.min_y + self.height as i32 {
+                return Err(anyhow!("Y {} is outside of the world", position.y));
+            }
+            let chunk = ChunkPos::of(position);
+            if !self.chunks.contains_key(&chunk) {
+                return Err(anyhow!("Chunk of {:?} is not loaded", position));
+            }
+            per_chunk.entry(chunk).or_default().push(((position.x & 15) as usize, position.y, (position.z & 15) as usize, state));
+        }
+        let incremental = per_chunk.values().map(Vec::len).sum::<usize>() <= INCREMENTAL_LIGHT_LIMIT;
+        let (mut updated, mut relit) = (Vec::new(), Vec::new());
+        let mut changed = 0;
+        for (position, changes) in per_chunk {
+            let chunk = self.chunks.get_mut(&position).expect("Chunks were checked");
+            let previous = changes.iter().map(|(x, y, z, _)| chunk.get_block(*x, *y, *z)).collect::<Vec<_>>();
+            changed += chunk.set_blocks(&changes)?;
+            if !incremental {
+                relit.push(position);
+                continue;
+            }
+            updated.extend(changes.iter().zip(previous)
+                .filter(|((_, _, _, state), previous)| state != previous)
+                .map(|((x, y, z, _), _)| BlockPos::new(position.x * 16 + *x as i32, *y, position.z * 16 + *z as i32)));
+        }
+        let registry = blocks().read().unwrap();
+        let mut engine = LightEngine::new(&mut self.chunks, &registry, &mut self.light_changes);
+        updated.into_iter().for_each(|position| engine.update_block(position));
+        engine.relight_chunks(&relit, true);
+        Ok(changed)
+    }
+
+    /// Y of the first block above the highest one blocking motion in the column at `x`, `z`, loading
+    /// or creating its chunk. `None` if nothing in the column blocks motion.
+    pub fn surface_y(&mut self, x: i32, z: i32) -> anyhow::Result<Option<i32>> {
+        let min_y = self.min_y;
+        let chunk = self.load_or_create_chunk(ChunkPos::of(BlockPos::new(x, 0, z)))?;
+        let y = chunk.height_at(HeightmapKind::MotionBlocking, (x & 15) as usize, (z & 15) as usize);
+        Ok((y > min_y).then_some(y))
+    }
+
+    /// Sets every block in the box between two corners, both inclusive.
+    pub fn fill(&mut self, from: BlockPos, to: BlockPos, state: BlockState) -> anyhow::Result<usize> {
+        let (min, max) = (
+            BlockPos::new(from.x.min(to.x), from.y.min(to.y), from.z.min(to.z)),
+            BlockPos::new(from.x.max(to.x), from.y.max(to.y), from.z.max(to.z)),
+        );
+        let positions = (min.y..=max.y).flat_map(move |y| (min.z..=max.z)
+            .flat_map(move |z| (min.x..=max.x).map(move |x| (BlockPos::new(x, y, z), state))));
+        self.set_blocks(positions)
+    }
+}
+
+/// Network id of `minecraft:plains`, the biome of chunks without one.
+pub fn default_biome() -> u16 {
+    let registries = registries().read().unwrap();
+    registries.opaque.iter()
+        .find(|registry| *registry.id() == Identifier::minecraft("worldgen/biome"))
+        .and_then(|biomes| biomes.network_id(&Identifier::minecraft("plains")))
+        .unwrap_or_default() as u16
+}
+
+/// A vanilla dimension, each has its own chunks in its own directory of the level.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Dimension {
+    #[default]
+    Overworld,
+    Nether,
+    End,
+}
+
+impl Dimension {
+    pub const ALL: [Dimension; 3] = [Dimension::Overworld, Dimension::Nether, Dimension::End];
+
+    /// Name in the protocol, also the key of its type in the `minecraft:dimension_type` registry.
+    pub fn name(self) -> Identifier {
+        Identifier::minecraft(match self {
+            Dimension::Overworld => "overworld",
+            Dimension::Nether => "the_nether",
+            Dimension::End => "the_end",
+        })
+    }
+
+    pub fn from_name(name: &Identifier) -> Option<Self> {
+        Self::ALL.into_iter().find(|dimension| dimension.name() == *name)
+    }
+
+    /// Directory of its `region` folder within the level, as vanilla lays them out.
+    pub fn directory(self) -> &'static str {
+        match self {
+            Dimension::Overworld => "",
+            Dimension::Nether => "DIM-1",
+            Dimension::End => "DIM1",
+        }
+    }
+
+    pub fn min_y(self) -> i32 {
+        match self {
+            Dimension::Overworld => OVERWORLD_MIN_Y,
+            Dimension::Nether | Dimension::End => 0,
+        }
+    }
+
+    pub fn height(self) -> u32 {
+        match self {
+            Dimension::Overworld => OVERWORLD_HEIGHT,
+            Dimension::Nether => NETHER_HEIGHT,
+            Dimension::End => END_HEIGHT,
+        }
+    }
+
+    /// Its chunks, replaced by ones backed by the level's region files at startup.
+    pub fn world(self) -> &'static RwLock<World> {
+        match self {
+            Dimension::Overworld => &WORLD,
+            Dimension::Nether => &NETHER,
+            Dimension::End => &END,
+        }
+    }
+}
+
+static WORLD: Lazy<RwLock<World>> = Lazy::new(|| RwLock::new(World::default()));
+static NETHER: Lazy<RwLock<World>> = Lazy::new(|| RwLock::new(World::new(0, NETHER_HEIGHT)));
+static END: Lazy<RwLock<World>> = Lazy::new(|| RwLock::new(World::new(0, END_HEIGHT)));
+
+/// The overworld, replaced by one backed by the level's region files at startup.
+pub fn world() -> &'static RwLock<World> {
+    &WORLD
+}
@@ -0,0 +1,140 @@
+use std::io::{self, Cursor};
+use dolls_world::prelude::*;
+
+fn encode(container: &PalettedContainer<u16>, strategy: &PaletteStrategy) -> Vec<u8> {
+    let mut data = Vec::new();
+    encode_paletted(&mut data, container, strategy, |value| value as u32).unwrap();
+    data
+}
+
+fn decode(data: &[u8], size: usize, strategy: &PaletteStrategy) -> io::Result<PalettedContainer<u16>> {
+    let mut reader = Cursor::new(data);
+    let container = decode_paletted(&mut reader, size, strategy, |id| Ok(id as u16))?;
+    assert_eq!(reader.position() as usize, data.len(), "trailing bytes");
+    Ok(container)
+}
+
+/// A section-sized container using `distinct` values.
+fn container_with(distinct: u16) -> PalettedContainer<u16> {
+    let values = (0..SECTION_VOLUME).map(|index| (index % distinct as usize) as u16 * 3).collect::<Vec<_>>();
+    PalettedContainer::from_values(&values)
+}
+
+#[test]
+fn pack_round_trips_every_width() {
+    for bits in 1..=16 {
+        let values = (0..SECTION_VOLUME).map(|index| (index as u32 * 7919 % (1 << bits)) as u16).collect::<Vec<_>>();
+        let packed = pack_indices(&values, bits);
+        assert_eq!(packed.len(), SECTION_VOLUME.div_ceil((64 / bits) as usize));
+        assert_eq!(unpack_indices(&packed, bits, values.len()).unwrap(), values, "{} bits", bits);
+    }
+}
+
+#[test]
+fn values_do_not_span_longs() {
+    // Five 12 bit values fit a long, the sixth starts the next one.
+    let packed = pack_indices(&[0xFFF; 6], 12);
+    assert_eq!(packed, vec![0x0FFF_FFFF_FFFF_FFFF, 0xFFF]);
+}
+
+#[test]
+fn unpack_rejects_wrong_lengths() {
+    assert!(unpack_indices(&[0; 3], 4, SECTION_VOLUME).is_err());
+    assert!(unpack_indices(&[0; 256], 0, SECTION_VOLUME).is_err());
+}
+
+#[test]
+fn block_formats_follow_palette_size() {
+    let strategy = PaletteStrategy::BLOCKS;
+    assert_eq!(strategy.format(1), PaletteFormat::Single);
+    assert_eq!(strategy.format(2), PaletteFormat::Indirect(4));
+    assert_eq!(strategy.format(16), PaletteFormat::Indirect(4));
+    assert_eq!(strategy.format(17), PaletteFormat::Indirect(5));
+    assert_eq!(strategy.format(256), PaletteFormat::Indirect(8));
+    assert_eq!(strategy.format(257), PaletteFormat::Direct(15));
+}
+
+#[test]
+fn biome_formats_follow_palette_size() {
+    let strategy = PaletteStrategy::biomes(64);
+    assert_eq!(strategy.global_bits, 6);
+    assert_eq!(strategy.format(2), PaletteFormat::Indirect(1));
+    assert_eq!(strategy.format(8), PaletteFormat::Indirect(3));
+    assert_eq!(strategy.format(9), PaletteFormat::Direct(6));
+    assert_eq!(PaletteStrategy::biomes(1).global_bits, 1);
+}
+
+#[test]
+fn single_value_round_trips() {
+    let container = PalettedContainer::filled(SECTION_VOLUME, 42u16);
+    let data = encode(&container, &PaletteStrategy::BLOCKS);
+    // Bits, the id and an empty array.
+    assert_eq!(data, vec![0, 42, 0]);
+    assert_eq!(decode(&data, SECTION_VOLUME, &PaletteStrategy::BLOCKS).unwrap(), container);
+}
+
+#[test]
+fn containers_round_trip_across_bit_transitions() {
+    let strategy = PaletteStrategy::BLOCKS;
+    for distinct in [2, 16, 17, 32, 33, 256, 257, 4096] {
+        let container = container_with(distinct);
+        let data = encode(&container, &strategy);
+        assert_eq!(data[0] as u32, strategy.format(distinct as usize).bits(), "{} values", distinct);
+        let decoded = decode(&data, SECTION_VOLUME, &strategy).unwrap();
+        assert!(decoded.iter().eq(container.iter()), "{} values", distinct);
+    }
+}
+
+#[test]
+fn biomes_round_trip_indirect_and_direct() {
+    let strategy = PaletteStrategy::biomes(64);
+    for distinct in [2, 8, 9, 20] {
+        let values = (0..SECTION_BIOMES).map(|index| (index % distinct) as u16).collect::<Vec<_>>();
+        let container = PalettedContainer::from_values(&values);
+        let decoded = decode(&encode(&container, &strategy), SECTION_BIOMES, &strategy).unwrap();
+        assert!(decoded.iter().eq(values.iter().copied()), "{} values", distinct);
+    }
+}
+
+#[test]
+fn shrinking_palettes_return_to_smaller_formats() {
+    let mut container = container_with(17);
+    for index in 0..SECTION_VOLUME {
+        container.set(index, 3);
+    }
+    container.set(0, 0);
+    container.compact();
+    assert_eq!(encode(&container, &PaletteStrategy::BLOCKS)[0], 4);
+    container.set(0, 3);
+    container.compact();
+    assert_eq!(container.single_value(), Some(3));
+    assert_eq!(encode(&container, &PaletteStrategy::BLOCKS)[0], 0);
+}
+
+#[test]
+fn indirect_sizes_below_the_minimum_are_rounded_up() {
+    // Two blocks announced with 1 bit, the client reads them with 4.
+    let mut data = vec![1, 2, 0, 9];
+    let indices = (0..SECTION_VOLUME).map(|index| (index % 2) as u16).collect::<Vec<_>>();
+    let longs = pack_indices(&indices, 4);
+    data.extend([0x80, 0x02]);
+    for long in longs {
+        data.extend(long.to_be_bytes());
+    }
+    let decoded = decode(&data, SECTION_VOLUME, &PaletteStrategy::BLOCKS).unwrap();
+    assert_eq!((decoded.get(0), decoded.get(1)), (0, 9));
+}
+
+#[test]
+fn malformed_containers_are_rejected() {
+    let strategy = PaletteStrategy::BLOCKS;
+    // Palette index 2 of 2 entries.
+    let mut data = vec![4, 2, 0, 1, 0x80, 0x02];
+    data.extend(2i64.to_be_bytes());
+    data.extend(std::iter::repeat_n(0, 255 * 8));
+    assert!(decode(&data, SECTION_VOLUME, &strategy).is_err());
+    // Too few longs.
+    assert!(decode(&[4, 2, 0, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0], SECTION_VOLUME, &strategy).is_err());
+    // Negative global id.
+    assert!(decode(&[0, 0xFF, 0xFF, 0xFF, 0xFF, 0x0F, 0], SECTION_VOLUME, &strategy).is_err());
+}
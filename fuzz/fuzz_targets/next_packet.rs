@@ -0,0 +1,17 @@
+//! Frames as a client sends them, the first byte picks the compression threshold.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use dolls_network::prelude::PacketHandler;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&threshold, mut stream)) = data.split_first() else {
+        return;
+    };
+    let mut handler = PacketHandler::new(&mut stream);
+    // Odd bytes disable compression, even ones enable it with a threshold of the byte.
+    handler.set_compression((threshold % 2 == 0).then_some(threshold as usize));
+    async_std::task::block_on(async {
+        while handler.next_packet().await.is_ok() {}
+    });
+});
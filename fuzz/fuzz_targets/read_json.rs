@@ -0,0 +1,17 @@
+//! Text components sent as JSON strings, e.g. in chat and disconnect packets of older states.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use dolls_core::datatype::Decode;
+use dolls_core::text::{JsonTextComponent, TextComponent};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(JsonTextComponent(component)) = JsonTextComponent::decode(&mut &data[..]) {
+        let _ = component.to_plain_text();
+    }
+    if let Ok(json) = std::str::from_utf8(data) {
+        if let Ok(component) = TextComponent::from_json(json) {
+            serde_json::to_string(&component).unwrap();
+        }
+    }
+});
@@ -0,0 +1,19 @@
+//! Network NBT from packets and named NBT from files players may hand over, such as schematics.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use dolls_core::datatype::{Decode, Encode};
+use dolls_core::nbt::{NbtCompound, NbtTag};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(tag) = NbtTag::decode(&mut &data[..]) {
+        let mut encoded = Vec::new();
+        tag.encode(&mut encoded).unwrap();
+        NbtTag::decode(&mut encoded.as_slice()).unwrap();
+    }
+    if let Ok((name, compound)) = NbtCompound::read_named(&mut &data[..]) {
+        let mut encoded = Vec::new();
+        compound.write_named(&mut encoded, &name).unwrap();
+        NbtCompound::read_named(&mut encoded.as_slice()).unwrap();
+    }
+});
@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use dolls_core::datatype::{Decode, Encode};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(value) = String::decode(&mut &data[..]) {
+        let mut encoded = Vec::new();
+        value.encode(&mut encoded).unwrap();
+        assert_eq!(String::decode(&mut encoded.as_slice()).unwrap(), value);
+    }
+});
@@ -0,0 +1,21 @@
+//! The stream reader of frame lengths and the decoder of packet fields must agree.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use dolls_core::datatype::{Decode, Encode, VarInt};
+use dolls_network::prelude::read_varint;
+
+fuzz_target!(|data: &[u8]| {
+    let streamed = async_std::task::block_on(read_varint(&mut &data[..]));
+    let decoded = VarInt::decode(&mut &data[..]);
+    match (streamed, decoded) {
+        (Ok(streamed), Ok(decoded)) => {
+            assert_eq!(streamed, decoded.0 as u32);
+            let mut encoded = Vec::new();
+            decoded.encode(&mut encoded).unwrap();
+            assert_eq!(VarInt::decode(&mut encoded.as_slice()).unwrap(), decoded);
+        }
+        (Err(_), Err(_)) => {}
+        (streamed, decoded) => panic!("Readers disagree: {:?} and {:?}", streamed, decoded),
+    }
+});